@@ -11,16 +11,24 @@ use std::path::Path;
 const COLOR_ACTIVE: [u8; 4] = [0x32, 0xD7, 0x4B, 0xFF];
 const COLOR_PENDING: [u8; 4] = [0xFF, 0x9F, 0x0A, 0xFF];
 const COLOR_IDLE: [u8; 4] = [0x8E, 0x8E, 0x93, 0xFF];
+const COLOR_RATE_LIMITED: [u8; 4] = [0xBF, 0x5A, 0xF2, 0xFF];
+const COLOR_UNKNOWN: [u8; 4] = [0x64, 0xD2, 0xFF, 0xFF];
 const DOT_DIAMETER: u32 = 10;
 const DOT_SPACING: u32 = 4;
 const PADDING: u32 = 3;
 const MAX_COLS: u32 = 3;
 
+// Keep in sync with Status::COUNT and icon::MAX_PREGENERATED
+const STATUS_COUNT: u32 = 5;
+const MAX_PREGENERATED: u32 = 5;
+
 #[derive(Clone, Copy)]
 enum Status {
     Active = 0,
     Pending = 1,
     Idle = 2,
+    RateLimited = 3,
+    Unknown = 4,
 }
 
 impl Status {
@@ -29,6 +37,8 @@ impl Status {
             Status::Active => COLOR_ACTIVE,
             Status::Pending => COLOR_PENDING,
             Status::Idle => COLOR_IDLE,
+            Status::RateLimited => COLOR_RATE_LIMITED,
+            Status::Unknown => COLOR_UNKNOWN,
         }
     }
 
@@ -36,7 +46,9 @@ impl Status {
         match i {
             0 => Status::Active,
             1 => Status::Pending,
-            _ => Status::Idle,
+            2 => Status::Idle,
+            3 => Status::RateLimited,
+            _ => Status::Unknown,
         }
     }
 }
@@ -46,7 +58,7 @@ fn grid_dims(n: u32) -> (u32, u32) {
         return (0, 0);
     }
     let cols = n.min(MAX_COLS);
-    let rows = (n + MAX_COLS - 1) / MAX_COLS;
+    let rows = n.div_ceil(MAX_COLS);
     (cols, rows)
 }
 
@@ -60,6 +72,24 @@ fn image_dims(n: u32) -> (u32, u32) {
     (w, h)
 }
 
+const SUBSAMPLES: u32 = 4;
+
+/// Fraction of the pixel at (`x0`, `y0`) relative to the center inside the circle.
+fn coverage(x0: f32, y0: f32, r: f32) -> f32 {
+    let step = 1.0 / SUBSAMPLES as f32;
+    let mut inside = 0;
+    for sy in 0..SUBSAMPLES {
+        for sx in 0..SUBSAMPLES {
+            let dx = x0 + (sx as f32 + 0.5) * step;
+            let dy = y0 + (sy as f32 + 0.5) * step;
+            if (dx * dx + dy * dy).sqrt() - r < 0.0 {
+                inside += 1;
+            }
+        }
+    }
+    inside as f32 / (SUBSAMPLES * SUBSAMPLES) as f32
+}
+
 fn make_dot_grid_png(statuses: &[Status]) -> Vec<u8> {
     let n = statuses.len() as u32;
     if n == 0 {
@@ -74,25 +104,22 @@ fn make_dot_grid_png(statuses: &[Status]) -> Vec<u8> {
     for (i, status) in statuses.iter().enumerate() {
         let col = i as u32 % cols;
         let row = i as u32 / cols;
-        let cx = PADDING + col * (DOT_DIAMETER + DOT_SPACING) + DOT_DIAMETER / 2;
-        let cy = PADDING + row * (DOT_DIAMETER + DOT_SPACING) + DOT_DIAMETER / 2;
+        let cx = (PADDING + col * (DOT_DIAMETER + DOT_SPACING) + DOT_DIAMETER / 2) as f32;
+        let cy = (PADDING + row * (DOT_DIAMETER + DOT_SPACING) + DOT_DIAMETER / 2) as f32;
         let color = status.color();
         let r = DOT_DIAMETER as f32 / 2.0;
 
-        let x_start = cx.saturating_sub(DOT_DIAMETER / 2 + 1);
-        let x_end = (cx + DOT_DIAMETER / 2 + 2).min(width);
-        let y_start = cy.saturating_sub(DOT_DIAMETER / 2 + 1);
-        let y_end = (cy + DOT_DIAMETER / 2 + 2).min(height);
+        let x_start = (cx - r - 1.0).floor().max(0.0) as u32;
+        let x_end = ((cx + r + 1.0).ceil() as u32).min(width);
+        let y_start = (cy - r - 1.0).floor().max(0.0) as u32;
+        let y_end = ((cy + r + 1.0).ceil() as u32).min(height);
 
         for py in y_start..y_end {
             for px in x_start..x_end {
-                let dx = px as f32 - cx as f32 + 0.5;
-                let dy = py as f32 - cy as f32 + 0.5;
-                let dist = (dx * dx + dy * dy).sqrt();
-                let alpha = (r - dist + 0.5).clamp(0.0, 1.0);
+                let alpha = coverage(px as f32 - cx, py as f32 - cy, r);
                 if alpha > 0.0 {
                     let offset = ((py * width + px) * 4) as usize;
-                    let a = (alpha * color[3] as f32) as u8;
+                    let a = (alpha * color[3] as f32).round() as u8;
                     pixels[offset] = color[0];
                     pixels[offset + 1] = color[1];
                     pixels[offset + 2] = color[2];
@@ -160,10 +187,10 @@ fn crc32(chunk_type: &[u8], data: &[u8]) -> u32 {
     crc ^ 0xFFFFFFFF
 }
 
-fn status_key(statuses: &[Status]) -> u16 {
-    let mut key: u16 = 0;
+fn status_key(statuses: &[Status]) -> u32 {
+    let mut key: u32 = 0;
     for s in statuses {
-        key = key * 3 + *s as u16;
+        key = key * STATUS_COUNT + *s as u32;
     }
     key
 }
@@ -178,15 +205,15 @@ fn main() {
     code.push_str("{\n");
     code.push_str("    match (count, key) {\n");
 
-    // Generate all combinations for count=1..=5
-    for count in 1u32..=5 {
-        let total = 3u32.pow(count);
+    // Generate all combinations for count=1..=MAX_PREGENERATED
+    for count in 1u32..=MAX_PREGENERATED {
+        let total = STATUS_COUNT.pow(count);
         for combo in 0..total {
             let mut statuses = Vec::new();
             let mut v = combo;
             for _ in 0..count {
-                statuses.push(Status::from_index((v % 3) as u8));
-                v /= 3;
+                statuses.push(Status::from_index((v % STATUS_COUNT) as u8));
+                v /= STATUS_COUNT;
             }
 
             let key = status_key(&statuses);
@@ -0,0 +1,120 @@
+/* C API for linking the claude-bar session core into native apps. */
+
+#ifndef CLAUDE_BAR_H
+#define CLAUDE_BAR_H
+
+/* Generated by cbindgen from src/ffi.rs; do not edit. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+// Session status, as in `claude-bar poll`.
+typedef enum ClaudeBarStatus {
+  CLAUDE_BAR_STATUS_ACTIVE = 0,
+  CLAUDE_BAR_STATUS_PENDING = 1,
+  CLAUDE_BAR_STATUS_IDLE = 2,
+  CLAUDE_BAR_STATUS_RATE_LIMITED = 3,
+  CLAUDE_BAR_STATUS_UNKNOWN = 4,
+} ClaudeBarStatus;
+
+// Sessions returned by [`claude_bar_poll_sessions`]; opaque to C.
+typedef struct ClaudeBarSessionList ClaudeBarSessionList;
+
+// One polled session. Strings are NUL-terminated UTF-8 owned by the list the
+// session came from; optional ones are NULL when absent.
+typedef struct ClaudeBarSession {
+  // Session key to pass to `claude_bar_focus`: the TTY, or
+  // `host:/dev/pts/1` for remote sessions
+  const char *key;
+  const char *tty;
+  uint32_t pid;
+  const char *cwd;
+  // Last component of `cwd`
+  const char *project;
+  // `claude` or `codex`
+  const char *provider;
+  // `iterm2`, `alacritty`, `cursor`, `unknown`, or `jetbrains:<ide>` with
+  // the IDE's slug (`jetbrains:rustrover`, `jetbrains:android-studio`, ...)
+  const char *terminal;
+  enum ClaudeBarStatus status;
+  // Tool a pending session is waiting on
+  const char *pending_tool;
+  const char *title;
+  // Remote host label for sessions polled over SSH or from a peer
+  const char *remote_host;
+  // Unix time the session started, 0 when unknown
+  uint64_t started_at;
+  uint32_t queued;
+  uint32_t turns;
+  uint32_t subagents;
+  // Found through its transcript alone, so there's no terminal to focus
+  bool detached;
+} ClaudeBarSession;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// Poll every session with the settings from the config file, as
+// `claude-bar poll` does. Never returns NULL; free the list with
+// `claude_bar_session_list_free`.
+struct ClaudeBarSessionList *claude_bar_poll_sessions(void);
+
+// Number of sessions in `list`.
+//
+// # Safety
+// `list` must be NULL or a list from `claude_bar_poll_sessions` not yet freed.
+size_t claude_bar_session_count(const struct ClaudeBarSessionList *list);
+
+// Session `index` of `list`, or NULL past the end. The pointer stays valid
+// until the list is freed.
+//
+// # Safety
+// `list` must be NULL or a list from `claude_bar_poll_sessions` not yet freed.
+const struct ClaudeBarSession *claude_bar_session_at(const struct ClaudeBarSessionList *list,
+                                                     size_t index);
+
+// Free a list from `claude_bar_poll_sessions`. NULL is ignored.
+//
+// # Safety
+// `list` must be NULL or a list from `claude_bar_poll_sessions` not yet freed.
+void claude_bar_session_list_free(struct ClaudeBarSessionList *list);
+
+// The full poll as `claude-bar poll --format json` prints it, for fields the
+// session struct leaves out (nested agents, containers, env). Free with
+// `claude_bar_string_free`.
+char *claude_bar_poll_sessions_json(void);
+
+// Free a string returned by the library. NULL is ignored.
+//
+// # Safety
+// `s` must be NULL or a string from this library not yet freed.
+void claude_bar_string_free(char *s);
+
+// Status of the session writing `transcript_path` (NULL when it has no
+// transcript yet), with the thresholds from the config file.
+//
+// # Safety
+// `transcript_path` must be NULL or a NUL-terminated string.
+enum ClaudeBarStatus claude_bar_determine_status(const char *transcript_path);
+
+// Raise the terminal tab hosting the session with key or TTY `target`
+// (`/dev/ttys003`, `ttys003`, `host:/dev/pts/1`). Returns 0, or an exit code
+// with the reason in `claude_bar_last_error`.
+//
+// # Safety
+// `target` must be NULL or a NUL-terminated string.
+int32_t claude_bar_focus(const char *target);
+
+// Message for the last failed call on this thread, or NULL after a call that
+// succeeded. Valid until the next library call on the same thread.
+const char *claude_bar_last_error(void);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* CLAUDE_BAR_H */
@@ -19,13 +19,53 @@ fi
 # 2. Build Swift menu bar app
 echo "Building claude-bar-app (Swift)..."
 swiftc -O -o "$SCRIPT_DIR/target/release/claude-bar-app" "$SCRIPT_DIR/swift/ClaudeBar.swift"
-APP_BINARY="$SCRIPT_DIR/target/release/claude-bar-app"
 
-if [[ ! -x "$APP_BINARY" ]]; then
+if [[ ! -x "$SCRIPT_DIR/target/release/claude-bar-app" ]]; then
     echo "Error: Swift build failed"
     exit 1
 fi
 
+# Wrap the app in a minimal bundle so LaunchServices routes claude-bar:// URLs to it
+APP_BUNDLE="$SCRIPT_DIR/target/release/ClaudeBar.app"
+rm -rf "$APP_BUNDLE"
+mkdir -p "$APP_BUNDLE/Contents/MacOS"
+cp "$SCRIPT_DIR/target/release/claude-bar-app" "$APP_BUNDLE/Contents/MacOS/claude-bar-app"
+ln -s "$BINARY" "$APP_BUNDLE/Contents/MacOS/claude-bar"
+
+cat > "$APP_BUNDLE/Contents/Info.plist" << EOF
+<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+    <key>CFBundleIdentifier</key>
+    <string>com.claude.claude-bar</string>
+    <key>CFBundleName</key>
+    <string>Claude Bar</string>
+    <key>CFBundleExecutable</key>
+    <string>claude-bar-app</string>
+    <key>CFBundlePackageType</key>
+    <string>APPL</string>
+    <key>LSUIElement</key>
+    <true/>
+    <key>CFBundleURLTypes</key>
+    <array>
+        <dict>
+            <key>CFBundleURLName</key>
+            <string>com.claude.claude-bar</string>
+            <key>CFBundleURLSchemes</key>
+            <array>
+                <string>claude-bar</string>
+            </array>
+        </dict>
+    </array>
+</dict>
+</plist>
+EOF
+
+LSREGISTER="/System/Library/Frameworks/CoreServices.framework/Frameworks/LaunchServices.framework/Support/lsregister"
+"$LSREGISTER" -f "$APP_BUNDLE"
+APP_BINARY="$APP_BUNDLE/Contents/MacOS/claude-bar-app"
+
 # 3. Daemon (launchd plist) — runs the Swift menu bar app
 PLIST_LABEL="com.claude.claude-bar-daemon"
 PLIST="$HOME/Library/LaunchAgents/$PLIST_LABEL.plist"
@@ -63,7 +103,7 @@ echo "Started daemon: $PLIST_LABEL"
 HOOK_CMD="$BINARY hook"
 SETTINGS="$HOME/.claude/settings.json"
 
-"$BINARY" hooks-install --settings "$SETTINGS" --command "$HOOK_CMD"
+"$BINARY" hooks install --settings "$SETTINGS" --command "$HOOK_CMD"
 
 echo "Registered hook: SessionStart → $HOOK_CMD"
 echo "Installation complete."
@@ -0,0 +1,49 @@
+//! Whether the user is at the keyboard.
+//!
+//! macOS keeps the time since the last input event (key, mouse, trackpad) of
+//! the HID system, which counts the user's own input but not events other
+//! apps post. Elsewhere the idle time is unknown and the user always counts
+//! as present.
+
+/// Seconds since the user last touched the keyboard, mouse or trackpad.
+#[cfg(target_os = "macos")]
+pub fn idle_secs() -> Option<f64> {
+    /// `kCGEventSourceStateHIDSystemState`
+    const HID_SYSTEM_STATE: i32 = 1;
+    /// `kCGAnyInputEventType`
+    const ANY_INPUT_EVENT: u32 = !0;
+
+    #[link(name = "CoreGraphics", kind = "framework")]
+    extern "C" {
+        fn CGEventSourceSecondsSinceLastEventType(source_state: i32, event_type: u32) -> f64;
+    }
+
+    // SAFETY: plain query without pointers or ownership.
+    let secs = unsafe { CGEventSourceSecondsSinceLastEventType(HID_SYSTEM_STATE, ANY_INPUT_EVENT) };
+    secs.is_finite().then_some(secs)
+}
+
+#[cfg(not(target_os = "macos"))]
+pub fn idle_secs() -> Option<f64> {
+    None
+}
+
+/// Whether the user counts as away: idle for `away_after_secs` or more (0
+/// never does), judged by `idle`, the idle time if known.
+pub fn is_away(idle: Option<f64>, away_after_secs: f64) -> bool {
+    away_after_secs > 0.0 && idle.is_some_and(|idle| idle >= away_after_secs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_away() {
+        assert!(is_away(Some(600.0), 300.0));
+        assert!(is_away(Some(300.0), 300.0));
+        assert!(!is_away(Some(12.0), 300.0));
+        assert!(!is_away(None, 300.0));
+        assert!(!is_away(Some(1e6), 0.0));
+    }
+}
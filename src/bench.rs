@@ -0,0 +1,236 @@
+//! `bench`: time each phase of a poll.
+//!
+//! The poll pipeline adds the time it spends in each [`Phase`] to process-wide
+//! counters (as [`process::run`] counts subprocesses), so a benchmark run reads
+//! the same code path the daemon takes rather than a copy of it. Per-session
+//! phases run on several threads at once and are summed across them, so they
+//! can add up to more than the poll's wall time.
+
+use crate::config::Config;
+use crate::icon;
+use crate::process;
+use crate::serve;
+use serde::Serialize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+/// A stage of the poll pipeline.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    /// Finding agent processes, their TTYs, CWDs, environment and CPU usage
+    ProcessDiscovery,
+    /// Listing iTerm2/Alacritty sessions and SSH tabs
+    TerminalEnumeration,
+    /// Finding each session's transcript (hook state, project directories),
+    /// plus transcript-only and container sessions
+    TranscriptResolution,
+    /// Reading transcripts and deciding statuses
+    TranscriptParsing,
+    /// Polling `remote_hosts` over SSH
+    RemoteHosts,
+    /// Rendering the status-dot icon
+    IconGeneration,
+}
+
+impl Phase {
+    pub const ALL: [Phase; 6] = [
+        Phase::ProcessDiscovery,
+        Phase::TerminalEnumeration,
+        Phase::TranscriptResolution,
+        Phase::TranscriptParsing,
+        Phase::RemoteHosts,
+        Phase::IconGeneration,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Phase::ProcessDiscovery => "process_discovery",
+            Phase::TerminalEnumeration => "terminal_enumeration",
+            Phase::TranscriptResolution => "transcript_resolution",
+            Phase::TranscriptParsing => "transcript_parsing",
+            Phase::RemoteHosts => "remote_hosts",
+            Phase::IconGeneration => "icon_generation",
+        }
+    }
+}
+
+/// Nanoseconds spent in each phase since startup, indexed like [`Phase::ALL`].
+static PHASE_NANOS: [AtomicU64; 6] = [const { AtomicU64::new(0) }; 6];
+
+/// Run `f`, adding its duration to `phase`.
+pub fn timed<T>(phase: Phase, f: impl FnOnce() -> T) -> T {
+    let started = Instant::now();
+    let result = f();
+    PHASE_NANOS[phase as usize].fetch_add(started.elapsed().as_nanos() as u64, Ordering::Relaxed);
+    result
+}
+
+/// Time spent in `phase` since startup.
+pub fn phase_time(phase: Phase) -> Duration {
+    Duration::from_nanos(PHASE_NANOS[phase as usize].load(Ordering::Relaxed))
+}
+
+/// Timings of one `bench` poll.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Sample {
+    pub wall_ms: f64,
+    /// Milliseconds per phase, in [`Phase::ALL`] order
+    pub phases_ms: Vec<f64>,
+    pub subprocesses: u64,
+    pub sessions: usize,
+}
+
+/// Summary of one measurement across iterations.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Stat {
+    pub name: &'static str,
+    pub mean_ms: f64,
+    pub min_ms: f64,
+    pub max_ms: f64,
+}
+
+impl Stat {
+    fn of(name: &'static str, values: &[f64]) -> Stat {
+        let n = values.len().max(1) as f64;
+        Stat {
+            name,
+            mean_ms: values.iter().sum::<f64>() / n,
+            min_ms: values.iter().copied().fold(f64::INFINITY, f64::min),
+            max_ms: values.iter().copied().fold(0.0, f64::max),
+        }
+    }
+}
+
+/// `bench` output.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Report {
+    pub iterations: usize,
+    pub sessions: usize,
+    pub subprocesses_per_poll: f64,
+    /// One entry per phase, then `total` (wall time of poll plus icon)
+    pub phases: Vec<Stat>,
+}
+
+impl Report {
+    pub fn from_samples(samples: &[Sample]) -> Report {
+        let column = |f: &dyn Fn(&Sample) -> f64| samples.iter().map(f).collect::<Vec<_>>();
+        let mut phases: Vec<Stat> = Phase::ALL
+            .iter()
+            .enumerate()
+            .map(|(i, phase)| Stat::of(phase.name(), &column(&|s| s.phases_ms[i])))
+            .collect();
+        phases.push(Stat::of("total", &column(&|s| s.wall_ms)));
+        let n = samples.len().max(1) as f64;
+        Report {
+            iterations: samples.len(),
+            sessions: samples.last().map_or(0, |s| s.sessions),
+            subprocesses_per_poll: samples.iter().map(|s| s.subprocesses as f64).sum::<f64>() / n,
+            phases,
+        }
+    }
+
+    pub fn format_text(&self) -> String {
+        let mut out = format!(
+            "{} polls, {} sessions, {:.1} subprocesses per poll\n\n",
+            self.iterations, self.sessions, self.subprocesses_per_poll
+        );
+        out.push_str(&format!(
+            "{:<22} {:>9} {:>9} {:>9}\n",
+            "phase", "mean ms", "min ms", "max ms"
+        ));
+        for stat in &self.phases {
+            out.push_str(&format!(
+                "{:<22} {:>9.1} {:>9.1} {:>9.1}\n",
+                stat.name, stat.mean_ms, stat.min_ms, stat.max_ms
+            ));
+        }
+        out.push_str("\nPer-session phases run concurrently and are summed across threads.\n");
+        out
+    }
+}
+
+/// One poll and icon render, timed.
+fn sample(config: &Config) -> Sample {
+    let before: Vec<Duration> = Phase::ALL.iter().map(|p| phase_time(*p)).collect();
+    let started = Instant::now();
+    let (sessions, tally) = process::tallied(|| serve::poll_sessions_with(config));
+    let statuses: Vec<_> = sessions.iter().map(|s| s.status).collect();
+    // Uncached, unlike the menu's icon, so the render itself is measured
+    timed(Phase::IconGeneration, || icon::make_dot_grid_png(&statuses));
+    let wall = started.elapsed();
+    Sample {
+        wall_ms: wall.as_secs_f64() * 1000.0,
+        phases_ms: Phase::ALL
+            .iter()
+            .zip(before)
+            .map(|(p, b)| (phase_time(*p) - b).as_secs_f64() * 1000.0)
+            .collect(),
+        subprocesses: tally.subprocesses,
+        sessions: sessions.len(),
+    }
+}
+
+pub fn run_bench(iterations: usize, json: bool) -> Result<(), Box<dyn std::error::Error>> {
+    if iterations == 0 {
+        return Err("--iterations must be at least 1".into());
+    }
+    let config = Config::load();
+    let samples: Vec<Sample> = (0..iterations).map(|_| sample(&config)).collect();
+    let report = Report::from_samples(&samples);
+    if json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        print!("{}", report.format_text());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_timed_accumulates() {
+        let before = phase_time(Phase::RemoteHosts);
+        let value = timed(Phase::RemoteHosts, || {
+            std::thread::sleep(Duration::from_millis(5));
+            42
+        });
+        assert_eq!(value, 42);
+        assert!(phase_time(Phase::RemoteHosts) - before >= Duration::from_millis(5));
+    }
+
+    #[test]
+    fn test_report_from_samples() {
+        let samples = vec![
+            Sample {
+                wall_ms: 10.0,
+                phases_ms: vec![4.0, 2.0, 1.0, 6.0, 0.0, 0.5],
+                subprocesses: 5,
+                sessions: 2,
+            },
+            Sample {
+                wall_ms: 20.0,
+                phases_ms: vec![8.0, 2.0, 3.0, 10.0, 0.0, 0.5],
+                subprocesses: 6,
+                sessions: 3,
+            },
+        ];
+        let report = Report::from_samples(&samples);
+        assert_eq!((report.iterations, report.sessions), (2, 3));
+        assert_eq!(report.subprocesses_per_poll, 5.5);
+        assert_eq!(report.phases.len(), Phase::ALL.len() + 1);
+        let discovery = &report.phases[0];
+        assert_eq!(discovery.name, "process_discovery");
+        assert_eq!(
+            (discovery.mean_ms, discovery.min_ms, discovery.max_ms),
+            (6.0, 4.0, 8.0)
+        );
+        let total = report.phases.last().unwrap();
+        assert_eq!((total.name, total.mean_ms), ("total", 15.0));
+
+        let text = report.format_text();
+        assert!(text.starts_with("2 polls, 3 sessions, 5.5 subprocesses per poll\n"));
+        assert!(text.contains("transcript_parsing           8.0       6.0      10.0\n"));
+    }
+}
@@ -0,0 +1,641 @@
+use crate::container::ContainerOptions;
+use crate::hotkey;
+use crate::logging;
+use crate::notifier::NotifyOptions;
+use crate::process::ProcessMatchers;
+use crate::remote::RemoteHost;
+use crate::render::{Colors, MenuOptions};
+use crate::serve::DiscoveryOptions;
+use crate::state::Rule;
+use crate::terminal::TerminalApp;
+use crate::transcript::{self, Thresholds};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// User configuration read from `~/.config/claude-bar/config.json`.
+/// Every field is optional; missing fields keep their defaults.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Seconds between daemon polls
+    pub poll_interval_secs: f64,
+    /// Polls slower than this count as overruns; repeated overruns stretch the interval
+    pub poll_budget_ms: u64,
+    pub thresholds: Thresholds,
+    pub colors: Colors,
+    /// Colors used instead of `colors` when the menu bar is light
+    #[serde(deserialize_with = "light_colors")]
+    pub light_colors: Colors,
+    /// Dropdown and icon options, toggled from the menu bar app
+    pub menu: MenuOptions,
+    /// How claude and codex processes are recognized
+    pub processes: ProcessMatchers,
+    /// Machines polled over SSH for their sessions
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub remote_hosts: Vec<RemoteHost>,
+    /// Sessions inside Docker containers and devcontainers, found through their transcripts
+    pub containers: ContainerOptions,
+    /// Listing sessions by their transcripts when no process matches
+    pub discovery: DiscoveryOptions,
+    /// Daemon socket location (read at startup; `CLAUDE_BAR_SOCKET` wins)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub socket_path: Option<PathBuf>,
+    /// Claude Code config directory holding `projects/` (`CLAUDE_CONFIG_DIR` wins)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub claude_config_dir: Option<PathBuf>,
+    /// Several Claude Code config directories used side by side; replaces
+    /// `claude_config_dir` when set
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub claude_profiles: Vec<ClaudeProfile>,
+    /// Terminal apps whose sessions are enumerated, highest precedence first;
+    /// apps left out are never queried
+    pub terminals: Vec<TerminalApp>,
+    /// Global shortcuts for `hotkey-daemon`: key combination -> action
+    pub hotkeys: BTreeMap<String, String>,
+    /// Notifications from `serve` for sessions left waiting for input
+    pub notifications: NotifyOptions,
+}
+
+/// A named Claude Code config directory, e.g. a work account run with
+/// `CLAUDE_CONFIG_DIR=~/.claude-work`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ClaudeProfile {
+    /// Badge shown on the profile's sessions
+    pub name: String,
+    pub config_dir: PathBuf,
+}
+
+/// A config directory sessions are looked up in: a profile, or the single
+/// unnamed directory when no profiles are configured.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClaudeRoot {
+    pub profile: Option<String>,
+    pub config_dir: PathBuf,
+}
+
+impl ClaudeRoot {
+    /// Where this config directory keeps per-project transcripts.
+    pub fn projects_dir(&self) -> PathBuf {
+        self.config_dir.join("projects")
+    }
+
+    /// Whether `transcript` lies under this root's `projects/`.
+    pub fn contains(&self, transcript: &str) -> bool {
+        Path::new(transcript).starts_with(self.projects_dir())
+    }
+
+    /// The root a process with `config_dir` in its `CLAUDE_CONFIG_DIR` (or
+    /// `~/.claude` when unset) uses: the matching one in `roots`, else an
+    /// unnamed root for that directory.
+    pub fn for_config_dir(roots: &[ClaudeRoot], config_dir: &Path) -> ClaudeRoot {
+        let canonical = |p: &Path| p.canonicalize().unwrap_or_else(|_| p.to_path_buf());
+        let wanted = canonical(config_dir);
+        roots
+            .iter()
+            .find(|root| canonical(&root.config_dir) == wanted)
+            .cloned()
+            .unwrap_or_else(|| ClaudeRoot {
+                profile: None,
+                config_dir: config_dir.to_path_buf(),
+            })
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            poll_interval_secs: 2.0,
+            poll_budget_ms: 1000,
+            thresholds: Thresholds::default(),
+            colors: Colors::default(),
+            light_colors: Colors::light(),
+            menu: MenuOptions::default(),
+            processes: ProcessMatchers::default(),
+            remote_hosts: Vec::new(),
+            containers: ContainerOptions::default(),
+            discovery: DiscoveryOptions::default(),
+            socket_path: None,
+            claude_config_dir: None,
+            claude_profiles: Vec::new(),
+            terminals: TerminalApp::ALL.to_vec(),
+            hotkeys: hotkey::default_hotkeys(),
+            notifications: NotifyOptions::default(),
+        }
+    }
+}
+
+/// `config.json` in [`crate::paths::config_dir`].
+pub fn default_config_path() -> PathBuf {
+    crate::paths::config_dir().join("config.json")
+}
+
+/// Environment variable Claude Code reads its config directory from.
+pub const CLAUDE_CONFIG_DIR_ENV: &str = "CLAUDE_CONFIG_DIR";
+
+/// Claude Code's config directory, resolved the way Claude does it:
+/// `$CLAUDE_CONFIG_DIR`, then `claude_config_dir` from our config file (for
+/// launchd, which doesn't see shell exports), then `~/.claude`.
+pub fn resolve_claude_config_dir(
+    env_override: Option<String>,
+    configured: Option<&Path>,
+    home: &str,
+) -> PathBuf {
+    if let Some(dir) = env_override {
+        return PathBuf::from(dir);
+    }
+    if let Some(dir) = configured {
+        return dir.to_path_buf();
+    }
+    PathBuf::from(home).join(".claude")
+}
+
+impl Config {
+    /// Status colors for the given menu bar appearance.
+    pub fn palette(&self, appearance: crate::icon::Appearance) -> &Colors {
+        match appearance {
+            crate::icon::Appearance::Dark => &self.colors,
+            crate::icon::Appearance::Light => &self.light_colors,
+        }
+    }
+
+    /// Resolved Claude Code config directory (see [`resolve_claude_config_dir`]).
+    pub fn claude_config_dir(&self) -> PathBuf {
+        resolve_claude_config_dir(
+            std::env::var(CLAUDE_CONFIG_DIR_ENV)
+                .ok()
+                .filter(|v| !v.is_empty()),
+            self.claude_config_dir.as_deref(),
+            &crate::paths::home_dir().to_string_lossy(),
+        )
+    }
+
+    /// Config directories to look for sessions in: each of `claude_profiles`,
+    /// or the unnamed [`Self::claude_config_dir`].
+    pub fn claude_roots(&self) -> Vec<ClaudeRoot> {
+        if self.claude_profiles.is_empty() {
+            return vec![ClaudeRoot {
+                profile: None,
+                config_dir: self.claude_config_dir(),
+            }];
+        }
+        self.claude_profiles
+            .iter()
+            .map(|p| ClaudeRoot {
+                profile: Some(p.name.clone()),
+                config_dir: p.config_dir.clone(),
+            })
+            .collect()
+    }
+
+    /// Read and validate a config file. A missing file yields the defaults.
+    pub fn read(path: &Path) -> Result<Config, String> {
+        let content = match std::fs::read_to_string(path) {
+            Ok(c) => c,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Config::default()),
+            Err(e) => return Err(format!("{}: {}", path.display(), e)),
+        };
+        let config: Config =
+            serde_json::from_str(&content).map_err(|e| format!("{}: {}", path.display(), e))?;
+        config
+            .validate()
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+        Ok(config)
+    }
+
+    /// Load the default config file, falling back to defaults if it is invalid.
+    pub fn load() -> Config {
+        Config::read(&default_config_path()).unwrap_or_else(|e| {
+            logging::warn("config", &e);
+            Config::default()
+        })
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if !self.poll_interval_secs.is_finite() || self.poll_interval_secs <= 0.0 {
+            return Err("poll_interval_secs must be positive".into());
+        }
+        if self.poll_budget_ms == 0 {
+            return Err("poll_budget_ms must be positive".into());
+        }
+        let t = &self.thresholds;
+        for (name, value) in [
+            ("pending_grace_secs", t.pending_grace_secs),
+            ("active_secs", t.active_secs),
+            ("idle_after_secs", t.idle_after_secs),
+            ("busy_cpu_percent", t.busy_cpu_percent),
+            ("quiet_cpu_percent", t.quiet_cpu_percent),
+            ("quiet_processing_secs", t.quiet_processing_secs),
+            ("no_transcript_secs", t.no_transcript_secs),
+        ] {
+            if !value.is_finite() || value < 0.0 {
+                return Err(format!("thresholds.{} must not be negative", name));
+            }
+        }
+        if t.max_scan_bytes == 0 {
+            return Err("thresholds.max_scan_bytes must be positive".into());
+        }
+        if t.rate_limit_retries == 0 {
+            return Err("thresholds.rate_limit_retries must be positive".into());
+        }
+        for (tool, value) in &t.pending_timeouts {
+            if !value.is_finite() || *value < 0.0 {
+                return Err(format!(
+                    "thresholds.pending_timeouts.{} must not be negative",
+                    tool
+                ));
+            }
+        }
+        for (rule, enabled) in &t.rules {
+            if !transcript::pipeline_rules().any(|r| r == *rule) {
+                return Err(format!(
+                    "thresholds.rules.{} is not a status rule that can be switched",
+                    rule
+                ));
+            }
+            if *rule == Rule::TurnFinished && !enabled {
+                return Err("thresholds.rules.turn_finished can't be disabled".into());
+            }
+        }
+        for (palette, colors) in [
+            ("colors", &self.colors),
+            ("light_colors", &self.light_colors),
+        ] {
+            for (name, value) in [
+                ("active", &colors.active),
+                ("pending", &colors.pending),
+                ("idle", &colors.idle),
+                ("rate_limited", &colors.rate_limited),
+                ("unknown", &colors.unknown),
+            ] {
+                if !is_hex_color(value) {
+                    return Err(format!("{}.{} must look like #RRGGBB", palette, name));
+                }
+            }
+        }
+        for (agent, patterns) in [
+            ("claude", &self.processes.claude),
+            ("codex", &self.processes.codex),
+        ] {
+            if patterns.names.iter().any(|n| n.trim().is_empty())
+                || patterns
+                    .command_patterns
+                    .iter()
+                    .any(|p| p.trim().is_empty())
+            {
+                return Err(format!(
+                    "processes.{} must not contain empty patterns",
+                    agent
+                ));
+            }
+        }
+        for (i, remote) in self.remote_hosts.iter().enumerate() {
+            if remote.host.trim().is_empty() || remote.command.trim().is_empty() {
+                return Err(format!("remote_hosts[{}] needs a host and a command", i));
+            }
+        }
+        if !self.containers.max_age_secs.is_finite() || self.containers.max_age_secs <= 0.0 {
+            return Err("containers.max_age_secs must be positive".into());
+        }
+        if !self.discovery.max_age_secs.is_finite() || self.discovery.max_age_secs <= 0.0 {
+            return Err("discovery.max_age_secs must be positive".into());
+        }
+        let mut names = std::collections::HashSet::new();
+        for (i, profile) in self.claude_profiles.iter().enumerate() {
+            if profile.name.trim().is_empty() || profile.config_dir.as_os_str().is_empty() {
+                return Err(format!(
+                    "claude_profiles[{}] needs a name and a config_dir",
+                    i
+                ));
+            }
+            if !names.insert(profile.name.as_str()) {
+                return Err(format!(
+                    "claude_profiles has two profiles named {}",
+                    profile.name
+                ));
+            }
+        }
+        for (i, app) in self.terminals.iter().enumerate() {
+            if self.terminals[..i].contains(app) {
+                return Err(format!("terminals lists {} twice", app.terminal()));
+            }
+        }
+        hotkey::parse_bindings(&self.hotkeys).map_err(|e| format!("hotkeys: {}", e))?;
+        self.notifications.validate()?;
+        Ok(())
+    }
+}
+
+/// Change the `menu` options in the config file at `path`, leaving every other
+/// key as written. The result must still be a valid config.
+pub fn edit_menu_options(
+    path: &Path,
+    edit: impl FnOnce(&mut MenuOptions),
+) -> Result<MenuOptions, String> {
+    let mut root: serde_json::Value = match std::fs::read_to_string(path) {
+        Ok(c) => serde_json::from_str(&c).map_err(|e| format!("{}: {}", path.display(), e))?,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => serde_json::json!({}),
+        Err(e) => return Err(format!("{}: {}", path.display(), e)),
+    };
+    let object = root
+        .as_object_mut()
+        .ok_or_else(|| format!("{}: not a JSON object", path.display()))?;
+    let mut menu: MenuOptions = match object.get("menu") {
+        Some(value) => serde_json::from_value(value.clone())
+            .map_err(|e| format!("{}: menu: {}", path.display(), e))?,
+        None => MenuOptions::default(),
+    };
+    edit(&mut menu);
+    object.insert(
+        "menu".into(),
+        serde_json::to_value(&menu).map_err(|e| e.to_string())?,
+    );
+    let config: Config =
+        serde_json::from_value(root.clone()).map_err(|e| format!("{}: {}", path.display(), e))?;
+    config.validate()?;
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    let json = serde_json::to_string_pretty(&root).map_err(|e| e.to_string())?;
+    std::fs::write(path, json + "\n").map_err(|e| format!("{}: {}", path.display(), e))?;
+    Ok(menu)
+}
+
+/// `light_colors` keys left out fall back to the light palette, not the dark one.
+fn light_colors<'de, D>(deserializer: D) -> Result<Colors, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    struct Partial {
+        active: Option<String>,
+        pending: Option<String>,
+        idle: Option<String>,
+        rate_limited: Option<String>,
+        unknown: Option<String>,
+    }
+    let partial = Partial::deserialize(deserializer)?;
+    let light = Colors::light();
+    Ok(Colors {
+        active: partial.active.unwrap_or(light.active),
+        pending: partial.pending.unwrap_or(light.pending),
+        idle: partial.idle.unwrap_or(light.idle),
+        rate_limited: partial.rate_limited.unwrap_or(light.rate_limited),
+        unknown: partial.unknown.unwrap_or(light.unknown),
+    })
+}
+
+fn is_hex_color(s: &str) -> bool {
+    s.len() == 7 && s.starts_with('#') && s[1..].chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// Detects edits to the config file by comparing its mtime between checks.
+pub struct ConfigWatcher {
+    path: PathBuf,
+    mtime: Option<SystemTime>,
+}
+
+impl ConfigWatcher {
+    pub fn new(path: &Path) -> Self {
+        ConfigWatcher {
+            path: path.to_path_buf(),
+            mtime: mtime(path),
+        }
+    }
+
+    /// True if the file was created, modified or removed since the last call.
+    pub fn changed(&mut self) -> bool {
+        let current = mtime(&self.path);
+        if current == self.mtime {
+            return false;
+        }
+        self.mtime = current;
+        true
+    }
+}
+
+fn mtime(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_read_missing_file_is_default() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = Config::read(&dir.path().join("claude-bar.json")).unwrap();
+        assert_eq!(config, Config::default());
+    }
+
+    #[test]
+    fn test_read_partial_file_keeps_defaults() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("claude-bar.json");
+        std::fs::write(
+            &path,
+            r##"{"poll_interval_secs": 5, "colors": {"pending": "#FF0000"}, "thresholds": {"idle_after_secs": 300}}"##,
+        )
+        .unwrap();
+
+        let config = Config::read(&path).unwrap();
+        assert_eq!(config.poll_interval_secs, 5.0);
+        assert_eq!(config.colors.pending, "#FF0000");
+        assert_eq!(config.colors.active, Colors::default().active);
+        assert_eq!(config.light_colors, Colors::light());
+        assert_eq!(config.thresholds.idle_after_secs, 300.0);
+        assert_eq!(config.thresholds.active_secs, 10.0);
+    }
+
+    #[test]
+    fn test_edit_menu_options_keeps_other_keys() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("claude-bar.json");
+        std::fs::write(&path, r#"{"poll_interval_secs": 5, "custom": 1}"#).unwrap();
+
+        let menu = edit_menu_options(&path, |m| {
+            m.collapse_idle = true;
+            m.pinned_projects.push("api".into());
+        })
+        .unwrap();
+        assert!(menu.collapse_idle);
+
+        let config = Config::read(&path).unwrap();
+        assert_eq!(config.poll_interval_secs, 5.0);
+        assert_eq!(config.menu, menu);
+        let raw: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(raw["custom"], 1);
+
+        // A missing file is created
+        let fresh = dir.path().join("new").join("claude-bar.json");
+        edit_menu_options(&fresh, |m| m.hide_idle_in_icon = true).unwrap();
+        assert!(Config::read(&fresh).unwrap().menu.hide_idle_in_icon);
+    }
+
+    #[test]
+    fn test_read_rejects_invalid_values() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("claude-bar.json");
+
+        std::fs::write(&path, r#"{"poll_interval_secs": 0}"#).unwrap();
+        assert!(Config::read(&path)
+            .unwrap_err()
+            .contains("poll_interval_secs"));
+
+        std::fs::write(&path, r#"{"poll_budget_ms": 0}"#).unwrap();
+        assert!(Config::read(&path).unwrap_err().contains("poll_budget_ms"));
+
+        std::fs::write(
+            &path,
+            r#"{"processes": {"claude": {"command_patterns": [""]}}}"#,
+        )
+        .unwrap();
+        assert!(Config::read(&path)
+            .unwrap_err()
+            .contains("processes.claude"));
+
+        std::fs::write(
+            &path,
+            r#"{"remote_hosts": [{"host": "build"}, {"host": ""}]}"#,
+        )
+        .unwrap();
+        assert!(Config::read(&path).unwrap_err().contains("remote_hosts[1]"));
+
+        std::fs::write(&path, r#"{"light_colors": {"idle": "dark"}}"#).unwrap();
+        assert!(Config::read(&path)
+            .unwrap_err()
+            .contains("light_colors.idle"));
+
+        std::fs::write(&path, r#"{"colors": {"idle": "grey"}}"#).unwrap();
+        assert!(Config::read(&path).unwrap_err().contains("colors.idle"));
+
+        std::fs::write(&path, r#"{"thresholds": {"rules": {"queued": false}}}"#).unwrap();
+        assert!(Config::read(&path)
+            .unwrap_err()
+            .contains("thresholds.rules.queued"));
+
+        std::fs::write(
+            &path,
+            r#"{"thresholds": {"rules": {"turn_finished": false}}}"#,
+        )
+        .unwrap();
+        assert!(Config::read(&path)
+            .unwrap_err()
+            .contains("turn_finished can't be disabled"));
+
+        std::fs::write(
+            &path,
+            r#"{"claude_profiles": [{"name": "work", "config_dir": "/w"}, {"name": "work", "config_dir": "/p"}]}"#,
+        )
+        .unwrap();
+        assert!(Config::read(&path)
+            .unwrap_err()
+            .contains("two profiles named work"));
+
+        std::fs::write(&path, r#"{"terminals": ["alacritty", "wezterm"]}"#).unwrap();
+        assert!(Config::read(&path)
+            .unwrap_err()
+            .contains("unknown variant `wezterm`"));
+
+        std::fs::write(&path, r#"{"terminals": ["iterm2", "iterm2"]}"#).unwrap();
+        assert!(Config::read(&path)
+            .unwrap_err()
+            .contains("terminals lists iterm2 twice"));
+
+        std::fs::write(&path, r#"{"hotkeys": {"shift+1": "session:1"}}"#).unwrap();
+        assert!(Config::read(&path)
+            .unwrap_err()
+            .contains("hotkeys: hotkey shift+1 needs ctrl, alt or cmd"));
+
+        std::fs::write(
+            &path,
+            r#"{"notifications": {"channels": [{"kind": "macos", "after_secs": -5}]}}"#,
+        )
+        .unwrap();
+        assert!(Config::read(&path)
+            .unwrap_err()
+            .contains("notifications.channels[0].after_secs"));
+
+        std::fs::write(&path, "{not json").unwrap();
+        assert!(Config::read(&path).is_err());
+    }
+
+    #[test]
+    fn test_claude_roots_for_profiles() {
+        let config = Config {
+            claude_config_dir: Some("/Users/me/.claude".into()),
+            ..Config::default()
+        };
+        let roots = config.claude_roots();
+        assert_eq!(roots.len(), 1);
+        assert_eq!(roots[0].profile, None);
+
+        let config = Config {
+            claude_profiles: vec![
+                ClaudeProfile {
+                    name: "work".into(),
+                    config_dir: "/Users/me/.claude-work".into(),
+                },
+                ClaudeProfile {
+                    name: "personal".into(),
+                    config_dir: "/Users/me/.claude".into(),
+                },
+            ],
+            ..Config::default()
+        };
+        let roots = config.claude_roots();
+        assert_eq!(
+            roots[0].projects_dir(),
+            PathBuf::from("/Users/me/.claude-work/projects")
+        );
+        assert!(roots[0].contains("/Users/me/.claude-work/projects/-src-api/abc.jsonl"));
+        assert!(!roots[1].contains("/Users/me/.claude-work/projects/-src-api/abc.jsonl"));
+
+        let personal = ClaudeRoot::for_config_dir(&roots, Path::new("/Users/me/.claude"));
+        assert_eq!(personal.profile.as_deref(), Some("personal"));
+        // A directory no profile names is still used, just without a badge
+        let other = ClaudeRoot::for_config_dir(&roots, Path::new("/tmp/claude-test"));
+        assert_eq!(other.profile, None);
+        assert_eq!(other.config_dir, PathBuf::from("/tmp/claude-test"));
+    }
+
+    #[test]
+    fn test_resolve_claude_config_dir_precedence() {
+        let configured = PathBuf::from("/Volumes/work/claude");
+        assert_eq!(
+            resolve_claude_config_dir(Some("/opt/claude".into()), Some(&configured), "/Users/me"),
+            PathBuf::from("/opt/claude")
+        );
+        assert_eq!(
+            resolve_claude_config_dir(None, Some(&configured), "/Users/me"),
+            configured
+        );
+        assert_eq!(
+            resolve_claude_config_dir(None, None, "/Users/me"),
+            PathBuf::from("/Users/me/.claude")
+        );
+    }
+
+    #[test]
+    fn test_watcher_detects_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("claude-bar.json");
+        let mut watcher = ConfigWatcher::new(&path);
+        assert!(!watcher.changed());
+
+        std::fs::write(&path, "{}").unwrap();
+        assert!(watcher.changed());
+        assert!(!watcher.changed());
+
+        let later = SystemTime::now() + Duration::from_secs(5);
+        filetime::set_file_mtime(&path, filetime::FileTime::from_system_time(later)).unwrap();
+        assert!(watcher.changed());
+
+        std::fs::remove_file(&path).unwrap();
+        assert!(watcher.changed());
+    }
+}
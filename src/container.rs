@@ -0,0 +1,338 @@
+//! Sessions running inside Docker containers and VS Code devcontainers.
+//!
+//! The host sees no `claude` process for them, but Claude Code in the container
+//! writes its transcripts to the bind-mounted `~/.claude`. A recently written
+//! transcript whose working directory doesn't exist on this machine is taken to
+//! belong to one. With `docker` on, `docker ps` names the devcontainer whose
+//! workspace holds that directory and `docker exec ... pgrep` confirms Claude
+//! is still running in it.
+
+use crate::logging;
+use crate::process;
+use crate::serve;
+use crate::state::{Container, SessionInfo};
+use crate::transcript::{self, Thresholds};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::process::Command;
+
+/// `tty` of container sessions: this prefix plus the start of the session id.
+pub const TTY_PREFIX: &str = "container:";
+
+/// Container session detection (`containers` in the config file).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ContainerOptions {
+    /// List sessions found only through their transcripts' container paths
+    pub enabled: bool,
+    /// Seconds since the last transcript write for a session to still be listed
+    pub max_age_secs: f64,
+    /// Match sessions to running devcontainers with `docker ps` and `docker exec`
+    pub docker: bool,
+}
+
+impl Default for ContainerOptions {
+    fn default() -> Self {
+        ContainerOptions {
+            enabled: false,
+            max_age_secs: 600.0,
+            docker: false,
+        }
+    }
+}
+
+impl Container {
+    /// Section header in the dropdown: the container name, else "container".
+    pub fn label(&self) -> &str {
+        self.name.as_deref().unwrap_or("container")
+    }
+}
+
+/// A running container, from `docker ps`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RunningContainer {
+    pub id: String,
+    pub name: String,
+    /// `devcontainer.local_folder` label: the host folder it was opened from
+    pub local_folder: Option<String>,
+}
+
+const DOCKER_PS_FORMAT: &str = "{{.ID}}\t{{.Names}}\t{{.Label \"devcontainer.local_folder\"}}";
+
+/// Parse `docker ps --format` output in [`DOCKER_PS_FORMAT`].
+pub fn parse_docker_ps(output: &str) -> Vec<RunningContainer> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split('\t');
+            let id = fields.next()?.trim();
+            let name = fields.next()?.trim();
+            if id.is_empty() || name.is_empty() {
+                return None;
+            }
+            let local_folder = fields
+                .next()
+                .map(str::trim)
+                .filter(|f| !f.is_empty())
+                .map(str::to_string);
+            Some(RunningContainer {
+                id: id.to_string(),
+                name: name.to_string(),
+                local_folder,
+            })
+        })
+        .collect()
+}
+
+/// Where a devcontainer mounts the folder it was opened from, unless its
+/// `devcontainer.json` sets `workspaceFolder`: `/workspaces/<folder name>`.
+pub fn workspace_folder(local_folder: &str) -> String {
+    let name = Path::new(local_folder)
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    format!("/workspaces/{}", name)
+}
+
+/// The devcontainer whose workspace holds `cwd`.
+pub fn container_for_cwd<'a>(
+    cwd: &str,
+    containers: &'a [RunningContainer],
+) -> Option<&'a RunningContainer> {
+    containers.iter().find(|c| {
+        c.local_folder.as_deref().is_some_and(|folder| {
+            let workspace = workspace_folder(folder);
+            cwd == workspace || cwd.starts_with(&format!("{}/", workspace))
+        })
+    })
+}
+
+/// URI that makes VS Code open (or raise) the window attached to a devcontainer.
+pub fn vscode_folder_uri(local_folder: &str) -> String {
+    let hex: String = local_folder.bytes().map(|b| format!("{:02x}", b)).collect();
+    format!(
+        "vscode-remote://dev-container+{}{}",
+        hex,
+        workspace_folder(local_folder)
+    )
+}
+
+fn list_containers() -> Option<Vec<RunningContainer>> {
+    let output = process::run(Command::new("docker").args(["ps", "--format", DOCKER_PS_FORMAT]))?;
+    if !output.status.success() {
+        logging::warn(
+            "container",
+            &format!(
+                "docker ps failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ),
+        );
+        return None;
+    }
+    Some(parse_docker_ps(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// Whether a `claude` process runs inside the container.
+fn runs_agent(id: &str) -> bool {
+    process::run(Command::new("docker").args(["exec", id, "pgrep", "-x", "claude"]))
+        .is_some_and(|output| output.status.success())
+}
+
+/// `tty` for the session writing `path`: [`TTY_PREFIX`] and its id's first 8 characters.
+pub fn session_tty(path: &str) -> String {
+    let id = Path::new(path)
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+    format!("{}{}", TTY_PREFIX, id.chars().take(8).collect::<String>())
+}
+
+/// Sessions in containers: recent transcripts not `claimed` by a local session
+/// whose working directory isn't on this machine, the newest per directory.
+pub fn discover(
+    projects_dir: &Path,
+    claimed: &HashSet<String>,
+    options: &ContainerOptions,
+    thresholds: &Thresholds,
+) -> Vec<SessionInfo> {
+    let running = if options.docker {
+        list_containers()
+    } else {
+        None
+    };
+    let mut live: HashMap<String, bool> = HashMap::new();
+    let mut seen_cwds = HashSet::new();
+    let mut sessions = Vec::new();
+    for path in transcript::recent_transcripts(projects_dir, options.max_age_secs) {
+        if claimed.contains(&path) {
+            continue;
+        }
+        let Some(cwd) = transcript::transcript_cwd(&path) else {
+            continue;
+        };
+        if Path::new(&cwd).exists() || !seen_cwds.insert(cwd.clone()) {
+            continue;
+        }
+        let matched = running
+            .as_deref()
+            .and_then(|running| container_for_cwd(&cwd, running));
+        let container = match matched {
+            Some(c) => {
+                let alive = *live
+                    .entry(c.id.clone())
+                    .or_insert_with(|| runs_agent(&c.id));
+                if !alive {
+                    continue;
+                }
+                Container {
+                    name: Some(c.name.clone()),
+                    local_folder: c.local_folder.clone(),
+                }
+            }
+            None => Container {
+                name: None,
+                local_folder: None,
+            },
+        };
+        let mut session = serve::transcript_session(&path, session_tty(&path), cwd, thresholds);
+        session.container = Some(container);
+        sessions.push(session);
+    }
+    sessions
+}
+
+/// Raise the VS Code window attached to the devcontainer. Containers not
+/// opened from VS Code have no window to focus.
+pub fn focus(container: &Container) -> Result<(), Box<dyn std::error::Error>> {
+    let folder = container.local_folder.as_deref().ok_or_else(|| {
+        format!(
+            "Sessions in {} can't be focused (not a VS Code devcontainer)",
+            container.label()
+        )
+    })?;
+    let status = Command::new("code")
+        .args(["--folder-uri", &vscode_folder_uri(folder)])
+        .status()
+        .map_err(|e| format!("VS Code's `code` command is not available: {}", e))?;
+    if !status.success() {
+        return Err(format!("code --folder-uri failed for {}", folder).into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn running(name: &str, folder: Option<&str>) -> RunningContainer {
+        RunningContainer {
+            id: format!("{}-id", name),
+            name: name.into(),
+            local_folder: folder.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn test_parse_docker_ps() {
+        let output = "a1b2c3\tapi_devcontainer-app-1\t/Users/me/src/api\n\
+                      d4e5f6\tpostgres\t\n\
+                      \n";
+        assert_eq!(
+            parse_docker_ps(output),
+            vec![
+                RunningContainer {
+                    id: "a1b2c3".into(),
+                    name: "api_devcontainer-app-1".into(),
+                    local_folder: Some("/Users/me/src/api".into()),
+                },
+                RunningContainer {
+                    id: "d4e5f6".into(),
+                    name: "postgres".into(),
+                    local_folder: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_container_for_cwd() {
+        let containers = vec![
+            running("db", None),
+            running("api", Some("/Users/me/src/api")),
+        ];
+        assert_eq!(workspace_folder("/Users/me/src/api"), "/workspaces/api");
+        let name = |cwd| container_for_cwd(cwd, &containers).map(|c| c.name.as_str());
+        assert_eq!(name("/workspaces/api"), Some("api"));
+        assert_eq!(name("/workspaces/api/server"), Some("api"));
+        assert_eq!(name("/workspaces/api-v2"), None);
+        assert_eq!(name("/home/vscode"), None);
+    }
+
+    #[test]
+    fn test_vscode_folder_uri() {
+        assert_eq!(
+            vscode_folder_uri("/src/api"),
+            "vscode-remote://dev-container+2f7372632f617069/workspaces/api"
+        );
+    }
+
+    #[test]
+    fn test_session_tty() {
+        assert_eq!(
+            session_tty("/h/.claude/projects/-workspaces-api/3f2a9c1e-77aa-4e1b-9d2c-0b5e.jsonl"),
+            "container:3f2a9c1e"
+        );
+    }
+
+    #[test]
+    fn test_discover_skips_host_and_claimed_transcripts() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let projects = tmp.path().join("projects");
+        let line = |cwd: &str| {
+            format!(
+                r#"{{"type":"user","cwd":"{}","message":{{"role":"user","content":"hi"}}}}"#,
+                cwd
+            )
+        };
+        for (dir, file, cwd) in [
+            (
+                "-workspaces-api",
+                "aaaaaaaa-1.jsonl",
+                "/workspaces/api".to_string(),
+            ),
+            (
+                "-workspaces-web",
+                "bbbbbbbb-1.jsonl",
+                "/workspaces/web".to_string(),
+            ),
+            (
+                "host",
+                "cccccccc-1.jsonl",
+                tmp.path().to_string_lossy().to_string(),
+            ),
+        ] {
+            std::fs::create_dir_all(projects.join(dir)).unwrap();
+            std::fs::write(projects.join(dir).join(file), line(&cwd) + "\n").unwrap();
+        }
+        let claimed_path = projects
+            .join("-workspaces-web")
+            .join("bbbbbbbb-1.jsonl")
+            .to_string_lossy()
+            .to_string();
+        let claimed: HashSet<String> = [claimed_path].into_iter().collect();
+        let options = ContainerOptions {
+            enabled: true,
+            ..ContainerOptions::default()
+        };
+
+        let sessions = discover(&projects, &claimed, &options, &Thresholds::default());
+        assert_eq!(sessions.len(), 1);
+        let session = &sessions[0];
+        assert_eq!(session.tty, "container:aaaaaaaa");
+        assert_eq!(session.cwd, "/workspaces/api");
+        assert_eq!(session.pid, 0);
+        assert_eq!(session.focus_tty(), None);
+        assert_eq!(session.container.as_ref().unwrap().label(), "container");
+    }
+}
@@ -0,0 +1,379 @@
+use crate::config::Config;
+use crate::error::ClaudeBarError;
+use crate::filter::SessionFilter;
+use crate::protocol::{self, HealthReport, Request};
+use crate::render::{self, MenuFooter, MenuView};
+use crate::serve;
+use crate::state::SessionInfo;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// How long the menu waits for the daemon to answer before warning about it.
+const PROBE_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Query the daemon's `health` endpoint and print a report.
+pub fn run_status(json: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let socket = protocol::socket_path();
+    let response = protocol::send_request(&socket, &Request::Health)?;
+    if !response.ok {
+        return Err(ClaudeBarError::Daemon(response.error.unwrap_or_default()).into());
+    }
+    let data = response.data.unwrap_or_default();
+    if json {
+        println!("{}", data);
+        return Ok(());
+    }
+
+    let health: HealthReport = serde_json::from_value(data)?;
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    print!("{}", format_report(&health, &socket.to_string_lossy(), now));
+    Ok(())
+}
+
+/// Ask the daemon to reload its config file and poll immediately.
+pub fn run_reload() -> Result<(), Box<dyn std::error::Error>> {
+    let response = protocol::send_request(&protocol::socket_path(), &Request::Reload)?;
+    if !response.ok {
+        return Err(ClaudeBarError::Daemon(response.error.unwrap_or_default()).into());
+    }
+    let changed = response
+        .data
+        .as_ref()
+        .and_then(|d| d["config_changed"].as_bool())
+        .unwrap_or(false);
+    println!(
+        "{}",
+        if changed {
+            "Config reloaded"
+        } else {
+            "Config unchanged"
+        }
+    );
+    Ok(())
+}
+
+/// Print where clients look for the daemon socket.
+pub fn run_socket_path() -> Result<(), Box<dyn std::error::Error>> {
+    println!("{}", protocol::socket_path().display());
+    Ok(())
+}
+
+/// Compact human duration: "45s", "12m 5s", "3h 20m", "2d 4h".
+pub fn format_duration(secs: u64) -> String {
+    match secs {
+        0..=59 => format!("{}s", secs),
+        60..=3599 => format!("{}m {}s", secs / 60, secs % 60),
+        3600..=86399 => format!("{}h {}m", secs / 3600, (secs % 3600) / 60),
+        _ => format!("{}d {}h", secs / 86400, (secs % 86400) / 3600),
+    }
+}
+
+fn ago(then: u64, now: u64) -> String {
+    format!("{} ago", format_duration(now.saturating_sub(then)))
+}
+
+pub fn format_report(health: &HealthReport, socket: &str, now: u64) -> String {
+    let last_poll = match health.last_poll_unix {
+        Some(at) => format!(
+            "{}, took {} ms, {} subprocesses",
+            ago(at, now),
+            health.last_poll_duration_ms,
+            health.last_poll_subprocesses
+        ),
+        None => "never".to_string(),
+    };
+    let interval = if health.poll_backoff > 1 {
+        format!(
+            "{}s (backed off {}x, polls averaging {} ms)",
+            health.effective_interval_secs, health.poll_backoff, health.avg_poll_duration_ms
+        )
+    } else {
+        format!("{}s", health.effective_interval_secs)
+    };
+    let last_error = match &health.last_error {
+        Some(e) => format!("{} ({})", e.message, ago(e.at_unix, now)),
+        None => "none".to_string(),
+    };
+
+    let mut out = String::new();
+    out.push_str(&format!(
+        "claude-bar daemon (pid {}, v{})\n",
+        health.pid, health.version
+    ));
+    out.push_str(&format!("  socket:     {}\n", socket));
+    out.push_str(&format!(
+        "  uptime:     {}\n",
+        format_duration(health.uptime_secs)
+    ));
+    out.push_str(&format!("  last poll:  {}\n", last_poll));
+    out.push_str(&format!("  interval:   {}\n", interval));
+    out.push_str(&format!(
+        "  sessions:   {} (seq {})\n",
+        health.sessions, health.seq
+    ));
+    out.push_str(&format!("  last error: {}\n", last_error));
+    for peer in &health.peers {
+        let last_ok = peer
+            .last_ok_unix
+            .map_or("never".to_string(), |at| ago(at, now));
+        let state = match &peer.last_error {
+            Some(e) if peer.failures > 0 => {
+                format!("failing ({}x: {}), last ok {}", peer.failures, e, last_ok)
+            }
+            _ => format!("{} sessions, ok {}", peer.sessions, last_ok),
+        };
+        out.push_str(&format!(
+            "  peer:       {} ({}): {}\n",
+            peer.label, peer.addr, state
+        ));
+    }
+    for issue in &health.permissions {
+        out.push_str(&format!(
+            "  permission: {} (since {})\n",
+            issue.describe(),
+            ago(issue.since_unix, now)
+        ));
+    }
+    out.push_str("config:\n");
+    if let Some(config) = health.config.as_object() {
+        for (key, value) in config {
+            let shown = match value {
+                serde_json::Value::Null => "-".to_string(),
+                serde_json::Value::String(s) => s.clone(),
+                other => other.to_string(),
+            };
+            out.push_str(&format!("  {:<22}{}\n", format!("{}:", key), shown));
+        }
+    }
+    out
+}
+
+/// Whether a `serve` daemon answers, for the dropdown footer.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DaemonProbe {
+    Running(Box<HealthReport>),
+    /// No socket: the daemon isn't started, and the menu polls by itself
+    NotRunning,
+    /// The socket is there but nothing answers on it
+    Unreachable(String),
+}
+
+/// Ask the daemon for its health, giving up quickly so the menu doesn't
+/// stall on a hung daemon.
+pub fn probe() -> DaemonProbe {
+    let socket = protocol::socket_path();
+    if !socket.exists() {
+        return DaemonProbe::NotRunning;
+    }
+    match protocol::send_request_with_timeout(&socket, &Request::Health, PROBE_TIMEOUT) {
+        Ok(response) if response.ok => {
+            match serde_json::from_value(response.data.unwrap_or_default()) {
+                Ok(health) => DaemonProbe::Running(Box::new(health)),
+                Err(e) => DaemonProbe::Unreachable(e.to_string()),
+            }
+        }
+        Ok(response) => DaemonProbe::Unreachable(response.error.unwrap_or_default()),
+        Err(e) => DaemonProbe::Unreachable(e.to_string()),
+    }
+}
+
+/// Sessions from the daemon, if it answers in time.
+fn daemon_sessions() -> Option<Vec<SessionInfo>> {
+    protocol::send_request_with_timeout(
+        &protocol::socket_path(),
+        &Request::sessions(),
+        PROBE_TIMEOUT,
+    )
+    .ok()
+    .filter(|r| r.ok)
+    .and_then(|r| r.data)
+    .and_then(|data| serde_json::from_value(data).ok())
+}
+
+/// The menu view (of the sessions `filter` selects) from the daemon's sessions
+/// while it runs, else from a local poll. The daemon's state goes in the
+/// footer only when its sessions are the ones shown.
+pub fn current_view(config: &Config, filter: &SessionFilter) -> (Vec<SessionInfo>, MenuView) {
+    let probe = probe();
+    let from_daemon = match probe {
+        DaemonProbe::Running(_) => daemon_sessions(),
+        _ => None,
+    };
+    let fell_back = from_daemon.is_none();
+    let sessions = filter.apply(from_daemon.unwrap_or_else(|| serve::poll_sessions_with(config)));
+    let mut view = render::menu_view(&sessions, &config.menu);
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    match probe {
+        DaemonProbe::Running(_) if fell_back => {
+            view.footer.warning =
+                Some("Daemon didn't send its sessions; showing a local poll".into());
+        }
+        _ => annotate_footer(&mut view.footer, &probe, now),
+    }
+    (sessions, view)
+}
+
+/// Fill in the daemon rows of the dropdown footer: uptime and last poll while
+/// it runs, and a warning when it doesn't answer or hasn't polled for five
+/// intervals (at least 30s), so stale sessions don't pass for current ones.
+pub fn annotate_footer(footer: &mut MenuFooter, probe: &DaemonProbe, now: u64) {
+    match probe {
+        DaemonProbe::Running(health) => {
+            let last_poll = health
+                .last_poll_unix
+                .map_or("no poll yet".to_string(), |at| {
+                    format!("last poll {}", ago(at, now))
+                });
+            footer.daemon = Some(format!(
+                "Daemon up {} · {}",
+                format_duration(health.uptime_secs),
+                last_poll
+            ));
+            let stale_after = (health.effective_interval_secs * 5.0).max(30.0) as u64;
+            let since = health
+                .last_poll_unix
+                .map_or(health.uptime_secs, |at| now.saturating_sub(at));
+            if since > stale_after {
+                footer.warning = Some(format!(
+                    "Daemon hasn't polled for {}; sessions may be out of date",
+                    format_duration(since)
+                ));
+            }
+        }
+        DaemonProbe::NotRunning => {}
+        DaemonProbe::Unreachable(e) => {
+            footer.warning = Some(format!("Daemon not responding ({})", e));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::peer::PeerReport;
+    use crate::permissions::{Permission, PermissionIssue};
+    use crate::protocol::ErrorReport;
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!(format_duration(0), "0s");
+        assert_eq!(format_duration(59), "59s");
+        assert_eq!(format_duration(725), "12m 5s");
+        assert_eq!(format_duration(12000), "3h 20m");
+        assert_eq!(format_duration(187200), "2d 4h");
+    }
+
+    fn health() -> HealthReport {
+        HealthReport {
+            pid: 42,
+            version: "0.1.0".into(),
+            uptime_secs: 7260,
+            seq: 17,
+            sessions: 3,
+            last_poll_unix: Some(998),
+            last_poll_duration_ms: 183,
+            last_poll_subprocesses: 14,
+            avg_poll_duration_ms: 1450,
+            effective_interval_secs: 4.0,
+            poll_backoff: 2,
+            last_error: Some(ErrorReport {
+                at_unix: 940,
+                message: "osascript: execution error".into(),
+            }),
+            peers: vec![
+                PeerReport {
+                    label: "desk".into(),
+                    addr: "10.0.0.5:7878".into(),
+                    sessions: 2,
+                    last_ok_unix: Some(997),
+                    failures: 0,
+                    last_error: None,
+                },
+                PeerReport {
+                    label: "mini".into(),
+                    addr: "mini:7878".into(),
+                    sessions: 1,
+                    last_ok_unix: None,
+                    failures: 4,
+                    last_error: Some("connection refused".into()),
+                },
+            ],
+            permissions: vec![PermissionIssue {
+                permission: Permission::Automation,
+                app: "iTerm2".into(),
+                detail: "Not authorized to send Apple events to iTerm2. (-1743)".into(),
+                since_unix: 700,
+            }],
+            config: serde_json::json!({"interval_secs": 2.0, "websocket_port": null}),
+        }
+    }
+
+    #[test]
+    fn test_format_report() {
+        let health = health();
+        let report = format_report(&health, "/tmp/claude-bar.sock", 1000);
+        assert!(report.starts_with("claude-bar daemon (pid 42, v0.1.0)\n"));
+        assert!(report.contains("  uptime:     2h 1m\n"));
+        assert!(report.contains("  last poll:  2s ago, took 183 ms, 14 subprocesses\n"));
+        assert!(report.contains("  interval:   4s (backed off 2x, polls averaging 1450 ms)\n"));
+        assert!(report.contains("  sessions:   3 (seq 17)\n"));
+        assert!(report.contains("  last error: osascript: execution error (1m 0s ago)\n"));
+        assert!(report.contains("  peer:       desk (10.0.0.5:7878): 2 sessions, ok 3s ago\n"));
+        assert!(report.contains(
+            "  peer:       mini (mini:7878): failing (4x: connection refused), last ok never\n"
+        ));
+        assert!(report.contains(
+            "  permission: Automation access to iTerm2 is missing (System Settings › Privacy & Security › Automation) (since 5m 0s ago)\n"
+        ));
+        assert!(report.contains("  interval_secs:        2.0\n"));
+        assert!(report.contains("  websocket_port:       -\n"));
+    }
+
+    #[test]
+    fn test_annotate_footer() {
+        let mut health = health();
+        let footer = |probe: &DaemonProbe, now| {
+            let mut footer = MenuFooter::default();
+            annotate_footer(&mut footer, probe, now);
+            footer
+        };
+        let running = footer(&DaemonProbe::Running(Box::new(health.clone())), 1001);
+        assert_eq!(
+            running.daemon.as_deref(),
+            Some("Daemon up 2h 1m · last poll 3s ago")
+        );
+        assert_eq!(running.warning, None);
+        // Five intervals of 4s would be 20s; 30s is the floor
+        let late = footer(&DaemonProbe::Running(Box::new(health.clone())), 1028);
+        assert_eq!(late.warning, None);
+        let stale = footer(&DaemonProbe::Running(Box::new(health.clone())), 1300);
+        assert_eq!(
+            stale.warning.as_deref(),
+            Some("Daemon hasn't polled for 5m 2s; sessions may be out of date")
+        );
+        health.last_poll_unix = None;
+        health.uptime_secs = 5;
+        let starting = footer(&DaemonProbe::Running(Box::new(health)), 1000);
+        assert_eq!(
+            starting.daemon.as_deref(),
+            Some("Daemon up 5s · no poll yet")
+        );
+        assert_eq!(starting.warning, None);
+
+        assert_eq!(
+            footer(&DaemonProbe::NotRunning, 1000),
+            MenuFooter::default()
+        );
+        let unreachable = footer(&DaemonProbe::Unreachable("timed out".into()), 1000);
+        assert_eq!(unreachable.daemon, None);
+        assert_eq!(
+            unreachable.warning.as_deref(),
+            Some("Daemon not responding (timed out)")
+        );
+    }
+}
@@ -0,0 +1,208 @@
+use crate::focus;
+use crate::state::SessionInfo;
+
+pub const URL_SCHEME: &str = "claude-bar";
+
+/// An action requested through a `claude-bar://` URL.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DeepLink {
+    /// `claude-bar://focus?tty=ttys003`, or a session key for sessions without
+    /// a local TTY (`tty=build:/dev/pts/1`). Only live polled sessions are
+    /// focused; the URL never says how.
+    FocusTty(String),
+    /// `claude-bar://focus?project=my-app`
+    FocusProject(String),
+}
+
+/// Parse a `claude-bar://<action>?<query>` URL.
+pub fn parse_url(url: &str) -> Result<DeepLink, String> {
+    let rest = url
+        .strip_prefix(URL_SCHEME)
+        .and_then(|r| r.strip_prefix("://"))
+        .ok_or_else(|| format!("Not a {}:// URL: {}", URL_SCHEME, url))?;
+
+    let (action, query) = rest.split_once('?').unwrap_or((rest, ""));
+    let action = action.trim_end_matches('/');
+    let params = parse_query(query);
+    let get = |key: &str| {
+        params
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.clone())
+            .filter(|v| !v.is_empty())
+    };
+
+    match action {
+        "focus" => {
+            // `terminal` and `cwd` from older links are ignored
+            if let Some(tty) = get("tty") {
+                Ok(DeepLink::FocusTty(normalize_tty(&tty)))
+            } else if let Some(project) = get("project") {
+                Ok(DeepLink::FocusProject(project))
+            } else {
+                Err("focus URL needs a `tty` or `project` parameter".into())
+            }
+        }
+        other => Err(format!("Unknown URL action: {}", other)),
+    }
+}
+
+/// Accept both `ttys003` and `/dev/ttys003` (session keys such as
+/// `build:/dev/pts/1`, `container:...` and `transcript:...` as is).
+fn normalize_tty(tty: &str) -> String {
+    if tty.starts_with("/dev/") || tty.contains(':') {
+        tty.to_string()
+    } else {
+        format!("/dev/{}", tty)
+    }
+}
+
+/// The URL focusing `session`: by TTY for local sessions, by key for the
+/// others.
+pub fn focus_url(session: &SessionInfo) -> String {
+    let target = if session.remote.is_none() && session.container.is_none() && !session.detached {
+        session.tty.trim_start_matches("/dev/").to_string()
+    } else {
+        session.key()
+    };
+    format!("{}://focus?tty={}", URL_SCHEME, percent_encode(&target))
+}
+
+/// Escape everything in a query value but unreserved characters, `/` and `:`.
+fn percent_encode(s: &str) -> String {
+    s.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' | b':' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+fn parse_query(query: &str) -> Vec<(String, String)> {
+    query
+        .split('&')
+        .filter(|p| !p.is_empty())
+        .map(|pair| {
+            let (k, v) = pair.split_once('=').unwrap_or((pair, ""));
+            (percent_decode(k), percent_decode(v))
+        })
+        .collect()
+}
+
+/// Decode `%XX` escapes and `+` (as space) in a URL query component.
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'%' if i + 2 < bytes.len() => {
+                let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).unwrap_or("");
+                match u8::from_str_radix(hex, 16) {
+                    Ok(b) => {
+                        out.push(b);
+                        i += 3;
+                    }
+                    Err(_) => {
+                        out.push(b'%');
+                        i += 1;
+                    }
+                }
+            }
+            b'+' => {
+                out.push(b' ');
+                i += 1;
+            }
+            b => {
+                out.push(b);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&out).to_string()
+}
+
+/// Handle a `claude-bar://` URL (invoked by the menu bar app's URL handler).
+pub fn run_open_url(url: &str) -> Result<(), Box<dyn std::error::Error>> {
+    match parse_url(url)? {
+        DeepLink::FocusTty(tty) => {
+            let sessions = crate::serve::poll_sessions();
+            let session =
+                focus::find_target(&sessions, &tty).ok_or(format!("No live session on {}", tty))?;
+            focus::focus_session(session)
+        }
+        DeepLink::FocusProject(project) => focus::run_focus_lookup(Some(&project), false),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_focus_tty() {
+        assert_eq!(
+            parse_url("claude-bar://focus?tty=ttys003"),
+            Ok(DeepLink::FocusTty("/dev/ttys003".into()))
+        );
+    }
+
+    #[test]
+    fn test_parse_focus_session_key() {
+        assert_eq!(
+            parse_url("claude-bar://focus?tty=container:3f2a9c1e"),
+            Ok(DeepLink::FocusTty("container:3f2a9c1e".into()))
+        );
+        assert_eq!(
+            parse_url("claude-bar://focus?tty=build%20box:/dev/pts/1"),
+            Ok(DeepLink::FocusTty("build box:/dev/pts/1".into()))
+        );
+    }
+
+    #[test]
+    fn test_parse_focus_ignores_terminal_and_cwd() {
+        // Whatever the URL says, only a polled session's own terminal is used
+        assert_eq!(
+            parse_url("claude-bar://focus/?tty=%2Fdev%2Fttys001&terminal=alacritty&cwd=x%22%20%26%20(do%20shell%20script%20%22id%22)"),
+            Ok(DeepLink::FocusTty("/dev/ttys001".into()))
+        );
+    }
+
+    #[test]
+    fn test_focus_url_roundtrip() {
+        let session: SessionInfo = serde_json::from_value(serde_json::json!({
+            "tty": "/dev/pts/1", "pid": 1, "cwd": "/p", "provider": "claude", "terminal": "iterm2",
+            "status": "idle",
+            "remote": {"host": "build box", "local_tty": "/dev/ttys007"}
+        }))
+        .unwrap();
+        let url = focus_url(&session);
+        assert_eq!(url, "claude-bar://focus?tty=build%20box:/dev/pts/1");
+        assert_eq!(parse_url(&url), Ok(DeepLink::FocusTty(session.key())));
+    }
+
+    #[test]
+    fn test_parse_focus_project() {
+        assert_eq!(
+            parse_url("claude-bar://focus?project=web+app"),
+            Ok(DeepLink::FocusProject("web app".into()))
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(parse_url("https://focus?tty=ttys000").is_err());
+        assert!(parse_url("claude-bar://focus").is_err());
+        assert!(parse_url("claude-bar://focus?tty=").is_err());
+        assert!(parse_url("claude-bar://explode?tty=ttys000").is_err());
+    }
+
+    #[test]
+    fn test_percent_decode_malformed() {
+        assert_eq!(percent_decode("100%"), "100%");
+        assert_eq!(percent_decode("%zz"), "%zz");
+        assert_eq!(percent_decode("a%20b"), "a b");
+    }
+}
@@ -0,0 +1,163 @@
+//! Typed errors for the failures callers react to, and how the CLI reports them.
+//!
+//! Most functions still return `Box<dyn Error>`; the ones that hit a failure a
+//! script or the menu bar app may want to handle (daemon down, Automation access
+//! denied, no such session) box a [`ClaudeBarError`], which `main` finds again
+//! with [`classify`] to pick the exit code and the `--json-errors` kind.
+
+use crate::permissions::Permission;
+use serde::Serialize;
+use std::error::Error;
+
+#[derive(Debug, thiserror::Error)]
+pub enum ClaudeBarError {
+    /// Hook stdin isn't a Claude Code hook payload
+    #[error("Failed to parse hook JSON from stdin")]
+    HookParse,
+    /// The hook runs outside any claude process with a TTY
+    #[error("Could not determine the claude session TTY")]
+    NoClaudeAncestor,
+    /// Nothing listening on the daemon socket (or TCP/TLS address)
+    #[error("daemon not reachable at {target}: {source}")]
+    SocketUnavailable {
+        target: String,
+        #[source]
+        source: std::io::Error,
+    },
+    /// The daemon answered with `"ok": false`
+    #[error("{0}")]
+    Daemon(String),
+    /// `osascript` lacks Automation or Accessibility access to `app`
+    #[error("osascript: {detail}")]
+    AppleScriptDenied {
+        app: String,
+        permission: Permission,
+        detail: String,
+    },
+    /// Any other `osascript` failure
+    #[error("osascript: {0}")]
+    AppleScript(String),
+    /// No live session matches the TTY or project asked for
+    #[error("{0}")]
+    NoSession(String),
+    /// The config file can't be read or edited
+    #[error("{0}")]
+    Config(String),
+}
+
+impl ClaudeBarError {
+    /// Stable identifier for `--json-errors`, e.g. `socket_unavailable`.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            ClaudeBarError::HookParse => "hook_parse",
+            ClaudeBarError::NoClaudeAncestor => "no_claude_ancestor",
+            ClaudeBarError::SocketUnavailable { .. } => "socket_unavailable",
+            ClaudeBarError::Daemon(_) => "daemon",
+            ClaudeBarError::AppleScriptDenied { .. } => "applescript_denied",
+            ClaudeBarError::AppleScript(_) => "applescript",
+            ClaudeBarError::NoSession(_) => "no_session",
+            ClaudeBarError::Config(_) => "config",
+        }
+    }
+
+    /// Process exit code, from `sysexits.h` where one fits.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            ClaudeBarError::HookParse => 65,                // EX_DATAERR
+            ClaudeBarError::NoClaudeAncestor => 66,         // EX_NOINPUT
+            ClaudeBarError::NoSession(_) => 67,             // EX_NOUSER
+            ClaudeBarError::SocketUnavailable { .. } => 69, // EX_UNAVAILABLE
+            ClaudeBarError::AppleScript(_) => 70,           // EX_SOFTWARE
+            ClaudeBarError::Daemon(_) => 76,                // EX_PROTOCOL
+            ClaudeBarError::AppleScriptDenied { .. } => 77, // EX_NOPERM
+            ClaudeBarError::Config(_) => 78,                // EX_CONFIG
+        }
+    }
+}
+
+/// An error as `main` reports it: printed as `Error: <message>`, or as JSON
+/// on stderr with `--json-errors`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ErrorOutput {
+    pub kind: &'static str,
+    pub message: String,
+    pub exit_code: i32,
+    /// `applescript_denied` only: `automation` or `accessibility`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub permission: Option<Permission>,
+}
+
+/// Find the [`ClaudeBarError`] in `err` or its sources; I/O errors map to
+/// `io` (exit 74) and anything else to `other` (exit 1).
+pub fn classify(err: &(dyn Error + 'static)) -> ErrorOutput {
+    let mut cause = Some(err);
+    let mut io = false;
+    while let Some(e) = cause {
+        if let Some(typed) = e.downcast_ref::<ClaudeBarError>() {
+            return ErrorOutput {
+                kind: typed.kind(),
+                message: err.to_string(),
+                exit_code: typed.exit_code(),
+                permission: match typed {
+                    ClaudeBarError::AppleScriptDenied { permission, .. } => Some(*permission),
+                    _ => None,
+                },
+            };
+        }
+        io |= e.is::<std::io::Error>();
+        cause = e.source();
+    }
+    let (kind, exit_code) = if io { ("io", 74) } else { ("other", 1) };
+    ErrorOutput {
+        kind,
+        message: err.to_string(),
+        exit_code,
+        permission: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify_typed_errors() {
+        let err: Box<dyn Error> = Box::new(ClaudeBarError::SocketUnavailable {
+            target: "/tmp/claude-bar.sock".into(),
+            source: std::io::Error::from(std::io::ErrorKind::ConnectionRefused),
+        });
+        let out = classify(err.as_ref());
+        assert_eq!(out.kind, "socket_unavailable");
+        assert_eq!(out.exit_code, 69);
+        assert!(out
+            .message
+            .starts_with("daemon not reachable at /tmp/claude-bar.sock: "));
+
+        let err: Box<dyn Error> = Box::new(ClaudeBarError::AppleScriptDenied {
+            app: "iTerm2".into(),
+            permission: Permission::Automation,
+            detail: "Not authorized to send Apple events to iTerm2. (-1743)".into(),
+        });
+        let json = serde_json::to_value(classify(err.as_ref())).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "kind": "applescript_denied",
+                "message": "osascript: Not authorized to send Apple events to iTerm2. (-1743)",
+                "exit_code": 77,
+                "permission": "automation",
+            })
+        );
+    }
+
+    #[test]
+    fn test_classify_untyped_errors() {
+        let err: Box<dyn Error> = "Nothing to send (use --text or --action)".into();
+        let out = classify(err.as_ref());
+        assert_eq!((out.kind, out.exit_code), ("other", 1));
+        assert_eq!(out.message, "Nothing to send (use --text or --action)");
+
+        let err: Box<dyn Error> = Box::new(std::io::Error::from(std::io::ErrorKind::NotFound));
+        assert_eq!(classify(err.as_ref()).exit_code, 74);
+    }
+}
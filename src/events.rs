@@ -0,0 +1,224 @@
+//! Event bus inside the `serve` daemon.
+//!
+//! Sources (the poller, a config reload, hook deliveries) publish [`SessionEvent`]s; consumers
+//! (the session store behind the socket, the status log) subscribe. Every
+//! subscriber gets each event published after it subscribed, in order, on its
+//! own channel and thread, so a slow consumer holds up neither the sources nor
+//! the other consumers. New cross-cutting features (notifiers, status bar
+//! triggers) subscribe here instead of being wired into the poll loop.
+
+use crate::serve::PollStats;
+use crate::state::{SessionInfo, Status};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+/// Something that happened in the daemon.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SessionEvent {
+    /// A poll finished; `sessions` includes the peers' sessions
+    Polled {
+        sessions: Vec<SessionInfo>,
+        stats: PollStats,
+        /// Subprocess failure hit during the poll
+        error: Option<String>,
+    },
+    /// The session store took a poll that differs from the previous one
+    SessionsChanged {
+        /// Store sequence number after the change
+        seq: u64,
+        /// Sessions that appeared, changed status or ended (other field
+        /// changes, such as a new title, bump `seq` only)
+        changes: Vec<SessionChange>,
+    },
+    /// The config file was re-read and differs from before
+    ConfigReloaded { colors_changed: bool },
+    /// A `claude-bar hook` run delivered its event and the daemon recorded it
+    HookReceived {
+        /// Session TTY, e.g. `/dev/ttys003`
+        tty: String,
+        /// Hook event name, e.g. `PreToolUse`
+        event: Option<String>,
+    },
+}
+
+/// How one session differs between two polls.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SessionChange {
+    Appeared(SessionInfo),
+    StatusChanged { from: Status, session: SessionInfo },
+    Gone(SessionInfo),
+}
+
+impl SessionChange {
+    /// Log line, e.g. "/dev/ttys000 api: active -> pending (Bash)".
+    pub fn describe(&self) -> String {
+        let tool = |s: &SessionInfo| {
+            s.pending_tool
+                .as_deref()
+                .map(|t| format!(" ({})", t))
+                .unwrap_or_default()
+        };
+        match self {
+            SessionChange::Appeared(s) => format!(
+                "{} {}: appeared {}{}",
+                s.key(),
+                s.project_name(),
+                s.status,
+                tool(s)
+            ),
+            SessionChange::StatusChanged { from, session: s } => format!(
+                "{} {}: {} -> {}{}",
+                s.key(),
+                s.project_name(),
+                from,
+                s.status,
+                tool(s)
+            ),
+            SessionChange::Gone(s) => format!("{} {}: gone", s.key(), s.project_name()),
+        }
+    }
+}
+
+/// Sessions that appeared or changed status in `new`, then those gone from `old`.
+pub fn session_changes(old: &[SessionInfo], new: &[SessionInfo]) -> Vec<SessionChange> {
+    let mut changes = Vec::new();
+    for s in new {
+        match old.iter().find(|o| o.key() == s.key()) {
+            None => changes.push(SessionChange::Appeared(s.clone())),
+            Some(o) if o.status != s.status => changes.push(SessionChange::StatusChanged {
+                from: o.status,
+                session: s.clone(),
+            }),
+            Some(_) => {}
+        }
+    }
+    for o in old {
+        if !new.iter().any(|s| s.key() == o.key()) {
+            changes.push(SessionChange::Gone(o.clone()));
+        }
+    }
+    changes
+}
+
+/// Fan-out of [`SessionEvent`]s to every subscriber.
+#[derive(Debug, Default)]
+pub struct EventBus {
+    subscribers: Mutex<Vec<Sender<Arc<SessionEvent>>>>,
+}
+
+impl EventBus {
+    /// Receive every event published from now on.
+    pub fn subscribe(&self) -> Receiver<Arc<SessionEvent>> {
+        let (tx, rx) = mpsc::channel();
+        self.subscribers.lock().unwrap().push(tx);
+        rx
+    }
+
+    /// Send `event` to every subscriber; those that hung up are dropped.
+    pub fn publish(&self, event: SessionEvent) {
+        let event = Arc::new(event);
+        self.subscribers
+            .lock()
+            .unwrap()
+            .retain(|tx| tx.send(Arc::clone(&event)).is_ok());
+    }
+
+    /// Subscribe and run `handle` on every event on a thread of its own.
+    pub fn spawn_consumer<F>(&self, name: &str, mut handle: F) -> thread::JoinHandle<()>
+    where
+        F: FnMut(&SessionEvent) + Send + 'static,
+    {
+        let events = self.subscribe();
+        thread::Builder::new()
+            .name(format!("events-{}", name))
+            .spawn(move || {
+                for event in events {
+                    handle(&event);
+                }
+            })
+            .expect("failed to spawn event consumer")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session(tty: &str, status: Status) -> SessionInfo {
+        SessionInfo::for_test(tty, "/tmp/p", status)
+    }
+
+    #[test]
+    fn test_session_changes() {
+        let old = vec![
+            session("/dev/ttys000", Status::Active),
+            session("/dev/ttys001", Status::Idle),
+        ];
+        let mut pending = session("/dev/ttys000", Status::Pending);
+        pending.pending_tool = Some("Bash".into());
+        let new = vec![pending, session("/dev/ttys002", Status::Active)];
+
+        let lines: Vec<String> = session_changes(&old, &new)
+            .iter()
+            .map(SessionChange::describe)
+            .collect();
+        assert_eq!(
+            lines,
+            vec![
+                "/dev/ttys000 p: active -> pending (Bash)",
+                "/dev/ttys002 p: appeared active",
+                "/dev/ttys001 p: gone",
+            ]
+        );
+        assert!(session_changes(&new, &new).is_empty());
+    }
+
+    #[test]
+    fn test_bus_delivers_in_order_to_every_subscriber() {
+        let bus = EventBus::default();
+        let early = bus.subscribe();
+        bus.publish(SessionEvent::ConfigReloaded {
+            colors_changed: false,
+        });
+        let late = bus.subscribe();
+        let dropped = bus.subscribe();
+        drop(dropped);
+        bus.publish(SessionEvent::ConfigReloaded {
+            colors_changed: true,
+        });
+
+        let seen: Vec<_> = early.try_iter().map(|e| (*e).clone()).collect();
+        assert_eq!(seen.len(), 2);
+        assert_eq!(
+            seen[1],
+            SessionEvent::ConfigReloaded {
+                colors_changed: true
+            }
+        );
+        // Subscribers only see what was published after they subscribed
+        assert_eq!(late.try_iter().count(), 1);
+        assert_eq!(bus.subscribers.lock().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_spawn_consumer_handles_events() {
+        let bus = EventBus::default();
+        let (tx, rx) = mpsc::channel();
+        bus.spawn_consumer("test", move |event| {
+            if let SessionEvent::SessionsChanged { seq, .. } = event {
+                tx.send(*seq).unwrap();
+            }
+        });
+        for seq in 1..=3 {
+            bus.publish(SessionEvent::SessionsChanged {
+                seq,
+                changes: Vec::new(),
+            });
+        }
+        let seqs: Vec<u64> = (0..3)
+            .map(|_| rx.recv_timeout(std::time::Duration::from_secs(5)).unwrap())
+            .collect();
+        assert_eq!(seqs, vec![1, 2, 3]);
+    }
+}
@@ -0,0 +1,250 @@
+//! `claude-bar export`: live sessions as JSON or CSV for spreadsheets and scripts.
+//!
+//! Only sessions that are running now are exported; there is no history
+//! recorder, so ended sessions are not available. `--since`/`--until` select by
+//! the session's start time.
+
+use crate::logging;
+use crate::state::SessionInfo;
+use clap::ValueEnum;
+use serde::Serialize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ExportFormat {
+    Json,
+    Csv,
+}
+
+/// One exported session, flattened so every field fits a CSV column.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ExportRecord {
+    pub project: String,
+    pub provider: String,
+    pub status: String,
+    pub pending: Option<String>,
+    pub host: Option<String>,
+    pub tty: String,
+    pub terminal: String,
+    pub cwd: String,
+    pub pid: u32,
+    /// RFC 3339 start time of the transcript
+    pub started_at: Option<String>,
+    pub turns: u32,
+    pub queued: u32,
+    pub title: Option<String>,
+    pub transcript: Option<String>,
+}
+
+const CSV_COLUMNS: [&str; 14] = [
+    "project",
+    "provider",
+    "status",
+    "pending",
+    "host",
+    "tty",
+    "terminal",
+    "cwd",
+    "pid",
+    "started_at",
+    "turns",
+    "queued",
+    "title",
+    "transcript",
+];
+
+impl ExportRecord {
+    pub fn from_session(s: &SessionInfo) -> ExportRecord {
+        ExportRecord {
+            project: s.project_name(),
+            provider: s.provider.to_string(),
+            status: s.status.to_string(),
+            pending: s.pending_reason.as_ref().map(|r| r.label()),
+            host: s.remote.as_ref().map(|r| r.host.clone()),
+            tty: s.tty.clone(),
+            terminal: s.terminal.to_string(),
+            cwd: s.cwd.clone(),
+            pid: s.pid,
+            started_at: s.started_at.map(logging::format_timestamp),
+            turns: s.turns,
+            queued: s.queued,
+            title: s.title.clone(),
+            transcript: s.transcript.clone(),
+        }
+    }
+
+    fn csv_fields(&self) -> [String; 14] {
+        let opt = |v: &Option<String>| v.clone().unwrap_or_default();
+        [
+            self.project.clone(),
+            self.provider.clone(),
+            self.status.clone(),
+            opt(&self.pending),
+            opt(&self.host),
+            self.tty.clone(),
+            self.terminal.clone(),
+            self.cwd.clone(),
+            self.pid.to_string(),
+            opt(&self.started_at),
+            self.turns.to_string(),
+            self.queued.to_string(),
+            opt(&self.title),
+            opt(&self.transcript),
+        ]
+    }
+}
+
+/// Parse a range bound: an RFC 3339 timestamp, or a duration before `now`
+/// such as `90s`, `30m`, `2h` or `7d`.
+pub fn parse_time(s: &str, now: u64) -> Result<u64, String> {
+    if let Some(at) = logging::parse_timestamp(s) {
+        return Ok(at);
+    }
+    let s = s.trim();
+    let invalid = || {
+        format!(
+            "invalid time '{}' (use e.g. 2h, 7d or an RFC 3339 timestamp)",
+            s
+        )
+    };
+    let unit = match s.chars().last() {
+        Some('s') => 1,
+        Some('m') => 60,
+        Some('h') => 3600,
+        Some('d') => 86400,
+        _ => return Err(invalid()),
+    };
+    let amount: u64 = s[..s.len() - 1].parse().map_err(|_| invalid())?;
+    Ok(now.saturating_sub(amount.saturating_mul(unit)))
+}
+
+/// Sessions started within `[since, until]`. Without bounds every session is
+/// kept; with one, sessions whose start time is unknown are dropped.
+pub fn select(
+    sessions: &[SessionInfo],
+    since: Option<u64>,
+    until: Option<u64>,
+) -> Vec<ExportRecord> {
+    sessions
+        .iter()
+        .filter(|s| match (since, until) {
+            (None, None) => true,
+            _ => s.started_at.is_some_and(|at| {
+                since.is_none_or(|since| at >= since) && until.is_none_or(|until| at <= until)
+            }),
+        })
+        .map(ExportRecord::from_session)
+        .collect()
+}
+
+/// Quote a CSV field when it contains a delimiter, quote or line break.
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+pub fn to_csv(records: &[ExportRecord]) -> String {
+    let mut out = CSV_COLUMNS.join(",");
+    out.push('\n');
+    for record in records {
+        let fields: Vec<String> = record.csv_fields().iter().map(|f| csv_field(f)).collect();
+        out.push_str(&fields.join(","));
+        out.push('\n');
+    }
+    out
+}
+
+pub fn to_json(records: &[ExportRecord], exported_at: u64) -> Result<String, serde_json::Error> {
+    serde_json::to_string_pretty(&serde_json::json!({
+        "exported_at": logging::format_timestamp(exported_at),
+        "sessions": records,
+    }))
+}
+
+pub fn run_export(
+    sessions: &[SessionInfo],
+    format: ExportFormat,
+    since: Option<&str>,
+    until: Option<&str>,
+    now: u64,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let since = since.map(|s| parse_time(s, now)).transpose()?;
+    let until = until.map(|s| parse_time(s, now)).transpose()?;
+    let records = select(sessions, since, until);
+    Ok(match format {
+        ExportFormat::Json => to_json(&records, now)?,
+        ExportFormat::Csv => to_csv(&records),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::{PendingReason, Status};
+
+    fn session(tty: &str, cwd: &str, started_at: Option<u64>) -> SessionInfo {
+        SessionInfo {
+            pid: 4242,
+            turns: 3,
+            started_at,
+            ..SessionInfo::for_test(tty, cwd, Status::Idle)
+        }
+    }
+
+    #[test]
+    fn test_parse_time() {
+        assert_eq!(parse_time("2h", 10_000), Ok(2_800));
+        assert_eq!(parse_time("90s", 100), Ok(10));
+        assert_eq!(parse_time("7d", 100), Ok(0));
+        assert_eq!(parse_time("1970-01-01T01:00:00Z", 0), Ok(3600));
+        assert!(parse_time("yesterday", 0).is_err());
+        assert!(parse_time("xh", 0).is_err());
+    }
+
+    #[test]
+    fn test_select_by_start_time() {
+        let sessions = vec![
+            session("/dev/ttys001", "/src/api", Some(1_000)),
+            session("/dev/ttys002", "/src/web", Some(5_000)),
+            session("/dev/ttys003", "/src/docs", None),
+        ];
+        assert_eq!(select(&sessions, None, None).len(), 3);
+        let recent = select(&sessions, Some(2_000), None);
+        assert_eq!(recent.len(), 1);
+        assert_eq!(recent[0].project, "web");
+        let early = select(&sessions, None, Some(2_000));
+        assert_eq!(early.len(), 1);
+        assert_eq!(early[0].project, "api");
+    }
+
+    #[test]
+    fn test_to_csv_quotes_fields() {
+        let mut s = session("/dev/ttys001", "/src/api", Some(0));
+        s.status = Status::Pending;
+        s.pending_reason = Some(PendingReason::Question);
+        s.title = Some("Fix \"login\", then deploy".into());
+        let csv = to_csv(&[ExportRecord::from_session(&s)]);
+        let mut lines = csv.lines();
+        assert_eq!(lines.next(), Some(CSV_COLUMNS.join(",").as_str()));
+        assert_eq!(
+            lines.next(),
+            Some(
+                "api,claude,pending,Question,,/dev/ttys001,iterm2,/src/api,4242,\
+                 1970-01-01T00:00:00Z,3,0,\"Fix \"\"login\"\", then deploy\","
+            )
+        );
+        assert_eq!(lines.next(), None);
+    }
+
+    #[test]
+    fn test_to_json() {
+        let records = select(&[session("/dev/ttys001", "/src/api", None)], None, None);
+        let json: serde_json::Value =
+            serde_json::from_str(&to_json(&records, 60).unwrap()).unwrap();
+        assert_eq!(json["exported_at"], "1970-01-01T00:01:00Z");
+        assert_eq!(json["sessions"][0]["project"], "api");
+        assert_eq!(json["sessions"][0]["started_at"], serde_json::Value::Null);
+    }
+}
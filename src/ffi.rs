@@ -0,0 +1,406 @@
+//! C API for native front ends that link the library instead of running the CLI.
+//!
+//! The crate builds as a `cdylib`/`staticlib` exporting the functions below;
+//! `include/claude_bar.h` is generated from this file by cbindgen (see
+//! `cbindgen.toml`) and `include/module.modulemap` lets Swift `import ClaudeBar`.
+//!
+//! Strings and session lists handed out by the library are owned by it and
+//! must be released with the matching `*_free` function. Functions that can
+//! fail return 0 on success or the exit code `claude-bar` would exit with
+//! (see [`crate::error`]), and leave the message for [`claude_bar_last_error`].
+
+use crate::config::Config;
+use crate::error::{self, ClaudeBarError};
+use crate::focus;
+use crate::serve;
+use crate::state::{DisplayResponse, SessionInfo, Status};
+use crate::transcript;
+use std::cell::RefCell;
+use std::ffi::{c_char, CStr, CString};
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// Session status, as in `claude-bar poll`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClaudeBarStatus {
+    Active = 0,
+    Pending = 1,
+    Idle = 2,
+    RateLimited = 3,
+    Unknown = 4,
+}
+
+impl From<Status> for ClaudeBarStatus {
+    fn from(status: Status) -> Self {
+        match status {
+            Status::Active => ClaudeBarStatus::Active,
+            Status::Pending => ClaudeBarStatus::Pending,
+            Status::Idle => ClaudeBarStatus::Idle,
+            Status::RateLimited => ClaudeBarStatus::RateLimited,
+            Status::Unknown => ClaudeBarStatus::Unknown,
+        }
+    }
+}
+
+/// One polled session. Strings are NUL-terminated UTF-8 owned by the list the
+/// session came from; optional ones are NULL when absent.
+#[repr(C)]
+#[derive(Debug)]
+pub struct ClaudeBarSession {
+    /// Session key to pass to `claude_bar_focus`: the TTY, or
+    /// `host:/dev/pts/1` for remote sessions
+    pub key: *const c_char,
+    pub tty: *const c_char,
+    pub pid: u32,
+    pub cwd: *const c_char,
+    /// Last component of `cwd`
+    pub project: *const c_char,
+    /// `claude` or `codex`
+    pub provider: *const c_char,
+    /// `iterm2`, `alacritty`, `cursor`, `unknown`, or `jetbrains:<ide>` with
+    /// the IDE's slug (`jetbrains:rustrover`, `jetbrains:android-studio`, ...)
+    pub terminal: *const c_char,
+    pub status: ClaudeBarStatus,
+    /// Tool a pending session is waiting on
+    pub pending_tool: *const c_char,
+    pub title: *const c_char,
+    /// Remote host label for sessions polled over SSH or from a peer
+    pub remote_host: *const c_char,
+    /// Unix time the session started, 0 when unknown
+    pub started_at: u64,
+    pub queued: u32,
+    pub turns: u32,
+    pub subagents: u32,
+    /// Found through its transcript alone, so there's no terminal to focus
+    pub detached: bool,
+}
+
+/// Sessions returned by [`claude_bar_poll_sessions`]; opaque to C.
+#[derive(Debug)]
+pub struct ClaudeBarSessionList {
+    sessions: Vec<ClaudeBarSession>,
+    /// Backing storage for the session string pointers
+    _strings: Vec<CString>,
+}
+
+impl ClaudeBarSessionList {
+    fn new(sessions: &[SessionInfo]) -> Self {
+        let mut strings = Vec::new();
+        let mut keep = |s: String| {
+            let s = to_cstring(s);
+            let p = s.as_ptr();
+            strings.push(s);
+            p
+        };
+        let sessions = sessions
+            .iter()
+            .map(|s| ClaudeBarSession {
+                key: keep(s.key()),
+                tty: keep(s.tty.clone()),
+                pid: s.pid,
+                cwd: keep(s.cwd.clone()),
+                project: keep(s.project_name()),
+                provider: keep(s.provider.to_string()),
+                terminal: keep(s.terminal.to_string()),
+                status: s.status.into(),
+                pending_tool: s.pending_tool.clone().map_or(ptr::null(), &mut keep),
+                title: s.title.clone().map_or(ptr::null(), &mut keep),
+                remote_host: s
+                    .remote
+                    .as_ref()
+                    .map_or(ptr::null(), |r| keep(r.host.clone())),
+                started_at: s.started_at.unwrap_or(0),
+                queued: s.queued,
+                turns: s.turns,
+                subagents: s.subagents,
+                detached: s.detached,
+            })
+            .collect();
+        ClaudeBarSessionList {
+            sessions,
+            _strings: strings,
+        }
+    }
+}
+
+/// `s` with interior NULs dropped, which C can't represent.
+fn to_cstring(s: String) -> CString {
+    CString::new(s).unwrap_or_else(|e| {
+        let mut bytes = e.into_vec();
+        bytes.retain(|&b| b != 0);
+        CString::new(bytes).expect("NULs removed")
+    })
+}
+
+fn set_last_error(message: Option<String>) {
+    LAST_ERROR.with(|e| *e.borrow_mut() = message.map(to_cstring));
+}
+
+/// Borrow a C string argument; NULL and invalid UTF-8 read as `None`.
+///
+/// # Safety
+/// `s` must be NULL or point to a NUL-terminated string valid for `'a`.
+unsafe fn str_arg<'a>(s: *const c_char) -> Option<&'a str> {
+    if s.is_null() {
+        return None;
+    }
+    CStr::from_ptr(s).to_str().ok()
+}
+
+/// Run `f`, recording its error (or a panic, which must not unwind into C)
+/// for [`claude_bar_last_error`]; returns 0 or the error's exit code.
+fn run(f: impl FnOnce() -> Result<(), Box<dyn std::error::Error>>) -> i32 {
+    match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(())) => {
+            set_last_error(None);
+            0
+        }
+        Ok(Err(e)) => {
+            let out = error::classify(e.as_ref());
+            set_last_error(Some(out.message));
+            out.exit_code
+        }
+        Err(_) => {
+            set_last_error(Some("claude-bar panicked".to_string()));
+            70
+        }
+    }
+}
+
+/// Poll every session with the settings from the config file, as
+/// `claude-bar poll` does. Never returns NULL; free the list with
+/// `claude_bar_session_list_free`.
+#[no_mangle]
+pub extern "C" fn claude_bar_poll_sessions() -> *mut ClaudeBarSessionList {
+    let sessions = panic::catch_unwind(serve::poll_sessions).unwrap_or_default();
+    Box::into_raw(Box::new(ClaudeBarSessionList::new(&sessions)))
+}
+
+/// Number of sessions in `list`.
+///
+/// # Safety
+/// `list` must be NULL or a list from `claude_bar_poll_sessions` not yet freed.
+#[no_mangle]
+pub unsafe extern "C" fn claude_bar_session_count(list: *const ClaudeBarSessionList) -> usize {
+    list.as_ref().map_or(0, |l| l.sessions.len())
+}
+
+/// Session `index` of `list`, or NULL past the end. The pointer stays valid
+/// until the list is freed.
+///
+/// # Safety
+/// `list` must be NULL or a list from `claude_bar_poll_sessions` not yet freed.
+#[no_mangle]
+pub unsafe extern "C" fn claude_bar_session_at(
+    list: *const ClaudeBarSessionList,
+    index: usize,
+) -> *const ClaudeBarSession {
+    list.as_ref()
+        .and_then(|l| l.sessions.get(index))
+        .map_or(ptr::null(), |s| s as *const ClaudeBarSession)
+}
+
+/// Free a list from `claude_bar_poll_sessions`. NULL is ignored.
+///
+/// # Safety
+/// `list` must be NULL or a list from `claude_bar_poll_sessions` not yet freed.
+#[no_mangle]
+pub unsafe extern "C" fn claude_bar_session_list_free(list: *mut ClaudeBarSessionList) {
+    if !list.is_null() {
+        drop(Box::from_raw(list));
+    }
+}
+
+/// The full poll as `claude-bar poll --format json` prints it, for fields the
+/// session struct leaves out (nested agents, containers, env). Free with
+/// `claude_bar_string_free`.
+#[no_mangle]
+pub extern "C" fn claude_bar_poll_sessions_json() -> *mut c_char {
+    let sessions = panic::catch_unwind(serve::poll_sessions).unwrap_or_default();
+    let json = serde_json::to_string(&DisplayResponse::new(sessions)).unwrap_or_default();
+    to_cstring(json).into_raw()
+}
+
+/// Free a string returned by the library. NULL is ignored.
+///
+/// # Safety
+/// `s` must be NULL or a string from this library not yet freed.
+#[no_mangle]
+pub unsafe extern "C" fn claude_bar_string_free(s: *mut c_char) {
+    if !s.is_null() {
+        drop(CString::from_raw(s));
+    }
+}
+
+/// Status of the session writing `transcript_path` (NULL when it has no
+/// transcript yet), with the thresholds from the config file.
+///
+/// # Safety
+/// `transcript_path` must be NULL or a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn claude_bar_determine_status(
+    transcript_path: *const c_char,
+) -> ClaudeBarStatus {
+    let path = str_arg(transcript_path);
+    if path.is_none() && !transcript_path.is_null() {
+        return ClaudeBarStatus::Unknown;
+    }
+    panic::catch_unwind(|| transcript::determine_status(path, &Config::load().thresholds))
+        .map_or(ClaudeBarStatus::Unknown, ClaudeBarStatus::from)
+}
+
+/// Raise the terminal tab hosting the session with key or TTY `target`
+/// (`/dev/ttys003`, `ttys003`, `host:/dev/pts/1`). Returns 0, or an exit code
+/// with the reason in `claude_bar_last_error`.
+///
+/// # Safety
+/// `target` must be NULL or a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn claude_bar_focus(target: *const c_char) -> i32 {
+    let target = str_arg(target);
+    run(|| {
+        let target = target.ok_or_else(|| ClaudeBarError::NoSession("no session given".into()))?;
+        let sessions = serve::poll_sessions();
+        let session = focus::find_target(&sessions, target)
+            .ok_or_else(|| ClaudeBarError::NoSession(format!("no live session on {}", target)))?;
+        focus::focus_session(session)
+    })
+}
+
+/// Message for the last failed call on this thread, or NULL after a call that
+/// succeeded. Valid until the next library call on the same thread.
+#[no_mangle]
+pub extern "C" fn claude_bar_last_error() -> *const c_char {
+    LAST_ERROR.with(|e| e.borrow().as_ref().map_or(ptr::null(), |s| s.as_ptr()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::Remote;
+    use std::io::Write;
+
+    fn session(tty: &str) -> SessionInfo {
+        SessionInfo {
+            pid: 4242,
+            pending_tool: Some("Bash".to_string()),
+            queued: 1,
+            turns: 3,
+            started_at: Some(1_700_000_000),
+            subagents: 2,
+            ..SessionInfo::for_test(tty, "/Users/me/api", Status::Pending)
+        }
+    }
+
+    fn string(p: *const c_char) -> Option<String> {
+        (!p.is_null()).then(|| unsafe { CStr::from_ptr(p) }.to_str().unwrap().to_string())
+    }
+
+    #[test]
+    fn test_session_list_accessors() {
+        let mut remote = session("/dev/pts/1");
+        remote.status = Status::Idle;
+        remote.pending_tool = None;
+        remote.title = Some("fix\0 tests".to_string());
+        remote.remote = Some(Remote {
+            host: "devbox".to_string(),
+            local_tty: None,
+        });
+        let list = Box::into_raw(Box::new(ClaudeBarSessionList::new(&[
+            session("/dev/ttys003"),
+            remote,
+        ])));
+
+        unsafe {
+            assert_eq!(claude_bar_session_count(list), 2);
+            let local = &*claude_bar_session_at(list, 0);
+            assert_eq!(string(local.key).as_deref(), Some("/dev/ttys003"));
+            assert_eq!(string(local.project).as_deref(), Some("api"));
+            assert_eq!(string(local.terminal).as_deref(), Some("iterm2"));
+            assert_eq!(string(local.pending_tool).as_deref(), Some("Bash"));
+            assert_eq!(local.status, ClaudeBarStatus::Pending);
+            assert_eq!((local.pid, local.started_at), (4242, 1_700_000_000));
+            assert_eq!((local.queued, local.turns, local.subagents), (1, 3, 2));
+            assert!(local.title.is_null() && local.remote_host.is_null());
+
+            let remote = &*claude_bar_session_at(list, 1);
+            assert_eq!(string(remote.key).as_deref(), Some("devbox:/dev/pts/1"));
+            assert_eq!(string(remote.remote_host).as_deref(), Some("devbox"));
+            assert_eq!(string(remote.title).as_deref(), Some("fix tests"));
+            assert_eq!(remote.status, ClaudeBarStatus::Idle);
+            assert!(remote.pending_tool.is_null());
+
+            assert!(claude_bar_session_at(list, 2).is_null());
+            assert!(claude_bar_session_at(ptr::null(), 0).is_null());
+            assert_eq!(claude_bar_session_count(ptr::null()), 0);
+            claude_bar_session_list_free(list);
+            claude_bar_session_list_free(ptr::null_mut());
+        }
+    }
+
+    #[test]
+    fn test_determine_status() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("t.jsonl");
+        let mut f = std::fs::File::create(&path).unwrap();
+        writeln!(
+            f,
+            r#"{{"type":"assistant","message":{{"content":[{{"type":"tool_use","id":"t1","name":"Bash","input":{{}}}}],"stop_reason":"tool_use"}}}}"#
+        )
+        .unwrap();
+        let path = CString::new(path.to_str().unwrap()).unwrap();
+        let expected = transcript::determine_status(path.to_str().ok(), &Config::load().thresholds);
+        unsafe {
+            assert_eq!(
+                claude_bar_determine_status(path.as_ptr()),
+                ClaudeBarStatus::from(expected)
+            );
+            let bad = CString::new(vec![0xff, 0xfe]).unwrap();
+            assert_eq!(
+                claude_bar_determine_status(bad.as_ptr()),
+                ClaudeBarStatus::Unknown
+            );
+        }
+    }
+
+    #[test]
+    fn test_errors_are_reported_through_last_error() {
+        assert_eq!(unsafe { claude_bar_focus(ptr::null()) }, 67);
+        assert_eq!(
+            string(claude_bar_last_error()).as_deref(),
+            Some("no session given")
+        );
+        assert_eq!(run(|| Ok(())), 0);
+        assert!(claude_bar_last_error().is_null());
+        assert_eq!(run(|| panic!("boom")), 70);
+        assert_eq!(
+            string(claude_bar_last_error()).as_deref(),
+            Some("claude-bar panicked")
+        );
+    }
+
+    #[test]
+    fn test_header_declares_every_export() {
+        let header = include_str!("../include/claude_bar.h");
+        let source = include_str!("ffi.rs");
+        let exports: Vec<&str> = source
+            .split("extern \"C\" fn ")
+            .skip(1)
+            .filter_map(|rest| rest.split('(').next())
+            .filter(|name| name.starts_with("claude_bar_"))
+            .collect();
+        assert!(exports.len() >= 9, "{:?}", exports);
+        for name in exports {
+            assert!(
+                header.contains(&format!("{}(", name)),
+                "{} missing from include/claude_bar.h; regenerate it with cbindgen",
+                name
+            );
+        }
+    }
+}
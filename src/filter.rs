@@ -0,0 +1,280 @@
+//! Session filters and field selection shared by `poll`, `export` and the
+//! socket's `sessions` request.
+//!
+//! `--status pending,active --project 'api-*' --terminal iterm2` on the CLI is
+//! `{"cmd":"sessions","status":["pending","active"],"project":"api-*","terminal":["iterm2"]}`
+//! on the socket; `--fields tty,status` and `"fields":["tty","status"]` cut each
+//! session down to those keys.
+
+use crate::icon;
+use crate::state::{SessionInfo, Status, Terminal};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+/// Which sessions to keep. Empty criteria match everything; given ones must
+/// all match.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, clap::Args)]
+pub struct SessionFilter {
+    /// Only sessions with one of these statuses (e.g. pending,active)
+    #[arg(long, value_delimiter = ',', value_parser = icon::parse_status)]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub status: Vec<Status>,
+    /// Only sessions whose project name matches this glob (`*` and `?`, case-insensitive)
+    #[arg(long, value_name = "GLOB")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub project: Option<String>,
+    /// Only sessions in one of these terminals (iterm2, alacritty, cursor,
+    /// jetbrains, or one IDE such as jetbrains:rustrover)
+    #[arg(long, value_delimiter = ',', value_parser = parse_terminal)]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub terminal: Vec<String>,
+}
+
+impl SessionFilter {
+    pub fn matches(&self, session: &SessionInfo) -> bool {
+        (self.status.is_empty() || self.status.contains(&session.status))
+            && (self.terminal.is_empty()
+                || self
+                    .terminal
+                    .iter()
+                    .any(|t| *t == session.terminal.kind() || *t == session.terminal.to_string()))
+            && self
+                .project
+                .as_deref()
+                .is_none_or(|glob| glob_matches(glob, &session.project_name()))
+    }
+
+    /// `sessions` without the ones that don't match.
+    pub fn apply(&self, mut sessions: Vec<SessionInfo>) -> Vec<SessionInfo> {
+        sessions.retain(|s| self.matches(s));
+        sessions
+    }
+}
+
+/// A `--terminal` value: a terminal's display name, or `jetbrains` for any IDE.
+pub fn parse_terminal(s: &str) -> Result<String, String> {
+    match s.trim() {
+        "jetbrains" => Ok("jetbrains".to_string()),
+        other => Terminal::parse(other).map(|t| t.to_string()).ok_or_else(|| {
+            format!(
+                "unknown terminal '{}' (expected iterm2, alacritty, cursor, jetbrains, jetbrains:<ide> or unknown)",
+                other
+            )
+        }),
+    }
+}
+
+/// Case-insensitive glob match where `*` is any run of characters and `?` any one.
+pub fn glob_matches(glob: &str, text: &str) -> bool {
+    let glob: Vec<char> = glob.to_lowercase().chars().collect();
+    let text: Vec<char> = text.to_lowercase().chars().collect();
+    let (mut g, mut t) = (0, 0);
+    // Where the last `*` was, and how much text it has swallowed so far
+    let mut star: Option<(usize, usize)> = None;
+    while t < text.len() {
+        match glob.get(g) {
+            Some('*') => {
+                star = Some((g, t));
+                g += 1;
+            }
+            Some(&c) if c == '?' || c == text[t] => {
+                g += 1;
+                t += 1;
+            }
+            _ => match star {
+                Some((star_g, star_t)) => {
+                    g = star_g + 1;
+                    t = star_t + 1;
+                    star = Some((star_g, star_t + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    glob[g..].iter().all(|&c| c == '*')
+}
+
+/// Names `--fields` accepts: every `SessionInfo` key, plus `project`.
+pub fn field_names() -> Vec<String> {
+    let schema = serde_json::to_value(schemars::schema_for!(SessionInfo)).unwrap_or_default();
+    let mut names: Vec<String> = schema["properties"]
+        .as_object()
+        .map(|props| props.keys().cloned().collect())
+        .unwrap_or_default();
+    names.push("project".to_string());
+    names.sort();
+    names
+}
+
+/// `poll --fields` output: the `poll` envelope around the cut-down sessions.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SelectedResponse {
+    pub version: u32,
+    pub sessions: Vec<Value>,
+}
+
+/// Each session as an object with just `fields`. Fields a session leaves out
+/// (an absent `title`) come out as null.
+pub fn select_fields(sessions: &[SessionInfo], fields: &[String]) -> Result<Vec<Value>, String> {
+    let known = field_names();
+    if let Some(unknown) = fields.iter().find(|f| !known.contains(f)) {
+        return Err(format!(
+            "unknown field '{}' (expected one of: {})",
+            unknown,
+            known.join(", ")
+        ));
+    }
+    Ok(sessions
+        .iter()
+        .map(|session| {
+            let full = serde_json::to_value(session).unwrap_or_default();
+            let picked: Map<String, Value> = fields
+                .iter()
+                .map(|field| {
+                    let value = match field.as_str() {
+                        "project" => Value::String(session.project_name()),
+                        _ => full.get(field).cloned().unwrap_or(Value::Null),
+                    };
+                    (field.clone(), value)
+                })
+                .collect();
+            Value::Object(picked)
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::JetBrainsIde;
+
+    fn session(tty: &str, cwd: &str, status: Status, terminal: Terminal) -> SessionInfo {
+        SessionInfo {
+            terminal,
+            ..SessionInfo::for_test(tty, cwd, status)
+        }
+    }
+
+    #[test]
+    fn test_glob_matches() {
+        assert!(glob_matches("api-*", "api-server"));
+        assert!(glob_matches("API-*", "api-"));
+        assert!(glob_matches("*web*", "my-webapp"));
+        assert!(glob_matches("a?c", "abc"));
+        assert!(glob_matches("*", ""));
+        assert!(glob_matches("*-*-x", "a-b-c-x"));
+        assert!(!glob_matches("api-*", "my-api"));
+        assert!(!glob_matches("a?c", "ac"));
+        assert!(!glob_matches("web", "webapp"));
+    }
+
+    #[test]
+    fn test_filter_matches() {
+        let sessions = vec![
+            session(
+                "/dev/ttys000",
+                "/src/api-server",
+                Status::Pending,
+                Terminal::ITerm2,
+            ),
+            session(
+                "/dev/ttys001",
+                "/src/api-web",
+                Status::Idle,
+                Terminal::ITerm2,
+            ),
+            session(
+                "/dev/ttys002",
+                "/src/docs",
+                Status::Active,
+                Terminal::Alacritty,
+            ),
+        ];
+        let ttys = |filter: &SessionFilter| -> Vec<String> {
+            filter
+                .apply(sessions.clone())
+                .into_iter()
+                .map(|s| s.tty)
+                .collect()
+        };
+        assert_eq!(ttys(&SessionFilter::default()).len(), 3);
+        let filter = SessionFilter {
+            status: vec![Status::Pending, Status::Active],
+            ..Default::default()
+        };
+        assert_eq!(ttys(&filter), vec!["/dev/ttys000", "/dev/ttys002"]);
+        let filter = SessionFilter {
+            status: vec![Status::Pending, Status::Active],
+            project: Some("api-*".into()),
+            ..Default::default()
+        };
+        assert_eq!(ttys(&filter), vec!["/dev/ttys000"]);
+        let filter = SessionFilter {
+            terminal: vec!["alacritty".into()],
+            ..Default::default()
+        };
+        assert_eq!(ttys(&filter), vec!["/dev/ttys002"]);
+
+        let mut sessions = sessions.clone();
+        sessions[0].terminal = Terminal::JetBrains(JetBrainsIde::RustRover);
+        sessions[1].terminal = Terminal::JetBrains(JetBrainsIde::PyCharm);
+        let ttys = |terminal: &str| -> Vec<String> {
+            let filter = SessionFilter {
+                terminal: vec![terminal.into()],
+                ..Default::default()
+            };
+            filter
+                .apply(sessions.clone())
+                .into_iter()
+                .map(|s| s.tty)
+                .collect()
+        };
+        assert_eq!(ttys("jetbrains"), vec!["/dev/ttys000", "/dev/ttys001"]);
+        assert_eq!(ttys("jetbrains:pycharm"), vec!["/dev/ttys001"]);
+    }
+
+    #[test]
+    fn test_filter_wire_format() {
+        let filter: SessionFilter = serde_json::from_str(
+            r#"{"status":["pending","rate_limited"],"project":"api-*","terminal":["iterm2"]}"#,
+        )
+        .unwrap();
+        assert_eq!(filter.status, vec![Status::Pending, Status::RateLimited]);
+        assert_eq!(filter.terminal, vec!["iterm2"]);
+        assert_eq!(
+            serde_json::to_string(&SessionFilter::default()).unwrap(),
+            "{}"
+        );
+        assert!(parse_terminal("kitty").is_err());
+        assert!(parse_terminal("jetbrains:vim").is_err());
+        assert_eq!(parse_terminal(" jetbrains ").unwrap(), "jetbrains");
+        assert_eq!(
+            parse_terminal("jetbrains:android-studio").unwrap(),
+            "jetbrains:android-studio"
+        );
+    }
+
+    #[test]
+    fn test_select_fields() {
+        let sessions = vec![session(
+            "/dev/ttys000",
+            "/src/api",
+            Status::Pending,
+            Terminal::ITerm2,
+        )];
+        let fields: Vec<String> = ["status", "tty", "project", "title"]
+            .iter()
+            .map(|f| f.to_string())
+            .collect();
+        let picked = select_fields(&sessions, &fields).unwrap();
+        // In the order asked for
+        assert_eq!(
+            serde_json::to_string(&picked).unwrap(),
+            r#"[{"status":"pending","tty":"/dev/ttys000","project":"api","title":null}]"#
+        );
+
+        let err = select_fields(&sessions, &["colour".to_string()]).unwrap_err();
+        assert!(err.starts_with("unknown field 'colour' (expected one of: "));
+        assert!(err.contains("cwd") && err.contains("pending_tool"));
+    }
+}
@@ -1,63 +1,819 @@
+use crate::container;
+use crate::error::ClaudeBarError;
+use crate::focusback;
+use crate::logging;
+use crate::permissions;
+use crate::process;
+use crate::screen;
+use crate::state::{JetBrainsIde, SessionInfo, Terminal};
+use crate::terminal;
+use clap::builder::PossibleValuesParser;
 use std::process::Command;
+use std::thread;
+use std::time::Duration;
 
-/// Focus the iTerm2 window/tab that owns the given TTY.
-pub fn focus_iterm2(tty: &str) -> Result<(), Box<dyn std::error::Error>> {
-    let script = format!(
+/// Quote a string as an AppleScript string literal.
+pub fn applescript_string(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Focus the iTerm2 window/tab holding the session with unique ID
+/// `session_id` when given (immune to the TTY having been reused by another
+/// tab), else the one that owns the given TTY. Fails if there's no such session.
+///
+/// `select`/`set index` alone don't switch Spaces when the window lives on
+/// another Space or is full screen, so the app is then brought forward with
+/// `open -a` (which jumps to its front window's Space) and the window raised
+/// through System Events. The raise needs Accessibility access and is best effort.
+pub fn focus_iterm2(tty: &str, session_id: Option<&str>) -> Result<(), Box<dyn std::error::Error>> {
+    let script = match session_id {
+        Some(id) => iterm2_select_script("id", id),
+        None => iterm2_select_script("tty", tty),
+    };
+    let window = run_applescript(&script, "iTerm2")?;
+    let _ = process::run(Command::new("open").args(["-a", "iTerm"]));
+    if let Err(e) = run_applescript(&iterm2_raise_script(window.trim()), "System Events") {
+        logging::warn("focus", &format!("couldn't raise iTerm2 window: {}", e));
+    }
+    Ok(())
+}
+
+/// Select the tab holding the session whose `property` (`tty` or `id`) is
+/// `value`, make its window iTerm2's front window and return the window's title.
+fn iterm2_select_script(property: &str, value: &str) -> String {
+    format!(
         r#"tell application "iTerm2"
     activate
     repeat with w in windows
         tell w
             repeat with t in tabs
                 repeat with s in sessions of t
-                    if tty of s is "{tty}" then
+                    if {property} of s is {value} then
                         select t
                         set index of w to 1
-                        return
+                        return name of w
                     end if
                 end repeat
             end repeat
         end tell
     end repeat
-end tell"#
-    );
+end tell
+error "no iTerm2 session with {property} " & {value}"#,
+        value = applescript_string(value)
+    )
+}
 
-    Command::new("osascript").arg("-e").arg(&script).output()?;
-    Ok(())
+/// Raise the iTerm2 window titled `name` (or the front one) above other apps.
+fn iterm2_raise_script(name: &str) -> String {
+    format!(
+        r#"tell application "System Events"
+    tell process "iTerm2"
+        set frontmost to true
+        repeat with w in windows
+            if name of w is {name} then
+                perform action "AXRaise" of w
+                return
+            end if
+        end repeat
+        perform action "AXRaise" of window 1
+    end tell
+end tell"#,
+        name = applescript_string(name)
+    )
 }
 
-/// Focus the Alacritty window whose title contains the given CWD.
+/// Focus the Alacritty window hosting `tty`.
+///
+/// The Alacritty process that owns the TTY is found through the process tree
+/// and its window raised, so custom titles and shared directory names don't
+/// matter. When that process has several windows, or no owner is found, the
+/// window whose title contains the CWD's directory name is used instead.
 /// Uses System Events accessibility to raise the window.
-pub fn focus_alacritty(cwd: &str) -> Result<(), Box<dyn std::error::Error>> {
+pub fn focus_alacritty(tty: &str, cwd: &str) -> Result<(), Box<dyn std::error::Error>> {
     let dir_name = std::path::Path::new(cwd)
         .file_name()
         .map(|n| n.to_string_lossy().to_string())
         .unwrap_or_default();
 
-    let script = format!(
+    let owner = if tty.is_empty() {
+        None
+    } else {
+        let alacritty = process::find_pids_by_name("alacritty");
+        process::owner_of_tty(&process::process_tree(), tty, &alacritty)
+    };
+    let script = match owner {
+        Some(pid) => alacritty_pid_script(pid, &dir_name, true),
+        None => alacritty_title_script(&dir_name),
+    };
+    run_applescript(&script, "System Events").map(|_| ())
+}
+
+/// Raise the Alacritty window hosting `tty` before typing into it. Unlike
+/// [`focus_alacritty`], there is no guessing: the process owning the TTY must
+/// be found, and when it has several windows one must be titled after the
+/// CWD's directory name. Returns the pid of that Alacritty process.
+pub fn raise_alacritty_window(tty: &str, cwd: &str) -> Result<u32, Box<dyn std::error::Error>> {
+    let dir_name = std::path::Path::new(cwd)
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let alacritty = process::find_pids_by_name("alacritty");
+    let pid = process::owner_of_tty(&process::process_tree(), tty, &alacritty)
+        .ok_or_else(|| format!("No Alacritty process owns {}", tty))?;
+    run_applescript(
+        &alacritty_pid_script(pid, &dir_name, false),
+        "System Events",
+    )?;
+    Ok(pid)
+}
+
+/// Raise the window of one Alacritty process: its only window, else the one
+/// titled after `dir_name`, else (with `fallback`) its first.
+fn alacritty_pid_script(pid: u32, dir_name: &str, fallback: bool) -> String {
+    let otherwise = if fallback {
+        "perform action \"AXRaise\" of window 1".to_string()
+    } else {
+        format!(
+            "error \"no Alacritty window titled \" & {}",
+            applescript_string(dir_name)
+        )
+    };
+    format!(
+        r#"tell application "System Events"
+    set p to first process whose unix id is {pid}
+    set frontmost of p to true
+    tell p
+        if (count of windows) is 1 then
+            perform action "AXRaise" of window 1
+            return
+        end if
+        repeat with w in windows
+            if name of w contains {dir_name} then
+                perform action "AXRaise" of w
+                return
+            end if
+        end repeat
+        {otherwise}
+    end tell
+end tell"#,
+        dir_name = applescript_string(dir_name)
+    )
+}
+
+/// Raise the Alacritty window whose title contains `dir_name`.
+fn alacritty_title_script(dir_name: &str) -> String {
+    format!(
         r#"tell application "Alacritty" to activate
 tell application "System Events"
     tell process "Alacritty"
         set frontmost to true
         repeat with w in windows
-            if name of w contains "{dir_name}" then
+            if name of w contains {dir_name} then
                 perform action "AXRaise" of w
                 return
             end if
         end repeat
     end tell
-end tell"#
-    );
+end tell
+error "no Alacritty window titled " & {dir_name}"#,
+        dir_name = applescript_string(dir_name)
+    )
+}
+
+/// Focus the JetBrains IDE hosting a session and raise the project window
+/// whose title contains the CWD's directory name (IDE titles lead with the
+/// project name), or its front window when none does. The terminal tool
+/// window itself isn't scriptable, so the tab isn't selected.
+pub fn focus_jetbrains(ide: JetBrainsIde, cwd: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let project = std::path::Path::new(cwd)
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    run_applescript(&jetbrains_raise_script(ide, &project), "System Events")?;
+    Ok(())
+}
+
+fn jetbrains_raise_script(ide: JetBrainsIde, project: &str) -> String {
+    format!(
+        r#"tell application "System Events"
+    set ide to first application process whose bundle identifier starts with {bundle}
+    set frontmost of ide to true
+    tell ide
+        repeat with w in windows
+            if name of w contains {project} then
+                perform action "AXRaise" of w
+                return
+            end if
+        end repeat
+        perform action "AXRaise" of window 1
+    end tell
+end tell"#,
+        bundle = applescript_string(ide.bundle_id_prefix()),
+        project = applescript_string(project)
+    )
+}
 
-    Command::new("osascript").arg("-e").arg(&script).output()?;
+/// Cursor's macOS bundle identifier.
+pub(crate) const CURSOR_BUNDLE_ID: &str = "com.todesktop.230313mzl4w4u92";
+
+/// Focus Cursor and raise the window whose workspace is the session's
+/// project (its title contains the folder name), or its front window when
+/// none is. As with JetBrains IDEs, the terminal panel isn't selected.
+pub fn focus_cursor(cwd: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let project = std::path::Path::new(cwd)
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let opened = process::run(Command::new("open").args(["-b", CURSOR_BUNDLE_ID]));
+    if !opened.is_some_and(|o| o.status.success()) {
+        return Err("Cursor could not be activated".into());
+    }
+    run_applescript(&cursor_raise_script(&project), "System Events")?;
     Ok(())
 }
 
+fn cursor_raise_script(project: &str) -> String {
+    format!(
+        r#"tell application "System Events"
+    set editor to first application process whose bundle identifier is {bundle}
+    set frontmost of editor to true
+    tell editor
+        repeat with w in windows
+            if name of w contains {project} then
+                perform action "AXRaise" of w
+                return
+            end if
+        end repeat
+        perform action "AXRaise" of window 1
+    end tell
+end tell"#,
+        bundle = applescript_string(CURSOR_BUNDLE_ID),
+        project = applescript_string(project)
+    )
+}
+
+/// Run an AppleScript that talks to `app` and return what it printed, logging
+/// and returning its error output on failure. Missing privacy permissions are
+/// recorded and reported.
+pub(crate) fn run_applescript(
+    script: &str,
+    app: &str,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let output = Command::new("osascript").arg("-e").arg(script).output()?;
+    permissions::check_osascript(app, &output);
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        logging::error("applescript", &stderr);
+        return Err(match permissions::classify_error(&stderr) {
+            Some(permission) => ClaudeBarError::AppleScriptDenied {
+                app: app.to_string(),
+                permission,
+                detail: stderr,
+            },
+            None => ClaudeBarError::AppleScript(stderr),
+        }
+        .into());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+/// `focus --terminal` values: every terminal's display name from `poll`, so
+/// shell completion can offer them.
+pub fn terminal_parser() -> PossibleValuesParser {
+    PossibleValuesParser::new(Terminal::all().iter().map(|t| t.to_string()))
+}
+
+/// Pause between launching a terminal app and the second focus attempt.
+const FOCUS_RETRY_DELAY: Duration = Duration::from_millis(700);
+
 /// Focus the terminal window for the given session.
-pub fn run_focus(terminal: &str, tty: &str, cwd: &str) -> Result<(), Box<dyn std::error::Error>> {
-    match terminal {
-        "iterm2" => focus_iterm2(tty),
-        "alacritty" => focus_alacritty(cwd),
-        "unknown" => Ok(()),
-        other => Err(format!("Unknown terminal: {}", other).into()),
+///
+/// A failed attempt is retried once after launching/activating the terminal app
+/// (it may not have been running). If that fails too, a notification tells the
+/// user and the error is returned so the CLI exits non-zero.
+pub fn run_focus(
+    terminal: &str,
+    tty: &str,
+    cwd: &str,
+    terminal_id: Option<&str>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let terminal =
+        Terminal::parse(terminal).ok_or_else(|| format!("Unknown terminal: {}", terminal))?;
+    let app = match terminal {
+        Terminal::ITerm2 => "iTerm",
+        Terminal::Alacritty => "Alacritty",
+        Terminal::JetBrains(ide) => ide.app_name(),
+        Terminal::Cursor => "Cursor",
+        Terminal::Unknown => return Ok(()),
+    };
+    focusback::record_frontmost();
+    let attempt = || match terminal {
+        Terminal::ITerm2 => focus_iterm2(tty, terminal_id),
+        Terminal::JetBrains(ide) => focus_jetbrains(ide, cwd),
+        Terminal::Cursor => focus_cursor(cwd),
+        _ => focus_alacritty(tty, cwd),
+    };
+
+    let result = retry_once(attempt, || {
+        logging::info(
+            "focus",
+            &format!("retrying {} after activating {}", tty, app),
+        );
+        let _ = Command::new("open").args(["-a", app]).output();
+        thread::sleep(FOCUS_RETRY_DELAY);
+    });
+    if let Err(e) = &result {
+        let message = focus_failure_message(tty);
+        logging::error("focus", &format!("{}: {}", message, e));
+        notify(&message);
+    }
+    result
+}
+
+/// Run `attempt`; if it fails, run `recover` and try exactly once more.
+fn retry_once<T, E>(attempt: impl Fn() -> Result<T, E>, recover: impl FnOnce()) -> Result<T, E> {
+    attempt().or_else(|_| {
+        recover();
+        attempt()
+    })
+}
+
+/// Notification text for a focus that failed twice.
+pub fn focus_failure_message(tty: &str) -> String {
+    format!(
+        "Couldn't focus {} — session may have closed",
+        tty.trim_start_matches("/dev/")
+    )
+}
+
+/// Post a macOS notification (best effort).
+pub fn notify(message: &str) {
+    let script = format!(
+        "display notification {} with title \"Claude Bar\"",
+        applescript_string(message)
+    );
+    let _ = Command::new("osascript").arg("-e").arg(script).output();
+}
+
+/// The session `target` names. Sessions without a local TTY are addressed by
+/// key (`host:/dev/pts/1`, `container:3f2a9c1e`, `transcript:9b1d04aa`), local
+/// ones by TTY with or without the `/dev/` prefix.
+pub fn find_target<'a>(sessions: &'a [SessionInfo], target: &str) -> Option<&'a SessionInfo> {
+    let keyed = sessions
+        .iter()
+        .find(|s| (s.remote.is_some() || s.container.is_some() || s.detached) && s.key() == target);
+    let tty = if target.starts_with("/dev/") {
+        target.to_string()
+    } else {
+        format!("/dev/{}", target)
+    };
+    keyed.or_else(|| sessions.iter().find(|s| s.remote.is_none() && s.tty == tty))
+}
+
+/// Focus the terminal window hosting a polled session (the SSH tab for remote ones).
+pub fn focus_session(session: &SessionInfo) -> Result<(), Box<dyn std::error::Error>> {
+    if let Some(container) = &session.container {
+        focusback::record_frontmost();
+        return container::focus(container);
+    }
+    let tty = session.focus_tty().ok_or_else(|| match &session.remote {
+        Some(remote) => format!("No local ssh tab connected to {}", remote.host),
+        None if session.detached => {
+            "Session was found through its transcript alone; it has no terminal to focus"
+                .to_string()
+        }
+        None => "Session has no TTY".to_string(),
+    })?;
+    // A screen window's PTY belongs to the screen server, not a terminal app
+    if session.remote.is_none() && session.terminal == Terminal::Unknown {
+        if let Some(window) = screen::window_for_pid(session.pid) {
+            return screen::focus(&window, &session.cwd);
+        }
+    }
+    if cfg!(target_os = "linux") && session.remote.is_none() {
+        return focus_window_for_pid(session.pid);
+    }
+    // A tmux pane's TTY isn't a tab; the attached client's is
+    let client_tty = match session.terminal {
+        Terminal::ITerm2 | Terminal::Alacritty if session.remote.is_none() => {
+            terminal::tmux_pane_clients().remove(tty)
+        }
+        _ => None,
+    };
+    run_focus(
+        &session.terminal.to_string(),
+        client_tty.as_deref().unwrap_or(tty),
+        &session.cwd,
+        session.terminal_id.as_deref(),
+    )
+}
+
+/// Raise the window of the terminal hosting `pid` with wmctrl (X11, or
+/// XWayland on Wayland desktops). The session process itself owns no window,
+/// so its ancestors are searched for the nearest one that does.
+pub fn focus_window_for_pid(pid: u32) -> Result<(), Box<dyn std::error::Error>> {
+    let output = Command::new("wmctrl")
+        .arg("-lp")
+        .output()
+        .map_err(|e| format!("wmctrl not available: {}", e))?;
+    let windows = parse_wmctrl_windows(&String::from_utf8_lossy(&output.stdout));
+    let id = window_for_pid(&windows, pid, |p| process::backend().parent_pid(p))
+        .ok_or_else(|| format!("No window found for pid {}", pid))?;
+    let status = Command::new("wmctrl").args(["-ia", id]).status()?;
+    if !status.success() {
+        return Err(format!("wmctrl could not activate window {}", id).into());
+    }
+    Ok(())
+}
+
+/// Parse `wmctrl -lp` lines ("0x03a00003  0 4242   host  Title") into
+/// (window id, pid) pairs. Windows without a known pid (0) are skipped.
+fn parse_wmctrl_windows(output: &str) -> Vec<(String, u32)> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let id = fields.next()?;
+            let pid = fields.nth(1)?.parse().ok().filter(|&pid| pid > 0)?;
+            Some((id.to_string(), pid))
+        })
+        .collect()
+}
+
+/// First window owned by `pid` or one of its ancestors.
+fn window_for_pid(
+    windows: &[(String, u32)],
+    pid: u32,
+    parent: impl Fn(u32) -> Option<u32>,
+) -> Option<&str> {
+    let mut current = pid;
+    // Bounded in case of a ppid cycle in a racing process table
+    for _ in 0..64 {
+        if let Some((id, _)) = windows.iter().find(|(_, owner)| *owner == current) {
+            return Some(id);
+        }
+        current = parent(current).filter(|&p| p > 1 && p != current)?;
+    }
+    None
+}
+
+/// Select sessions whose project name matches `name`.
+/// Exact (case-insensitive) matches win; otherwise fall back to substring matches.
+pub fn match_sessions_by_project<'a>(
+    sessions: &'a [SessionInfo],
+    name: &str,
+) -> Vec<&'a SessionInfo> {
+    let needle = name.to_lowercase();
+    let exact: Vec<_> = sessions
+        .iter()
+        .filter(|s| s.project_name().to_lowercase() == needle)
+        .collect();
+    if !exact.is_empty() {
+        return exact;
+    }
+    sessions
+        .iter()
+        .filter(|s| s.project_name().to_lowercase().contains(&needle))
+        .collect()
+}
+
+/// Format one picker row. The leading index is parsed back from the selection.
+fn picker_line(index: usize, session: &SessionInfo) -> String {
+    format!(
+        "{}\t{}\t{}\t{}\t{}",
+        index + 1,
+        session.project_name(),
+        session.status,
+        session.terminal,
+        session.tty.trim_start_matches("/dev/")
+    )
+}
+
+/// Parse the 1-based index from a picker selection line (or a bare number).
+fn parse_picker_selection(selection: &str, count: usize) -> Option<usize> {
+    let first = selection.split_whitespace().next()?;
+    let n: usize = first.parse().ok()?;
+    if n == 0 || n > count {
+        return None;
+    }
+    Some(n - 1)
+}
+
+/// Let the user choose a session interactively.
+/// Uses `fzf` when it is installed, otherwise a numbered prompt on stderr/stdin.
+fn pick_session<'a>(candidates: &[&'a SessionInfo]) -> Option<&'a SessionInfo> {
+    let lines: Vec<String> = candidates
+        .iter()
+        .enumerate()
+        .map(|(i, s)| picker_line(i, s))
+        .collect();
+
+    let selection = pick_with_fzf(&lines).or_else(|| pick_with_prompt(&lines))?;
+    let idx = parse_picker_selection(&selection, candidates.len())?;
+    Some(candidates[idx])
+}
+
+fn pick_with_fzf(lines: &[String]) -> Option<String> {
+    use std::io::Write;
+    use std::process::Stdio;
+
+    let mut child = Command::new("fzf")
+        .args(["--with-nth=2..", "--delimiter=\t", "--prompt=session> "])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .ok()?;
+    {
+        let mut stdin = child.stdin.take()?;
+        stdin.write_all(lines.join("\n").as_bytes()).ok()?;
+    }
+    let output = child.wait_with_output().ok()?;
+    if !output.status.success() {
+        // Cancelled (Esc / Ctrl-C): return an empty selection so we don't fall back to the prompt.
+        return Some(String::new());
+    }
+    Some(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+fn pick_with_prompt(lines: &[String]) -> Option<String> {
+    use std::io::{BufRead, Write};
+
+    let mut stderr = std::io::stderr();
+    for line in lines {
+        writeln!(stderr, "{}", line.replace('\t', "  ")).ok()?;
+    }
+    write!(stderr, "Select session [1-{}]: ", lines.len()).ok()?;
+    stderr.flush().ok()?;
+
+    let mut answer = String::new();
+    std::io::stdin().lock().read_line(&mut answer).ok()?;
+    Some(answer.trim().to_string())
+}
+
+/// Focus a live session chosen by project name and/or interactive picker.
+pub fn run_focus_lookup(
+    project: Option<&str>,
+    pick: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let sessions = crate::serve::poll_sessions();
+    let candidates: Vec<&SessionInfo> = match project {
+        Some(name) => match_sessions_by_project(&sessions, name),
+        None => sessions.iter().collect(),
+    };
+
+    let chosen = match candidates.len() {
+        0 => {
+            return Err(ClaudeBarError::NoSession(match project {
+                Some(name) => format!("No live session matches project '{}'", name),
+                None => "No live sessions".into(),
+            })
+            .into())
+        }
+        1 => candidates[0],
+        _ if pick => pick_session(&candidates).ok_or("No session selected")?,
+        n => {
+            let names: Vec<String> = candidates
+                .iter()
+                .map(|s| format!("{} ({})", s.project_name(), s.tty))
+                .collect();
+            return Err(format!(
+                "{} sessions match: {} (use --pick to choose)",
+                n,
+                names.join(", ")
+            )
+            .into());
+        }
+    };
+
+    focus_session(chosen)
+}
+
+/// `focus --next-pending`: focus the session that has been waiting for input
+/// longest.
+pub fn run_focus_next_pending() -> Result<(), Box<dyn std::error::Error>> {
+    let sessions = crate::serve::poll_sessions();
+    let session = crate::render::longest_pending(&sessions, crate::render::transcript_age)
+        .ok_or_else(|| ClaudeBarError::NoSession("No session needs input".into()))?;
+    focus_session(session)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::Status;
+
+    fn session(tty: &str, cwd: &str) -> SessionInfo {
+        SessionInfo::for_test(tty, cwd, Status::Idle)
+    }
+
+    #[test]
+    fn test_terminal_values_complete() {
+        let mut cmd = clap::Command::new("claude-bar").subcommand(
+            clap::Command::new("focus").arg(
+                clap::Arg::new("terminal")
+                    .long("terminal")
+                    .value_parser(terminal_parser()),
+            ),
+        );
+        let mut script = Vec::new();
+        clap_complete::generate(
+            clap_complete::Shell::Bash,
+            &mut cmd,
+            "claude-bar",
+            &mut script,
+        );
+        let script = String::from_utf8(script).unwrap();
+        assert!(script.contains("iterm2"));
+        assert!(script.contains("jetbrains:android-studio"));
+
+        let matches = cmd
+            .clone()
+            .try_get_matches_from(["claude-bar", "focus", "--terminal", "jetbrains:rustrover"])
+            .unwrap();
+        let (_, focus) = matches.subcommand().unwrap();
+        assert_eq!(
+            focus.get_one::<String>("terminal").map(String::as_str),
+            Some("jetbrains:rustrover")
+        );
+        assert!(cmd
+            .try_get_matches_from_mut(["claude-bar", "focus", "--terminal", "kitty"])
+            .is_err());
+    }
+
+    #[test]
+    fn test_applescript_string_escapes() {
+        assert_eq!(applescript_string("/dev/ttys003"), r#""/dev/ttys003""#);
+        assert_eq!(applescript_string(r#"my "app"\x"#), r#""my \"app\"\\x""#);
+    }
+
+    #[test]
+    fn test_alacritty_scripts() {
+        let by_pid = alacritty_pid_script(4321, "api", true);
+        assert!(by_pid.contains("first process whose unix id is 4321"));
+        assert!(by_pid.contains(r#"name of w contains "api""#));
+        assert!(by_pid.contains(
+            r#"perform action "AXRaise" of window 1
+    end tell"#
+        ));
+        let strict = alacritty_pid_script(4321, "api", false);
+        assert!(!strict.contains(
+            r#"perform action "AXRaise" of window 1
+    end tell"#
+        ));
+        assert!(strict.contains(r#"error "no Alacritty window titled " & "api""#));
+
+        let by_title = alacritty_title_script("my \"app\"");
+        assert!(by_title.contains(r#"tell process "Alacritty""#));
+        assert!(by_title.contains(r#"contains "my \"app\"""#));
+    }
+
+    #[test]
+    fn test_iterm2_scripts() {
+        let select = iterm2_select_script("tty", "/dev/ttys003");
+        assert!(select.contains(r#"if tty of s is "/dev/ttys003" then"#));
+        assert!(select.contains("return name of w"));
+        let by_id = iterm2_select_script("id", "5C2A-77E1");
+        assert!(by_id.contains(r#"if id of s is "5C2A-77E1" then"#));
+        assert!(by_id.contains(r#"error "no iTerm2 session with id " & "5C2A-77E1""#));
+
+        let raise = iterm2_raise_script("~/src/api (-zsh)");
+        assert!(raise.contains(r#"tell process "iTerm2""#));
+        assert!(raise.contains(r#"if name of w is "~/src/api (-zsh)" then"#));
+        assert!(raise.contains(r#"perform action "AXRaise" of window 1"#));
+    }
+
+    #[test]
+    fn test_jetbrains_script() {
+        let script = jetbrains_raise_script(JetBrainsIde::RustRover, "claude-bar");
+        assert!(script.contains(r#"whose bundle identifier starts with "com.jetbrains.rustrover""#));
+        assert!(script.contains(r#"if name of w contains "claude-bar" then"#));
+
+        let script = cursor_raise_script("web");
+        assert!(script.contains(r#"bundle identifier is "com.todesktop.230313mzl4w4u92""#));
+        assert!(script.contains(r#"if name of w contains "web" then"#));
+    }
+
+    #[test]
+    fn test_retry_once() {
+        use std::cell::Cell;
+
+        let calls = Cell::new(0);
+        let recovered = Cell::new(false);
+        let result: Result<u32, &str> = retry_once(
+            || {
+                calls.set(calls.get() + 1);
+                if recovered.get() {
+                    Ok(calls.get())
+                } else {
+                    Err("not running")
+                }
+            },
+            || recovered.set(true),
+        );
+        assert_eq!(result, Ok(2));
+
+        // Gives up after the second failure
+        let calls = Cell::new(0);
+        let result: Result<(), &str> = retry_once(
+            || {
+                calls.set(calls.get() + 1);
+                Err("gone")
+            },
+            || {},
+        );
+        assert_eq!(result, Err("gone"));
+        assert_eq!(calls.get(), 2);
+
+        // Success on the first try skips the recovery
+        let result: Result<(), &str> = retry_once(|| Ok(()), || panic!("not needed"));
+        assert_eq!(result, Ok(()));
+    }
+
+    #[test]
+    fn test_focus_failure_message() {
+        assert_eq!(
+            focus_failure_message("/dev/ttys003"),
+            "Couldn't focus ttys003 — session may have closed"
+        );
+    }
+
+    #[test]
+    fn test_run_focus_unknown_terminal() {
+        assert!(run_focus("unknown", "/dev/ttys003", "/p", None).is_ok());
+        assert!(run_focus("kitty", "/dev/ttys003", "/p", None)
+            .unwrap_err()
+            .to_string()
+            .contains("Unknown terminal"));
+    }
+
+    #[test]
+    fn test_match_exact_project_wins() {
+        let sessions = vec![
+            session("/dev/ttys000", "/src/api"),
+            session("/dev/ttys001", "/src/api-gateway"),
+        ];
+        let matched = match_sessions_by_project(&sessions, "API");
+        assert_eq!(matched.len(), 1);
+        assert_eq!(matched[0].tty, "/dev/ttys000");
+    }
+
+    #[test]
+    fn test_match_substring_fallback() {
+        let sessions = vec![
+            session("/dev/ttys000", "/src/web-app"),
+            session("/dev/ttys001", "/src/mobile-app"),
+            session("/dev/ttys002", "/src/docs"),
+        ];
+        let matched = match_sessions_by_project(&sessions, "app");
+        assert_eq!(matched.len(), 2);
+        assert!(match_sessions_by_project(&sessions, "nope").is_empty());
+    }
+
+    #[test]
+    fn test_picker_line_roundtrip() {
+        let s = session("/dev/ttys003", "/src/api");
+        let line = picker_line(2, &s);
+        assert_eq!(line, "3\tapi\tidle\titerm2\tttys003");
+        assert_eq!(parse_picker_selection(&line, 3), Some(2));
+    }
+
+    #[test]
+    fn test_parse_picker_selection_bounds() {
+        assert_eq!(parse_picker_selection("1", 2), Some(0));
+        assert_eq!(parse_picker_selection("0", 2), None);
+        assert_eq!(parse_picker_selection("3", 2), None);
+        assert_eq!(parse_picker_selection("", 2), None);
+        assert_eq!(parse_picker_selection("abc", 2), None);
+    }
+
+    #[test]
+    fn test_parse_wmctrl_windows() {
+        let output = "0x01e00003  0 4242   laptop Terminal\n\
+                      0x02000007 -1 0      laptop Desktop\n\
+                      0x02400002  1 5150   laptop myapp — zsh\n\
+                      garbage\n";
+        assert_eq!(
+            parse_wmctrl_windows(output),
+            vec![
+                ("0x01e00003".to_string(), 4242),
+                ("0x02400002".to_string(), 5150)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_window_for_pid_walks_ancestors() {
+        let windows = vec![("0x1".to_string(), 100), ("0x2".to_string(), 200)];
+        // 300 (claude) -> 250 (shell) -> 200 (terminal) -> 1
+        let parent = |pid: u32| match pid {
+            300 => Some(250),
+            250 => Some(200),
+            200 => Some(1),
+            _ => None,
+        };
+        assert_eq!(window_for_pid(&windows, 300, parent), Some("0x2"));
+        assert_eq!(window_for_pid(&windows, 100, parent), Some("0x1"));
+        assert_eq!(window_for_pid(&windows, 400, parent), None);
+        assert_eq!(window_for_pid(&windows, 7, |_| Some(7)), None);
     }
 }
@@ -0,0 +1,213 @@
+//! `focus --back`: return to the app that was frontmost before `focus`.
+//!
+//! Every focus first asks System Events for the frontmost app and the title of
+//! its front window and keeps them in `previous_app.json` in the state
+//! directory, where the menu bar app also reads them for its "Return to
+//! Previous App" item. Going back records the app it leaves in turn, so a
+//! second `focus --back` toggles between the two. macOS only: elsewhere nothing
+//! is recorded.
+
+use crate::focus::{applescript_string, run_applescript};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The app (and window) that was frontmost when a session was focused.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PreviousApp {
+    pub name: String,
+    /// Empty for processes without one
+    #[serde(default)]
+    pub bundle_id: String,
+    /// Title of its front window, if it had one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub window: Option<String>,
+}
+
+pub fn default_path() -> PathBuf {
+    crate::paths::state_dir().join("previous_app.json")
+}
+
+/// The recorded app (none if the file is missing or unreadable).
+pub fn read(path: &Path) -> Option<PreviousApp> {
+    fs::read_to_string(path)
+        .ok()
+        .and_then(|c| serde_json::from_str(&c).ok())
+}
+
+fn write(path: &Path, app: &PreviousApp) {
+    if let Some(parent) = path.parent() {
+        let _ = fs::create_dir_all(parent);
+    }
+    if let Ok(json) = serde_json::to_string_pretty(app) {
+        let _ = fs::write(path, json);
+    }
+}
+
+/// Prints `name<TAB>bundle id<TAB>front window title`; the last two are empty
+/// when the app has no bundle or no windows.
+const FRONTMOST_SCRIPT: &str = r#"tell application "System Events"
+    set p to first application process whose frontmost is true
+    set b to ""
+    try
+        set b to bundle identifier of p
+    end try
+    set w to ""
+    try
+        set w to name of front window of p
+    end try
+    return (name of p) & tab & b & tab & w
+end tell"#;
+
+/// Parse [`FRONTMOST_SCRIPT`]'s output.
+pub fn parse_frontmost(output: &str) -> Option<PreviousApp> {
+    let mut fields = output.trim_end_matches(['\r', '\n']).splitn(3, '\t');
+    let name = fields.next()?.trim();
+    if name.is_empty() {
+        return None;
+    }
+    let bundle_id = fields.next().unwrap_or("").trim();
+    let window = fields.next().unwrap_or("");
+    Some(PreviousApp {
+        name: name.to_string(),
+        bundle_id: if bundle_id == "missing value" {
+            String::new()
+        } else {
+            bundle_id.to_string()
+        },
+        window: (!window.is_empty() && window != "missing value").then(|| window.to_string()),
+    })
+}
+
+/// The frontmost app right now.
+pub(crate) fn frontmost() -> Option<PreviousApp> {
+    parse_frontmost(&run_applescript(FRONTMOST_SCRIPT, "System Events").ok()?)
+}
+
+/// Remember the frontmost app so `focus --back` can return to it. Called
+/// before a focus raises anything; best effort.
+pub fn record_frontmost() {
+    if !cfg!(target_os = "macos") {
+        return;
+    }
+    if let Some(app) = frontmost() {
+        write(&default_path(), &app);
+    }
+}
+
+/// Bring `app` to the front and raise the window it had in front, found by
+/// title. The process is looked up by bundle id when it has one (names can
+/// repeat), and it's an error if it has quit since.
+fn restore_script(app: &PreviousApp) -> String {
+    let lookup = if app.bundle_id.is_empty() {
+        format!("name is {}", applescript_string(&app.name))
+    } else {
+        format!(
+            "bundle identifier is {}",
+            applescript_string(&app.bundle_id)
+        )
+    };
+    let raise = match &app.window {
+        Some(window) => format!(
+            r#"
+    try
+        perform action "AXRaise" of (first window of p whose name is {})
+    end try"#,
+            applescript_string(window)
+        ),
+        None => String::new(),
+    };
+    format!(
+        r#"tell application "System Events"
+    set procs to every application process whose {lookup}
+    if procs is {{}} then error {gone}
+    set p to item 1 of procs
+    set frontmost of p to true{raise}
+end tell"#,
+        gone = applescript_string(&format!("{} is no longer running", app.name)),
+    )
+}
+
+/// `focus --back`: return to the recorded app, recording the one being left.
+pub fn run_back() -> Result<(), Box<dyn std::error::Error>> {
+    if !cfg!(target_os = "macos") {
+        return Err("focus --back needs macOS (System Events)".into());
+    }
+    let path = default_path();
+    let previous = read(&path).ok_or("Nothing to go back to: no session has been focused yet")?;
+    let leaving = frontmost();
+    run_applescript(&restore_script(&previous), "System Events")?;
+    if let Some(leaving) = leaving.filter(|app| *app != previous) {
+        write(&path, &leaving);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_frontmost() {
+        assert_eq!(
+            parse_frontmost("Safari\tcom.apple.Safari\tDocs — Rust\n"),
+            Some(PreviousApp {
+                name: "Safari".into(),
+                bundle_id: "com.apple.Safari".into(),
+                window: Some("Docs — Rust".into()),
+            })
+        );
+        // Tabs in the title stay in it
+        assert_eq!(
+            parse_frontmost("Notes\tcom.apple.Notes\ta\tb\n")
+                .unwrap()
+                .window,
+            Some("a\tb".into())
+        );
+        assert_eq!(
+            parse_frontmost("java\tmissing value\t\n"),
+            Some(PreviousApp {
+                name: "java".into(),
+                bundle_id: String::new(),
+                window: None,
+            })
+        );
+        assert_eq!(parse_frontmost("\n"), None);
+    }
+
+    #[test]
+    fn test_restore_script() {
+        let app = PreviousApp {
+            name: "Safari".into(),
+            bundle_id: "com.apple.Safari".into(),
+            window: Some("Say \"hi\"".into()),
+        };
+        let script = restore_script(&app);
+        assert!(script.contains("whose bundle identifier is \"com.apple.Safari\""));
+        assert!(script.contains("first window of p whose name is \"Say \\\"hi\\\"\""));
+        assert!(script.contains("error \"Safari is no longer running\""));
+
+        let script = restore_script(&PreviousApp {
+            name: "java".into(),
+            bundle_id: String::new(),
+            window: None,
+        });
+        assert!(script.contains("whose name is \"java\""));
+        assert!(!script.contains("AXRaise"));
+    }
+
+    #[test]
+    fn test_read_write_roundtrip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("state/previous_app.json");
+        assert_eq!(read(&path), None);
+        let app = PreviousApp {
+            name: "Mail".into(),
+            bundle_id: "com.apple.mail".into(),
+            window: None,
+        };
+        write(&path, &app);
+        assert_eq!(read(&path), Some(app));
+        assert!(!fs::read_to_string(&path).unwrap().contains("window"));
+    }
+}
@@ -1,67 +1,421 @@
-use crate::process;
-use crate::state::SessionState;
+use crate::config::Config;
+use crate::error::ClaudeBarError;
+use crate::hooklog::{self, HookLogEntry};
+use crate::process::{self, AgentProcess, ProcessMatchers};
+use crate::protocol::{self, Request};
+use crate::state::{Provider, SessionState, ToolInFlight};
+use crate::transcript::SUBAGENT_TOOL;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::fs;
 use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Fields of a Claude Code hook payload that claude-bar uses.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HookInput {
+    pub session_id: String,
+    pub transcript_path: String,
+    /// Session working directory as Claude Code reports it
+    pub cwd: Option<String>,
+    pub hook_event_name: Option<String>,
+    /// `SessionStart` only: `startup`, `resume`, `clear` or `compact`
+    pub source: Option<String>,
+    /// `PreToolUse`/`PostToolUse`: the tool call
+    pub tool_name: Option<String>,
+    pub tool_use_id: Option<String>,
+    /// `Notification` only: the text shown, e.g. "Claude needs your permission to use Bash"
+    pub message: Option<String>,
+    /// `Notification` only, from newer Claude Code versions: e.g. `permission_prompt`
+    pub notification_type: Option<String>,
+}
+
+impl HookInput {
+    /// A session picked up again with `claude --resume`/`--continue` or `/resume`.
+    pub fn is_resume(&self) -> bool {
+        self.source.as_deref() == Some("resume")
+    }
+
+    /// Subagents running after this event, given the count recorded before it:
+    /// `PreToolUse` for `Task` starts one, `SubagentStop` ends one, and a
+    /// finished turn or a new session ends them all.
+    pub fn subagents_after(&self, previous: u32) -> u32 {
+        match self.hook_event_name.as_deref() {
+            Some("PreToolUse") if self.tool_name.as_deref() == Some(SUBAGENT_TOOL) => previous + 1,
+            Some("SubagentStop") => previous.saturating_sub(1),
+            Some("Stop") | Some("SessionStart") => 0,
+            _ => previous,
+        }
+    }
+
+    /// A `Notification` announcing a permission prompt.
+    pub fn is_permission_prompt(&self) -> bool {
+        self.hook_event_name.as_deref() == Some("Notification")
+            && (self.notification_type.as_deref() == Some("permission_prompt")
+                || self
+                    .message
+                    .as_deref()
+                    .is_some_and(|m| m.contains("needs your permission")))
+    }
+
+    /// Tool calls in flight after this event, given those before it: `PreToolUse`
+    /// adds one (started at `now`), `PostToolUse` removes it, a permission prompt
+    /// marks the newest as waiting for the user, and a finished turn, a new
+    /// prompt or a new session ends them all. A denied or interrupted call gets
+    /// neither `PostToolUse` nor `Stop`; the next prompt clears it.
+    pub fn tools_after(&self, mut tools: Vec<ToolInFlight>, now: u64) -> Vec<ToolInFlight> {
+        match self.hook_event_name.as_deref() {
+            Some("PreToolUse") => tools.push(ToolInFlight {
+                tool: self.tool_name.clone().unwrap_or_default(),
+                id: self.tool_use_id.clone(),
+                since: now,
+                awaiting_permission: false,
+            }),
+            Some("PostToolUse") => {
+                let finished = match &self.tool_use_id {
+                    Some(id) => tools.iter().rposition(|call| call.id.as_ref() == Some(id)),
+                    None => tools
+                        .iter()
+                        .rposition(|call| Some(&call.tool) == self.tool_name.as_ref()),
+                };
+                if let Some(i) = finished {
+                    tools.remove(i);
+                }
+            }
+            Some("Stop") | Some("UserPromptSubmit") | Some("SessionStart") => tools.clear(),
+            _ if self.is_permission_prompt() => {
+                if let Some(call) = tools.last_mut() {
+                    call.awaiting_permission = true;
+                }
+            }
+            _ => {}
+        }
+        tools
+    }
+}
 
 /// Parse the hook JSON input from stdin.
-pub fn parse_hook_input(input: &str) -> Option<(String, String)> {
+pub fn parse_hook_input(input: &str) -> Option<HookInput> {
     let v: serde_json::Value = serde_json::from_str(input).ok()?;
-    let session_id = v.get("session_id")?.as_str()?.to_string();
-    let transcript_path = v.get("transcript_path")?.as_str()?.to_string();
-    Some((session_id, transcript_path))
+    let optional = |key: &str| {
+        v.get(key)
+            .and_then(|e| e.as_str())
+            .filter(|e| !e.is_empty())
+            .map(str::to_string)
+    };
+    Some(HookInput {
+        session_id: v.get("session_id")?.as_str()?.to_string(),
+        transcript_path: v.get("transcript_path")?.as_str()?.to_string(),
+        cwd: optional("cwd"),
+        hook_event_name: optional("hook_event_name"),
+        source: optional("source"),
+        tool_name: optional("tool_name"),
+        tool_use_id: optional("tool_use_id"),
+        message: optional("message"),
+        notification_type: optional("notification_type"),
+    })
+}
+
+/// Env vars that can name the session TTY when the ancestor walk fails.
+/// `CLAUDE_BAR_TTY` is an explicit override for wrappers; `TTY` is exported by some shells.
+pub const TTY_ENV_VARS: [&str; 2] = ["CLAUDE_BAR_TTY", "TTY"];
+
+/// Project directory Claude Code exports to hook commands.
+pub const PROJECT_DIR_ENV: &str = "CLAUDE_PROJECT_DIR";
+
+/// Normalize a TTY name from the environment (`ttys003`, `/dev/ttys003`) to a device path.
+pub fn normalize_tty(value: &str) -> Option<String> {
+    let tty = value.trim();
+    let short = tty.strip_prefix("/dev/").unwrap_or(tty);
+    if short.is_empty() || short == "??" || short == "not a tty" {
+        return None;
+    }
+    Some(format!("/dev/{}", short))
+}
+
+/// PID of the claude process that owns `tty`, if any.
+fn claude_on_tty(agents: &HashMap<String, AgentProcess>, tty: &str) -> Option<u32> {
+    agents
+        .get(tty)
+        .filter(|a| a.provider == Provider::Claude)
+        .map(|a| a.pid)
+}
+
+/// The only claude process running in `cwd`, with its TTY.
+/// Ambiguous matches return None rather than guessing.
+pub fn unique_claude_in_cwd(
+    agents: &HashMap<String, AgentProcess>,
+    cwd: &str,
+    cwd_of: impl Fn(u32) -> Option<String>,
+) -> Option<(u32, String)> {
+    let mut matches = agents
+        .iter()
+        .filter(|(_, a)| a.provider == Provider::Claude)
+        .filter(|(_, a)| cwd_of(a.pid).as_deref() == Some(cwd));
+    let (tty, agent) = matches.next()?;
+    if matches.next().is_some() {
+        return None;
+    }
+    Some((agent.pid, tty.clone()))
+}
+
+/// Find the claude session this hook belongs to: (claude pid if known, TTY path).
+///
+/// Tries, in order: a claude ancestor process (per the configured matchers), a TTY from the environment,
+/// the hook's own controlling terminal (matched against live claude TTYs), and
+/// finally the single claude process running in the hook's cwd.
+fn locate_session(
+    ppid: u32,
+    cwd: Option<&str>,
+    matchers: &ProcessMatchers,
+) -> Option<(Option<u32>, String)> {
+    if let Some((pid, tty)) = process::find_claude_ancestor(ppid, &matchers.claude) {
+        return Some((Some(pid), tty));
+    }
+
+    let agents = process::build_agent_by_tty(matchers);
+    let tty = TTY_ENV_VARS
+        .iter()
+        .filter_map(|name| std::env::var(name).ok())
+        .find_map(|v| normalize_tty(&v))
+        // Portable ttyname(): stdin is the JSON pipe, but ps still reports our controlling terminal
+        .or_else(|| process::get_pid_tty(std::process::id()));
+    if let Some(tty) = tty {
+        return Some((claude_on_tty(&agents, &tty), tty));
+    }
+
+    let (pid, tty) = unique_claude_in_cwd(&agents, cwd?, process::get_pid_cwd)?;
+    Some((Some(pid), tty))
 }
 
-/// Run the hook subcommand: read stdin JSON, find claude ancestor, write state file.
-pub fn run_hook() -> Result<(), Box<dyn std::error::Error>> {
+/// Run the hook subcommand: read stdin JSON, locate the claude session, write
+/// state file.
+///
+/// Never fails: a non-zero exit or stderr output would surface inside Claude
+/// Code, so problems (including panics) are recorded in the hook log instead.
+pub fn run_hook() {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let mut entry = HookLogEntry {
+        timestamp: crate::logging::format_timestamp(now),
+        ..Default::default()
+    };
     let mut input = String::new();
-    std::io::stdin().read_to_string(&mut input)?;
+    let result = match std::io::stdin().read_to_string(&mut input) {
+        Ok(_) => {
+            std::panic::set_hook(Box::new(|_| {}));
+            std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                handle_hook(&input, &mut entry)
+            }))
+            .unwrap_or_else(|panic| Err(panic_message(&panic).into()))
+        }
+        Err(e) => Err(format!("Failed to read stdin: {}", e).into()),
+    };
+    entry.outcome = match result {
+        Ok(outcome) => outcome,
+        Err(e) => format!("error: {}", e),
+    };
+    hooklog::append(&hooklog::default_dir(), &entry, now);
+}
+
+fn panic_message(panic: &Box<dyn std::any::Any + Send>) -> String {
+    let msg = panic
+        .downcast_ref::<&str>()
+        .map(|s| s.to_string())
+        .or_else(|| panic.downcast_ref::<String>().cloned())
+        .unwrap_or_default();
+    format!("panicked: {}", msg)
+}
 
-    let (session_id, transcript_path) =
-        parse_hook_input(&input).ok_or("Failed to parse hook JSON from stdin")?;
+/// A hook event with the session it belongs to, as located by the hook
+/// process. Applied to the session's state file by the daemon
+/// (`{"cmd":"hook",...}`), or by the hook itself when the daemon is down.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HookDelivery {
+    pub input: HookInput,
+    /// TTY without `/dev/`, e.g. `ttys003`
+    pub tty: String,
+    /// Session working directory
+    pub cwd: String,
+    /// The claude process that ran the hook, and its start time
+    pub pid: Option<u32>,
+    pub process_started: Option<u64>,
+    /// When the hook ran (unix seconds)
+    pub at: u64,
+}
+
+/// How long the hook waits on the daemon before writing the state file itself.
+const DAEMON_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// The hook proper; fills in `entry` with what it learns along the way and
+/// returns the outcome to log.
+fn handle_hook(
+    input: &str,
+    entry: &mut HookLogEntry,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let hook = parse_hook_input(input).ok_or(ClaudeBarError::HookParse)?;
+    entry.event = hook.hook_event_name.clone();
+    entry.session_id = Some(hook.session_id.clone());
+
+    // Claude Code passes the CWD (and exports the project dir); lsof on the
+    // claude process is the fallback for older versions
+    let cwd_hint = hook.cwd.clone().or_else(|| {
+        std::env::var(PROJECT_DIR_ENV)
+            .ok()
+            .filter(|d| !d.is_empty())
+    });
+    entry.cwd = cwd_hint.clone();
 
-    // Walk up process tree to find claude and its TTY
     let ppid = std::os::unix::process::parent_id();
-    let (_, tty) = process::find_claude_ancestor(ppid)
-        .ok_or("Could not find claude process in ancestor chain")?;
+    let matchers = Config::load().processes;
+    let (claude_pid, tty) = locate_session(ppid, cwd_hint.as_deref(), &matchers)
+        .ok_or(ClaudeBarError::NoClaudeAncestor)?;
+    entry.tty = Some(tty.clone());
 
     let tty_short = tty.trim_start_matches("/dev/");
 
-    // Determine CWD from the claude process to find the centralized state dir
-    let cwd = find_project_cwd_from_transcript(&transcript_path);
-    let state_dir = crate::transcript::state_dir_for_cwd(&cwd);
+    let cwd = cwd_hint
+        .or_else(|| claude_pid.and_then(process::get_pid_cwd))
+        .unwrap_or_default();
+    entry.cwd = Some(cwd.clone()).filter(|c| !c.is_empty());
+
+    let delivery = HookDelivery {
+        input: hook,
+        tty: tty_short.to_string(),
+        cwd,
+        pid: claude_pid,
+        process_started: claude_pid.and_then(process::get_pid_start_time),
+        at: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0),
+    };
+    // The daemon applies hooks one at a time and polls right away; without it
+    // (or with one too old to know `hook`) the state file is written here
+    match deliver_to_daemon(&protocol::socket_path(), &delivery) {
+        Ok(outcome) => Ok(outcome),
+        Err(e) => apply_hook(&delivery).map(|outcome| format!("{} (daemon: {})", outcome, e)),
+    }
+}
 
-    fs::create_dir_all(&state_dir)?;
+/// Hand `delivery` to the daemon listening on `socket`; returns the outcome
+/// it reports.
+pub fn deliver_to_daemon(
+    socket: &Path,
+    delivery: &HookDelivery,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let response = protocol::send_request_with_timeout(
+        socket,
+        &Request::Hook(Box::new(delivery.clone())),
+        DAEMON_TIMEOUT,
+    )?;
+    if !response.ok {
+        let error = response.error.unwrap_or_else(|| "hook refused".into());
+        return Err(ClaudeBarError::Daemon(error).into());
+    }
+    let outcome = response
+        .data
+        .as_ref()
+        .and_then(|d| d.as_str())
+        .unwrap_or("ok")
+        .to_string();
+    Ok(format!("{} via daemon", outcome))
+}
 
+/// Record `delivery` in its session's state file, carrying the counters of the
+/// session's previous hook run over.
+pub fn apply_hook(delivery: &HookDelivery) -> Result<String, Box<dyn std::error::Error>> {
+    let hook = &delivery.input;
+    let dirs = [
+        crate::transcript::state_dir_for_cwd(&delivery.cwd),
+        crate::transcript::legacy_state_dir_for_cwd(&delivery.cwd),
+    ];
+    let previous = recorded_state(&dirs, &delivery.tty, &hook.session_id).unwrap_or_default();
     let state = SessionState {
-        session_id,
-        transcript_path,
-        cwd,
+        session_id: hook.session_id.clone(),
+        transcript_path: hook.transcript_path.clone(),
+        cwd: delivery.cwd.clone(),
+        pid: delivery.pid,
+        process_started: delivery.process_started,
+        hook_event: hook.hook_event_name.clone(),
+        source: hook.source.clone(),
+        updated_at: Some(delivery.at),
+        subagents: hook.subagents_after(previous.subagents),
+        tool_hooks: previous.tool_hooks || hook.hook_event_name.as_deref() == Some("PostToolUse"),
+        tools_in_flight: hook.tools_after(previous.tools_in_flight, delivery.at),
+        ..Default::default()
     };
+    write_session_state(&dirs, &delivery.tty, &state, hook.is_resume())
+}
 
-    let state_file = state_dir.join(format!("session-{}.json", tty_short));
-    let json = serde_json::to_string(&state)?;
-    fs::write(state_file, json)?;
+/// The state file `session_id` last wrote for `tty_short`, from the first of
+/// `dirs` that has one; what it counted carries over to the next hook run.
+pub fn recorded_state(dirs: &[PathBuf], tty_short: &str, session_id: &str) -> Option<SessionState> {
+    dirs.iter()
+        .filter_map(|dir| {
+            SessionState::read(&dir.join(crate::transcript::state_file_name(tty_short)))
+        })
+        .find(|state| state.session_id == session_id)
+}
 
-    Ok(())
+/// Write `state` for `tty_short` into the first of `dirs` that accepts it.
+/// Returns `ok`, or a note naming the directories that failed when a later
+/// one was used.
+pub fn write_session_state(
+    dirs: &[PathBuf],
+    tty_short: &str,
+    state: &SessionState,
+    resume: bool,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let mut failures = Vec::new();
+    for state_dir in dirs {
+        let state_file = state_dir.join(crate::transcript::state_file_name(tty_short));
+        let written = fs::create_dir_all(state_dir).and_then(|_| {
+            if resume {
+                release_transcript_claims(state_dir, &state_file, &state.transcript_path);
+            }
+            state.write(&state_file)
+        });
+        match written {
+            Ok(()) if failures.is_empty() => return Ok("ok".to_string()),
+            Ok(()) => {
+                return Ok(format!(
+                    "ok (wrote {}; {})",
+                    state_file.display(),
+                    failures.join("; ")
+                ))
+            }
+            Err(e) => failures.push(format!("{}: {}", state_dir.display(), e)),
+        }
+    }
+    Err(format!("Could not write state: {}", failures.join("; ")).into())
 }
 
-/// Try to find the project CWD by walking up the process tree and using lsof.
-fn find_project_cwd_from_transcript(_transcript_path: &str) -> String {
-    // Try to get CWD from our parent claude process
-    let ppid = std::os::unix::process::parent_id();
-    if let Some((pid, _)) = process::find_claude_ancestor(ppid) {
-        if let Some(cwd) = process::get_pid_cwd(pid) {
-            return cwd;
+/// Remove other TTYs' state files pointing at `transcript_path`. A resumed
+/// session takes its transcript over from the process that wrote it, which
+/// would otherwise keep claiming it while its state file lingers.
+pub fn release_transcript_claims(state_dir: &Path, own_file: &Path, transcript_path: &str) {
+    let Ok(entries) = fs::read_dir(state_dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let name = entry.file_name().to_string_lossy().to_string();
+        if path == own_file || !name.starts_with("session-") || !name.ends_with(".json") {
+            continue;
+        }
+        if SessionState::read(&path).is_some_and(|s| s.transcript_path == transcript_path) {
+            let _ = fs::remove_file(&path);
         }
     }
-    String::new()
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::process;
-    use std::collections::HashMap;
 
     /// Testable version: find claude in a mock process tree and return the TTY.
     fn find_tty_from_tree(
@@ -73,18 +427,35 @@ mod tests {
 
     #[test]
     fn test_parse_hook_stdin() {
-        let input = r#"{"session_id":"abc-123","transcript_path":"/home/user/.claude/projects/test/session.jsonl"}"#;
-        let (sid, tp) = parse_hook_input(input).unwrap();
-        assert_eq!(sid, "abc-123");
-        assert_eq!(tp, "/home/user/.claude/projects/test/session.jsonl");
+        let input = r#"{"session_id":"abc-123","transcript_path":"/home/user/.claude/projects/test/session.jsonl","cwd":"/home/user/test","hook_event_name":"SessionStart"}"#;
+        let hook = parse_hook_input(input).unwrap();
+        assert_eq!(hook.session_id, "abc-123");
+        assert_eq!(
+            hook.transcript_path,
+            "/home/user/.claude/projects/test/session.jsonl"
+        );
+        assert_eq!(hook.cwd.as_deref(), Some("/home/user/test"));
+        assert_eq!(hook.hook_event_name.as_deref(), Some("SessionStart"));
+        assert!(!hook.is_resume());
+
+        let input = r#"{"session_id":"x","transcript_path":"/t.jsonl","hook_event_name":"SessionStart","source":"resume"}"#;
+        let hook = parse_hook_input(input).unwrap();
+        assert_eq!(hook.source.as_deref(), Some("resume"));
+        assert!(hook.is_resume());
     }
 
     #[test]
     fn test_parse_hook_stdin_extra_fields() {
         let input = r#"{"session_id":"x","transcript_path":"/t.jsonl","extra":"ignored"}"#;
-        let (sid, tp) = parse_hook_input(input).unwrap();
-        assert_eq!(sid, "x");
-        assert_eq!(tp, "/t.jsonl");
+        let hook = parse_hook_input(input).unwrap();
+        assert_eq!(hook.session_id, "x");
+        assert_eq!(hook.transcript_path, "/t.jsonl");
+        assert_eq!(hook.cwd, None);
+        assert_eq!(hook.hook_event_name, None);
+
+        // Empty cwd counts as absent so the lsof fallback runs
+        let hook = parse_hook_input(r#"{"session_id":"x","transcript_path":"/t","cwd":""}"#);
+        assert_eq!(hook.unwrap().cwd, None);
     }
 
     #[test]
@@ -94,6 +465,113 @@ mod tests {
         assert!(parse_hook_input("not json").is_none());
     }
 
+    #[test]
+    fn test_subagents_after() {
+        let event = |name: &str, tool: Option<&str>| {
+            let mut payload = serde_json::json!({
+                "session_id": "s", "transcript_path": "/t.jsonl", "hook_event_name": name,
+            });
+            if let Some(tool) = tool {
+                payload["tool_name"] = tool.into();
+            }
+            parse_hook_input(&payload.to_string()).unwrap()
+        };
+        let task = event("PreToolUse", Some("Task"));
+        assert_eq!(task.tool_name.as_deref(), Some("Task"));
+        assert_eq!(task.subagents_after(0), 1);
+        assert_eq!(task.subagents_after(2), 3);
+        assert_eq!(event("PreToolUse", Some("Bash")).subagents_after(2), 2);
+        assert_eq!(event("SubagentStop", None).subagents_after(2), 1);
+        assert_eq!(event("SubagentStop", None).subagents_after(0), 0);
+        assert_eq!(event("Stop", None).subagents_after(3), 0);
+        assert_eq!(event("SessionStart", None).subagents_after(3), 0);
+        assert_eq!(event("Notification", None).subagents_after(3), 3);
+    }
+
+    #[test]
+    fn test_recorded_subagents() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let (primary, legacy) = (tmp.path().join("a"), tmp.path().join("b"));
+        fs::create_dir_all(&legacy).unwrap();
+        SessionState {
+            session_id: "s".into(),
+            subagents: 2,
+            ..Default::default()
+        }
+        .write(&legacy.join("session-ttys001.json"))
+        .unwrap();
+        let dirs = [primary, legacy];
+
+        assert_eq!(recorded_state(&dirs, "ttys001", "s").unwrap().subagents, 2);
+        // A new session on the TTY starts from scratch
+        assert!(recorded_state(&dirs, "ttys001", "other").is_none());
+        assert!(recorded_state(&dirs, "ttys002", "s").is_none());
+    }
+
+    #[test]
+    fn test_tools_after() {
+        let event = |payload: serde_json::Value| {
+            let mut base = serde_json::json!({"session_id": "s", "transcript_path": "/t.jsonl"});
+            base.as_object_mut()
+                .unwrap()
+                .extend(payload.as_object().unwrap().clone());
+            parse_hook_input(&base.to_string()).unwrap()
+        };
+        let pre = |tool: &str, id: &str| {
+            event(
+                serde_json::json!({"hook_event_name": "PreToolUse", "tool_name": tool, "tool_use_id": id}),
+            )
+        };
+        let post = |tool: &str, id: &str| {
+            event(
+                serde_json::json!({"hook_event_name": "PostToolUse", "tool_name": tool, "tool_use_id": id}),
+            )
+        };
+
+        let tools = pre("Read", "t1").tools_after(Vec::new(), 100);
+        let tools = pre("Bash", "t2").tools_after(tools, 101);
+        assert_eq!(tools.len(), 2);
+        assert_eq!((tools[1].tool.as_str(), tools[1].since), ("Bash", 101));
+
+        let prompt = event(serde_json::json!({
+            "hook_event_name": "Notification",
+            "message": "Claude needs your permission to use Bash",
+        }));
+        assert!(prompt.is_permission_prompt());
+        let tools = prompt.tools_after(tools, 102);
+        assert!(tools[1].awaiting_permission && !tools[0].awaiting_permission);
+
+        // Results are matched by id, in any order
+        let tools = post("Read", "t1").tools_after(tools, 103);
+        assert_eq!(tools.len(), 1);
+        assert_eq!(tools[0].id.as_deref(), Some("t2"));
+        assert!(post("Bash", "t2")
+            .tools_after(tools.clone(), 104)
+            .is_empty());
+        let by_name =
+            event(serde_json::json!({"hook_event_name": "PostToolUse", "tool_name": "Bash"}));
+        assert!(by_name.tools_after(tools.clone(), 104).is_empty());
+        assert_eq!(post("Grep", "t9").tools_after(tools.clone(), 104), tools);
+
+        let idle = event(serde_json::json!({
+            "hook_event_name": "Notification",
+            "message": "Claude is waiting for your input",
+        }));
+        assert!(!idle.is_permission_prompt());
+        let typed = event(serde_json::json!({
+            "hook_event_name": "Notification",
+            "notification_type": "permission_prompt",
+        }));
+        assert!(typed.is_permission_prompt());
+        let stop = event(serde_json::json!({"hook_event_name": "Stop"}));
+        assert!(stop.tools_after(tools.clone(), 105).is_empty());
+        // A denied prompt ends the turn without Stop; the next prompt clears it
+        let denied = typed.tools_after(tools, 105);
+        assert!(denied[0].awaiting_permission);
+        let next = event(serde_json::json!({"hook_event_name": "UserPromptSubmit"}));
+        assert!(next.tools_after(denied, 106).is_empty());
+    }
+
     #[test]
     fn test_find_tty_from_tree() {
         let mut lookup = HashMap::new();
@@ -125,6 +603,96 @@ mod tests {
         assert_eq!(find_tty_from_tree(100, &lookup), None);
     }
 
+    #[test]
+    fn test_normalize_tty() {
+        assert_eq!(normalize_tty("ttys003"), Some("/dev/ttys003".to_string()));
+        assert_eq!(
+            normalize_tty("/dev/ttys003\n"),
+            Some("/dev/ttys003".to_string())
+        );
+        assert_eq!(normalize_tty("/dev/pts/4"), Some("/dev/pts/4".to_string()));
+        assert_eq!(normalize_tty(""), None);
+        assert_eq!(normalize_tty("/dev/"), None);
+        assert_eq!(normalize_tty("??"), None);
+        assert_eq!(normalize_tty("not a tty"), None);
+    }
+
+    fn agent(pid: u32, provider: Provider) -> AgentProcess {
+        AgentProcess {
+            pid,
+            provider,
+            started_at: None,
+            foreground: true,
+        }
+    }
+
+    #[test]
+    fn test_claude_on_tty() {
+        let mut agents = HashMap::new();
+        agents.insert("/dev/ttys001".to_string(), agent(10, Provider::Claude));
+        agents.insert("/dev/ttys002".to_string(), agent(20, Provider::Codex));
+
+        assert_eq!(claude_on_tty(&agents, "/dev/ttys001"), Some(10));
+        assert_eq!(claude_on_tty(&agents, "/dev/ttys002"), None);
+        assert_eq!(claude_on_tty(&agents, "/dev/ttys003"), None);
+    }
+
+    #[test]
+    fn test_unique_claude_in_cwd() {
+        let mut agents = HashMap::new();
+        agents.insert("/dev/ttys001".to_string(), agent(10, Provider::Claude));
+        agents.insert("/dev/ttys002".to_string(), agent(20, Provider::Claude));
+        agents.insert("/dev/ttys003".to_string(), agent(30, Provider::Codex));
+        let cwds: HashMap<u32, &str> = [(10, "/a"), (20, "/b"), (30, "/a")].into();
+        let cwd_of = |pid: u32| cwds.get(&pid).map(|c| c.to_string());
+
+        // Codex in the same dir doesn't count
+        assert_eq!(
+            unique_claude_in_cwd(&agents, "/a", cwd_of),
+            Some((10, "/dev/ttys001".to_string()))
+        );
+        assert_eq!(unique_claude_in_cwd(&agents, "/c", cwd_of), None);
+
+        // Two claude sessions in one dir are ambiguous
+        let cwd_of = |_| Some("/a".to_string());
+        assert_eq!(unique_claude_in_cwd(&agents, "/a", cwd_of), None);
+    }
+
+    #[test]
+    fn test_deliver_to_daemon() {
+        use std::io::{BufRead, BufReader, Write};
+        use std::os::unix::net::UnixListener;
+
+        let tmp = tempfile::TempDir::new().unwrap();
+        let socket = tmp.path().join("serve.sock");
+        let delivery = HookDelivery {
+            input: parse_hook_input(
+                r#"{"session_id":"s","transcript_path":"/t.jsonl","hook_event_name":"Stop"}"#,
+            )
+            .unwrap(),
+            tty: "ttys000".into(),
+            cwd: "/p".into(),
+            pid: Some(7),
+            process_started: None,
+            at: 1,
+        };
+        // Daemon down: the caller falls back to writing the file itself
+        assert!(deliver_to_daemon(&socket, &delivery).is_err());
+
+        let listener = UnixListener::bind(&socket).unwrap();
+        let server = std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut line = String::new();
+            BufReader::new(&stream).read_line(&mut line).unwrap();
+            let reply = r#"{"v":1,"ok":true,"data":"wrote session-ttys000.json"}"#;
+            writeln!(&stream, "{}", reply).unwrap();
+            protocol::parse_request(line.trim()).unwrap()
+        });
+        let outcome = deliver_to_daemon(&socket, &delivery).unwrap();
+        assert_eq!(outcome, "wrote session-ttys000.json via daemon");
+        assert_eq!(server.join().unwrap(), Request::Hook(Box::new(delivery)));
+    }
+
     #[test]
     fn test_state_file_write() {
         let tmp = tempfile::TempDir::new().unwrap();
@@ -134,15 +702,81 @@ mod tests {
             session_id: "test-123".into(),
             transcript_path: "/path/to/transcript.jsonl".into(),
             cwd: "/some/project".into(),
+            pid: Some(100),
+            process_started: None,
+            ..Default::default()
         };
 
         let state_file = state_dir.join("session-ttys000.json");
-        let json = serde_json::to_string(&state).unwrap();
-        fs::write(&state_file, &json).unwrap();
+        state.write(&state_file).unwrap();
 
-        let read_back: SessionState =
-            serde_json::from_str(&fs::read_to_string(&state_file).unwrap()).unwrap();
+        let read_back = SessionState::read(&state_file).unwrap();
         assert_eq!(read_back.session_id, "test-123");
         assert_eq!(read_back.transcript_path, "/path/to/transcript.jsonl");
     }
+
+    #[test]
+    fn test_release_transcript_claims() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let dir = tmp.path();
+        let write = |tty: &str, transcript: &str| {
+            let path = dir.join(format!("session-{}.json", tty));
+            SessionState {
+                session_id: tty.into(),
+                transcript_path: transcript.into(),
+                ..Default::default()
+            }
+            .write(&path)
+            .unwrap();
+            path
+        };
+        let own = write("ttys001", "/t/old.jsonl");
+        let stale = write("ttys002", "/t/resumed.jsonl");
+        let other = write("ttys003", "/t/other.jsonl");
+
+        release_transcript_claims(dir, &own, "/t/resumed.jsonl");
+        assert!(own.exists());
+        assert!(!stale.exists());
+        assert!(other.exists());
+    }
+
+    #[test]
+    fn test_write_session_state_falls_back() {
+        use std::os::unix::fs::PermissionsExt;
+        let tmp = tempfile::TempDir::new().unwrap();
+        let readonly = tmp.path().join("readonly");
+        fs::create_dir_all(&readonly).unwrap();
+        fs::set_permissions(&readonly, fs::Permissions::from_mode(0o555)).unwrap();
+        let primary = readonly.join("project");
+        let fallback = tmp.path().join("fallback").join("project");
+        let state = SessionState {
+            session_id: "s".into(),
+            transcript_path: "/t.jsonl".into(),
+            ..Default::default()
+        };
+
+        // Running as root ignores the permission bits; nothing to observe then
+        if fs::create_dir(readonly.join("probe")).is_ok() {
+            return;
+        }
+        let outcome = write_session_state(
+            &[primary.clone(), fallback.clone()],
+            "ttys001",
+            &state,
+            false,
+        )
+        .unwrap();
+        assert!(outcome.starts_with("ok (wrote "));
+        assert!(outcome.contains(&primary.display().to_string()));
+        assert!(SessionState::read(&fallback.join("session-ttys001.json")).is_some());
+
+        assert!(write_session_state(&[primary], "ttys001", &state, false)
+            .unwrap_err()
+            .to_string()
+            .starts_with("Could not write state"));
+        assert_eq!(
+            write_session_state(&[fallback], "ttys001", &state, false).unwrap(),
+            "ok"
+        );
+    }
 }
@@ -0,0 +1,220 @@
+//! Audit log of hook invocations, for when state files don't match what the
+//! menu should show.
+//!
+//! Every `claude-bar hook` run appends one JSON line (event, session id, TTY,
+//! time, outcome) to `~/.local/state/claude-bar/hooks/<YYYY-MM-DD>.jsonl`;
+//! `claude-bar hooks log` prints them. Files older than [`KEEP_DAYS`] are
+//! removed as new ones are started.
+
+use crate::logging;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Days of hook logs kept.
+pub const KEEP_DAYS: u64 = 14;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HookLogEntry {
+    /// RFC 3339 time the hook ran
+    pub timestamp: String,
+    pub event: Option<String>,
+    pub session_id: Option<String>,
+    pub tty: Option<String>,
+    pub cwd: Option<String>,
+    /// `ok`, or `error: <reason>`
+    pub outcome: String,
+}
+
+/// `hooks` in [`crate::paths::state_dir`]: `~/.local/state/claude-bar/hooks`
+pub fn default_dir() -> PathBuf {
+    crate::paths::state_dir().join("hooks")
+}
+
+/// `YYYY-MM-DD.jsonl` for the UTC day containing `unix_secs`.
+pub fn file_name_for(unix_secs: u64) -> String {
+    format!("{}.jsonl", &logging::format_timestamp(unix_secs)[..10])
+}
+
+/// Append `entry` to the day file for `now` in `dir`. Failures are ignored:
+/// the log must never get in the way of the hook itself.
+pub fn append(dir: &Path, entry: &HookLogEntry, now: u64) {
+    let path = dir.join(file_name_for(now));
+    if !path.exists() {
+        let _ = fs::create_dir_all(dir);
+        prune(dir, now);
+    }
+    let Ok(line) = serde_json::to_string(entry) else {
+        return;
+    };
+    if let Ok(mut file) = OpenOptions::new().create(true).append(true).open(&path) {
+        let _ = writeln!(file, "{}", line);
+    }
+}
+
+/// Remove day files older than [`KEEP_DAYS`] before `now`.
+fn prune(dir: &Path, now: u64) {
+    let oldest = file_name_for(now.saturating_sub((KEEP_DAYS - 1) * 86400));
+    for path in day_files(dir) {
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        // Day files sort by name, so string order is date order
+        if *name < *oldest {
+            let _ = fs::remove_file(&path);
+        }
+    }
+}
+
+/// Day files in `dir`, oldest first.
+fn day_files(dir: &Path) -> Vec<PathBuf> {
+    let mut files: Vec<PathBuf> = fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| p.extension().is_some_and(|e| e == "jsonl"))
+        .collect();
+    files.sort();
+    files
+}
+
+/// The last `limit` entries in `dir`, oldest first; `day` (`YYYY-MM-DD`)
+/// restricts them to that day's file. Unparseable lines are skipped.
+pub fn read_entries(dir: &Path, day: Option<&str>, limit: usize) -> Vec<HookLogEntry> {
+    let files: Vec<PathBuf> = match day {
+        Some(day) => vec![dir.join(format!("{}.jsonl", day))],
+        None => day_files(dir),
+    };
+    let mut entries = Vec::new();
+    // Newest files first, stopping once enough entries are collected
+    for path in files.iter().rev() {
+        let Ok(content) = fs::read_to_string(path) else {
+            continue;
+        };
+        let mut day_entries: Vec<HookLogEntry> = content
+            .lines()
+            .filter_map(|l| serde_json::from_str(l).ok())
+            .collect();
+        day_entries.append(&mut entries);
+        entries = day_entries;
+        if entries.len() >= limit {
+            break;
+        }
+    }
+    let skip = entries.len().saturating_sub(limit);
+    entries.split_off(skip)
+}
+
+/// One line per entry: time, event, TTY, short session id, outcome.
+pub fn format_entry(entry: &HookLogEntry) -> String {
+    let dash = || "-".to_string();
+    let session = entry
+        .session_id
+        .as_deref()
+        .map(|id| id.chars().take(8).collect())
+        .unwrap_or_else(dash);
+    format!(
+        "{}  {:<12}  {:<8}  {:<8}  {}",
+        entry.timestamp,
+        entry.event.clone().unwrap_or_else(dash),
+        entry
+            .tty
+            .as_deref()
+            .map(|t| t.trim_start_matches("/dev/").to_string())
+            .unwrap_or_else(dash),
+        session,
+        entry.outcome
+    )
+}
+
+pub fn run_log(
+    day: Option<&str>,
+    limit: usize,
+    json: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let dir = default_dir();
+    let entries = read_entries(&dir, day, limit);
+    if entries.is_empty() {
+        println!("No hook events logged in {}", dir.display());
+        return Ok(());
+    }
+    for entry in &entries {
+        if json {
+            println!("{}", serde_json::to_string(entry)?);
+        } else {
+            println!("{}", format_entry(entry));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DAY: u64 = 86400;
+
+    fn entry(event: &str, outcome: &str) -> HookLogEntry {
+        HookLogEntry {
+            timestamp: "2026-10-16T09:30:00Z".into(),
+            event: Some(event.into()),
+            session_id: Some("0123456789abcdef".into()),
+            tty: Some("/dev/ttys003".into()),
+            cwd: Some("/src/api".into()),
+            outcome: outcome.into(),
+        }
+    }
+
+    #[test]
+    fn test_append_and_read_across_days() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let dir = tmp.path().join("hooks");
+        append(&dir, &entry("SessionStart", "ok"), DAY * 100);
+        append(&dir, &entry("Stop", "ok"), DAY * 100 + 60);
+        append(&dir, &entry("SessionStart", "error: no tty"), DAY * 101);
+        assert!(dir.join(file_name_for(DAY * 100)).exists());
+
+        let all = read_entries(&dir, None, 10);
+        assert_eq!(all.len(), 3);
+        assert_eq!(all[0].event.as_deref(), Some("SessionStart"));
+        assert_eq!(all[2].outcome, "error: no tty");
+
+        let last = read_entries(&dir, None, 2);
+        assert_eq!(last[0].event.as_deref(), Some("Stop"));
+        assert_eq!(last.len(), 2);
+
+        let day = &logging::format_timestamp(DAY * 100)[..10];
+        assert_eq!(read_entries(&dir, Some(day), 10).len(), 2);
+        assert!(read_entries(&dir, Some("1999-01-01"), 10).is_empty());
+    }
+
+    #[test]
+    fn test_old_day_files_are_pruned() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let dir = tmp.path();
+        append(dir, &entry("SessionStart", "ok"), DAY * 100);
+        append(
+            dir,
+            &entry("SessionStart", "ok"),
+            DAY * (100 + KEEP_DAYS - 1),
+        );
+        assert!(dir.join(file_name_for(DAY * 100)).exists());
+        append(dir, &entry("SessionStart", "ok"), DAY * (100 + KEEP_DAYS));
+        assert!(!dir.join(file_name_for(DAY * 100)).exists());
+        assert_eq!(day_files(dir).len(), 2);
+    }
+
+    #[test]
+    fn test_format_entry() {
+        assert_eq!(
+            format_entry(&entry("SessionStart", "ok")),
+            "2026-10-16T09:30:00Z  SessionStart  ttys003   01234567  ok"
+        );
+        let empty = HookLogEntry {
+            timestamp: "2026-10-16T09:30:00Z".into(),
+            outcome: "error: bad input".into(),
+            ..Default::default()
+        };
+        assert!(format_entry(&empty).contains("-             -         -         error: bad input"));
+    }
+}
@@ -0,0 +1,445 @@
+//! `hotkey-daemon`: global keyboard shortcuts that focus sessions.
+//!
+//! The `hotkeys` config maps key combinations to actions:
+//!
+//! ```json
+//! "hotkeys": {"ctrl+alt+1": "session:1", "ctrl+alt+p": "pending", "ctrl+alt+b": "back"}
+//! ```
+//!
+//! `session:N` is the Nth session in dropdown order, `pending` the first one
+//! waiting for input, `next-pending` the one waiting longest (as `focus
+//! --next-pending`), `back` the same as `focus --back`. The daemon listens
+//! through a Quartz event tap, which needs Accessibility access for the
+//! claude-bar binary; matching key presses are swallowed, everything else
+//! passes through. macOS only.
+
+use crate::config::Config;
+use crate::error::ClaudeBarError;
+use crate::focus;
+use crate::focusback;
+use crate::logging;
+use crate::render;
+use crate::serve;
+use crate::state::{SessionInfo, Status};
+use std::collections::BTreeMap;
+
+/// `CGEventFlags` bits for the modifiers a hotkey can use.
+pub const SHIFT: u64 = 1 << 17;
+pub const CONTROL: u64 = 1 << 18;
+pub const OPTION: u64 = 1 << 19;
+pub const COMMAND: u64 = 1 << 20;
+const MODIFIERS: u64 = SHIFT | CONTROL | OPTION | COMMAND;
+
+/// A key combination: modifier flags plus a macOS virtual key code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Hotkey {
+    pub modifiers: u64,
+    pub keycode: u16,
+}
+
+impl Hotkey {
+    /// Whether a key press with `flags` (as reported by the event, other bits
+    /// included) and `keycode` is this combination.
+    pub fn matches(&self, flags: u64, keycode: u16) -> bool {
+        self.keycode == keycode && flags & MODIFIERS == self.modifiers
+    }
+}
+
+/// Virtual key code (ANSI layout) of a key name: a letter, a digit, `f1`-`f12`
+/// or `space`.
+fn keycode(name: &str) -> Option<u16> {
+    const LETTERS: [u16; 26] = [
+        0, 11, 8, 2, 14, 3, 5, 4, 34, 38, 40, 37, 46, 45, 31, 35, 12, 15, 1, 17, 32, 9, 13, 7, 16,
+        6,
+    ];
+    const DIGITS: [u16; 10] = [29, 18, 19, 20, 21, 23, 22, 26, 28, 25];
+    const FUNCTION: [u16; 12] = [122, 120, 99, 118, 96, 97, 98, 100, 101, 109, 103, 111];
+    let mut chars = name.chars();
+    match (chars.next()?, chars.next()) {
+        (c @ 'a'..='z', None) => Some(LETTERS[c as usize - 'a' as usize]),
+        (c @ '0'..='9', None) => Some(DIGITS[c as usize - '0' as usize]),
+        _ if name == "space" => Some(49),
+        ('f', Some(_)) => {
+            let n: usize = name[1..].parse().ok()?;
+            FUNCTION.get(n.checked_sub(1)?).copied()
+        }
+        _ => None,
+    }
+}
+
+/// Parse `ctrl+alt+1`-style combinations (case-insensitive; `control`,
+/// `opt`/`option`, `cmd`/`command` and `⌃⌥⇧⌘` work too). Shift alone isn't
+/// enough: the combination must not swallow ordinary typing.
+pub fn parse_hotkey(s: &str) -> Result<Hotkey, String> {
+    let lower = s.to_lowercase();
+    let mut parts: Vec<&str> = lower.split('+').map(str::trim).collect();
+    let key = parts.pop().filter(|k| !k.is_empty());
+    let mut modifiers = 0;
+    for part in parts {
+        modifiers |= match part {
+            "ctrl" | "control" | "⌃" => CONTROL,
+            "alt" | "opt" | "option" | "⌥" => OPTION,
+            "shift" | "⇧" => SHIFT,
+            "cmd" | "command" | "⌘" => COMMAND,
+            other => return Err(format!("unknown modifier '{}' in hotkey {}", other, s)),
+        };
+    }
+    let keycode = key
+        .and_then(keycode)
+        .ok_or_else(|| format!("hotkey {} must end in a letter, digit, f1-f12 or space", s))?;
+    if modifiers & (CONTROL | OPTION | COMMAND) == 0 {
+        return Err(format!("hotkey {} needs ctrl, alt or cmd", s));
+    }
+    Ok(Hotkey { modifiers, keycode })
+}
+
+/// What a hotkey does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    /// Focus the Nth session in dropdown order (1-based)
+    Session(usize),
+    /// Focus the first session waiting for input
+    Pending,
+    /// Focus the session that has waited for input longest
+    NextPending,
+    /// `focus --back`
+    Back,
+}
+
+pub fn parse_action(s: &str) -> Result<Action, String> {
+    match s.trim() {
+        "pending" => Ok(Action::Pending),
+        "next-pending" => Ok(Action::NextPending),
+        "back" => Ok(Action::Back),
+        other => other
+            .strip_prefix("session:")
+            .and_then(|n| n.parse().ok())
+            .filter(|&n| n > 0)
+            .map(Action::Session)
+            .ok_or_else(|| {
+                format!(
+                    "unknown hotkey action '{}' (expected session:<n>, pending, next-pending or back)",
+                    other
+                )
+            }),
+    }
+}
+
+/// The `hotkeys` config, parsed.
+pub fn parse_bindings(hotkeys: &BTreeMap<String, String>) -> Result<Vec<(Hotkey, Action)>, String> {
+    let mut bindings: Vec<(Hotkey, Action)> = Vec::new();
+    for (combo, action) in hotkeys {
+        let hotkey = parse_hotkey(combo)?;
+        if bindings.iter().any(|(h, _)| *h == hotkey) {
+            return Err(format!("hotkey {} is bound twice", combo));
+        }
+        bindings.push((hotkey, parse_action(action)?));
+    }
+    Ok(bindings)
+}
+
+/// `ctrl+alt+1`..`9` for the first nine sessions and `ctrl+alt+p` for the
+/// first pending one.
+pub fn default_hotkeys() -> BTreeMap<String, String> {
+    let mut hotkeys: BTreeMap<String, String> = (1..=9)
+        .map(|n| (format!("ctrl+alt+{}", n), format!("session:{}", n)))
+        .collect();
+    hotkeys.insert("ctrl+alt+p".into(), "pending".into());
+    hotkeys
+}
+
+/// The session `action` focuses, out of `sessions` in dropdown order.
+pub fn target(action: Action, sessions: &[SessionInfo]) -> Result<&SessionInfo, String> {
+    match action {
+        Action::Session(n) => sessions
+            .get(n - 1)
+            .ok_or_else(|| format!("No session {} ({} running)", n, sessions.len())),
+        Action::Pending => sessions
+            .iter()
+            .find(|s| s.status == Status::Pending)
+            .ok_or_else(|| "No session needs input".to_string()),
+        Action::NextPending => render::longest_pending(sessions, render::transcript_age)
+            .ok_or_else(|| "No session needs input".to_string()),
+        Action::Back => Err("`back` has no session".to_string()),
+    }
+}
+
+/// Carry out `action` with a fresh poll. Failures are logged and shown as a
+/// notification, since nobody is watching the daemon's output.
+pub fn run_action(action: Action, config: &Config) {
+    let result = match action {
+        Action::Back => focusback::run_back().map_err(|e| e.to_string()),
+        _ => {
+            let view = render::build_menu_view(&serve::poll_sessions_with(config), &config.menu);
+            let ordered: Vec<SessionInfo> = view
+                .sessions
+                .into_iter()
+                .chain(view.collapsed_idle)
+                .collect();
+            target(action, &ordered)
+                .and_then(|session| focus::focus_session(session).map_err(|e| e.to_string()))
+        }
+    };
+    if let Err(e) = result {
+        logging::warn("hotkey", &e);
+        focus::notify(&e);
+    }
+}
+
+/// `hotkey-daemon`: listen until killed.
+pub fn run_hotkey_daemon() -> Result<(), Box<dyn std::error::Error>> {
+    let config = Config::load();
+    let bindings = parse_bindings(&config.hotkeys).map_err(ClaudeBarError::Config)?;
+    if bindings.is_empty() {
+        return Err(ClaudeBarError::Config("no hotkeys configured".into()).into());
+    }
+    listen(bindings, config)
+}
+
+#[cfg(target_os = "macos")]
+fn listen(
+    bindings: Vec<(Hotkey, Action)>,
+    config: Config,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let (tx, rx) = std::sync::mpsc::channel();
+    std::thread::Builder::new()
+        .name("claude-bar-hotkey".into())
+        .spawn(move || {
+            for action in rx {
+                run_action(action, &config);
+            }
+        })?;
+    logging::info(
+        "hotkey",
+        &format!("listening for {} hotkeys", bindings.len()),
+    );
+    tap::run(bindings, tx)
+}
+
+#[cfg(not(target_os = "macos"))]
+fn listen(_: Vec<(Hotkey, Action)>, _: Config) -> Result<(), Box<dyn std::error::Error>> {
+    Err("hotkey-daemon needs macOS (Quartz event taps)".into())
+}
+
+/// The Quartz event tap, over the raw CoreGraphics/CoreFoundation C API.
+#[cfg(target_os = "macos")]
+mod tap {
+    use super::{Action, Hotkey};
+    use std::ffi::c_void;
+    use std::ptr;
+    use std::sync::mpsc::Sender;
+
+    type CFMachPortRef = *mut c_void;
+    type CFRunLoopSourceRef = *mut c_void;
+    type CFRunLoopRef = *mut c_void;
+    type CFStringRef = *const c_void;
+    type CGEventRef = *mut c_void;
+    type CGEventTapProxy = *mut c_void;
+    type CGEventTapCallBack =
+        extern "C" fn(CGEventTapProxy, u32, CGEventRef, *mut c_void) -> CGEventRef;
+
+    const SESSION_EVENT_TAP: u32 = 1;
+    const HEAD_INSERT_EVENT_TAP: u32 = 0;
+    const TAP_OPTION_DEFAULT: u32 = 0;
+    const KEY_DOWN: u32 = 10;
+    const TAP_DISABLED_BY_TIMEOUT: u32 = 0xFFFF_FFFE;
+    const TAP_DISABLED_BY_USER_INPUT: u32 = 0xFFFF_FFFF;
+    const KEYBOARD_EVENT_AUTOREPEAT: u32 = 8;
+    const KEYBOARD_EVENT_KEYCODE: u32 = 9;
+
+    #[link(name = "CoreGraphics", kind = "framework")]
+    extern "C" {
+        fn CGEventTapCreate(
+            tap: u32,
+            place: u32,
+            options: u32,
+            events_of_interest: u64,
+            callback: CGEventTapCallBack,
+            user_info: *mut c_void,
+        ) -> CFMachPortRef;
+        fn CGEventTapEnable(tap: CFMachPortRef, enable: bool);
+        fn CGEventGetFlags(event: CGEventRef) -> u64;
+        fn CGEventGetIntegerValueField(event: CGEventRef, field: u32) -> i64;
+    }
+
+    #[link(name = "CoreFoundation", kind = "framework")]
+    extern "C" {
+        static kCFRunLoopCommonModes: CFStringRef;
+        fn CFMachPortCreateRunLoopSource(
+            allocator: *const c_void,
+            port: CFMachPortRef,
+            order: isize,
+        ) -> CFRunLoopSourceRef;
+        fn CFRunLoopGetCurrent() -> CFRunLoopRef;
+        fn CFRunLoopAddSource(rl: CFRunLoopRef, source: CFRunLoopSourceRef, mode: CFStringRef);
+        fn CFRunLoopRun();
+    }
+
+    struct State {
+        bindings: Vec<(Hotkey, Action)>,
+        actions: Sender<Action>,
+        port: CFMachPortRef,
+    }
+
+    extern "C" fn callback(
+        _proxy: CGEventTapProxy,
+        event_type: u32,
+        event: CGEventRef,
+        user_info: *mut c_void,
+    ) -> CGEventRef {
+        // SAFETY: `user_info` is the `State` leaked in `run`, alive for the
+        // rest of the process and only touched from the run loop's thread
+        let state = unsafe { &*(user_info as *const State) };
+        if event_type == TAP_DISABLED_BY_TIMEOUT || event_type == TAP_DISABLED_BY_USER_INPUT {
+            // macOS turns off taps that answer slowly; turn it back on
+            unsafe { CGEventTapEnable(state.port, true) };
+            return event;
+        }
+        if event_type != KEY_DOWN {
+            return event;
+        }
+        let (flags, keycode, repeat) = unsafe {
+            (
+                CGEventGetFlags(event),
+                CGEventGetIntegerValueField(event, KEYBOARD_EVENT_KEYCODE) as u16,
+                CGEventGetIntegerValueField(event, KEYBOARD_EVENT_AUTOREPEAT) != 0,
+            )
+        };
+        match state
+            .bindings
+            .iter()
+            .find(|(h, _)| h.matches(flags, keycode))
+        {
+            Some((_, action)) => {
+                // Held keys repeat; act once but swallow them all
+                if !repeat {
+                    let _ = state.actions.send(*action);
+                }
+                ptr::null_mut()
+            }
+            None => event,
+        }
+    }
+
+    /// Install the tap on this thread's run loop and run it forever.
+    pub fn run(
+        bindings: Vec<(Hotkey, Action)>,
+        actions: Sender<Action>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let state = Box::into_raw(Box::new(State {
+            bindings,
+            actions,
+            port: ptr::null_mut(),
+        }));
+        unsafe {
+            let port = CGEventTapCreate(
+                SESSION_EVENT_TAP,
+                HEAD_INSERT_EVENT_TAP,
+                TAP_OPTION_DEFAULT,
+                1 << KEY_DOWN,
+                callback,
+                state as *mut c_void,
+            );
+            if port.is_null() {
+                drop(Box::from_raw(state));
+                return Err(
+                    "couldn't create the keyboard event tap; allow claude-bar under \
+                            System Settings › Privacy & Security › Accessibility"
+                        .into(),
+                );
+            }
+            (*state).port = port;
+            let source = CFMachPortCreateRunLoopSource(ptr::null(), port, 0);
+            CFRunLoopAddSource(CFRunLoopGetCurrent(), source, kCFRunLoopCommonModes);
+            CGEventTapEnable(port, true);
+            CFRunLoopRun();
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_hotkey() {
+        assert_eq!(
+            parse_hotkey("ctrl+alt+1"),
+            Ok(Hotkey {
+                modifiers: CONTROL | OPTION,
+                keycode: 18,
+            })
+        );
+        assert_eq!(parse_hotkey("⌃+⌥+P"), parse_hotkey("ctrl+alt+p"));
+        assert_eq!(
+            parse_hotkey("Control + Option + P"),
+            Ok(Hotkey {
+                modifiers: CONTROL | OPTION,
+                keycode: 35,
+            })
+        );
+        assert_eq!(parse_hotkey("cmd+shift+f12").unwrap().keycode, 111);
+        assert_eq!(parse_hotkey("⌘+space").unwrap().modifiers, COMMAND);
+        assert!(parse_hotkey("shift+a")
+            .unwrap_err()
+            .contains("needs ctrl, alt or cmd"));
+        assert!(parse_hotkey("ctrl+alt+")
+            .unwrap_err()
+            .contains("must end in"));
+        assert!(parse_hotkey("ctrl+f13").is_err());
+        assert!(parse_hotkey("ctrl+hyper+a")
+            .unwrap_err()
+            .contains("'hyper'"));
+    }
+
+    #[test]
+    fn test_hotkey_matches() {
+        let hotkey = parse_hotkey("ctrl+alt+1").unwrap();
+        // Caps lock and the non-coalesced bit don't matter
+        assert!(hotkey.matches(CONTROL | OPTION | (1 << 16) | 0x100, 18));
+        assert!(!hotkey.matches(CONTROL | OPTION | SHIFT, 18));
+        assert!(!hotkey.matches(CONTROL, 18));
+        assert!(!hotkey.matches(CONTROL | OPTION, 19));
+    }
+
+    #[test]
+    fn test_parse_bindings() {
+        let bindings = parse_bindings(&default_hotkeys()).unwrap();
+        assert_eq!(bindings.len(), 10);
+        assert!(bindings.contains(&(parse_hotkey("ctrl+alt+9").unwrap(), Action::Session(9))));
+        assert!(bindings.contains(&(parse_hotkey("ctrl+alt+p").unwrap(), Action::Pending)));
+
+        let mut hotkeys = BTreeMap::new();
+        hotkeys.insert("ctrl+alt+b".to_string(), "back".to_string());
+        hotkeys.insert("alt+ctrl+b".to_string(), "pending".to_string());
+        assert!(parse_bindings(&hotkeys)
+            .unwrap_err()
+            .contains("bound twice"));
+        assert!(parse_action("session:0").is_err());
+        assert!(parse_action("first").unwrap_err().contains("'first'"));
+    }
+
+    #[test]
+    fn test_target() {
+        let session = |tty: &str, status: Status| SessionInfo::for_test(tty, "/tmp/p", status);
+        let sessions = vec![
+            session("/dev/ttys000", Status::Active),
+            session("/dev/ttys001", Status::Pending),
+            session("/dev/ttys002", Status::Pending),
+        ];
+        let tty = |action| target(action, &sessions).map(|s| s.tty.as_str());
+        assert_eq!(tty(Action::Session(1)), Ok("/dev/ttys000"));
+        assert_eq!(tty(Action::Pending), Ok("/dev/ttys001"));
+        // No transcripts to tell how long they've waited
+        assert_eq!(tty(Action::NextPending), Ok("/dev/ttys001"));
+        assert_eq!(
+            tty(Action::Session(4)),
+            Err("No session 4 (3 running)".into())
+        );
+        assert_eq!(
+            target(Action::Pending, &sessions[..1]).unwrap_err(),
+            "No session needs input"
+        );
+    }
+}
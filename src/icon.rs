@@ -1,12 +1,23 @@
+//! Menu bar icon PNGs: a grid of status dots, drawn at @1x, @2x or @3x.
+//!
+//! `claude-bar icon` writes them to disk for previewing colors and styles.
+
+use crate::render::Colors;
 use crate::state::Status;
+use clap::ValueEnum;
 use flate2::write::ZlibEncoder;
 use flate2::Compression;
+use std::collections::VecDeque;
 use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
 
 // Colors (RGBA)
 const COLOR_ACTIVE: [u8; 4] = [0x32, 0xD7, 0x4B, 0xFF]; // #32D74B
 const COLOR_PENDING: [u8; 4] = [0xFF, 0x9F, 0x0A, 0xFF]; // #FF9F0A
 const COLOR_IDLE: [u8; 4] = [0x8E, 0x8E, 0x93, 0xFF]; // #8E8E93
+const COLOR_RATE_LIMITED: [u8; 4] = [0xBF, 0x5A, 0xF2, 0xFF]; // #BF5AF2
+const COLOR_UNKNOWN: [u8; 4] = [0x64, 0xD2, 0xFF, 0xFF]; // #64D2FF
 
 // Layout params (@2x retina)
 const DOT_DIAMETER: u32 = 10;
@@ -19,7 +30,186 @@ fn status_color(s: Status) -> [u8; 4] {
         Status::Active => COLOR_ACTIVE,
         Status::Pending => COLOR_PENDING,
         Status::Idle => COLOR_IDLE,
+        Status::RateLimited => COLOR_RATE_LIMITED,
+        Status::Unknown => COLOR_UNKNOWN,
+    }
+}
+
+/// How sessions are drawn.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum IconStyle {
+    /// One dot per session, up to three per row
+    #[default]
+    Grid,
+    /// A single dot in the color of the most urgent session
+    Aggregate,
+    /// Like grid, with a shape per status: dot (active), diamond (pending), ring (idle),
+    /// square (rate limited), bar (unknown)
+    Shapes,
+}
+
+/// Menu bar appearance the icon is drawn for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Appearance {
+    Light,
+    Dark,
+}
+
+/// Appearance hint SwiftBar passes to plugins (`Light` or `Dark`).
+pub const APPEARANCE_ENV: &str = "OS_APPEARANCE";
+
+/// Interpret an appearance hint or `AppleInterfaceStyle` value.
+pub fn parse_appearance(value: &str) -> Option<Appearance> {
+    match value.trim().to_ascii_lowercase().as_str() {
+        "dark" => Some(Appearance::Dark),
+        "light" => Some(Appearance::Light),
+        _ => None,
+    }
+}
+
+/// Current menu bar appearance: SwiftBar's hint if present, else the global
+/// `AppleInterfaceStyle` default, which only exists in dark mode. Cheap enough
+/// to call on every refresh so switching modes takes effect right away.
+pub fn detect_appearance() -> Appearance {
+    if let Some(hint) = std::env::var(APPEARANCE_ENV)
+        .ok()
+        .as_deref()
+        .and_then(parse_appearance)
+    {
+        return hint;
+    }
+    let output = std::process::Command::new("defaults")
+        .args(["read", "-g", "AppleInterfaceStyle"])
+        .stderr(std::process::Stdio::null())
+        .output();
+    match output {
+        Ok(out) if out.status.success() => {
+            parse_appearance(&String::from_utf8_lossy(&out.stdout)).unwrap_or(Appearance::Dark)
+        }
+        Ok(_) => Appearance::Light,
+        // Not macOS: keep the palette the icons were designed for
+        Err(_) => Appearance::Dark,
+    }
+}
+
+/// Parse `#RRGGBB` into opaque RGBA.
+pub fn parse_hex_color(hex: &str) -> Option<[u8; 4]> {
+    let digits = hex.strip_prefix('#')?;
+    if digits.len() != 6 || !digits.is_ascii() {
+        return None;
+    }
+    let channel = |i: usize| u8::from_str_radix(&digits[i..i + 2], 16).ok();
+    Some([channel(0)?, channel(2)?, channel(4)?, 0xFF])
+}
+
+/// Parse a status name for `--statuses`.
+pub fn parse_status(s: &str) -> Result<Status, String> {
+    match s.trim() {
+        "active" => Ok(Status::Active),
+        "pending" => Ok(Status::Pending),
+        "idle" => Ok(Status::Idle),
+        "rate_limited" => Ok(Status::RateLimited),
+        "unknown" => Ok(Status::Unknown),
+        other => Err(format!(
+            "unknown status '{}' (expected active, pending, idle, rate_limited or unknown)",
+            other
+        )),
+    }
+}
+
+/// Where the @`scale`x variant of `out` goes: `icon.png` -> `icon@3x.png`.
+pub fn variant_path(out: &Path, scale: u32) -> PathBuf {
+    let stem = out.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match out.extension() {
+        Some(ext) => format!("{}@{}x.{}", stem, scale, ext.to_string_lossy()),
+        None => format!("{}@{}x", stem, scale),
+    };
+    out.with_file_name(name)
+}
+
+/// `claude-bar icon`: write the @2x icon for `statuses` to `out`, plus a
+/// sibling file for each of `extra_scales`. Colors come from the config file's
+/// palette for `appearance` (detected when None).
+pub fn run_icon(
+    statuses: &[Status],
+    style: IconStyle,
+    appearance: Option<Appearance>,
+    out: &Path,
+    extra_scales: &[u32],
+) -> Result<(), Box<dyn std::error::Error>> {
+    let config = crate::config::Config::load();
+    let colors = config.palette(appearance.unwrap_or_else(detect_appearance));
+    let mut targets = vec![(out.to_path_buf(), 2)];
+    targets.extend(extra_scales.iter().map(|&s| (variant_path(out, s), s)));
+    for (path, scale) in targets {
+        let png = make_icon_png(statuses, style, colors, scale);
+        std::fs::write(&path, png).map_err(|e| format!("{}: {}", path.display(), e))?;
+        println!("{}", path.display());
+    }
+    Ok(())
+}
+
+/// The session that decides an aggregate icon: pending beats rate limited beats
+/// unknown beats active beats idle.
+fn most_urgent(statuses: &[Status]) -> Option<Status> {
+    [
+        Status::Pending,
+        Status::RateLimited,
+        Status::Unknown,
+        Status::Active,
+        Status::Idle,
+    ]
+    .into_iter()
+    .find(|s| statuses.contains(s))
+}
+
+/// Signed distance from a point (relative to the dot center) to the edge of
+/// the shape drawn for `status`; negative inside.
+fn shape_distance(style: IconStyle, status: Status, dx: f32, dy: f32, r: f32) -> f32 {
+    let dist = (dx * dx + dy * dy).sqrt();
+    match (style, status) {
+        (IconStyle::Shapes, Status::Pending) => {
+            (dx.abs() + dy.abs() - r) / std::f32::consts::SQRT_2
+        }
+        (IconStyle::Shapes, Status::Idle) => {
+            let half_width = r * RING_WIDTH / 2.0;
+            (dist - (r - half_width)).abs() - half_width
+        }
+        // Same area as the dot
+        (IconStyle::Shapes, Status::RateLimited) => dx.abs().max(dy.abs()) - r * SQUARE_HALF_SIDE,
+        (IconStyle::Shapes, Status::Unknown) => (dx.abs() - r).max(dy.abs() - r * BAR_HALF_HEIGHT),
+        _ => dist - r,
+    }
+}
+
+/// Stroke width of the idle ring in the `shapes` style, relative to the radius.
+const RING_WIDTH: f32 = 0.6;
+
+/// Half the side of the rate-limited square in the `shapes` style, relative to
+/// the radius (sqrt(pi)/2).
+const SQUARE_HALF_SIDE: f32 = 0.886;
+
+/// Half the height of the unknown bar in the `shapes` style, relative to the radius.
+const BAR_HALF_HEIGHT: f32 = 0.4;
+
+/// Subsamples per pixel axis for coverage-based anti-aliasing.
+const SUBSAMPLES: u32 = 4;
+
+/// Fraction of the pixel whose top-left corner is at (`x0`, `y0`) relative to
+/// the dot center that the shape covers.
+fn coverage(style: IconStyle, status: Status, x0: f32, y0: f32, r: f32) -> f32 {
+    let step = 1.0 / SUBSAMPLES as f32;
+    let mut inside = 0;
+    for sy in 0..SUBSAMPLES {
+        for sx in 0..SUBSAMPLES {
+            let dx = x0 + (sx as f32 + 0.5) * step;
+            let dy = y0 + (sy as f32 + 0.5) * step;
+            if shape_distance(style, status, dx, dy, r) < 0.0 {
+                inside += 1;
+            }
+        }
     }
+    inside as f32 / (SUBSAMPLES * SUBSAMPLES) as f32
 }
 
 /// Calculate grid dimensions for N dots.
@@ -28,11 +218,11 @@ fn grid_dims(n: u32) -> (u32, u32) {
         return (0, 0);
     }
     let cols = n.min(MAX_COLS);
-    let rows = (n + MAX_COLS - 1) / MAX_COLS;
+    let rows = n.div_ceil(MAX_COLS);
     (cols, rows)
 }
 
-/// Calculate image dimensions in pixels for N dots.
+/// Calculate image dimensions in pixels for N dots at @2x.
 pub fn image_dims(n: u32) -> (u32, u32) {
     if n == 0 {
         return (0, 0);
@@ -43,16 +233,79 @@ pub fn image_dims(n: u32) -> (u32, u32) {
     (w, h)
 }
 
+/// Image dimensions in pixels for N dots at a display scale (1, 2 or 3).
+pub fn image_dims_at(n: u32, scale: u32) -> (u32, u32) {
+    let (w, h) = image_dims(n);
+    let k = scale_factor(scale);
+    ((w as f32 * k).round() as u32, (h as f32 * k).round() as u32)
+}
+
+/// Multiplier from the @2x layout constants to pixels at `scale`.
+fn scale_factor(scale: u32) -> f32 {
+    scale.max(1) as f32 / 2.0
+}
+
 /// Generate a PNG dot grid for the given statuses.
 /// Returns raw PNG bytes. Empty if no statuses.
 pub fn make_dot_grid_png(statuses: &[Status]) -> Vec<u8> {
+    match draw(statuses, IconStyle::Grid, 2, status_color) {
+        Some((width, height, pixels)) => encode_png(width, height, &pixels),
+        None => Vec::new(),
+    }
+}
+
+/// Render the icon for `statuses` in `style` with the configured colors (falling
+/// back to the defaults for any that don't parse), drawn natively at `scale`
+/// (1 for non-retina, 2 for retina, 3 for newer high-density displays). Empty if
+/// no statuses.
+pub fn make_icon_png(
+    statuses: &[Status],
+    style: IconStyle,
+    colors: &Colors,
+    scale: u32,
+) -> Vec<u8> {
+    match make_icon_rgba(statuses, style, colors, scale) {
+        Some((width, height, pixels)) => encode_png(width, height, &pixels),
+        None => Vec::new(),
+    }
+}
+
+/// [`make_icon_png`] as raw RGBA pixels: (width, height, pixels). None if no
+/// statuses.
+pub fn make_icon_rgba(
+    statuses: &[Status],
+    style: IconStyle,
+    colors: &Colors,
+    scale: u32,
+) -> Option<(u32, u32, Vec<u8>)> {
+    let color = |s: Status| parse_hex_color(colors.for_status(s)).unwrap_or(status_color(s));
+    draw(statuses, style, scale, color)
+}
+
+/// Draw the RGBA buffer at `scale`, returning (width, height, pixels).
+fn draw(
+    statuses: &[Status],
+    style: IconStyle,
+    scale: u32,
+    color_of: impl Fn(Status) -> [u8; 4],
+) -> Option<(u32, u32, Vec<u8>)> {
+    let aggregate;
+    let statuses = match style {
+        IconStyle::Aggregate => {
+            aggregate = [most_urgent(statuses)?];
+            &aggregate[..]
+        }
+        _ => statuses,
+    };
     let n = statuses.len() as u32;
     if n == 0 {
-        return Vec::new();
+        return None;
     }
 
-    let (width, height) = image_dims(n);
+    let k = scale_factor(scale);
+    let (width, height) = image_dims_at(n, scale);
     let (cols, _rows) = grid_dims(n);
+    let r = DOT_DIAMETER as f32 / 2.0 * k;
 
     // Build RGBA pixel buffer
     let mut pixels = vec![0u8; (width * height * 4) as usize];
@@ -60,27 +313,22 @@ pub fn make_dot_grid_png(statuses: &[Status]) -> Vec<u8> {
     for (i, &status) in statuses.iter().enumerate() {
         let col = i as u32 % cols;
         let row = i as u32 / cols;
-        let cx = PADDING + col * (DOT_DIAMETER + DOT_SPACING) + DOT_DIAMETER / 2;
-        let cy = PADDING + row * (DOT_DIAMETER + DOT_SPACING) + DOT_DIAMETER / 2;
-        let color = status_color(status);
-        let r = DOT_DIAMETER as f32 / 2.0;
+        let cx = (PADDING + col * (DOT_DIAMETER + DOT_SPACING) + DOT_DIAMETER / 2) as f32 * k;
+        let cy = (PADDING + row * (DOT_DIAMETER + DOT_SPACING) + DOT_DIAMETER / 2) as f32 * k;
+        let color = color_of(status);
 
-        // Draw anti-aliased filled circle
-        let x_start = cx.saturating_sub(DOT_DIAMETER / 2 + 1);
-        let x_end = (cx + DOT_DIAMETER / 2 + 2).min(width);
-        let y_start = cy.saturating_sub(DOT_DIAMETER / 2 + 1);
-        let y_end = (cy + DOT_DIAMETER / 2 + 2).min(height);
+        // Only pixels the shape can touch
+        let x_start = (cx - r - 1.0).floor().max(0.0) as u32;
+        let x_end = ((cx + r + 1.0).ceil() as u32).min(width);
+        let y_start = (cy - r - 1.0).floor().max(0.0) as u32;
+        let y_end = ((cy + r + 1.0).ceil() as u32).min(height);
 
         for py in y_start..y_end {
             for px in x_start..x_end {
-                let dx = px as f32 - cx as f32 + 0.5;
-                let dy = py as f32 - cy as f32 + 0.5;
-                let dist = (dx * dx + dy * dy).sqrt();
-                // Smooth edge: 1px anti-aliasing band
-                let alpha = (r - dist + 0.5).clamp(0.0, 1.0);
+                let alpha = coverage(style, status, px as f32 - cx, py as f32 - cy, r);
                 if alpha > 0.0 {
                     let offset = ((py * width + px) * 4) as usize;
-                    let a = (alpha * color[3] as f32) as u8;
+                    let a = (alpha * color[3] as f32).round() as u8;
                     pixels[offset] = color[0];
                     pixels[offset + 1] = color[1];
                     pixels[offset + 2] = color[2];
@@ -90,7 +338,7 @@ pub fn make_dot_grid_png(statuses: &[Status]) -> Vec<u8> {
         }
     }
 
-    encode_png(width, height, &pixels)
+    Some((width, height, pixels))
 }
 
 /// Encode RGBA pixels into a PNG file.
@@ -156,18 +404,73 @@ fn crc32(chunk_type: &[u8], data: &[u8]) -> u32 {
     crc ^ 0xFFFFFFFF
 }
 
+/// Session counts up to this have their icons generated at build time.
+/// Keep in sync with `MAX_PREGENERATED` in build.rs.
+pub const MAX_PREGENERATED: usize = 5;
+
 /// Compute a lookup key for pregenerated icon table.
-/// Encodes statuses as a base-3 number (0=Active, 1=Pending, 2=Idle).
-pub fn status_key(statuses: &[Status]) -> u16 {
-    let mut key: u16 = 0;
+/// Encodes statuses as a base-[`Status::COUNT`] number of their indices
+/// (0=Active, 1=Pending, 2=Idle, 3=RateLimited, 4=Unknown), so new statuses only change the base.
+pub fn status_key(statuses: &[Status]) -> u32 {
+    let mut key: u32 = 0;
     for &s in statuses {
-        key = key * 3 + s.index() as u16;
+        key = key * Status::COUNT as u32 + s.index() as u32;
     }
     key
 }
 
+/// Small least-recently-used cache of rendered icons, for session counts the
+/// build-time table doesn't cover.
+#[derive(Debug)]
+pub struct IconCache {
+    capacity: usize,
+    /// Least recently used first
+    entries: VecDeque<(Vec<Status>, String)>,
+}
+
+impl IconCache {
+    pub const fn new(capacity: usize) -> Self {
+        IconCache {
+            capacity,
+            entries: VecDeque::new(),
+        }
+    }
+
+    /// Cached icon for `statuses`, rendering it with `render` on a miss.
+    pub fn get_or_insert_with(
+        &mut self,
+        statuses: &[Status],
+        render: impl FnOnce() -> String,
+    ) -> String {
+        if let Some(i) = self.entries.iter().position(|(k, _)| k == statuses) {
+            let entry = self.entries.remove(i).unwrap();
+            let value = entry.1.clone();
+            self.entries.push_back(entry);
+            return value;
+        }
+        let value = render();
+        if self.entries.len() >= self.capacity {
+            self.entries.pop_front();
+        }
+        self.entries.push_back((statuses.to_vec(), value.clone()));
+        value
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+/// Runtime-rendered icons for more than [`MAX_PREGENERATED`] sessions.
+static RUNTIME_ICONS: Mutex<IconCache> = Mutex::new(IconCache::new(32));
+
 /// Get pregenerated dot grid PNG as base64 string.
-/// Falls back to runtime generation if count > 5.
+/// Counts above [`MAX_PREGENERATED`] are rendered once and then served from an
+/// LRU cache.
 pub fn get_dot_grid_base64(statuses: &[Status]) -> String {
     if statuses.is_empty() {
         return String::new();
@@ -176,17 +479,20 @@ pub fn get_dot_grid_base64(statuses: &[Status]) -> String {
     let count = statuses.len();
     let key = status_key(statuses);
 
-    // Try pregenerated table (count 1..=5)
-    if count <= 5 {
+    if count <= MAX_PREGENERATED {
         if let Some(b64) = include!(concat!(env!("OUT_DIR"), "/icon_table.rs")) {
             return b64.to_string();
         }
     }
 
     // Fallback: runtime generation
-    use base64::Engine;
-    let png = make_dot_grid_png(statuses);
-    base64::engine::general_purpose::STANDARD.encode(&png)
+    RUNTIME_ICONS
+        .lock()
+        .unwrap()
+        .get_or_insert_with(statuses, || {
+            use base64::Engine;
+            base64::engine::general_purpose::STANDARD.encode(make_dot_grid_png(statuses))
+        })
 }
 
 #[cfg(test)]
@@ -271,30 +577,39 @@ mod tests {
         // Two statuses
         assert_eq!(status_key(&[Status::Active, Status::Active]), 0);
         assert_eq!(status_key(&[Status::Active, Status::Pending]), 1);
-        assert_eq!(status_key(&[Status::Idle, Status::Idle]), 8); // 2*3+2
+        assert_eq!(status_key(&[Status::RateLimited]), 3);
+        assert_eq!(status_key(&[Status::Unknown]), 4);
+        assert_eq!(status_key(&[Status::Idle, Status::Idle]), 12); // 2*5+2
 
         // All keys for count=2 should be unique
-        let all_statuses = [Status::Active, Status::Pending, Status::Idle];
+        let all_statuses = [
+            Status::Active,
+            Status::Pending,
+            Status::Idle,
+            Status::RateLimited,
+            Status::Unknown,
+        ];
         let mut keys = std::collections::HashSet::new();
         for &a in &all_statuses {
             for &b in &all_statuses {
                 keys.insert(status_key(&[a, b]));
             }
         }
-        assert_eq!(keys.len(), 9); // 3^2
+        assert_eq!(keys.len(), 25); // 5^2
     }
 
     #[test]
     fn test_pregenerated_table_complete() {
-        // Verify all count=1..5 combinations have entries
-        for count in 1..=5u32 {
-            let total = 3u32.pow(count);
+        // Verify all count=1..=MAX_PREGENERATED combinations have entries
+        let base = Status::COUNT as u32;
+        for count in 1..=MAX_PREGENERATED as u32 {
+            let total = base.pow(count);
             for combo in 0..total {
                 let mut statuses = Vec::new();
                 let mut v = combo;
                 for _ in 0..count {
-                    statuses.push(Status::from_index((v % 3) as u8).unwrap());
-                    v /= 3;
+                    statuses.push(Status::from_index((v % base) as u8).unwrap());
+                    v /= base;
                 }
                 let b64 = get_dot_grid_base64(&statuses);
                 assert!(
@@ -305,6 +620,40 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_icon_cache_evicts_least_recently_used() {
+        let mut cache = IconCache::new(2);
+        let mut renders = 0;
+        let mut get = |cache: &mut IconCache, statuses: &[Status]| {
+            cache.get_or_insert_with(statuses, || {
+                renders += 1;
+                format!("{:?}", statuses)
+            })
+        };
+        let a = [Status::Active; 7];
+        let b = [Status::Idle; 7];
+        let c = [Status::Pending; 8];
+
+        get(&mut cache, &a);
+        get(&mut cache, &b);
+        assert_eq!(get(&mut cache, &a), format!("{:?}", a)); // hit; b is now oldest
+        get(&mut cache, &c); // evicts b
+        get(&mut cache, &a); // still cached
+        get(&mut cache, &b); // rendered again
+        assert_eq!(renders, 4);
+        assert_eq!(cache.len(), 2);
+    }
+
+    #[test]
+    fn test_large_counts_render_at_runtime() {
+        use base64::Engine;
+        let statuses = [Status::Pending; 9];
+        let expected =
+            base64::engine::general_purpose::STANDARD.encode(make_dot_grid_png(&statuses));
+        assert_eq!(get_dot_grid_base64(&statuses), expected);
+        assert_eq!(get_dot_grid_base64(&statuses), expected);
+    }
+
     #[test]
     fn test_pregenerated_matches_runtime() {
         use base64::Engine;
@@ -324,6 +673,137 @@ mod tests {
         }
     }
 
+    fn png_dims(png: &[u8]) -> (u32, u32) {
+        let be = |at: usize| u32::from_be_bytes([png[at], png[at + 1], png[at + 2], png[at + 3]]);
+        (be(16), be(20))
+    }
+
+    #[test]
+    fn test_icon_styles() {
+        let colors = Colors::default();
+        let mixed = [Status::Idle, Status::Pending, Status::Active];
+
+        // Default colors in the grid style match the pregenerated icons
+        assert_eq!(
+            make_icon_png(&mixed, IconStyle::Grid, &colors, 2),
+            make_dot_grid_png(&mixed)
+        );
+
+        // Aggregate: one dot in the most urgent color
+        let aggregate = make_icon_png(&mixed, IconStyle::Aggregate, &colors, 2);
+        assert_eq!(png_dims(&aggregate), image_dims(1));
+        assert_eq!(aggregate, make_dot_grid_png(&[Status::Pending]));
+        assert!(make_icon_png(&[], IconStyle::Aggregate, &colors, 2).is_empty());
+        // A rate-limited session outranks active and idle ones
+        assert_eq!(
+            make_icon_png(
+                &[Status::Active, Status::RateLimited, Status::Idle],
+                IconStyle::Aggregate,
+                &colors,
+                2
+            ),
+            make_dot_grid_png(&[Status::RateLimited])
+        );
+
+        // Shapes: same layout, different pixels for pending and idle only
+        let shapes = make_icon_png(&mixed, IconStyle::Shapes, &colors, 2);
+        assert_eq!(png_dims(&shapes), image_dims(3));
+        assert_ne!(shapes, make_dot_grid_png(&mixed));
+        assert_eq!(
+            make_icon_png(&[Status::Active], IconStyle::Shapes, &colors, 2),
+            make_dot_grid_png(&[Status::Active])
+        );
+        assert_ne!(
+            make_icon_png(&[Status::RateLimited], IconStyle::Shapes, &colors, 2),
+            make_dot_grid_png(&[Status::RateLimited])
+        );
+        assert_ne!(
+            make_icon_png(&[Status::Unknown], IconStyle::Shapes, &colors, 2),
+            make_dot_grid_png(&[Status::Unknown])
+        );
+
+        // Custom colors change the output
+        let custom = Colors {
+            active: "#0000FF".into(),
+            ..Colors::default()
+        };
+        assert_ne!(
+            make_icon_png(&[Status::Active], IconStyle::Grid, &custom, 2),
+            make_dot_grid_png(&[Status::Active])
+        );
+    }
+
+    #[test]
+    fn test_scaled_variants() {
+        let colors = Colors::default();
+        let four = [Status::Active; 4];
+        assert_eq!(image_dims_at(4, 2), image_dims(4));
+        assert_eq!(image_dims_at(4, 1), (22, 15));
+        assert_eq!(image_dims_at(4, 3), (66, 45));
+        for scale in 1..=3 {
+            let png = make_icon_png(&four, IconStyle::Grid, &colors, scale);
+            assert_eq!(png_dims(&png), image_dims_at(4, scale));
+        }
+    }
+
+    #[test]
+    fn test_coverage_antialiasing() {
+        let r = 5.0;
+        // Well inside, well outside, and a pixel straddling the edge
+        assert_eq!(
+            coverage(IconStyle::Grid, Status::Active, -0.5, -0.5, r),
+            1.0
+        );
+        assert_eq!(coverage(IconStyle::Grid, Status::Active, 6.0, 6.0, r), 0.0);
+        let edge = coverage(IconStyle::Grid, Status::Active, 4.5, -0.5, r);
+        assert!(edge > 0.0 && edge < 1.0, "edge coverage {}", edge);
+        // The ring leaves the center empty
+        assert_eq!(
+            coverage(IconStyle::Shapes, Status::Idle, -0.5, -0.5, r),
+            0.0
+        );
+    }
+
+    #[test]
+    fn test_parse_status_and_variant_path() {
+        assert_eq!(parse_status("pending"), Ok(Status::Pending));
+        assert!(parse_status("busy").unwrap_err().contains("busy"));
+        assert_eq!(
+            variant_path(Path::new("/tmp/icon.png"), 1),
+            PathBuf::from("/tmp/icon@1x.png")
+        );
+        assert_eq!(variant_path(Path::new("icon"), 3), PathBuf::from("icon@3x"));
+    }
+
+    #[test]
+    fn test_light_palette_keeps_idle_visible() {
+        let config = crate::config::Config::default();
+        assert_eq!(parse_appearance("Dark\n"), Some(Appearance::Dark));
+        assert_eq!(parse_appearance("light"), Some(Appearance::Light));
+        assert_eq!(parse_appearance(""), None);
+
+        // The light idle grey is darker than the dark-mode one
+        let luma = |hex: &str| {
+            let [r, g, b, _] = parse_hex_color(hex).unwrap();
+            r as u32 * 299 + g as u32 * 587 + b as u32 * 114
+        };
+        let light = config.palette(Appearance::Light);
+        let dark = config.palette(Appearance::Dark);
+        assert!(luma(&light.idle) < luma(&dark.idle));
+        assert_ne!(
+            make_icon_png(&[Status::Idle], IconStyle::Grid, light, 2),
+            make_icon_png(&[Status::Idle], IconStyle::Grid, dark, 2)
+        );
+    }
+
+    #[test]
+    fn test_parse_hex_color() {
+        assert_eq!(parse_hex_color("#32D74B"), Some(COLOR_ACTIVE));
+        assert_eq!(parse_hex_color("32D74B"), None);
+        assert_eq!(parse_hex_color("#32D7"), None);
+        assert_eq!(parse_hex_color("#GGGGGG"), None);
+    }
+
     #[test]
     fn test_png_has_correct_chunks() {
         let png = make_dot_grid_png(&[Status::Active]);
@@ -0,0 +1,56 @@
+//! Claude Code / Codex session discovery and status detection.
+//!
+//! The `claude-bar` binary is a thin CLI over this library; other tools can call
+//! [`poll_sessions`] for the live session list, [`SessionMonitor`] for a
+//! callback on every status change, or [`analyze_transcript`] for a single
+//! transcript without shelling out to `claude-bar poll`.
+
+pub mod activity;
+pub mod bench;
+pub mod config;
+pub mod container;
+pub mod daemon;
+pub mod deeplink;
+pub mod error;
+pub mod events;
+pub mod export;
+pub mod ffi;
+pub mod filter;
+pub mod focus;
+pub mod focusback;
+pub mod hook;
+pub mod hooklog;
+pub mod hotkey;
+pub mod icon;
+pub mod logging;
+pub mod monitor;
+pub mod notifier;
+pub mod paths;
+pub mod peer;
+pub mod permissions;
+pub mod process;
+pub mod protocol;
+pub mod remote;
+pub mod render;
+pub mod replay;
+pub mod screen;
+pub mod send;
+pub mod serve;
+pub mod settings;
+pub mod state;
+pub mod streamdeck;
+pub mod tail;
+pub mod terminal;
+pub mod tls;
+pub mod transcript;
+pub mod tray;
+pub mod update;
+pub mod websocket;
+
+pub use events::SessionChange;
+pub use monitor::SessionMonitor;
+pub use serve::{poll_sessions, poll_sessions_with};
+pub use state::{Provider, SessionInfo, Status, Terminal};
+pub use transcript::{
+    analyze_transcript, analyze_transcript_with, Thresholds, TokenUsage, TranscriptSummary,
+};
@@ -0,0 +1,374 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Rotate the log once it grows past this size.
+pub const MAX_LOG_BYTES: u64 = 1024 * 1024;
+/// Number of rotated files kept (`daemon.log.1` .. `daemon.log.N`).
+pub const KEEP_ROTATED: usize = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Debug,
+    Info,
+    Warn,
+    Error,
+}
+
+impl Level {
+    pub fn parse(s: &str) -> Option<Level> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "debug" => Some(Level::Debug),
+            "info" => Some(Level::Info),
+            "warn" | "warning" => Some(Level::Warn),
+            "error" => Some(Level::Error),
+            _ => None,
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            Level::Debug => "DEBUG",
+            Level::Info => "INFO",
+            Level::Warn => "WARN",
+            Level::Error => "ERROR",
+        }
+    }
+}
+
+struct Logger {
+    path: PathBuf,
+    file: File,
+    size: u64,
+    min_level: Level,
+    /// Last entry written and how many identical entries were suppressed after it
+    last: Option<(Level, String, String)>,
+    repeats: u64,
+}
+
+/// Process-wide logger; `None` until [`init`] is called (only the daemon logs).
+static LOGGER: Mutex<Option<Logger>> = Mutex::new(None);
+
+/// `daemon.log` in [`crate::paths::log_dir`]: `~/Library/Logs/claude-bar/daemon.log` on macOS
+pub fn default_log_path() -> PathBuf {
+    crate::paths::log_dir().join("daemon.log")
+}
+
+/// Minimum level from `CLAUDE_BAR_LOG` (debug/info/warn/error), default info.
+pub fn level_from_env() -> Level {
+    std::env::var("CLAUDE_BAR_LOG")
+        .ok()
+        .and_then(|v| Level::parse(&v))
+        .unwrap_or(Level::Info)
+}
+
+/// Start writing log lines to `path`, creating parent directories as needed.
+pub fn init(path: &Path, min_level: Level) -> std::io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let file = OpenOptions::new().create(true).append(true).open(path)?;
+    let size = file.metadata()?.len();
+    *LOGGER.lock().unwrap() = Some(Logger {
+        path: path.to_path_buf(),
+        file,
+        size,
+        min_level,
+        last: None,
+        repeats: 0,
+    });
+    Ok(())
+}
+
+pub fn log(level: Level, target: &str, msg: &str) {
+    let mut guard = LOGGER.lock().unwrap();
+    let Some(logger) = guard.as_mut() else {
+        return;
+    };
+    if level < logger.min_level {
+        return;
+    }
+    // Collapse runs of identical entries (e.g. the same lsof failure every poll)
+    let entry = (level, target.to_string(), msg.to_string());
+    if logger.last.as_ref() == Some(&entry) {
+        logger.repeats += 1;
+        return;
+    }
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    if let Some((last_level, last_target, _)) = &logger.last {
+        if logger.repeats > 0 {
+            let note = format!("previous message repeated {} times", logger.repeats);
+            let line = format_line(now, *last_level, last_target, &note);
+            logger.write(&line);
+        }
+    }
+    logger.last = Some(entry);
+    logger.repeats = 0;
+    logger.write(&format_line(now, level, target, msg));
+}
+
+impl Logger {
+    fn write(&mut self, line: &str) {
+        if self.size + line.len() as u64 > MAX_LOG_BYTES && self.size > 0 {
+            rotate(&self.path, KEEP_ROTATED);
+            match OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&self.path)
+            {
+                Ok(file) => {
+                    self.file = file;
+                    self.size = 0;
+                }
+                Err(_) => return,
+            }
+        }
+        if self.file.write_all(line.as_bytes()).is_ok() {
+            self.size += line.len() as u64;
+        }
+    }
+}
+
+pub fn debug(target: &str, msg: &str) {
+    log(Level::Debug, target, msg);
+}
+
+pub fn info(target: &str, msg: &str) {
+    log(Level::Info, target, msg);
+}
+
+pub fn warn(target: &str, msg: &str) {
+    log(Level::Warn, target, msg);
+}
+
+pub fn error(target: &str, msg: &str) {
+    log(Level::Error, target, msg);
+}
+
+/// One log line: `2026-01-02T03:04:05Z WARN  process: lsof: ...\n`.
+/// Newlines in `msg` are escaped so every entry stays on one line.
+pub fn format_line(unix_secs: u64, level: Level, target: &str, msg: &str) -> String {
+    format!(
+        "{} {:<5} {}: {}\n",
+        format_timestamp(unix_secs),
+        level.as_str(),
+        target,
+        msg.trim_end().replace('\n', "\\n")
+    )
+}
+
+/// RFC 3339 UTC timestamp for a Unix time.
+pub fn format_timestamp(unix_secs: u64) -> String {
+    let days = (unix_secs / 86400) as i64;
+    let rem = unix_secs % 86400;
+
+    // Civil-from-days (Howard Hinnant)
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        rem / 3600,
+        (rem % 3600) / 60,
+        rem % 60
+    )
+}
+
+/// Unix time of an RFC 3339 timestamp such as `2025-06-01T12:34:56.789Z` or
+/// `2025-06-01T14:34:56+02:00`; fractional seconds are dropped.
+pub fn parse_timestamp(s: &str) -> Option<u64> {
+    let (date, time) = s.trim().split_once(['T', ' '])?;
+    let mut ymd = date.splitn(3, '-').map(|p| p.parse::<i64>().ok());
+    let (year, month, day) = (ymd.next()??, ymd.next()??, ymd.next()??);
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+
+    let (clock, offset_secs) = match time.find(['Z', 'z', '+', '-']) {
+        Some(i) => {
+            let zone = &time[i..];
+            let offset = match zone {
+                "Z" | "z" => 0,
+                _ => {
+                    let sign = if zone.starts_with('-') { -1 } else { 1 };
+                    let (h, m) = zone[1..].split_once(':')?;
+                    sign * (h.parse::<i64>().ok()? * 3600 + m.parse::<i64>().ok()? * 60)
+                }
+            };
+            (&time[..i], offset)
+        }
+        None => (time, 0),
+    };
+    let clock = clock.split('.').next()?;
+    let mut hms = clock.splitn(3, ':').map(|p| p.parse::<i64>().ok());
+    let (hour, min, sec) = (hms.next()??, hms.next()??, hms.next()??);
+
+    // Days-from-civil (Howard Hinnant)
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y.div_euclid(400);
+    let yoe = y.rem_euclid(400);
+    let mp = if month > 2 { month - 3 } else { month + 9 };
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = era * 146097 + doe - 719468;
+
+    let secs = days * 86400 + hour * 3600 + min * 60 + sec - offset_secs;
+    u64::try_from(secs).ok()
+}
+
+fn rotated_path(path: &Path, n: usize) -> PathBuf {
+    let mut name = path.as_os_str().to_os_string();
+    name.push(format!(".{}", n));
+    PathBuf::from(name)
+}
+
+/// Shift `log` -> `log.1` -> `log.2` ..., dropping anything past `keep`.
+pub fn rotate(path: &Path, keep: usize) {
+    if keep == 0 {
+        let _ = fs::remove_file(path);
+        return;
+    }
+    let _ = fs::remove_file(rotated_path(path, keep));
+    for n in (1..keep).rev() {
+        let _ = fs::rename(rotated_path(path, n), rotated_path(path, n + 1));
+    }
+    let _ = fs::rename(path, rotated_path(path, 1));
+}
+
+/// Last `n` lines of `content`.
+pub fn tail_lines(content: &str, n: usize) -> Vec<&str> {
+    let lines: Vec<&str> = content.lines().collect();
+    lines[lines.len().saturating_sub(n)..].to_vec()
+}
+
+/// Print the last `lines` log entries, then keep printing new ones with `follow`.
+pub fn run_logs(lines: usize, follow: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let path = default_log_path();
+    let content = fs::read_to_string(&path).unwrap_or_default();
+    if content.is_empty() && !follow {
+        eprintln!("No daemon log at {}", path.display());
+        return Ok(());
+    }
+    for line in tail_lines(&content, lines) {
+        println!("{}", line);
+    }
+    if !follow {
+        return Ok(());
+    }
+
+    let mut pos = content.len() as u64;
+    loop {
+        std::thread::sleep(Duration::from_millis(500));
+        let Ok(mut file) = File::open(&path) else {
+            continue;
+        };
+        let len = file.metadata()?.len();
+        if len < pos {
+            // Rotated: start over from the new file
+            pos = 0;
+        }
+        if len == pos {
+            continue;
+        }
+        file.seek(SeekFrom::Start(pos))?;
+        let mut chunk = String::new();
+        file.read_to_string(&mut chunk)?;
+        pos += chunk.len() as u64;
+        print!("{}", chunk);
+        std::io::stdout().flush()?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_timestamp() {
+        assert_eq!(format_timestamp(0), "1970-01-01T00:00:00Z");
+        assert_eq!(format_timestamp(951782400), "2000-02-29T00:00:00Z");
+        assert_eq!(format_timestamp(1700000000), "2023-11-14T22:13:20Z");
+    }
+
+    #[test]
+    fn test_parse_timestamp() {
+        assert_eq!(parse_timestamp("1970-01-01T00:00:00Z"), Some(0));
+        assert_eq!(parse_timestamp("2000-02-29T00:00:00Z"), Some(951782400));
+        assert_eq!(
+            parse_timestamp("2023-11-14T22:13:20.123Z"),
+            Some(1700000000)
+        );
+        assert_eq!(
+            parse_timestamp("2023-11-15T00:13:20+02:00"),
+            Some(1700000000)
+        );
+        assert_eq!(
+            parse_timestamp("2023-11-14T17:13:20-05:00"),
+            Some(1700000000)
+        );
+        for t in [0, 951782400, 1700000000, 4102444800] {
+            assert_eq!(parse_timestamp(&format_timestamp(t)), Some(t));
+        }
+        assert_eq!(parse_timestamp("2023-13-01T00:00:00Z"), None);
+        assert_eq!(parse_timestamp("yesterday"), None);
+        assert_eq!(parse_timestamp(""), None);
+    }
+
+    #[test]
+    fn test_format_line_escapes_newlines() {
+        let line = format_line(0, Level::Warn, "applescript", "line one\nline two\n");
+        assert_eq!(
+            line,
+            "1970-01-01T00:00:00Z WARN  applescript: line one\\nline two\n"
+        );
+    }
+
+    #[test]
+    fn test_level_parse_and_order() {
+        assert_eq!(Level::parse("DEBUG"), Some(Level::Debug));
+        assert_eq!(Level::parse("warning"), Some(Level::Warn));
+        assert_eq!(Level::parse("loud"), None);
+        assert!(Level::Debug < Level::Info && Level::Warn < Level::Error);
+    }
+
+    #[test]
+    fn test_rotate_shifts_and_drops_oldest() {
+        let dir = tempfile::tempdir().unwrap();
+        let log = dir.path().join("daemon.log");
+        fs::write(&log, "current").unwrap();
+        fs::write(rotated_path(&log, 1), "one").unwrap();
+        fs::write(rotated_path(&log, 2), "two").unwrap();
+
+        rotate(&log, 2);
+
+        assert!(!log.exists());
+        assert_eq!(
+            fs::read_to_string(rotated_path(&log, 1)).unwrap(),
+            "current"
+        );
+        assert_eq!(fs::read_to_string(rotated_path(&log, 2)).unwrap(), "one");
+        assert!(!rotated_path(&log, 3).exists());
+    }
+
+    #[test]
+    fn test_tail_lines() {
+        assert_eq!(tail_lines("a\nb\nc\n", 2), vec!["b", "c"]);
+        assert_eq!(tail_lines("a\n", 5), vec!["a"]);
+        assert!(tail_lines("", 5).is_empty());
+    }
+}
@@ -1,23 +1,22 @@
-mod focus;
-mod hook;
-#[cfg(test)]
-mod icon;
-mod process;
-mod serve;
-mod settings;
-mod state;
-mod terminal;
-mod transcript;
-
-use clap::{Parser, Subcommand};
+use clap::{CommandFactory, Parser, Subcommand, ValueHint};
+use claude_bar::{
+    bench, config, daemon, deeplink, error, export, filter, focus, focusback, hook, hooklog,
+    hotkey, icon, logging, paths, peer, render, replay, send, serve, settings, state, tail, tray,
+    update,
+};
 use std::path::PathBuf;
 
 #[derive(Parser)]
 #[command(
     name = "claude-bar",
-    about = "Claude Code session status for macOS menu bar"
+    about = "Claude Code session status for macOS menu bar",
+    after_help = "Examples:\n  claude-bar poll --format alfred\n  claude-bar focus --project api\n  claude-bar menu set collapse-idle on\n  claude-bar completions zsh > ~/.zfunc/_claude-bar"
 )]
 struct Cli {
+    /// On failure, print `{"error":{"kind":..,"message":..,"exit_code":..}}` to
+    /// stderr instead of `Error: ...`
+    #[arg(long, global = true)]
+    json_errors: bool,
     #[command(subcommand)]
     command: Commands,
 }
@@ -25,45 +24,404 @@ struct Cli {
 #[derive(Subcommand)]
 enum Commands {
     /// Poll sessions once and output JSON to stdout
-    Poll,
-    /// SessionStart hook: read stdin JSON, write session state file
+    Poll {
+        /// Output format
+        #[arg(long, value_enum, default_value_t = render::OutputFormat::Json)]
+        format: render::OutputFormat,
+        /// Add each session's status inputs (transcript age, last role, pending
+        /// tool, plan mode) and the rule that decided it; JSON only
+        #[arg(long, conflicts_with = "format")]
+        verbose: bool,
+        #[command(flatten)]
+        filter: filter::SessionFilter,
+        /// Print only these session fields (e.g. tty,status,cwd); JSON only
+        #[arg(long, value_delimiter = ',', conflicts_with_all = ["format", "verbose"])]
+        fields: Vec<String>,
+        /// Indent the JSON output
+        #[arg(long)]
+        pretty: bool,
+        /// One line per session (`ttys003  pending  myapp  Bash`) instead of JSON
+        #[arg(long, conflicts_with_all = ["format", "verbose", "fields", "pretty"])]
+        compact: bool,
+    },
+    /// Run the polling daemon and serve session state over a Unix socket
+    Serve {
+        /// Poll interval in seconds (overrides poll_interval_secs in the config file)
+        #[arg(long)]
+        interval: Option<f64>,
+        /// Also push status frames to Stream Deck clients over WebSocket on this port
+        #[arg(long)]
+        websocket_port: Option<u16>,
+        /// Serve this machine's sessions to peer daemons on a loopback ADDR, e.g. for an
+        /// SSH tunnel (127.0.0.1:7878); other machines use --listen
+        #[arg(long, value_name = "ADDR")]
+        listen_tcp: Option<String>,
+        /// Serve this machine's sessions over TLS to peers holding the shared token (e.g. 0.0.0.0:7879)
+        #[arg(long, value_name = "ADDR", requires_all = ["auth_token_file", "tls_cert", "tls_key"])]
+        listen: Option<String>,
+        /// File with the token shared by --listen and TLS peers (must not be readable by others)
+        #[arg(long, value_name = "FILE", value_hint = ValueHint::FilePath)]
+        auth_token_file: Option<PathBuf>,
+        /// PEM certificate for --listen
+        #[arg(long, value_name = "FILE", value_hint = ValueHint::FilePath)]
+        tls_cert: Option<PathBuf>,
+        /// PEM private key for --listen
+        #[arg(long, value_name = "FILE", value_hint = ValueHint::FilePath)]
+        tls_key: Option<PathBuf>,
+        /// Merge sessions from another machine's daemon ([label=]host:port[#sha256-fingerprint], repeatable)
+        #[arg(long = "peer", value_name = "PEER", value_parser = peer::parse_peer)]
+        peers: Vec<peer::Peer>,
+    },
+    /// Talk to a running `serve` daemon
+    Daemon {
+        #[command(subcommand)]
+        command: DaemonCommand,
+    },
+    /// Show the daemon log (~/Library/Logs/claude-bar/daemon.log)
+    Logs {
+        /// Number of trailing lines to print
+        #[arg(short = 'n', long, default_value_t = 50)]
+        lines: usize,
+        /// Keep printing new lines as they are written
+        #[arg(short, long)]
+        follow: bool,
+    },
+    /// SessionStart hook: read stdin JSON, write session state file (always exits 0)
     Hook,
     /// Focus a terminal window
     Focus {
-        /// Terminal type: iterm2 or alacritty
-        #[arg(long)]
-        terminal: String,
+        /// Terminal type (iterm2, alacritty, jetbrains:<ide>, cursor or unknown)
+        #[arg(
+            long,
+            value_parser = focus::terminal_parser(),
+            required_unless_present_any = ["project", "pick", "back", "next_pending"]
+        )]
+        terminal: Option<String>,
         /// TTY device path (e.g. /dev/ttys000)
         #[arg(long, default_value = "")]
         tty: String,
         /// Working directory (used for Alacritty window matching)
         #[arg(long, default_value = "")]
         cwd: String,
+        /// The terminal's id for the tab (`terminal_id` in `poll` output); focuses
+        /// it directly instead of searching by TTY
+        #[arg(long, requires = "terminal")]
+        terminal_id: Option<String>,
+        /// Focus the live session whose project directory name matches
+        #[arg(long, conflicts_with = "terminal")]
+        project: Option<String>,
+        /// Choose interactively when several live sessions match
+        #[arg(long, conflicts_with = "terminal")]
+        pick: bool,
+        /// Return to the app (and window) that was in front before the last focus;
+        /// again to toggle back (macOS)
+        #[arg(long, conflicts_with_all = ["terminal", "project", "pick"])]
+        back: bool,
+        /// Focus the session that has been waiting for input longest
+        #[arg(long, conflicts_with_all = ["terminal", "project", "pick", "back"])]
+        next_pending: bool,
+    },
+    /// Write the menu bar icon for a set of statuses to a PNG file
+    Icon {
+        /// Session statuses, in order (e.g. active,pending,idle)
+        #[arg(long, value_delimiter = ',', value_parser = icon::parse_status, required = true)]
+        statuses: Vec<claude_bar::Status>,
+        /// Output path for the @2x PNG
+        #[arg(long, value_hint = ValueHint::FilePath)]
+        out: PathBuf,
+        /// How sessions are drawn
+        #[arg(long, value_enum, default_value_t = icon::IconStyle::Grid)]
+        style: icon::IconStyle,
+        /// Menu bar appearance to pick colors for (detected when omitted)
+        #[arg(long, value_enum)]
+        appearance: Option<icon::Appearance>,
+        /// Also write a @1x variant next to it (icon.png -> icon@1x.png)
+        #[arg(long = "1x")]
+        one_x: bool,
+        /// Also write a @3x variant next to it (icon.png -> icon@3x.png)
+        #[arg(long = "3x")]
+        three_x: bool,
     },
-    /// Install/update Claude hook entries in settings.json
+    /// Show sessions in the Linux desktop tray (StatusNotifierItem; needs the `tray` feature)
+    Tray {
+        /// Seconds between refreshes
+        #[arg(long, default_value_t = 2.0)]
+        interval: f64,
+        /// How sessions are drawn
+        #[arg(long, value_enum, default_value_t = icon::IconStyle::Grid)]
+        style: icon::IconStyle,
+    },
+    /// Focus sessions with global keyboard shortcuts (`hotkeys` in the config; macOS, needs Accessibility access)
+    HotkeyDaemon,
+    /// Change dropdown and icon options (`menu` in ~/.config/claude-bar/config.json)
+    Menu {
+        #[command(subcommand)]
+        command: MenuCommand,
+    },
+    /// Handle a claude-bar:// URL (e.g. claude-bar://focus?tty=ttys003)
+    OpenUrl {
+        /// URL to handle
+        url: String,
+    },
+    /// Register or remove the claude-bar hook in Claude Code's settings
+    Hooks {
+        #[command(subcommand)]
+        command: HooksCommand,
+    },
+    /// Install/update Claude hook entries in settings.json (use `hooks install`)
+    #[command(hide = true)]
     HooksInstall {
         /// Hook command to register under SessionStart
         #[arg(long)]
         command: String,
-        /// Optional settings path (defaults to ~/.claude/settings.json)
-        #[arg(long)]
+        /// Optional settings path (defaults to settings.json in $CLAUDE_CONFIG_DIR or ~/.claude)
+        #[arg(long, value_hint = ValueHint::FilePath)]
         settings: Option<PathBuf>,
     },
-    /// Remove Claude Bar-managed hook entries from settings.json
+    /// Remove Claude Bar-managed hook entries from settings.json (use `hooks uninstall`)
+    #[command(hide = true)]
     HooksUninstall {
-        /// Optional settings path (defaults to ~/.claude/settings.json)
+        /// Optional settings path (defaults to settings.json in $CLAUDE_CONFIG_DIR or ~/.claude)
+        #[arg(long, value_hint = ValueHint::FilePath)]
+        settings: Option<PathBuf>,
+    },
+    /// Type a prompt into a session's terminal (tmux, iTerm2 or Alacritty)
+    Send {
+        /// TTY of the session (e.g. ttys003 or /dev/ttys003)
+        #[arg(long)]
+        tty: String,
+        /// Text to type
+        #[arg(long, required_unless_present = "action", conflicts_with = "action")]
+        text: Option<String>,
+        /// Quick reply instead of --text
+        #[arg(long, value_enum)]
+        action: Option<send::QuickAction>,
+        /// Type the text without pressing Return
+        #[arg(long)]
+        no_enter: bool,
+    },
+    /// Approve or deny the permission prompt a pending session is waiting on
+    Respond {
+        /// TTY of the session (e.g. ttys003 or /dev/ttys003)
+        #[arg(long)]
+        tty: String,
+        #[arg(value_enum)]
+        response: send::Response,
+    },
+    /// Show a session's recent transcript entries in readable form
+    Tail {
+        /// TTY of the session (e.g. ttys003 or /dev/ttys003)
+        #[arg(long)]
+        tty: String,
+        /// Number of lines to print
+        #[arg(short = 'n', long, default_value_t = 20)]
+        lines: usize,
+        /// Keep printing new entries as they are written
+        #[arg(short, long)]
+        follow: bool,
+    },
+    /// Decide the status of a saved transcript (or a directory of them) and explain why
+    Replay {
+        /// Claude transcript (.jsonl), or a directory of them; `<name>.<status>.jsonl`
+        /// fixtures fail the run if they get another status
+        #[arg(value_hint = ValueHint::AnyPath)]
+        path: PathBuf,
+        /// Treat each transcript as last written this many seconds ago (default: its mtime)
+        #[arg(long)]
+        age: Option<f64>,
+        /// Print the decisions as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Export live sessions as JSON or CSV
+    Export {
+        #[arg(long, value_enum, default_value_t = export::ExportFormat::Json)]
+        format: export::ExportFormat,
+        /// Only sessions started at or after this time (RFC 3339, or e.g. 2h, 7d ago)
         #[arg(long)]
+        since: Option<String>,
+        /// Only sessions started at or before this time (RFC 3339, or e.g. 30m ago)
+        #[arg(long)]
+        until: Option<String>,
+        #[command(flatten)]
+        filter: filter::SessionFilter,
+    },
+    /// Check GitHub releases for a newer version and install it
+    Update {
+        /// Only report whether an update is available
+        #[arg(long)]
+        check: bool,
+    },
+    /// Print the JSON Schema of `poll` output, for validating integrations
+    Schema,
+    /// Time each phase of a poll (process discovery, terminals, transcripts, icon)
+    Bench {
+        /// Number of polls to time
+        #[arg(long, default_value_t = 5)]
+        iterations: usize,
+        /// Print the breakdown as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Print a shell completion script (e.g. `claude-bar completions zsh > _claude-bar`)
+    Completions {
+        #[arg(value_enum)]
+        shell: clap_complete::Shell,
+    },
+}
+
+#[derive(Subcommand)]
+enum MenuCommand {
+    /// Turn an option on or off
+    Set {
+        option: render::MenuToggle,
+        /// on/off, true/false, yes/no
+        #[arg(action = clap::ArgAction::Set, value_parser = clap::builder::BoolishValueParser::new())]
+        value: bool,
+    },
+    /// Add a project (directory name) to the pinned list
+    Pin { project: String },
+    /// Remove a project from the pinned list
+    Unpin { project: String },
+    /// Order dropdown entries
+    Sort { order: render::SortOrder },
+    /// Split dropdown entries into sections
+    Group { by: render::GroupBy },
+}
+
+#[derive(Subcommand)]
+enum HooksCommand {
+    /// Add the hook to a settings file, merging with what is there
+    Install {
+        /// Hook command (defaults to this binary's `hook` subcommand)
+        #[arg(long)]
+        command: Option<String>,
+        /// User settings, or .claude/settings.local.json in the current project
+        #[arg(long, value_enum, default_value_t = settings::Scope::User)]
+        scope: settings::Scope,
+        /// Settings file to edit instead of the scope's
+        #[arg(long, value_hint = ValueHint::FilePath)]
         settings: Option<PathBuf>,
+        /// Events to register for (repeatable)
+        #[arg(long = "event", value_enum, default_values_t = [settings::HookEvent::SessionStart])]
+        events: Vec<settings::HookEvent>,
+    },
+    /// Remove claude-bar hook entries from a settings file
+    Uninstall {
+        #[arg(long, value_enum, default_value_t = settings::Scope::User)]
+        scope: settings::Scope,
+        /// Settings file to edit instead of the scope's
+        #[arg(long, value_hint = ValueHint::FilePath)]
+        settings: Option<PathBuf>,
+    },
+    /// Show recent hook invocations and their outcome
+    Log {
+        /// Only this day (YYYY-MM-DD, UTC)
+        #[arg(long)]
+        date: Option<String>,
+        /// Number of entries to show
+        #[arg(short = 'n', long, default_value_t = 20)]
+        lines: usize,
+        /// Print raw JSON lines
+        #[arg(long)]
+        json: bool,
     },
 }
 
+#[derive(Subcommand)]
+enum DaemonCommand {
+    /// Report uptime, poll cost, last error and config of the running daemon
+    Status {
+        /// Print the raw health JSON instead of a report
+        #[arg(long)]
+        json: bool,
+    },
+    /// Re-read ~/.config/claude-bar/config.json and poll immediately
+    Reload,
+    /// Print the resolved daemon socket path
+    SocketPath,
+}
+
 fn main() {
     let cli = Cli::parse();
+    // `serve` migrates on its own so it can log what moved
+    if !matches!(cli.command, Commands::Serve { .. }) {
+        paths::migrate_legacy_files();
+    }
 
     let result = match cli.command {
-        Commands::Poll => run_poll(),
-        Commands::Hook => hook::run_hook(),
-        Commands::Focus { terminal, tty, cwd } => focus::run_focus(&terminal, &tty, &cwd),
+        Commands::Poll {
+            format,
+            verbose,
+            filter,
+            fields,
+            pretty,
+            compact,
+        } => run_poll(format, verbose, &filter, &fields, pretty, compact),
+        Commands::Serve {
+            interval,
+            websocket_port,
+            listen_tcp,
+            listen,
+            auth_token_file,
+            tls_cert,
+            tls_key,
+            peers,
+        } => serve::run_serve(serve::ServeConfig {
+            interval_secs: interval,
+            websocket_port,
+            listen_tcp,
+            listen,
+            auth_token_file,
+            tls_cert,
+            tls_key,
+            peers,
+            ..Default::default()
+        }),
+        Commands::Daemon { command } => match command {
+            DaemonCommand::Status { json } => daemon::run_status(json),
+            DaemonCommand::Reload => daemon::run_reload(),
+            DaemonCommand::SocketPath => daemon::run_socket_path(),
+        },
+        Commands::Logs { lines, follow } => logging::run_logs(lines, follow),
+        Commands::Hook => {
+            hook::run_hook();
+            Ok(())
+        }
+        Commands::Focus {
+            terminal,
+            tty,
+            cwd,
+            terminal_id,
+            project,
+            pick,
+            back,
+            next_pending,
+        } => match terminal {
+            Some(terminal) => focus::run_focus(&terminal, &tty, &cwd, terminal_id.as_deref()),
+            None if back => focusback::run_back(),
+            None if next_pending => focus::run_focus_next_pending(),
+            None => focus::run_focus_lookup(project.as_deref(), pick),
+        },
+        Commands::Icon {
+            statuses,
+            out,
+            style,
+            appearance,
+            one_x,
+            three_x,
+        } => {
+            let extra: Vec<u32> = [(one_x, 1), (three_x, 3)]
+                .into_iter()
+                .filter_map(|(wanted, scale)| wanted.then_some(scale))
+                .collect();
+            icon::run_icon(&statuses, style, appearance, &out, &extra)
+        }
+        Commands::Tray { interval, style } => tray::run_tray(interval, style),
+        Commands::HotkeyDaemon => hotkey::run_hotkey_daemon(),
+        Commands::Menu { command } => run_menu(command),
+        Commands::OpenUrl { url } => deeplink::run_open_url(&url),
+        Commands::Hooks { command } => run_hooks(command),
         Commands::HooksInstall { command, settings } => {
             let settings_path = settings.unwrap_or_else(settings::default_settings_path);
             settings::install_session_start_hook(&settings_path, &command).map(|_| ())
@@ -72,17 +430,170 @@ fn main() {
             let settings_path = settings.unwrap_or_else(settings::default_settings_path);
             settings::uninstall_managed_hooks(&settings_path).map(|_| ())
         }
+        Commands::Send {
+            tty,
+            text,
+            action,
+            no_enter,
+        } => send::run_send(&tty, text.as_deref(), action, !no_enter),
+        Commands::Respond { tty, response } => send::run_respond(&tty, response),
+        Commands::Tail { tty, lines, follow } => tail::run_tail(&tty, lines, follow),
+        Commands::Replay { path, age, json } => replay::run_replay(&path, age, json),
+        Commands::Export {
+            format,
+            since,
+            until,
+            filter,
+        } => run_export(format, since.as_deref(), until.as_deref(), &filter),
+        Commands::Update { check } => update::run_update(check),
+        Commands::Schema => serde_json::to_string_pretty(&state::DisplayResponse::json_schema())
+            .map(|schema| println!("{}", schema))
+            .map_err(Into::into),
+        Commands::Bench { iterations, json } => bench::run_bench(iterations, json),
+        Commands::Completions { shell } => {
+            let mut cmd = Cli::command();
+            let bin = cmd.get_name().to_string();
+            clap_complete::generate(shell, &mut cmd, bin, &mut std::io::stdout());
+            Ok(())
+        }
     };
 
     if let Err(e) = result {
-        eprintln!("Error: {}", e);
-        std::process::exit(1);
+        let error = error::classify(e.as_ref());
+        if cli.json_errors {
+            eprintln!("{}", serde_json::json!({ "error": error }));
+        } else {
+            eprintln!("Error: {}", error.message);
+        }
+        std::process::exit(error.exit_code);
     }
 }
 
-fn run_poll() -> Result<(), Box<dyn std::error::Error>> {
-    let sessions = serve::poll_sessions();
-    let json = serde_json::to_string(&sessions)?;
-    println!("{}", json);
+fn run_hooks(command: HooksCommand) -> Result<(), Box<dyn std::error::Error>> {
+    let paths_for = |scope, settings: Option<PathBuf>| -> std::io::Result<Vec<PathBuf>> {
+        match settings {
+            Some(path) => Ok(vec![path]),
+            None => Ok(settings::scope_settings_paths(
+                scope,
+                &std::env::current_dir()?,
+            )),
+        }
+    };
+    match command {
+        HooksCommand::Install {
+            command,
+            scope,
+            settings,
+            events,
+        } => {
+            let command = match command {
+                Some(command) => command,
+                None => format!("{} hook", std::env::current_exe()?.display()),
+            };
+            for path in paths_for(scope, settings)? {
+                let changed = settings::install_hooks(&path, &command, &events)?;
+                println!(
+                    "{} {}",
+                    if changed {
+                        "Updated"
+                    } else {
+                        "Already up to date:"
+                    },
+                    path.display()
+                );
+            }
+        }
+        HooksCommand::Uninstall { scope, settings } => {
+            for path in paths_for(scope, settings)? {
+                let changed = settings::uninstall_managed_hooks(&path)?;
+                println!(
+                    "{} {}",
+                    if changed {
+                        "Removed claude-bar hooks from"
+                    } else {
+                        "No claude-bar hooks in"
+                    },
+                    path.display()
+                );
+            }
+        }
+        HooksCommand::Log { date, lines, json } => {
+            hooklog::run_log(date.as_deref(), lines, json)?;
+        }
+    }
+    Ok(())
+}
+
+fn run_menu(command: MenuCommand) -> Result<(), Box<dyn std::error::Error>> {
+    let menu = config::edit_menu_options(&config::default_config_path(), |menu| match command {
+        MenuCommand::Set { option, value } => menu.set(option, value),
+        MenuCommand::Pin { project } => menu.pin(&project, true),
+        MenuCommand::Unpin { project } => menu.pin(&project, false),
+        MenuCommand::Sort { order } => menu.sort = order,
+        MenuCommand::Group { by } => menu.group = by,
+    })
+    .map_err(error::ClaudeBarError::Config)?;
+    println!("{}", serde_json::to_string(&menu)?);
+    Ok(())
+}
+
+fn run_export(
+    format: export::ExportFormat,
+    since: Option<&str>,
+    until: Option<&str>,
+    filter: &filter::SessionFilter,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let config = config::Config::load();
+    let sessions = filter.apply(serve::poll_sessions_with(&config));
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    print!(
+        "{}",
+        export::run_export(&sessions, format, since, until, now)?
+    );
+    Ok(())
+}
+
+fn run_poll(
+    format: render::OutputFormat,
+    verbose: bool,
+    filter: &filter::SessionFilter,
+    fields: &[String],
+    pretty: bool,
+    compact: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let config = config::Config::load();
+    if format == render::OutputFormat::Menu && !compact && fields.is_empty() && !verbose {
+        let (_, view) = daemon::current_view(&config, filter);
+        println!("{}", render::to_json(&view, pretty)?);
+        return Ok(());
+    }
+    let sessions = filter.apply(serve::poll_sessions_with(&config));
+    if compact {
+        print!(
+            "{}",
+            render::render_compact(&sessions, &config.colors, &config.menu)
+        );
+        return Ok(());
+    }
+    if !fields.is_empty() {
+        let output = filter::SelectedResponse {
+            version: state::SESSIONS_SCHEMA_VERSION,
+            sessions: filter::select_fields(&sessions, fields)?,
+        };
+        println!("{}", render::to_json(&output, pretty)?);
+        return Ok(());
+    }
+    if verbose {
+        let explained = serve::explain_sessions(sessions, &config.thresholds);
+        println!("{}", serde_json::to_string_pretty(&explained)?);
+        return Ok(());
+    }
+    println!(
+        "{}",
+        render::render(&sessions, format, &config.colors, &config.menu, pretty)?
+    );
     Ok(())
 }
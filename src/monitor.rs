@@ -0,0 +1,182 @@
+//! Push-style session updates for apps embedding the library.
+//!
+//! [`SessionMonitor`] runs the same discovery and status engine as `claude-bar
+//! poll` on a background thread and calls back on every transition, so a TUI
+//! or GUI doesn't need the `serve` daemon or a poll loop of its own.
+//!
+//! ```no_run
+//! use claude_bar::{config::Config, SessionChange, SessionMonitor};
+//!
+//! let monitor = SessionMonitor::start(Config::load(), |change: &SessionChange| {
+//!     println!("{}", change.describe());
+//! });
+//! // ... later; dropping the monitor stops it too
+//! monitor.stop();
+//! ```
+
+use crate::config::Config;
+use crate::events::{session_changes, SessionChange};
+use crate::serve;
+use crate::state::SessionInfo;
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::Duration;
+
+/// Background poller that reports [`SessionChange`]s to a callback.
+///
+/// The first poll reports every running session as
+/// [`SessionChange::Appeared`]; after that, only sessions that appeared,
+/// changed status or ended are reported.
+#[derive(Debug)]
+pub struct SessionMonitor {
+    shared: Arc<Shared>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+#[derive(Debug, Default)]
+struct Shared {
+    sessions: Mutex<Vec<SessionInfo>>,
+    /// Set to stop; `wake` interrupts the wait between polls
+    stopped: Mutex<bool>,
+    wake: Condvar,
+}
+
+impl SessionMonitor {
+    /// Poll every `config.poll_interval_secs` with `config`'s settings and call
+    /// `callback` on the monitor thread for each change.
+    pub fn start<F>(config: Config, callback: F) -> SessionMonitor
+    where
+        F: FnMut(&SessionChange) + Send + 'static,
+    {
+        let interval = Duration::from_secs_f64(config.poll_interval_secs);
+        Self::start_with(
+            interval,
+            move || serve::poll_sessions_with(&config),
+            callback,
+        )
+    }
+
+    fn start_with<P, F>(interval: Duration, mut poll: P, mut callback: F) -> SessionMonitor
+    where
+        P: FnMut() -> Vec<SessionInfo> + Send + 'static,
+        F: FnMut(&SessionChange) + Send + 'static,
+    {
+        let shared = Arc::new(Shared::default());
+        let thread = {
+            let shared = Arc::clone(&shared);
+            thread::Builder::new()
+                .name("claude-bar-monitor".into())
+                .spawn(move || loop {
+                    let sessions = poll();
+                    let changes = {
+                        let mut current = shared.sessions.lock().unwrap();
+                        let changes = session_changes(&current, &sessions);
+                        *current = sessions;
+                        changes
+                    };
+                    for change in &changes {
+                        callback(change);
+                    }
+                    let stopped = shared.stopped.lock().unwrap();
+                    let (stopped, _) = shared
+                        .wake
+                        .wait_timeout_while(stopped, interval, |stopped| !*stopped)
+                        .unwrap();
+                    if *stopped {
+                        return;
+                    }
+                })
+                .expect("failed to spawn session monitor")
+        };
+        SessionMonitor {
+            shared,
+            thread: Some(thread),
+        }
+    }
+
+    /// Sessions from the latest poll (empty until the first one finishes).
+    pub fn sessions(&self) -> Vec<SessionInfo> {
+        self.shared.sessions.lock().unwrap().clone()
+    }
+
+    /// Stop polling and wait for a poll in progress to finish.
+    pub fn stop(mut self) {
+        self.shutdown();
+    }
+
+    fn shutdown(&mut self) {
+        *self.shared.stopped.lock().unwrap() = true;
+        self.shared.wake.notify_all();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for SessionMonitor {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::Status;
+    use std::sync::mpsc;
+
+    fn session(tty: &str, status: Status) -> SessionInfo {
+        SessionInfo::for_test(tty, "/tmp/p", status)
+    }
+
+    #[test]
+    fn test_monitor_reports_transitions() {
+        let mut polls = vec![
+            vec![session("/dev/ttys000", Status::Active)],
+            vec![session("/dev/ttys000", Status::Active)],
+            vec![
+                session("/dev/ttys000", Status::Pending),
+                session("/dev/ttys001", Status::Idle),
+            ],
+            vec![session("/dev/ttys001", Status::Idle)],
+        ]
+        .into_iter();
+        let (tx, rx) = mpsc::channel();
+        let monitor = SessionMonitor::start_with(
+            Duration::from_millis(1),
+            move || polls.next().unwrap_or_default(),
+            move |change| tx.send(change.describe()).unwrap(),
+        );
+
+        let lines: Vec<String> = (0..5)
+            .map(|_| rx.recv_timeout(Duration::from_secs(5)).unwrap())
+            .collect();
+        assert_eq!(
+            lines,
+            vec![
+                "/dev/ttys000 p: appeared active",
+                "/dev/ttys000 p: active -> pending",
+                "/dev/ttys001 p: appeared idle",
+                "/dev/ttys000 p: gone",
+                "/dev/ttys001 p: gone",
+            ]
+        );
+        monitor.stop();
+        assert!(rx.recv_timeout(Duration::from_millis(50)).is_err());
+    }
+
+    #[test]
+    fn test_stop_interrupts_the_wait() {
+        let (tx, rx) = mpsc::channel();
+        let monitor = SessionMonitor::start_with(
+            Duration::from_secs(3600),
+            || vec![session("/dev/ttys000", Status::Idle)],
+            move |_| tx.send(()).unwrap(),
+        );
+        rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(monitor.sessions().len(), 1);
+        let started = std::time::Instant::now();
+        drop(monitor);
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+}
@@ -0,0 +1,1045 @@
+//! Notifications for sessions waiting on the user, sent by the `serve` daemon.
+//!
+//! The notifier is a consumer on the event bus. A session turning pending
+//! starts a clock, and each of `notifications.channels` fires once when the
+//! session has waited `pending_after_secs` plus that channel's `after_secs`,
+//! so a prompt can show on the Mac right away and reach a phone over ntfy only
+//! if it is still waiting minutes later. Leaving pending, or ending, stops the
+//! clock. `rules` change the delay for projects matching a glob, or silence
+//! them; the first matching rule wins. A session still pending after its
+//! first notification gets `reminders` on the channels that have fired, each
+//! gap `backoff` times the last. Between transitions the notifier wakes for
+//! the next notification due. Before sending, it asks which terminal tab is in
+//! front (macOS): a session you are already looking at is skipped, or with
+//! `when_focused: "quiet"` only shown on the Mac without a sound. While the
+//! user has been idle for `away_after_secs`, Mac notifications are held (ntfy
+//! keeps going, since that is what it's for); on their return one summary
+//! ("3 sessions finished, 1 needs input") is shown on the Mac instead.
+
+use crate::activity;
+use crate::daemon::format_duration;
+use crate::events::{SessionChange, SessionEvent};
+use crate::filter::glob_matches;
+use crate::focus::{applescript_string, run_applescript, CURSOR_BUNDLE_ID};
+use crate::focusback::{self, PreviousApp};
+use crate::logging;
+use crate::process;
+use crate::serve::Shared;
+use crate::state::{SessionInfo, Status, Terminal};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
+use std::process::Command;
+use std::sync::mpsc::RecvTimeoutError;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How long the notifier sleeps when nothing is due.
+const IDLE_WAIT: Duration = Duration::from_secs(60);
+
+/// How often the notifier checks whether the user is back.
+const AWAY_CHECK: Duration = Duration::from_secs(5);
+
+/// `notifications` in the config file.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NotifyOptions {
+    pub enabled: bool,
+    /// Seconds a session stays pending before the first channel fires
+    pub pending_after_secs: f64,
+    /// Where notifications go, each after its own extra delay
+    pub channels: Vec<Channel>,
+    /// Per-project overrides, first match wins
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub rules: Vec<NotifyRule>,
+    /// Repeats for sessions still pending after their first notification
+    pub reminders: ReminderOptions,
+    /// What to do for a session whose terminal is in front
+    pub when_focused: WhenFocused,
+    /// Seconds without keyboard or mouse input after which notifications
+    /// are held for a summary; 0 turns this off
+    pub away_after_secs: f64,
+}
+
+/// Handling of notifications for the session in the frontmost terminal tab.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WhenFocused {
+    /// Drop them (reminders still come if you look away)
+    #[default]
+    Skip,
+    /// Show them on the Mac only, without a sound
+    Quiet,
+    /// Send them as usual
+    Notify,
+}
+
+/// `notifications.reminders` in the config file.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ReminderOptions {
+    /// Seconds from the first notification to the first reminder; 0 turns
+    /// reminders off
+    pub after_secs: f64,
+    /// Each gap between reminders is this many times the previous one
+    pub backoff: f64,
+    /// Longest gap between reminders
+    pub max_interval_secs: f64,
+    /// Notification Center sound for reminders, e.g. `Glass`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sound: Option<String>,
+}
+
+impl Default for ReminderOptions {
+    fn default() -> Self {
+        ReminderOptions {
+            after_secs: 0.0,
+            backoff: 2.0,
+            max_interval_secs: 3600.0,
+            sound: None,
+        }
+    }
+}
+
+impl ReminderOptions {
+    /// Seconds between reminder `n - 1` (or the first notification, for
+    /// `n == 0`) and reminder `n`; None when reminders are off.
+    pub fn interval(&self, n: u32) -> Option<f64> {
+        if self.after_secs <= 0.0 {
+            return None;
+        }
+        let secs = self.after_secs * self.backoff.powi(n.min(64) as i32);
+        Some(secs.min(self.max_interval_secs.max(self.after_secs)))
+    }
+}
+
+impl Default for NotifyOptions {
+    fn default() -> Self {
+        NotifyOptions {
+            enabled: false,
+            pending_after_secs: 0.0,
+            channels: vec![Channel::Macos { after_secs: 0.0 }],
+            rules: Vec::new(),
+            reminders: ReminderOptions::default(),
+            when_focused: WhenFocused::Skip,
+            away_after_secs: 300.0,
+        }
+    }
+}
+
+/// A notification channel.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum Channel {
+    /// Notification Center, through `osascript`
+    Macos {
+        /// Seconds on top of `pending_after_secs`
+        #[serde(default)]
+        after_secs: f64,
+    },
+    /// An ntfy topic, posted to with `curl`
+    Ntfy {
+        topic: String,
+        #[serde(default = "default_ntfy_server")]
+        server: String,
+        #[serde(default)]
+        after_secs: f64,
+    },
+}
+
+fn default_ntfy_server() -> String {
+    "https://ntfy.sh".into()
+}
+
+impl Channel {
+    pub fn after_secs(&self) -> f64 {
+        match self {
+            Channel::Macos { after_secs } | Channel::Ntfy { after_secs, .. } => *after_secs,
+        }
+    }
+
+    /// Name for log lines, e.g. `ntfy:my-topic`.
+    pub fn name(&self) -> String {
+        match self {
+            Channel::Macos { .. } => "macos".into(),
+            Channel::Ntfy { topic, .. } => format!("ntfy:{}", topic),
+        }
+    }
+}
+
+/// Notification settings for projects whose name matches `project`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NotifyRule {
+    /// Project name glob (`*` and `?`, case-insensitive)
+    pub project: String,
+    /// Never notify for these projects
+    #[serde(default)]
+    pub never: bool,
+    /// Replaces `pending_after_secs`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pending_after_secs: Option<f64>,
+}
+
+impl NotifyOptions {
+    /// Seconds a session of `project` must wait before the first channel
+    /// fires; None when a rule silences the project.
+    pub fn delay_for(&self, project: &str) -> Option<f64> {
+        match self
+            .rules
+            .iter()
+            .find(|rule| glob_matches(&rule.project, project))
+        {
+            Some(rule) if rule.never => None,
+            Some(rule) => Some(rule.pending_after_secs.unwrap_or(self.pending_after_secs)),
+            None => Some(self.pending_after_secs),
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        let valid = |secs: f64| secs.is_finite() && secs >= 0.0;
+        if !valid(self.pending_after_secs) {
+            return Err("notifications.pending_after_secs must not be negative".into());
+        }
+        if !valid(self.away_after_secs) {
+            return Err("notifications.away_after_secs must not be negative".into());
+        }
+        for (i, channel) in self.channels.iter().enumerate() {
+            if !valid(channel.after_secs()) {
+                return Err(format!(
+                    "notifications.channels[{}].after_secs must not be negative",
+                    i
+                ));
+            }
+            if let Channel::Ntfy { topic, server, .. } = channel {
+                if topic.trim().is_empty() || server.trim().is_empty() {
+                    return Err(format!(
+                        "notifications.channels[{}] needs a topic and a server",
+                        i
+                    ));
+                }
+            }
+        }
+        for (i, rule) in self.rules.iter().enumerate() {
+            if rule.project.trim().is_empty() {
+                return Err(format!("notifications.rules[{}] needs a project", i));
+            }
+            if rule.pending_after_secs.is_some_and(|secs| !valid(secs)) {
+                return Err(format!(
+                    "notifications.rules[{}].pending_after_secs must not be negative",
+                    i
+                ));
+            }
+        }
+        let reminders = &self.reminders;
+        if !valid(reminders.after_secs) || !valid(reminders.max_interval_secs) {
+            return Err("notifications.reminders durations must not be negative".into());
+        }
+        if !(reminders.backoff.is_finite() && reminders.backoff >= 1.0) {
+            return Err("notifications.reminders.backoff must be at least 1".into());
+        }
+        if reminders
+            .sound
+            .as_deref()
+            .is_some_and(|s| s.trim().is_empty())
+        {
+            return Err("notifications.reminders.sound must not be empty".into());
+        }
+        Ok(())
+    }
+}
+
+/// A notification to send now.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Notification {
+    pub channel: Channel,
+    pub session: SessionInfo,
+    /// How long the session has been pending
+    pub waited: Duration,
+    /// 0 for the channel's first notification, N for the Nth reminder
+    pub reminder: u32,
+}
+
+impl Notification {
+    /// e.g. "api needs input", "api still needs input" for reminders
+    pub fn title(&self) -> String {
+        let still = if self.reminder > 0 { "still " } else { "" };
+        format!("{} {}needs input", self.session.project_name(), still)
+    }
+
+    /// e.g. "Bash · waiting 5m 0s"
+    pub fn body(&self) -> String {
+        let reason = match &self.session.pending_reason {
+            Some(reason) => reason.label(),
+            None => self
+                .session
+                .pending_tool
+                .clone()
+                .unwrap_or_else(|| "Waiting".into()),
+        };
+        format!(
+            "{} · waiting {}",
+            reason,
+            format_duration(self.waited.as_secs())
+        )
+    }
+}
+
+/// A session that is pending, since when, which channels have fired, and
+/// when the last notification or reminder was due.
+#[derive(Debug, Clone)]
+struct Waiting {
+    session: SessionInfo,
+    since: Instant,
+    sent: BTreeSet<usize>,
+    last: Option<Instant>,
+    reminders: u32,
+}
+
+/// What falls due for a pending session.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Due {
+    /// Channel `i` for the first time
+    Channel(usize),
+    /// The next reminder, on every channel sent so far
+    Reminder,
+}
+
+/// What happened while the user was away.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Summary {
+    /// Sessions that finished a turn (went from active to idle)
+    pub finished: usize,
+    /// Sessions waiting for input now
+    pub pending: usize,
+}
+
+impl Summary {
+    /// e.g. "3 sessions finished, 1 needs input"
+    pub fn text(&self) -> String {
+        let mut parts = Vec::new();
+        if self.finished > 0 {
+            let sessions = if self.finished == 1 {
+                "session"
+            } else {
+                "sessions"
+            };
+            parts.push(format!("{} {} finished", self.finished, sessions));
+        }
+        if self.pending > 0 {
+            let needs = if self.pending == 1 { "needs" } else { "need" };
+            parts.push(format!("{} {} input", self.pending, needs));
+        }
+        parts.join(", ")
+    }
+}
+
+/// Pending clocks per session key, fed with status transitions.
+#[derive(Debug, Default)]
+pub struct Notifier {
+    waiting: HashMap<String, Waiting>,
+    /// Set while the user is away: keys of sessions that finished a turn
+    away: Option<BTreeSet<String>>,
+}
+
+impl Notifier {
+    /// Start the clock for sessions that turned pending at `now`, stop it for
+    /// those that left pending or ended.
+    pub fn on_changes(&mut self, changes: &[SessionChange], now: Instant) {
+        for change in changes {
+            if let (Some(finished), SessionChange::StatusChanged { from, session }) =
+                (&mut self.away, change)
+            {
+                if *from == Status::Active && session.status == Status::Idle {
+                    finished.insert(session.key());
+                }
+            }
+            match change {
+                SessionChange::Appeared(session) | SessionChange::StatusChanged { session, .. }
+                    if session.status == Status::Pending =>
+                {
+                    self.waiting
+                        .entry(session.key())
+                        .or_insert_with(|| Waiting {
+                            session: session.clone(),
+                            since: now,
+                            sent: BTreeSet::new(),
+                            last: None,
+                            reminders: 0,
+                        });
+                }
+                SessionChange::Appeared(session)
+                | SessionChange::StatusChanged { session, .. }
+                | SessionChange::Gone(session) => {
+                    self.waiting.remove(&session.key());
+                }
+            }
+        }
+    }
+
+    pub fn is_away(&self) -> bool {
+        self.away.is_some()
+    }
+
+    /// Whether `notification` waits for the summary instead of being sent:
+    /// Mac notifications while the user is away, which nobody would see.
+    pub fn holds(&self, notification: &Notification) -> bool {
+        self.is_away() && matches!(notification.channel, Channel::Macos { .. })
+    }
+
+    /// Note whether the user is away. Coming back ends the away stretch with
+    /// a summary of it, if there is anything to tell.
+    pub fn set_away(&mut self, away: bool, options: &NotifyOptions) -> Option<Summary> {
+        if away {
+            self.away.get_or_insert_with(BTreeSet::new);
+            return None;
+        }
+        let finished = self.away.take()?;
+        let summary = Summary {
+            finished: finished.len(),
+            pending: self
+                .waiting
+                .values()
+                .filter(|w| options.delay_for(&w.session.project_name()).is_some())
+                .count(),
+        };
+        (options.enabled && summary.finished + summary.pending > 0).then_some(summary)
+    }
+
+    /// When each unsent channel, and the next reminder, of each pending
+    /// session is due.
+    fn schedule<'a>(
+        &'a self,
+        options: &'a NotifyOptions,
+    ) -> impl Iterator<Item = (&'a str, Due, Instant)> + 'a {
+        self.waiting
+            .iter()
+            .filter(|_| options.enabled)
+            .filter_map(|(key, waiting)| {
+                let delay = options.delay_for(&waiting.session.project_name())?;
+                Some((key, waiting, delay))
+            })
+            .flat_map(move |(key, waiting, delay)| {
+                options
+                    .channels
+                    .iter()
+                    .enumerate()
+                    .filter(|(i, _)| !waiting.sent.contains(i))
+                    .map(move |(i, channel)| {
+                        let after = Duration::from_secs_f64(delay + channel.after_secs());
+                        (key.as_str(), Due::Channel(i), waiting.since + after)
+                    })
+                    .chain(waiting.last.and_then(|last| {
+                        let interval = options.reminders.interval(waiting.reminders)?;
+                        let at = last + Duration::from_secs_f64(interval);
+                        Some((key.as_str(), Due::Reminder, at))
+                    }))
+            })
+    }
+
+    /// The earliest time a notification becomes due, if any.
+    pub fn next_due(&self, options: &NotifyOptions) -> Option<Instant> {
+        self.schedule(options).map(|(_, _, at)| at).min()
+    }
+
+    /// Notifications due at `now`. Each channel fires once per pending
+    /// stretch; a reminder goes to the channels that have fired, and the next
+    /// one is scheduled from when this one was due.
+    pub fn due(&mut self, options: &NotifyOptions, now: Instant) -> Vec<Notification> {
+        let due: Vec<(String, Due, Instant)> = self
+            .schedule(options)
+            .filter(|(_, _, at)| *at <= now)
+            .map(|(key, due, at)| (key.to_string(), due, at))
+            .collect();
+        let mut notifications = Vec::new();
+        for (key, due, at) in due {
+            let Some(waiting) = self.waiting.get_mut(&key) else {
+                continue;
+            };
+            let channels = match due {
+                Due::Channel(i) => {
+                    waiting.sent.insert(i);
+                    // Reminders count from the first notification
+                    waiting.last.get_or_insert(at);
+                    vec![i]
+                }
+                Due::Reminder => {
+                    waiting.reminders += 1;
+                    waiting.last = Some(at);
+                    waiting.sent.iter().copied().collect()
+                }
+            };
+            let reminder = if due == Due::Reminder {
+                waiting.reminders
+            } else {
+                0
+            };
+            for i in channels {
+                notifications.push(Notification {
+                    channel: options.channels[i].clone(),
+                    session: waiting.session.clone(),
+                    waited: now.saturating_duration_since(waiting.since),
+                    reminder,
+                });
+            }
+        }
+        notifications.sort_by_key(|n| (n.session.key(), n.reminder));
+        notifications
+    }
+}
+
+/// The frontmost app, and the TTY of its selected tab when it's iTerm2.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FrontWindow {
+    pub app: PreviousApp,
+    pub tty: Option<String>,
+}
+
+const ITERM2_BUNDLE_ID: &str = "com.googlecode.iterm2";
+
+const ITERM2_CURRENT_TTY: &str = r#"tell application "iTerm2"
+    return tty of current session of current window
+end tell"#;
+
+/// What's in front right now (None off macOS or when System Events can't
+/// tell).
+fn front_window() -> Option<FrontWindow> {
+    if !cfg!(target_os = "macos") {
+        return None;
+    }
+    let app = focusback::frontmost()?;
+    let tty = (app.bundle_id == ITERM2_BUNDLE_ID)
+        .then(|| run_applescript(ITERM2_CURRENT_TTY, "iTerm2").ok())
+        .flatten()
+        .map(|tty| tty.trim().to_string())
+        .filter(|tty| tty.starts_with("/dev/"));
+    Some(FrontWindow { app, tty })
+}
+
+/// Whether `session` is what's on screen in `front`. iTerm2 is matched by the
+/// selected tab's TTY; apps without one to ask (Alacritty, the IDEs) count
+/// when they're in front with the project's name in the window title. A
+/// session on another machine matches by the TTY of the local SSH tab it
+/// runs behind; container sessions never match.
+pub fn is_focused(session: &SessionInfo, front: &FrontWindow) -> bool {
+    if let Some(remote) = &session.remote {
+        return front.app.bundle_id == ITERM2_BUNDLE_ID
+            && front.tty.is_some()
+            && front.tty == remote.local_tty;
+    }
+    if session.container.is_some() {
+        return false;
+    }
+    let app = &front.app;
+    let title_names_project = || {
+        let project = session.project_name().to_lowercase();
+        app.window
+            .as_ref()
+            .is_some_and(|title| title.to_lowercase().contains(&project))
+    };
+    match session.terminal {
+        Terminal::ITerm2 if app.bundle_id == ITERM2_BUNDLE_ID => match &front.tty {
+            Some(tty) => *tty == session.tty,
+            None => title_names_project(),
+        },
+        Terminal::Alacritty if app.name.eq_ignore_ascii_case("alacritty") => title_names_project(),
+        Terminal::JetBrains(ide) if app.bundle_id.starts_with(ide.bundle_id_prefix()) => {
+            title_names_project()
+        }
+        Terminal::Cursor if app.bundle_id == CURSOR_BUNDLE_ID => title_names_project(),
+        _ => false,
+    }
+}
+
+/// Post `notification` on its channel; reminders play `sound` and go to ntfy
+/// with high priority.
+fn deliver(notification: &Notification, sound: Option<&str>) -> Result<(), String> {
+    let urgent = notification.reminder > 0;
+    post(
+        &notification.channel,
+        &notification.title(),
+        &notification.body(),
+        urgent,
+        sound.filter(|_| urgent),
+    )
+}
+
+/// Post `body` under `title` on `channel`.
+fn post(
+    channel: &Channel,
+    title: &str,
+    body: &str,
+    urgent: bool,
+    sound: Option<&str>,
+) -> Result<(), String> {
+    let output = match channel {
+        Channel::Macos { .. } => {
+            if !cfg!(target_os = "macos") {
+                return Err("macos notifications need macOS".into());
+            }
+            let mut script = format!(
+                "display notification {} with title \"Claude Bar\" subtitle {}",
+                applescript_string(body),
+                applescript_string(title)
+            );
+            if let Some(sound) = sound {
+                script.push_str(&format!(" sound name {}", applescript_string(sound)));
+            }
+            process::run(Command::new("osascript").arg("-e").arg(script))
+        }
+        Channel::Ntfy { topic, server, .. } => process::run(
+            Command::new("curl")
+                .args(["-fsS", "-m", "10", "-H"])
+                .arg(format!("Title: {}", title))
+                .args(["-H", "Tags: hourglass", "-H"])
+                .arg(if urgent {
+                    "Priority: high"
+                } else {
+                    "Priority: default"
+                })
+                .arg("-d")
+                .arg(body)
+                .arg(format!("{}/{}", server.trim_end_matches('/'), topic)),
+        ),
+    };
+    match output {
+        Some(o) if o.status.success() => Ok(()),
+        Some(o) => Err(String::from_utf8_lossy(&o.stderr).trim().to_string()),
+        None => Err("couldn't run the notification command".into()),
+    }
+}
+
+/// Run the notifier on its own thread, reading `notifications` from the live
+/// config each time it wakes so reloads apply.
+pub fn spawn(shared: Arc<Shared>) -> thread::JoinHandle<()> {
+    let events = shared.events.subscribe();
+    thread::Builder::new()
+        .name("events-notify".into())
+        .spawn(move || {
+            let mut notifier = Notifier::default();
+            loop {
+                let options = shared.config().notifications;
+                let mut wait = notifier
+                    .next_due(&options)
+                    .map_or(IDLE_WAIT, |at| at.saturating_duration_since(Instant::now()));
+                if notifier.is_away() {
+                    wait = wait.min(AWAY_CHECK);
+                }
+                let event = match events.recv_timeout(wait) {
+                    Ok(event) => Some(event),
+                    Err(RecvTimeoutError::Timeout) => None,
+                    Err(RecvTimeoutError::Disconnected) => return,
+                };
+                let options = shared.config().notifications;
+                let away = options.enabled
+                    && activity::is_away(activity::idle_secs(), options.away_after_secs);
+                if let Some(summary) = notifier.set_away(away, &options) {
+                    let text = summary.text();
+                    let macos = Channel::Macos { after_secs: 0.0 };
+                    match post(&macos, "While you were away", &text, false, None) {
+                        Ok(()) => logging::info("notify", &format!("summary: {}", text)),
+                        Err(e) => logging::warn("notify", &format!("summary: {}", e)),
+                    }
+                }
+                if let Some(SessionEvent::SessionsChanged { changes, .. }) = event.as_deref() {
+                    notifier.on_changes(changes, Instant::now());
+                }
+                let (held, due): (Vec<_>, Vec<_>) = notifier
+                    .due(&options, Instant::now())
+                    .into_iter()
+                    .partition(|n| notifier.holds(n));
+                for notification in held {
+                    let what = format!(
+                        "{} {}",
+                        notification.session.key(),
+                        notification.session.project_name()
+                    );
+                    logging::debug("notify", &format!("{}: held, user away", what));
+                }
+                // Nobody is looking at the front window while away
+                let front = match options.when_focused {
+                    WhenFocused::Notify => None,
+                    _ if due.is_empty() || notifier.is_away() => None,
+                    _ => front_window(),
+                };
+                for notification in due {
+                    let mut what = format!(
+                        "{} {} via {}",
+                        notification.session.key(),
+                        notification.session.project_name(),
+                        notification.channel.name()
+                    );
+                    if notification.reminder > 0 {
+                        what.push_str(&format!(" (reminder {})", notification.reminder));
+                    }
+                    let focused = front
+                        .as_ref()
+                        .is_some_and(|front| is_focused(&notification.session, front));
+                    let mut sound = options.reminders.sound.as_deref();
+                    if focused {
+                        let macos = matches!(notification.channel, Channel::Macos { .. });
+                        if options.when_focused == WhenFocused::Skip || !macos {
+                            logging::debug("notify", &format!("{}: skipped, in front", what));
+                            continue;
+                        }
+                        what.push_str(" (in front, quiet)");
+                        sound = None;
+                    }
+                    match deliver(&notification, sound) {
+                        Ok(()) => logging::info("notify", &what),
+                        Err(e) => logging::warn("notify", &format!("{}: {}", what, e)),
+                    }
+                }
+            }
+        })
+        .expect("failed to spawn notifier")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::JetBrainsIde;
+
+    fn session(tty: &str, cwd: &str, status: Status) -> SessionInfo {
+        SessionInfo {
+            pending_tool: Some("Bash".into()),
+            ..SessionInfo::for_test(tty, cwd, status)
+        }
+    }
+
+    fn options() -> NotifyOptions {
+        serde_json::from_str(
+            r#"{
+                "enabled": true,
+                "pending_after_secs": 30,
+                "channels": [{"kind": "macos"}, {"kind": "ntfy", "topic": "me", "after_secs": 300}],
+                "rules": [
+                    {"project": "scratch-*", "never": true},
+                    {"project": "api", "pending_after_secs": 0}
+                ]
+            }"#,
+        )
+        .unwrap()
+    }
+
+    fn sent(notifications: &[Notification]) -> Vec<String> {
+        notifications
+            .iter()
+            .map(|n| format!("{} {}", n.session.project_name(), n.channel.name()))
+            .collect()
+    }
+
+    #[test]
+    fn test_escalation_and_rules() {
+        let options = options();
+        let start = Instant::now();
+        let at = |secs: u64| start + Duration::from_secs(secs);
+        let mut notifier = Notifier::default();
+        notifier.on_changes(
+            &[
+                SessionChange::Appeared(session("/dev/ttys001", "/src/web", Status::Pending)),
+                SessionChange::Appeared(session("/dev/ttys002", "/src/api", Status::Pending)),
+                SessionChange::Appeared(session("/dev/ttys003", "/src/scratch-1", Status::Pending)),
+                SessionChange::Appeared(session("/dev/ttys004", "/src/docs", Status::Idle)),
+            ],
+            start,
+        );
+        assert_eq!(sent(&notifier.due(&options, at(0))), ["api macos"]);
+        assert_eq!(notifier.next_due(&options), Some(at(30)));
+        assert!(notifier.due(&options, at(29)).is_empty());
+        let web = notifier.due(&options, at(31));
+        assert_eq!(sent(&web), ["web macos"]);
+        assert_eq!(web[0].title(), "web needs input");
+        assert_eq!(web[0].body(), "Bash · waiting 31s");
+
+        // api leaves pending before its ntfy escalation
+        notifier.on_changes(
+            &[SessionChange::StatusChanged {
+                from: Status::Pending,
+                session: session("/dev/ttys002", "/src/api", Status::Active),
+            }],
+            at(100),
+        );
+        assert_eq!(notifier.next_due(&options), Some(at(330)));
+        assert_eq!(sent(&notifier.due(&options, at(330))), ["web ntfy:me"]);
+        assert!(notifier.due(&options, at(3600)).is_empty());
+        assert_eq!(notifier.next_due(&options), None);
+
+        // Pending again: a new clock
+        notifier.on_changes(
+            &[SessionChange::StatusChanged {
+                from: Status::Active,
+                session: session("/dev/ttys002", "/src/api", Status::Pending),
+            }],
+            at(4000),
+        );
+        assert_eq!(sent(&notifier.due(&options, at(4000))), ["api macos"]);
+        notifier.on_changes(
+            &[SessionChange::Gone(session(
+                "/dev/ttys002",
+                "/src/api",
+                Status::Pending,
+            ))],
+            at(4001),
+        );
+        assert!(notifier.due(&options, at(5000)).is_empty());
+
+        let disabled = NotifyOptions {
+            enabled: false,
+            ..options.clone()
+        };
+        notifier.on_changes(
+            &[SessionChange::Appeared(session(
+                "/dev/ttys005",
+                "/src/cli",
+                Status::Pending,
+            ))],
+            at(6000),
+        );
+        assert_eq!(notifier.next_due(&disabled), None);
+        assert!(notifier.due(&disabled, at(9000)).is_empty());
+    }
+
+    #[test]
+    fn test_reminders_back_off() {
+        let mut options = options();
+        options.reminders = serde_json::from_str(
+            r#"{"after_secs": 600, "backoff": 2, "max_interval_secs": 1800, "sound": "Glass"}"#,
+        )
+        .unwrap();
+        let start = Instant::now();
+        let at = |secs: u64| start + Duration::from_secs(secs);
+        let mut notifier = Notifier::default();
+        notifier.on_changes(
+            &[SessionChange::Appeared(session(
+                "/dev/ttys001",
+                "/src/web",
+                Status::Pending,
+            ))],
+            start,
+        );
+        assert_eq!(sent(&notifier.due(&options, at(30))), ["web macos"]);
+        assert_eq!(notifier.next_due(&options), Some(at(330)));
+        assert_eq!(sent(&notifier.due(&options, at(330))), ["web ntfy:me"]);
+
+        // 10 minutes after the first notification, on both channels
+        assert_eq!(notifier.next_due(&options), Some(at(630)));
+        let reminders = notifier.due(&options, at(640));
+        assert_eq!(sent(&reminders), ["web macos", "web ntfy:me"]);
+        assert_eq!(reminders[0].reminder, 1);
+        assert_eq!(reminders[0].title(), "web still needs input");
+        assert_eq!(reminders[0].body(), "Bash · waiting 10m 40s");
+        // Then 20 minutes, then capped at 30
+        assert_eq!(notifier.next_due(&options), Some(at(630 + 1200)));
+        assert_eq!(notifier.due(&options, at(1830))[0].reminder, 2);
+        assert_eq!(notifier.next_due(&options), Some(at(1830 + 1800)));
+        assert_eq!(notifier.due(&options, at(3630))[0].reminder, 3);
+        assert_eq!(notifier.next_due(&options), Some(at(3630 + 1800)));
+
+        // Answering resets everything
+        notifier.on_changes(
+            &[SessionChange::StatusChanged {
+                from: Status::Pending,
+                session: session("/dev/ttys001", "/src/web", Status::Active),
+            }],
+            at(4000),
+        );
+        assert_eq!(notifier.next_due(&options), None);
+
+        options.reminders.after_secs = 0.0;
+        assert_eq!(options.reminders.interval(0), None);
+        options.reminders.backoff = 0.5;
+        assert_eq!(
+            options.validate().unwrap_err(),
+            "notifications.reminders.backoff must be at least 1"
+        );
+    }
+
+    #[test]
+    fn test_away_summary() {
+        let options = options();
+        let change = |tty: &str, cwd: &str, from, status| SessionChange::StatusChanged {
+            from,
+            session: session(tty, cwd, status),
+        };
+        let start = Instant::now();
+        let mut notifier = Notifier::default();
+        assert_eq!(notifier.set_away(false, &options), None);
+        assert_eq!(notifier.set_away(true, &options), None);
+        assert!(notifier.is_away());
+        notifier.on_changes(
+            &[
+                change("/dev/ttys001", "/src/web", Status::Active, Status::Idle),
+                change("/dev/ttys002", "/src/api", Status::Active, Status::Idle),
+                change("/dev/ttys003", "/src/cli", Status::Active, Status::Pending),
+                // Silenced by a rule, so not counted as needing input
+                change(
+                    "/dev/ttys004",
+                    "/src/scratch-1",
+                    Status::Active,
+                    Status::Pending,
+                ),
+                change("/dev/ttys005", "/src/docs", Status::Pending, Status::Idle),
+            ],
+            start,
+        );
+        notifier.on_changes(
+            &[change(
+                "/dev/ttys001",
+                "/src/web",
+                Status::Active,
+                Status::Idle,
+            )],
+            start + Duration::from_secs(60),
+        );
+        // Still away
+        assert_eq!(notifier.set_away(true, &options), None);
+        let summary = notifier.set_away(false, &options).unwrap();
+        assert_eq!(
+            summary,
+            Summary {
+                finished: 2,
+                pending: 1,
+            }
+        );
+        assert_eq!(summary.text(), "2 sessions finished, 1 needs input");
+        assert!(!notifier.is_away());
+        // Finished turns while present aren't collected
+        notifier.on_changes(
+            &[change(
+                "/dev/ttys002",
+                "/src/api",
+                Status::Active,
+                Status::Idle,
+            )],
+            start,
+        );
+        notifier.set_away(true, &options);
+        notifier.on_changes(
+            &[change(
+                "/dev/ttys003",
+                "/src/cli",
+                Status::Pending,
+                Status::Active,
+            )],
+            start,
+        );
+        assert_eq!(notifier.set_away(false, &options), None);
+
+        // While away, Mac notifications are held but ntfy still escalates
+        notifier.set_away(true, &options);
+        notifier.on_changes(
+            &[change(
+                "/dev/ttys006",
+                "/src/web",
+                Status::Active,
+                Status::Pending,
+            )],
+            start,
+        );
+        let due = notifier.due(&options, start + Duration::from_secs(330));
+        assert_eq!(sent(&due), ["web macos", "web ntfy:me"]);
+        assert!(notifier.holds(&due[0]));
+        assert!(!notifier.holds(&due[1]));
+        assert_eq!(
+            notifier.set_away(false, &options),
+            Some(Summary {
+                finished: 0,
+                pending: 1,
+            })
+        );
+        assert!(!notifier.holds(&due[0]));
+
+        assert_eq!(
+            Summary {
+                finished: 1,
+                pending: 0,
+            }
+            .text(),
+            "1 session finished"
+        );
+        assert_eq!(
+            Summary {
+                finished: 0,
+                pending: 2,
+            }
+            .text(),
+            "2 need input"
+        );
+    }
+
+    #[test]
+    fn test_is_focused() {
+        let front =
+            |name: &str, bundle_id: &str, window: Option<&str>, tty: Option<&str>| FrontWindow {
+                app: PreviousApp {
+                    name: name.into(),
+                    bundle_id: bundle_id.into(),
+                    window: window.map(String::from),
+                },
+                tty: tty.map(String::from),
+            };
+        let web = session("/dev/ttys001", "/src/web", Status::Pending);
+        let iterm = |tty| front("iTerm2", ITERM2_BUNDLE_ID, Some("claude"), tty);
+        assert!(is_focused(&web, &iterm(Some("/dev/ttys001"))));
+        assert!(!is_focused(&web, &iterm(Some("/dev/ttys002"))));
+        // iTerm2 didn't answer: fall back to the window title
+        assert!(!is_focused(&web, &iterm(None)));
+        assert!(is_focused(
+            &web,
+            &front("iTerm2", ITERM2_BUNDLE_ID, Some("~/src/web (claude)"), None)
+        ));
+        assert!(!is_focused(
+            &web,
+            &front("Safari", "com.apple.Safari", Some("web"), None)
+        ));
+
+        let mut ide = web.clone();
+        ide.terminal = Terminal::JetBrains(JetBrainsIde::RustRover);
+        let rustrover = |title| front("RustRover", "com.jetbrains.rustrover", Some(title), None);
+        assert!(is_focused(&ide, &rustrover("Web – main.rs")));
+        assert!(!is_focused(&ide, &rustrover("api – lib.rs")));
+
+        // Remote: by the local SSH tab
+        let mut remote = web.clone();
+        remote.remote =
+            serde_json::from_str(r#"{"host": "devbox", "local_tty": "/dev/ttys009"}"#).unwrap();
+        assert!(is_focused(&remote, &iterm(Some("/dev/ttys009"))));
+        assert!(!is_focused(&remote, &iterm(Some("/dev/ttys001"))));
+        remote.remote.as_mut().unwrap().local_tty = None;
+        assert!(!is_focused(&remote, &iterm(None)));
+    }
+
+    #[test]
+    fn test_options_parse_and_validate() {
+        let options = options();
+        assert_eq!(
+            options.channels[1],
+            Channel::Ntfy {
+                topic: "me".into(),
+                server: "https://ntfy.sh".into(),
+                after_secs: 300.0,
+            }
+        );
+        assert_eq!(options.delay_for("Scratch-2"), None);
+        assert_eq!(options.delay_for("api"), Some(0.0));
+        assert_eq!(options.delay_for("web"), Some(30.0));
+        assert!(options.validate().is_ok());
+
+        let mut bad = options.clone();
+        bad.channels.push(Channel::Ntfy {
+            topic: " ".into(),
+            server: default_ntfy_server(),
+            after_secs: 0.0,
+        });
+        assert_eq!(
+            bad.validate().unwrap_err(),
+            "notifications.channels[2] needs a topic and a server"
+        );
+        let mut bad = options;
+        bad.rules[1].pending_after_secs = Some(-1.0);
+        assert!(bad.validate().unwrap_err().contains("rules[1]"));
+        assert!(
+            serde_json::from_str::<NotifyOptions>(r#"{"channels": [{"kind": "slack"}]}"#).is_err()
+        );
+    }
+}
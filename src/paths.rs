@@ -0,0 +1,242 @@
+//! Where claude-bar keeps its files, following the XDG base directory spec
+//! (and macOS conventions for logs and caches):
+//!
+//! | Kind    | Location                                                        |
+//! |---------|-----------------------------------------------------------------|
+//! | config  | `$XDG_CONFIG_HOME/claude-bar`, else `~/.config/claude-bar`      |
+//! | state   | `$XDG_STATE_HOME/claude-bar`, else `~/.local/state/claude-bar`  |
+//! | cache   | `$XDG_CACHE_HOME/claude-bar`, else `~/Library/Caches/claude-bar` on macOS, `~/.cache/claude-bar` elsewhere |
+//! | logs    | `~/Library/Logs/claude-bar` on macOS, else `<state>/logs`       |
+//! | runtime | `$XDG_RUNTIME_DIR`, else the state dir                           |
+//!
+//! Older releases kept everything under `~/.claude`; [`migrate_legacy_files`]
+//! moves those files over.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const APP_DIR: &str = "claude-bar";
+
+/// Resolved base directories, all already ending in `claude-bar` except `runtime`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Dirs {
+    pub home: PathBuf,
+    pub config: PathBuf,
+    pub state: PathBuf,
+    pub cache: PathBuf,
+    pub logs: PathBuf,
+    pub runtime: PathBuf,
+}
+
+impl Dirs {
+    /// Directories for the current environment.
+    pub fn from_env() -> Dirs {
+        let var = |name: &str| std::env::var(name).ok().filter(|v| !v.is_empty());
+        Dirs::resolve(var, cfg!(target_os = "macos"))
+    }
+
+    /// Resolve against the environment lookup `var`. Relative XDG values are
+    /// ignored, as the spec requires.
+    pub fn resolve(var: impl Fn(&str) -> Option<String>, macos: bool) -> Dirs {
+        let home = PathBuf::from(var("HOME").unwrap_or_default());
+        let xdg = |name: &str| var(name).map(PathBuf::from).filter(|p| p.is_absolute());
+        let config = xdg("XDG_CONFIG_HOME")
+            .unwrap_or_else(|| home.join(".config"))
+            .join(APP_DIR);
+        let state = xdg("XDG_STATE_HOME")
+            .unwrap_or_else(|| home.join(".local").join("state"))
+            .join(APP_DIR);
+        let cache = xdg("XDG_CACHE_HOME")
+            .unwrap_or_else(|| {
+                if macos {
+                    home.join("Library").join("Caches")
+                } else {
+                    home.join(".cache")
+                }
+            })
+            .join(APP_DIR);
+        let logs = if macos {
+            home.join("Library").join("Logs").join(APP_DIR)
+        } else {
+            state.join("logs")
+        };
+        // Not `$TMPDIR`: it is often the shared /tmp, where another user
+        // could create the socket first
+        let runtime = xdg("XDG_RUNTIME_DIR").unwrap_or_else(|| state.clone());
+        Dirs {
+            home,
+            config,
+            state,
+            cache,
+            logs,
+            runtime,
+        }
+    }
+}
+
+pub fn home_dir() -> PathBuf {
+    PathBuf::from(std::env::var("HOME").unwrap_or_default())
+}
+
+pub fn config_dir() -> PathBuf {
+    Dirs::from_env().config
+}
+
+pub fn state_dir() -> PathBuf {
+    Dirs::from_env().state
+}
+
+pub fn cache_dir() -> PathBuf {
+    Dirs::from_env().cache
+}
+
+pub fn log_dir() -> PathBuf {
+    Dirs::from_env().logs
+}
+
+pub fn runtime_dir() -> PathBuf {
+    Dirs::from_env().runtime
+}
+
+/// `~/.claude/claude-bar`, where older releases kept state files.
+pub fn legacy_state_dir() -> PathBuf {
+    home_dir().join(".claude").join(APP_DIR)
+}
+
+/// Files that moved from `~/.claude` to the XDG locations, as (old, new).
+fn legacy_files(dirs: &Dirs) -> Vec<(PathBuf, PathBuf)> {
+    let legacy = dirs.home.join(".claude");
+    vec![
+        (
+            legacy.join("claude-bar.json"),
+            dirs.config.join("config.json"),
+        ),
+        (
+            legacy.join(APP_DIR).join("permissions.json"),
+            dirs.state.join("permissions.json"),
+        ),
+        (
+            legacy.join(APP_DIR).join("update.json"),
+            dirs.state.join("update.json"),
+        ),
+    ]
+}
+
+/// Move files left behind by older releases into their current locations.
+/// A file already present at the new location wins. Errors are ignored (the
+/// hook runs this too and must never fail); returns how many files moved.
+pub fn migrate_legacy_files() -> usize {
+    let dirs = Dirs::from_env();
+    let moved = legacy_files(&dirs)
+        .iter()
+        .filter(|(from, to)| move_file(from, to))
+        .count();
+    moved
+        + crate::transcript::migrate_state_dirs(
+            &dirs.home.join(".claude").join(APP_DIR),
+            &dirs.state.join("sessions"),
+        )
+}
+
+/// Move `from` to `to` unless `to` exists. Falls back to copy + remove across
+/// filesystems. Returns whether the file moved.
+pub fn move_file(from: &Path, to: &Path) -> bool {
+    if !from.is_file() || to.exists() {
+        return false;
+    }
+    let Some(parent) = to.parent() else {
+        return false;
+    };
+    let moved = fs::create_dir_all(parent)
+        .and_then(|_| fs::rename(from, to).or_else(|_| fs::copy(from, to).map(|_| ())));
+    if moved.is_ok() {
+        let _ = fs::remove_file(from);
+    }
+    moved.is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use tempfile::TempDir;
+
+    fn resolve(vars: &[(&str, &str)], macos: bool) -> Dirs {
+        let vars: HashMap<String, String> = vars
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        Dirs::resolve(|name| vars.get(name).cloned(), macos)
+    }
+
+    #[test]
+    fn test_resolve_defaults() {
+        let dirs = resolve(&[("HOME", "/Users/me")], true);
+        assert_eq!(dirs.config, PathBuf::from("/Users/me/.config/claude-bar"));
+        assert_eq!(
+            dirs.state,
+            PathBuf::from("/Users/me/.local/state/claude-bar")
+        );
+        assert_eq!(
+            dirs.cache,
+            PathBuf::from("/Users/me/Library/Caches/claude-bar")
+        );
+        assert_eq!(
+            dirs.logs,
+            PathBuf::from("/Users/me/Library/Logs/claude-bar")
+        );
+        assert_eq!(dirs.runtime, dirs.state);
+
+        let dirs = resolve(&[("HOME", "/home/me")], false);
+        assert_eq!(dirs.cache, PathBuf::from("/home/me/.cache/claude-bar"));
+        assert_eq!(
+            dirs.logs,
+            PathBuf::from("/home/me/.local/state/claude-bar/logs")
+        );
+    }
+
+    #[test]
+    fn test_resolve_env_overrides() {
+        let dirs = resolve(
+            &[
+                ("HOME", "/home/me"),
+                ("XDG_CONFIG_HOME", "/cfg"),
+                ("XDG_STATE_HOME", "/st"),
+                ("XDG_CACHE_HOME", "relative/cache"),
+                ("XDG_RUNTIME_DIR", "/run/user/501"),
+                ("TMPDIR", "/var/folders/xy/T"),
+            ],
+            false,
+        );
+        assert_eq!(dirs.config, PathBuf::from("/cfg/claude-bar"));
+        assert_eq!(dirs.state, PathBuf::from("/st/claude-bar"));
+        // Relative XDG paths are ignored
+        assert_eq!(dirs.cache, PathBuf::from("/home/me/.cache/claude-bar"));
+        assert_eq!(dirs.logs, PathBuf::from("/st/claude-bar/logs"));
+        assert_eq!(dirs.runtime, PathBuf::from("/run/user/501"));
+
+        let dirs = resolve(&[("HOME", "/home/me"), ("TMPDIR", "/tmp")], false);
+        assert_eq!(
+            dirs.runtime,
+            PathBuf::from("/home/me/.local/state/claude-bar")
+        );
+    }
+
+    #[test]
+    fn test_move_file_keeps_existing_target() {
+        let tmp = TempDir::new().unwrap();
+        let old = tmp.path().join("old.json");
+        let new = tmp.path().join("new").join("config.json");
+        fs::write(&old, "old").unwrap();
+
+        assert!(move_file(&old, &new));
+        assert!(!old.exists());
+        assert_eq!(fs::read_to_string(&new).unwrap(), "old");
+
+        fs::write(&old, "stale").unwrap();
+        assert!(!move_file(&old, &new));
+        assert!(old.exists());
+        assert_eq!(fs::read_to_string(&new).unwrap(), "old");
+        assert!(!move_file(&tmp.path().join("missing"), &new));
+    }
+}
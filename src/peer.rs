@@ -0,0 +1,272 @@
+//! Pulling session lists from `serve` daemons on other machines (`serve --peer`).
+//!
+//! Each peer is polled on its own thread over the loopback TCP listener of the
+//! remote daemon (`serve --listen-tcp`, reached through a tunnel), or over its
+//! authenticated TLS listener (`serve --listen`) when a certificate fingerprint
+//! is given, so a slow or unreachable machine never delays local polling or the
+//! other peers.
+
+use crate::logging;
+use crate::protocol::{self, Request};
+use crate::state::{Remote, SessionInfo};
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// A daemon to pull sessions from, given as `[label=]host:port[#fingerprint]`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Peer {
+    /// Host label shown in the menu
+    pub label: String,
+    /// `host:port` of the peer's `--listen-tcp` or `--listen` listener
+    pub addr: String,
+    /// SHA-256 of the peer's `--listen` certificate; connect over TLS and
+    /// authenticate with the shared token when set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fingerprint: Option<String>,
+}
+
+/// Parse a `--peer` value. Without a label, the host part of the address is used.
+pub fn parse_peer(value: &str) -> Result<Peer, String> {
+    let (spec, fingerprint) = match value.split_once('#') {
+        Some((spec, fingerprint)) => {
+            crate::tls::parse_fingerprint(fingerprint.trim())?;
+            (spec, Some(fingerprint.trim().to_string()))
+        }
+        None => (value, None),
+    };
+    let (label, addr) = match spec.split_once('=') {
+        Some((label, addr)) => (label.trim(), addr.trim()),
+        None => (spec.trim(), spec.trim()),
+    };
+    let (host, port) = addr
+        .rsplit_once(':')
+        .ok_or_else(|| format!("peer `{}` must be host:port", value))?;
+    if host.is_empty() || port.parse::<u16>().is_err() {
+        return Err(format!("peer `{}` must be host:port", value));
+    }
+    let label = if label == addr { host } else { label };
+    if label.is_empty() {
+        return Err(format!("peer `{}` has an empty label", value));
+    }
+    Ok(Peer {
+        label: label.trim_matches(['[', ']']).to_string(),
+        addr: addr.to_string(),
+        fingerprint,
+    })
+}
+
+/// How long a peer's last good session list is shown after it stops answering,
+/// so a dropped packet doesn't make its sessions flicker out of the menu.
+pub const PEER_STALE_AFTER: Duration = Duration::from_secs(30);
+/// Longest wait between attempts to reach a failing peer.
+pub const MAX_PEER_RETRY: Duration = Duration::from_secs(60);
+/// Connect/read timeout for one peer request.
+pub const PEER_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Last known state of one peer.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PeerState {
+    pub peer: Peer,
+    sessions: Vec<SessionInfo>,
+    last_ok: Option<SystemTime>,
+    /// Consecutive failed attempts
+    pub failures: u32,
+    pub last_error: Option<String>,
+}
+
+impl PeerState {
+    pub fn new(peer: Peer) -> Self {
+        PeerState {
+            peer,
+            sessions: Vec::new(),
+            last_ok: None,
+            failures: 0,
+            last_error: None,
+        }
+    }
+
+    /// Store a fresh session list, tagging each session with the peer's label.
+    /// Sessions the peer got from its own peers are dropped so daemons can't loop.
+    pub fn record_success(&mut self, sessions: Vec<SessionInfo>, now: SystemTime) {
+        if self.failures > 0 {
+            logging::info("peer", &format!("{} reachable again", self.peer.label));
+        }
+        self.sessions = sessions
+            .into_iter()
+            .filter(|s| s.remote.is_none())
+            .map(|s| SessionInfo {
+                remote: Some(Remote {
+                    host: self.peer.label.clone(),
+                    local_tty: None,
+                }),
+                ..s
+            })
+            .collect();
+        self.last_ok = Some(now);
+        self.failures = 0;
+        self.last_error = None;
+    }
+
+    pub fn record_failure(&mut self, error: String) {
+        if self.failures == 0 {
+            logging::warn("peer", &format!("{}: {}", self.peer.label, error));
+        }
+        self.failures += 1;
+        self.last_error = Some(error);
+    }
+
+    /// Sessions to show: the last good list, unless it has gone stale.
+    pub fn sessions(&self, now: SystemTime) -> &[SessionInfo] {
+        let fresh = self.last_ok.is_some_and(|at| {
+            now.duration_since(at)
+                .map_or(true, |age| age <= PEER_STALE_AFTER)
+        });
+        if fresh {
+            &self.sessions
+        } else {
+            &[]
+        }
+    }
+
+    /// Delay before the next attempt: the poll interval, doubled per
+    /// consecutive failure up to [`MAX_PEER_RETRY`].
+    pub fn next_delay(&self, interval: Duration) -> Duration {
+        let factor = 1u32 << self.failures.min(6);
+        (interval * factor).min(MAX_PEER_RETRY.max(interval))
+    }
+
+    pub fn report(&self) -> PeerReport {
+        PeerReport {
+            label: self.peer.label.clone(),
+            addr: self.peer.addr.clone(),
+            sessions: self.sessions.len(),
+            last_ok_unix: self
+                .last_ok
+                .and_then(|t| t.duration_since(UNIX_EPOCH).ok().map(|d| d.as_secs())),
+            failures: self.failures,
+            last_error: self.last_error.clone(),
+        }
+    }
+}
+
+/// Per-peer entry in the `health` reply.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PeerReport {
+    pub label: String,
+    pub addr: String,
+    pub sessions: usize,
+    pub last_ok_unix: Option<u64>,
+    pub failures: u32,
+    pub last_error: Option<String>,
+}
+
+/// Ask a peer daemon for its current sessions, over TLS with `token` when the
+/// peer has a certificate fingerprint, else over plain TCP without it.
+pub fn fetch_sessions(peer: &Peer, token: Option<&str>) -> Result<Vec<SessionInfo>, String> {
+    let response = match &peer.fingerprint {
+        Some(fingerprint) => {
+            let fingerprint = crate::tls::parse_fingerprint(fingerprint)?;
+            let token = token.ok_or("no --auth-token-file for this TLS peer")?;
+            protocol::send_request_tls(
+                &peer.addr,
+                &fingerprint,
+                token,
+                &Request::sessions(),
+                PEER_TIMEOUT,
+            )
+        }
+        None => protocol::send_request_tcp(&peer.addr, &Request::sessions(), PEER_TIMEOUT),
+    }
+    .map_err(|e| e.to_string())?;
+    if !response.ok {
+        return Err(response.error.unwrap_or_default());
+    }
+    serde_json::from_value(response.data.unwrap_or_default()).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::Status;
+
+    fn session(tty: &str) -> SessionInfo {
+        SessionInfo::for_test(tty, "/p", Status::Active)
+    }
+
+    fn peer(label: &str, addr: &str) -> Peer {
+        Peer {
+            label: label.into(),
+            addr: addr.into(),
+            fingerprint: None,
+        }
+    }
+
+    #[test]
+    fn test_parse_peer() {
+        assert_eq!(
+            parse_peer("desktop.local:7878"),
+            Ok(peer("desktop.local", "desktop.local:7878"))
+        );
+        assert_eq!(
+            parse_peer("desk=10.0.0.5:7878"),
+            Ok(peer("desk", "10.0.0.5:7878"))
+        );
+        assert_eq!(parse_peer("[::1]:7878"), Ok(peer("::1", "[::1]:7878")));
+        assert!(parse_peer("desktop").is_err());
+        assert!(parse_peer("desktop:http").is_err());
+        assert!(parse_peer(":7878").is_err());
+        assert!(parse_peer("=host:7878").is_err());
+
+        let fingerprint = crate::tls::tests::TEST_FINGERPRINT;
+        let tls = parse_peer(&format!("desk=10.0.0.5:7878#{}", fingerprint)).unwrap();
+        assert_eq!(tls.addr, "10.0.0.5:7878");
+        assert_eq!(tls.fingerprint.as_deref(), Some(fingerprint));
+        assert!(parse_peer("desk=10.0.0.5:7878#abcd").is_err());
+    }
+
+    #[test]
+    fn test_peer_state_tags_and_expires_sessions() {
+        let mut state = PeerState::new(peer("desk", "desk:7878"));
+        let t0 = UNIX_EPOCH + Duration::from_secs(1000);
+        assert!(state.sessions(t0).is_empty());
+
+        let mut looped = session("/dev/ttys002");
+        looped.remote = Some(Remote {
+            host: "laptop".into(),
+            local_tty: None,
+        });
+        state.record_success(vec![session("/dev/ttys001"), looped], t0);
+        let shown = state.sessions(t0);
+        assert_eq!(shown.len(), 1);
+        assert_eq!(shown[0].key(), "desk:/dev/ttys001");
+
+        // A failure keeps the last list until it goes stale
+        state.record_failure("connection refused".into());
+        assert_eq!(state.sessions(t0 + Duration::from_secs(10)).len(), 1);
+        assert!(state
+            .sessions(t0 + PEER_STALE_AFTER + Duration::from_secs(1))
+            .is_empty());
+
+        let report = state.report();
+        assert_eq!(report.failures, 1);
+        assert_eq!(report.last_ok_unix, Some(1000));
+        assert_eq!(report.last_error.as_deref(), Some("connection refused"));
+
+        state.record_success(vec![], t0 + Duration::from_secs(60));
+        assert_eq!(state.failures, 0);
+        assert_eq!(state.last_error, None);
+    }
+
+    #[test]
+    fn test_peer_retry_backs_off() {
+        let mut state = PeerState::new(peer("desk", "desk:7878"));
+        let interval = Duration::from_secs(2);
+        assert_eq!(state.next_delay(interval), interval);
+        state.record_failure("timeout".into());
+        assert_eq!(state.next_delay(interval), Duration::from_secs(4));
+        for _ in 0..10 {
+            state.record_failure("timeout".into());
+        }
+        assert_eq!(state.next_delay(interval), MAX_PEER_RETRY);
+    }
+}
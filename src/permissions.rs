@@ -0,0 +1,232 @@
+//! Detection of missing macOS privacy permissions behind failing AppleScript.
+//!
+//! Without Automation (Apple events to iTerm2/System Events) or Accessibility
+//! access, `osascript` fails and sessions silently show up as `unknown`. Failures
+//! are recognized by their error codes and recorded in
+//! `~/.claude/claude-bar/permissions.json`, which `daemon status` and the menu bar
+//! app read; a success clears the entry again.
+
+use crate::focus;
+use crate::logging;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Output;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Permission {
+    /// Privacy & Security › Automation (sending Apple events to an app)
+    Automation,
+    /// Privacy & Security › Accessibility (raising windows via System Events)
+    Accessibility,
+}
+
+impl Permission {
+    /// System Settings deep link to the pane where the permission is granted.
+    pub fn settings_url(self) -> &'static str {
+        match self {
+            Permission::Automation => {
+                "x-apple.systempreferences:com.apple.preference.security?Privacy_Automation"
+            }
+            Permission::Accessibility => {
+                "x-apple.systempreferences:com.apple.preference.security?Privacy_Accessibility"
+            }
+        }
+    }
+
+    pub fn pane(self) -> &'static str {
+        match self {
+            Permission::Automation => "Privacy & Security › Automation",
+            Permission::Accessibility => "Privacy & Security › Accessibility",
+        }
+    }
+}
+
+/// Which permission an `osascript` error message points at, if any.
+pub fn classify_error(stderr: &str) -> Option<Permission> {
+    // -1743: "Not authorized to send Apple events to <app>"
+    if stderr.contains("-1743") || stderr.contains("Not authorized to send Apple events") {
+        return Some(Permission::Automation);
+    }
+    // -1719 / -25211: "osascript is not allowed assistive access"
+    if stderr.contains("-1719")
+        || stderr.contains("-25211")
+        || stderr.contains("not allowed assistive access")
+    {
+        return Some(Permission::Accessibility);
+    }
+    None
+}
+
+/// A permission found missing, and for which app.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PermissionIssue {
+    pub permission: Permission,
+    /// App the script was talking to (`iTerm2`, `System Events`)
+    pub app: String,
+    /// The `osascript` error that revealed it
+    pub detail: String,
+    pub since_unix: u64,
+}
+
+impl PermissionIssue {
+    /// One-line explanation with where to fix it.
+    pub fn describe(&self) -> String {
+        match self.permission {
+            Permission::Automation => format!(
+                "Automation access to {} is missing (System Settings › {})",
+                self.app,
+                self.permission.pane()
+            ),
+            Permission::Accessibility => format!(
+                "Accessibility access is missing (System Settings › {})",
+                self.permission.pane()
+            ),
+        }
+    }
+
+    fn same_as(&self, permission: Permission, app: &str) -> bool {
+        self.permission == permission && self.app == app
+    }
+}
+
+pub fn default_path() -> PathBuf {
+    crate::paths::state_dir().join("permissions.json")
+}
+
+/// Currently known permission problems (none if the file is missing or unreadable).
+pub fn read(path: &Path) -> Vec<PermissionIssue> {
+    fs::read_to_string(path)
+        .ok()
+        .and_then(|c| serde_json::from_str(&c).ok())
+        .unwrap_or_default()
+}
+
+fn write(path: &Path, issues: &[PermissionIssue]) {
+    if issues.is_empty() {
+        let _ = fs::remove_file(path);
+        return;
+    }
+    if let Some(parent) = path.parent() {
+        let _ = fs::create_dir_all(parent);
+    }
+    if let Ok(json) = serde_json::to_string_pretty(issues) {
+        let _ = fs::write(path, json);
+    }
+}
+
+/// Update the record at `path` after an `osascript` run against `app`: a
+/// success clears every issue recorded for that app. Returns the issue if this
+/// failure revealed a permission not already recorded.
+pub fn record_result(
+    path: &Path,
+    app: &str,
+    success: bool,
+    stderr: &str,
+    now: u64,
+) -> Option<PermissionIssue> {
+    let mut issues = read(path);
+    if success {
+        if issues.iter().any(|i| i.app == app) {
+            issues.retain(|i| i.app != app);
+            write(path, &issues);
+            logging::info("permissions", &format!("access to {} granted", app));
+        }
+        return None;
+    }
+    let found = classify_error(stderr)?;
+    if issues.iter().any(|i| i.same_as(found, app)) {
+        return None;
+    }
+    let issue = PermissionIssue {
+        permission: found,
+        app: app.to_string(),
+        detail: stderr.trim().to_string(),
+        since_unix: now,
+    };
+    issues.push(issue.clone());
+    write(path, &issues);
+    Some(issue)
+}
+
+/// Check the result of an `osascript` run against `app`. The first time a
+/// permission turns up missing, the user gets a notification.
+pub fn check_osascript(app: &str, output: &Output) {
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let newly_missing = record_result(&default_path(), app, output.status.success(), &stderr, now);
+    if let Some(issue) = newly_missing {
+        logging::warn("permissions", &issue.describe());
+        focus::notify(&format!("Claude Bar: {}", issue.describe()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify_error() {
+        assert_eq!(
+            classify_error(
+                "execution error: Not authorized to send Apple events to iTerm2. (-1743)"
+            ),
+            Some(Permission::Automation)
+        );
+        assert_eq!(
+            classify_error(
+                "System Events got an error: osascript is not allowed assistive access. (-1719)"
+            ),
+            Some(Permission::Accessibility)
+        );
+        assert_eq!(
+            classify_error("execution error: iTerm2 got an error: Can't get window 1. (-1728)"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_record_result_reports_new_issues_once_and_clears_on_success() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("permissions.json");
+        let denied = "Not authorized to send Apple events to iTerm2. (-1743)";
+
+        let issue = record_result(&path, "iTerm2", false, denied, 100).unwrap();
+        assert_eq!(issue.permission, Permission::Automation);
+        assert_eq!(issue.since_unix, 100);
+        assert!(issue.describe().contains("Automation access to iTerm2"));
+
+        // Already known: no second notification, original timestamp kept
+        assert!(record_result(&path, "iTerm2", false, denied, 200).is_none());
+        assert_eq!(read(&path)[0].since_unix, 100);
+
+        // Unrelated errors are not permission problems
+        assert!(record_result(
+            &path,
+            "System Events",
+            false,
+            "Can't get window 1. (-1728)",
+            300
+        )
+        .is_none());
+        assert_eq!(read(&path).len(), 1);
+
+        record_result(&path, "iTerm2", true, "", 400);
+        assert!(read(&path).is_empty());
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_settings_urls() {
+        assert!(Permission::Automation
+            .settings_url()
+            .ends_with("Privacy_Automation"));
+        assert!(Permission::Accessibility
+            .settings_url()
+            .ends_with("Privacy_Accessibility"));
+    }
+}
@@ -1,6 +1,75 @@
-use crate::state::Provider;
-use std::collections::HashMap;
-use std::process::Command;
+use crate::logging;
+use crate::state::{ProcessIdentity, Provider};
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Subprocesses spawned through [`run`] for one piece of work (a poll) and the
+/// most recent failure among them (spawn error or non-zero exit with stderr).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Tally {
+    pub subprocesses: u64,
+    pub last_error: Option<String>,
+}
+
+impl Tally {
+    fn add(&mut self, other: Tally) {
+        self.subprocesses += other.subprocesses;
+        if other.last_error.is_some() {
+            self.last_error = other.last_error;
+        }
+    }
+}
+
+thread_local! {
+    /// What [`run`] did on this thread, so concurrent work is counted apart.
+    static TALLY: RefCell<Tally> = RefCell::new(Tally::default());
+}
+
+/// Run `f`, returning what [`run`] did meanwhile on this thread and on the
+/// workers whose tallies it [`absorb`]ed. It still counts toward any
+/// enclosing `tallied` call.
+pub fn tallied<R>(f: impl FnOnce() -> R) -> (R, Tally) {
+    let outer = TALLY.take();
+    let result = f();
+    let tally = TALLY.replace(outer);
+    absorb(tally.clone());
+    (result, tally)
+}
+
+/// Count a worker thread's tally (from [`tallied`]) toward this thread's.
+pub fn absorb(tally: Tally) {
+    TALLY.with_borrow_mut(|t| t.add(tally));
+}
+
+/// Run a command to completion, counting it and recording failures for `daemon status`.
+pub fn run(cmd: &mut Command) -> Option<Output> {
+    TALLY.with_borrow_mut(|t| t.subprocesses += 1);
+    let program = cmd.get_program().to_string_lossy().to_string();
+    match cmd.output() {
+        Ok(output) => {
+            if !output.status.success() && !output.stderr.is_empty() {
+                let stderr = String::from_utf8_lossy(&output.stderr);
+                record_error(format!("{}: {}", program, stderr.trim()));
+            }
+            Some(output)
+        }
+        Err(e) => {
+            record_error(format!("failed to run {}: {}", program, e));
+            None
+        }
+    }
+}
+
+fn record_error(msg: String) {
+    logging::warn("process", &msg);
+    TALLY.with_borrow_mut(|t| t.last_error = Some(msg));
+}
 
 /// Parse `pgrep -x claude` output into a list of PIDs.
 pub fn parse_pgrep_output(output: &str) -> Vec<u32> {
@@ -26,10 +95,8 @@ pub fn parse_lsof_ttys(output: &str) -> Vec<String> {
     let mut ttys = Vec::new();
     for line in output.lines() {
         for field in line.split_whitespace() {
-            if field.starts_with("/dev/ttys") {
-                if !ttys.contains(&field.to_string()) {
-                    ttys.push(field.to_string());
-                }
+            if field.starts_with("/dev/ttys") && !ttys.contains(&field.to_string()) {
+                ttys.push(field.to_string());
             }
         }
     }
@@ -64,108 +131,753 @@ pub fn parse_ps_comm(output: &str) -> Option<String> {
     }
 }
 
+/// Parse a `ps -o etime=` value (`[[dd-]hh:]mm:ss`) into seconds.
+pub fn parse_ps_etime(output: &str) -> Option<u64> {
+    let etime = output.trim();
+    let (days, clock) = match etime.split_once('-') {
+        Some((d, rest)) => (d.parse::<u64>().ok()?, rest),
+        None => (0, etime),
+    };
+    let mut secs = 0;
+    let mut fields = 0;
+    for part in clock.split(':') {
+        secs = secs * 60 + part.parse::<u64>().ok()?;
+        fields += 1;
+    }
+    if !(2..=3).contains(&fields) {
+        return None;
+    }
+    Some(days * 86400 + secs)
+}
+
+/// Parse `ps -o time=` CPU time (`[dd-][hh:]mm:ss[.cc]`) into seconds.
+pub fn parse_ps_cputime(output: &str) -> Option<f64> {
+    let time = output.trim();
+    let (days, clock) = match time.split_once('-') {
+        Some((d, rest)) => (d.parse::<f64>().ok()?, rest),
+        None => (0.0, time),
+    };
+    let parts: Vec<&str> = clock.split(':').collect();
+    if !(2..=3).contains(&parts.len()) {
+        return None;
+    }
+    let mut secs = 0.0;
+    for part in parts {
+        secs = secs * 60.0 + part.parse::<f64>().ok()?;
+    }
+    Some(days * 86400.0 + secs)
+}
+
+/// `NAME=value` words of `ps eww -o command=` output: the command line followed
+/// by the environment. Values containing spaces are cut at the first one.
+pub fn parse_ps_environ(output: &str) -> Vec<(String, String)> {
+    output
+        .split_whitespace()
+        .filter_map(|word| {
+            let (name, value) = word.split_once('=')?;
+            let valid = name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+                && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+            valid.then(|| (name.to_string(), value.to_string()))
+        })
+        .collect()
+}
+
+/// Parse `/proc/<pid>/environ`: NUL-separated `NAME=value` entries.
+pub fn parse_proc_environ(content: &[u8]) -> Vec<(String, String)> {
+    content
+        .split(|&b| b == 0)
+        .filter_map(|entry| {
+            let entry = String::from_utf8_lossy(entry);
+            let (name, value) = entry.split_once('=')?;
+            (!name.is_empty()).then(|| (name.to_string(), value.to_string()))
+        })
+        .collect()
+}
+
+/// `ps -o tty= -o stat= -o etime=` details of one agent process.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PsAgentLine {
+    pub tty: String,
+    /// In the terminal's foreground process group (`+` in `stat`)
+    pub foreground: bool,
+    /// Elapsed seconds since the process started
+    pub elapsed: Option<u64>,
+}
+
+/// Parse `ps -o tty= -o stat= -o etime= -p PID` output.
+pub fn parse_ps_agent_line(output: &str) -> Option<PsAgentLine> {
+    let mut fields = output.split_whitespace();
+    let tty = parse_ps_tty(fields.next()?)?;
+    let stat = fields.next().unwrap_or_default();
+    Some(PsAgentLine {
+        tty,
+        foreground: stat.contains('+'),
+        elapsed: fields.next().and_then(parse_ps_etime),
+    })
+}
+
 /// Parse `ps -o ppid= -p PID` output to get the parent PID.
 pub fn parse_ps_ppid(output: &str) -> Option<u32> {
     output.trim().parse::<u32>().ok()
 }
 
+/// One row of the process table.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProcEntry {
+    pub pid: u32,
+    pub ppid: u32,
+    /// Controlling terminal as a device path (None when detached)
+    pub tty: Option<String>,
+}
+
+/// Parse `ps -A -o pid= -o ppid= -o tty=` output.
+pub fn parse_ps_tree(output: &str) -> Vec<ProcEntry> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let pid = fields.next()?.parse().ok()?;
+            let ppid = fields.next()?.parse().ok()?;
+            let tty = fields.next().and_then(parse_ps_tty);
+            Some(ProcEntry { pid, ppid, tty })
+        })
+        .collect()
+}
+
+/// The whole process table.
+pub fn process_tree() -> Vec<ProcEntry> {
+    backend().process_tree()
+}
+
+/// Which of `owners` (e.g. terminal app PIDs) has a descendant attached to `tty`.
+pub fn owner_of_tty(tree: &[ProcEntry], tty: &str, owners: &[u32]) -> Option<u32> {
+    let parent: HashMap<u32, u32> = tree.iter().map(|p| (p.pid, p.ppid)).collect();
+    tree.iter()
+        .filter(|p| p.tty.as_deref() == Some(tty))
+        .find_map(|p| {
+            let mut pid = p.pid;
+            // Bounded walk in case the table is inconsistent
+            for _ in 0..64 {
+                if owners.contains(&pid) {
+                    return Some(pid);
+                }
+                pid = *parent.get(&pid).filter(|&&pp| pp != pid)?;
+            }
+            None
+        })
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct AgentProcess {
     pub pid: u32,
     pub provider: Provider,
+    /// Start time (unix seconds), if `ps` reported it
+    pub started_at: Option<u64>,
+    /// In its terminal's foreground process group, i.e. the one the user is typing into
+    pub foreground: bool,
+}
+
+impl AgentProcess {
+    pub fn identity(&self) -> ProcessIdentity {
+        ProcessIdentity {
+            pid: self.pid,
+            started_at: self.started_at,
+        }
+    }
+
+    /// Whether `self` should stand for a TTY shared with `other`: the
+    /// foreground process first, then the longest running, then the lower PID.
+    fn outranks(&self, other: &AgentProcess) -> bool {
+        let rank = |a: &AgentProcess| (!a.foreground, a.started_at.unwrap_or(u64::MAX), a.pid);
+        rank(self) < rank(other)
+    }
+}
+
+/// The agent processes running on one TTY.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TtyAgents {
+    /// The process the session is shown as
+    pub primary: AgentProcess,
+    /// Other agents on the same TTY (e.g. a `claude` started from inside another), by PID
+    pub nested: Vec<AgentProcess>,
+}
+
+/// Group agent processes by TTY, picking each TTY's primary process.
+pub fn group_agents_by_tty(agents: Vec<(String, AgentProcess)>) -> HashMap<String, TtyAgents> {
+    let mut map: HashMap<String, TtyAgents> = HashMap::new();
+    for (tty, proc) in agents {
+        match map.get_mut(&tty) {
+            None => {
+                map.insert(
+                    tty,
+                    TtyAgents {
+                        primary: proc,
+                        nested: Vec::new(),
+                    },
+                );
+            }
+            Some(group) => {
+                if proc.outranks(&group.primary) {
+                    group
+                        .nested
+                        .push(std::mem::replace(&mut group.primary, proc));
+                } else {
+                    group.nested.push(proc);
+                }
+                group.nested.sort_by_key(|a| a.pid);
+            }
+        }
+    }
+    map
+}
+
+/// How one agent's processes are recognized (configurable, see `config.rs`).
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProcessPatterns {
+    /// Exact process names (`pgrep -x`)
+    pub names: Vec<String>,
+    /// Extended regexes matched against the full command line (`pgrep -f`)
+    pub command_patterns: Vec<String>,
+}
+
+impl ProcessPatterns {
+    fn named(name: &str) -> Self {
+        ProcessPatterns {
+            names: vec![name.to_string()],
+            command_patterns: Vec::new(),
+        }
+    }
 }
 
-fn find_pids_by_name(process_name: &str) -> Vec<u32> {
-    let output = Command::new("pgrep")
-        .args(["-x", process_name])
-        .output()
-        .ok()
+/// Process matchers for each supported agent.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProcessMatchers {
+    pub claude: ProcessPatterns,
+    pub codex: ProcessPatterns,
+}
+
+impl Default for ProcessMatchers {
+    fn default() -> Self {
+        ProcessMatchers {
+            claude: ProcessPatterns::named("claude"),
+            codex: ProcessPatterns::named("codex"),
+        }
+    }
+}
+
+fn pgrep(args: &[&str]) -> Vec<u32> {
+    let output = run(Command::new("pgrep").args(args))
         .map(|o| String::from_utf8_lossy(&o.stdout).to_string())
         .unwrap_or_default();
     parse_pgrep_output(&output)
 }
 
-/// Find all claude PIDs via pgrep.
-pub fn find_claude_pids() -> Vec<u32> {
-    find_pids_by_name("claude")
+/// PIDs of processes named exactly `process_name` (like `pgrep -x`).
+pub fn find_pids_by_name(process_name: &str) -> Vec<u32> {
+    backend().pids_named(process_name)
+}
+
+/// PIDs of processes whose command line matches `pattern` (via `pgrep -f`).
+pub fn find_pids_by_command(pattern: &str) -> Vec<u32> {
+    pgrep(&["-f", "--", pattern])
 }
 
-/// Find all codex PIDs via pgrep.
-pub fn find_codex_pids() -> Vec<u32> {
-    find_pids_by_name("codex")
+/// PIDs matching any name or command pattern, sorted and deduplicated.
+/// Our own process is excluded since a loose pattern can match `claude-bar` itself.
+pub fn find_pids_matching(patterns: &ProcessPatterns) -> Vec<u32> {
+    let mut pids: Vec<u32> = patterns
+        .names
+        .iter()
+        .flat_map(|name| find_pids_by_name(name))
+        .chain(
+            patterns
+                .command_patterns
+                .iter()
+                .flat_map(|pattern| find_pids_by_command(pattern)),
+        )
+        .filter(|&pid| pid != std::process::id())
+        .collect();
+    pids.sort_unstable();
+    pids.dedup();
+    pids
 }
 
 /// Get the TTY for a given PID.
 pub fn get_pid_tty(pid: u32) -> Option<String> {
-    let output = Command::new("ps")
-        .args(["-o", "tty=", "-p", &pid.to_string()])
-        .output()
-        .ok()?;
-    parse_ps_tty(&String::from_utf8_lossy(&output.stdout))
+    backend().pid_tty(pid)
 }
 
-/// Get CWD for a given PID via lsof.
-pub fn get_pid_cwd(pid: u32) -> Option<String> {
-    let output = Command::new("lsof")
-        .args(["-p", &pid.to_string(), "-Fn"])
-        .output()
-        .ok()?;
-    parse_lsof_cwd(&String::from_utf8_lossy(&output.stdout))
+/// Start time of a process (unix seconds).
+pub fn get_pid_start_time(pid: u32) -> Option<u64> {
+    backend().pid_start_time(pid)
 }
 
-/// Build a map of TTY -> (PID, provider) for Claude and Codex processes.
-pub fn build_agent_by_tty() -> HashMap<String, AgentProcess> {
-    let mut map: HashMap<String, AgentProcess> = HashMap::new();
+/// Seconds from the unix time `started_at` until now.
+pub fn secs_since(started_at: u64) -> f64 {
+    unix_now().saturating_sub(started_at) as f64
+}
 
-    let mut all = Vec::new();
-    all.extend(find_claude_pids().into_iter().map(|pid| AgentProcess {
-        pid,
-        provider: Provider::Claude,
-    }));
-    all.extend(find_codex_pids().into_iter().map(|pid| AgentProcess {
+fn unix_now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// A process's CPU time at one poll.
+#[derive(Debug, Clone, Copy)]
+struct CpuSample {
+    at: Instant,
+    cpu_secs: f64,
+    /// Usage computed at this sample, reused by polls that follow too closely
+    percent: Option<f64>,
+}
+
+/// Polls closer together than this reuse the previous reading.
+const MIN_CPU_SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Previous sample per PID; cleared when it grows past [`MAX_CPU_SAMPLES`]
+/// entries so exited processes don't pile up.
+static CPU_SAMPLES: Mutex<BTreeMap<u32, CpuSample>> = Mutex::new(BTreeMap::new());
+const MAX_CPU_SAMPLES: usize = 256;
+
+/// Percent of one core used since the previous sample, or None for the first.
+fn cpu_percent_between(previous: Option<&CpuSample>, at: Instant, cpu_secs: f64) -> Option<f64> {
+    let previous = previous?;
+    let elapsed = at.duration_since(previous.at).as_secs_f64();
+    // Less CPU time than before: the PID was reused by a new process
+    if elapsed <= 0.0 || cpu_secs < previous.cpu_secs {
+        return None;
+    }
+    Some((cpu_secs - previous.cpu_secs) / elapsed * 100.0)
+}
+
+/// Percent of one core `pid` used since the previous call for it (None on the
+/// first call, so a one-shot `poll` never has it).
+pub fn cpu_percent(pid: u32) -> Option<f64> {
+    let cpu_secs = backend().cpu_time(pid)?;
+    let now = Instant::now();
+    let mut samples = CPU_SAMPLES.lock().unwrap();
+    if samples.len() >= MAX_CPU_SAMPLES && !samples.contains_key(&pid) {
+        samples.clear();
+    }
+    let previous = samples.get(&pid).copied();
+    if let Some(previous) = previous.filter(|p| now.duration_since(p.at) < MIN_CPU_SAMPLE_INTERVAL)
+    {
+        return previous.percent;
+    }
+    let percent = cpu_percent_between(previous.as_ref(), now, cpu_secs);
+    samples.insert(
         pid,
-        provider: Provider::Codex,
-    }));
-
-    for proc in all {
-        if let Some(tty) = get_pid_tty(proc.pid) {
-            // Prefer the most recently created process when two providers share a TTY.
-            match map.get(&tty) {
-                Some(existing) if existing.pid > proc.pid => {}
-                _ => {
-                    map.insert(tty, proc);
-                }
-            }
+        CpuSample {
+            at: now,
+            cpu_secs,
+            percent,
+        },
+    );
+    percent
+}
+
+/// Model a session was started with (`ANTHROPIC_MODEL=opus claude`).
+pub const MODEL_ENV: &str = "ANTHROPIC_MODEL";
+/// User-defined label for a session (`CLAUDE_BAR_LABEL=backend claude`).
+pub const LABEL_ENV: &str = "CLAUDE_BAR_LABEL";
+/// Variables starting with this are user-defined tags and are always read.
+pub const TAG_ENV_PREFIX: &str = "CLAUDE_BAR_";
+
+/// Environment variables claude-bar reads from agent processes, besides
+/// [`TAG_ENV_PREFIX`] tags.
+pub const AGENT_ENV_VARS: &[&str] = &[crate::config::CLAUDE_CONFIG_DIR_ENV, MODEL_ENV];
+
+/// Whether claude-bar keeps the variable `name` of an agent process.
+pub fn is_agent_env_var(name: &str) -> bool {
+    AGENT_ENV_VARS.contains(&name) || name.starts_with(TAG_ENV_PREFIX)
+}
+
+/// Values of the variables [`is_agent_env_var`] keeps, by name.
+pub type AgentEnv = BTreeMap<String, String>;
+
+/// [`AgentEnv`] of each process, by PID; cleared when it grows past
+/// [`MAX_ENV_CACHE`] entries so exited processes don't pile up.
+static ENV_CACHE: Mutex<BTreeMap<u32, (Option<u64>, AgentEnv)>> = Mutex::new(BTreeMap::new());
+const MAX_ENV_CACHE: usize = 256;
+
+/// The variables [`is_agent_env_var`] keeps from an agent process's environment, or None if
+/// it can't be read. A process's environment doesn't change, so it is read
+/// once per process.
+pub fn agent_env(process: ProcessIdentity) -> Option<AgentEnv> {
+    let mut cache = ENV_CACHE.lock().unwrap();
+    if let Some((started_at, env)) = cache.get(&process.pid) {
+        if *started_at == process.started_at {
+            return Some(env.clone());
         }
     }
+    let env: AgentEnv = backend()
+        .environ(process.pid)?
+        .into_iter()
+        .filter(|(name, _)| is_agent_env_var(name))
+        .collect();
+    if cache.len() >= MAX_ENV_CACHE {
+        cache.clear();
+    }
+    cache.insert(process.pid, (process.started_at, env.clone()));
+    Some(env)
+}
 
-    map
+/// Get CWD for a given PID.
+pub fn get_pid_cwd(pid: u32) -> Option<String> {
+    backend().pid_cwd(pid)
+}
+
+/// Build a map of TTY -> (PID, provider) for Claude and Codex processes.
+pub fn build_agent_by_tty(matchers: &ProcessMatchers) -> HashMap<String, AgentProcess> {
+    build_agents_by_tty(matchers)
+        .into_iter()
+        .map(|(tty, group)| (tty, group.primary))
+        .collect()
 }
 
-/// Walk up the process tree from `start_pid` to find a process named "claude".
-/// Returns (pid, tty) if found.
-pub fn find_claude_ancestor(start_pid: u32) -> Option<(u32, String)> {
+/// All agent processes attached to a terminal, grouped by TTY.
+pub fn build_agents_by_tty(matchers: &ProcessMatchers) -> HashMap<String, TtyAgents> {
+    let mut all = Vec::new();
+    all.extend(
+        find_pids_matching(&matchers.claude)
+            .into_iter()
+            .map(|pid| (pid, Provider::Claude)),
+    );
+    all.extend(
+        find_pids_matching(&matchers.codex)
+            .into_iter()
+            .map(|pid| (pid, Provider::Codex)),
+    );
+
+    let now = unix_now();
+    let agents = all
+        .into_iter()
+        .filter_map(|(pid, provider)| {
+            let line = backend().agent_line(pid)?;
+            let proc = AgentProcess {
+                pid,
+                provider,
+                started_at: line.elapsed.map(|e| now.saturating_sub(e)),
+                foreground: line.foreground,
+            };
+            Some((line.tty, proc))
+        })
+        .collect();
+    group_agents_by_tty(agents)
+}
+
+/// Walk up the process tree from `start_pid` to find a claude process
+/// (any process matching `patterns`). Returns (pid, tty) if found.
+pub fn find_claude_ancestor(start_pid: u32, patterns: &ProcessPatterns) -> Option<(u32, String)> {
+    let claude_pids: HashSet<u32> = find_pids_matching(patterns).into_iter().collect();
     let mut pid = start_pid;
     loop {
         if pid <= 1 {
             return None;
         }
-        // Get process name
-        let comm_output = Command::new("ps")
-            .args(["-o", "comm=", "-p", &pid.to_string()])
-            .output()
-            .ok()?;
-        let name = parse_ps_comm(&String::from_utf8_lossy(&comm_output.stdout));
-        if name.as_deref() == Some("claude") {
+        if claude_pids.contains(&pid) {
             let tty = get_pid_tty(pid)?;
             return Some((pid, tty));
         }
         // Move to parent
-        let ppid_output = Command::new("ps")
-            .args(["-o", "ppid=", "-p", &pid.to_string()])
-            .output()
-            .ok()?;
-        pid = parse_ps_ppid(&String::from_utf8_lossy(&ppid_output.stdout))?;
+        pid = backend().parent_pid(pid)?;
+    }
+}
+
+/// Where process details come from: `ps`/`lsof`/`pgrep` on macOS and BSD,
+/// `/proc` on Linux (where `ps -o tty=` output differs and `lsof` may be
+/// missing). Command-line patterns always go through `pgrep -f`.
+pub trait ProcessBackend: Send + Sync {
+    /// PIDs of processes named exactly `name`
+    fn pids_named(&self, name: &str) -> Vec<u32>;
+    /// Controlling terminal as a device path
+    fn pid_tty(&self, pid: u32) -> Option<String>;
+    /// Start time (unix seconds)
+    fn pid_start_time(&self, pid: u32) -> Option<u64>;
+    fn pid_cwd(&self, pid: u32) -> Option<String>;
+    fn parent_pid(&self, pid: u32) -> Option<u32>;
+    /// Process name (`comm`; an executable path from `ps` on macOS)
+    fn pid_name(&self, pid: u32) -> Option<String>;
+    /// TTY, foreground state and elapsed time of an agent process
+    fn agent_line(&self, pid: u32) -> Option<PsAgentLine>;
+    fn process_tree(&self) -> Vec<ProcEntry>;
+    /// User plus system CPU time used so far, in seconds
+    fn cpu_time(&self, pid: u32) -> Option<f64>;
+    /// Environment variables the process was started with
+    fn environ(&self, pid: u32) -> Option<Vec<(String, String)>>;
+}
+
+/// The backend for this machine: `/proc` when it is mounted, else `ps`.
+pub fn backend() -> &'static dyn ProcessBackend {
+    static BACKEND: std::sync::OnceLock<Box<dyn ProcessBackend>> = std::sync::OnceLock::new();
+    BACKEND
+        .get_or_init(|| {
+            if cfg!(target_os = "linux") && Path::new("/proc/self/stat").is_file() {
+                Box::new(ProcFsBackend::new("/proc"))
+            } else {
+                Box::new(PsBackend)
+            }
+        })
+        .as_ref()
+}
+
+/// `ps`, `lsof` and `pgrep` subprocesses.
+pub struct PsBackend;
+
+impl ProcessBackend for PsBackend {
+    fn pids_named(&self, name: &str) -> Vec<u32> {
+        pgrep(&["-x", name])
+    }
+
+    fn pid_tty(&self, pid: u32) -> Option<String> {
+        let output = run(Command::new("ps").args(["-o", "tty=", "-p", &pid.to_string()]))?;
+        parse_ps_tty(&String::from_utf8_lossy(&output.stdout))
+    }
+
+    fn pid_start_time(&self, pid: u32) -> Option<u64> {
+        let output = run(Command::new("ps").args(["-o", "etime=", "-p", &pid.to_string()]))?;
+        let elapsed = parse_ps_etime(&String::from_utf8_lossy(&output.stdout))?;
+        Some(unix_now().saturating_sub(elapsed))
+    }
+
+    fn pid_cwd(&self, pid: u32) -> Option<String> {
+        let output = run(Command::new("lsof").args(["-p", &pid.to_string(), "-Fn"]))?;
+        parse_lsof_cwd(&String::from_utf8_lossy(&output.stdout))
+    }
+
+    fn parent_pid(&self, pid: u32) -> Option<u32> {
+        let output = run(Command::new("ps").args(["-o", "ppid=", "-p", &pid.to_string()]))?;
+        parse_ps_ppid(&String::from_utf8_lossy(&output.stdout))
+    }
+
+    fn pid_name(&self, pid: u32) -> Option<String> {
+        let output = run(Command::new("ps").args(["-o", "comm=", "-p", &pid.to_string()]))?;
+        parse_ps_comm(&String::from_utf8_lossy(&output.stdout))
+    }
+
+    fn agent_line(&self, pid: u32) -> Option<PsAgentLine> {
+        // One ps call yields the TTY, foreground state and start time
+        let output = run(Command::new("ps").args([
+            "-o",
+            "tty=",
+            "-o",
+            "stat=",
+            "-o",
+            "etime=",
+            "-p",
+            &pid.to_string(),
+        ]))?;
+        parse_ps_agent_line(&String::from_utf8_lossy(&output.stdout))
+    }
+
+    fn process_tree(&self) -> Vec<ProcEntry> {
+        run(Command::new("ps").args(["-A", "-o", "pid=", "-o", "ppid=", "-o", "tty="]))
+            .map(|o| parse_ps_tree(&String::from_utf8_lossy(&o.stdout)))
+            .unwrap_or_default()
+    }
+
+    fn cpu_time(&self, pid: u32) -> Option<f64> {
+        let output = run(Command::new("ps").args(["-o", "time=", "-p", &pid.to_string()]))?;
+        parse_ps_cputime(&String::from_utf8_lossy(&output.stdout))
+    }
+
+    fn environ(&self, pid: u32) -> Option<Vec<(String, String)>> {
+        let output =
+            run(Command::new("ps").args(["eww", "-o", "command=", "-p", &pid.to_string()]))?;
+        let env = parse_ps_environ(&String::from_utf8_lossy(&output.stdout));
+        // Processes of other users show no environment at all
+        (!env.is_empty()).then_some(env)
+    }
+}
+
+/// Fields of `/proc/<pid>/stat` claude-bar uses.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProcStat {
+    pub comm: String,
+    pub ppid: u32,
+    pub pgrp: i32,
+    /// Encoded device number of the controlling terminal (0 when detached)
+    pub tty_nr: u32,
+    /// Foreground process group of that terminal
+    pub tpgid: i32,
+    /// Clock ticks spent in user and kernel mode
+    pub utime: u64,
+    pub stime: u64,
+    /// Clock ticks after boot
+    pub starttime: u64,
+}
+
+/// Parse `/proc/<pid>/stat`. The command name is parenthesized and may itself
+/// contain spaces and parentheses, so fields are counted from the last `)`.
+pub fn parse_proc_stat(content: &str) -> Option<ProcStat> {
+    let open = content.find('(')?;
+    let close = content.rfind(')')?;
+    let comm = content.get(open + 1..close)?.to_string();
+    // Fields after the name start at field 3 (state)
+    let fields: Vec<&str> = content.get(close + 1..)?.split_whitespace().collect();
+    let field = |n: usize| fields.get(n - 3).copied();
+    Some(ProcStat {
+        comm,
+        ppid: field(4)?.parse().ok()?,
+        pgrp: field(5)?.parse().ok()?,
+        tty_nr: field(7)?.parse::<i64>().ok()? as u32,
+        tpgid: field(8)?.parse().ok()?,
+        utime: field(14)?.parse().ok()?,
+        stime: field(15)?.parse().ok()?,
+        starttime: field(22)?.parse().ok()?,
+    })
+}
+
+/// Device path for a `tty_nr` from `/proc/<pid>/stat`: pseudo-terminals
+/// (majors 136-143) are `/dev/pts/N`, virtual consoles `/dev/ttyN`.
+pub fn tty_nr_to_path(tty_nr: u32) -> Option<String> {
+    let major = (tty_nr >> 8) & 0xfff;
+    let minor = (tty_nr & 0xff) | ((tty_nr >> 12) & 0xfff00);
+    match major {
+        0 => None,
+        136..=143 => Some(format!("/dev/pts/{}", (major - 136) * 256 + minor)),
+        4 if minor < 64 => Some(format!("/dev/tty{}", minor)),
+        4 => Some(format!("/dev/ttyS{}", minor - 64)),
+        _ => None,
+    }
+}
+
+/// Boot time (unix seconds) from the `btime` line of `/proc/stat`.
+pub fn parse_proc_btime(content: &str) -> Option<u64> {
+    content
+        .lines()
+        .find_map(|line| line.strip_prefix("btime "))
+        .and_then(|v| v.trim().parse().ok())
+}
+
+/// `/proc` reads, no subprocesses. `root` is `/proc` except in tests.
+pub struct ProcFsBackend {
+    root: PathBuf,
+    ticks_per_sec: u64,
+}
+
+impl ProcFsBackend {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        let ticks = unsafe { libc::sysconf(libc::_SC_CLK_TCK) };
+        ProcFsBackend {
+            root: root.into(),
+            ticks_per_sec: if ticks > 0 { ticks as u64 } else { 100 },
+        }
+    }
+
+    fn stat(&self, pid: u32) -> Option<ProcStat> {
+        parse_proc_stat(&fs::read_to_string(self.root.join(pid.to_string()).join("stat")).ok()?)
+    }
+
+    fn pids(&self) -> Vec<u32> {
+        let mut pids: Vec<u32> = fs::read_dir(&self.root)
+            .map(|entries| {
+                entries
+                    .flatten()
+                    .filter_map(|e| e.file_name().to_str()?.parse().ok())
+                    .collect()
+            })
+            .unwrap_or_default();
+        pids.sort_unstable();
+        pids
+    }
+
+    /// The controlling terminal, else a terminal on stdin/stdout/stderr
+    /// (a process that called `setsid` keeps its fds but loses `tty_nr`).
+    fn tty(&self, pid: u32, stat: &ProcStat) -> Option<String> {
+        tty_nr_to_path(stat.tty_nr).or_else(|| {
+            (0..3).find_map(|fd| {
+                let target = fs::read_link(
+                    self.root
+                        .join(pid.to_string())
+                        .join("fd")
+                        .join(fd.to_string()),
+                )
+                .ok()?;
+                let target = target.to_string_lossy();
+                (target.starts_with("/dev/pts/") || target.starts_with("/dev/tty"))
+                    .then(|| target.to_string())
+            })
+        })
+    }
+
+    fn started_at(&self, stat: &ProcStat) -> Option<u64> {
+        let btime = parse_proc_btime(&fs::read_to_string(self.root.join("stat")).ok()?)?;
+        Some(btime + stat.starttime / self.ticks_per_sec)
+    }
+}
+
+impl ProcessBackend for ProcFsBackend {
+    fn pids_named(&self, name: &str) -> Vec<u32> {
+        self.pids()
+            .into_iter()
+            .filter(|pid| {
+                fs::read_to_string(self.root.join(pid.to_string()).join("comm"))
+                    .is_ok_and(|comm| comm.trim_end_matches('\n') == name)
+            })
+            .collect()
+    }
+
+    fn pid_tty(&self, pid: u32) -> Option<String> {
+        let stat = self.stat(pid)?;
+        self.tty(pid, &stat)
+    }
+
+    fn pid_start_time(&self, pid: u32) -> Option<u64> {
+        self.started_at(&self.stat(pid)?)
+    }
+
+    fn pid_cwd(&self, pid: u32) -> Option<String> {
+        fs::read_link(self.root.join(pid.to_string()).join("cwd"))
+            .ok()
+            .map(|p| p.to_string_lossy().to_string())
+    }
+
+    fn parent_pid(&self, pid: u32) -> Option<u32> {
+        self.stat(pid).map(|s| s.ppid)
+    }
+
+    fn pid_name(&self, pid: u32) -> Option<String> {
+        self.stat(pid).map(|s| s.comm)
+    }
+
+    fn agent_line(&self, pid: u32) -> Option<PsAgentLine> {
+        let stat = self.stat(pid)?;
+        Some(PsAgentLine {
+            tty: self.tty(pid, &stat)?,
+            foreground: stat.tpgid > 0 && stat.pgrp == stat.tpgid,
+            elapsed: self
+                .started_at(&stat)
+                .map(|started| unix_now().saturating_sub(started)),
+        })
+    }
+
+    fn process_tree(&self) -> Vec<ProcEntry> {
+        self.pids()
+            .into_iter()
+            .filter_map(|pid| {
+                let stat = self.stat(pid)?;
+                Some(ProcEntry {
+                    pid,
+                    ppid: stat.ppid,
+                    tty: tty_nr_to_path(stat.tty_nr),
+                })
+            })
+            .collect()
+    }
+
+    fn cpu_time(&self, pid: u32) -> Option<f64> {
+        let stat = self.stat(pid)?;
+        Some((stat.utime + stat.stime) as f64 / self.ticks_per_sec as f64)
+    }
+
+    fn environ(&self, pid: u32) -> Option<Vec<(String, String)>> {
+        let content = fs::read(self.root.join(pid.to_string()).join("environ")).ok()?;
+        Some(parse_proc_environ(&content))
     }
 }
 
@@ -193,6 +905,130 @@ pub fn find_claude_in_tree(
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_run_counts_and_records_spawn_errors() {
+        let (outer, tally) = tallied(|| {
+            let (output, inner) =
+                tallied(|| run(&mut Command::new("/nonexistent/claude-bar-test-binary")));
+            assert!(output.is_none());
+            inner
+        });
+        assert_eq!(outer, tally);
+        assert_eq!(tally.subprocesses, 1);
+        let err = tally.last_error.unwrap();
+        assert!(err.contains("failed to run /nonexistent/claude-bar-test-binary"));
+
+        // Another thread's failures stay out of this thread's tally
+        std::thread::spawn(|| run(&mut Command::new("/nonexistent/claude-bar-test-binary")))
+            .join()
+            .unwrap();
+        assert_eq!(tallied(|| ()).1, Tally::default());
+    }
+
+    #[test]
+    fn test_parse_proc_stat() {
+        let stat = parse_proc_stat(
+            "4242 (claude (node) x) S 4200 4242 4200 34819 4242 4194560 1 0 0 0 5 2 0 0 20 0 11 0 98765 0 0",
+        )
+        .unwrap();
+        assert_eq!(stat.comm, "claude (node) x");
+        assert_eq!(stat.ppid, 4200);
+        assert_eq!(stat.pgrp, 4242);
+        assert_eq!(stat.tty_nr, 34819);
+        assert_eq!(stat.tpgid, 4242);
+        assert_eq!((stat.utime, stat.stime), (5, 2));
+        assert_eq!(stat.starttime, 98765);
+        assert!(parse_proc_stat("4242 (claude) S 1").is_none());
+    }
+
+    #[test]
+    fn test_tty_nr_to_path() {
+        assert_eq!(tty_nr_to_path(0), None);
+        assert_eq!(tty_nr_to_path(34819).as_deref(), Some("/dev/pts/3"));
+        // Minor numbers above 255 spill into the high bits
+        assert_eq!(
+            tty_nr_to_path((136 << 8) | (1 << 20) | 4).as_deref(),
+            Some("/dev/pts/260")
+        );
+        assert_eq!(
+            tty_nr_to_path((137 << 8) | 1).as_deref(),
+            Some("/dev/pts/257")
+        );
+        assert_eq!(tty_nr_to_path((4 << 8) | 2).as_deref(), Some("/dev/tty2"));
+        assert_eq!(tty_nr_to_path((4 << 8) | 65).as_deref(), Some("/dev/ttyS1"));
+    }
+
+    #[test]
+    fn test_procfs_backend() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let root = tmp.path();
+        let btime = unix_now() - 1000;
+        fs::write(
+            root.join("stat"),
+            format!("cpu  1 2 3\nbtime {}\nprocesses 9\n", btime),
+        )
+        .unwrap();
+        let add = |pid: u32, comm: &str, stat: &str, cwd: &str| {
+            let dir = root.join(pid.to_string());
+            fs::create_dir_all(dir.join("fd")).unwrap();
+            fs::write(dir.join("comm"), format!("{}\n", comm)).unwrap();
+            fs::write(dir.join("stat"), format!("{} ({}) {}", pid, comm, stat)).unwrap();
+            std::os::unix::fs::symlink(cwd, dir.join("cwd")).unwrap();
+        };
+        let backend = ProcFsBackend::new(root);
+        let ticks = backend.ticks_per_sec;
+        // Foreground claude on pts/3, started 900s after boot
+        add(
+            10,
+            "claude",
+            &format!(
+                "S 9 10 9 34819 10 0 0 0 0 0 0 0 0 0 0 0 0 0 {}",
+                900 * ticks
+            ),
+            "/home/me/api",
+        );
+        // Background claude detached from its session, still writing to pts/5
+        add(
+            11,
+            "claude",
+            "S 9 11 11 0 -1 0 0 0 0 0 0 0 0 0 0 0 0 0 0",
+            "/home/me/web",
+        );
+        std::os::unix::fs::symlink("/dev/pts/5", root.join("11/fd/0")).unwrap();
+        add(
+            9,
+            "zsh",
+            "S 1 9 9 34819 10 0 0 0 0 0 0 0 0 0 0 0 0 0 0",
+            "/home/me",
+        );
+        fs::create_dir_all(root.join("self")).unwrap();
+
+        assert_eq!(backend.pids_named("claude"), vec![10, 11]);
+        assert_eq!(backend.pid_tty(10).as_deref(), Some("/dev/pts/3"));
+        assert_eq!(backend.pid_tty(11).as_deref(), Some("/dev/pts/5"));
+        assert_eq!(backend.pid_cwd(11).as_deref(), Some("/home/me/web"));
+        assert_eq!(backend.parent_pid(10), Some(9));
+        assert_eq!(backend.pid_start_time(10), Some(btime + 900));
+        fs::write(
+            root.join("10/environ"),
+            "HOME=/home/me\0CLAUDE_CONFIG_DIR=/home/me/.claude-work\0",
+        )
+        .unwrap();
+        let env = backend.environ(10).unwrap();
+        assert!(env.contains(&("CLAUDE_CONFIG_DIR".into(), "/home/me/.claude-work".into())));
+        assert!(backend.environ(11).is_none());
+
+        let line = backend.agent_line(10).unwrap();
+        assert!(line.foreground);
+        assert!((99..=101).contains(&line.elapsed.unwrap()));
+        assert!(!backend.agent_line(11).unwrap().foreground);
+        assert!(backend.agent_line(99).is_none());
+
+        let tree = backend.process_tree();
+        assert_eq!(tree.len(), 3);
+        assert_eq!(owner_of_tty(&tree, "/dev/pts/3", &[9]), Some(9));
+    }
+
     #[test]
     fn test_parse_pgrep_output() {
         let output = "12345\n67890\n111\n";
@@ -200,6 +1036,68 @@ mod tests {
         assert_eq!(pids, vec![12345, 67890, 111]);
     }
 
+    #[test]
+    fn test_process_matchers_deserialize() {
+        assert_eq!(
+            serde_json::from_str::<ProcessMatchers>("{}").unwrap(),
+            ProcessMatchers::default()
+        );
+
+        let matchers: ProcessMatchers = serde_json::from_str(
+            r#"{"claude": {"names": ["claude", "claude-code"], "command_patterns": ["node .*/claude( |$)"]}}"#,
+        )
+        .unwrap();
+        assert_eq!(matchers.claude.names, vec!["claude", "claude-code"]);
+        assert_eq!(
+            matchers.claude.command_patterns,
+            vec!["node .*/claude( |$)"]
+        );
+        assert_eq!(matchers.codex, ProcessPatterns::named("codex"));
+    }
+
+    #[test]
+    fn test_find_pids_matching_excludes_self() {
+        // The test binary's name (`claude_bar-<hash>`) has no regex metacharacters
+        let exe = std::env::current_exe().unwrap();
+        let name = exe.file_name().unwrap().to_string_lossy().to_string();
+        let patterns = ProcessPatterns {
+            names: vec![name.clone()],
+            command_patterns: vec![name],
+        };
+        assert!(!find_pids_matching(&patterns).contains(&std::process::id()));
+        assert!(find_pids_matching(&ProcessPatterns::default()).is_empty());
+    }
+
+    #[test]
+    fn test_parse_ps_tree() {
+        let output =
+            "    1     0 ??\n  500     1 ??\n  501   500 ttys003\n  502   501 ttys003\n bad\n";
+        let tree = parse_ps_tree(output);
+        assert_eq!(tree.len(), 4);
+        assert_eq!(tree[0].tty, None);
+        assert_eq!(
+            tree[3],
+            ProcEntry {
+                pid: 502,
+                ppid: 501,
+                tty: Some("/dev/ttys003".into())
+            }
+        );
+    }
+
+    #[test]
+    fn test_owner_of_tty() {
+        // alacritty 500 -> login 501 -> zsh 502 (ttys003); alacritty 600 -> zsh 601 (ttys004)
+        let tree = parse_ps_tree(
+            "1 0 ??\n500 1 ??\n501 500 ttys003\n502 501 ttys003\n600 1 ??\n601 600 ttys004\n700 1 ttys005\n",
+        );
+        let owners = [500, 600];
+        assert_eq!(owner_of_tty(&tree, "/dev/ttys003", &owners), Some(500));
+        assert_eq!(owner_of_tty(&tree, "/dev/ttys004", &owners), Some(600));
+        assert_eq!(owner_of_tty(&tree, "/dev/ttys005", &owners), None);
+        assert_eq!(owner_of_tty(&tree, "/dev/ttys009", &owners), None);
+    }
+
     #[test]
     fn test_parse_pgrep_empty() {
         assert_eq!(parse_pgrep_output(""), Vec::<u32>::new());
@@ -276,6 +1174,129 @@ mod tests {
         assert_eq!(parse_ps_comm("  \n  "), None);
     }
 
+    #[test]
+    fn test_parse_ps_etime() {
+        assert_eq!(parse_ps_etime("   00:07\n"), Some(7));
+        assert_eq!(parse_ps_etime("12:34"), Some(754));
+        assert_eq!(parse_ps_etime("01:02:03"), Some(3723));
+        assert_eq!(parse_ps_etime("2-01:02:03"), Some(2 * 86400 + 3723));
+        assert_eq!(parse_ps_etime(""), None);
+        assert_eq!(parse_ps_etime("7"), None);
+        assert_eq!(parse_ps_etime("x:01"), None);
+    }
+
+    #[test]
+    fn test_parse_ps_environ() {
+        let output = "node /usr/local/bin/claude --model=opus PATH=/usr/bin:/bin CLAUDE_CONFIG_DIR=/Users/me/.claude-work TERM=xterm\n";
+        let env = parse_ps_environ(output);
+        assert!(env.contains(&("CLAUDE_CONFIG_DIR".into(), "/Users/me/.claude-work".into())));
+        assert!(env.contains(&("TERM".into(), "xterm".into())));
+        // Flags with values are not variables
+        assert!(!env.iter().any(|(name, _)| name.starts_with('-')));
+        assert!(is_agent_env_var("ANTHROPIC_MODEL"));
+        assert!(is_agent_env_var("CLAUDE_BAR_LABEL"));
+        assert!(is_agent_env_var("CLAUDE_BAR_TEAM"));
+        assert!(!is_agent_env_var("PATH"));
+        assert_eq!(
+            parse_proc_environ(b"A=1\0B=x=y\0\0junk\0"),
+            vec![("A".into(), "1".into()), ("B".into(), "x=y".into())]
+        );
+    }
+
+    #[test]
+    fn test_parse_ps_cputime() {
+        assert_eq!(parse_ps_cputime("  0:01.50\n"), Some(1.5));
+        assert_eq!(parse_ps_cputime("01:02:03"), Some(3723.0));
+        assert_eq!(parse_ps_cputime("1-00:00:05"), Some(86405.0));
+        assert_eq!(parse_ps_cputime(""), None);
+        assert_eq!(parse_ps_cputime("5"), None);
+    }
+
+    #[test]
+    fn test_cpu_percent_between() {
+        let start = Instant::now();
+        let previous = CpuSample {
+            at: start,
+            cpu_secs: 10.0,
+            percent: None,
+        };
+        let later = start + Duration::from_secs(4);
+        assert_eq!(cpu_percent_between(None, later, 12.0), None);
+        assert_eq!(
+            cpu_percent_between(Some(&previous), later, 12.0),
+            Some(50.0)
+        );
+        assert_eq!(cpu_percent_between(Some(&previous), later, 10.0), Some(0.0));
+        // A reused PID starts over with less CPU time
+        assert_eq!(cpu_percent_between(Some(&previous), later, 1.0), None);
+    }
+
+    #[test]
+    fn test_parse_ps_agent_line() {
+        assert_eq!(
+            parse_ps_agent_line("ttys003  S+   1-00:00:05\n"),
+            Some(PsAgentLine {
+                tty: "/dev/ttys003".into(),
+                foreground: true,
+                elapsed: Some(86405),
+            })
+        );
+        assert_eq!(
+            parse_ps_agent_line("ttys003  Ss  00:10"),
+            Some(PsAgentLine {
+                tty: "/dev/ttys003".into(),
+                foreground: false,
+                elapsed: Some(10),
+            })
+        );
+        assert_eq!(
+            parse_ps_agent_line("ttys003"),
+            Some(PsAgentLine {
+                tty: "/dev/ttys003".into(),
+                foreground: false,
+                elapsed: None,
+            })
+        );
+        assert_eq!(parse_ps_agent_line("??  S  00:10"), None);
+        assert_eq!(parse_ps_agent_line(""), None);
+    }
+
+    fn agent(pid: u32, started_at: Option<u64>, foreground: bool) -> AgentProcess {
+        AgentProcess {
+            pid,
+            provider: Provider::Claude,
+            started_at,
+            foreground,
+        }
+    }
+
+    #[test]
+    fn test_group_agents_by_tty_prefers_foreground_then_senior() {
+        let tty = |t: &str| format!("/dev/{}", t);
+        let groups = group_agents_by_tty(vec![
+            // A claude started from a shell inside another claude: the child has the terminal
+            (tty("ttys001"), agent(100, Some(1000), false)),
+            (tty("ttys001"), agent(200, Some(2000), true)),
+            // Neither in the foreground: the longest running wins
+            (tty("ttys002"), agent(400, Some(3000), false)),
+            (tty("ttys002"), agent(300, Some(4000), false)),
+            (tty("ttys002"), agent(500, None, false)),
+            (tty("ttys003"), agent(600, None, true)),
+        ]);
+        assert_eq!(groups.len(), 3);
+
+        let first = &groups[&tty("ttys001")];
+        assert_eq!(first.primary.pid, 200);
+        assert_eq!(first.nested, vec![agent(100, Some(1000), false)]);
+
+        let second = &groups[&tty("ttys002")];
+        assert_eq!(second.primary.pid, 400);
+        let nested: Vec<u32> = second.nested.iter().map(|a| a.pid).collect();
+        assert_eq!(nested, vec![300, 500]);
+
+        assert!(groups[&tty("ttys003")].nested.is_empty());
+    }
+
     #[test]
     fn test_parse_ps_ppid() {
         assert_eq!(parse_ps_ppid("  12345\n"), Some(12345));
@@ -0,0 +1,405 @@
+//! Line-delimited JSON protocol spoken over the daemon's Unix socket (and,
+//! read-only, over TCP with `serve --listen-tcp`).
+//!
+//! Each request is one JSON object per line, e.g. `{"v":1,"cmd":"sessions"}`;
+//! each reply is one line: `{"v":1,"ok":true,"data":...}` or
+//! `{"v":1,"ok":false,"error":"..."}`. A connection may carry many requests.
+
+use crate::error::ClaudeBarError;
+use crate::filter::SessionFilter;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::error::Error;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// Environment variable that overrides the socket location.
+pub const SOCKET_ENV: &str = "CLAUDE_BAR_SOCKET";
+const SOCKET_NAME: &str = "claude-bar.sock";
+
+/// Where the daemon listens, shared by `serve` and every client.
+///
+/// `$CLAUDE_BAR_SOCKET`, then `socket_path` in the config file, then a per-user
+/// runtime dir (see [`crate::paths::runtime_dir`]).
+pub fn socket_path() -> PathBuf {
+    resolve_socket_path(
+        std::env::var(SOCKET_ENV).ok().filter(|v| !v.is_empty()),
+        crate::config::Config::load().socket_path,
+        &crate::paths::runtime_dir(),
+    )
+}
+
+pub fn resolve_socket_path(
+    env_override: Option<String>,
+    configured: Option<PathBuf>,
+    runtime_dir: &Path,
+) -> PathBuf {
+    if let Some(path) = env_override {
+        return PathBuf::from(path);
+    }
+    if let Some(path) = configured {
+        return path;
+    }
+    runtime_dir.join(SOCKET_NAME)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "cmd", rename_all = "lowercase")]
+pub enum Request {
+    /// Current session list, optionally filtered and cut down to `fields`
+    Sessions {
+        #[serde(default, flatten)]
+        filter: SessionFilter,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        fields: Vec<String>,
+    },
+    /// Sessions changed since a sequence number
+    Changes {
+        #[serde(default)]
+        since: u64,
+        /// `epoch` of the reply `since` came from; another one means the
+        /// daemon restarted and the full list is sent
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        epoch: Option<String>,
+    },
+    /// Focus the terminal hosting a session
+    Focus { tty: String },
+    /// Daemon liveness and timing
+    Health,
+    /// Re-poll immediately (and re-read configuration)
+    Reload,
+    /// First request on a `serve --listen` connection: the shared token
+    Auth { token: String },
+    /// A Claude Code hook event for the daemon to record (sent by `claude-bar hook`)
+    Hook(Box<crate::hook::HookDelivery>),
+}
+
+impl Request {
+    /// Every session, every field.
+    pub fn sessions() -> Self {
+        Request::Sessions {
+            filter: SessionFilter::default(),
+            fields: Vec::new(),
+        }
+    }
+
+    /// Requests that only report state, and so may be served over TCP.
+    pub fn is_read_only(&self) -> bool {
+        matches!(
+            self,
+            Request::Sessions { .. } | Request::Changes { .. } | Request::Health
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Response {
+    pub v: u32,
+    pub ok: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl Response {
+    pub fn ok(data: Value) -> Self {
+        Response {
+            v: PROTOCOL_VERSION,
+            ok: true,
+            data: Some(data),
+            error: None,
+        }
+    }
+
+    pub fn err(msg: impl Into<String>) -> Self {
+        Response {
+            v: PROTOCOL_VERSION,
+            ok: false,
+            data: None,
+            error: Some(msg.into()),
+        }
+    }
+}
+
+/// Reply data for `health`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HealthReport {
+    pub pid: u32,
+    pub version: String,
+    pub uptime_secs: u64,
+    pub seq: u64,
+    pub sessions: usize,
+    pub last_poll_unix: Option<u64>,
+    pub last_poll_duration_ms: u64,
+    pub last_poll_subprocesses: u64,
+    /// Mean duration of the recent polls the backoff decision is based on
+    #[serde(default)]
+    pub avg_poll_duration_ms: u64,
+    /// Interval the poller is currently sleeping between polls
+    #[serde(default)]
+    pub effective_interval_secs: f64,
+    /// Multiplier applied to the configured interval (1 = not backed off)
+    #[serde(default)]
+    pub poll_backoff: u32,
+    pub last_error: Option<ErrorReport>,
+    /// Daemons this one pulls sessions from (`serve --peer`)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub peers: Vec<crate::peer::PeerReport>,
+    /// macOS privacy permissions AppleScript calls were refused
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub permissions: Vec<crate::permissions::PermissionIssue>,
+    /// Settings the daemon is running with
+    pub config: Value,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ErrorReport {
+    pub at_unix: u64,
+    pub message: String,
+}
+
+/// Send one request to the daemon and read its reply.
+pub fn send_request(socket: &Path, request: &Request) -> Result<Response, Box<dyn Error>> {
+    send_request_with_timeout(socket, request, Duration::from_secs(10))
+}
+
+/// [`send_request`], giving up on the reply after `timeout`.
+pub fn send_request_with_timeout(
+    socket: &Path,
+    request: &Request,
+    timeout: Duration,
+) -> Result<Response, Box<dyn Error>> {
+    let stream =
+        UnixStream::connect(socket).map_err(|source| ClaudeBarError::SocketUnavailable {
+            target: socket.display().to_string(),
+            source,
+        })?;
+    stream.set_read_timeout(Some(timeout))?;
+    stream.set_write_timeout(Some(timeout))?;
+    exchange(stream, request)
+}
+
+/// Send one request to a daemon's loopback TCP listener (`serve --listen-tcp`).
+/// It takes no token: that only ever travels over TLS.
+pub fn send_request_tcp(
+    addr: &str,
+    request: &Request,
+    timeout: Duration,
+) -> Result<Response, Box<dyn Error>> {
+    let target = addr
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| format!("{} did not resolve", addr))?;
+    let stream = TcpStream::connect_timeout(&target, timeout).map_err(|source| {
+        ClaudeBarError::SocketUnavailable {
+            target: addr.to_string(),
+            source,
+        }
+    })?;
+    stream.set_read_timeout(Some(timeout))?;
+    stream.set_write_timeout(Some(timeout))?;
+    exchange(stream, request)
+}
+
+/// Send one request to a daemon's authenticated TLS listener (`serve --listen`),
+/// trusting only the certificate with `fingerprint` (SHA-256).
+pub fn send_request_tls(
+    addr: &str,
+    fingerprint: &[u8],
+    token: &str,
+    request: &Request,
+    timeout: Duration,
+) -> Result<Response, Box<dyn Error>> {
+    let target = addr
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| format!("{} did not resolve", addr))?;
+    let tcp = TcpStream::connect_timeout(&target, timeout).map_err(|source| {
+        ClaudeBarError::SocketUnavailable {
+            target: addr.to_string(),
+            source,
+        }
+    })?;
+    tcp.set_read_timeout(Some(timeout))?;
+    tcp.set_write_timeout(Some(timeout))?;
+    let conn = rustls::ClientConnection::new(
+        crate::tls::client_config(fingerprint),
+        crate::tls::server_name(addr),
+    )?;
+    let mut stream = rustls::StreamOwned::new(conn, tcp);
+    authenticate(&mut stream, token)?;
+    exchange(&mut stream, request)
+}
+
+/// Send the shared token as the connection's first request.
+fn authenticate<S: Read + Write>(stream: &mut S, token: &str) -> Result<(), Box<dyn Error>> {
+    let auth = exchange(
+        &mut *stream,
+        &Request::Auth {
+            token: token.to_string(),
+        },
+    )?;
+    if !auth.ok {
+        return Err(auth
+            .error
+            .unwrap_or_else(|| "authentication failed".into())
+            .into());
+    }
+    Ok(())
+}
+
+fn exchange<S: Read + Write>(mut stream: S, request: &Request) -> Result<Response, Box<dyn Error>> {
+    let mut line = serde_json::to_value(request)?;
+    line["v"] = PROTOCOL_VERSION.into();
+    writeln!(stream, "{}", line)?;
+    stream.flush()?;
+
+    let mut reply = String::new();
+    BufReader::new(stream).read_line(&mut reply)?;
+    Ok(serde_json::from_str(&reply)?)
+}
+
+/// Parse one request line. Requests without `v` are treated as the current version;
+/// requests from a newer major version are rejected so clients can detect the mismatch.
+pub fn parse_request(line: &str) -> Result<Request, String> {
+    let value: Value = serde_json::from_str(line).map_err(|e| format!("invalid JSON: {}", e))?;
+    let version = value
+        .get("v")
+        .and_then(|v| v.as_u64())
+        .unwrap_or(PROTOCOL_VERSION as u64);
+    if version > PROTOCOL_VERSION as u64 {
+        return Err(format!(
+            "unsupported protocol version {} (daemon speaks {})",
+            version, PROTOCOL_VERSION
+        ));
+    }
+    serde_json::from_value(value).map_err(|e| format!("bad request: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_requests() {
+        assert_eq!(
+            parse_request(r#"{"v":1,"cmd":"sessions"}"#),
+            Ok(Request::sessions())
+        );
+        assert_eq!(
+            parse_request(
+                r#"{"cmd":"sessions","status":["pending"],"project":"api-*","fields":["tty"]}"#
+            ),
+            Ok(Request::Sessions {
+                filter: SessionFilter {
+                    status: vec![crate::state::Status::Pending],
+                    project: Some("api-*".into()),
+                    terminal: Vec::new(),
+                },
+                fields: vec!["tty".into()],
+            })
+        );
+        assert_eq!(
+            serde_json::to_string(&Request::sessions()).unwrap(),
+            r#"{"cmd":"sessions"}"#
+        );
+        assert_eq!(parse_request(r#"{"cmd":"health"}"#), Ok(Request::Health));
+        assert_eq!(parse_request(r#"{"cmd":"reload"}"#), Ok(Request::Reload));
+        assert_eq!(
+            parse_request(r#"{"cmd":"changes","since":42}"#),
+            Ok(Request::Changes {
+                since: 42,
+                epoch: None
+            })
+        );
+        assert_eq!(
+            parse_request(r#"{"cmd":"changes"}"#),
+            Ok(Request::Changes {
+                since: 0,
+                epoch: None
+            })
+        );
+        assert_eq!(
+            parse_request(r#"{"cmd":"changes","since":3,"epoch":"4d2-17"}"#),
+            Ok(Request::Changes {
+                since: 3,
+                epoch: Some("4d2-17".into())
+            })
+        );
+        assert_eq!(
+            parse_request(r#"{"v":1,"cmd":"focus","tty":"/dev/ttys003"}"#),
+            Ok(Request::Focus {
+                tty: "/dev/ttys003".into()
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_hook_request() {
+        let line = r#"{"v":1,"cmd":"hook","input":{"session_id":"abc","transcript_path":"/t.jsonl","hook_event_name":"PreToolUse","tool_name":"Bash"},"tty":"ttys003","cwd":"/p","pid":42,"process_started":1700000000,"at":1700000100}"#;
+        let Ok(Request::Hook(delivery)) = parse_request(line) else {
+            panic!("not a hook request");
+        };
+        assert_eq!(delivery.tty, "ttys003");
+        assert_eq!(delivery.pid, Some(42));
+        assert_eq!(delivery.input.tool_name.as_deref(), Some("Bash"));
+        assert!(delivery.input.tool_use_id.is_none());
+        assert!(!Request::Hook(delivery).is_read_only());
+    }
+
+    #[test]
+    fn test_read_only_requests() {
+        assert!(Request::sessions().is_read_only());
+        assert!(Request::Changes {
+            since: 3,
+            epoch: None
+        }
+        .is_read_only());
+        assert!(Request::Health.is_read_only());
+        assert!(!Request::Reload.is_read_only());
+        assert!(!Request::Focus { tty: "x".into() }.is_read_only());
+    }
+
+    #[test]
+    fn test_parse_request_errors() {
+        assert!(parse_request("nope").is_err());
+        assert!(parse_request(r#"{"cmd":"explode"}"#).is_err());
+        assert!(parse_request(r#"{"cmd":"focus"}"#).is_err());
+        let err = parse_request(r#"{"v":2,"cmd":"sessions"}"#).unwrap_err();
+        assert!(err.contains("unsupported protocol version"));
+    }
+
+    #[test]
+    fn test_response_serialization() {
+        let ok = serde_json::to_string(&Response::ok(serde_json::json!([]))).unwrap();
+        assert_eq!(ok, r#"{"v":1,"ok":true,"data":[]}"#);
+        let err = serde_json::to_string(&Response::err("boom")).unwrap();
+        assert_eq!(err, r#"{"v":1,"ok":false,"error":"boom"}"#);
+    }
+
+    #[test]
+    fn test_resolve_socket_path_precedence() {
+        let runtime = Path::new("/run/user/501");
+        let env = || Some("/tmp/custom.sock".to_string());
+        let configured = || Some(PathBuf::from("/var/run/cb.sock"));
+
+        assert_eq!(
+            resolve_socket_path(env(), configured(), runtime),
+            PathBuf::from("/tmp/custom.sock")
+        );
+        assert_eq!(
+            resolve_socket_path(None, configured(), runtime),
+            PathBuf::from("/var/run/cb.sock")
+        );
+        assert_eq!(
+            resolve_socket_path(None, None, runtime),
+            PathBuf::from("/run/user/501/claude-bar.sock")
+        );
+    }
+}
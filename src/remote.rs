@@ -0,0 +1,336 @@
+use crate::logging;
+use crate::process;
+use crate::state::{DisplayResponse, Remote, SessionInfo, Terminal};
+use serde::{Deserialize, Serialize};
+use std::process::Command;
+
+/// A machine whose sessions are collected by running `claude-bar poll` over SSH.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RemoteHost {
+    /// `ssh` destination: `host`, `user@host` or a `~/.ssh/config` alias
+    pub host: String,
+    /// Name shown in the menu (defaults to `host`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    /// Remote command printing `poll` JSON (non-interactive SSH often lacks the usual PATH)
+    #[serde(default = "default_remote_command")]
+    pub command: String,
+}
+
+fn default_remote_command() -> String {
+    "claude-bar poll".into()
+}
+
+impl RemoteHost {
+    pub fn label(&self) -> &str {
+        self.label.as_deref().unwrap_or(&self.host)
+    }
+
+    /// `ssh` arguments: never prompt, and give up quickly on unreachable hosts.
+    pub fn ssh_args(&self) -> Vec<String> {
+        vec![
+            "-o".into(),
+            "BatchMode=yes".into(),
+            "-o".into(),
+            format!("ConnectTimeout={}", SSH_CONNECT_TIMEOUT_SECS),
+            self.host.clone(),
+            self.command.clone(),
+        ]
+    }
+}
+
+/// Seconds `ssh` waits for a connection before giving up on a host.
+pub const SSH_CONNECT_TIMEOUT_SECS: u64 = 5;
+
+/// Parse the remote `claude-bar poll` output, from an older build too. Sessions
+/// the remote itself collected from other machines are dropped so hosts can't loop.
+pub fn parse_remote_sessions(output: &str) -> Result<Vec<SessionInfo>, String> {
+    let response =
+        DisplayResponse::parse(output.trim()).map_err(|e| format!("bad poll output: {}", e))?;
+    Ok(response
+        .sessions
+        .into_iter()
+        .filter(|s| s.remote.is_none())
+        .collect())
+}
+
+/// Run `command` on the host over SSH and return its sessions.
+pub fn fetch_sessions(host: &RemoteHost) -> Result<Vec<SessionInfo>, String> {
+    let output = process::run(Command::new("ssh").args(host.ssh_args()))
+        .ok_or_else(|| "failed to run ssh".to_string())?;
+    if !output.status.success() {
+        // stderr is already logged by process::run
+        return Err(format!("ssh exited with {}", output.status));
+    }
+    parse_remote_sessions(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// A local `ssh` client process attached to a terminal.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SshTab {
+    pub pid: u32,
+    pub tty: String,
+    /// Destination as typed on the command line
+    pub destination: String,
+}
+
+/// `ssh` options that consume the following argument.
+const SSH_OPTS_WITH_ARG: &str = "BbcDEeFIiJLlmOoPpQRSWw";
+
+/// Destination of an `ssh` command line, e.g. `ssh -p 2222 me@build make` -> `me@build`.
+pub fn parse_ssh_destination(args: &str) -> Option<String> {
+    let mut words = args.split_whitespace();
+    let program = words.next()?;
+    if program.rsplit('/').next() != Some("ssh") {
+        return None;
+    }
+    while let Some(word) = words.next() {
+        if word == "--" {
+            return words.next().map(str::to_string);
+        }
+        match word.strip_prefix('-') {
+            // `-p 22` takes the next word; `-p22` and flag clusters like `-tA` don't
+            Some(opts) if !opts.is_empty() => {
+                let last = opts.chars().last()?;
+                if opts.len() == 1 && SSH_OPTS_WITH_ARG.contains(last) {
+                    words.next();
+                }
+            }
+            _ => return Some(word.to_string()),
+        }
+    }
+    None
+}
+
+/// Host part of an SSH destination: drops `ssh://`, `user@` and `:port`.
+pub fn destination_host(destination: &str) -> &str {
+    let d = destination.strip_prefix("ssh://").unwrap_or(destination);
+    let d = d.rsplit_once('@').map_or(d, |(_, host)| host);
+    d.split(':').next().unwrap_or(d)
+}
+
+/// The SSH tab showing `host`; with several connections the newest wins.
+pub fn tab_for_host<'a>(tabs: &'a [SshTab], host: &RemoteHost) -> Option<&'a SshTab> {
+    let wanted = destination_host(&host.host);
+    tabs.iter()
+        .filter(|t| t.destination == host.host || destination_host(&t.destination) == wanted)
+        .max_by_key(|t| t.pid)
+}
+
+/// Parse `ps -o tty= -o args= -p PID` output into an [`SshTab`].
+pub fn parse_ssh_ps_line(pid: u32, output: &str) -> Option<SshTab> {
+    let line = output.trim();
+    let (tty, args) = line.split_once(char::is_whitespace)?;
+    Some(SshTab {
+        pid,
+        tty: process::parse_ps_tty(tty)?,
+        destination: parse_ssh_destination(args.trim())?,
+    })
+}
+
+/// Interactive `ssh` clients running in local terminals.
+pub fn find_ssh_tabs() -> Vec<SshTab> {
+    process::find_pids_by_name("ssh")
+        .into_iter()
+        .filter_map(|pid| {
+            let output = process::run(Command::new("ps").args([
+                "-o",
+                "tty=",
+                "-o",
+                "args=",
+                "-p",
+                &pid.to_string(),
+            ]))?;
+            parse_ssh_ps_line(pid, &String::from_utf8_lossy(&output.stdout))
+        })
+        .collect()
+}
+
+/// Sessions from one remote host, tagged with its label and local SSH tab.
+/// `terminal_of` maps a local TTY to the terminal app hosting it.
+/// An unreachable host yields no sessions; the failure is logged.
+pub fn poll_host(
+    host: &RemoteHost,
+    tabs: &[SshTab],
+    terminal_of: impl Fn(&str) -> Terminal,
+) -> Vec<SessionInfo> {
+    let sessions = match fetch_sessions(host) {
+        Ok(sessions) => sessions,
+        Err(e) => {
+            logging::warn("remote", &format!("{}: {}", host.label(), e));
+            return Vec::new();
+        }
+    };
+    let local_tty = tab_for_host(tabs, host).map(|t| t.tty.clone());
+    let terminal = local_tty.as_deref().map_or(Terminal::Unknown, &terminal_of);
+    tag_sessions(sessions, host.label(), local_tty, terminal)
+}
+
+/// Mark remote sessions with their host and the local terminal showing them.
+pub fn tag_sessions(
+    sessions: Vec<SessionInfo>,
+    label: &str,
+    local_tty: Option<String>,
+    terminal: Terminal,
+) -> Vec<SessionInfo> {
+    sessions
+        .into_iter()
+        .map(|s| SessionInfo {
+            terminal,
+            // The remote machine's tab ID means nothing here; the SSH tab is found by TTY
+            terminal_id: None,
+            remote: Some(Remote {
+                host: label.to_string(),
+                local_tty: local_tty.clone(),
+            }),
+            ..s
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::{Provider, Status};
+
+    fn host(host: &str) -> RemoteHost {
+        RemoteHost {
+            host: host.into(),
+            label: None,
+            command: default_remote_command(),
+        }
+    }
+
+    fn tab(pid: u32, tty: &str, destination: &str) -> SshTab {
+        SshTab {
+            pid,
+            tty: tty.into(),
+            destination: destination.into(),
+        }
+    }
+
+    #[test]
+    fn test_remote_host_deserialize_defaults() {
+        let h: RemoteHost = serde_json::from_str(r#"{"host": "me@build"}"#).unwrap();
+        assert_eq!(h, host("me@build"));
+        assert_eq!(h.label(), "me@build");
+        assert_eq!(
+            h.ssh_args(),
+            vec![
+                "-o",
+                "BatchMode=yes",
+                "-o",
+                "ConnectTimeout=5",
+                "me@build",
+                "claude-bar poll"
+            ]
+        );
+
+        let h: RemoteHost = serde_json::from_str(
+            r#"{"host": "build", "label": "Build box", "command": "~/.cargo/bin/claude-bar poll"}"#,
+        )
+        .unwrap();
+        assert_eq!(h.label(), "Build box");
+        assert_eq!(h.ssh_args()[5], "~/.cargo/bin/claude-bar poll");
+    }
+
+    #[test]
+    fn test_parse_ssh_destination() {
+        assert_eq!(parse_ssh_destination("ssh build").as_deref(), Some("build"));
+        assert_eq!(
+            parse_ssh_destination("/usr/bin/ssh -p 2222 -tA me@build tmux attach").as_deref(),
+            Some("me@build")
+        );
+        assert_eq!(
+            parse_ssh_destination("ssh -o ServerAliveInterval=30 -p2222 build").as_deref(),
+            Some("build")
+        );
+        assert_eq!(
+            parse_ssh_destination("ssh -i ~/.ssh/id -- build").as_deref(),
+            Some("build")
+        );
+        assert_eq!(parse_ssh_destination("ssh -p 22"), None);
+        assert_eq!(parse_ssh_destination("sshd: me@pts/0"), None);
+        assert_eq!(parse_ssh_destination(""), None);
+    }
+
+    #[test]
+    fn test_destination_host() {
+        assert_eq!(destination_host("build"), "build");
+        assert_eq!(destination_host("me@build"), "build");
+        assert_eq!(destination_host("ssh://me@build:2222"), "build");
+    }
+
+    #[test]
+    fn test_tab_for_host() {
+        let tabs = vec![
+            tab(100, "/dev/ttys001", "me@build"),
+            tab(300, "/dev/ttys003", "build"),
+            tab(200, "/dev/ttys002", "other"),
+        ];
+        // Matches with or without the user part; newest connection wins
+        assert_eq!(tab_for_host(&tabs, &host("build")).unwrap().pid, 300);
+        assert_eq!(tab_for_host(&tabs, &host("me@build")).unwrap().pid, 300);
+        assert_eq!(
+            tab_for_host(&tabs, &host("other")).unwrap().tty,
+            "/dev/ttys002"
+        );
+        assert!(tab_for_host(&tabs, &host("missing")).is_none());
+    }
+
+    #[test]
+    fn test_parse_ssh_ps_line() {
+        assert_eq!(
+            parse_ssh_ps_line(42, "ttys004  ssh -t me@build\n"),
+            Some(tab(42, "/dev/ttys004", "me@build"))
+        );
+        // Our own BatchMode polls run without a terminal
+        assert_eq!(
+            parse_ssh_ps_line(43, "??  ssh -o BatchMode=yes build"),
+            None
+        );
+        assert_eq!(parse_ssh_ps_line(44, ""), None);
+    }
+
+    #[test]
+    fn test_parse_remote_sessions_and_tag() {
+        let output = r#"[
+            {"tty":"/dev/pts/1","pid":10,"cwd":"/srv/api","provider":"claude","terminal":"unknown","transcript":null,"status":"pending","pending_tool":"Bash","terminal_id":"w0t0p0"},
+            {"tty":"/dev/pts/2","pid":11,"cwd":"/srv/x","provider":"claude","terminal":"unknown","transcript":null,"status":"idle","remote":{"host":"elsewhere"}}
+        ]"#;
+        let sessions = parse_remote_sessions(output).unwrap();
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0].pending_tool.as_deref(), Some("Bash"));
+
+        let tagged = tag_sessions(
+            sessions,
+            "build",
+            Some("/dev/ttys004".into()),
+            Terminal::ITerm2,
+        );
+        assert_eq!(tagged[0].terminal, Terminal::ITerm2);
+        assert_eq!(tagged[0].provider, Provider::Claude);
+        assert_eq!(tagged[0].status, Status::Pending);
+        assert_eq!(tagged[0].key(), "build:/dev/pts/1");
+        assert_eq!(tagged[0].focus_tty(), Some("/dev/ttys004"));
+        assert_eq!(tagged[0].terminal_id, None);
+
+        assert!(parse_remote_sessions("bash: claude-bar: command not found").is_err());
+    }
+
+    #[test]
+    fn test_parse_remote_sessions_across_versions() {
+        // A newer remote: unknown fields, status and rule don't fail the poll
+        let output = r#"{"version":1,"generated_by":"claude-bar 9.0","sessions":[
+            {"tty":"/dev/pts/1","pid":10,"cwd":"/srv/api","provider":"claude","terminal":"kitty","transcript":null,"status":"compacting","rule":"compaction","pending_reason":{"kind":"elicitation"},"sparkline":[1,2]}
+        ]}"#;
+        let sessions = parse_remote_sessions(output).unwrap();
+        assert_eq!(sessions[0].status, Status::Unknown);
+        assert_eq!(sessions[0].terminal, Terminal::Unknown);
+        assert_eq!(sessions[0].rule, None);
+        assert_eq!(sessions[0].pending_reason, None);
+
+        let err = parse_remote_sessions(r#"{"version":2,"sessions":[]}"#).unwrap_err();
+        assert!(err.contains("schema version 2 is newer"), "{}", err);
+    }
+}
@@ -0,0 +1,1343 @@
+use crate::state::{DisplayResponse, PendingReason, SessionInfo, Status, Terminal};
+use crate::transcript;
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+
+/// Output format for `poll`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// Raw SessionInfo array (consumed by the menu bar app)
+    Json,
+    /// Raycast list items
+    Raycast,
+    /// Alfred Script Filter items
+    Alfred,
+    /// Versioned flat JSON for desktop widgets (Übersicht etc.)
+    Widget,
+    /// Dropdown model for the menu bar app, with the `menu` options applied
+    Menu,
+}
+
+/// Schema version of the `widget` format. Bump on breaking changes only;
+/// new fields may be added without a bump.
+pub const WIDGET_SCHEMA_VERSION: u32 = 1;
+
+// Status colors, kept in sync with icon.rs and the Swift app
+const HEX_ACTIVE: &str = "#32D74B";
+const HEX_PENDING: &str = "#FF9F0A";
+const HEX_IDLE: &str = "#8E8E93";
+const HEX_RATE_LIMITED: &str = "#BF5AF2";
+const HEX_UNKNOWN: &str = "#64D2FF";
+// Darker variants that stay legible on a light menu bar
+const HEX_ACTIVE_LIGHT: &str = "#248A3D";
+const HEX_PENDING_LIGHT: &str = "#C93400";
+const HEX_IDLE_LIGHT: &str = "#6C6C70";
+const HEX_RATE_LIMITED_LIGHT: &str = "#8944AB";
+const HEX_UNKNOWN_LIGHT: &str = "#0071A4";
+
+/// Status colors as `#RRGGBB` (overridable in the config file).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Colors {
+    pub active: String,
+    pub pending: String,
+    pub idle: String,
+    pub rate_limited: String,
+    pub unknown: String,
+}
+
+impl Default for Colors {
+    fn default() -> Self {
+        Colors {
+            active: HEX_ACTIVE.into(),
+            pending: HEX_PENDING.into(),
+            idle: HEX_IDLE.into(),
+            rate_limited: HEX_RATE_LIMITED.into(),
+            unknown: HEX_UNKNOWN.into(),
+        }
+    }
+}
+
+impl Colors {
+    /// Default palette for a light menu bar.
+    pub fn light() -> Self {
+        Colors {
+            active: HEX_ACTIVE_LIGHT.into(),
+            pending: HEX_PENDING_LIGHT.into(),
+            idle: HEX_IDLE_LIGHT.into(),
+            rate_limited: HEX_RATE_LIMITED_LIGHT.into(),
+            unknown: HEX_UNKNOWN_LIGHT.into(),
+        }
+    }
+
+    pub fn for_status(&self, status: Status) -> &str {
+        match status {
+            Status::Active => &self.active,
+            Status::Pending => &self.pending,
+            Status::Idle => &self.idle,
+            Status::RateLimited => &self.rate_limited,
+            Status::Unknown => &self.unknown,
+        }
+    }
+}
+
+/// Human-readable status label (matches the dropdown in the menu bar app).
+pub fn status_label(status: Status) -> &'static str {
+    match status {
+        Status::Active => "Running",
+        Status::Pending => "Needs input",
+        Status::Idle => "Idle",
+        Status::RateLimited => "Rate limited",
+        Status::Unknown => "No transcript",
+    }
+}
+
+/// Dropdown and icon options (`menu` in the config file), toggled from the
+/// menu bar app via `claude-bar menu`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MenuOptions {
+    /// Leave idle sessions out of the menu bar icon
+    pub hide_idle_in_icon: bool,
+    /// Fold idle sessions into a single dropdown row
+    pub collapse_idle: bool,
+    /// Only list sessions whose project is in `pinned_projects`
+    pub pinned_only: bool,
+    /// Project directory names
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub pinned_projects: Vec<String>,
+    /// Order of dropdown entries within each group
+    pub sort: SortOrder,
+    /// Section headers in the dropdown
+    pub group: GroupBy,
+    /// Ask before the dropdown's Approve/Deny items answer a permission prompt
+    pub confirm_responses: bool,
+    /// Show "Session 1", "Session 2"... instead of project names, for screen sharing
+    pub privacy_mode: bool,
+    /// Badge sessions with their `claude_profiles` name
+    pub show_profiles: bool,
+}
+
+impl Default for MenuOptions {
+    fn default() -> Self {
+        MenuOptions {
+            hide_idle_in_icon: false,
+            collapse_idle: false,
+            pinned_only: false,
+            pinned_projects: Vec::new(),
+            sort: SortOrder::default(),
+            group: GroupBy::default(),
+            confirm_responses: true,
+            privacy_mode: false,
+            show_profiles: true,
+        }
+    }
+}
+
+/// Dropdown ordering. Ties fall back to project name, then TTY, so rows stay put
+/// between polls.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    /// Pending first, then active, then idle
+    #[default]
+    Status,
+    /// Project directory name
+    Project,
+    /// Most recently written transcript first
+    Activity,
+    /// Terminal app
+    Terminal,
+}
+
+/// How dropdown entries are split into sections.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum GroupBy {
+    /// One flat list
+    None,
+    /// Local sessions first, then a section per remote host
+    #[default]
+    Host,
+    /// A section per terminal app
+    Terminal,
+}
+
+/// A run of consecutive `MenuView::sessions` under one header.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct MenuSection {
+    /// Header text; None for the untitled local section
+    pub title: Option<String>,
+    pub count: usize,
+}
+
+/// The on/off [`MenuOptions`], as named on the command line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum MenuToggle {
+    HideIdleInIcon,
+    CollapseIdle,
+    PinnedOnly,
+    ConfirmResponses,
+    PrivacyMode,
+    ShowProfiles,
+}
+
+impl MenuOptions {
+    pub fn set(&mut self, toggle: MenuToggle, on: bool) {
+        match toggle {
+            MenuToggle::HideIdleInIcon => self.hide_idle_in_icon = on,
+            MenuToggle::CollapseIdle => self.collapse_idle = on,
+            MenuToggle::PinnedOnly => self.pinned_only = on,
+            MenuToggle::ConfirmResponses => self.confirm_responses = on,
+            MenuToggle::PrivacyMode => self.privacy_mode = on,
+            MenuToggle::ShowProfiles => self.show_profiles = on,
+        }
+    }
+
+    /// Add or remove `project` from the pinned list.
+    pub fn pin(&mut self, project: &str, pinned: bool) {
+        self.pinned_projects.retain(|p| p != project);
+        if pinned {
+            self.pinned_projects.push(project.to_string());
+        }
+    }
+
+    pub fn is_pinned(&self, project: &str) -> bool {
+        self.pinned_projects.iter().any(|p| p == project)
+    }
+}
+
+/// What the menu bar app draws: the sessions listed in the dropdown, idle ones
+/// folded into one row, and the statuses shown in the icon.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MenuView {
+    pub sessions: Vec<SessionInfo>,
+    /// Headers splitting `sessions`, in order; counts add up to its length
+    pub sections: Vec<MenuSection>,
+    /// Idle sessions behind the collapsed row (empty unless `collapse_idle`)
+    pub collapsed_idle: Vec<SessionInfo>,
+    /// One entry per session drawn in the icon
+    pub icon: Vec<Status>,
+    /// Hover text and accessibility label for the icon
+    pub tooltip: String,
+    /// Key of the listed session that has been pending longest (see [`longest_pending`])
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_pending: Option<String>,
+    pub options: MenuOptions,
+    pub footer: MenuFooter,
+}
+
+/// Rows at the bottom of the dropdown: totals, and the state of the `serve`
+/// daemon when one is around (see [`crate::daemon::annotate_footer`]).
+#[derive(Debug, Clone, PartialEq, Default, Serialize)]
+pub struct MenuFooter {
+    /// e.g. "4 sessions · 1 needs input"
+    pub totals: String,
+    /// e.g. "Daemon up 2h 1m · last poll 3s ago"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub daemon: Option<String>,
+    /// Shown as a warning row, e.g. when the daemon doesn't answer
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub warning: Option<String>,
+}
+
+/// Session count and how many wait for input, e.g. "4 sessions · 1 needs
+/// input".
+pub fn menu_totals(sessions: &[SessionInfo]) -> String {
+    let mut totals = match sessions.len() {
+        1 => "1 session".to_string(),
+        n => format!("{} sessions", n),
+    };
+    match sessions
+        .iter()
+        .filter(|s| s.status == Status::Pending)
+        .count()
+    {
+        0 => {}
+        1 => totals.push_str(" · 1 needs input"),
+        n => totals.push_str(&format!(" · {} need input", n)),
+    }
+    totals
+}
+
+/// One-line summary of every session for the icon's tooltip, e.g.
+/// "myapp: running · api: needs input (Bash) · docs: idle".
+pub fn summary_tooltip(sessions: &[SessionInfo]) -> String {
+    if sessions.is_empty() {
+        return "No Claude sessions".into();
+    }
+    sessions
+        .iter()
+        .map(|s| {
+            let mut part = format!(
+                "{}: {}",
+                s.project_name(),
+                status_label(s.status).to_lowercase()
+            );
+            if s.status == Status::Pending {
+                let detail = match &s.pending_reason {
+                    Some(reason) => Some(reason.label()),
+                    None => s.pending_tool.clone(),
+                };
+                if let Some(detail) = detail {
+                    part.push_str(&format!(" ({})", detail));
+                }
+            }
+            if let Some(remote) = &s.remote {
+                part.push_str(&format!(" on {}", remote.host));
+            }
+            part
+        })
+        .collect::<Vec<_>>()
+        .join(" · ")
+}
+
+/// Apply the menu options. `pinned_only` with nothing pinned shows everything
+/// rather than an empty menu.
+pub fn build_menu_view(sessions: &[SessionInfo], options: &MenuOptions) -> MenuView {
+    build_menu_view_with(sessions, options, transcript_age)
+}
+
+/// Seconds since the session's transcript was written, for local sessions.
+pub fn transcript_age(s: &SessionInfo) -> Option<f64> {
+    // A remote transcript path means nothing on this machine
+    s.transcript
+        .as_deref()
+        .filter(|_| s.remote.is_none())
+        .and_then(transcript::get_mtime_age)
+}
+
+/// The pending session that has waited longest. Nothing is written to a
+/// transcript while its prompt waits, so that's the one whose transcript
+/// `age_of` says is oldest; sessions of unknown age come after the rest, and
+/// ties go to the earlier session.
+pub fn longest_pending(
+    sessions: &[SessionInfo],
+    age_of: impl Fn(&SessionInfo) -> Option<f64>,
+) -> Option<&SessionInfo> {
+    let mut best: Option<(&SessionInfo, f64)> = None;
+    for session in sessions.iter().filter(|s| s.status == Status::Pending) {
+        let age = age_of(session).unwrap_or(f64::NEG_INFINITY);
+        if best.is_none_or(|(_, best_age)| age > best_age) {
+            best = Some((session, age));
+        }
+    }
+    best.map(|(session, _)| session)
+}
+
+fn status_rank(status: Status) -> u8 {
+    match status {
+        Status::Pending => 0,
+        Status::RateLimited => 1,
+        Status::Unknown => 2,
+        Status::Active => 3,
+        Status::Idle => 4,
+    }
+}
+
+/// Section a session belongs to under `group`; None sorts (and shows) first.
+fn group_title(session: &SessionInfo, group: GroupBy) -> Option<String> {
+    match group {
+        GroupBy::None => None,
+        GroupBy::Host => match (&session.remote, &session.container) {
+            (Some(remote), _) => Some(remote.host.clone()),
+            (None, Some(container)) => Some(container.label().to_string()),
+            (None, None) => None,
+        },
+        GroupBy::Terminal => Some(session.terminal.to_string()),
+    }
+}
+
+/// [`build_menu_view`] with the transcript age (seconds) supplied by `age_of`.
+pub fn build_menu_view_with(
+    sessions: &[SessionInfo],
+    options: &MenuOptions,
+    age_of: impl Fn(&SessionInfo) -> Option<f64>,
+) -> MenuView {
+    let pinned_filter = options.pinned_only && !options.pinned_projects.is_empty();
+    let shown = sessions
+        .iter()
+        .filter(|s| !pinned_filter || options.is_pinned(&s.project_name()));
+    let (mut listed, mut collapsed_idle) = (Vec::new(), Vec::new());
+    for session in shown {
+        let mut session = session.clone();
+        if !options.show_profiles {
+            session.profile = None;
+        }
+        if options.collapse_idle && session.status == Status::Idle {
+            collapsed_idle.push(session);
+        } else {
+            listed.push(session);
+        }
+    }
+
+    let ages: Vec<f64> = listed
+        .iter()
+        .map(|s| age_of(s).unwrap_or(f64::INFINITY))
+        .collect();
+    let mut order: Vec<usize> = (0..listed.len()).collect();
+    order.sort_by(|&a, &b| {
+        let (x, y) = (&listed[a], &listed[b]);
+        let by_group = group_title(x, options.group).cmp(&group_title(y, options.group));
+        let by_sort = match options.sort {
+            SortOrder::Status => status_rank(x.status).cmp(&status_rank(y.status)),
+            SortOrder::Project => std::cmp::Ordering::Equal,
+            SortOrder::Activity => ages[a].total_cmp(&ages[b]),
+            SortOrder::Terminal => x.terminal.to_string().cmp(&y.terminal.to_string()),
+        };
+        by_group
+            .then(by_sort)
+            .then_with(|| x.project_name().cmp(&y.project_name()))
+            .then_with(|| x.tty.cmp(&y.tty))
+    });
+    let listed: Vec<SessionInfo> = order.into_iter().map(|i| listed[i].clone()).collect();
+    let next_pending = longest_pending(&listed, &age_of).map(SessionInfo::key);
+
+    let mut sections: Vec<MenuSection> = Vec::new();
+    for session in &listed {
+        let title = group_title(session, options.group);
+        match sections.last_mut() {
+            Some(last) if last.title == title => last.count += 1,
+            _ => sections.push(MenuSection { title, count: 1 }),
+        }
+    }
+
+    let icon = listed
+        .iter()
+        .chain(&collapsed_idle)
+        .map(|s| s.status)
+        .filter(|&s| !(options.hide_idle_in_icon && s == Status::Idle))
+        .collect();
+    let all = [listed.as_slice(), collapsed_idle.as_slice()].concat();
+    let tooltip = summary_tooltip(&all);
+    let footer = MenuFooter {
+        totals: menu_totals(&all),
+        ..MenuFooter::default()
+    };
+    MenuView {
+        sessions: listed,
+        sections,
+        collapsed_idle,
+        icon,
+        tooltip,
+        next_pending,
+        options: options.clone(),
+        footer,
+    }
+}
+
+/// Presentation-level view of a session shared by all launcher/widget renderers.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SessionRow {
+    pub project: String,
+    pub status: Status,
+    pub status_label: &'static str,
+    pub pending_tool: Option<String>,
+    pub pending_reason: Option<PendingReason>,
+    /// Prompts waiting for the current turn to finish
+    pub queued: u32,
+    /// Subagents still running
+    pub subagents: u32,
+    /// Prompts the user has sent in the session
+    pub turns: u32,
+    /// When the session's transcript began (unix seconds)
+    pub started_at: Option<u64>,
+    /// Conversation summary or first prompt
+    pub title: Option<String>,
+    pub color: String,
+    pub tty: String,
+    pub terminal: Terminal,
+    pub cwd: String,
+    /// Remote host label (None for local sessions)
+    pub host: Option<String>,
+    /// `claude_profiles` name of the session's config directory
+    pub profile: Option<String>,
+    /// `CLAUDE_BAR_LABEL` of the session's process
+    pub user_label: Option<String>,
+    /// Seconds since the transcript was last written (None without a local transcript)
+    pub elapsed_secs: Option<u64>,
+    pub focus_url: String,
+    pub focus_command: Vec<String>,
+}
+
+/// Build the render model for a list of sessions.
+/// `binary` is the claude-bar executable used in focus commands.
+pub fn build_rows(sessions: &[SessionInfo], binary: &str, colors: &Colors) -> Vec<SessionRow> {
+    sessions
+        .iter()
+        .map(|s| {
+            let tty_short = s.tty.trim_start_matches("/dev/").to_string();
+            let host = s.remote.as_ref().map(|r| r.host.clone());
+            let focus_url = crate::deeplink::focus_url(s);
+            SessionRow {
+                project: s.project_name(),
+                status: s.status,
+                status_label: status_label(s.status),
+                pending_tool: s.pending_tool.clone(),
+                pending_reason: s.pending_reason.clone(),
+                queued: s.queued,
+                subagents: s.subagents,
+                turns: s.turns,
+                started_at: s.started_at,
+                title: s.title.clone(),
+                color: colors.for_status(s.status).to_string(),
+                // Container sessions have no terminal; the URL routes them to VS Code
+                focus_command: match &s.container {
+                    Some(_) => vec![binary.to_string(), "open-url".into(), focus_url.clone()],
+                    None => {
+                        let mut command = vec![
+                            binary.to_string(),
+                            "focus".into(),
+                            "--terminal".into(),
+                            s.terminal.to_string(),
+                            "--tty".into(),
+                            s.focus_tty().unwrap_or_default().to_string(),
+                        ];
+                        if let Some(id) = s.terminal_id.as_ref().filter(|_| s.remote.is_none()) {
+                            command.extend(["--terminal-id".into(), id.clone()]);
+                        }
+                        // Last, so privacy mode can blank it
+                        command.extend(["--cwd".into(), s.cwd.clone()]);
+                        command
+                    }
+                },
+                focus_url,
+                tty: tty_short,
+                terminal: s.terminal,
+                cwd: s.cwd.clone(),
+                // A remote transcript path means nothing on this machine
+                elapsed_secs: s
+                    .transcript
+                    .as_deref()
+                    .filter(|_| host.is_none())
+                    .and_then(transcript::get_mtime_age)
+                    .map(|age| age as u64),
+                host,
+                profile: s.profile.clone(),
+                user_label: s.user_label().map(str::to_string),
+            }
+        })
+        .collect()
+}
+
+impl SessionRow {
+    /// Stable item id: the TTY, prefixed with the host for remote sessions.
+    pub fn id(&self) -> String {
+        match &self.host {
+            Some(host) => format!("{}:{}", host, self.tty),
+            None => self.tty.clone(),
+        }
+    }
+
+    /// Project name with the user's label and the profile badge, e.g.
+    /// "api · backend [work]".
+    pub fn label(&self) -> String {
+        let mut out = self.project.clone();
+        if let Some(label) = &self.user_label {
+            out.push_str(&format!(" · {}", label));
+        }
+        if let Some(profile) = &self.profile {
+            out.push_str(&format!(" [{}]", profile));
+        }
+        out
+    }
+
+    /// Status label plus what the session waits for, e.g. "Needs input · Bash"
+    /// or "Needs input · Plan approval", then running subagents and queued
+    /// prompts ("Running · 2 subagents running · 1 queued").
+    pub fn status_detail(&self) -> String {
+        let detail = match &self.pending_reason {
+            Some(reason) => Some(reason.label()),
+            None => self.pending_tool.clone(),
+        };
+        let mut out = match detail {
+            Some(detail) => format!("{} · {}", self.status_label, detail),
+            None => self.status_label.to_string(),
+        };
+        match self.subagents {
+            0 => {}
+            1 => out.push_str(" · 1 subagent running"),
+            n => out.push_str(&format!(" · {} subagents running", n)),
+        }
+        if self.queued > 0 {
+            out.push_str(&format!(" · {} queued", self.queued));
+        }
+        out
+    }
+
+    /// Secondary line: the status detail followed by the conversation title,
+    /// e.g. "Running · Fix flaky CI on macOS runners".
+    pub fn subtitle(&self) -> String {
+        match &self.title {
+            Some(title) => format!("{} · {}", self.status_detail(), title),
+            None => self.status_detail(),
+        }
+    }
+}
+
+/// Raycast list items: title, subtitle, tinted icon, accessories and a focus action.
+pub fn render_raycast(rows: &[SessionRow]) -> Value {
+    let items: Vec<Value> = rows
+        .iter()
+        .map(|r| {
+            json!({
+                "id": r.id(),
+                "title": r.project,
+                "subtitle": r.cwd,
+                "icon": {"source": "circle-filled-16", "tintColor": r.color},
+                "accessories": [
+                    {"tag": {"value": r.status_detail(), "color": r.color}},
+                    {"text": format!("{} · {}", r.terminal, r.tty)}
+                ],
+                "actions": [
+                    {
+                        "title": "Focus Session",
+                        "command": r.focus_command,
+                        "url": r.focus_url
+                    }
+                ]
+            })
+        })
+        .collect();
+    json!({ "items": items })
+}
+
+/// Alfred Script Filter JSON: title = project, subtitle = status (+ pending tool, title), arg = tty.
+pub fn render_alfred(rows: &[SessionRow]) -> Value {
+    let items: Vec<Value> = rows
+        .iter()
+        .map(|r| {
+            json!({
+                "uid": r.id(),
+                "title": r.project,
+                "subtitle": r.subtitle(),
+                "arg": r.tty,
+                "match": format!("{} {} {}", r.project, r.status, r.terminal),
+                "valid": true,
+                "variables": {
+                    "terminal": r.terminal.to_string(),
+                    "cwd": r.cwd,
+                    "focus_url": r.focus_url
+                },
+                "text": {"copy": r.cwd, "largetype": r.cwd}
+            })
+        })
+        .collect();
+    json!({ "items": items })
+}
+
+/// Quote a command for display in a POSIX shell.
+pub fn shell_join(args: &[String]) -> String {
+    args.iter()
+        .map(|a| {
+            let safe = !a.is_empty()
+                && a.chars()
+                    .all(|c| c.is_ascii_alphanumeric() || "-_./:=@%+,".contains(c));
+            if safe {
+                a.clone()
+            } else {
+                format!("'{}'", a.replace('\'', "'\\''"))
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Widget JSON: a version header and a flat array with everything a widget needs to draw.
+pub fn render_widget(rows: &[SessionRow], generated_at: u64) -> Value {
+    let sessions: Vec<Value> = rows
+        .iter()
+        .map(|r| {
+            json!({
+                "project": r.project,
+                "status": r.status,
+                "label": r.status_label,
+                "pending_tool": r.pending_tool,
+                "pending_reason": r.pending_reason,
+                "queued": r.queued,
+                "subagents": r.subagents,
+                "turns": r.turns,
+                "started_at": r.started_at,
+                "title": r.title,
+                "color": r.color,
+                "elapsed_secs": r.elapsed_secs,
+                "tty": r.tty,
+                "terminal": r.terminal,
+                "cwd": r.cwd,
+                "host": r.host,
+                "focus_command": shell_join(&r.focus_command),
+                "focus_url": r.focus_url,
+            })
+        })
+        .collect();
+    json!({
+        "version": WIDGET_SCHEMA_VERSION,
+        "generated_at": generated_at,
+        "sessions": sessions,
+    })
+}
+
+/// Generic label for the `index`th session (0-based) in privacy mode.
+fn private_label(index: usize) -> String {
+    format!("Session {}", index + 1)
+}
+
+/// Privacy mode: sessions labelled "Session 1", "Session 2"... in poll order,
+/// with no working directory, conversation title, transcript path or process
+/// environment. TTYs are kept so focusing still works.
+pub fn anonymize(sessions: &[SessionInfo]) -> Vec<SessionInfo> {
+    sessions
+        .iter()
+        .enumerate()
+        .map(|(i, s)| SessionInfo {
+            cwd: private_label(i),
+            title: None,
+            transcript: None,
+            env: Default::default(),
+            ..s.clone()
+        })
+        .collect()
+}
+
+/// [`anonymize`] for rows built from the real sessions, which keeps their
+/// elapsed times.
+fn anonymize_rows(rows: &mut [SessionRow]) {
+    for (i, row) in rows.iter_mut().enumerate() {
+        row.project = private_label(i);
+        row.cwd.clear();
+        row.title = None;
+        row.user_label = None;
+        // Focus falls back to the TTY alone
+        if let Some(cwd) = row.focus_command.last_mut() {
+            cwd.clear();
+        }
+    }
+}
+
+/// The render model for `sessions`, anonymized in privacy mode.
+fn display_rows(sessions: &[SessionInfo], colors: &Colors, menu: &MenuOptions) -> Vec<SessionRow> {
+    let binary = std::env::current_exe()
+        .map(|p| p.to_string_lossy().to_string())
+        .unwrap_or_else(|_| "claude-bar".into());
+    let mut rows = build_rows(sessions, &binary, colors);
+    if menu.privacy_mode {
+        anonymize_rows(&mut rows);
+    }
+    rows
+}
+
+/// `value` as one line of JSON, or indented with `pretty` (`poll --pretty`).
+pub fn to_json<T: Serialize>(value: &T, pretty: bool) -> Result<String, serde_json::Error> {
+    if pretty {
+        serde_json::to_string_pretty(value)
+    } else {
+        serde_json::to_string(value)
+    }
+}
+
+/// Render sessions in the requested output format.
+pub fn render(
+    sessions: &[SessionInfo],
+    format: OutputFormat,
+    colors: &Colors,
+    menu: &MenuOptions,
+    pretty: bool,
+) -> Result<String, serde_json::Error> {
+    let rows = || display_rows(sessions, colors, menu);
+    match format {
+        OutputFormat::Json if menu.privacy_mode => {
+            to_json(&DisplayResponse::new(anonymize(sessions)), pretty)
+        }
+        OutputFormat::Json => to_json(&DisplayResponse::new(sessions.to_vec()), pretty),
+        OutputFormat::Raycast => to_json(&render_raycast(&rows()), pretty),
+        OutputFormat::Alfred => to_json(&render_alfred(&rows()), pretty),
+        OutputFormat::Widget => {
+            let now = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0);
+            to_json(&render_widget(&rows(), now), pretty)
+        }
+        OutputFormat::Menu => to_json(&menu_view(sessions, menu), pretty),
+    }
+}
+
+/// `poll --compact`: one aligned line per session for reading in a terminal,
+/// e.g. `ttys003  pending  myapp  Bash` (TTY, status, project, then what a
+/// pending session waits for). Remote TTYs are prefixed with their host.
+pub fn render_compact(sessions: &[SessionInfo], colors: &Colors, menu: &MenuOptions) -> String {
+    let lines: Vec<[String; 4]> = display_rows(sessions, colors, menu)
+        .into_iter()
+        .map(|row| {
+            let target = row.id();
+            let waiting_on = match &row.pending_reason {
+                Some(reason) => Some(reason.label()),
+                None => row.pending_tool.clone(),
+            };
+            [
+                target,
+                row.status.to_string(),
+                row.project,
+                waiting_on.unwrap_or_default(),
+            ]
+        })
+        .collect();
+    let width = |i: usize| {
+        lines
+            .iter()
+            .map(|l| l[i].chars().count())
+            .max()
+            .unwrap_or(0)
+    };
+    let (w0, w1, w2) = (width(0), width(1), width(2));
+    lines
+        .iter()
+        .map(|[target, status, project, waiting_on]| {
+            let line = format!(
+                "{:<w0$}  {:<w1$}  {:<w2$}  {}",
+                target, status, project, waiting_on
+            );
+            format!("{}\n", line.trim_end())
+        })
+        .collect()
+}
+
+/// [`build_menu_view`], anonymized in privacy mode.
+pub fn menu_view(sessions: &[SessionInfo], menu: &MenuOptions) -> MenuView {
+    if !menu.privacy_mode {
+        return build_menu_view(sessions, menu);
+    }
+    // Sort by activity on the real transcripts, then drop them
+    let ages: HashMap<String, Option<f64>> = sessions
+        .iter()
+        .map(|s| (s.key(), transcript_age(s)))
+        .collect();
+    build_menu_view_with(&anonymize(sessions), menu, |s| {
+        ages.get(&s.key()).copied().flatten()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::Provider;
+
+    fn sessions() -> Vec<SessionInfo> {
+        vec![
+            SessionInfo {
+                pid: 100,
+                pending_tool: Some("Bash".into()),
+                ..SessionInfo::for_test("/dev/ttys003", "/Users/me/api", Status::Pending)
+            },
+            SessionInfo {
+                pid: 200,
+                provider: Provider::Codex,
+                terminal: Terminal::Alacritty,
+                ..SessionInfo::for_test("/dev/ttys004", "/Users/me/docs", Status::Idle)
+            },
+        ]
+    }
+
+    #[test]
+    fn test_build_rows() {
+        let rows = build_rows(&sessions(), "/bin/claude-bar", &Colors::default());
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].project, "api");
+        assert_eq!(rows[0].status_label, "Needs input");
+        assert_eq!(rows[0].color, HEX_PENDING);
+        assert_eq!(rows[0].tty, "ttys003");
+        assert_eq!(rows[0].focus_url, "claude-bar://focus?tty=ttys003");
+        assert_eq!(
+            rows[1].focus_command,
+            vec![
+                "/bin/claude-bar",
+                "focus",
+                "--terminal",
+                "alacritty",
+                "--tty",
+                "/dev/ttys004",
+                "--cwd",
+                "/Users/me/docs"
+            ]
+        );
+
+        let mut tabbed = sessions();
+        tabbed[0].terminal_id = Some("8E3A51B2".into());
+        let rows = build_rows(&tabbed, "/bin/claude-bar", &Colors::default());
+        assert_eq!(
+            rows[0].focus_command[4..9],
+            [
+                "--tty",
+                "/dev/ttys003",
+                "--terminal-id",
+                "8E3A51B2",
+                "--cwd"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_build_rows_remote_session_focuses_ssh_tab() {
+        let remote = SessionInfo {
+            tty: "/dev/pts/1".into(),
+            transcript: Some("/home/me/.claude/projects/x/s.jsonl".into()),
+            remote: Some(crate::state::Remote {
+                host: "build".into(),
+                local_tty: Some("/dev/ttys007".into()),
+            }),
+            ..sessions().remove(0)
+        };
+        let rows = build_rows(&[remote], "/bin/claude-bar", &Colors::default());
+        assert_eq!(rows[0].host.as_deref(), Some("build"));
+        assert_eq!(rows[0].id(), "build:pts/1");
+        assert_eq!(rows[0].elapsed_secs, None);
+        assert_eq!(rows[0].focus_url, "claude-bar://focus?tty=build:/dev/pts/1");
+        assert_eq!(rows[0].focus_command[5], "/dev/ttys007");
+    }
+
+    #[test]
+    fn test_container_session_row_and_group() {
+        let container = SessionInfo {
+            tty: "container:3f2a9c1e".into(),
+            pid: 0,
+            cwd: "/workspaces/api".into(),
+            terminal: Terminal::Unknown,
+            container: Some(crate::state::Container {
+                name: Some("api_devcontainer-app-1".into()),
+                local_folder: Some("/Users/me/api".into()),
+            }),
+            ..sessions().remove(0)
+        };
+        let rows = build_rows(
+            std::slice::from_ref(&container),
+            "/bin/claude-bar",
+            &Colors::default(),
+        );
+        assert_eq!(
+            rows[0].focus_command,
+            vec![
+                "/bin/claude-bar",
+                "open-url",
+                "claude-bar://focus?tty=container:3f2a9c1e"
+            ]
+        );
+        assert_eq!(
+            group_title(&container, GroupBy::Host).as_deref(),
+            Some("api_devcontainer-app-1")
+        );
+    }
+
+    #[test]
+    fn test_build_rows_uses_configured_colors() {
+        let colors = Colors {
+            pending: "#123456".into(),
+            ..Colors::default()
+        };
+        let rows = build_rows(&sessions(), "/bin/claude-bar", &colors);
+        assert_eq!(rows[0].color, "#123456");
+        assert_eq!(rows[1].color, HEX_IDLE);
+    }
+
+    #[test]
+    fn test_render_raycast_shape() {
+        let v = render_raycast(&build_rows(
+            &sessions(),
+            "/bin/claude-bar",
+            &Colors::default(),
+        ));
+        let items = v["items"].as_array().unwrap();
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[0]["title"], "api");
+        assert_eq!(items[0]["subtitle"], "/Users/me/api");
+        assert_eq!(items[0]["icon"]["tintColor"], HEX_PENDING);
+        assert_eq!(
+            items[0]["accessories"][0]["tag"]["value"],
+            "Needs input · Bash"
+        );
+        assert_eq!(items[1]["actions"][0]["command"][1], "focus");
+    }
+
+    #[test]
+    fn test_render_alfred_shape() {
+        let v = render_alfred(&build_rows(
+            &sessions(),
+            "/bin/claude-bar",
+            &Colors::default(),
+        ));
+        let items = v["items"].as_array().unwrap();
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[0]["title"], "api");
+        assert_eq!(items[0]["subtitle"], "Needs input · Bash");
+        assert_eq!(items[0]["arg"], "ttys003");
+        assert_eq!(items[1]["subtitle"], "Idle");
+
+        let mut plan = sessions();
+        plan[0].pending_tool = Some("ExitPlanMode".into());
+        plan[0].pending_reason = Some(PendingReason::PlanApproval);
+        let rows = build_rows(&plan, "/bin/claude-bar", &Colors::default());
+        assert_eq!(rows[0].status_detail(), "Needs input · Plan approval");
+
+        let mut busy = sessions();
+        busy[1].status = Status::Active;
+        busy[1].queued = 1;
+        let rows = build_rows(&busy, "/bin/claude-bar", &Colors::default());
+        assert_eq!(rows[1].status_detail(), "Running · 1 queued");
+        busy[1].subagents = 2;
+        let rows = build_rows(&busy, "/bin/claude-bar", &Colors::default());
+        assert_eq!(
+            rows[1].status_detail(),
+            "Running · 2 subagents running · 1 queued"
+        );
+        busy[1].subagents = 0;
+
+        busy[1].title = Some("Fix flaky CI on macOS runners".into());
+        let v = render_alfred(&build_rows(&busy, "/bin/claude-bar", &Colors::default()));
+        assert_eq!(
+            v["items"][1]["subtitle"],
+            "Running · 1 queued · Fix flaky CI on macOS runners"
+        );
+        assert_eq!(items[1]["variables"]["terminal"], "alacritty");
+    }
+
+    #[test]
+    fn test_build_menu_view() {
+        let mut all = sessions();
+        let mut third = all[0].clone();
+        third.tty = "/dev/ttys009".into();
+        third.cwd = "/Users/me/web".into();
+        third.status = Status::Idle;
+        all.push(third);
+
+        let view = build_menu_view(&all, &MenuOptions::default());
+        assert_eq!(view.sessions.len(), 3);
+        assert!(view.collapsed_idle.is_empty());
+        assert_eq!(view.icon, vec![Status::Pending, Status::Idle, Status::Idle]);
+
+        let options = MenuOptions {
+            hide_idle_in_icon: true,
+            collapse_idle: true,
+            ..Default::default()
+        };
+        let view = build_menu_view(&all, &options);
+        assert_eq!(view.sessions.len(), 1);
+        assert_eq!(view.collapsed_idle.len(), 2);
+        assert_eq!(view.icon, vec![Status::Pending]);
+
+        let pinned = MenuOptions {
+            pinned_only: true,
+            pinned_projects: vec!["web".into(), "docs".into()],
+            ..Default::default()
+        };
+        let view = build_menu_view(&all, &pinned);
+        let projects: Vec<String> = view.sessions.iter().map(|s| s.project_name()).collect();
+        assert_eq!(projects, ["docs", "web"]);
+
+        // Nothing pinned: the filter would hide everything, so it is ignored
+        let nothing = MenuOptions {
+            pinned_only: true,
+            ..Default::default()
+        };
+        assert_eq!(build_menu_view(&all, &nothing).sessions.len(), 3);
+    }
+
+    #[test]
+    fn test_summary_tooltip() {
+        let mut all = sessions();
+        all[0].pending_reason = Some(PendingReason::Question);
+        assert_eq!(
+            summary_tooltip(&all),
+            "api: needs input (Question) · docs: idle"
+        );
+        assert_eq!(
+            build_menu_view(&all, &MenuOptions::default()).tooltip,
+            summary_tooltip(&all)
+        );
+        assert_eq!(summary_tooltip(&[]), "No Claude sessions");
+    }
+
+    #[test]
+    fn test_menu_totals() {
+        let mut all = sessions();
+        assert_eq!(menu_totals(&all), "2 sessions · 1 needs input");
+        all[1].status = Status::Pending;
+        assert_eq!(menu_totals(&all), "2 sessions · 2 need input");
+        assert_eq!(menu_totals(&all[..1]), "1 session · 1 needs input");
+        assert_eq!(menu_totals(&[]), "0 sessions");
+        // Collapsed idle sessions count too
+        let options = MenuOptions {
+            collapse_idle: true,
+            ..MenuOptions::default()
+        };
+        all[1].status = Status::Idle;
+        let view = build_menu_view(&all, &options);
+        assert_eq!(view.collapsed_idle.len(), 1);
+        assert_eq!(view.footer.totals, "2 sessions · 1 needs input");
+        assert_eq!(view.footer.daemon, None);
+    }
+
+    #[test]
+    fn test_menu_view_sorting_and_sections() {
+        use crate::state::Remote;
+        let session = |tty: &str, project: &str, status: Status, terminal: Terminal| SessionInfo {
+            tty: tty.into(),
+            cwd: format!("/p/{}", project),
+            status,
+            terminal,
+            ..sessions()[0].clone()
+        };
+        let mut remote = session("/dev/ttys001", "build", Status::Pending, Terminal::ITerm2);
+        remote.remote = Some(Remote {
+            host: "devbox".into(),
+            local_tty: None,
+        });
+        let all = vec![
+            session("/dev/ttys005", "web", Status::Idle, Terminal::Alacritty),
+            remote,
+            session("/dev/ttys002", "api", Status::Active, Terminal::ITerm2),
+            session("/dev/ttys003", "cli", Status::Pending, Terminal::ITerm2),
+        ];
+        let ages = |s: &SessionInfo| match s.project_name().as_str() {
+            "web" => Some(5.0),
+            "api" => Some(60.0),
+            _ => None,
+        };
+        let view_of = |sort, group| {
+            let options = MenuOptions {
+                sort,
+                group,
+                ..Default::default()
+            };
+            let view = build_menu_view_with(&all, &options, ages);
+            let projects: Vec<String> = view.sessions.iter().map(|s| s.project_name()).collect();
+            (projects, view.sections)
+        };
+
+        // Default: status priority, remote host in its own section after local ones
+        let (projects, sections) = view_of(SortOrder::Status, GroupBy::Host);
+        assert_eq!(projects, ["cli", "api", "web", "build"]);
+        assert_eq!(
+            sections,
+            vec![
+                MenuSection {
+                    title: None,
+                    count: 3
+                },
+                MenuSection {
+                    title: Some("devbox".into()),
+                    count: 1
+                }
+            ]
+        );
+
+        let (projects, sections) = view_of(SortOrder::Project, GroupBy::None);
+        assert_eq!(projects, ["api", "build", "cli", "web"]);
+        assert_eq!(sections.len(), 1);
+
+        // Sessions without a local transcript sort last by activity
+        let (projects, _) = view_of(SortOrder::Activity, GroupBy::None);
+        assert_eq!(projects, ["web", "api", "build", "cli"]);
+
+        let (projects, sections) = view_of(SortOrder::Project, GroupBy::Terminal);
+        assert_eq!(projects, ["web", "api", "build", "cli"]);
+        let titles: Vec<_> = sections.iter().map(|s| s.title.clone().unwrap()).collect();
+        assert_eq!(titles, ["alacritty", "iterm2"]);
+    }
+
+    #[test]
+    fn test_longest_pending() {
+        let session = |tty: &str, status: Status| SessionInfo {
+            tty: tty.into(),
+            status,
+            ..sessions()[0].clone()
+        };
+        let all = vec![
+            session("/dev/ttys001", Status::Pending),
+            session("/dev/ttys002", Status::Idle),
+            session("/dev/ttys003", Status::Pending),
+            session("/dev/ttys004", Status::Pending),
+        ];
+        let ages = |s: &SessionInfo| match s.tty.as_str() {
+            "/dev/ttys002" => Some(900.0),
+            "/dev/ttys003" => Some(120.0),
+            "/dev/ttys004" => Some(30.0),
+            _ => None,
+        };
+        let tty = |sessions: &[SessionInfo]| longest_pending(sessions, ages).map(|s| s.tty.clone());
+        assert_eq!(tty(&all).as_deref(), Some("/dev/ttys003"));
+        // Unknown ages only win when nothing else is pending
+        assert_eq!(tty(&all[..2]).as_deref(), Some("/dev/ttys001"));
+        assert_eq!(tty(&all[1..2]), None);
+        assert_eq!(
+            build_menu_view_with(&all, &MenuOptions::default(), ages).next_pending,
+            Some("/dev/ttys003".into())
+        );
+    }
+
+    #[test]
+    fn test_render_widget_shape() {
+        let v = render_widget(
+            &build_rows(&sessions(), "/bin/claude-bar", &Colors::default()),
+            1700000000,
+        );
+        assert_eq!(v["version"], WIDGET_SCHEMA_VERSION);
+        assert_eq!(v["generated_at"], 1700000000u64);
+        let items = v["sessions"].as_array().unwrap();
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[0]["project"], "api");
+        assert_eq!(items[0]["status"], "pending");
+        assert_eq!(items[0]["color"], HEX_PENDING);
+        assert!(items[0]["elapsed_secs"].is_null());
+        assert_eq!(
+            items[1]["focus_command"],
+            "/bin/claude-bar focus --terminal alacritty --tty /dev/ttys004 --cwd /Users/me/docs"
+        );
+    }
+
+    #[test]
+    fn test_shell_join_quotes() {
+        let args = vec![
+            "a b".to_string(),
+            "it's".to_string(),
+            "".to_string(),
+            "ok".to_string(),
+        ];
+        assert_eq!(shell_join(&args), r#"'a b' 'it'\''s' '' ok"#);
+    }
+
+    #[test]
+    fn test_render_json_is_versioned_sessions() {
+        let out = render(
+            &sessions(),
+            OutputFormat::Json,
+            &Colors::default(),
+            &MenuOptions::default(),
+            false,
+        )
+        .unwrap();
+        let back: DisplayResponse = serde_json::from_str(&out).unwrap();
+        assert_eq!(back.version, crate::state::SESSIONS_SCHEMA_VERSION);
+        assert_eq!(back.sessions.len(), 2);
+        assert!(!out.contains('\n'));
+
+        let pretty = render(
+            &sessions(),
+            OutputFormat::Json,
+            &Colors::default(),
+            &MenuOptions::default(),
+            true,
+        )
+        .unwrap();
+        assert!(pretty.starts_with("{\n  \"version\": 1,\n  \"sessions\": [\n"));
+        assert_eq!(
+            serde_json::from_str::<DisplayResponse>(&pretty).unwrap(),
+            back
+        );
+    }
+
+    #[test]
+    fn test_render_compact() {
+        let mut input = sessions();
+        input.push(SessionInfo {
+            tty: "/dev/pts/1".into(),
+            status: Status::Pending,
+            pending_reason: Some(PendingReason::PlanApproval),
+            remote: Some(crate::state::Remote {
+                host: "build".into(),
+                local_tty: None,
+            }),
+            ..sessions().remove(0)
+        });
+        let out = render_compact(&input, &Colors::default(), &MenuOptions::default());
+        assert_eq!(
+            out,
+            concat!(
+                "ttys003      pending  api   Bash\n",
+                "ttys004      idle     docs\n",
+                "build:pts/1  pending  api   Plan approval\n",
+            )
+        );
+        assert_eq!(
+            render_compact(&[], &Colors::default(), &MenuOptions::default()),
+            ""
+        );
+    }
+
+    #[test]
+    fn test_profile_badge() {
+        let mut input = sessions();
+        input[0].profile = Some("work".into());
+        input[0]
+            .env
+            .insert("CLAUDE_BAR_LABEL".into(), "backend".into());
+        let rows = build_rows(&input, "claude-bar", &Colors::default());
+        assert_eq!(
+            rows[0].label(),
+            format!("{} · backend [work]", rows[0].project)
+        );
+        assert_eq!(rows[1].label(), rows[1].project);
+
+        let view = build_menu_view_with(&input, &MenuOptions::default(), |_| None);
+        assert!(view.sessions.iter().any(|s| s.profile.is_some()));
+        let mut options = MenuOptions::default();
+        options.set(MenuToggle::ShowProfiles, false);
+        let view = build_menu_view_with(&input, &options, |_| None);
+        assert!(view.sessions.iter().all(|s| s.profile.is_none()));
+    }
+
+    #[test]
+    fn test_privacy_mode_hides_projects() {
+        let mut input = sessions();
+        input[0].title = Some("Fix login".into());
+        input[0].transcript = Some("/Users/me/.claude/projects/-Users-me-api/a.jsonl".into());
+        input[0]
+            .env
+            .insert("CLAUDE_CONFIG_DIR".into(), "/Users/me/.claude-work".into());
+        let private = MenuOptions {
+            privacy_mode: true,
+            ..Default::default()
+        };
+        let colors = Colors::default();
+
+        for format in [
+            OutputFormat::Json,
+            OutputFormat::Raycast,
+            OutputFormat::Alfred,
+            OutputFormat::Widget,
+            OutputFormat::Menu,
+        ] {
+            let out = render(&input, format, &colors, &private, false).unwrap();
+            for secret in ["api", "docs", "/Users/me", "Fix login"] {
+                assert!(
+                    !out.contains(secret),
+                    "{:?} leaks {}: {}",
+                    format,
+                    secret,
+                    out
+                );
+            }
+            assert!(out.contains("Session 1") && out.contains("Session 2"));
+            // Colors and focus targets survive
+            assert!(out.contains("ttys003"));
+        }
+        let compact = render_compact(&input, &colors, &private);
+        assert!(compact.contains("Session 1") && !compact.contains("api"));
+
+        let alfred: Value = serde_json::from_str(
+            &render(&input, OutputFormat::Alfred, &colors, &private, true).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(alfred["items"][0]["title"], "Session 1");
+        assert_eq!(alfred["items"][1]["variables"]["cwd"], "");
+
+        let menu: Value = serde_json::from_str(
+            &render(&input, OutputFormat::Menu, &colors, &private, false).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(
+            menu["tooltip"],
+            "Session 1: needs input (Bash) · Session 2: idle"
+        );
+    }
+}
@@ -0,0 +1,200 @@
+//! `replay`: run the status heuristics against saved transcripts.
+//!
+//! A transcript goes through the same scan and rules as a live session, as if it
+//! was last written `--age` seconds ago (default: its real mtime), so a wrong
+//! status can be reproduced from the file alone. Given a directory, every
+//! `.jsonl` in it is replayed; fixtures named `<name>.<status>.jsonl` state the
+//! status they should get, and any that get another one fail the run.
+
+use crate::config;
+use crate::icon;
+use crate::state::Status;
+use crate::transcript::{self, StatusDecision, Thresholds};
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The decision for one replayed transcript.
+#[derive(Debug, Clone, Serialize)]
+pub struct Replay {
+    /// Status named by the fixture's file name
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expected: Option<Status>,
+    #[serde(flatten)]
+    pub decision: StatusDecision,
+}
+
+impl Replay {
+    /// False if the fixture expected a different status.
+    pub fn matches(&self) -> bool {
+        self.expected.is_none_or(|s| s == self.decision.status)
+    }
+}
+
+/// Status a fixture should get: `plan-review.pending.jsonl` -> pending.
+pub fn expected_status(path: &Path) -> Option<Status> {
+    let stem = path.file_stem()?.to_string_lossy();
+    let (_, status) = stem.rsplit_once('.')?;
+    icon::parse_status(status).ok()
+}
+
+/// `path` itself, or the `.jsonl` files directly in it, sorted by name.
+pub fn fixtures(path: &Path) -> Result<Vec<PathBuf>, String> {
+    if !path.is_dir() {
+        if !path.exists() {
+            return Err(format!("{} does not exist", path.display()));
+        }
+        return Ok(vec![path.to_path_buf()]);
+    }
+    let entries =
+        fs::read_dir(path).map_err(|e| format!("Can't read {}: {}", path.display(), e))?;
+    let mut files: Vec<PathBuf> = entries
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .filter(|p| p.is_file() && p.extension().is_some_and(|ext| ext == "jsonl"))
+        .collect();
+    if files.is_empty() {
+        return Err(format!("No .jsonl transcripts in {}", path.display()));
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Decide the status of one Claude transcript, as if last written `age_secs` ago.
+pub fn replay_file(
+    path: &Path,
+    age_secs: Option<f64>,
+    thresholds: &Thresholds,
+) -> Result<Replay, String> {
+    let mut summary = transcript::analyze_transcript_with(path, thresholds.max_scan_bytes)
+        .ok_or_else(|| format!("Can't read {}", path.display()))?;
+    if age_secs.is_some() {
+        summary.age_secs = age_secs;
+    }
+    let display = path.to_string_lossy();
+    let decision = StatusDecision::from_summary(summary, &display, None, thresholds)
+        .with_queued(transcript::queued_messages_for(&display));
+    Ok(Replay {
+        expected: expected_status(path),
+        decision,
+    })
+}
+
+/// Replay a transcript or a directory of fixtures.
+pub fn replay(
+    path: &Path,
+    age_secs: Option<f64>,
+    thresholds: &Thresholds,
+) -> Result<Vec<Replay>, String> {
+    fixtures(path)?
+        .iter()
+        .map(|file| replay_file(file, age_secs, thresholds))
+        .collect()
+}
+
+/// `path: status (rule)` and the reason for each replay, with mismatches marked.
+pub fn format_text(replays: &[Replay], thresholds: &Thresholds) -> String {
+    let mut out = String::new();
+    for replay in replays {
+        let decision = &replay.decision;
+        out.push_str(&format!(
+            "{}: {} ({})",
+            decision.transcript.as_deref().unwrap_or_default(),
+            decision.status,
+            decision.rule
+        ));
+        if let Some(expected) = replay.expected.filter(|_| !replay.matches()) {
+            out.push_str(&format!("  MISMATCH, expected {}", expected));
+        }
+        out.push_str(&format!("\n    {}\n", decision.reason(thresholds)));
+    }
+    out
+}
+
+/// Entry point of `claude-bar replay`: fails if any fixture got an unexpected status.
+pub fn run_replay(
+    path: &Path,
+    age_secs: Option<f64>,
+    json: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let thresholds = config::Config::load().thresholds;
+    let replays = replay(path, age_secs, &thresholds)?;
+    if json {
+        println!("{}", serde_json::to_string_pretty(&replays)?);
+    } else {
+        print!("{}", format_text(&replays, &thresholds));
+    }
+    let mismatched = replays.iter().filter(|r| !r.matches()).count();
+    if mismatched > 0 {
+        return Err(format!(
+            "{} of {} transcripts got an unexpected status",
+            mismatched,
+            replays.len()
+        )
+        .into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::Rule;
+    use tempfile::TempDir;
+
+    const PLAN: &str = concat!(
+        r#"{"type":"assistant","message":{"role":"assistant","content":[{"type":"tool_use","id":"p1","name":"EnterPlanMode","input":{}}]}}"#,
+        "\n",
+        r#"{"type":"user","message":{"role":"user","content":[{"type":"tool_result","tool_use_id":"p1","content":"ok"}]}}"#,
+        "\n",
+        r#"{"type":"assistant","message":{"role":"assistant","content":[{"type":"text","text":"Here is the plan"}]}}"#,
+        "\n",
+    );
+    const DONE: &str = concat!(
+        r#"{"type":"user","message":{"role":"user","content":"fix it"}}"#,
+        "\n",
+        r#"{"type":"assistant","message":{"role":"assistant","content":[{"type":"text","text":"Fixed"}]}}"#,
+        "\n",
+    );
+
+    #[test]
+    fn test_expected_status() {
+        assert_eq!(
+            expected_status(Path::new("f/plan-review.pending.jsonl")),
+            Some(Status::Pending)
+        );
+        assert_eq!(expected_status(Path::new("f/plan-review.jsonl")), None);
+        assert_eq!(expected_status(Path::new("f/v1.2.jsonl")), None);
+    }
+
+    #[test]
+    fn test_replay_directory_with_age() {
+        let tmp = TempDir::new().unwrap();
+        fs::write(tmp.path().join("plan.pending.jsonl"), PLAN).unwrap();
+        fs::write(tmp.path().join("done.idle.jsonl"), DONE).unwrap();
+        fs::write(tmp.path().join("notes.txt"), "not a transcript").unwrap();
+
+        let t = Thresholds::default();
+        let replays = replay(tmp.path(), Some(600.0), &t).unwrap();
+        assert_eq!(replays.len(), 2);
+        assert!(replays.iter().all(Replay::matches));
+        assert_eq!(replays[0].decision.rule, Rule::TurnFinished);
+        assert_eq!(replays[1].decision.rule, Rule::PlanMode);
+        assert_eq!(replays[1].decision.age_secs, Some(600.0));
+
+        // Written a moment ago, the finished turn still counts as active
+        let recent = replay(&tmp.path().join("done.idle.jsonl"), Some(1.0), &t).unwrap();
+        assert!(!recent[0].matches());
+        let text = format_text(&recent, &t);
+        assert!(text.contains("done.idle.jsonl: active (recent_write)  MISMATCH, expected idle"));
+        assert!(text.contains("written 1s ago, within active_secs (10s)"));
+    }
+
+    #[test]
+    fn test_fixtures_errors() {
+        let tmp = TempDir::new().unwrap();
+        assert!(fixtures(tmp.path()).unwrap_err().contains("No .jsonl"));
+        assert!(fixtures(&tmp.path().join("missing.jsonl"))
+            .unwrap_err()
+            .contains("does not exist"));
+    }
+}
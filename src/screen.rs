@@ -0,0 +1,245 @@
+//! Sessions running inside GNU screen.
+//!
+//! A session's process inherits `STY` (`12345.pts-0.host`: the screen server's
+//! PID and session name) and `WINDOW` (the window number) from screen, which is
+//! how its window is found; `screen -ls` confirms the session is still there
+//! and whether a client is attached. Its PTY belongs to the screen server
+//! rather than a terminal app, so it polls as `unknown`; focusing selects the
+//! window in the attached client and raises the terminal that client runs in.
+
+use crate::config::Config;
+use crate::focus;
+use crate::process;
+use crate::state::Terminal;
+use crate::terminal;
+use std::process::Command;
+
+/// The screen window a session runs in.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScreenWindow {
+    /// `STY`: `<server pid>.<name>`
+    pub session: String,
+    /// `WINDOW`: the window number
+    pub window: String,
+}
+
+impl ScreenWindow {
+    /// PID of the screen server, the part of `STY` before the first dot.
+    pub fn server_pid(&self) -> Option<u32> {
+        self.session.split('.').next()?.parse().ok()
+    }
+}
+
+/// The screen window of a process whose environment is `env`, if it runs in one.
+pub fn window_from_env(env: &[(String, String)]) -> Option<ScreenWindow> {
+    let var = |name: &str| {
+        env.iter()
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v.clone())
+            .filter(|v| !v.is_empty())
+    };
+    Some(ScreenWindow {
+        session: var("STY")?,
+        window: var("WINDOW")?,
+    })
+}
+
+/// The screen window `pid` runs in.
+pub fn window_for_pid(pid: u32) -> Option<ScreenWindow> {
+    window_from_env(&process::backend().environ(pid)?)
+}
+
+/// A session from `screen -ls`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScreenSession {
+    /// `<server pid>.<name>`, as in `STY`
+    pub session: String,
+    pub attached: bool,
+}
+
+/// Parse `screen -ls` output: a tab-indented line per session, e.g.
+/// `\t12345.pts-0.host\t(01/02/24 10:00:00)\t(Attached)`. The state is the
+/// last parenthesized field (`Attached`, `Detached`, `Multi, attached`).
+pub fn parse_screen_ls(output: &str) -> Vec<ScreenSession> {
+    output
+        .lines()
+        .filter(|line| line.starts_with('\t'))
+        .filter_map(|line| {
+            let mut fields = line.split('\t').filter(|f| !f.is_empty());
+            let session = fields.next()?.trim();
+            let (pid, _) = session.split_once('.')?;
+            pid.parse::<u32>().ok()?;
+            let state = fields.next_back().unwrap_or("").to_lowercase();
+            Some(ScreenSession {
+                session: session.to_string(),
+                attached: state.contains("attached") && !state.contains("detached"),
+            })
+        })
+        .collect()
+}
+
+/// Sessions of the current user's screen server(s). `screen -ls` exits
+/// non-zero even when it lists sessions, so only its output is looked at.
+pub fn list_sessions() -> Vec<ScreenSession> {
+    process::run(Command::new("screen").arg("-ls"))
+        .map(|o| parse_screen_ls(&String::from_utf8_lossy(&o.stdout)))
+        .unwrap_or_default()
+}
+
+/// The screen client showing a session, out of `clients` (screen processes
+/// with a terminal): the one that started the server if it's still its parent,
+/// else one whose command line names the session, else the only client.
+pub fn pick_client(clients: &[u32], server_parent: Option<u32>, naming: &[u32]) -> Option<u32> {
+    if let Some(parent) = server_parent.filter(|p| clients.contains(p)) {
+        return Some(parent);
+    }
+    if let Some(&pid) = clients.iter().find(|pid| naming.contains(pid)) {
+        return Some(pid);
+    }
+    match clients {
+        [only] => Some(*only),
+        _ => None,
+    }
+}
+
+/// PID and TTY of the client attached to `window`'s session.
+fn attached_client(window: &ScreenWindow) -> Option<(u32, String)> {
+    let backend = process::backend();
+    let server = window.server_pid()?;
+    let clients: Vec<(u32, String)> = process::find_pids_by_name("screen")
+        .into_iter()
+        .filter(|&pid| pid != server)
+        .filter_map(|pid| Some((pid, backend.pid_tty(pid)?)))
+        .collect();
+    let pids: Vec<u32> = clients.iter().map(|(pid, _)| *pid).collect();
+    let name = window.session.split_once('.').map_or("", |(_, name)| name);
+    let naming =
+        process::find_pids_by_command(&format!("screen .*({}|{})", server, regex_escape(name)));
+    let client = pick_client(&pids, backend.parent_pid(server), &naming)?;
+    clients.into_iter().find(|(pid, _)| *pid == client)
+}
+
+/// `s` with the characters special in a `pgrep` extended regex escaped.
+fn regex_escape(s: &str) -> String {
+    s.chars()
+        .flat_map(|c| {
+            let special = "\\.^$|?*+()[]{}".contains(c);
+            special.then_some('\\').into_iter().chain(Some(c))
+        })
+        .collect()
+}
+
+/// Terminal app (or IDE) hosting the client on `tty`.
+fn client_terminal(client: u32, tty: &str) -> Terminal {
+    let apps = Config::load().terminals;
+    match terminal::enumerate_terminal_ttys(&[], &apps).terminal_for(tty) {
+        Terminal::Unknown => {
+            let backend = process::backend();
+            terminal::find_ide_ancestor(client, |p| backend.pid_name(p), |p| backend.parent_pid(p))
+                .map_or(Terminal::Unknown, |(ide, _)| ide)
+        }
+        found => found,
+    }
+}
+
+/// Switch the attached screen client to `window` and raise the terminal it
+/// runs in. A detached session has nothing to raise and is an error.
+pub fn focus(window: &ScreenWindow, cwd: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let session = list_sessions()
+        .into_iter()
+        .find(|s| s.session == window.session)
+        .ok_or_else(|| format!("screen session {} is gone", window.session))?;
+    if !session.attached {
+        return Err(format!(
+            "screen session {} is detached; attach it with `screen -r {}`",
+            window.session, window.session
+        )
+        .into());
+    }
+    let status = Command::new("screen")
+        .args(["-S", &window.session, "-X", "select", &window.window])
+        .status()?;
+    if !status.success() {
+        return Err(format!(
+            "screen couldn't select window {} of {}",
+            window.window, window.session
+        )
+        .into());
+    }
+    let (client, tty) = attached_client(window)
+        .ok_or_else(|| format!("No terminal found attached to {}", window.session))?;
+    if cfg!(target_os = "linux") {
+        return focus::focus_window_for_pid(client);
+    }
+    focus::run_focus(&client_terminal(client, &tty).to_string(), &tty, cwd, None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn env(vars: &[(&str, &str)]) -> Vec<(String, String)> {
+        vars.iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_window_from_env() {
+        let window = window_from_env(&env(&[
+            ("HOME", "/home/me"),
+            ("STY", "12345.pts-0.devbox"),
+            ("WINDOW", "2"),
+        ]))
+        .unwrap();
+        assert_eq!(window.session, "12345.pts-0.devbox");
+        assert_eq!(window.window, "2");
+        assert_eq!(window.server_pid(), Some(12345));
+        assert_eq!(window_from_env(&env(&[("STY", "12345.x")])), None);
+        assert_eq!(window_from_env(&env(&[("STY", ""), ("WINDOW", "0")])), None);
+    }
+
+    #[test]
+    fn test_parse_screen_ls() {
+        let output = "There are screens on:\n\
+                      \t12345.pts-0.devbox\t(Attached)\n\
+                      \t6789.work\t(01/02/24 10:00:00)\t(Detached)\n\
+                      \t4242.pair\t(Multi, attached)\n\
+                      3 Sockets in /run/screen/S-me.\n";
+        assert_eq!(
+            parse_screen_ls(output),
+            vec![
+                ScreenSession {
+                    session: "12345.pts-0.devbox".into(),
+                    attached: true,
+                },
+                ScreenSession {
+                    session: "6789.work".into(),
+                    attached: false,
+                },
+                ScreenSession {
+                    session: "4242.pair".into(),
+                    attached: true,
+                },
+            ]
+        );
+        assert!(parse_screen_ls("No Sockets found in /run/screen/S-me.\n").is_empty());
+    }
+
+    #[test]
+    fn test_pick_client() {
+        // The client that started the server is still its parent
+        assert_eq!(pick_client(&[10, 20], Some(20), &[10]), Some(20));
+        // Reattached with `screen -r work`
+        assert_eq!(pick_client(&[10, 20], Some(1), &[20]), Some(20));
+        assert_eq!(pick_client(&[10], Some(1), &[]), Some(10));
+        assert_eq!(pick_client(&[10, 20], Some(1), &[]), None);
+        assert_eq!(pick_client(&[], None, &[]), None);
+    }
+
+    #[test]
+    fn test_regex_escape() {
+        assert_eq!(regex_escape("pts-0.devbox"), r"pts-0\.devbox");
+        assert_eq!(regex_escape("a(b)|c"), r"a\(b\)\|c");
+    }
+}
@@ -0,0 +1,425 @@
+//! `claude-bar send` and `respond`: type a prompt, or the answer to a
+//! permission prompt, into a session's terminal.
+//!
+//! Sessions inside tmux get the text through `tmux send-keys` on their pane;
+//! iTerm2 sessions through the session's `write text`, neither of which needs
+//! the window in front. Alacritty has no scripting interface, so its window is
+//! focused and the text typed as keystrokes (needs Accessibility access), so
+//! `respond` refuses it: a misdirected Return could approve something else.
+
+use crate::error::ClaudeBarError;
+use crate::focus::{self, applescript_string};
+use crate::logging;
+use crate::state::{PendingReason, Provider, SessionInfo, Status, Terminal};
+use std::process::Command;
+
+/// Quick replies offered for pending sessions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum QuickAction {
+    /// Accept the highlighted choice of a permission prompt (just Enter)
+    Approve,
+    /// Send "continue"
+    Continue,
+}
+
+impl QuickAction {
+    pub fn text(self) -> &'static str {
+        match self {
+            QuickAction::Approve => "",
+            QuickAction::Continue => "continue",
+        }
+    }
+}
+
+/// tmux pane whose TTY is `tty`, from `list-panes -a -F '#{pane_tty} #{pane_id}'`.
+pub fn tmux_pane_for_tty(list_panes: &str, tty: &str) -> Option<String> {
+    list_panes.lines().find_map(|line| {
+        let (pane_tty, pane_id) = line.trim().split_once(' ')?;
+        (pane_tty == tty).then(|| pane_id.to_string())
+    })
+}
+
+fn tmux_pane(tty: &str) -> Option<String> {
+    // Most sessions aren't in tmux; a missing tmux or server is expected
+    let output = Command::new("tmux")
+        .args(["list-panes", "-a", "-F", "#{pane_tty} #{pane_id}"])
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    tmux_pane_for_tty(&String::from_utf8_lossy(&output.stdout), tty)
+}
+
+/// One thing to type into a terminal.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Key {
+    Text(String),
+    Enter,
+    Escape,
+}
+
+/// `text`, then Return if `enter`.
+pub fn text_keys(text: &str, enter: bool) -> Vec<Key> {
+    let mut keys = Vec::new();
+    if !text.is_empty() {
+        keys.push(Key::Text(text.to_string()));
+    }
+    if enter {
+        keys.push(Key::Enter);
+    }
+    keys
+}
+
+fn send_tmux(pane: &str, keys: &[Key]) -> Result<(), Box<dyn std::error::Error>> {
+    for key in keys {
+        let args: Vec<&str> = match key {
+            Key::Text(text) => vec!["-l", text],
+            Key::Enter => vec!["Enter"],
+            Key::Escape => vec!["Escape"],
+        };
+        let status = Command::new("tmux")
+            .args(["send-keys", "-t", pane])
+            .args(args)
+            .status()?;
+        if !status.success() {
+            return Err(format!("tmux send-keys to {} failed", pane).into());
+        }
+    }
+    Ok(())
+}
+
+/// Write `keys` to the iTerm2 session on `tty`.
+fn iterm2_write_script(tty: &str, keys: &[Key]) -> String {
+    let writes: Vec<String> = keys
+        .iter()
+        .map(|key| match key {
+            Key::Text(text) => format!(
+                "tell s to write text {} newline no",
+                applescript_string(text)
+            ),
+            Key::Enter => "tell s to write text \"\" newline yes".to_string(),
+            Key::Escape => "tell s to write text (character id 27) newline no".to_string(),
+        })
+        .map(|line| format!("                    {}\n", line))
+        .collect();
+    format!(
+        r#"tell application "iTerm2"
+    repeat with w in windows
+        repeat with t in tabs of w
+            repeat with s in sessions of t
+                if tty of s is {tty} then
+{writes}                    return
+                end if
+            end repeat
+        end repeat
+    end repeat
+end tell
+error "no iTerm2 session on " & {tty}"#,
+        tty = applescript_string(tty),
+        writes = writes.concat()
+    )
+}
+
+/// Type `keys` into the frontmost window, provided it belongs to process
+/// `pid`: a focus that silently failed must not send them to another app.
+fn keystroke_script(pid: u32, keys: &[Key]) -> String {
+    let mut script = format!(
+        "tell application \"System Events\"\n    \
+         if unix id of first process whose frontmost is true is not {pid} then \
+         error \"the terminal window is not in front\"\n"
+    );
+    for key in keys {
+        match key {
+            Key::Text(text) => {
+                script.push_str(&format!("    keystroke {}\n", applescript_string(text)))
+            }
+            Key::Enter => script.push_str("    key code 36\n"),
+            Key::Escape => script.push_str("    key code 53\n"),
+        }
+    }
+    script.push_str("end tell");
+    script
+}
+
+/// Where keys for a session are delivered.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Target {
+    /// tmux pane id
+    Tmux(String),
+    /// iTerm2 session, by TTY
+    ITerm2(String),
+    /// Keystrokes to the raised Alacritty window on this TTY
+    Alacritty(String),
+}
+
+impl Target {
+    /// Whether keys reach this terminal and no other: tmux panes and iTerm2
+    /// sessions are addressed directly, keystrokes go to whatever is in front.
+    fn is_exact(&self) -> bool {
+        !matches!(self, Target::Alacritty(_))
+    }
+}
+
+/// Where to type for `session` (the SSH tab for remote ones).
+fn target(session: &SessionInfo) -> Result<Target, Box<dyn std::error::Error>> {
+    let tty = session.focus_tty().ok_or_else(|| match &session.remote {
+        Some(remote) => format!("No local ssh tab connected to {}", remote.host),
+        None => "Session has no TTY".to_string(),
+    })?;
+    if let Some(pane) = tmux_pane(tty) {
+        return Ok(Target::Tmux(pane));
+    }
+    match session.terminal {
+        Terminal::ITerm2 => Ok(Target::ITerm2(tty.to_string())),
+        Terminal::Alacritty => Ok(Target::Alacritty(tty.to_string())),
+        // Keystrokes to an IDE may land in the editor instead
+        Terminal::JetBrains(_) | Terminal::Cursor | Terminal::Unknown => {
+            Err(format!("Don't know how to type into the terminal on {}", tty).into())
+        }
+    }
+}
+
+fn deliver(
+    session: &SessionInfo,
+    target: &Target,
+    keys: &[Key],
+) -> Result<(), Box<dyn std::error::Error>> {
+    match target {
+        Target::Tmux(pane) => send_tmux(pane, keys),
+        Target::ITerm2(tty) => {
+            focus::run_applescript(&iterm2_write_script(tty, keys), "iTerm2").map(|_| ())
+        }
+        Target::Alacritty(tty) => {
+            let pid = focus::raise_alacritty_window(tty, &session.cwd)?;
+            focus::run_applescript(&keystroke_script(pid, keys), "System Events").map(|_| ())
+        }
+    }
+}
+
+/// Type `keys` into the terminal showing `session` (the SSH tab for remote ones).
+pub fn send_keys(session: &SessionInfo, keys: &[Key]) -> Result<(), Box<dyn std::error::Error>> {
+    deliver(session, &target(session)?, keys)
+}
+
+/// The live session whose terminal is on `tty` (its own, or the SSH tab's).
+fn find_session(sessions: &[SessionInfo], tty: &str) -> Result<SessionInfo, ClaudeBarError> {
+    let tty_short = tty.trim_start_matches("/dev/");
+    sessions
+        .iter()
+        .find(|s| {
+            s.focus_tty()
+                .is_some_and(|t| t.trim_start_matches("/dev/") == tty_short)
+        })
+        .cloned()
+        .ok_or_else(|| ClaudeBarError::NoSession(format!("No live session on {}", tty_short)))
+}
+
+pub fn run_send(
+    tty: &str,
+    text: Option<&str>,
+    action: Option<QuickAction>,
+    enter: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let text = match (text, action) {
+        (Some(text), _) => text,
+        (None, Some(action)) => action.text(),
+        (None, None) => return Err("Nothing to send (use --text or --action)".into()),
+    };
+    let session = find_session(&crate::serve::poll_sessions(), tty)?;
+    send_keys(&session, &text_keys(text, enter))
+}
+
+/// Answer to a permission prompt.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Response {
+    Approve,
+    Deny,
+}
+
+/// Keys answering the prompt `session` is waiting on. Only tool permissions and
+/// plan approvals are answered; questions need a real reply.
+///
+/// Claude Code highlights "Yes" in its permission menu, so Return approves and
+/// Escape denies; Codex takes `y`/`n`.
+pub fn response_keys(session: &SessionInfo, response: Response) -> Result<Vec<Key>, String> {
+    let tty = session.tty.trim_start_matches("/dev/");
+    if session.status != Status::Pending {
+        return Err(format!("Session on {} isn't waiting for approval", tty));
+    }
+    match &session.pending_reason {
+        Some(PendingReason::ToolPermission { .. } | PendingReason::PlanApproval) => {}
+        Some(PendingReason::Question) => {
+            return Err(format!(
+                "Session on {} is asking a question; answer it with `send`",
+                tty
+            ))
+        }
+        None => return Err(format!("Session on {} isn't waiting for approval", tty)),
+    }
+    Ok(match (session.provider, response) {
+        (Provider::Claude, Response::Approve) => vec![Key::Enter],
+        (Provider::Claude, Response::Deny) => vec![Key::Escape],
+        (Provider::Codex, Response::Approve) => vec![Key::Text("y".into())],
+        (Provider::Codex, Response::Deny) => vec![Key::Text("n".into())],
+    })
+}
+
+/// Whether keys for `session` can only reach its own prompt. Remote sessions
+/// are typed into through the newest SSH tab to their host, which may show
+/// another session or a shell, so they never qualify.
+fn check_respond_target(session: &SessionInfo, target: &Target) -> Result<(), String> {
+    let tty = session.tty.trim_start_matches("/dev/");
+    if let Some(remote) = &session.remote {
+        return Err(format!(
+            "Session on {} runs on {}; answer it from its terminal",
+            tty, remote.host
+        ));
+    }
+    if !target.is_exact() {
+        return Err(format!(
+            "Session on {} can only be reached by typing into the front window; \
+             answer it from its terminal or run it inside tmux",
+            tty
+        ));
+    }
+    Ok(())
+}
+
+/// Approve or deny the permission prompt of the session on `tty`. The session
+/// is polled fresh, so a prompt answered in the meantime isn't answered twice.
+/// Only sessions whose terminal is addressed exactly (a tmux pane or an
+/// iTerm2 session) are answered, so Return or `y` never lands elsewhere.
+pub fn run_respond(tty: &str, response: Response) -> Result<(), Box<dyn std::error::Error>> {
+    let session = find_session(&crate::serve::poll_sessions(), tty)?;
+    let keys = response_keys(&session, response)?;
+    let target = target(&session)?;
+    check_respond_target(&session, &target)?;
+    logging::info(
+        "respond",
+        &format!(
+            "{:?} {} on {}",
+            response,
+            session
+                .pending_reason
+                .as_ref()
+                .map(|r| r.label())
+                .unwrap_or_default(),
+            session.tty
+        ),
+    );
+    deliver(&session, &target, &keys)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tmux_pane_for_tty() {
+        let panes = "/dev/ttys004 %0\n/dev/ttys007 %3\n";
+        assert_eq!(
+            tmux_pane_for_tty(panes, "/dev/ttys007"),
+            Some("%3".to_string())
+        );
+        assert_eq!(tmux_pane_for_tty(panes, "/dev/ttys001"), None);
+        assert_eq!(tmux_pane_for_tty("", "/dev/ttys004"), None);
+    }
+
+    #[test]
+    fn test_iterm2_write_script_quotes_text() {
+        let script = iterm2_write_script("/dev/ttys003", &text_keys("say \"hi\"", true));
+        assert!(script.contains("if tty of s is \"/dev/ttys003\" then"));
+        assert!(script.contains("write text \"say \\\"hi\\\"\" newline no\n"));
+        assert!(script.contains("write text \"\" newline yes\n                    return"));
+        assert!(iterm2_write_script("/dev/ttys003", &[Key::Escape]).contains("character id 27"));
+    }
+
+    #[test]
+    fn test_keystroke_script() {
+        assert_eq!(
+            keystroke_script(4321, &text_keys("continue", true)),
+            "tell application \"System Events\"\n    \
+             if unix id of first process whose frontmost is true is not 4321 then \
+             error \"the terminal window is not in front\"\n    \
+             keystroke \"continue\"\n    key code 36\nend tell"
+        );
+        assert!(!keystroke_script(4321, &text_keys("", true)).contains("keystroke"));
+        assert!(keystroke_script(4321, &[Key::Escape]).contains("key code 53"));
+    }
+
+    fn pending(provider: Provider, reason: Option<PendingReason>) -> SessionInfo {
+        SessionInfo {
+            provider,
+            pending_reason: reason,
+            ..SessionInfo::for_test("/dev/ttys003", "/src/api", Status::Pending)
+        }
+    }
+
+    #[test]
+    fn test_response_keys() {
+        let bash = || Some(PendingReason::for_tool("Bash"));
+        assert_eq!(
+            response_keys(&pending(Provider::Claude, bash()), Response::Approve),
+            Ok(vec![Key::Enter])
+        );
+        assert_eq!(
+            response_keys(&pending(Provider::Claude, bash()), Response::Deny),
+            Ok(vec![Key::Escape])
+        );
+        assert_eq!(
+            response_keys(&pending(Provider::Codex, bash()), Response::Approve),
+            Ok(vec![Key::Text("y".into())])
+        );
+        assert!(response_keys(
+            &pending(Provider::Claude, Some(PendingReason::PlanApproval)),
+            Response::Approve
+        )
+        .is_ok());
+
+        // Guarded: questions and sessions that aren't pending
+        assert!(response_keys(
+            &pending(Provider::Claude, Some(PendingReason::Question)),
+            Response::Approve
+        )
+        .unwrap_err()
+        .contains("asking a question"));
+        let mut idle = pending(Provider::Claude, None);
+        idle.status = Status::Idle;
+        assert!(response_keys(&idle, Response::Deny).is_err());
+    }
+
+    #[test]
+    fn test_respond_needs_an_exact_target() {
+        let session = pending(Provider::Claude, None);
+        assert!(check_respond_target(&session, &Target::Tmux("%3".into())).is_ok());
+        assert!(check_respond_target(&session, &Target::ITerm2("/dev/ttys003".into())).is_ok());
+        assert!(
+            check_respond_target(&session, &Target::Alacritty("/dev/ttys003".into()))
+                .unwrap_err()
+                .contains("inside tmux")
+        );
+
+        let mut remote = session.clone();
+        remote.remote = Some(crate::state::Remote {
+            host: "build".into(),
+            local_tty: Some("/dev/ttys010".into()),
+        });
+        assert!(
+            check_respond_target(&remote, &Target::ITerm2("/dev/ttys010".into()))
+                .unwrap_err()
+                .contains("runs on build")
+        );
+    }
+
+    #[test]
+    fn test_find_session_matches_short_and_full_tty() {
+        let sessions = vec![pending(Provider::Claude, None)];
+        assert!(find_session(&sessions, "ttys003").is_ok());
+        assert!(find_session(&sessions, "/dev/ttys003").is_ok());
+        assert_eq!(
+            find_session(&sessions, "ttys009").unwrap_err().to_string(),
+            "No live session on ttys009"
+        );
+    }
+}
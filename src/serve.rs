@@ -1,84 +1,2571 @@
+use crate::bench::{self, Phase};
+use crate::config::{self, ClaudeRoot, Config, ConfigWatcher};
+use crate::container;
+use crate::events::{self, EventBus, SessionEvent};
+use crate::filter;
+use crate::focus;
+use crate::hook;
+use crate::logging;
+use crate::peer::{self, Peer, PeerState};
+use crate::permissions;
 use crate::process;
-use crate::state::{Provider, SessionInfo};
+use crate::protocol::{self, ErrorReport, HealthReport, Request, Response};
+use crate::remote;
+use crate::state::{
+    NestedAgent, PendingReason, ProcessIdentity, Provider, Rule, SessionInfo, SessionState, Status,
+    Terminal, ToolInFlight,
+};
 use crate::terminal;
-use crate::transcript;
-use std::collections::HashMap;
-use std::collections::HashSet;
-use std::path::Path;
+use crate::transcript::{self, StatusDecision, Thresholds, TranscriptSource};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::collections::{HashMap, HashSet};
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+use std::os::unix::io::AsRawFd;
+use std::os::unix::net::UnixListener;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt};
+use tokio::sync::{watch, Notify};
 
-/// Poll all terminal sessions and determine their statuses.
-pub fn poll_sessions() -> Vec<SessionInfo> {
-    let agent_by_tty = process::build_agent_by_tty();
-    let pid_by_tty: HashMap<String, u32> = agent_by_tty
-        .iter()
-        .map(|(tty, proc)| (tty.clone(), proc.pid))
-        .collect();
+/// Latest poll result shared between the poller task and socket clients.
+#[derive(Debug)]
+pub struct DaemonState {
+    /// Identifies this daemon process, so `changes` clients holding a `seq`
+    /// from before a restart get the full list.
+    pub epoch: String,
+    pub sessions: Vec<SessionInfo>,
+    /// Bumped whenever the session list changes.
+    pub seq: u64,
+    /// Session key -> seq at which that session last changed (or appeared).
+    pub changed_at: HashMap<String, u64>,
+    /// Session key -> seq at which that session disappeared, and whether it
+    /// was a peer's (remote) session.
+    pub removed_at: HashMap<String, (u64, bool)>,
+    /// When the poller last finished a poll.
+    pub last_poll_at: Option<SystemTime>,
+    pub last_poll: PollStats,
+    pub backoff: PollBackoff,
+    /// Most recent subprocess failure and when it happened.
+    pub last_error: Option<(SystemTime, String)>,
+}
 
-    let iterm2_ttys = terminal::enumerate_iterm2_ttys();
-    let alacritty_ttys = terminal::enumerate_alacritty_ttys();
-    let merged = terminal::merge_sessions(&iterm2_ttys, &alacritty_ttys, &pid_by_tty);
+/// Cost of the most recent poll.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PollStats {
+    pub duration_ms: u64,
+    pub subprocesses: u64,
+}
 
-    let active_ttys: HashSet<String> = merged
-        .iter()
-        .map(|(tty, _)| tty.trim_start_matches("/dev/").to_string())
-        .collect();
+/// Polls considered when deciding whether to back off or recover.
+const BACKOFF_WINDOW: usize = 5;
+/// Largest multiplier applied to the configured poll interval.
+const MAX_BACKOFF: u32 = 8;
+
+/// Stretches the poll interval while polls routinely overrun their time budget,
+/// so an overloaded machine isn't buried under `lsof`/`osascript` processes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PollBackoff {
+    recent_ms: VecDeque<u64>,
+    /// Polls recorded since the factor last changed
+    polls_at_factor: usize,
+    /// Multiplier applied to the configured interval
+    pub factor: u32,
+}
+
+impl Default for PollBackoff {
+    fn default() -> Self {
+        PollBackoff {
+            recent_ms: VecDeque::with_capacity(BACKOFF_WINDOW),
+            polls_at_factor: 0,
+            factor: 1,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BackoffChange {
+    Slower,
+    Faster,
+}
+
+impl PollBackoff {
+    /// Record a poll. After a full window at the current factor, double it if
+    /// most polls overran `budget_ms`, or halve it if none did.
+    pub fn record(&mut self, duration_ms: u64, budget_ms: u64) -> Option<BackoffChange> {
+        if self.recent_ms.len() == BACKOFF_WINDOW {
+            self.recent_ms.pop_front();
+        }
+        self.recent_ms.push_back(duration_ms);
+        self.polls_at_factor += 1;
+        // Judge each interval on polls made at that interval only
+        if self.polls_at_factor < BACKOFF_WINDOW {
+            return None;
+        }
+
+        let slow = self.recent_ms.iter().filter(|&&ms| ms > budget_ms).count();
+        let change = if slow * 2 > BACKOFF_WINDOW && self.factor < MAX_BACKOFF {
+            self.factor *= 2;
+            BackoffChange::Slower
+        } else if slow == 0 && self.factor > 1 {
+            self.factor /= 2;
+            BackoffChange::Faster
+        } else {
+            return None;
+        };
+        self.polls_at_factor = 0;
+        Some(change)
+    }
+
+    pub fn avg_ms(&self) -> u64 {
+        if self.recent_ms.is_empty() {
+            return 0;
+        }
+        self.recent_ms.iter().sum::<u64>() / self.recent_ms.len() as u64
+    }
+}
+
+/// Daemon settings fixed at startup (reported by `health`).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ServeConfig {
+    /// `--interval` override; otherwise the config file's `poll_interval_secs` applies
+    pub interval_secs: Option<f64>,
+    pub websocket_port: Option<u16>,
+    /// Address serving read-only requests to peer daemons (`--listen-tcp`)
+    pub listen_tcp: Option<String>,
+    /// Address serving read-only requests over TLS to peers that present the
+    /// shared token (`--listen`)
+    pub listen: Option<String>,
+    /// Shared token for `--listen` and for peers with a fingerprint
+    pub auth_token_file: Option<PathBuf>,
+    /// PEM certificate chain and private key for `--listen`
+    pub tls_cert: Option<PathBuf>,
+    pub tls_key: Option<PathBuf>,
+    /// Daemons whose sessions are merged into ours (`--peer`)
+    pub peers: Vec<Peer>,
+    pub socket_path: PathBuf,
+    pub config_path: PathBuf,
+}
+
+impl Default for ServeConfig {
+    fn default() -> Self {
+        ServeConfig {
+            interval_secs: None,
+            websocket_port: None,
+            listen_tcp: None,
+            listen: None,
+            auth_token_file: None,
+            tls_cert: None,
+            tls_key: None,
+            peers: Vec::new(),
+            socket_path: protocol::socket_path(),
+            config_path: config::default_config_path(),
+        }
+    }
+}
+
+/// Answer to `{"cmd":"changes","since":N,"epoch":E}`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChangesResponse {
+    /// [`DaemonState::epoch`]; send it back with the next `since`
+    pub epoch: String,
+    pub seq: u64,
+    /// True when `since` was unknown (0, ahead of `seq`, or from another
+    /// epoch) and `sessions` holds the complete list rather than a delta.
+    pub full: bool,
+    /// Sessions that changed after `since`
+    pub sessions: Vec<SessionInfo>,
+    /// Keys ([`SessionInfo::key`]: the TTY, `host:tty` for remote sessions) of sessions that ended after `since`
+    pub removed: Vec<String>,
+}
+
+impl Default for DaemonState {
+    fn default() -> Self {
+        let started = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos())
+            .unwrap_or(0);
+        DaemonState {
+            epoch: format!("{:x}-{:x}", std::process::id(), started),
+            sessions: Vec::new(),
+            seq: 0,
+            changed_at: HashMap::new(),
+            removed_at: HashMap::new(),
+            last_poll_at: None,
+            last_poll: PollStats::default(),
+            backoff: PollBackoff::default(),
+            last_error: None,
+        }
+    }
+}
+
+impl DaemonState {
+    /// Replace the session list, recording per-TTY change sequence numbers.
+    fn apply(&mut self, sessions: Vec<SessionInfo>) {
+        self.seq += 1;
+        let seq = self.seq;
+
+        for s in &sessions {
+            let unchanged = self.sessions.iter().any(|old| old == s);
+            if !unchanged {
+                self.changed_at.insert(s.key(), seq);
+            }
+            self.removed_at.remove(&s.key());
+        }
+        for old in &self.sessions {
+            if !sessions.iter().any(|s| s.key() == old.key()) {
+                self.changed_at.remove(&old.key());
+                self.removed_at
+                    .insert(old.key(), (seq, old.remote.is_some()));
+            }
+        }
+        self.sessions = sessions;
+    }
+
+    /// Sessions changed and TTYs removed after `since` of `epoch` (clients
+    /// that send none are trusted to be on this one). `local_only` leaves out
+    /// remote sessions, for peers.
+    pub fn changes_since(
+        &self,
+        since: u64,
+        epoch: Option<&str>,
+        local_only: bool,
+    ) -> ChangesResponse {
+        let visible = |s: &&SessionInfo| !local_only || s.remote.is_none();
+        let other_epoch = epoch.is_some_and(|epoch| epoch != self.epoch);
+        if since == 0 || since > self.seq || other_epoch {
+            return ChangesResponse {
+                epoch: self.epoch.clone(),
+                seq: self.seq,
+                full: true,
+                sessions: self.sessions.iter().filter(visible).cloned().collect(),
+                removed: Vec::new(),
+            };
+        }
+        let sessions = self
+            .sessions
+            .iter()
+            .filter(visible)
+            .filter(|s| self.changed_at.get(&s.key()).is_some_and(|&at| at > since))
+            .cloned()
+            .collect();
+        let mut removed: Vec<String> = self
+            .removed_at
+            .iter()
+            .filter(|(_, &(at, remote))| at > since && !(local_only && remote))
+            .map(|(tty, _)| tty.clone())
+            .collect();
+        removed.sort();
+        ChangesResponse {
+            epoch: self.epoch.clone(),
+            seq: self.seq,
+            full: false,
+            sessions,
+            removed,
+        }
+    }
+}
+
+/// Daemon state plus a watch channel carrying its `seq` on every change.
+pub struct Shared {
+    pub state: Mutex<DaemonState>,
+    pub changed: watch::Sender<u64>,
+    pub started_at: Instant,
+    pub config: ServeConfig,
+    /// Settings from the config file, replaced on reload
+    live_config: Mutex<Config>,
+    /// Latest result from each `--peer`, in command-line order
+    pub peers: Mutex<Vec<PeerState>>,
+    /// Notified to request an immediate poll; the poller sleeps on it.
+    wake: Notify,
+    /// Polls, session changes and config reloads, for whoever subscribes
+    pub events: EventBus,
+    /// Serializes hook deliveries, which read-modify-write the state files
+    hook_lock: Mutex<()>,
+}
+
+impl Default for Shared {
+    fn default() -> Self {
+        Shared::new(ServeConfig::default())
+    }
+}
+
+impl Shared {
+    pub fn new(config: ServeConfig) -> Self {
+        Shared {
+            state: Mutex::new(DaemonState::default()),
+            changed: watch::Sender::new(0),
+            started_at: Instant::now(),
+            live_config: Mutex::new(Config::default()),
+            peers: Mutex::new(config.peers.iter().cloned().map(PeerState::new).collect()),
+            wake: Notify::new(),
+            events: EventBus::default(),
+            hook_lock: Mutex::new(()),
+            config,
+        }
+    }
+
+    /// Current (hot-reloadable) config.
+    pub fn config(&self) -> Config {
+        self.live_config.lock().unwrap().clone()
+    }
+
+    /// Effective poll interval: `--interval` if given, else the config file's.
+    pub fn poll_interval(&self) -> Duration {
+        let secs = self
+            .config
+            .interval_secs
+            .unwrap_or_else(|| self.live_config.lock().unwrap().poll_interval_secs);
+        Duration::from_secs_f64(secs)
+    }
 
-    let home = std::env::var("HOME").unwrap_or_default();
+    /// Re-read the config file. Returns whether anything changed; on error the
+    /// previous config stays in effect.
+    pub fn reload_config(&self) -> Result<bool, String> {
+        let new = Config::read(&self.config.config_path).inspect_err(|e| {
+            logging::error("config", &format!("reload failed: {}", e));
+        })?;
+        let mut current = self.live_config.lock().unwrap();
+        if *current == new {
+            return Ok(false);
+        }
+        let colors_changed = current.colors != new.colors;
+        *current = new;
+        drop(current);
+        logging::info(
+            "config",
+            &format!("reloaded {}", self.config.config_path.display()),
+        );
+        self.events
+            .publish(SessionEvent::ConfigReloaded { colors_changed });
+        Ok(true)
+    }
+
+    /// Poll interval after backoff for slow polls.
+    pub fn effective_poll_interval(&self) -> Duration {
+        let factor = self.state.lock().unwrap().backoff.factor;
+        self.poll_interval() * factor
+    }
+
+    /// Record the cost of a poll and any subprocess failure it hit, backing the
+    /// interval off (or recovering) when polls keep overrunning the budget.
+    pub fn record_poll(&self, stats: PollStats, error: Option<String>) {
+        let budget_ms = self.config().poll_budget_ms;
+        let mut state = self.state.lock().unwrap();
+        state.last_poll = stats;
+        match state.backoff.record(stats.duration_ms, budget_ms) {
+            Some(BackoffChange::Slower) => logging::warn(
+                "poll",
+                &format!(
+                    "polls averaging {} ms exceed the {} ms budget; interval now {}x",
+                    state.backoff.avg_ms(),
+                    budget_ms,
+                    state.backoff.factor
+                ),
+            ),
+            Some(BackoffChange::Faster) => logging::info(
+                "poll",
+                &format!(
+                    "polls back under the {} ms budget; interval now {}x",
+                    budget_ms, state.backoff.factor
+                ),
+            ),
+            None => {}
+        }
+        if let Some(msg) = error {
+            state.last_error = Some((SystemTime::now(), msg));
+        }
+    }
+
+    /// Store a new poll result; returns true (and wakes waiters and publishes
+    /// [`SessionEvent::SessionsChanged`]) if it differs.
+    pub fn update(&self, sessions: Vec<SessionInfo>) -> bool {
+        let mut state = self.state.lock().unwrap();
+        state.last_poll_at = Some(SystemTime::now());
+        if state.seq > 0 && state.sessions == sessions {
+            return false;
+        }
+        let changes = events::session_changes(&state.sessions, &sessions);
+        state.apply(sessions);
+        let seq = state.seq;
+        drop(state);
+        self.changed.send_replace(seq);
+        self.events
+            .publish(SessionEvent::SessionsChanged { seq, changes });
+        true
+    }
+
+    /// The session store's side of the event bus: take each poll, and push a
+    /// fresh frame to Stream Deck clients when the colors change even if no
+    /// session did.
+    pub fn handle_event(&self, event: &SessionEvent) {
+        match event {
+            SessionEvent::Polled {
+                sessions,
+                stats,
+                error,
+            } => {
+                self.record_poll(*stats, error.clone());
+                self.update(sessions.clone());
+            }
+            SessionEvent::ConfigReloaded {
+                colors_changed: true,
+            } => {
+                let seq = {
+                    let mut state = self.state.lock().unwrap();
+                    state.seq += 1;
+                    state.seq
+                };
+                self.changed.send_replace(seq);
+            }
+            // The state file changed under the session; show it without
+            // waiting out the poll interval
+            SessionEvent::HookReceived { .. } => self.request_poll(),
+            _ => {}
+        }
+    }
+
+    pub fn snapshot(&self) -> (u64, Vec<SessionInfo>) {
+        let state = self.state.lock().unwrap();
+        (state.seq, state.sessions.clone())
+    }
+
+    /// Wait until `seq` advances past `since`, or `timeout` elapses.
+    pub async fn wait_for_change(&self, since: u64, timeout: Duration) -> (u64, Vec<SessionInfo>) {
+        let mut seq = self.changed.subscribe();
+        let _ = tokio::time::timeout(timeout, seq.wait_for(|seq| *seq > since)).await;
+        self.snapshot()
+    }
+
+    /// Ask the poller to run now instead of waiting out its interval.
+    pub fn request_poll(&self) {
+        self.wake.notify_one();
+    }
+
+    /// Sleep for `interval` or until [`Shared::request_poll`] is called.
+    pub async fn sleep_until_next_poll(&self, interval: Duration) {
+        let _ = tokio::time::timeout(interval, self.wake.notified()).await;
+    }
+
+    /// Sessions from every peer that answered recently, grouped by peer.
+    pub fn peer_sessions(&self) -> Vec<SessionInfo> {
+        let now = SystemTime::now();
+        self.peers
+            .lock()
+            .unwrap()
+            .iter()
+            .flat_map(|p| p.sessions(now).to_vec())
+            .collect()
+    }
+
+    /// Liveness summary returned by the `health` request.
+    pub fn health(&self) -> HealthReport {
+        let unix = |t: SystemTime| {
+            t.duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0)
+        };
+        let state = self.state.lock().unwrap();
+        HealthReport {
+            pid: std::process::id(),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            uptime_secs: self.started_at.elapsed().as_secs(),
+            seq: state.seq,
+            sessions: state.sessions.len(),
+            last_poll_unix: state.last_poll_at.map(unix),
+            last_poll_duration_ms: state.last_poll.duration_ms,
+            last_poll_subprocesses: state.last_poll.subprocesses,
+            avg_poll_duration_ms: state.backoff.avg_ms(),
+            effective_interval_secs: (self.poll_interval() * state.backoff.factor).as_secs_f64(),
+            poll_backoff: state.backoff.factor,
+            last_error: state.last_error.as_ref().map(|(at, msg)| ErrorReport {
+                at_unix: unix(*at),
+                message: msg.clone(),
+            }),
+            peers: self
+                .peers
+                .lock()
+                .unwrap()
+                .iter()
+                .map(|p| p.report())
+                .collect(),
+            permissions: permissions::read(&permissions::default_path()),
+            config: {
+                let mut config = serde_json::to_value(&self.config).unwrap_or_default();
+                if let Ok(serde_json::Value::Object(live)) = serde_json::to_value(self.config()) {
+                    if let Some(obj) = config.as_object_mut() {
+                        obj.extend(live);
+                    }
+                }
+                config
+            },
+        }
+    }
+}
 
-    let mut sessions = Vec::new();
-    for (tty, term) in &merged {
-        let agent = match agent_by_tty.get(tty) {
-            Some(p) => p,
-            None => continue,
+/// The status log's side of the event bus: one line per session that
+/// appeared, changed status or ended, and (at debug level) each hook delivered.
+fn log_status_changes(event: &SessionEvent) {
+    match event {
+        SessionEvent::SessionsChanged { changes, .. } => {
+            for change in changes {
+                logging::info("status", &change.describe());
+            }
+        }
+        SessionEvent::HookReceived { tty, event } => logging::debug(
+            "hook",
+            &format!("{}: {}", tty, event.as_deref().unwrap_or("-")),
+        ),
+        _ => {}
+    }
+}
+
+/// Run the daemon: poll every `interval`, serve the latest sessions over the Unix
+/// socket, and optionally push them to Stream Deck clients over WebSocket.
+pub fn run_serve(config: ServeConfig) -> Result<(), Box<dyn std::error::Error>> {
+    if let Err(e) = logging::init(&logging::default_log_path(), logging::level_from_env()) {
+        eprintln!("Warning: logging disabled: {}", e);
+    }
+    logging::info(
+        "serve",
+        &format!(
+            "starting (pid {}, socket {})",
+            std::process::id(),
+            config.socket_path.display()
+        ),
+    );
+
+    let migrated = crate::paths::migrate_legacy_files();
+    if migrated > 0 {
+        logging::info(
+            "serve",
+            &format!(
+                "moved {} files from ~/.claude to the XDG directories",
+                migrated
+            ),
+        );
+    }
+
+    tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .thread_name("claude-bar-serve")
+        .build()?
+        .block_on(serve(config))
+}
+
+/// The daemon proper: one task per feature (poller, each peer, each listener),
+/// with polls, lsof, AppleScript and file I/O on the blocking pool.
+async fn serve(config: ServeConfig) -> Result<(), Box<dyn std::error::Error>> {
+    let shared = Arc::new(Shared::new(config.clone()));
+    let store = Arc::clone(&shared);
+    shared
+        .events
+        .spawn_consumer("store", move |event| store.handle_event(event));
+    shared.events.spawn_consumer("log", log_status_changes);
+    crate::notifier::spawn(Arc::clone(&shared));
+    if let Err(e) = shared.reload_config() {
+        eprintln!("Warning: using default config: {}", e);
+    }
+
+    spawn_poller(Arc::clone(&shared));
+
+    let needs_token =
+        config.listen.is_some() || config.peers.iter().any(|p| p.fingerprint.is_some());
+    let token: Option<Arc<str>> = match &config.auth_token_file {
+        Some(path) => Some(crate::tls::read_token_file(path)?.into()),
+        None if needs_token => {
+            return Err("--auth-token-file is required with --listen and TLS peers".into())
+        }
+        None => None,
+    };
+
+    for index in 0..config.peers.len() {
+        spawn_peer_poller(Arc::clone(&shared), index, token.clone());
+    }
+
+    if let Some(port) = config.websocket_port {
+        crate::streamdeck::spawn_server(port, Arc::clone(&shared))?;
+    }
+
+    if let Some(addr) = &config.listen_tcp {
+        spawn_tcp_listener(addr, Arc::clone(&shared)).await?;
+    }
+
+    if let (Some(addr), Some(token)) = (&config.listen, &token) {
+        let (Some(cert), Some(key)) = (&config.tls_cert, &config.tls_key) else {
+            return Err("--listen needs --tls-cert and --tls-key".into());
         };
-        let pid = agent.pid;
-        let provider = agent.provider;
+        let tls = crate::tls::server_config(cert, key)?;
+        let listener = std::net::TcpListener::bind(addr)
+            .map_err(|e| format!("cannot listen on {}: {}", addr, e))?;
+        logging::info(
+            "serve",
+            &format!(
+                "listening for authenticated peers on {} (TLS, certificate SHA-256 {})",
+                addr,
+                crate::tls::cert_fingerprint(cert)?
+            ),
+        );
+        spawn_tls_listener(listener, tls, Arc::clone(token), Arc::clone(&shared))?;
+    }
+
+    serve_unix_socket(&config.socket_path, shared).await
+}
+
+/// Poll local sessions every interval (or on [`Shared::request_poll`]) and
+/// publish each result. The poll itself blocks on subprocesses, so it runs on
+/// the blocking pool; a poll that panics is logged and the next one goes ahead.
+fn spawn_poller(shared: Arc<Shared>) {
+    tokio::spawn(async move {
+        let watcher = Arc::new(Mutex::new(ConfigWatcher::new(&shared.config.config_path)));
+        loop {
+            let (poller, watcher) = (Arc::clone(&shared), Arc::clone(&watcher));
+            let polled = tokio::task::spawn_blocking(move || {
+                poll_once(&poller, &mut watcher.lock().unwrap())
+            })
+            .await;
+            if let Err(e) = polled {
+                logging::error("poll", &format!("poll failed: {}", e));
+            }
+            shared
+                .sleep_until_next_poll(shared.effective_poll_interval())
+                .await;
+        }
+    });
+}
+
+/// One poll: pick up config edits, collect sessions and publish them.
+fn poll_once(shared: &Shared, watcher: &mut ConfigWatcher) {
+    if watcher.changed() {
+        let _ = shared.reload_config();
+    }
+    let started = Instant::now();
+    let (mut sessions, tally) = process::tallied(|| poll_sessions_with(&shared.config()));
+    let stats = PollStats {
+        duration_ms: started.elapsed().as_millis() as u64,
+        subprocesses: tally.subprocesses,
+    };
+    logging::debug(
+        "poll",
+        &format!(
+            "{} sessions in {} ms, {} subprocesses",
+            sessions.len(),
+            stats.duration_ms,
+            stats.subprocesses
+        ),
+    );
+    sessions.extend(shared.peer_sessions());
+    shared.events.publish(SessionEvent::Polled {
+        sessions,
+        stats,
+        error: tally.last_error,
+    });
+}
+
+/// Poll one peer daemon in its own task, backing off while it fails, so an
+/// unreachable machine never holds up local polls or other peers.
+fn spawn_peer_poller(shared: Arc<Shared>, index: usize, token: Option<Arc<str>>) {
+    tokio::spawn(async move {
+        loop {
+            let peer = shared.peers.lock().unwrap()[index].peer.clone();
+            let token = token.clone();
+            let result =
+                tokio::task::spawn_blocking(move || peer::fetch_sessions(&peer, token.as_deref()))
+                    .await
+                    .unwrap_or_else(|e| Err(e.to_string()));
+            let delay = {
+                let mut peers = shared.peers.lock().unwrap();
+                let state = &mut peers[index];
+                match result {
+                    Ok(sessions) => state.record_success(sessions, SystemTime::now()),
+                    Err(e) => state.record_failure(e),
+                }
+                state.next_delay(shared.poll_interval())
+            };
+            tokio::time::sleep(delay).await;
+        }
+    });
+}
 
-        let cwd = process::get_pid_cwd(pid).unwrap_or_default();
-        let transcript_path = match provider {
-            Provider::Claude => {
-                let project_hash = transcript::project_hash(&cwd);
-                let tty_short = tty.trim_start_matches("/dev/");
-                let project_dir = Path::new(&home)
-                    .join(".claude/projects")
-                    .join(&project_hash);
-                let state_dir = transcript::state_dir_for_cwd(&cwd);
-                transcript::resolve_transcript(tty_short, &state_dir, &project_dir, &active_ttys)
+/// Most `--listen-tcp` connections served at once; further ones are closed.
+const MAX_TCP_CLIENTS: usize = 16;
+
+/// Serve read-only protocol requests to peer daemons over TCP. Only this
+/// machine's own sessions are exported, so chained peers can't loop.
+///
+/// The listener is unauthenticated plaintext, so it must be bound to loopback
+/// (e.g. behind an SSH tunnel); other machines use `--listen`.
+async fn spawn_tcp_listener(
+    addr: &str,
+    shared: Arc<Shared>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let listener = tokio::net::TcpListener::bind(addr)
+        .await
+        .map_err(|e| format!("cannot listen on {}: {}", addr, e))?;
+    // Plain TCP carries sessions (and would carry a token) in the clear
+    if !listener.local_addr()?.ip().is_loopback() {
+        return Err(format!(
+            "--listen-tcp {} is not a loopback address; serve other machines with --listen (TLS)",
+            addr
+        )
+        .into());
+    }
+    logging::info("serve", &format!("listening for peers on {}", addr));
+    let slots = Arc::new(tokio::sync::Semaphore::new(MAX_TCP_CLIENTS));
+    tokio::spawn(async move {
+        loop {
+            let Ok((stream, peer_addr)) = listener.accept().await else {
+                continue;
+            };
+            let Ok(slot) = Arc::clone(&slots).try_acquire_owned() else {
+                logging::warn(
+                    "serve",
+                    &format!("refused peer {}: too many connections", peer_addr),
+                );
+                continue;
+            };
+            let shared = Arc::clone(&shared);
+            tokio::spawn(async move {
+                handle_client(stream, shared, Access::ReadOnly).await;
+                drop(slot);
+            });
+        }
+    });
+    Ok(())
+}
+
+/// How long a `--listen` client has to finish the TLS handshake and authenticate.
+const AUTH_TIMEOUT: Duration = Duration::from_secs(5);
+/// Most `--listen` connections still authenticating at once; each holds a
+/// blocking-pool thread, so further ones are closed until a slot frees up.
+const MAX_UNAUTHENTICATED: usize = 8;
+
+/// Serve read-only protocol requests over TLS to clients that send the shared
+/// token first. Like `--listen-tcp`, only this machine's own sessions are exported.
+/// Connections are accepted here and served on the blocking pool by rustls.
+fn spawn_tls_listener(
+    listener: std::net::TcpListener,
+    tls: Arc<rustls::ServerConfig>,
+    token: Arc<str>,
+    shared: Arc<Shared>,
+) -> std::io::Result<()> {
+    listener.set_nonblocking(true)?;
+    let listener = tokio::net::TcpListener::from_std(listener)?;
+    let slots = Arc::new(tokio::sync::Semaphore::new(MAX_UNAUTHENTICATED));
+    tokio::spawn(async move {
+        loop {
+            let Ok((stream, peer_addr)) = listener.accept().await else {
+                continue;
+            };
+            let Ok(slot) = Arc::clone(&slots).try_acquire_owned() else {
+                logging::warn(
+                    "serve",
+                    &format!("refused peer {}: too many connections", peer_addr),
+                );
+                continue;
+            };
+            let Ok(stream) = stream.into_std() else {
+                continue;
+            };
+            let Ok(watched) = stream.try_clone() else {
+                continue;
+            };
+            if stream.set_nonblocking(false).is_err() {
+                continue;
             }
-            Provider::Codex => transcript::find_latest_codex_session_for_cwd(&cwd),
+            // Hold the slot until the client authenticates or goes away, and
+            // cut it off if that takes longer than AUTH_TIMEOUT in total
+            let (authenticated, done) = tokio::sync::oneshot::channel();
+            tokio::spawn(async move {
+                let _slot = slot;
+                if tokio::time::timeout(AUTH_TIMEOUT, done).await.is_err() {
+                    let _ = watched.shutdown(std::net::Shutdown::Both);
+                }
+            });
+            let (tls, token, shared) = (Arc::clone(&tls), Arc::clone(&token), Arc::clone(&shared));
+            tokio::task::spawn_blocking(move || {
+                handle_tls_client(stream, tls, &token, &shared, authenticated)
+            });
+        }
+    });
+    Ok(())
+}
+
+/// Serve one `--listen` client, signalling `on_auth` once it has authenticated.
+fn handle_tls_client(
+    tcp: TcpStream,
+    tls: Arc<rustls::ServerConfig>,
+    token: &str,
+    shared: &Shared,
+    on_auth: tokio::sync::oneshot::Sender<()>,
+) {
+    let peer_addr = tcp.peer_addr().map(|a| a.to_string()).unwrap_or_default();
+    if tcp.set_read_timeout(Some(AUTH_TIMEOUT)).is_err()
+        || tcp.set_write_timeout(Some(AUTH_TIMEOUT)).is_err()
+    {
+        return;
+    }
+    let Ok(conn) = rustls::ServerConnection::new(tls) else {
+        return;
+    };
+    let mut stream = rustls::StreamOwned::new(conn, tcp);
+
+    let authenticated = match read_line(&mut stream)
+        .as_deref()
+        .map(protocol::parse_request)
+    {
+        Some(Ok(Request::Auth { token: given })) => crate::tls::tokens_match(&given, token),
+        _ => false,
+    };
+    let reply = if authenticated {
+        Response::ok(serde_json::Value::Null)
+    } else {
+        logging::warn(
+            "serve",
+            &format!("refused unauthenticated peer {}", peer_addr),
+        );
+        Response::err("authentication failed")
+    };
+    let json = serde_json::to_string(&reply).unwrap_or_default();
+    if writeln!(stream, "{}", json)
+        .and_then(|_| stream.flush())
+        .is_err()
+        || !authenticated
+    {
+        return;
+    }
+
+    let _ = on_auth.send(());
+    let _ = stream.sock.set_read_timeout(None);
+    let _ = stream.sock.set_write_timeout(None);
+    while let Some(line) = read_line(&mut stream) {
+        let reply = handle_request(line.trim(), shared, Access::ReadOnly);
+        let json = serde_json::to_string(&reply).unwrap_or_default();
+        if writeln!(stream, "{}", json)
+            .and_then(|_| stream.flush())
+            .is_err()
+        {
+            return;
+        }
+    }
+}
+
+/// Read one `\n`-terminated line of at most [`MAX_REQUEST_LINE`] bytes; `None`
+/// on EOF, error, timeout or an overlong line. Reads a byte at a time so
+/// nothing is buffered past the line (rustls buffers the decrypted records
+/// itself).
+fn read_line<R: Read>(reader: &mut R) -> Option<String> {
+    let mut line = Vec::new();
+    let mut byte = [0u8; 1];
+    loop {
+        if line.len() > MAX_REQUEST_LINE {
+            return None;
+        }
+        match reader.read(&mut byte) {
+            Ok(1) if byte[0] == b'\n' => break,
+            Ok(1) => line.push(byte[0]),
+            _ => return None,
+        }
+    }
+    String::from_utf8(line).ok()
+}
+
+/// Serve socket clients speaking the line protocol in [`crate::protocol`].
+/// A client that sends nothing gets the bare session array (legacy clients).
+/// Connections from other users are refused.
+async fn serve_unix_socket(
+    path: &Path,
+    shared: Arc<Shared>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let listener = bind_private_socket(path)?;
+    listener.set_nonblocking(true)?;
+    let listener = tokio::net::UnixListener::from_std(listener)?;
+    logging::info("serve", &format!("listening on {}", path.display()));
+
+    let uid = unsafe { libc::getuid() };
+    loop {
+        let stream = match listener.accept().await {
+            Ok((s, _)) => s,
+            Err(_) => continue,
         };
+        match peer_uid(&stream) {
+            Ok(peer) if peer == uid => {}
+            Ok(peer) => {
+                logging::warn("serve", &format!("refused socket client with uid {}", peer));
+                continue;
+            }
+            Err(e) => {
+                logging::warn("serve", &format!("refused socket client: {}", e));
+                continue;
+            }
+        }
+        tokio::spawn(handle_client(stream, Arc::clone(&shared), Access::Full));
+    }
+}
+
+/// Bind a Unix socket at `path` that only this user can connect to (it can
+/// focus windows and type into terminals, and lists project paths).
+///
+/// The socket is bound in a fresh 0700 directory next to `path`, set to 0600
+/// and renamed into place, so it is never reachable with looser permissions.
+/// A parent directory we create, or our own state dir, is made 0700.
+fn bind_private_socket(path: &Path) -> std::io::Result<UnixListener> {
+    let parent = path
+        .parent()
+        .filter(|p| !p.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    if !parent.exists() {
+        std::fs::DirBuilder::new()
+            .recursive(true)
+            .mode(0o700)
+            .create(parent)?;
+    } else if parent == crate::paths::state_dir() {
+        std::fs::set_permissions(parent, std::fs::Permissions::from_mode(0o700))?;
+    }
+    if path.exists() {
+        std::fs::remove_file(path)?;
+    }
+
+    let staging = parent.join(format!(".claude-bar-{}.tmp", std::process::id()));
+    let _ = std::fs::remove_dir_all(&staging);
+    std::fs::DirBuilder::new().mode(0o700).create(&staging)?;
+    let staged = staging.join("s");
+    let bound = UnixListener::bind(&staged).and_then(|listener| {
+        std::fs::set_permissions(&staged, std::fs::Permissions::from_mode(0o600))?;
+        std::fs::rename(&staged, path)?;
+        Ok(listener)
+    });
+    let _ = std::fs::remove_dir_all(&staging);
+    bound
+}
+
+/// UID of the process on the other end of `stream` (`SO_PEERCRED`).
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn peer_uid(stream: &impl AsRawFd) -> std::io::Result<u32> {
+    let mut cred = libc::ucred {
+        pid: 0,
+        uid: 0,
+        gid: 0,
+    };
+    let mut len = std::mem::size_of::<libc::ucred>() as libc::socklen_t;
+    let rc = unsafe {
+        libc::getsockopt(
+            stream.as_raw_fd(),
+            libc::SOL_SOCKET,
+            libc::SO_PEERCRED,
+            &mut cred as *mut libc::ucred as *mut libc::c_void,
+            &mut len,
+        )
+    };
+    if rc != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(cred.uid)
+}
+
+/// UID of the process on the other end of `stream` (`LOCAL_PEERCRED`, via
+/// `getpeereid`).
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+fn peer_uid(stream: &impl AsRawFd) -> std::io::Result<u32> {
+    let mut uid: libc::uid_t = 0;
+    let mut gid: libc::gid_t = 0;
+    if unsafe { libc::getpeereid(stream.as_raw_fd(), &mut uid, &mut gid) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(uid)
+}
+
+/// What a connected client may do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Access {
+    /// Unix socket: owned by this user
+    Full,
+    /// TCP: state queries only, limited to local sessions
+    ReadOnly,
+}
 
-        let transcript_opt = if transcript_path.is_empty() {
-            None
+/// How long to wait for a first request line before treating a Unix socket
+/// client as legacy.
+const REQUEST_TIMEOUT: Duration = Duration::from_millis(100);
+/// Longest request line a client may send; longer ones close the connection.
+const MAX_REQUEST_LINE: usize = 64 * 1024;
+
+/// Answer requests from one Unix socket or TCP client until it hangs up.
+/// Requests may focus windows or write files, so each is handled on the
+/// blocking pool.
+async fn handle_client<S>(stream: S, shared: Arc<Shared>, access: Access)
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let (reader, mut writer) = tokio::io::split(stream);
+    let mut reader = tokio::io::BufReader::new(reader);
+
+    // Legacy clients predate TCP, so only Unix socket clients get the short
+    // first-request window; a peer's request may take longer to arrive
+    let mut first = access == Access::Full;
+    loop {
+        let read = if first {
+            tokio::time::timeout(REQUEST_TIMEOUT, next_line(&mut reader))
+                .await
+                .unwrap_or(Ok(None))
         } else {
-            Some(transcript_path)
+            next_line(&mut reader).await
         };
+        let read = match read {
+            Ok(read) => read,
+            Err(e) => {
+                let json = serde_json::to_string(&Response::err(e.to_string())).unwrap_or_default();
+                let _ = writer.write_all(format!("{}\n", json).as_bytes()).await;
+                return;
+            }
+        };
+        let line = read.as_deref().map(str::trim).unwrap_or_default();
+        if line.is_empty() {
+            // Legacy client: no request within the timeout (or immediate EOF)
+            if first {
+                if let Ok(json) = serde_json::to_string(&visible_sessions(&shared, access)) {
+                    let _ = writer.write_all(format!("{}\n", json).as_bytes()).await;
+                }
+            }
+            if read.is_none() {
+                return;
+            }
+            continue;
+        }
+        // Protocol client: keep the connection open for further requests
+        first = false;
+        let (line, request_shared) = (line.to_string(), Arc::clone(&shared));
+        let reply =
+            tokio::task::spawn_blocking(move || handle_request(&line, &request_shared, access))
+                .await
+                .unwrap_or_else(|e| Response::err(e.to_string()));
+        let json = serde_json::to_string(&reply).unwrap_or_default();
+        if writer
+            .write_all(format!("{}\n", json).as_bytes())
+            .await
+            .is_err()
+        {
+            return;
+        }
+    }
+}
+
+/// Read one request line of at most [`MAX_REQUEST_LINE`] bytes, without the
+/// newline; `None` at EOF.
+async fn next_line<R>(reader: &mut R) -> std::io::Result<Option<String>>
+where
+    R: tokio::io::AsyncBufRead + Unpin,
+{
+    use tokio::io::AsyncReadExt;
+    let mut line = Vec::new();
+    let limit = MAX_REQUEST_LINE as u64 + 1;
+    if reader.take(limit).read_until(b'\n', &mut line).await? == 0 {
+        return Ok(None);
+    }
+    if line.last() == Some(&b'\n') {
+        line.pop();
+    } else if line.len() > MAX_REQUEST_LINE {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            "request line too long",
+        ));
+    }
+    String::from_utf8(line)
+        .map(Some)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+}
+
+/// Sessions a client may see: peers only get this machine's own.
+fn visible_sessions(shared: &Shared, access: Access) -> Vec<SessionInfo> {
+    let (_, sessions) = shared.snapshot();
+    match access {
+        Access::Full => sessions,
+        Access::ReadOnly => sessions
+            .into_iter()
+            .filter(|s| s.remote.is_none())
+            .collect(),
+    }
+}
+
+/// Answer one protocol request line.
+fn handle_request(line: &str, shared: &Shared, access: Access) -> Response {
+    let request = match protocol::parse_request(line) {
+        Ok(r) => r,
+        Err(e) => return Response::err(e),
+    };
+    if access == Access::ReadOnly && !request.is_read_only() {
+        return Response::err("only sessions, changes and health are served over TCP");
+    }
+    let to_value = |v: Result<serde_json::Value, serde_json::Error>| match v {
+        Ok(v) => Response::ok(v),
+        Err(e) => Response::err(e.to_string()),
+    };
+
+    match request {
+        Request::Sessions { filter, fields } => {
+            let sessions = filter.apply(visible_sessions(shared, access));
+            if fields.is_empty() {
+                to_value(serde_json::to_value(sessions))
+            } else {
+                match filter::select_fields(&sessions, &fields) {
+                    Ok(picked) => Response::ok(serde_json::Value::Array(picked)),
+                    Err(e) => Response::err(e),
+                }
+            }
+        }
+        Request::Changes { since, epoch } => {
+            let changes = shared.state.lock().unwrap().changes_since(
+                since,
+                epoch.as_deref(),
+                access == Access::ReadOnly,
+            );
+            to_value(serde_json::to_value(changes))
+        }
+        Request::Focus { tty } => {
+            let (_, sessions) = shared.snapshot();
+            let target = focus::find_target(&sessions, &tty);
+            let tty = if tty.starts_with("/dev/") {
+                tty
+            } else {
+                format!("/dev/{}", tty)
+            };
+            match target {
+                Some(session) => match focus::focus_session(session) {
+                    Ok(()) => Response::ok(serde_json::json!({"focused": tty})),
+                    Err(e) => Response::err(e.to_string()),
+                },
+                None => Response::err(format!("no live session on {}", tty)),
+            }
+        }
+        Request::Health => to_value(serde_json::to_value(shared.health())),
+        Request::Reload => match shared.reload_config() {
+            Ok(changed) => {
+                shared.request_poll();
+                Response::ok(serde_json::json!({"reloaded": true, "config_changed": changed}))
+            }
+            Err(e) => Response::err(e),
+        },
+        // Only meaningful as the first request on a `--listen` connection
+        Request::Auth { .. } => Response::ok(serde_json::Value::Null),
+        Request::Hook(delivery) => {
+            let applied = {
+                let _guard = shared.hook_lock.lock().unwrap();
+                hook::apply_hook(&delivery)
+            };
+            match applied {
+                Ok(outcome) => {
+                    shared.events.publish(SessionEvent::HookReceived {
+                        tty: format!("/dev/{}", delivery.tty),
+                        event: delivery.input.hook_event_name.clone(),
+                    });
+                    Response::ok(serde_json::Value::String(outcome))
+                }
+                Err(e) => Response::err(e.to_string()),
+            }
+        }
+    }
+}
+
+/// Poll all terminal sessions using settings from the config file.
+pub fn poll_sessions() -> Vec<SessionInfo> {
+    poll_sessions_with(&Config::load())
+}
+
+/// Poll all terminal sessions and determine their statuses.
+pub fn poll_sessions_with(config: &Config) -> Vec<SessionInfo> {
+    let agents_by_tty = bench::timed(Phase::ProcessDiscovery, || {
+        process::build_agents_by_tty(&config.processes)
+    });
+    let pid_by_tty: HashMap<String, u32> = agents_by_tty
+        .iter()
+        .map(|(tty, group)| (tty.clone(), group.primary.pid))
+        .collect();
+
+    let ssh_tabs = if config.remote_hosts.is_empty() {
+        Vec::new()
+    } else {
+        bench::timed(Phase::TerminalEnumeration, remote::find_ssh_tabs)
+    };
+    // New SSH tabs must refresh the terminal enumeration like new agents do
+    let watched_pids: Vec<u32> = agents_by_tty
+        .values()
+        .flat_map(|group| std::iter::once(&group.primary).chain(&group.nested))
+        .map(|agent| agent.pid)
+        .chain(ssh_tabs.iter().map(|t| t.pid))
+        .collect();
+    let (terminal_ttys, merged) = bench::timed(Phase::TerminalEnumeration, || {
+        let ttys = terminal::enumerate_terminal_ttys(&watched_pids, &config.terminals);
+        let mut merged = terminal::merge_sessions(&ttys, &pid_by_tty);
+        if merged.iter().any(|(_, t)| *t == Terminal::Unknown) {
+            let pane_clients = terminal::tmux_pane_clients();
+            terminal::resolve_tmux_terminals(&mut merged, &pane_clients, &ttys);
+        }
+        terminal::resolve_ide_terminals(&mut merged, &agents_by_tty);
+        (ttys, merged)
+    });
+
+    let active_ttys: HashMap<String, ProcessIdentity> = merged
+        .iter()
+        .filter_map(|(tty, _)| {
+            let group = agents_by_tty.get(tty)?;
+            Some((
+                tty.trim_start_matches("/dev/").to_string(),
+                group.primary.identity(),
+            ))
+        })
+        .collect();
+
+    let roots = config.claude_roots();
+    let thresholds = &config.thresholds;
+
+    let agents: Vec<_> = merged
+        .iter()
+        .filter_map(|(tty, term)| agents_by_tty.get(tty).map(|group| (tty, *term, group)))
+        .collect();
+    let terminal_of = |tty: &str| terminal_ttys.terminal_for(tty);
+    thread::scope(|scope| {
+        // SSH round trips overlap with local collection instead of adding to it
+        let remote = scope.spawn(|| {
+            process::tallied(|| {
+                parallel_map(&config.remote_hosts, POLL_WORKERS, |host| {
+                    bench::timed(Phase::RemoteHosts, || {
+                        remote::poll_host(host, &ssh_tabs, terminal_of)
+                    })
+                })
+            })
+        });
+        // lsof and transcript reads dominate a poll; run them concurrently per session
+        let mut local = parallel_map(&agents, POLL_WORKERS, |(tty, term, group)| {
+            collect_session(tty, *term, group, &roots, &active_ttys, thresholds)
+        });
+        release_duplicate_transcripts(&mut local, thresholds);
+        for (session, _) in &mut local {
+            if session.terminal == Terminal::ITerm2 {
+                session.terminal_id = terminal_ttys.tab_id(&session.tty).map(str::to_string);
+            }
+        }
+        let mut sessions: Vec<SessionInfo> = local.into_iter().map(|(s, _)| s).collect();
+        let mut claimed: HashSet<String> = sessions
+            .iter()
+            .filter_map(|s| s.transcript.clone())
+            .collect();
+        for root in &roots {
+            let projects_dir = root.projects_dir();
+            let mut found = Vec::new();
+            if config.containers.enabled {
+                let in_containers = bench::timed(Phase::TranscriptResolution, || {
+                    container::discover(&projects_dir, &claimed, &config.containers, thresholds)
+                });
+                claimed.extend(in_containers.iter().filter_map(|s| s.transcript.clone()));
+                found.extend(in_containers);
+            }
+            if config.discovery.transcript_first {
+                found.extend(bench::timed(Phase::TranscriptResolution, || {
+                    detached_sessions(
+                        &projects_dir,
+                        &claimed,
+                        config.discovery.max_age_secs,
+                        thresholds,
+                    )
+                }));
+            }
+            for session in &mut found {
+                session.profile = root.profile.clone();
+            }
+            sessions.extend(found);
+        }
+        let (remote, tally) = remote.join().unwrap();
+        process::absorb(tally);
+        sessions.extend(remote.into_iter().flatten());
+        sessions
+    })
+}
+
+/// Upper bound on threads collecting session details during one poll.
+const POLL_WORKERS: usize = 8;
 
-        let status = transcript::determine_status_for(provider, transcript_opt.as_deref());
+/// Map `f` over `items` on up to `workers` threads, keeping the input order.
+/// Subprocesses the workers run count toward the calling thread's
+/// [`process::tallied`].
+fn parallel_map<T, R, F>(items: &[T], workers: usize, f: F) -> Vec<R>
+where
+    T: Sync,
+    R: Send,
+    F: Fn(&T) -> R + Sync,
+{
+    if items.len() <= 1 || workers <= 1 {
+        return items.iter().map(f).collect();
+    }
+    let next = AtomicUsize::new(0);
+    let mut results: Vec<(usize, R)> = thread::scope(|scope| {
+        let handles: Vec<_> = (0..workers.min(items.len()))
+            .map(|_| {
+                scope.spawn(|| {
+                    process::tallied(|| {
+                        let mut out = Vec::new();
+                        loop {
+                            let i = next.fetch_add(1, Ordering::Relaxed);
+                            match items.get(i) {
+                                Some(item) => out.push((i, f(item))),
+                                None => return out,
+                            }
+                        }
+                    })
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .flat_map(|h| {
+                let (out, tally) = h.join().unwrap();
+                process::absorb(tally);
+                out
+            })
+            .collect()
+    });
+    results.sort_by_key(|(i, _)| *i);
+    results.into_iter().map(|(_, r)| r).collect()
+}
+
+/// Resolve cwd, transcript and status for the primary agent process on a TTY,
+/// along with how the transcript was found.
+fn collect_session(
+    tty: &str,
+    terminal: Terminal,
+    agents: &process::TtyAgents,
+    roots: &[ClaudeRoot],
+    active_ttys: &HashMap<String, ProcessIdentity>,
+    thresholds: &Thresholds,
+) -> (SessionInfo, Option<TranscriptSource>) {
+    let pid = agents.primary.pid;
+    let provider = agents.primary.provider;
+
+    let tty_short = tty.trim_start_matches("/dev/");
+    // A hook state written for this very process already knows the CWD, how
+    // many subagents it has running and, with tool hooks, its tool calls
+    let recorded = bench::timed(Phase::TranscriptResolution, || match provider {
+        Provider::Claude => active_ttys
+            .get(tty_short)
+            .and_then(|live| transcript::find_state_for_tty(tty_short, live)),
+        Provider::Codex => None,
+    });
+    let hooks = recorded.as_ref().map(HookCounts::of).unwrap_or_default();
+    let recorded_cwd = recorded
+        .filter(|state| state.pid.is_some() && !state.cwd.is_empty())
+        .map(|state| state.cwd);
+    let (cwd, env) = bench::timed(Phase::ProcessDiscovery, || {
+        let cwd = recorded_cwd
+            .or_else(|| process::get_pid_cwd(pid))
+            .unwrap_or_default();
+        (cwd, process::agent_env(agents.primary.identity()))
+    });
+    let (resolved, profile) = bench::timed(Phase::TranscriptResolution, || match provider {
+        Provider::Claude => {
+            resolve_claude_transcript(tty_short, &cwd, env.as_ref(), roots, active_ttys)
+        }
+        Provider::Codex => (
+            Some(transcript::find_latest_codex_session_for_cwd(&cwd))
+                .filter(|path| !path.is_empty())
+                .map(|path| (path, TranscriptSource::Newest)),
+            None,
+        ),
+    });
+    let (transcript_opt, source) = match resolved {
+        Some((path, source)) => (Some(path), Some(source)),
+        None => (None, None),
+    };
 
-        sessions.push(SessionInfo {
-            tty: tty.clone(),
-            pid,
-            cwd,
+    let cpu_percent = bench::timed(Phase::ProcessDiscovery, || process::cpu_percent(pid));
+    let details = bench::timed(Phase::TranscriptParsing, || {
+        TranscriptDetails::read(
             provider,
-            terminal: *term,
-            transcript: transcript_opt,
+            transcript_opt.as_deref(),
+            cpu_percent,
+            agents.primary.started_at,
+            &hooks,
+            thresholds,
+        )
+    });
+
+    let session = SessionInfo {
+        tty: tty.to_string(),
+        pid,
+        cwd,
+        provider,
+        terminal,
+        transcript: transcript_opt,
+        status: details.status,
+        pending_reason: details.pending_tool.as_deref().map(PendingReason::for_tool),
+        pending_tool: details.pending_tool,
+        queued: details.queued,
+        turns: details.stats.turns,
+        started_at: details.stats.started_at,
+        title: details.stats.title().map(str::to_string),
+        remote: None,
+        nested: agents
+            .nested
+            .iter()
+            .map(|a| NestedAgent {
+                pid: a.pid,
+                provider: a.provider,
+            })
+            .collect(),
+        container: None,
+        detached: false,
+        rule: Some(details.decision.rule),
+        cpu_percent,
+        profile,
+        env: env.unwrap_or_default(),
+        subagents: details.decision.subagents,
+        terminal_id: None,
+    };
+    (session, source)
+}
+
+/// A Claude session's transcript and profile, looked up in the config
+/// directory its process `env` names in `CLAUDE_CONFIG_DIR` (`~/.claude` when
+/// unset). When the environment can't be read every root is tried: a transcript
+/// recorded by the hook wins, else the newest guess.
+fn resolve_claude_transcript(
+    tty_short: &str,
+    cwd: &str,
+    env: Option<&process::AgentEnv>,
+    roots: &[ClaudeRoot],
+    active_ttys: &HashMap<String, ProcessIdentity>,
+) -> (Option<(String, TranscriptSource)>, Option<String>) {
+    let candidates = match env {
+        Some(env) => {
+            let config_dir = env
+                .get(config::CLAUDE_CONFIG_DIR_ENV)
+                .filter(|dir| !dir.is_empty())
+                .map(PathBuf::from)
+                .unwrap_or_else(|| crate::paths::home_dir().join(".claude"));
+            vec![ClaudeRoot::for_config_dir(roots, &config_dir)]
+        }
+        None => roots.to_vec(),
+    };
+    let project_hash = transcript::project_hash(cwd);
+    let state_dir = transcript::session_state_dir(cwd, tty_short);
+    let mut best: Option<(String, TranscriptSource)> = None;
+    for root in &candidates {
+        let project_dir = root.projects_dir().join(&project_hash);
+        let Some(found) = transcript::resolve_transcript_with_source(
+            tty_short,
+            &state_dir,
+            &project_dir,
+            active_ttys,
+        ) else {
+            continue;
+        };
+        if found.1 == TranscriptSource::Hook {
+            best = Some(found);
+            break;
+        }
+        let age = |path: &str| transcript::get_mtime_age(path).unwrap_or(f64::MAX);
+        if best
+            .as_ref()
+            .is_none_or(|(path, _)| age(&found.0) < age(path))
+        {
+            best = Some(found);
+        }
+    }
+    // The transcript's location is the surest sign; without one, the environment
+    let profile = match (&best, candidates.as_slice()) {
+        (Some((path, _)), _) => profile_of(roots, path),
+        (None, [root]) => root.profile.clone(),
+        (None, _) => None,
+    };
+    (best, profile)
+}
+
+/// The profile of the config directory `transcript` is in, if any.
+fn profile_of(roots: &[ClaudeRoot], transcript: &str) -> Option<String> {
+    roots
+        .iter()
+        .find(|root| root.contains(transcript))
+        .and_then(|root| root.profile.clone())
+}
+
+/// What the session's hooks recorded in its state file.
+#[derive(Default)]
+struct HookCounts {
+    subagents: u32,
+    /// Tool calls in flight, when tool hooks run for the session
+    tools_in_flight: Option<Vec<ToolInFlight>>,
+}
+
+impl HookCounts {
+    fn of(state: &SessionState) -> Self {
+        HookCounts {
+            subagents: state.subagents,
+            tools_in_flight: state.tool_hooks.then(|| state.tools_in_flight.clone()),
+        }
+    }
+}
+
+/// What a transcript says about its session.
+struct TranscriptDetails {
+    status: Status,
+    pending_tool: Option<String>,
+    queued: u32,
+    stats: transcript::TranscriptStats,
+    decision: StatusDecision,
+}
+
+impl TranscriptDetails {
+    fn read(
+        provider: Provider,
+        transcript: Option<&str>,
+        cpu_percent: Option<f64>,
+        process_started: Option<u64>,
+        hooks: &HookCounts,
+        thresholds: &Thresholds,
+    ) -> Self {
+        let queued = match (provider, transcript) {
+            (Provider::Claude, Some(path)) => transcript::queued_messages_for(path),
+            _ => 0,
+        };
+        let stats = match (provider, transcript) {
+            (Provider::Claude, Some(path)) => transcript::transcript_stats(path),
+            _ => Default::default(),
+        };
+        let decision =
+            transcript::explain_status_for(provider, transcript, cpu_percent, thresholds)
+                .with_tool_hooks(hooks.tools_in_flight.as_deref(), thresholds)
+                .with_queued(queued)
+                .with_subagents(hooks.subagents)
+                .with_process_age(process_started.map(process::secs_since), thresholds);
+        let status = decision.status;
+        let pending_tool = match (status, transcript) {
+            (Status::Pending, _) if decision.rule == Rule::HookPermission => {
+                decision.pending_tool.clone()
+            }
+            // Found by the transcript scan the status came from
+            (Status::Pending, _) if provider == Provider::Claude => decision.pending_tool.clone(),
+            (Status::Pending, Some(path)) => transcript::pending_tool_for(provider, path),
+            _ => None,
+        };
+        TranscriptDetails {
             status,
-        });
+            pending_tool,
+            queued,
+            stats,
+            decision,
+        }
     }
+}
+
+/// A session with the inputs its status was decided from (`poll --verbose`).
+#[derive(Debug, Serialize)]
+pub struct ExplainedSession {
+    #[serde(flatten)]
+    pub session: SessionInfo,
+    /// Absent for remote sessions, whose transcripts live on another machine
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub decision: Option<StatusDecision>,
+}
 
+/// Re-read each local session's transcript and attach how its status was decided.
+pub fn explain_sessions(
+    sessions: Vec<SessionInfo>,
+    thresholds: &Thresholds,
+) -> Vec<ExplainedSession> {
     sessions
+        .into_iter()
+        .map(|session| {
+            let decision = session.remote.is_none().then(|| {
+                let started = (session.pid != 0)
+                    .then(|| process::get_pid_start_time(session.pid))
+                    .flatten();
+                let hooks = match session.provider {
+                    Provider::Claude if session.pid != 0 => transcript::find_state_for_tty(
+                        session.tty.trim_start_matches("/dev/"),
+                        &ProcessIdentity {
+                            pid: session.pid,
+                            started_at: started,
+                        },
+                    )
+                    .map(|state| HookCounts::of(&state)),
+                    _ => None,
+                };
+                TranscriptDetails::read(
+                    session.provider,
+                    session.transcript.as_deref(),
+                    session.cpu_percent,
+                    started,
+                    &hooks.unwrap_or_default(),
+                    thresholds,
+                )
+                .decision
+            });
+            ExplainedSession { session, decision }
+        })
+        .collect()
+}
+
+/// A Claude session known only from its transcript, with no process or TTY on
+/// this machine: `tty` is the identifier it is addressed by, `pid` is 0.
+pub fn transcript_session(
+    path: &str,
+    tty: String,
+    cwd: String,
+    thresholds: &Thresholds,
+) -> SessionInfo {
+    let details = TranscriptDetails::read(
+        Provider::Claude,
+        Some(path),
+        None,
+        None,
+        &HookCounts::default(),
+        thresholds,
+    );
+    SessionInfo {
+        tty,
+        pid: 0,
+        cwd,
+        provider: Provider::Claude,
+        terminal: Terminal::Unknown,
+        transcript: Some(path.to_string()),
+        status: details.status,
+        pending_reason: details.pending_tool.as_deref().map(PendingReason::for_tool),
+        pending_tool: details.pending_tool,
+        queued: details.queued,
+        turns: details.stats.turns,
+        started_at: details.stats.started_at,
+        title: details.stats.title().map(str::to_string),
+        remote: None,
+        nested: Vec::new(),
+        container: None,
+        detached: false,
+        rule: Some(details.decision.rule),
+        cpu_percent: None,
+        profile: None,
+        env: Default::default(),
+        subagents: details.decision.subagents,
+        terminal_id: None,
+    }
+}
+
+/// How sessions are found (`discovery` in the config file).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DiscoveryOptions {
+    /// Also list recently written transcripts no process was matched to
+    pub transcript_first: bool,
+    /// Seconds since the last write for such a transcript to still be listed
+    pub max_age_secs: f64,
+}
+
+impl Default for DiscoveryOptions {
+    fn default() -> Self {
+        DiscoveryOptions {
+            transcript_first: false,
+            max_age_secs: 600.0,
+        }
+    }
+}
+
+/// `tty` of detached sessions: this prefix plus the start of the session id.
+pub const DETACHED_TTY_PREFIX: &str = "transcript:";
+
+/// Sessions for the transcripts in `projects_dir` written within
+/// `max_age_secs` that no other session has `claimed`: agents without a TTY,
+/// or whose process or terminal wasn't recognized.
+pub fn detached_sessions(
+    projects_dir: &Path,
+    claimed: &HashSet<String>,
+    max_age_secs: f64,
+    thresholds: &Thresholds,
+) -> Vec<SessionInfo> {
+    transcript::recent_transcripts(projects_dir, max_age_secs)
+        .into_iter()
+        .filter(|path| !claimed.contains(path))
+        .map(|path| {
+            let id: String = Path::new(&path)
+                .file_stem()
+                .map(|s| s.to_string_lossy().chars().take(8).collect())
+                .unwrap_or_default();
+            let cwd = transcript::transcript_cwd(&path).unwrap_or_default();
+            let tty = format!("{}{}", DETACHED_TTY_PREFIX, id);
+            let mut session = transcript_session(&path, tty, cwd, thresholds);
+            session.detached = true;
+            session
+        })
+        .collect()
+}
+
+/// Give each transcript to at most one session.
+///
+/// With no hook state, every session in a project falls back to the newest
+/// transcript, and two of them would mirror each other. A transcript recorded by
+/// the hook wins over a guessed one; among guesses the newest process (highest
+/// PID) keeps it. The other sessions are shown without a transcript.
+fn release_duplicate_transcripts(
+    sessions: &mut [(SessionInfo, Option<TranscriptSource>)],
+    thresholds: &Thresholds,
+) {
+    let mut owner: HashMap<String, usize> = HashMap::new();
+    for i in 0..sessions.len() {
+        let Some(path) = sessions[i].0.transcript.clone() else {
+            continue;
+        };
+        let rank = |(s, source): &(SessionInfo, Option<TranscriptSource>)| (*source, s.pid);
+        let loser = match owner.get(&path) {
+            None => {
+                owner.insert(path, i);
+                continue;
+            }
+            Some(&j) if rank(&sessions[i]) > rank(&sessions[j]) => {
+                owner.insert(path, i);
+                j
+            }
+            Some(_) => i,
+        };
+        let (session, source) = &mut sessions[loser];
+        logging::debug(
+            "poll",
+            &format!(
+                "{} shares {} with another session; showing it without a transcript",
+                session.tty,
+                session.transcript.as_deref().unwrap_or_default()
+            ),
+        );
+        session.transcript = None;
+        session.pending_tool = None;
+        session.pending_reason = None;
+        session.queued = 0;
+        session.subagents = 0;
+        session.turns = 0;
+        session.started_at = None;
+        session.title = None;
+        let decision = transcript::explain_status_for(session.provider, None, None, thresholds)
+            .with_process_age(
+                process::get_pid_start_time(session.pid).map(process::secs_since),
+                thresholds,
+            );
+        session.status = decision.status;
+        session.rule = Some(decision.rule);
+        *source = None;
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::net::TcpListener;
+    use std::os::unix::net::UnixStream;
+
+    fn session(tty: &str, status: Status) -> SessionInfo {
+        SessionInfo::for_test(tty, "/p", status)
+    }
+
+    #[test]
+    fn test_shared_update_bumps_seq_only_on_change() {
+        let shared = Shared::default();
+        assert!(shared.update(vec![]));
+        assert_eq!(shared.snapshot().0, 1);
+
+        assert!(!shared.update(vec![]));
+        assert_eq!(shared.snapshot().0, 1);
+
+        assert!(shared.update(vec![session("/dev/ttys000", Status::Active)]));
+        assert!(!shared.update(vec![session("/dev/ttys000", Status::Active)]));
+        assert!(shared.update(vec![session("/dev/ttys000", Status::Pending)]));
+        assert_eq!(shared.snapshot().0, 3);
+    }
+
+    #[test]
+    fn test_parallel_map_keeps_order() {
+        let items: Vec<u64> = (0..20).collect();
+        let out = parallel_map(&items, 4, |&i| {
+            // Finish later items first to shake out ordering bugs
+            thread::sleep(Duration::from_millis(20 - i));
+            i * 10
+        });
+        assert_eq!(out, (0..20).map(|i| i * 10).collect::<Vec<_>>());
+        assert!(parallel_map(&Vec::<u64>::new(), 4, |&i| i).is_empty());
+    }
+
+    #[test]
+    fn test_parallel_map_tallies_workers() {
+        let items: Vec<u64> = (0..6).collect();
+        let (_, tally) = process::tallied(|| {
+            parallel_map(&items, 4, |_| {
+                process::run(&mut std::process::Command::new(
+                    "/nonexistent/claude-bar-test-binary",
+                ))
+            })
+        });
+        assert_eq!(tally.subprocesses, 6);
+        assert!(tally.last_error.is_some());
+    }
+
+    #[test]
+    fn test_release_duplicate_transcripts() {
+        let with = |tty: &str, pid: u32, path: &str, source| {
+            let mut s = session(tty, Status::Pending);
+            s.pid = pid;
+            s.transcript = Some(path.into());
+            s.pending_tool = Some("Bash".into());
+            (s, Some(source))
+        };
+        let mut sessions = vec![
+            // Hook-confirmed claim beats a newer process's guess
+            with("/dev/ttys001", 10, "/t/a.jsonl", TranscriptSource::Hook),
+            with("/dev/ttys002", 20, "/t/a.jsonl", TranscriptSource::Newest),
+            // Two guesses: the newest process keeps it
+            with("/dev/ttys003", 30, "/t/b.jsonl", TranscriptSource::Newest),
+            with("/dev/ttys004", 40, "/t/b.jsonl", TranscriptSource::Newest),
+            with("/dev/ttys005", 50, "/t/c.jsonl", TranscriptSource::Newest),
+        ];
+        release_duplicate_transcripts(&mut sessions, &Thresholds::default());
+
+        let transcripts: Vec<Option<&str>> = sessions
+            .iter()
+            .map(|(s, _)| s.transcript.as_deref())
+            .collect();
+        assert_eq!(
+            transcripts,
+            vec![
+                Some("/t/a.jsonl"),
+                None,
+                None,
+                Some("/t/b.jsonl"),
+                Some("/t/c.jsonl")
+            ]
+        );
+        let loser = &sessions[1];
+        assert_eq!(loser.1, None);
+        assert_eq!(loser.0.pending_tool, None);
+        assert_ne!(loser.0.status, Status::Pending);
+        assert_eq!(sessions[0].0.status, Status::Pending);
+    }
+
+    #[test]
+    fn test_explain_sessions_skips_remote_transcripts() {
+        // No process to ask for a start time, so no transcript still means active
+        let local = SessionInfo {
+            pid: 0,
+            ..session("ttys001", Status::Active)
+        };
+        let mut remote = session("ttys002", Status::Idle);
+        remote.transcript = Some("/home/me/.claude/projects/x/abc.jsonl".into());
+        remote.remote = Some(crate::state::Remote {
+            host: "devbox".into(),
+            local_tty: None,
+        });
+
+        let explained = explain_sessions(vec![local, remote], &Thresholds::default());
+        let json = serde_json::to_value(&explained).unwrap();
+        assert_eq!(json[0]["tty"], "ttys001");
+        assert_eq!(json[0]["decision"]["rule"], "no_transcript");
+        assert_eq!(json[0]["decision"]["status"], "active");
+        assert_eq!(json[1]["tty"], "ttys002");
+        assert!(json[1].get("decision").is_none());
+    }
+
+    #[test]
+    fn test_detached_sessions_lists_unclaimed_transcripts() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let project = tmp.path().join("-Users-me-api");
+        std::fs::create_dir_all(&project).unwrap();
+        let entry = r#"{"type":"user","cwd":"/Users/me/api","message":{"role":"user","content":"Fix the login bug"}}"#;
+        let claimed_path = project.join("11111111-aaaa.jsonl");
+        let detached_path = project.join("9b1d04aa-bbbb.jsonl");
+        std::fs::write(&claimed_path, format!("{}\n", entry)).unwrap();
+        std::fs::write(&detached_path, format!("{}\n", entry)).unwrap();
+        let claimed: HashSet<String> = [claimed_path.to_string_lossy().to_string()]
+            .into_iter()
+            .collect();
+
+        let sessions = detached_sessions(tmp.path(), &claimed, 600.0, &Thresholds::default());
+        assert_eq!(sessions.len(), 1);
+        let detached = &sessions[0];
+        assert_eq!(detached.tty, "transcript:9b1d04aa");
+        assert_eq!(detached.key(), "transcript:9b1d04aa");
+        assert_eq!(detached.cwd, "/Users/me/api");
+        assert_eq!(detached.title.as_deref(), Some("Fix the login bug"));
+        assert!(detached.detached);
+        assert_eq!(detached.focus_tty(), None);
+        assert!(focus::focus_session(detached).is_err());
+    }
+
+    #[test]
+    fn test_poll_backoff_slows_and_recovers() {
+        let mut backoff = PollBackoff::default();
+        for _ in 0..4 {
+            assert_eq!(backoff.record(1500, 1000), None);
+        }
+        assert_eq!(backoff.record(1500, 1000), Some(BackoffChange::Slower));
+        assert_eq!(backoff.factor, 2);
+        assert_eq!(backoff.avg_ms(), 1500);
+
+        // Occasional slow polls neither back off further nor recover
+        for ms in [1500, 200, 200, 1500, 200] {
+            assert_eq!(backoff.record(ms, 1000), None);
+        }
+        assert_eq!(backoff.factor, 2);
+
+        // Recovers once the last slow poll leaves the window
+        for _ in 0..3 {
+            assert_eq!(backoff.record(200, 1000), None);
+        }
+        assert_eq!(backoff.record(200, 1000), Some(BackoffChange::Faster));
+        assert_eq!(backoff.factor, 1);
+    }
+
+    #[test]
+    fn test_poll_backoff_is_capped() {
+        let mut backoff = PollBackoff::default();
+        for _ in 0..100 {
+            backoff.record(5000, 1000);
+        }
+        assert_eq!(backoff.factor, MAX_BACKOFF);
+    }
+
+    #[test]
+    fn test_effective_interval_reported_in_health() {
+        let shared = Shared::default();
+        for _ in 0..BACKOFF_WINDOW {
+            shared.record_poll(
+                PollStats {
+                    duration_ms: 3000,
+                    subprocesses: 1,
+                },
+                None,
+            );
+        }
+        assert_eq!(shared.effective_poll_interval(), Duration::from_secs(4));
+        let health = shared.health();
+        assert_eq!(health.poll_backoff, 2);
+        assert_eq!(health.avg_poll_duration_ms, 3000);
+        assert_eq!(health.effective_interval_secs, 4.0);
+    }
+
+    #[tokio::test]
+    async fn test_hook_received_wakes_poller() {
+        let shared = Shared::default();
+        shared.handle_event(&SessionEvent::HookReceived {
+            tty: "/dev/ttys000".into(),
+            event: Some("PreToolUse".into()),
+        });
+        let started = Instant::now();
+        shared.sleep_until_next_poll(Duration::from_secs(30)).await;
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn test_store_consumes_polls_and_publishes_changes() {
+        let shared = Shared::default();
+        let events = shared.events.subscribe();
+        let poll = |sessions| SessionEvent::Polled {
+            sessions,
+            stats: PollStats {
+                duration_ms: 40,
+                subprocesses: 3,
+            },
+            error: None,
+        };
+
+        shared.handle_event(&poll(vec![session("/dev/ttys000", Status::Active)]));
+        shared.handle_event(&poll(vec![session("/dev/ttys000", Status::Active)]));
+        shared.handle_event(&poll(vec![session("/dev/ttys000", Status::Idle)]));
+        assert_eq!(shared.snapshot().0, 2);
+        assert_eq!(shared.health().last_poll_subprocesses, 3);
+
+        // Unchanged polls publish nothing
+        let seen: Vec<_> = events.try_iter().collect();
+        assert_eq!(seen.len(), 2);
+        let SessionEvent::SessionsChanged { seq, changes } = &*seen[1] else {
+            panic!("unexpected event {:?}", seen[1]);
+        };
+        assert_eq!(*seq, 2);
+        assert_eq!(changes[0].describe(), "/dev/ttys000 p: active -> idle");
+
+        // New colors mean a new frame even with the same sessions
+        shared.handle_event(&SessionEvent::ConfigReloaded {
+            colors_changed: true,
+        });
+        assert_eq!(shared.snapshot().0, 3);
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_change_returns_after_update() {
+        let shared = Arc::new(Shared::default());
+        shared.update(vec![]);
+
+        let writer = Arc::clone(&shared);
+        let handle = tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            writer.update(vec![session("/dev/ttys001", Status::Idle)]);
+        });
+
+        let (seq, sessions) = shared.wait_for_change(1, Duration::from_secs(5)).await;
+        handle.await.unwrap();
+        assert_eq!(seq, 2);
+        assert_eq!(sessions.len(), 1);
+    }
+
+    #[test]
+    fn test_changes_since_tracks_changed_and_removed() {
+        let shared = Shared::default();
+        shared.update(vec![
+            session("/dev/ttys000", Status::Active),
+            session("/dev/ttys001", Status::Active),
+        ]); // seq 1
+        shared.update(vec![
+            session("/dev/ttys000", Status::Pending),
+            session("/dev/ttys001", Status::Active),
+        ]); // seq 2
+        shared.update(vec![session("/dev/ttys000", Status::Pending)]); // seq 3
+
+        let state = shared.state.lock().unwrap();
+        let since1 = state.changes_since(1, None, false);
+        assert_eq!(since1.seq, 3);
+        assert!(!since1.full);
+        assert_eq!(since1.sessions.len(), 1);
+        assert_eq!(since1.sessions[0].status, Status::Pending);
+        assert_eq!(since1.removed, vec!["/dev/ttys001".to_string()]);
+
+        let since2 = state.changes_since(2, None, false);
+        assert!(since2.sessions.is_empty());
+        assert_eq!(since2.removed, vec!["/dev/ttys001".to_string()]);
+
+        let current = state.changes_since(3, None, false);
+        assert!(current.sessions.is_empty());
+        assert!(current.removed.is_empty());
+    }
+
+    #[test]
+    fn test_changes_since_unknown_seq_is_full() {
+        let shared = Shared::default();
+        shared.update(vec![session("/dev/ttys000", Status::Idle)]);
+        let state = shared.state.lock().unwrap();
+        for since in [0, 99] {
+            let resp = state.changes_since(since, None, false);
+            assert!(resp.full);
+            assert_eq!(resp.sessions.len(), 1);
+        }
+    }
+
+    #[test]
+    fn test_changes_since_other_epoch_is_full() {
+        let shared = Shared::default();
+        shared.update(vec![session("/dev/ttys000", Status::Idle)]);
+        shared.update(vec![session("/dev/ttys001", Status::Idle)]);
+        let state = shared.state.lock().unwrap();
+        let epoch = state.epoch.clone();
+        let delta = state.changes_since(1, Some(&epoch), false);
+        assert!(!delta.full);
+        assert_eq!(delta.epoch, epoch);
+        assert_eq!(delta.removed, vec!["/dev/ttys000".to_string()]);
+
+        // A `since` the restarted daemon has already passed is still stale
+        let restarted = state.changes_since(1, Some("4d2-17"), false);
+        assert!(restarted.full);
+        assert_eq!(restarted.sessions.len(), 1);
+        assert!(restarted.removed.is_empty());
+        assert_ne!(Shared::default().state.lock().unwrap().epoch, "");
+    }
+
+    #[test]
+    fn test_reappearing_session_clears_removed() {
+        let shared = Shared::default();
+        shared.update(vec![session("/dev/ttys000", Status::Idle)]);
+        shared.update(vec![]);
+        shared.update(vec![session("/dev/ttys000", Status::Idle)]);
+        let state = shared.state.lock().unwrap();
+        let resp = state.changes_since(1, None, false);
+        assert_eq!(resp.sessions.len(), 1);
+        assert!(resp.removed.is_empty());
+    }
+
+    #[test]
+    fn test_handle_request_sessions_and_changes() {
+        let shared = Shared::default();
+        shared.update(vec![session("/dev/ttys000", Status::Idle)]);
+
+        let resp = handle_request(r#"{"v":1,"cmd":"sessions"}"#, &shared, Access::Full);
+        assert!(resp.ok);
+        let sessions: Vec<SessionInfo> = serde_json::from_value(resp.data.unwrap()).unwrap();
+        assert_eq!(sessions.len(), 1);
+
+        let resp = handle_request(
+            r#"{"cmd":"sessions","status":["idle"],"fields":["tty","status"]}"#,
+            &shared,
+            Access::Full,
+        );
+        assert_eq!(
+            resp.data.unwrap(),
+            serde_json::json!([{"tty": "/dev/ttys000", "status": "idle"}])
+        );
+        let resp = handle_request(
+            r#"{"cmd":"sessions","status":["pending"]}"#,
+            &shared,
+            Access::Full,
+        );
+        assert_eq!(resp.data.unwrap(), serde_json::json!([]));
+        let resp = handle_request(
+            r#"{"cmd":"sessions","fields":["colour"]}"#,
+            &shared,
+            Access::Full,
+        );
+        assert!(resp.error.unwrap().starts_with("unknown field 'colour'"));
+
+        let resp = handle_request(r#"{"cmd":"changes","since":1}"#, &shared, Access::Full);
+        let changes: ChangesResponse = serde_json::from_value(resp.data.unwrap()).unwrap();
+        assert_eq!(changes.seq, 1);
+        assert!(changes.sessions.is_empty());
+
+        let resp = handle_request(r#"{"cmd":"nope"}"#, &shared, Access::Full);
+        assert!(!resp.ok);
+        assert!(resp.error.is_some());
+    }
+
+    #[test]
+    fn test_handle_request_health_and_focus_errors() {
+        let shared = Shared::default();
+        shared.update(vec![]);
+
+        shared.record_poll(
+            PollStats {
+                duration_ms: 120,
+                subprocesses: 9,
+            },
+            Some("osascript: not allowed".into()),
+        );
+
+        let data = handle_request(r#"{"cmd":"health"}"#, &shared, Access::Full)
+            .data
+            .unwrap();
+        let health: HealthReport = serde_json::from_value(data).unwrap();
+        assert_eq!(health.seq, 1);
+        assert_eq!(health.sessions, 0);
+        assert!(health.last_poll_unix.is_some());
+        assert_eq!(health.last_poll_duration_ms, 120);
+        assert_eq!(health.last_poll_subprocesses, 9);
+        assert_eq!(health.last_error.unwrap().message, "osascript: not allowed");
+        assert_eq!(health.config["poll_interval_secs"], 2.0);
+        assert!(health.config["interval_secs"].is_null());
+
+        let resp = handle_request(r#"{"cmd":"focus","tty":"ttys009"}"#, &shared, Access::Full);
+        assert!(!resp.ok);
+        assert_eq!(resp.error.unwrap(), "no live session on /dev/ttys009");
+    }
+
+    #[test]
+    fn test_read_only_access_hides_peer_sessions_and_refuses_actions() {
+        let shared = Shared::default();
+        let mut from_peer = session("/dev/ttys001", Status::Idle);
+        from_peer.remote = Some(crate::state::Remote {
+            host: "desk".into(),
+            local_tty: None,
+        });
+        shared.update(vec![
+            session("/dev/ttys000", Status::Active),
+            from_peer.clone(),
+        ]);
+
+        let resp = handle_request(r#"{"cmd":"sessions"}"#, &shared, Access::ReadOnly);
+        let sessions: Vec<SessionInfo> = serde_json::from_value(resp.data.unwrap()).unwrap();
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0].tty, "/dev/ttys000");
+        let resp = handle_request(r#"{"cmd":"sessions"}"#, &shared, Access::Full);
+        assert_eq!(resp.data.unwrap().as_array().unwrap().len(), 2);
+
+        // Container and transcript-only sessions are this machine's too
+        shared.update(vec![
+            session("/dev/ttys000", Status::Active),
+            session("container:web", Status::Idle),
+            session("transcript:0a1b", Status::Idle),
+            from_peer,
+        ]);
+        shared.update(vec![]);
+        let resp = handle_request(r#"{"cmd":"changes","since":2}"#, &shared, Access::ReadOnly);
+        assert_eq!(
+            resp.data.unwrap()["removed"],
+            serde_json::json!(["/dev/ttys000", "container:web", "transcript:0a1b"])
+        );
+        let resp = handle_request(r#"{"cmd":"changes","since":2}"#, &shared, Access::Full);
+        assert_eq!(resp.data.unwrap()["removed"].as_array().unwrap().len(), 4);
+
+        let hook = r#"{"cmd":"hook","input":{"session_id":"s","transcript_path":"/t.jsonl"},"tty":"ttys000","cwd":"/tmp/p","pid":1,"process_started":null,"at":0}"#;
+        for cmd in [
+            r#"{"cmd":"reload"}"#,
+            r#"{"cmd":"focus","tty":"ttys000"}"#,
+            hook,
+        ] {
+            let resp = handle_request(cmd, &shared, Access::ReadOnly);
+            assert!(!resp.ok);
+            assert!(resp.error.unwrap().contains("over TCP"));
+        }
+    }
+
+    #[test]
+    fn test_peer_fetches_sessions_over_tcp() {
+        let shared = Arc::new(Shared::default());
+        shared.update(vec![session("/dev/ttys000", Status::Pending)]);
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let listener = runtime
+            .block_on(tokio::net::TcpListener::bind("127.0.0.1:0"))
+            .unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let server = Arc::clone(&shared);
+        runtime.spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            handle_client(stream, server, Access::ReadOnly).await;
+        });
+
+        let peer = peer::parse_peer(&addr).unwrap();
+        let sessions = peer::fetch_sessions(&peer, Some("shared-secret-token")).unwrap();
+        assert_eq!(sessions, vec![session("/dev/ttys000", Status::Pending)]);
+    }
+
+    #[test]
+    fn test_slow_tcp_request_is_not_legacy() {
+        let shared = Arc::new(Shared::default());
+        shared.update(vec![session("/dev/ttys000", Status::Pending)]);
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let listener = runtime
+            .block_on(tokio::net::TcpListener::bind("127.0.0.1:0"))
+            .unwrap();
+        let addr = listener.local_addr().unwrap();
+        runtime.spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            handle_client(stream, shared, Access::ReadOnly).await;
+        });
+
+        use std::io::BufRead;
+
+        let mut stream = std::net::TcpStream::connect(addr).unwrap();
+        thread::sleep(REQUEST_TIMEOUT * 3);
+        writeln!(stream, r#"{{"v":1,"cmd":"health"}}"#).unwrap();
+        let mut reply = String::new();
+        std::io::BufReader::new(stream)
+            .read_line(&mut reply)
+            .unwrap();
+        let reply: Response = serde_json::from_str(&reply).unwrap();
+        assert!(reply.ok);
+    }
+
+    #[test]
+    fn test_tcp_listener_stays_on_loopback() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let shared = Arc::new(Shared::default());
+        runtime
+            .block_on(spawn_tcp_listener("127.0.0.1:0", shared))
+            .unwrap();
+
+        // Not even with a token, which would cross the network in the clear
+        let dir = tempfile::TempDir::new().unwrap();
+        let token_file = dir.path().join("token");
+        std::fs::write(&token_file, "shared-secret-token").unwrap();
+        std::fs::set_permissions(&token_file, std::fs::Permissions::from_mode(0o600)).unwrap();
+        let err = runtime
+            .block_on(serve(ServeConfig {
+                listen_tcp: Some("0.0.0.0:0".into()),
+                auth_token_file: Some(token_file),
+                socket_path: dir.path().join("claude-bar.sock"),
+                config_path: dir.path().join("config.json"),
+                ..ServeConfig::default()
+            }))
+            .unwrap_err();
+        assert!(err.to_string().contains("not a loopback address"));
+    }
+
+    #[tokio::test]
+    async fn test_request_lines_are_capped() {
+        let mut input = format!("{}\n", r#"{"cmd":"health"}"#).into_bytes();
+        input.extend(vec![b'x'; MAX_REQUEST_LINE + 10]);
+        let mut reader = tokio::io::BufReader::new(&input[..]);
+        let line = next_line(&mut reader).await.unwrap();
+        assert_eq!(line.as_deref(), Some(r#"{"cmd":"health"}"#));
+        let err = next_line(&mut reader).await.unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_health_reports_peers_and_merges_their_sessions() {
+        let shared = Shared::new(ServeConfig {
+            peers: vec![peer::parse_peer("desk=127.0.0.1:9").unwrap()],
+            ..ServeConfig::default()
+        });
+        assert!(shared.peer_sessions().is_empty());
+        shared.peers.lock().unwrap()[0].record_success(
+            vec![session("/dev/ttys000", Status::Pending)],
+            SystemTime::now(),
+        );
+        let merged = shared.peer_sessions();
+        assert_eq!(merged[0].key(), "desk:/dev/ttys000");
+
+        let health = shared.health();
+        assert_eq!(health.peers.len(), 1);
+        assert_eq!(health.peers[0].label, "desk");
+        assert_eq!(health.peers[0].sessions, 1);
+    }
+
+    fn shared_with_config_file(path: &Path) -> Shared {
+        Shared::new(ServeConfig {
+            config_path: path.to_path_buf(),
+            ..ServeConfig::default()
+        })
+    }
+
+    #[test]
+    fn test_reload_applies_config_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("claude-bar.json");
+        let shared = shared_with_config_file(&path);
+        shared.update(vec![]);
+        let events = shared.events.subscribe();
+
+        std::fs::write(
+            &path,
+            r##"{"poll_interval_secs": 0.5, "colors": {"active": "#00FF00"}}"##,
+        )
+        .unwrap();
+        let resp = handle_request(r#"{"cmd":"reload"}"#, &shared, Access::Full);
+        assert!(resp.ok);
+        assert_eq!(resp.data.unwrap()["config_changed"], true);
+        assert_eq!(shared.poll_interval(), Duration::from_millis(500));
+        assert_eq!(shared.config().colors.active, "#00FF00");
+        // Color change bumps seq (in the store) so Stream Deck clients redraw
+        for event in events.try_iter() {
+            shared.handle_event(&event);
+        }
+        assert_eq!(shared.snapshot().0, 2);
+
+        let resp = handle_request(r#"{"cmd":"reload"}"#, &shared, Access::Full);
+        assert_eq!(resp.data.unwrap()["config_changed"], false);
+
+        // Invalid edits are rejected and the previous config stays
+        std::fs::write(&path, r#"{"poll_interval_secs": -1}"#).unwrap();
+        let resp = handle_request(r#"{"cmd":"reload"}"#, &shared, Access::Full);
+        assert!(!resp.ok);
+        assert_eq!(shared.poll_interval(), Duration::from_millis(500));
+    }
+
+    #[test]
+    fn test_interval_flag_overrides_config_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("claude-bar.json");
+        std::fs::write(&path, r#"{"poll_interval_secs": 10}"#).unwrap();
+        let shared = Shared::new(ServeConfig {
+            interval_secs: Some(1.0),
+            config_path: path,
+            ..ServeConfig::default()
+        });
+        shared.reload_config().unwrap();
+        assert_eq!(shared.poll_interval(), Duration::from_secs(1));
+    }
+
+    #[tokio::test]
+    async fn test_reload_wakes_poller() {
+        let dir = tempfile::tempdir().unwrap();
+        let shared = Arc::new(shared_with_config_file(&dir.path().join("claude-bar.json")));
+        let poller = Arc::clone(&shared);
+        let handle = tokio::spawn(async move {
+            let start = Instant::now();
+            poller.sleep_until_next_poll(Duration::from_secs(30)).await;
+            start.elapsed()
+        });
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        let resp = handle_request(r#"{"cmd":"reload"}"#, &shared, Access::Full);
+        assert!(resp.ok);
+        assert!(handle.await.unwrap() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn test_tls_listener_requires_token() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let cert = dir.path().join("cert.pem");
+        let key = dir.path().join("key.pem");
+        std::fs::write(&cert, crate::tls::tests::TEST_CERT).unwrap();
+        std::fs::write(&key, crate::tls::tests::TEST_KEY).unwrap();
+        let tls = crate::tls::server_config(&cert, &key).unwrap();
+
+        let shared = Arc::new(Shared::default());
+        let mut remote = session("/dev/ttys009", Status::Idle);
+        remote.remote = Some(crate::state::Remote {
+            host: "other".into(),
+            local_tty: None,
+        });
+        shared.update(vec![session("/dev/ttys001", Status::Active), remote]);
+
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let _guard = runtime.enter();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        spawn_tls_listener(listener, tls, "shared-secret-token".into(), shared).unwrap();
+
+        let fingerprint =
+            crate::tls::parse_fingerprint(crate::tls::tests::TEST_FINGERPRINT).unwrap();
+        let timeout = Duration::from_secs(5);
+        let reply = protocol::send_request_tls(
+            &addr,
+            &fingerprint,
+            "shared-secret-token",
+            &Request::sessions(),
+            timeout,
+        )
+        .unwrap();
+        assert!(reply.ok);
+        // Only local sessions are exported
+        let sessions: Vec<SessionInfo> = serde_json::from_value(reply.data.unwrap()).unwrap();
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0].tty, "/dev/ttys001");
+
+        let err = protocol::send_request_tls(
+            &addr,
+            &fingerprint,
+            "wrong-token-value",
+            &Request::sessions(),
+            timeout,
+        )
+        .unwrap_err();
+        assert_eq!(err.to_string(), "authentication failed");
+
+        let reply = protocol::send_request_tls(
+            &addr,
+            &fingerprint,
+            "shared-secret-token",
+            &Request::Focus {
+                tty: "/dev/ttys001".into(),
+            },
+            timeout,
+        )
+        .unwrap();
+        assert!(!reply.ok);
+
+        let mut other = fingerprint.clone();
+        other[0] ^= 1;
+        assert!(protocol::send_request_tls(
+            &addr,
+            &other,
+            "shared-secret-token",
+            &Request::sessions(),
+            timeout
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_tls_listener_limits_unauthenticated_clients() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let cert = dir.path().join("cert.pem");
+        let key = dir.path().join("key.pem");
+        std::fs::write(&cert, crate::tls::tests::TEST_CERT).unwrap();
+        std::fs::write(&key, crate::tls::tests::TEST_KEY).unwrap();
+        let tls = crate::tls::server_config(&cert, &key).unwrap();
+
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let _guard = runtime.enter();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let shared = Arc::new(Shared::default());
+        spawn_tls_listener(listener, tls, "shared-secret-token".into(), shared).unwrap();
+
+        // Clients that never finish the handshake fill every slot...
+        let idle: Vec<TcpStream> = (0..MAX_UNAUTHENTICATED)
+            .map(|_| TcpStream::connect(addr).unwrap())
+            .collect();
+        std::thread::sleep(Duration::from_millis(200));
+        // ...so the next one is closed at once rather than served
+        let mut extra = TcpStream::connect(addr).unwrap();
+        extra
+            .set_read_timeout(Some(Duration::from_secs(2)))
+            .unwrap();
+        assert_eq!(extra.read(&mut [0u8; 16]).unwrap(), 0);
+
+        // A slot frees up once one of them hangs up
+        drop(idle);
+        std::thread::sleep(Duration::from_millis(200));
+        let fingerprint =
+            crate::tls::parse_fingerprint(crate::tls::tests::TEST_FINGERPRINT).unwrap();
+        let reply = protocol::send_request_tls(
+            &addr.to_string(),
+            &fingerprint,
+            "shared-secret-token",
+            &Request::sessions(),
+            Duration::from_secs(5),
+        )
+        .unwrap();
+        assert!(reply.ok);
+    }
+
+    #[test]
+    fn test_unix_clients_legacy_and_protocol() {
+        use std::io::{BufRead, BufReader};
+
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("claude-bar.sock");
+        let shared = Arc::new(Shared::default());
+        shared.update(vec![session("/dev/ttys000", Status::Idle)]);
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let _guard = runtime.enter();
+        let listener = tokio::net::UnixListener::bind(&path).unwrap();
+        runtime.spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(handle_client(stream, Arc::clone(&shared), Access::Full));
+            }
+        });
+
+        // Legacy client: says nothing, gets the bare array and a hangup
+        let legacy = UnixStream::connect(&path).unwrap();
+        let mut lines = BufReader::new(legacy).lines();
+        let sessions: Vec<SessionInfo> =
+            serde_json::from_str(&lines.next().unwrap().unwrap()).unwrap();
+        assert_eq!(sessions.len(), 1);
+        assert!(lines.next().is_none());
+
+        // Protocol client: many requests on one connection
+        let client = UnixStream::connect(&path).unwrap();
+        let mut lines = BufReader::new(client.try_clone().unwrap()).lines();
+        for cmd in [r#"{"cmd":"health"}"#, r#"{"cmd":"changes","since":0}"#] {
+            writeln!(&client, "{}", cmd).unwrap();
+            let reply: Response = serde_json::from_str(&lines.next().unwrap().unwrap()).unwrap();
+            assert!(reply.ok);
+            assert_eq!(reply.data.unwrap()["seq"], 1);
+        }
+    }
+
+    #[test]
+    fn test_bind_private_socket_is_user_only() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("run").join("claude-bar.sock");
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, "stale").unwrap();
+
+        let listener = bind_private_socket(&path).unwrap();
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        // Staging directory is gone, only the socket remains
+        let entries: Vec<_> = std::fs::read_dir(path.parent().unwrap()).unwrap().collect();
+        assert_eq!(entries.len(), 1);
+
+        let client = UnixStream::connect(&path).unwrap();
+        let (server, _) = listener.accept().unwrap();
+        let uid = unsafe { libc::getuid() };
+        assert_eq!(peer_uid(&server).unwrap(), uid);
+        assert_eq!(peer_uid(&client).unwrap(), uid);
+
+        let fresh = dir.path().join("new").join("claude-bar.sock");
+        bind_private_socket(&fresh).unwrap();
+        let mode = std::fs::metadata(fresh.parent().unwrap())
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o777, 0o700);
+    }
 
     #[test]
     fn test_state_dir_uses_centralized_path() {
-        let home = std::env::var("HOME").unwrap_or_default();
-        let expected =
-            std::path::PathBuf::from(&home).join(".claude/claude-bar/-Users-test-project");
+        let expected = crate::paths::state_dir().join("sessions/-Users-test-project");
         assert_eq!(
             transcript::state_dir_for_cwd("/Users/test/project"),
             expected
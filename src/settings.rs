@@ -1,3 +1,11 @@
+//! Claude Code `settings.json` hook registration (`claude-bar hooks install|uninstall`).
+//!
+//! Settings are parsed and merged rather than rewritten: unrelated keys and
+//! hooks are kept, the file's indentation is reused, an existing claude-bar hook
+//! is updated in place instead of duplicated, and nothing is written when
+//! nothing changed.
+
+use clap::ValueEnum;
 use serde_json::{Map, Value};
 use std::error::Error;
 use std::fs;
@@ -11,18 +19,100 @@ const LEGACY_EVENTS: &[&str] = &[
     "SessionStart",
 ];
 const LEGACY_PATTERNS: &[&str] = &["update-status.sh", "session-track.sh"];
-const UNINSTALL_PATTERNS: &[&str] = &["session-track.sh", "update-status.sh", "claude-bar"];
 
+/// `settings.json` in Claude Code's config directory (honors `CLAUDE_CONFIG_DIR`).
 pub fn default_settings_path() -> PathBuf {
-    let home = std::env::var("HOME").unwrap_or_default();
-    PathBuf::from(home).join(".claude").join("settings.json")
+    crate::config::Config::load()
+        .claude_config_dir()
+        .join("settings.json")
+}
+
+/// Which Claude Code settings file hooks go into.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum Scope {
+    /// `settings.json` in Claude Code's config directory (each profile's), for every project
+    #[default]
+    User,
+    /// `.claude/settings.local.json` in a project (not meant to be committed)
+    Project,
+}
+
+/// Settings files for `scope`: one per configured Claude profile for
+/// [`Scope::User`], the project's own for [`Scope::Project`].
+pub fn scope_settings_paths(scope: Scope, project_dir: &Path) -> Vec<PathBuf> {
+    match scope {
+        Scope::User => crate::config::Config::load()
+            .claude_roots()
+            .into_iter()
+            .map(|root| root.config_dir.join("settings.json"))
+            .collect(),
+        Scope::Project => vec![project_dir.join(".claude").join("settings.local.json")],
+    }
+}
+
+/// Hook events claude-bar can register for. The hook records the session's
+/// transcript whichever event fires; `SessionStart` is the one that matters.
+/// `PreToolUse`, `PostToolUse`, `Notification` and `Stop` track tool calls in
+/// flight; `SubagentStop` counts running subagents.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum HookEvent {
+    SessionStart,
+    Stop,
+    Notification,
+    PreToolUse,
+    PostToolUse,
+    SubagentStop,
+    UserPromptSubmit,
+}
+
+impl HookEvent {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            HookEvent::SessionStart => "SessionStart",
+            HookEvent::Stop => "Stop",
+            HookEvent::Notification => "Notification",
+            HookEvent::PreToolUse => "PreToolUse",
+            HookEvent::PostToolUse => "PostToolUse",
+            HookEvent::SubagentStop => "SubagentStop",
+            HookEvent::UserPromptSubmit => "UserPromptSubmit",
+        }
+    }
+}
+
+/// A hook script from the shell-based releases that preceded `claude-bar hook`.
+fn is_legacy_hook(command: &str) -> bool {
+    LEGACY_PATTERNS
+        .iter()
+        .any(|pattern| command.contains(pattern))
+}
+
+/// A command registered by claude-bar, possibly from an older install path.
+fn is_claude_bar_hook(command: &str) -> bool {
+    let mut words = command.split_whitespace();
+    let program = words.next().unwrap_or("");
+    Path::new(program)
+        .file_name()
+        .is_some_and(|n| n == "claude-bar")
+        && words.next() == Some("hook")
 }
 
 pub fn install_session_start_hook(
     settings_path: &Path,
     hook_cmd: &str,
 ) -> Result<bool, Box<dyn Error>> {
-    let mut cfg = read_settings_or_empty(settings_path)?;
+    install_hooks(settings_path, hook_cmd, &[HookEvent::SessionStart])
+}
+
+/// Register `hook_cmd` under each of `events`. A claude-bar hook already
+/// registered for an event (e.g. from a binary that has since moved) is
+/// pointed at `hook_cmd` instead of being added again. Returns whether the
+/// file changed.
+pub fn install_hooks(
+    settings_path: &Path,
+    hook_cmd: &str,
+    events: &[HookEvent],
+) -> Result<bool, Box<dyn Error>> {
+    let (mut cfg, indent) = read_settings_or_empty(settings_path)?;
     let cfg_obj = as_object_mut(&mut cfg, "settings root must be a JSON object")?;
 
     let hooks_val = cfg_obj
@@ -31,19 +121,66 @@ pub fn install_session_start_hook(
     let hooks_obj = as_object_mut(hooks_val, "`hooks` must be a JSON object")?;
 
     let mut changed = false;
-    cleanup_event_patterns(hooks_obj, LEGACY_EVENTS, LEGACY_PATTERNS, &mut changed);
+    remove_hooks(hooks_obj, LEGACY_EVENTS, is_legacy_hook, &mut changed);
 
-    let session_start = hooks_obj
-        .entry("SessionStart".to_string())
-        .or_insert_with(|| Value::Array(Vec::new()));
-    let matchers = session_start
-        .as_array_mut()
-        .ok_or("`hooks.SessionStart` must be an array")?;
+    for event in events {
+        let entries = hooks_obj
+            .entry(event.as_str().to_string())
+            .or_insert_with(|| Value::Array(Vec::new()));
+        let matchers = entries
+            .as_array_mut()
+            .ok_or_else(|| format!("`hooks.{}` must be an array", event.as_str()))?;
+        changed |= upsert_hook_command(matchers, hook_cmd);
+    }
+
+    if hooks_obj.is_empty() {
+        cfg_obj.shift_remove("hooks");
+    }
+
+    if changed {
+        write_settings_with_indent(settings_path, &cfg, &indent)?;
+    }
+    Ok(changed)
+}
 
-    let already = matchers
+/// Make `hook_cmd` the one claude-bar hook among `matchers`: keep it if
+/// present, else repoint the first stale claude-bar command, else append.
+/// Further stale duplicates are dropped.
+fn upsert_hook_command(matchers: &mut Vec<Value>, hook_cmd: &str) -> bool {
+    let mut changed = false;
+    let mut found = matchers
         .iter()
-        .any(|matcher| matcher_has_command_containing(matcher, hook_cmd));
-    if !already {
+        .any(|matcher| matcher_has_command(matcher, hook_cmd));
+
+    for matcher in matchers.iter_mut() {
+        let Some(hooks) = matcher.get_mut("hooks").and_then(|h| h.as_array_mut()) else {
+            continue;
+        };
+        let before = hooks.len();
+        hooks.retain_mut(|hook| {
+            let Some(command) = hook.get("command").and_then(|c| c.as_str()) else {
+                return true;
+            };
+            if command == hook_cmd || !is_claude_bar_hook(command) {
+                return true;
+            }
+            if found {
+                return false;
+            }
+            hook["command"] = Value::String(hook_cmd.to_string());
+            found = true;
+            changed = true;
+            true
+        });
+        changed |= hooks.len() != before;
+    }
+    matchers.retain(|m| {
+        m.get("hooks")
+            .and_then(|h| h.as_array())
+            .is_none_or(|h| !h.is_empty())
+    });
+
+    if !found {
         matchers.push(serde_json::json!({
             "hooks": [
                 {"type": "command", "command": hook_cmd}
@@ -51,15 +188,7 @@ pub fn install_session_start_hook(
         }));
         changed = true;
     }
-
-    if hooks_obj.is_empty() {
-        cfg_obj.remove("hooks");
-    }
-
-    if changed {
-        write_settings(settings_path, &cfg)?;
-    }
-    Ok(changed)
+    changed
 }
 
 pub fn uninstall_managed_hooks(settings_path: &Path) -> Result<bool, Box<dyn Error>> {
@@ -67,63 +196,72 @@ pub fn uninstall_managed_hooks(settings_path: &Path) -> Result<bool, Box<dyn Err
         return Ok(false);
     }
 
-    let mut cfg = read_settings_file(settings_path)?;
+    let (mut cfg, indent) = read_settings_file(settings_path)?;
     let cfg_obj = as_object_mut(&mut cfg, "settings root must be a JSON object")?;
 
     let mut changed = false;
     if let Some(hooks) = cfg_obj.get_mut("hooks") {
         let hooks_obj = as_object_mut(hooks, "`hooks` must be a JSON object")?;
         let events: Vec<String> = hooks_obj.keys().cloned().collect();
-        cleanup_event_patterns(
+        remove_hooks(
             hooks_obj,
             &events_as_strs(&events),
-            UNINSTALL_PATTERNS,
+            |command| is_claude_bar_hook(command) || is_legacy_hook(command),
             &mut changed,
         );
         if hooks_obj.is_empty() {
-            cfg_obj.remove("hooks");
+            cfg_obj.shift_remove("hooks");
             changed = true;
         }
     }
 
-    // Keep behavior close to previous uninstall script: write file whenever it exists.
-    write_settings(settings_path, &cfg)?;
+    if changed {
+        write_settings_with_indent(settings_path, &cfg, &indent)?;
+    }
     Ok(changed)
 }
 
-fn cleanup_event_patterns(
+/// Drop the hooks of `events` whose command `is_managed` accepts. Other hooks
+/// in the same matcher group stay; a group goes only once it is empty, and an
+/// event once it has no groups left.
+fn remove_hooks(
     hooks_obj: &mut Map<String, Value>,
     events: &[&str],
-    patterns: &[&str],
+    is_managed: impl Fn(&str) -> bool,
     changed: &mut bool,
 ) {
     for event in events {
-        let Some(existing) = hooks_obj.get(*event).and_then(|v| v.as_array()) else {
+        let Some(matchers) = hooks_obj.get_mut(*event).and_then(|v| v.as_array_mut()) else {
             continue;
         };
-        let filtered: Vec<Value> = existing
-            .iter()
-            .filter(|m| !matcher_has_any_pattern(m, patterns))
-            .cloned()
-            .collect();
-        if filtered.len() != existing.len() {
-            *changed = true;
-            if filtered.is_empty() {
-                hooks_obj.remove(*event);
-            } else {
-                hooks_obj.insert((*event).to_string(), Value::Array(filtered));
+        let before = matchers.len();
+        matchers.retain_mut(|matcher| {
+            let Some(hooks) = matcher.get_mut("hooks").and_then(|h| h.as_array_mut()) else {
+                return true;
+            };
+            let hooks_before = hooks.len();
+            hooks.retain(|hook| {
+                !hook
+                    .get("command")
+                    .and_then(|c| c.as_str())
+                    .is_some_and(&is_managed)
+            });
+            if hooks.len() == hooks_before {
+                return true;
             }
+            *changed = true;
+            !hooks.is_empty()
+        });
+        if matchers.len() != before {
+            *changed = true;
+        }
+        if matchers.is_empty() {
+            hooks_obj.shift_remove(*event);
         }
     }
 }
 
-fn matcher_has_any_pattern(matcher: &Value, patterns: &[&str]) -> bool {
-    patterns
-        .iter()
-        .any(|pattern| matcher_has_command_containing(matcher, pattern))
-}
-
-fn matcher_has_command_containing(matcher: &Value, needle: &str) -> bool {
+fn matcher_has_command(matcher: &Value, command: &str) -> bool {
     matcher
         .get("hooks")
         .and_then(|v| v.as_array())
@@ -131,34 +269,81 @@ fn matcher_has_command_containing(matcher: &Value, needle: &str) -> bool {
             hooks.iter().any(|h| {
                 h.get("command")
                     .and_then(|v| v.as_str())
-                    .map(|cmd| cmd.contains(needle))
+                    .map(|cmd| cmd == command)
                     .unwrap_or(false)
             })
         })
         .unwrap_or(false)
 }
 
-fn read_settings_or_empty(path: &Path) -> Result<Value, Box<dyn Error>> {
+/// Indentation used when the file has none to copy.
+const DEFAULT_INDENT: &str = "  ";
+
+fn read_settings_or_empty(path: &Path) -> Result<(Value, String), Box<dyn Error>> {
     if path.is_file() {
         read_settings_file(path)
     } else {
-        Ok(Value::Object(Map::new()))
+        Ok((Value::Object(Map::new()), DEFAULT_INDENT.to_string()))
     }
 }
 
-fn read_settings_file(path: &Path) -> Result<Value, Box<dyn Error>> {
+/// The settings and the indentation the file uses.
+fn read_settings_file(path: &Path) -> Result<(Value, String), Box<dyn Error>> {
     let content = fs::read_to_string(path)?;
-    let value: Value = serde_json::from_str(&content)?;
-    Ok(value)
+    let value: Value = serde_json::from_str(&content).map_err(|e| {
+        format!(
+            "{} is not valid JSON ({}); not touching it",
+            path.display(),
+            e
+        )
+    })?;
+    Ok((value, detect_indent(&content)))
+}
+
+/// Leading whitespace of the first indented line (tabs or N spaces).
+fn detect_indent(content: &str) -> String {
+    content
+        .lines()
+        .skip(1)
+        .map(|line| {
+            let trimmed = line.trim_start_matches([' ', '\t']);
+            &line[..line.len() - trimmed.len()]
+        })
+        .find(|indent| !indent.is_empty())
+        .unwrap_or(DEFAULT_INDENT)
+        .to_string()
 }
 
+#[cfg(test)]
 fn write_settings(path: &Path, value: &Value) -> Result<(), Box<dyn Error>> {
+    write_settings_with_indent(path, value, DEFAULT_INDENT)
+}
+
+fn write_settings_with_indent(
+    path: &Path,
+    value: &Value,
+    indent: &str,
+) -> Result<(), Box<dyn Error>> {
+    use serde::Serialize;
+
     if let Some(parent) = path.parent() {
         fs::create_dir_all(parent)?;
     }
-    let mut out = serde_json::to_string_pretty(value)?;
-    out.push('\n');
-    fs::write(path, out)?;
+    let mut out = Vec::new();
+    let formatter = serde_json::ser::PrettyFormatter::with_indent(indent.as_bytes());
+    let mut serializer = serde_json::Serializer::with_formatter(&mut out, formatter);
+    value.serialize(&mut serializer)?;
+    out.push(b'\n');
+    // Write next to the file and rename, so Claude Code never reads a half-written
+    // file. A symlinked settings.json (dotfile managers) is written through, at
+    // its target, and the file keeps its permissions.
+    let target = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let staged = target.with_extension("json.claude-bar-tmp");
+    fs::write(&staged, out)?;
+    if let Ok(meta) = fs::metadata(&target) {
+        fs::set_permissions(&staged, meta.permissions())?;
+    }
+    fs::rename(&staged, &target)?;
     Ok(())
 }
 
@@ -236,12 +421,10 @@ mod tests {
 
         let session = hooks.get("SessionStart").unwrap().as_array().unwrap();
         assert_eq!(session.len(), 2);
+        assert!(session.iter().any(|m| matcher_has_command(m, "/b/keep.sh")));
         assert!(session
             .iter()
-            .any(|m| matcher_has_command_containing(m, "/b/keep.sh")));
-        assert!(session
-            .iter()
-            .any(|m| matcher_has_command_containing(m, "/tmp/claude-bar hook")));
+            .any(|m| matcher_has_command(m, "/tmp/claude-bar hook")));
     }
 
     #[test]
@@ -267,7 +450,216 @@ mod tests {
 
         let session = hooks.get("SessionStart").unwrap().as_array().unwrap();
         assert_eq!(session.len(), 1);
-        assert!(matcher_has_command_containing(&session[0], "/tmp/keep.sh"));
+        assert!(matcher_has_command(&session[0], "/tmp/keep.sh"));
         assert!(hooks.get("Notification").is_none());
     }
+
+    #[test]
+    fn uninstall_keeps_other_hooks_in_shared_groups() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let path = tmp.path().join("settings.json");
+        let seed = serde_json::json!({
+            "hooks": {
+                "Stop": [
+                    {"matcher": "*", "hooks": [
+                        {"type": "command", "command": "/usr/local/bin/claude-bar hook"},
+                        {"type": "command", "command": "/tmp/keep.sh"}
+                    ]},
+                    {"hooks": [{"type": "command", "command": "/opt/claude-bar-sync/run.sh"}]}
+                ]
+            }
+        });
+        write_settings(&path, &seed).unwrap();
+
+        assert!(uninstall_managed_hooks(&path).unwrap());
+        let v = read_json(&path);
+        assert_eq!(
+            v["hooks"]["Stop"],
+            serde_json::json!([
+                {"matcher": "*", "hooks": [{"type": "command", "command": "/tmp/keep.sh"}]},
+                {"hooks": [{"type": "command", "command": "/opt/claude-bar-sync/run.sh"}]}
+            ])
+        );
+        assert!(!uninstall_managed_hooks(&path).unwrap());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn writes_through_symlinks_and_keeps_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let tmp = tempfile::TempDir::new().unwrap();
+        let real = tmp.path().join("dotfiles-settings.json");
+        let path = tmp.path().join("settings.json");
+        write_settings(&real, &serde_json::json!({"model": "opus"})).unwrap();
+        fs::set_permissions(&real, fs::Permissions::from_mode(0o600)).unwrap();
+        std::os::unix::fs::symlink(&real, &path).unwrap();
+
+        install_session_start_hook(&path, "/tmp/claude-bar hook").unwrap();
+
+        assert!(fs::symlink_metadata(&path)
+            .unwrap()
+            .file_type()
+            .is_symlink());
+        assert_eq!(
+            fs::metadata(&real).unwrap().permissions().mode() & 0o777,
+            0o600
+        );
+        let v = read_json(&real);
+        assert_eq!(v["model"], "opus");
+        assert!(matcher_has_command(
+            &v["hooks"]["SessionStart"][0],
+            "/tmp/claude-bar hook"
+        ));
+    }
+
+    #[test]
+    fn install_repoints_moved_binary_instead_of_duplicating() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let path = tmp.path().join("settings.json");
+        let seed = serde_json::json!({
+            "hooks": {
+                "SessionStart": [
+                    {"hooks": [
+                        {"type": "command", "command": "/old/target/release/claude-bar hook"},
+                        {"type": "command", "command": "/tmp/keep.sh"}
+                    ]},
+                    {"hooks": [{"type": "command", "command": "/other/claude-bar hook"}]}
+                ]
+            }
+        });
+        write_settings(&path, &seed).unwrap();
+
+        let changed = install_hooks(
+            &path,
+            "/new/claude-bar hook",
+            &[HookEvent::SessionStart, HookEvent::Stop],
+        )
+        .unwrap();
+        assert!(changed);
+        let v = read_json(&path);
+        let session = v["hooks"]["SessionStart"].as_array().unwrap();
+        assert_eq!(session.len(), 1);
+        assert_eq!(session[0]["hooks"][0]["command"], "/new/claude-bar hook");
+        assert_eq!(session[0]["hooks"][1]["command"], "/tmp/keep.sh");
+        let stop = v["hooks"]["Stop"].as_array().unwrap();
+        assert_eq!(stop.len(), 1);
+        assert!(matcher_has_command(&stop[0], "/new/claude-bar hook"));
+    }
+
+    #[test]
+    fn install_repoints_a_longer_command_instead_of_dropping_it() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let path = tmp.path().join("settings.json");
+        let seed = serde_json::json!({
+            "hooks": {
+                "SessionStart": [
+                    {"hooks": [{"type": "command", "command": "/opt/bin/claude-bar hook --log"}]}
+                ]
+            }
+        });
+        write_settings(&path, &seed).unwrap();
+
+        assert!(install_session_start_hook(&path, "/opt/bin/claude-bar hook").unwrap());
+        let v = read_json(&path);
+        assert_eq!(
+            v["hooks"]["SessionStart"],
+            serde_json::json!([
+                {"hooks": [{"type": "command", "command": "/opt/bin/claude-bar hook"}]}
+            ])
+        );
+    }
+
+    #[test]
+    fn install_keeps_key_order() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let path = tmp.path().join("settings.json");
+        fs::write(
+            &path,
+            "{\n  \"theme\": \"dark\",\n  \"model\": \"opus\"\n}\n",
+        )
+        .unwrap();
+
+        install_session_start_hook(&path, "/tmp/claude-bar hook").unwrap();
+        let content = fs::read_to_string(&path).unwrap();
+        let theme = content.find("\"theme\"").unwrap();
+        let model = content.find("\"model\"").unwrap();
+        let hooks = content.find("\"hooks\"").unwrap();
+        assert!(theme < model && model < hooks, "{}", content);
+    }
+
+    #[test]
+    fn install_tool_hooks_for_every_tool() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let path = tmp.path().join("settings.json");
+
+        install_hooks(
+            &path,
+            "/tmp/claude-bar hook",
+            &[
+                HookEvent::PreToolUse,
+                HookEvent::PostToolUse,
+                HookEvent::SubagentStop,
+            ],
+        )
+        .unwrap();
+        let v = read_json(&path);
+        for event in ["PreToolUse", "PostToolUse", "SubagentStop"] {
+            assert!(v["hooks"][event][0].get("matcher").is_none());
+            assert!(matcher_has_command(
+                &v["hooks"][event][0],
+                "/tmp/claude-bar hook"
+            ));
+        }
+
+        uninstall_managed_hooks(&path).unwrap();
+        assert!(read_json(&path).get("hooks").is_none());
+    }
+
+    #[test]
+    fn install_keeps_indentation_and_skips_unchanged_writes() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let path = tmp.path().join("settings.json");
+        fs::write(&path, "{\n    \"model\": \"opus\"\n}\n").unwrap();
+
+        install_session_start_hook(&path, "/tmp/claude-bar hook").unwrap();
+        let content = fs::read_to_string(&path).unwrap();
+        assert!(content.contains("\n    \"hooks\": {\n        \"SessionStart\""));
+        assert!(content.contains("\n    \"model\": \"opus\""));
+
+        // Hand-formatted file with the hook already present: left byte-for-byte alone
+        let compact = "{\"hooks\":{\"SessionStart\":[{\"hooks\":[{\"type\":\"command\",\"command\":\"/tmp/claude-bar hook\"}]}]}}";
+        fs::write(&path, compact).unwrap();
+        assert!(!install_session_start_hook(&path, "/tmp/claude-bar hook").unwrap());
+        assert_eq!(fs::read_to_string(&path).unwrap(), compact);
+    }
+
+    #[test]
+    fn install_refuses_invalid_json() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let path = tmp.path().join("settings.json");
+        fs::write(&path, "{ // comment\n}").unwrap();
+        let err = install_session_start_hook(&path, "/tmp/claude-bar hook").unwrap_err();
+        assert!(err.to_string().contains("not valid JSON"));
+        assert_eq!(fs::read_to_string(&path).unwrap(), "{ // comment\n}");
+    }
+
+    #[test]
+    fn detects_indentation() {
+        assert_eq!(detect_indent("{\n\t\"a\": 1\n}"), "\t");
+        assert_eq!(detect_indent("{\n    \"a\": 1\n}"), "    ");
+        assert_eq!(detect_indent("{}"), DEFAULT_INDENT);
+    }
+
+    #[test]
+    fn recognizes_claude_bar_hook_commands() {
+        assert!(is_claude_bar_hook("/usr/local/bin/claude-bar hook"));
+        assert!(is_claude_bar_hook("claude-bar hook"));
+        assert!(!is_claude_bar_hook("/usr/local/bin/claude-bar poll"));
+        assert!(!is_claude_bar_hook("/tmp/not-claude-bar hook"));
+        assert_eq!(
+            scope_settings_paths(Scope::Project, Path::new("/src/api")),
+            vec![PathBuf::from("/src/api/.claude/settings.local.json")]
+        );
+    }
 }
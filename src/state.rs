@@ -1,12 +1,24 @@
-use serde::{Deserialize, Serialize};
+use schemars::JsonSchema;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::collections::BTreeMap;
 use std::fmt;
+use std::fs;
+use std::path::Path;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "lowercase")]
 pub enum Status {
     Active,
     Pending,
     Idle,
+    /// The API keeps failing (overloaded, rate limited) and Claude is retrying
+    #[serde(rename = "rate_limited")]
+    RateLimited,
+    /// The process has run for a while without a transcript (hooks or the
+    /// projects directory are likely misconfigured). Statuses added by newer
+    /// builds read as this.
+    #[serde(other)]
+    Unknown,
 }
 
 impl fmt::Display for Status {
@@ -15,17 +27,23 @@ impl fmt::Display for Status {
             Status::Active => write!(f, "active"),
             Status::Pending => write!(f, "pending"),
             Status::Idle => write!(f, "idle"),
+            Status::RateLimited => write!(f, "rate_limited"),
+            Status::Unknown => write!(f, "unknown"),
         }
     }
 }
 
 impl Status {
-    #[cfg(test)]
+    /// Number of variants; the base of [`crate::icon::status_key`].
+    pub const COUNT: u8 = 5;
+
     pub fn index(&self) -> u8 {
         match self {
             Status::Active => 0,
             Status::Pending => 1,
             Status::Idle => 2,
+            Status::RateLimited => 3,
+            Status::Unknown => 4,
         }
     }
 
@@ -35,12 +53,14 @@ impl Status {
             0 => Some(Status::Active),
             1 => Some(Status::Pending),
             2 => Some(Status::Idle),
+            3 => Some(Status::RateLimited),
+            4 => Some(Status::Unknown),
             _ => None,
         }
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "lowercase")]
 pub enum Provider {
     Claude,
@@ -56,25 +76,194 @@ impl fmt::Display for Provider {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
-#[serde(rename_all = "lowercase")]
+/// Terminal app hosting a session. Serialized as its display name
+/// (`iterm2`, `jetbrains:rustrover`); names this build doesn't know read as
+/// [`Terminal::Unknown`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(into = "String", from = "String")]
 pub enum Terminal {
     ITerm2,
     Alacritty,
+    /// The terminal tool window of a JetBrains IDE
+    JetBrains(JetBrainsIde),
+    /// The integrated terminal of the Cursor editor
+    Cursor,
     Unknown,
 }
 
+impl Terminal {
+    /// Name without the IDE product: `iterm2`, `alacritty`, `jetbrains`,
+    /// `cursor` or `unknown`.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Terminal::ITerm2 => "iterm2",
+            Terminal::Alacritty => "alacritty",
+            Terminal::JetBrains(_) => "jetbrains",
+            Terminal::Cursor => "cursor",
+            Terminal::Unknown => "unknown",
+        }
+    }
+
+    /// Every terminal this build knows, with one `JetBrains` per IDE.
+    pub fn all() -> Vec<Terminal> {
+        let mut all = vec![Terminal::ITerm2, Terminal::Alacritty];
+        all.extend(JetBrainsIde::ALL.map(Terminal::JetBrains));
+        all.extend([Terminal::Cursor, Terminal::Unknown]);
+        all
+    }
+
+    /// Parse a display name; `None` for names this build doesn't know.
+    pub fn parse(name: &str) -> Option<Terminal> {
+        match name {
+            "iterm2" => Some(Terminal::ITerm2),
+            "alacritty" => Some(Terminal::Alacritty),
+            "cursor" => Some(Terminal::Cursor),
+            "unknown" => Some(Terminal::Unknown),
+            _ => name
+                .strip_prefix("jetbrains:")
+                .and_then(JetBrainsIde::parse)
+                .map(Terminal::JetBrains),
+        }
+    }
+
+    /// The IDE whose terminal a child of the process named `name` runs in:
+    /// a JetBrains launcher, or Cursor's main or helper process
+    /// (`Cursor Helper (Plugin)`; Linux truncates names to 15 characters).
+    pub fn from_host_process(name: &str) -> Option<Terminal> {
+        if let Some(ide) = JetBrainsIde::from_process_name(name) {
+            return Some(Terminal::JetBrains(ide));
+        }
+        let exe = name.rsplit('/').next().unwrap_or(name);
+        (exe == "Cursor" || exe == "cursor" || exe.starts_with("Cursor Helper"))
+            .then_some(Terminal::Cursor)
+    }
+}
+
 impl fmt::Display for Terminal {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            Terminal::ITerm2 => write!(f, "iterm2"),
-            Terminal::Alacritty => write!(f, "alacritty"),
-            Terminal::Unknown => write!(f, "unknown"),
+            Terminal::JetBrains(ide) => write!(f, "jetbrains:{}", ide.slug()),
+            other => f.write_str(other.kind()),
         }
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl From<Terminal> for String {
+    fn from(terminal: Terminal) -> String {
+        terminal.to_string()
+    }
+}
+
+impl From<String> for Terminal {
+    fn from(name: String) -> Terminal {
+        Terminal::parse(&name).unwrap_or(Terminal::Unknown)
+    }
+}
+
+/// JetBrains IDEs whose built-in terminal can host a session.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, JsonSchema)]
+pub enum JetBrainsIde {
+    IntelliJ,
+    RustRover,
+    PyCharm,
+    WebStorm,
+    GoLand,
+    CLion,
+    PhpStorm,
+    Rider,
+    RubyMine,
+    DataGrip,
+    AndroidStudio,
+}
+
+impl JetBrainsIde {
+    pub const ALL: [JetBrainsIde; 11] = [
+        JetBrainsIde::IntelliJ,
+        JetBrainsIde::RustRover,
+        JetBrainsIde::PyCharm,
+        JetBrainsIde::WebStorm,
+        JetBrainsIde::GoLand,
+        JetBrainsIde::CLion,
+        JetBrainsIde::PhpStorm,
+        JetBrainsIde::Rider,
+        JetBrainsIde::RubyMine,
+        JetBrainsIde::DataGrip,
+        JetBrainsIde::AndroidStudio,
+    ];
+
+    /// Name after `jetbrains:` in [`Terminal`]'s display name.
+    pub fn slug(self) -> &'static str {
+        match self {
+            JetBrainsIde::IntelliJ => "intellij",
+            JetBrainsIde::RustRover => "rustrover",
+            JetBrainsIde::PyCharm => "pycharm",
+            JetBrainsIde::WebStorm => "webstorm",
+            JetBrainsIde::GoLand => "goland",
+            JetBrainsIde::CLion => "clion",
+            JetBrainsIde::PhpStorm => "phpstorm",
+            JetBrainsIde::Rider => "rider",
+            JetBrainsIde::RubyMine => "rubymine",
+            JetBrainsIde::DataGrip => "datagrip",
+            JetBrainsIde::AndroidStudio => "android-studio",
+        }
+    }
+
+    pub fn parse(slug: &str) -> Option<JetBrainsIde> {
+        Self::ALL.into_iter().find(|ide| ide.slug() == slug)
+    }
+
+    /// Product name, as passed to `open -a`.
+    pub fn app_name(self) -> &'static str {
+        match self {
+            JetBrainsIde::IntelliJ => "IntelliJ IDEA",
+            JetBrainsIde::RustRover => "RustRover",
+            JetBrainsIde::PyCharm => "PyCharm",
+            JetBrainsIde::WebStorm => "WebStorm",
+            JetBrainsIde::GoLand => "GoLand",
+            JetBrainsIde::CLion => "CLion",
+            JetBrainsIde::PhpStorm => "PhpStorm",
+            JetBrainsIde::Rider => "Rider",
+            JetBrainsIde::RubyMine => "RubyMine",
+            JetBrainsIde::DataGrip => "DataGrip",
+            JetBrainsIde::AndroidStudio => "Android Studio",
+        }
+    }
+
+    /// macOS bundle identifier prefix; Community editions add a suffix
+    /// (`com.jetbrains.intellij.ce`).
+    pub fn bundle_id_prefix(self) -> &'static str {
+        match self {
+            JetBrainsIde::IntelliJ => "com.jetbrains.intellij",
+            JetBrainsIde::RustRover => "com.jetbrains.rustrover",
+            JetBrainsIde::PyCharm => "com.jetbrains.pycharm",
+            JetBrainsIde::WebStorm => "com.jetbrains.WebStorm",
+            JetBrainsIde::GoLand => "com.jetbrains.goland",
+            JetBrainsIde::CLion => "com.jetbrains.CLion",
+            JetBrainsIde::PhpStorm => "com.jetbrains.PhpStorm",
+            JetBrainsIde::Rider => "com.jetbrains.rider",
+            JetBrainsIde::RubyMine => "com.jetbrains.rubymine",
+            JetBrainsIde::DataGrip => "com.jetbrains.datagrip",
+            JetBrainsIde::AndroidStudio => "com.google.android.studio",
+        }
+    }
+
+    /// The IDE whose launcher is named `name` (a process name or executable
+    /// path: `idea`, `/Applications/RustRover.app/Contents/MacOS/rustrover`).
+    pub fn from_process_name(name: &str) -> Option<JetBrainsIde> {
+        let exe = name.rsplit('/').next().unwrap_or(name);
+        let exe = exe.strip_suffix("64").unwrap_or(exe);
+        let exe = exe.strip_suffix(".sh").unwrap_or(exe);
+        match exe {
+            "idea" => Some(JetBrainsIde::IntelliJ),
+            "studio" => Some(JetBrainsIde::AndroidStudio),
+            _ => Self::ALL
+                .into_iter()
+                .find(|ide| ide.slug() == exe && *ide != JetBrainsIde::AndroidStudio),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub struct SessionInfo {
     pub tty: String,
     pub pid: u32,
@@ -83,20 +272,520 @@ pub struct SessionInfo {
     pub terminal: Terminal,
     pub transcript: Option<String>,
     pub status: Status,
+    /// Tool awaiting the user's decision when status is pending
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pending_tool: Option<String>,
+    /// What kind of input a pending session is waiting for
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "unknown_as_none"
+    )]
+    pub pending_reason: Option<PendingReason>,
+    /// Prompts typed while the agent was busy, not yet picked up
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub queued: u32,
+    /// Prompts the user has sent in this session
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub turns: u32,
+    /// When the session's transcript began (unix seconds)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub started_at: Option<u64>,
+    /// What the conversation is about: Claude's latest summary, else the first prompt
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    /// Set for sessions running on another machine
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub remote: Option<Remote>,
+    /// Other agent processes on the same TTY, e.g. a `claude` started from inside this one
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub nested: Vec<NestedAgent>,
+    /// Set for sessions running inside a Docker container or devcontainer
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub container: Option<Container>,
+    /// Found through its transcript alone (`discovery.transcript_first`): no
+    /// process or terminal is known, so it can't be focused or typed into
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub detached: bool,
+    /// Status rule that decided `status` (unset for remote sessions from older daemons)
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "unknown_as_none"
+    )]
+    pub rule: Option<Rule>,
+    /// CPU used by the agent process since the previous poll (percent of one core)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cpu_percent: Option<f64>,
+    /// Name of the `claude_profiles` entry whose config directory the session uses
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub profile: Option<String>,
+    /// Selected variables of the agent process's environment: `CLAUDE_CONFIG_DIR`,
+    /// `ANTHROPIC_MODEL` and `CLAUDE_BAR_*` tags
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub env: BTreeMap<String, String>,
+    /// Subagents (`Task` tool calls) still running
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub subagents: u32,
+    /// The terminal's own id for the session's tab (iTerm2's session unique
+    /// ID), which `focus` goes to directly instead of searching by TTY
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub terminal_id: Option<String>,
+}
+
+/// The check that decided a session's status.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize, JsonSchema,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum Rule {
+    /// No transcript, or it couldn't be read (assumed active)
+    NoTranscript,
+    /// Still no transcript long after the process started
+    NoTranscriptTimeout,
+    /// The user pressed Escape, ending the turn
+    Interrupted,
+    /// API calls keep failing and Claude is retrying
+    RateLimited,
+    /// The agent process is using CPU, though nothing was written lately
+    CpuBusy,
+    /// The user spoke last but the agent process is using no CPU
+    CpuQuiet,
+    /// A tool call is waiting in plan mode, where it never times out
+    PlanModePending,
+    /// A tool call has waited longer than the grace period
+    PendingTool,
+    /// A tool call has waited longer than its timeout
+    PendingTimeout,
+    /// The transcript was written within `active_secs`
+    RecentWrite,
+    /// The user spoke last and Claude is working on a reply
+    Processing,
+    /// The user spoke last but nothing was written within `idle_after_secs`
+    ProcessingTimeout,
+    /// Claude finished its turn in plan mode
+    PlanMode,
+    /// Claude finished its turn
+    TurnFinished,
+    /// The turn ended with prompts queued, which run next
+    Queued,
+    /// The main transcript is quiet while subagents work
+    Subagents,
+    /// The hooks saw a tool call start that waits for the user (a permission
+    /// prompt, a question or a plan)
+    HookPermission,
+    /// The hooks saw a tool call start and not finish yet
+    HookTool,
+    /// A Codex command is waiting for escalation approval
+    CodexEscalation,
+    /// A Codex function call has no output yet
+    CodexCall,
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Rule::NoTranscript => "no_transcript",
+            Rule::NoTranscriptTimeout => "no_transcript_timeout",
+            Rule::Interrupted => "interrupted",
+            Rule::RateLimited => "rate_limited",
+            Rule::CpuBusy => "cpu_busy",
+            Rule::CpuQuiet => "cpu_quiet",
+            Rule::PlanModePending => "plan_mode_pending",
+            Rule::PendingTool => "pending_tool",
+            Rule::PendingTimeout => "pending_timeout",
+            Rule::RecentWrite => "recent_write",
+            Rule::Processing => "processing",
+            Rule::ProcessingTimeout => "processing_timeout",
+            Rule::PlanMode => "plan_mode",
+            Rule::TurnFinished => "turn_finished",
+            Rule::Queued => "queued",
+            Rule::Subagents => "subagents",
+            Rule::HookPermission => "hook_permission",
+            Rule::HookTool => "hook_tool",
+            Rule::CodexEscalation => "codex_escalation",
+            Rule::CodexCall => "codex_call",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Why a pending session needs the user.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum PendingReason {
+    /// Claude asked a question (`AskUserQuestion`)
+    Question,
+    /// A plan is waiting to be approved (`ExitPlanMode`)
+    PlanApproval,
+    /// A tool call needs permission
+    ToolPermission {
+        tool: String,
+        /// MCP server providing the tool (`github` for `mcp__github__create_pr`)
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        server: Option<String>,
+    },
+}
+
+impl PendingReason {
+    /// Reason behind a pending call to `tool`.
+    pub fn for_tool(tool: &str) -> PendingReason {
+        match tool {
+            "AskUserQuestion" => PendingReason::Question,
+            "ExitPlanMode" => PendingReason::PlanApproval,
+            _ => PendingReason::ToolPermission {
+                tool: tool.to_string(),
+                server: crate::transcript::split_mcp_tool(tool)
+                    .map(|(server, _)| server.to_string()),
+            },
+        }
+    }
+
+    /// Short description, e.g. "Question", "Plan approval", the tool name, or
+    /// "create_pr (github)" for MCP tools.
+    pub fn label(&self) -> String {
+        match self {
+            PendingReason::Question => "Question".into(),
+            PendingReason::PlanApproval => "Plan approval".into(),
+            PendingReason::ToolPermission { tool, .. } => {
+                match crate::transcript::split_mcp_tool(tool) {
+                    Some((server, name)) => format!("{} ({})", name, server),
+                    None => tool.clone(),
+                }
+            }
+        }
+    }
+}
+
+/// An agent process sharing its TTY with the session's own process.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct NestedAgent {
+    pub pid: u32,
+    pub provider: Provider,
+}
+
+pub(crate) fn is_zero(n: &u32) -> bool {
+    *n == 0
+}
+
+fn is_false(b: &bool) -> bool {
+    !*b
+}
+
+/// Read an optional enum, taking values this build doesn't know (a rule or
+/// pending reason added later) as absent rather than failing the whole list.
+fn unknown_as_none<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: serde::de::DeserializeOwned,
+{
+    let value = Option::<serde_json::Value>::deserialize(deserializer)?;
+    Ok(value.and_then(|v| serde_json::from_value(v).ok()))
+}
+
+/// Where a remote session was collected from.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct Remote {
+    /// Host label shown in the menu
+    pub host: String,
+    /// Local TTY of the SSH connection the session runs behind, if one was found
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub local_tty: Option<String>,
+}
+
+/// The container a session runs in. Such sessions have no TTY on this machine;
+/// their `tty` is `container:<session id>` instead.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct Container {
+    /// Container name from `docker ps`, when it was matched
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// Host folder the devcontainer was opened from (its VS Code window is focused)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub local_folder: Option<String>,
 }
 
 #[cfg(test)]
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl SessionInfo {
+    /// A local Claude session in iTerm2 with nothing else known, for tests to
+    /// fill in with struct-update syntax.
+    pub(crate) fn for_test(tty: &str, cwd: &str, status: Status) -> Self {
+        SessionInfo {
+            tty: tty.into(),
+            pid: 1,
+            cwd: cwd.into(),
+            provider: Provider::Claude,
+            terminal: Terminal::ITerm2,
+            transcript: None,
+            status,
+            pending_tool: None,
+            pending_reason: None,
+            queued: 0,
+            turns: 0,
+            started_at: None,
+            title: None,
+            remote: None,
+            nested: Vec::new(),
+            container: None,
+            detached: false,
+            rule: None,
+            cpu_percent: None,
+            profile: None,
+            env: BTreeMap::new(),
+            subagents: 0,
+            terminal_id: None,
+        }
+    }
+}
+
+impl SessionInfo {
+    /// Identifies the session across polls; TTY names repeat across hosts.
+    pub fn key(&self) -> String {
+        match &self.remote {
+            Some(remote) => format!("{}:{}", remote.host, self.tty),
+            None => self.tty.clone(),
+        }
+    }
+
+    /// Local TTY whose terminal window shows this session: its own TTY, or the
+    /// SSH connection's for remote sessions.
+    pub fn focus_tty(&self) -> Option<&str> {
+        match &self.remote {
+            Some(remote) => remote.local_tty.as_deref(),
+            None if self.container.is_some() || self.detached => None,
+            None => Some(&self.tty),
+        }
+    }
+
+    /// Model from `ANTHROPIC_MODEL`, when the session was started with one.
+    pub fn model(&self) -> Option<&str> {
+        self.env
+            .get(crate::process::MODEL_ENV)
+            .map(String::as_str)
+            .filter(|m| !m.is_empty())
+    }
+
+    /// User-defined label from `CLAUDE_BAR_LABEL`, e.g. "backend".
+    pub fn user_label(&self) -> Option<&str> {
+        self.env
+            .get(crate::process::LABEL_ENV)
+            .map(String::as_str)
+            .filter(|l| !l.trim().is_empty())
+    }
+
+    /// Project name shown to the user: the last component of the session CWD.
+    pub fn project_name(&self) -> String {
+        std::path::Path::new(&self.cwd)
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default()
+    }
+}
+
+/// Schema version of `poll` JSON output ([`DisplayResponse`]).
+///
+/// Compatibility rules for it and every type it contains:
+/// - Fields may be added without a bump. New fields are optional on input
+///   (`#[serde(default)]`), so output from older builds still parses, and
+///   readers ignore fields they don't know.
+/// - Removing, renaming or retyping a field, or changing what a value means,
+///   bumps the version.
+/// - Enums may gain variants without a bump: an unknown `status` or `terminal`
+///   reads as `unknown`, an unknown `rule` or `pending_reason` as absent.
+/// - Readers refuse versions newer than they know.
+///
+/// `claude-bar schema` prints the JSON Schema. Socket replies carry the same
+/// types under the protocol's own `v`.
+pub const SESSIONS_SCHEMA_VERSION: u32 = 1;
+
+/// `poll` JSON output.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub struct DisplayResponse {
+    /// Schema version of the build that wrote it; bumped on breaking changes only
+    pub version: u32,
     pub sessions: Vec<SessionInfo>,
 }
 
+impl DisplayResponse {
+    pub fn new(sessions: Vec<SessionInfo>) -> Self {
+        DisplayResponse {
+            version: SESSIONS_SCHEMA_VERSION,
+            sessions,
+        }
+    }
+
+    /// Parse `poll` output from any claude-bar build: this object, or the bare
+    /// session array printed before it was versioned (read as version 0).
+    pub fn parse(json: &str) -> Result<DisplayResponse, String> {
+        let value: serde_json::Value = serde_json::from_str(json).map_err(|e| e.to_string())?;
+        let response = if value.is_array() {
+            DisplayResponse {
+                version: 0,
+                sessions: serde_json::from_value(value).map_err(|e| e.to_string())?,
+            }
+        } else {
+            serde_json::from_value(value).map_err(|e| e.to_string())?
+        };
+        if response.version > SESSIONS_SCHEMA_VERSION {
+            return Err(format!(
+                "schema version {} is newer than this build reads ({})",
+                response.version, SESSIONS_SCHEMA_VERSION
+            ));
+        }
+        Ok(response)
+    }
+
+    /// JSON Schema of `poll` output, printed by `claude-bar schema`.
+    pub fn json_schema() -> serde_json::Value {
+        serde_json::to_value(schemars::schema_for!(DisplayResponse)).unwrap_or_default()
+    }
+}
+
+/// Version written by this build. Readers accept older files (missing fields
+/// take defaults; files without `version` are v1) and ignore unknown fields.
+pub const STATE_VERSION: u32 = 2;
+
+/// Hook-written record tying a TTY to the Claude session running on it.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SessionState {
+    #[serde(default = "legacy_state_version")]
+    pub version: u32,
     pub session_id: String,
     pub transcript_path: String,
     #[serde(default)]
     pub cwd: String,
+    /// PID of the claude process that ran the hook
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pid: Option<u32>,
+    /// Start time of that process (unix seconds)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub process_started: Option<u64>,
+    /// Hook event that wrote the file (e.g. "SessionStart")
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hook_event: Option<String>,
+    /// How the session started, from `SessionStart` (`startup`, `resume`, `clear`, `compact`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    /// When the hook wrote the file (unix seconds)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub updated_at: Option<u64>,
+    /// Subagents started (`PreToolUse` for `Task`) and not yet stopped
+    /// (`SubagentStop`) in the current turn
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub subagents: u32,
+    /// A `PostToolUse` hook has run for this session, so `tools_in_flight` is
+    /// kept up to date and decides whether a tool call is pending
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub tool_hooks: bool,
+    /// Tool calls whose `PreToolUse` hook ran but not their `PostToolUse`, oldest first
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tools_in_flight: Vec<ToolInFlight>,
+}
+
+/// A tool call the hook saw start but not finish.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ToolInFlight {
+    pub tool: String,
+    /// `tool_use_id` of the call, when Claude Code reports it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    /// When `PreToolUse` ran (unix seconds)
+    pub since: u64,
+    /// A permission prompt for the call was announced (`Notification`)
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub awaiting_permission: bool,
+}
+
+impl ToolInFlight {
+    /// Waiting for the user: a permission prompt, or a tool that asks the user
+    /// itself (a question, a plan to approve).
+    pub fn awaits_user(&self) -> bool {
+        self.awaiting_permission
+            || !matches!(
+                PendingReason::for_tool(&self.tool),
+                PendingReason::ToolPermission { .. }
+            )
+    }
+}
+
+fn legacy_state_version() -> u32 {
+    1
+}
+
+impl Default for SessionState {
+    fn default() -> Self {
+        SessionState {
+            version: STATE_VERSION,
+            session_id: String::new(),
+            transcript_path: String::new(),
+            cwd: String::new(),
+            pid: None,
+            process_started: None,
+            hook_event: None,
+            source: None,
+            updated_at: None,
+            subagents: 0,
+            tool_hooks: false,
+            tools_in_flight: Vec::new(),
+        }
+    }
+}
+
+/// Slack when comparing process start times derived from `ps -o etime`.
+const START_TIME_TOLERANCE_SECS: u64 = 2;
+
+/// The live agent process on a TTY.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ProcessIdentity {
+    pub pid: u32,
+    /// Start time (unix seconds), if known
+    pub started_at: Option<u64>,
+}
+
+impl SessionState {
+    /// Read a state file; None if it is missing or unparsable.
+    pub fn read(path: &Path) -> Option<SessionState> {
+        serde_json::from_str(&fs::read_to_string(path).ok()?).ok()
+    }
+
+    /// Write atomically (temp file + rename) so the daemon never reads a torn file.
+    pub fn write(&self, path: &Path) -> std::io::Result<()> {
+        let json = serde_json::to_string(self)?;
+        let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+        let tmp = path.with_file_name(format!(".{}.{}.tmp", file_name, std::process::id()));
+        fs::write(&tmp, json)?;
+        fs::rename(&tmp, path).inspect_err(|_| {
+            let _ = fs::remove_file(&tmp);
+        })
+    }
+
+    /// When the file was written: `updated_at`, else the file's mtime (v1 files).
+    pub fn written_at(&self, path: &Path) -> Option<u64> {
+        self.updated_at.or_else(|| {
+            let mtime = fs::metadata(path).ok()?.modified().ok()?;
+            Some(mtime.duration_since(std::time::UNIX_EPOCH).ok()?.as_secs())
+        })
+    }
+
+    /// Whether this state was written for `live` rather than an earlier process
+    /// that used the same TTY. States without a PID (written by older hooks)
+    /// are accepted unless written (`written_at`, unix seconds) before `live` started.
+    pub fn is_for(&self, live: &ProcessIdentity, written_at: Option<u64>) -> bool {
+        let close = |a: u64, b: u64| a.abs_diff(b) <= START_TIME_TOLERANCE_SECS;
+        match self.pid {
+            Some(pid) => {
+                pid == live.pid
+                    && match (self.process_started, live.started_at) {
+                        (Some(a), Some(b)) => close(a, b),
+                        _ => true,
+                    }
+            }
+            None => match (written_at, live.started_at) {
+                (Some(written), Some(started)) => written + START_TIME_TOLERANCE_SECS >= started,
+                _ => true,
+            },
+        }
+    }
 }
 
 #[cfg(test)]
@@ -115,13 +804,9 @@ mod tests {
     #[test]
     fn test_session_info_roundtrip() {
         let info = SessionInfo {
-            tty: "/dev/ttys000".into(),
             pid: 12345,
-            cwd: "/Users/test/project".into(),
-            provider: Provider::Claude,
-            terminal: Terminal::ITerm2,
             transcript: Some("/path/to/transcript.jsonl".into()),
-            status: Status::Active,
+            ..SessionInfo::for_test("/dev/ttys000", "/Users/test/project", Status::Active)
         };
         let json = serde_json::to_string(&info).unwrap();
         let back: SessionInfo = serde_json::from_str(&json).unwrap();
@@ -130,32 +815,78 @@ mod tests {
         assert_eq!(back.status, Status::Active);
         assert_eq!(back.provider, Provider::Claude);
         assert_eq!(back.terminal, Terminal::ITerm2);
+        assert!(!json.contains("remote"));
+        assert_eq!(back.key(), "/dev/ttys000");
+        assert_eq!(back.focus_tty(), Some("/dev/ttys000"));
     }
 
     #[test]
-    fn test_display_response_roundtrip() {
-        let resp = DisplayResponse {
-            sessions: vec![
-                SessionInfo {
-                    tty: "/dev/ttys000".into(),
-                    pid: 100,
-                    cwd: "/a".into(),
-                    provider: Provider::Claude,
-                    terminal: Terminal::ITerm2,
-                    transcript: None,
-                    status: Status::Active,
-                },
-                SessionInfo {
-                    tty: "/dev/ttys001".into(),
-                    pid: 200,
-                    cwd: "/b".into(),
-                    provider: Provider::Codex,
-                    terminal: Terminal::Alacritty,
-                    transcript: Some("/t.jsonl".into()),
-                    status: Status::Idle,
-                },
-            ],
+    fn test_remote_session_key_and_focus_tty() {
+        let json = r#"{"tty":"/dev/pts/2","pid":7,"cwd":"/srv/app","provider":"claude","terminal":"iterm2","transcript":null,"status":"idle","remote":{"host":"build","local_tty":"/dev/ttys004"}}"#;
+        let info: SessionInfo = serde_json::from_str(json).unwrap();
+        assert_eq!(info.key(), "build:/dev/pts/2");
+        assert_eq!(info.focus_tty(), Some("/dev/ttys004"));
+
+        let info = SessionInfo {
+            remote: Some(Remote {
+                host: "build".into(),
+                local_tty: None,
+            }),
+            ..info
         };
+        assert_eq!(info.focus_tty(), None);
+    }
+
+    #[test]
+    fn test_pending_reason_for_tool_and_serde() {
+        assert_eq!(
+            PendingReason::for_tool("AskUserQuestion"),
+            PendingReason::Question
+        );
+        assert_eq!(
+            PendingReason::for_tool("ExitPlanMode"),
+            PendingReason::PlanApproval
+        );
+        let bash = PendingReason::for_tool("Bash");
+        assert_eq!(bash.label(), "Bash");
+        assert_eq!(PendingReason::PlanApproval.label(), "Plan approval");
+        let mcp = PendingReason::for_tool("mcp__github__create_pr");
+        assert_eq!(
+            mcp,
+            PendingReason::ToolPermission {
+                tool: "mcp__github__create_pr".into(),
+                server: Some("github".into()),
+            }
+        );
+        assert_eq!(mcp.label(), "create_pr (github)");
+
+        assert_eq!(
+            serde_json::to_string(&bash).unwrap(),
+            r#"{"kind":"tool_permission","tool":"Bash"}"#
+        );
+        assert_eq!(
+            serde_json::to_string(&PendingReason::Question).unwrap(),
+            r#"{"kind":"question"}"#
+        );
+        let back: PendingReason = serde_json::from_str(r#"{"kind":"plan_approval"}"#).unwrap();
+        assert_eq!(back, PendingReason::PlanApproval);
+    }
+
+    #[test]
+    fn test_display_response_roundtrip() {
+        let resp = DisplayResponse::new(vec![
+            SessionInfo {
+                pid: 100,
+                ..SessionInfo::for_test("/dev/ttys000", "/a", Status::Active)
+            },
+            SessionInfo {
+                pid: 200,
+                provider: Provider::Codex,
+                terminal: Terminal::Alacritty,
+                transcript: Some("/t.jsonl".into()),
+                ..SessionInfo::for_test("/dev/ttys001", "/b", Status::Idle)
+            },
+        ]);
         let json = serde_json::to_string(&resp).unwrap();
         let back: DisplayResponse = serde_json::from_str(&json).unwrap();
         assert_eq!(back.sessions.len(), 2);
@@ -163,12 +894,65 @@ mod tests {
         assert_eq!(back.sessions[1].terminal, Terminal::Alacritty);
     }
 
+    #[test]
+    fn test_schema_covers_every_field() {
+        let session = SessionInfo {
+            pid: 100,
+            transcript: Some("/t.jsonl".into()),
+            pending_tool: Some("Bash".into()),
+            pending_reason: Some(PendingReason::for_tool("Bash")),
+            queued: 1,
+            turns: 2,
+            started_at: Some(1),
+            title: Some("t".into()),
+            remote: Some(Remote {
+                host: "h".into(),
+                local_tty: None,
+            }),
+            nested: vec![NestedAgent {
+                pid: 101,
+                provider: Provider::Codex,
+            }],
+            container: Some(Container {
+                name: None,
+                local_folder: None,
+            }),
+            detached: true,
+            rule: Some(Rule::PendingTool),
+            cpu_percent: Some(1.5),
+            profile: Some("work".into()),
+            env: [("ANTHROPIC_MODEL".to_string(), "opus".to_string())].into(),
+            subagents: 1,
+            ..SessionInfo::for_test("/dev/ttys000", "/a", Status::Pending)
+        };
+        let schema = DisplayResponse::json_schema();
+        let properties = schema["$defs"]["SessionInfo"]["properties"]
+            .as_object()
+            .unwrap();
+        let value = serde_json::to_value(&session).unwrap();
+        for key in value.as_object().unwrap().keys() {
+            assert!(properties.contains_key(key), "{} missing from schema", key);
+        }
+        assert_eq!(
+            schema["required"],
+            serde_json::json!(["version", "sessions"])
+        );
+
+        // Output from before versioning still parses
+        let bare = serde_json::to_string(&vec![session]).unwrap();
+        let parsed = DisplayResponse::parse(&bare).unwrap();
+        assert_eq!((parsed.version, parsed.sessions.len()), (0, 1));
+    }
+
     #[test]
     fn test_session_state_roundtrip() {
         let state = SessionState {
             session_id: "abc-123".into(),
             transcript_path: "/path/to/transcript.jsonl".into(),
             cwd: "/some/project".into(),
+            pid: Some(4242),
+            process_started: Some(1_700_000_000),
+            ..Default::default()
         };
         let json = serde_json::to_string(&state).unwrap();
         let back: SessionState = serde_json::from_str(&json).unwrap();
@@ -183,14 +967,104 @@ mod tests {
         let state: SessionState = serde_json::from_str(json).unwrap();
         assert_eq!(state.session_id, "old");
         assert_eq!(state.cwd, "");
+        assert_eq!(state.pid, None);
+    }
+
+    #[test]
+    fn test_session_state_v1_file_parses() {
+        let json = r#"{"session_id":"old","transcript_path":"/t.jsonl","cwd":"/p"}"#;
+        let state: SessionState = serde_json::from_str(json).unwrap();
+        assert_eq!(state.version, 1);
+        assert_eq!(state.hook_event, None);
+        assert_eq!(state.updated_at, None);
+
+        // Fields from a future version are ignored
+        let json = r#"{"version":3,"session_id":"new","transcript_path":"/t.jsonl","pid":7,"extra":{"a":1}}"#;
+        let state: SessionState = serde_json::from_str(json).unwrap();
+        assert_eq!(state.version, 3);
+        assert_eq!(state.pid, Some(7));
+    }
+
+    #[test]
+    fn test_session_state_atomic_write() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let path = tmp.path().join("session-ttys000.json");
+        let state = SessionState {
+            session_id: "abc".into(),
+            transcript_path: "/t.jsonl".into(),
+            cwd: "/p".into(),
+            pid: Some(42),
+            hook_event: Some("SessionStart".into()),
+            updated_at: Some(1_700_000_000),
+            ..Default::default()
+        };
+        state.write(&path).unwrap();
+        state.write(&path).unwrap();
+
+        let back = SessionState::read(&path).unwrap();
+        assert_eq!(back.version, STATE_VERSION);
+        assert_eq!(back.hook_event.as_deref(), Some("SessionStart"));
+        assert_eq!(back.written_at(&path), Some(1_700_000_000));
+        // No temp files left behind
+        assert_eq!(std::fs::read_dir(tmp.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn test_session_state_is_for_live_process() {
+        let live = ProcessIdentity {
+            pid: 500,
+            started_at: Some(1_000),
+        };
+        let state = |pid, process_started| SessionState {
+            session_id: "s".into(),
+            transcript_path: "/t.jsonl".into(),
+            cwd: String::new(),
+            pid,
+            process_started,
+            ..Default::default()
+        };
+
+        assert!(state(Some(500), Some(1_001)).is_for(&live, None));
+        // TTY reused by a new claude process
+        assert!(!state(Some(400), Some(1_000)).is_for(&live, None));
+        // PID reused too, but the process started at a different time
+        assert!(!state(Some(500), Some(600)).is_for(&live, None));
+
+        // Legacy states: accepted unless written before the process started
+        assert!(state(None, None).is_for(&live, Some(1_500)));
+        assert!(!state(None, None).is_for(&live, Some(900)));
+        assert!(state(None, None).is_for(&live, None));
+    }
+
+    #[test]
+    fn test_session_env_model_and_label() {
+        let json = r#"{"tty":"/dev/ttys001","pid":1,"cwd":"/src/api","provider":"claude","terminal":"iterm2","transcript":null,"status":"active",
+            "env":{"ANTHROPIC_MODEL":"claude-opus-4","CLAUDE_BAR_LABEL":"backend","CLAUDE_BAR_TEAM":"infra"}}"#;
+        let session: SessionInfo = serde_json::from_str(json).unwrap();
+        assert_eq!(session.model(), Some("claude-opus-4"));
+        assert_eq!(session.user_label(), Some("backend"));
+        assert_eq!(session.env["CLAUDE_BAR_TEAM"], "infra");
+
+        let plain: SessionInfo = serde_json::from_str(
+            r#"{"tty":"/dev/ttys002","pid":2,"cwd":"/src/web","provider":"claude","terminal":"iterm2","transcript":null,"status":"idle"}"#,
+        )
+        .unwrap();
+        assert_eq!((plain.model(), plain.user_label()), (None, None));
+        assert!(serde_json::to_value(&plain).unwrap().get("env").is_none());
     }
 
     #[test]
     fn test_status_index_roundtrip() {
-        for s in [Status::Active, Status::Pending, Status::Idle] {
+        for s in [
+            Status::Active,
+            Status::Pending,
+            Status::Idle,
+            Status::RateLimited,
+            Status::Unknown,
+        ] {
             assert_eq!(Status::from_index(s.index()), Some(s));
         }
-        assert_eq!(Status::from_index(3), None);
+        assert_eq!(Status::from_index(5), None);
     }
 
     #[test]
@@ -198,6 +1072,12 @@ mod tests {
         assert_eq!(format!("{}", Status::Active), "active");
         assert_eq!(format!("{}", Status::Pending), "pending");
         assert_eq!(format!("{}", Status::Idle), "idle");
+        assert_eq!(format!("{}", Status::RateLimited), "rate_limited");
+        assert_eq!(format!("{}", Status::Unknown), "unknown");
+        assert_eq!(
+            serde_json::to_value(Status::RateLimited).unwrap(),
+            serde_json::json!("rate_limited")
+        );
     }
 
     #[test]
@@ -205,6 +1085,80 @@ mod tests {
         assert_eq!(format!("{}", Terminal::ITerm2), "iterm2");
         assert_eq!(format!("{}", Terminal::Alacritty), "alacritty");
         assert_eq!(format!("{}", Terminal::Unknown), "unknown");
+        assert_eq!(
+            format!("{}", Terminal::JetBrains(JetBrainsIde::AndroidStudio)),
+            "jetbrains:android-studio"
+        );
+    }
+
+    #[test]
+    fn test_terminal_serde() {
+        let rustrover = Terminal::JetBrains(JetBrainsIde::RustRover);
+        assert_eq!(
+            serde_json::to_string(&rustrover).unwrap(),
+            r#""jetbrains:rustrover""#
+        );
+        for terminal in [
+            Terminal::ITerm2,
+            Terminal::Alacritty,
+            Terminal::Unknown,
+            rustrover,
+        ] {
+            let json = serde_json::to_string(&terminal).unwrap();
+            assert_eq!(serde_json::from_str::<Terminal>(&json).unwrap(), terminal);
+        }
+        // Terminals and IDEs from newer builds
+        for json in [r#""kitty""#, r#""jetbrains:fleet""#] {
+            assert_eq!(
+                serde_json::from_str::<Terminal>(json).unwrap(),
+                Terminal::Unknown
+            );
+        }
+    }
+
+    #[test]
+    fn test_jetbrains_process_names() {
+        let ide = JetBrainsIde::from_process_name;
+        assert_eq!(ide("idea"), Some(JetBrainsIde::IntelliJ));
+        assert_eq!(
+            ide("/Applications/RustRover.app/Contents/MacOS/rustrover"),
+            Some(JetBrainsIde::RustRover)
+        );
+        assert_eq!(ide("pycharm.sh"), Some(JetBrainsIde::PyCharm));
+        assert_eq!(ide("webstorm64"), Some(JetBrainsIde::WebStorm));
+        assert_eq!(ide("studio"), Some(JetBrainsIde::AndroidStudio));
+        assert_eq!(ide("zsh"), None);
+        assert_eq!(ide("java"), None);
+        for ide in JetBrainsIde::ALL {
+            assert_eq!(JetBrainsIde::parse(ide.slug()), Some(ide));
+        }
+    }
+
+    #[test]
+    fn test_terminal_from_host_process() {
+        let host = Terminal::from_host_process;
+        assert_eq!(
+            host("/Applications/Cursor.app/Contents/Frameworks/Cursor Helper (Plugin).app/Contents/MacOS/Cursor Helper (Plugin)"),
+            Some(Terminal::Cursor)
+        );
+        assert_eq!(host("Cursor Helper ("), Some(Terminal::Cursor));
+        assert_eq!(host("cursor"), Some(Terminal::Cursor));
+        assert_eq!(
+            host("idea"),
+            Some(Terminal::JetBrains(JetBrainsIde::IntelliJ))
+        );
+        assert_eq!(host("Code Helper (Plugin)"), None);
+        assert_eq!(host("zsh"), None);
+        assert_eq!(Terminal::parse("cursor"), Some(Terminal::Cursor));
+    }
+
+    #[test]
+    fn test_project_name() {
+        let mut info =
+            SessionInfo::for_test("/dev/ttys000", "/Users/test/my-project", Status::Idle);
+        assert_eq!(info.project_name(), "my-project");
+        info.cwd = String::new();
+        assert_eq!(info.project_name(), "");
     }
 
     #[test]
@@ -0,0 +1,293 @@
+//! Stream Deck bridge: pushes per-key session status over WebSocket and accepts focus commands.
+
+use crate::focus;
+use crate::logging;
+use crate::render::{self, SessionRow};
+use crate::serve::Shared;
+use crate::state::SessionInfo;
+use crate::websocket::{self, Frame};
+use serde_json::{json, Value};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use tokio::runtime::Handle;
+use tokio::sync::Notify;
+
+/// How long a client gets to send its upgrade request.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Most Stream Deck connections served at once; further ones are closed. Each
+/// holds a blocking-pool thread (and a reader thread) for its lifetime.
+const MAX_CLIENTS: usize = 8;
+
+/// How often an idle connection re-sends the current frame (keeps keys fresh after reconnects).
+const HEARTBEAT: Duration = Duration::from_secs(30);
+
+/// Status frame: one entry per session, indexed in menu order (key N shows session N).
+pub fn status_frame(seq: u64, rows: &[SessionRow]) -> Value {
+    let keys: Vec<Value> = rows
+        .iter()
+        .enumerate()
+        .map(|(index, r)| {
+            json!({
+                "index": index,
+                "project": r.project,
+                "status": r.status,
+                "label": r.status_label,
+                "color": r.color,
+                "tty": r.tty,
+                "terminal": r.terminal,
+            })
+        })
+        .collect();
+    json!({ "type": "sessions", "seq": seq, "keys": keys })
+}
+
+/// A command sent by the Stream Deck plugin.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Command {
+    FocusIndex(usize),
+    FocusTty(String),
+}
+
+pub fn parse_command(text: &str) -> Result<Command, String> {
+    let v: Value = serde_json::from_str(text).map_err(|e| format!("invalid JSON: {}", e))?;
+    match v.get("cmd").and_then(|c| c.as_str()) {
+        Some("focus") => {
+            if let Some(index) = v.get("index").and_then(|i| i.as_u64()) {
+                Ok(Command::FocusIndex(index as usize))
+            } else if let Some(tty) = v.get("tty").and_then(|t| t.as_str()) {
+                Ok(Command::FocusTty(
+                    tty.trim_start_matches("/dev/").to_string(),
+                ))
+            } else {
+                Err("focus needs `index` or `tty`".into())
+            }
+        }
+        Some(other) => Err(format!("unknown cmd: {}", other)),
+        None => Err("missing `cmd`".into()),
+    }
+}
+
+fn find_target<'a>(sessions: &'a [SessionInfo], cmd: &Command) -> Option<&'a SessionInfo> {
+    match cmd {
+        Command::FocusIndex(i) => sessions.get(*i),
+        Command::FocusTty(tty) => sessions
+            .iter()
+            .find(|s| s.remote.is_none() && s.tty.trim_start_matches("/dev/") == tty),
+    }
+}
+
+fn handle_command(shared: &Shared, text: &str) -> Value {
+    let result = parse_command(text).and_then(|cmd| {
+        let (_, sessions) = shared.snapshot();
+        let session = find_target(&sessions, &cmd).ok_or("no such session")?;
+        focus::focus_session(session).map_err(|e| e.to_string())
+    });
+    match result {
+        Ok(()) => json!({"type": "focus", "ok": true}),
+        Err(e) => json!({"type": "focus", "ok": false, "error": e}),
+    }
+}
+
+/// Listen on 127.0.0.1:`port` in a task of the daemon's runtime. Clients are
+/// served on the blocking pool, since the WebSocket framing is synchronous.
+pub fn spawn_server(port: u16, shared: Arc<Shared>) -> std::io::Result<()> {
+    let listener = TcpListener::bind(("127.0.0.1", port))?;
+    listener.set_nonblocking(true)?;
+    let listener = tokio::net::TcpListener::from_std(listener)?;
+    let slots = Arc::new(tokio::sync::Semaphore::new(MAX_CLIENTS));
+    tokio::spawn(async move {
+        loop {
+            let Ok((stream, peer_addr)) = listener.accept().await else {
+                continue;
+            };
+            let Ok(slot) = Arc::clone(&slots).try_acquire_owned() else {
+                logging::warn(
+                    "streamdeck",
+                    &format!("refused {}: too many connections", peer_addr),
+                );
+                continue;
+            };
+            let Ok(stream) = stream.into_std() else {
+                continue;
+            };
+            if stream.set_nonblocking(false).is_err() {
+                continue;
+            }
+            let (shared, runtime) = (Arc::clone(&shared), Handle::current());
+            tokio::task::spawn_blocking(move || {
+                let _ = handle_client(stream, shared, runtime);
+                drop(slot);
+            });
+        }
+    });
+    Ok(())
+}
+
+fn send_frame(writer: &Mutex<TcpStream>, value: &Value) -> std::io::Result<()> {
+    let mut stream = writer.lock().unwrap();
+    websocket::write_text(&mut *stream, &value.to_string())
+}
+
+fn handle_client(
+    mut stream: TcpStream,
+    shared: Arc<Shared>,
+    runtime: Handle,
+) -> std::io::Result<()> {
+    stream.set_read_timeout(Some(HANDSHAKE_TIMEOUT))?;
+    if let Err(e) = websocket::handshake(&mut stream) {
+        if e.kind() == std::io::ErrorKind::PermissionDenied {
+            logging::warn("streamdeck", &format!("refused connection: {}", e));
+        }
+        return Err(e);
+    }
+    stream.set_read_timeout(None)?;
+    let mut reader = stream.try_clone()?;
+    let writer = Arc::new(Mutex::new(stream));
+    let closed = Arc::new(Notify::new());
+
+    // Reader: focus commands, pings, close
+    {
+        let writer = Arc::clone(&writer);
+        let closed = Arc::clone(&closed);
+        let shared = Arc::clone(&shared);
+        thread::spawn(move || {
+            loop {
+                match websocket::read_frame(&mut reader) {
+                    Ok(Frame::Text(text)) => {
+                        if send_frame(&writer, &handle_command(&shared, &text)).is_err() {
+                            break;
+                        }
+                    }
+                    Ok(Frame::Ping(payload)) => {
+                        let mut stream = writer.lock().unwrap();
+                        if websocket::write_pong(&mut *stream, &payload).is_err() {
+                            break;
+                        }
+                    }
+                    Ok(Frame::Other) => {}
+                    Ok(Frame::Close) | Err(_) => {
+                        let _ = websocket::write_close(&mut *writer.lock().unwrap());
+                        break;
+                    }
+                }
+            }
+            closed.notify_one();
+        });
+    }
+
+    // Writer: push a frame on every change (and periodically)
+    let binary = std::env::current_exe()
+        .map(|p| p.to_string_lossy().to_string())
+        .unwrap_or_else(|_| "claude-bar".into());
+    let (mut seq, mut sessions) = shared.snapshot();
+    loop {
+        let frame = status_frame(
+            seq,
+            &render::build_rows(&sessions, &binary, &shared.config().colors),
+        );
+        if send_frame(&writer, &frame).is_err() {
+            break;
+        }
+        let next = runtime.block_on(async {
+            tokio::select! {
+                changed = shared.wait_for_change(seq, HEARTBEAT) => Some(changed),
+                _ = closed.notified() => None,
+            }
+        });
+        match next {
+            Some(changed) => (seq, sessions) = changed,
+            None => break,
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::Status;
+
+    fn session(tty: &str, cwd: &str, status: Status) -> SessionInfo {
+        SessionInfo::for_test(tty, cwd, status)
+    }
+
+    #[tokio::test]
+    async fn test_connections_over_cap_are_closed() {
+        use tokio::io::AsyncReadExt;
+
+        let port = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        spawn_server(port, Arc::new(Shared::default())).unwrap();
+
+        // Idle clients that never finish the handshake hold their slots
+        let mut held = Vec::new();
+        for _ in 0..MAX_CLIENTS {
+            held.push(
+                tokio::net::TcpStream::connect(("127.0.0.1", port))
+                    .await
+                    .unwrap(),
+            );
+        }
+        let mut extra = tokio::net::TcpStream::connect(("127.0.0.1", port))
+            .await
+            .unwrap();
+        let mut buf = [0u8; 1];
+        let read = tokio::time::timeout(Duration::from_secs(5), extra.read(&mut buf)).await;
+        assert_eq!(read.unwrap().unwrap(), 0);
+    }
+
+    #[test]
+    fn test_status_frame_keys() {
+        let sessions = vec![
+            session("/dev/ttys000", "/src/api", Status::Active),
+            session("/dev/ttys001", "/src/web", Status::Pending),
+        ];
+        let frame = status_frame(
+            7,
+            &render::build_rows(&sessions, "claude-bar", &render::Colors::default()),
+        );
+        assert_eq!(frame["type"], "sessions");
+        assert_eq!(frame["seq"], 7);
+        let keys = frame["keys"].as_array().unwrap();
+        assert_eq!(keys.len(), 2);
+        assert_eq!(keys[1]["index"], 1);
+        assert_eq!(keys[1]["project"], "web");
+        assert_eq!(keys[1]["status"], "pending");
+        assert_eq!(keys[1]["color"], "#FF9F0A");
+        assert_eq!(keys[1]["tty"], "ttys001");
+    }
+
+    #[test]
+    fn test_parse_command() {
+        assert_eq!(
+            parse_command(r#"{"cmd":"focus","index":3}"#),
+            Ok(Command::FocusIndex(3))
+        );
+        assert_eq!(
+            parse_command(r#"{"cmd":"focus","tty":"/dev/ttys004"}"#),
+            Ok(Command::FocusTty("ttys004".into()))
+        );
+        assert!(parse_command(r#"{"cmd":"focus"}"#).is_err());
+        assert!(parse_command(r#"{"cmd":"reboot"}"#).is_err());
+        assert!(parse_command("nope").is_err());
+    }
+
+    #[test]
+    fn test_find_target() {
+        let sessions = vec![
+            session("/dev/ttys000", "/src/api", Status::Active),
+            session("/dev/ttys001", "/src/web", Status::Pending),
+        ];
+        let by_index = find_target(&sessions, &Command::FocusIndex(1)).unwrap();
+        assert_eq!(by_index.tty, "/dev/ttys001");
+        let by_tty = find_target(&sessions, &Command::FocusTty("ttys000".into())).unwrap();
+        assert_eq!(by_tty.cwd, "/src/api");
+        assert!(find_target(&sessions, &Command::FocusIndex(5)).is_none());
+    }
+}
@@ -0,0 +1,379 @@
+//! `claude-bar tail`: a readable view of a session's transcript.
+//!
+//! The transcript is found the same way the poller finds it (hook state file,
+//! else the newest unclaimed transcript in the project), so `tail --tty` shows
+//! exactly what the menu bar's status is based on. Claude and Codex entries are
+//! rendered as one line per message, tool call or tool result.
+
+use crate::error::ClaudeBarError;
+use crate::logging;
+use crate::transcript::ReverseLines;
+use serde_json::Value;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::time::Duration;
+
+/// Longest text shown for one message or tool result.
+const MAX_TEXT_CHARS: usize = 200;
+/// How far back the initial view looks for entries.
+const MAX_SCAN_BYTES: u64 = 4 * 1024 * 1024;
+
+/// Collapse whitespace and cut `text` to `max` characters.
+fn shorten(text: &str, max: usize) -> String {
+    let flat = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    if flat.chars().count() <= max {
+        return flat;
+    }
+    let cut: String = flat.chars().take(max - 1).collect();
+    format!("{}…", cut.trim_end())
+}
+
+/// The interesting part of a tool's input: the command, path or pattern it
+/// acts on, else the compact JSON.
+fn tool_input_summary(input: &Value) -> String {
+    for key in [
+        "command",
+        "file_path",
+        "path",
+        "pattern",
+        "url",
+        "query",
+        "description",
+    ] {
+        if let Some(value) = input.get(key).and_then(|v| v.as_str()) {
+            return shorten(value, MAX_TEXT_CHARS);
+        }
+    }
+    match input {
+        Value::Null => String::new(),
+        Value::Object(map) if map.is_empty() => String::new(),
+        other => shorten(&other.to_string(), MAX_TEXT_CHARS),
+    }
+}
+
+/// Text of a `tool_result` content, which is a string or a list of text blocks.
+fn result_text(content: &Value) -> String {
+    match content {
+        Value::String(s) => s.clone(),
+        Value::Array(blocks) => blocks
+            .iter()
+            .filter_map(|b| b.get("text").and_then(|t| t.as_str()))
+            .collect::<Vec<_>>()
+            .join(" "),
+        _ => String::new(),
+    }
+}
+
+/// `HH:MM:SS` (UTC) of an entry's timestamp, if it has one.
+fn entry_time(entry: &Value) -> Option<String> {
+    let at = entry
+        .get("timestamp")
+        .and_then(|t| t.as_str())
+        .and_then(logging::parse_timestamp)?;
+    Some(logging::format_timestamp(at)[11..19].to_string())
+}
+
+fn claude_lines(entry: &Value, out: &mut Vec<String>) {
+    let role = match entry.get("type").and_then(|t| t.as_str()) {
+        Some(role @ ("user" | "assistant")) => role,
+        _ => return,
+    };
+    if entry.get("isMeta").and_then(|m| m.as_bool()) == Some(true) {
+        return;
+    }
+    let Some(content) = entry.get("message").and_then(|m| m.get("content")) else {
+        return;
+    };
+    let blocks = match content {
+        Value::String(text) => {
+            out.push(format!("{}: {}", role, shorten(text, MAX_TEXT_CHARS)));
+            return;
+        }
+        Value::Array(blocks) => blocks,
+        _ => return,
+    };
+    for block in blocks {
+        match block.get("type").and_then(|t| t.as_str()) {
+            Some("text") => {
+                let text = block.get("text").and_then(|t| t.as_str()).unwrap_or("");
+                if !text.trim().is_empty() {
+                    out.push(format!("{}: {}", role, shorten(text, MAX_TEXT_CHARS)));
+                }
+            }
+            Some("tool_use") => {
+                let name = block.get("name").and_then(|n| n.as_str()).unwrap_or("tool");
+                let input = tool_input_summary(block.get("input").unwrap_or(&Value::Null));
+                out.push(format!("→ {} {}", name, input).trim_end().to_string());
+            }
+            Some("tool_result") => {
+                let failed = block.get("is_error").and_then(|e| e.as_bool()) == Some(true);
+                let text = result_text(block.get("content").unwrap_or(&Value::Null));
+                out.push(format!(
+                    "← {}{}",
+                    if failed { "error: " } else { "" },
+                    shorten(&text, MAX_TEXT_CHARS)
+                ));
+            }
+            _ => {}
+        }
+    }
+}
+
+fn codex_lines(payload: &Value, out: &mut Vec<String>) {
+    match payload.get("type").and_then(|t| t.as_str()) {
+        Some("message") => {
+            let role = payload
+                .get("role")
+                .and_then(|r| r.as_str())
+                .unwrap_or("assistant");
+            if role != "user" && role != "assistant" {
+                return;
+            }
+            let text = payload
+                .get("content")
+                .and_then(|c| c.as_array())
+                .map(|blocks| {
+                    blocks
+                        .iter()
+                        .filter_map(|b| b.get("text").and_then(|t| t.as_str()))
+                        .collect::<Vec<_>>()
+                        .join(" ")
+                })
+                .unwrap_or_default();
+            // Environment context and instructions are injected as user messages
+            if !text.trim().is_empty() && !text.trim_start().starts_with('<') {
+                out.push(format!("{}: {}", role, shorten(&text, MAX_TEXT_CHARS)));
+            }
+        }
+        Some("function_call") => {
+            let name = payload
+                .get("name")
+                .and_then(|n| n.as_str())
+                .unwrap_or("function_call");
+            let arguments = payload
+                .get("arguments")
+                .and_then(|a| a.as_str())
+                .and_then(|a| serde_json::from_str::<Value>(a).ok())
+                .unwrap_or(Value::Null);
+            let input = match arguments.get("command") {
+                Some(Value::Array(parts)) => shorten(
+                    &parts
+                        .iter()
+                        .filter_map(|p| p.as_str())
+                        .collect::<Vec<_>>()
+                        .join(" "),
+                    MAX_TEXT_CHARS,
+                ),
+                _ => tool_input_summary(&arguments),
+            };
+            out.push(format!("→ {} {}", name, input).trim_end().to_string());
+        }
+        Some("function_call_output") => {
+            let output = payload.get("output").and_then(|o| o.as_str()).unwrap_or("");
+            // Codex wraps shell output as {"output": ..., "metadata": ...}
+            let text = serde_json::from_str::<Value>(output)
+                .ok()
+                .and_then(|v| v.get("output").and_then(|o| o.as_str()).map(String::from))
+                .unwrap_or_else(|| output.to_string());
+            out.push(format!("← {}", shorten(&text, MAX_TEXT_CHARS)));
+        }
+        _ => {}
+    }
+}
+
+/// Readable lines for one transcript entry (none for metadata and unparsable lines).
+pub fn format_entry(line: &str) -> Vec<String> {
+    let Ok(entry) = serde_json::from_str::<Value>(line) else {
+        return Vec::new();
+    };
+    let mut out = Vec::new();
+    if entry.get("type").and_then(|t| t.as_str()) == Some("response_item") {
+        if let Some(payload) = entry.get("payload") {
+            codex_lines(payload, &mut out);
+        }
+    } else {
+        claude_lines(&entry, &mut out);
+    }
+    match entry_time(&entry) {
+        Some(time) => out.into_iter().map(|l| format!("{} {}", time, l)).collect(),
+        None => out,
+    }
+}
+
+/// The last `count` rendered lines of the transcript at `path`.
+pub fn recent_lines(path: &Path, count: usize) -> Vec<String> {
+    let Some(lines) = ReverseLines::open(path, MAX_SCAN_BYTES) else {
+        return Vec::new();
+    };
+    let mut out: Vec<String> = Vec::new();
+    for line in lines {
+        let mut rendered = format_entry(&line);
+        rendered.reverse();
+        out.extend(rendered);
+        if out.len() >= count {
+            break;
+        }
+    }
+    out.truncate(count);
+    out.reverse();
+    out
+}
+
+/// Offset just past the last complete line, so following resumes with any
+/// entry still being written.
+fn last_line_end(file: &mut File, size: u64) -> std::io::Result<u64> {
+    let start = size.saturating_sub(65536);
+    file.seek(SeekFrom::Start(start))?;
+    let mut block = Vec::new();
+    file.read_to_end(&mut block)?;
+    Ok(match block.iter().rposition(|&b| b == b'\n') {
+        Some(i) => start + i as u64 + 1,
+        None => size,
+    })
+}
+
+pub fn run_tail(tty: &str, lines: usize, follow: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let tty_short = tty.trim_start_matches("/dev/");
+    let sessions = crate::serve::poll_sessions();
+    let session = sessions
+        .iter()
+        .find(|s| s.remote.is_none() && s.tty.trim_start_matches("/dev/") == tty_short)
+        .ok_or_else(|| ClaudeBarError::NoSession(format!("No live session on {}", tty_short)))?;
+    let path = session
+        .transcript
+        .clone()
+        .filter(|p| !p.is_empty())
+        .ok_or_else(|| format!("No transcript found for the session on {}", tty_short))?;
+    eprintln!("{} ({})", session.project_name(), path);
+
+    for line in recent_lines(Path::new(&path), lines) {
+        println!("{}", line);
+    }
+    if !follow {
+        return Ok(());
+    }
+
+    let mut file = File::open(&path)?;
+    let size = file.metadata()?.len();
+    let mut pos = last_line_end(&mut file, size)?;
+    let mut partial: Vec<u8> = Vec::new();
+    loop {
+        std::thread::sleep(Duration::from_millis(500));
+        let len = file.metadata()?.len();
+        if len < pos {
+            // Truncated or replaced: start over
+            pos = 0;
+            partial.clear();
+        }
+        if len == pos {
+            continue;
+        }
+        file.seek(SeekFrom::Start(pos))?;
+        let mut chunk = Vec::new();
+        file.read_to_end(&mut chunk)?;
+        pos += chunk.len() as u64;
+        partial.extend_from_slice(&chunk);
+        // Only complete lines; the rest waits for the next read
+        let Some(end) = partial.iter().rposition(|&b| b == b'\n') else {
+            continue;
+        };
+        let complete: Vec<u8> = partial.drain(..=end).collect();
+        for line in String::from_utf8_lossy(&complete).lines() {
+            for rendered in format_entry(line) {
+                println!("{}", rendered);
+            }
+        }
+        std::io::stdout().flush()?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn test_format_claude_entries() {
+        assert_eq!(
+            format_entry(
+                r#"{"type":"user","timestamp":"2025-06-01T12:34:56.789Z","message":{"role":"user","content":"Fix the\nlogin bug"}}"#
+            ),
+            vec!["12:34:56 user: Fix the login bug"]
+        );
+        assert_eq!(
+            format_entry(
+                r#"{"type":"assistant","message":{"content":[{"type":"thinking","thinking":"hmm"},{"type":"text","text":"Looking."},{"type":"tool_use","id":"t1","name":"Bash","input":{"command":"cargo test","timeout":600}}]}}"#
+            ),
+            vec!["assistant: Looking.", "→ Bash cargo test"]
+        );
+        assert_eq!(
+            format_entry(
+                r#"{"type":"user","message":{"content":[{"type":"tool_result","tool_use_id":"t1","is_error":true,"content":[{"type":"text","text":"exit 101"}]}]}}"#
+            ),
+            vec!["← error: exit 101"]
+        );
+        assert!(
+            format_entry(r#"{"type":"user","isMeta":true,"message":{"content":"x"}}"#).is_empty()
+        );
+        assert!(format_entry(r#"{"type":"summary","summary":"Login fix"}"#).is_empty());
+        assert!(format_entry("{not json").is_empty());
+    }
+
+    #[test]
+    fn test_format_codex_entries() {
+        assert_eq!(
+            format_entry(
+                r#"{"type":"response_item","payload":{"type":"function_call","name":"shell","arguments":"{\"command\":[\"bash\",\"-lc\",\"ls\"]}","call_id":"c1"}}"#
+            ),
+            vec!["→ shell bash -lc ls"]
+        );
+        assert_eq!(
+            format_entry(
+                r#"{"type":"response_item","payload":{"type":"function_call_output","call_id":"c1","output":"{\"output\":\"README.md\\n\",\"metadata\":{}}"}}"#
+            ),
+            vec!["← README.md"]
+        );
+        assert_eq!(
+            format_entry(
+                r#"{"type":"response_item","payload":{"type":"message","role":"user","content":[{"type":"input_text","text":"<environment_context>..."}]}}"#
+            ),
+            Vec::<String>::new()
+        );
+    }
+
+    #[test]
+    fn test_shorten() {
+        assert_eq!(shorten("a  b\n c", 10), "a b c");
+        assert_eq!(shorten("abcdefghij", 5), "abcd…");
+    }
+
+    #[test]
+    fn test_recent_lines_and_last_line_end() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("t.jsonl");
+        let mut f = File::create(&path).unwrap();
+        for i in 0..5 {
+            writeln!(
+                f,
+                r#"{{"type":"user","message":{{"content":"prompt {}"}}}}"#,
+                i
+            )
+            .unwrap();
+        }
+        // An entry still being written
+        f.write_all(br#"{"type":"assistant","mess"#).unwrap();
+        drop(f);
+
+        assert_eq!(
+            recent_lines(&path, 2),
+            vec!["user: prompt 3", "user: prompt 4"]
+        );
+        assert_eq!(recent_lines(&path, 50).len(), 5);
+
+        let mut file = File::open(&path).unwrap();
+        let size = file.metadata().unwrap().len();
+        let end = last_line_end(&mut file, size).unwrap();
+        assert_eq!(size - end, br#"{"type":"assistant","mess"#.len() as u64);
+    }
+}
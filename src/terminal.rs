@@ -1,7 +1,12 @@
+use crate::permissions;
 use crate::process;
-use crate::state::Terminal;
+use crate::process::TtyAgents;
+use crate::state::{ProcessIdentity, Terminal};
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::process::Command;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
 
 const ITERM2_APPLESCRIPT: &str = r#"
 tell application "iTerm2"
@@ -10,7 +15,7 @@ tell application "iTerm2"
         tell w
             repeat with t in tabs
                 repeat with s in sessions of t
-                    set out to out & (tty of s) & linefeed
+                    set out to out & (tty of s) & tab & (id of s) & linefeed
                 end repeat
             end repeat
         end tell
@@ -19,70 +24,382 @@ tell application "iTerm2"
 end tell
 "#;
 
-/// Enumerate all iTerm2 session TTYs in tab order via AppleScript.
-pub fn enumerate_iterm2_ttys() -> Vec<String> {
-    let output = Command::new("osascript")
-        .arg("-e")
-        .arg(ITERM2_APPLESCRIPT)
-        .output()
-        .ok()
+/// Enumerate all iTerm2 session TTYs in tab order via AppleScript, with each
+/// session's unique ID by TTY.
+pub fn enumerate_iterm2_ttys() -> (Vec<String>, HashMap<String, String>) {
+    let output = process::run(Command::new("osascript").arg("-e").arg(ITERM2_APPLESCRIPT))
+        .inspect(|o| permissions::check_osascript("iTerm2", o))
         .map(|o| String::from_utf8_lossy(&o.stdout).to_string())
         .unwrap_or_default();
 
-    parse_iterm2_output(&output)
+    (
+        parse_iterm2_output(&output),
+        parse_iterm2_session_ids(&output),
+    )
 }
 
-/// Parse AppleScript output (one TTY per line) into a list of TTY paths.
+/// Parse AppleScript output (a TTY, a tab and the session ID per line) into
+/// a list of TTY paths.
 pub fn parse_iterm2_output(output: &str) -> Vec<String> {
     output
         .lines()
-        .map(|l| l.trim().to_string())
+        .map(|l| l.split('\t').next().unwrap_or("").trim().to_string())
         .filter(|l| l.starts_with("/dev/ttys"))
         .collect()
 }
 
+/// Session unique IDs from the same output, by TTY.
+pub fn parse_iterm2_session_ids(output: &str) -> HashMap<String, String> {
+    output
+        .lines()
+        .filter_map(|l| {
+            let (tty, id) = l.trim().split_once('\t')?;
+            let (tty, id) = (tty.trim(), id.trim());
+            (tty.starts_with("/dev/ttys") && !id.is_empty())
+                .then(|| (tty.to_string(), id.to_string()))
+        })
+        .collect()
+}
+
 /// Enumerate all Alacritty session TTYs via lsof.
 pub fn enumerate_alacritty_ttys() -> Vec<String> {
-    let output = Command::new("lsof")
-        .args(["-c", "alacritty"])
-        .output()
-        .ok()
+    let output = process::run(Command::new("lsof").args(["-c", "alacritty"]))
         .map(|o| String::from_utf8_lossy(&o.stdout).to_string())
         .unwrap_or_default();
 
     process::parse_lsof_ttys(&output)
 }
 
-/// Merge sessions from iTerm2 and Alacritty.
-/// iTerm2 sessions come first (preserving tab order), then Alacritty (sorted by TTY).
+/// A terminal app whose sessions are enumerated (`terminals` in the config file).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TerminalApp {
+    ITerm2,
+    Alacritty,
+}
+
+impl TerminalApp {
+    /// Every app, in the default precedence order.
+    pub const ALL: [TerminalApp; 2] = [TerminalApp::ITerm2, TerminalApp::Alacritty];
+
+    pub fn terminal(self) -> Terminal {
+        match self {
+            TerminalApp::ITerm2 => Terminal::ITerm2,
+            TerminalApp::Alacritty => Terminal::Alacritty,
+        }
+    }
+
+    /// Process name; the app isn't queried while none is running.
+    fn process_name(self) -> &'static str {
+        match self {
+            TerminalApp::ITerm2 => "iTerm2",
+            TerminalApp::Alacritty => "alacritty",
+        }
+    }
+
+    /// Whether the app lists its TTYs in a meaningful order (iTerm2's tab
+    /// order); the others are sorted by TTY.
+    fn keeps_order(self) -> bool {
+        self == TerminalApp::ITerm2
+    }
+
+    /// The app's session TTYs, and the app's own ID for each tab by TTY
+    /// where it has one.
+    fn enumerate(self) -> (Vec<String>, HashMap<String, String>) {
+        match self {
+            TerminalApp::ITerm2 => enumerate_iterm2_ttys(),
+            TerminalApp::Alacritty => (enumerate_alacritty_ttys(), HashMap::new()),
+        }
+    }
+}
+
+/// Session TTYs of each enabled terminal app, highest precedence first.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TerminalTtys {
+    pub apps: Vec<(TerminalApp, Vec<String>)>,
+    /// Tab IDs by TTY (iTerm2 session unique IDs)
+    pub tab_ids: HashMap<String, String>,
+}
+
+impl TerminalTtys {
+    /// Terminal hosting `tty`: the first app in precedence order that lists it.
+    pub fn terminal_for(&self, tty: &str) -> Terminal {
+        self.apps
+            .iter()
+            .find(|(_, ttys)| ttys.iter().any(|t| t == tty))
+            .map_or(Terminal::Unknown, |(app, _)| app.terminal())
+    }
+
+    pub fn tab_id(&self, tty: &str) -> Option<&str> {
+        self.tab_ids.get(tty).map(String::as_str)
+    }
+}
+
+/// How long an enumeration is reused while the process table is unchanged.
+pub const ENUMERATION_TTL: Duration = Duration::from_secs(10);
+
+/// Processes whose appearance or exit invalidates cached terminal enumeration.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProcessFingerprint {
+    /// PIDs of each enabled terminal app, in precedence order
+    pub apps: Vec<(TerminalApp, Vec<u32>)>,
+    pub agents: Vec<u32>,
+}
+
+impl ProcessFingerprint {
+    fn current(agent_pids: &[u32], apps: &[TerminalApp]) -> Self {
+        let sorted = |mut pids: Vec<u32>| {
+            pids.sort_unstable();
+            pids
+        };
+        ProcessFingerprint {
+            apps: apps
+                .iter()
+                .map(|app| (*app, sorted(process::find_pids_by_name(app.process_name()))))
+                .collect(),
+            agents: sorted(agent_pids.to_vec()),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CachedEnumeration {
+    pub at: Instant,
+    pub fingerprint: ProcessFingerprint,
+    pub ttys: TerminalTtys,
+}
+
+impl CachedEnumeration {
+    /// Reusable if nothing relevant started or exited and it is younger than the TTL.
+    pub fn is_fresh(&self, fingerprint: &ProcessFingerprint, now: Instant) -> bool {
+        self.fingerprint == *fingerprint && now.duration_since(self.at) < ENUMERATION_TTL
+    }
+}
+
+static ENUMERATION_CACHE: Mutex<Option<CachedEnumeration>> = Mutex::new(None);
+
+/// TTYs of the terminal `apps` (in that precedence order), skipping apps that
+/// aren't running (so no AppleScript/Automation prompt without iTerm2) and
+/// reusing the previous result while the process table is unchanged. Apps
+/// left out aren't looked for at all.
+pub fn enumerate_terminal_ttys(agent_pids: &[u32], apps: &[TerminalApp]) -> TerminalTtys {
+    let fingerprint = ProcessFingerprint::current(agent_pids, apps);
+    let mut cache = ENUMERATION_CACHE.lock().unwrap();
+    if let Some(cached) = cache.as_ref() {
+        if cached.is_fresh(&fingerprint, Instant::now()) {
+            return cached.ttys.clone();
+        }
+    }
+
+    let mut ttys = TerminalTtys::default();
+    for (app, pids) in &fingerprint.apps {
+        let (app_ttys, tab_ids) = if pids.is_empty() {
+            Default::default()
+        } else {
+            app.enumerate()
+        };
+        ttys.apps.push((*app, app_ttys));
+        ttys.tab_ids.extend(tab_ids);
+    }
+    *cache = Some(CachedEnumeration {
+        at: Instant::now(),
+        fingerprint,
+        ttys: ttys.clone(),
+    });
+    ttys
+}
+
+/// The IDE (JetBrains or Cursor) `pid` was started from, and the PID it was
+/// recognized by: the nearest ancestor that [`Terminal::from_host_process`] knows.
+pub fn find_ide_ancestor(
+    pid: u32,
+    name: impl Fn(u32) -> Option<String>,
+    parent: impl Fn(u32) -> Option<u32>,
+) -> Option<(Terminal, u32)> {
+    let mut current = parent(pid)?;
+    // Bounded in case of a ppid cycle in a racing process table
+    for _ in 0..64 {
+        if current <= 1 {
+            return None;
+        }
+        if let Some(ide) = name(current)
+            .as_deref()
+            .and_then(Terminal::from_host_process)
+        {
+            return Some((ide, current));
+        }
+        current = parent(current).filter(|&p| p != current)?;
+    }
+    None
+}
+
+/// IDE (if any) each agent process runs under. A process keeps its
+/// ancestors, so each is only looked up once.
+static IDE_HOSTS: Mutex<Option<HashMap<ProcessIdentity, Option<Terminal>>>> = Mutex::new(None);
+
+/// Give sessions no terminal app claimed the IDE they run in, if their agent
+/// was started from a JetBrains terminal tool window or Cursor's terminal.
+pub fn resolve_ide_terminals(
+    merged: &mut [(String, Terminal)],
+    agents: &HashMap<String, TtyAgents>,
+) {
+    let mut guard = IDE_HOSTS.lock().unwrap();
+    let known = guard.get_or_insert_with(HashMap::new);
+    let mut live = HashMap::new();
+    for (tty, terminal) in merged.iter_mut() {
+        let Some(group) = agents.get(tty) else {
+            continue;
+        };
+        let identity = group.primary.identity();
+        let ide = match known.get(&identity) {
+            Some(ide) => *ide,
+            None if *terminal == Terminal::Unknown => {
+                let backend = process::backend();
+                find_ide_ancestor(
+                    identity.pid,
+                    |p| backend.pid_name(p),
+                    |p| backend.parent_pid(p),
+                )
+                .map(|(ide, _)| ide)
+            }
+            None => continue,
+        };
+        live.insert(identity, ide);
+        if let (Terminal::Unknown, Some(ide)) = (*terminal, ide) {
+            *terminal = ide;
+        }
+    }
+    *known = live;
+}
+
+/// tmux formats for [`tmux_pane_clients`]; the session name goes last since it
+/// may contain spaces.
+const TMUX_PANE_FORMAT: &str = "#{pane_tty} #{session_name}";
+const TMUX_CLIENT_FORMAT: &str = "#{client_activity} #{client_tty} #{session_name}";
+
+/// A client attached to a tmux session, from `tmux list-clients`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TmuxClient {
+    /// Unix time of the client's last input
+    pub activity: u64,
+    pub tty: String,
+    pub session: String,
+}
+
+/// Parse `list-panes -a` output in [`TMUX_PANE_FORMAT`] into
+/// (pane TTY, tmux session) pairs.
+pub fn parse_tmux_panes(output: &str) -> Vec<(String, String)> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let (tty, session) = line.trim_end().split_once(' ')?;
+            Some((tty.to_string(), session.to_string()))
+        })
+        .collect()
+}
+
+/// Parse `list-clients` output in [`TMUX_CLIENT_FORMAT`].
+pub fn parse_tmux_clients(output: &str) -> Vec<TmuxClient> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let (activity, rest) = line.trim_end().split_once(' ')?;
+            let (tty, session) = rest.split_once(' ')?;
+            Some(TmuxClient {
+                activity: activity.parse().ok()?,
+                tty: tty.to_string(),
+                session: session.to_string(),
+            })
+        })
+        .collect()
+}
+
+/// TTY of the client showing each pane, by pane TTY. With several clients on
+/// one tmux session, the one used last wins.
+pub fn tmux_client_ttys(
+    panes: &[(String, String)],
+    clients: &[TmuxClient],
+) -> HashMap<String, String> {
+    panes
+        .iter()
+        .filter_map(|(pane, session)| {
+            let client = clients
+                .iter()
+                .filter(|c| c.session == *session)
+                .max_by_key(|c| c.activity)?;
+            Some((pane.clone(), client.tty.clone()))
+        })
+        .collect()
+}
+
+/// [`tmux_client_ttys`] for the running tmux server; empty without one, so
+/// tmux isn't run (nor its "no server running" recorded) when unused.
+pub fn tmux_pane_clients() -> HashMap<String, String> {
+    // The server renames itself on Linux
+    if ["tmux", "tmux: server"]
+        .iter()
+        .all(|name| process::find_pids_by_name(name).is_empty())
+    {
+        return HashMap::new();
+    }
+    let tmux = |args: &[&str]| {
+        process::run(Command::new("tmux").args(args))
+            .filter(|o| o.status.success())
+            .map(|o| String::from_utf8_lossy(&o.stdout).to_string())
+    };
+    let panes = tmux(&["list-panes", "-a", "-F", TMUX_PANE_FORMAT]);
+    let clients = tmux(&["list-clients", "-F", TMUX_CLIENT_FORMAT]);
+    match (panes, clients) {
+        (Some(panes), Some(clients)) => {
+            tmux_client_ttys(&parse_tmux_panes(&panes), &parse_tmux_clients(&clients))
+        }
+        _ => HashMap::new(),
+    }
+}
+
+/// Give sessions in tmux panes that no terminal app claimed the terminal app
+/// hosting the attached tmux client (`pane_clients`: pane TTY to client TTY),
+/// so their icon and click-to-focus name the right app.
+pub fn resolve_tmux_terminals(
+    merged: &mut [(String, Terminal)],
+    pane_clients: &HashMap<String, String>,
+    ttys: &TerminalTtys,
+) {
+    for (tty, terminal) in merged.iter_mut() {
+        if *terminal != Terminal::Unknown {
+            continue;
+        }
+        if let Some(client) = pane_clients.get(tty) {
+            *terminal = ttys.terminal_for(client);
+        }
+    }
+}
+
+/// Merge sessions from the enabled terminal apps, in precedence order: by
+/// default iTerm2 (preserving tab order), then Alacritty (sorted by TTY).
 /// Only TTYs that have a running Claude process (present in pid_by_tty) are included.
-/// If the same TTY appears in both, iTerm2 takes priority.
+/// If the same TTY appears in two apps, the earlier one takes priority.
 pub fn merge_sessions(
-    iterm2_ttys: &[String],
-    alacritty_ttys: &[String],
+    ttys: &TerminalTtys,
     pid_by_tty: &HashMap<String, u32>,
 ) -> Vec<(String, Terminal)> {
     let mut result = Vec::new();
     let mut seen = std::collections::HashSet::new();
 
-    // iTerm2 first, preserving tab order
-    for tty in iterm2_ttys {
-        if pid_by_tty.contains_key(tty) && seen.insert(tty.clone()) {
-            result.push((tty.clone(), Terminal::ITerm2));
+    for (app, app_ttys) in &ttys.apps {
+        let mut claimed: Vec<_> = app_ttys
+            .iter()
+            .filter(|tty| pid_by_tty.contains_key(*tty) && !seen.contains(*tty))
+            .cloned()
+            .collect();
+        if !app.keeps_order() {
+            claimed.sort();
+        }
+        for tty in claimed {
+            if seen.insert(tty.clone()) {
+                result.push((tty, app.terminal()));
+            }
         }
-    }
-
-    // Alacritty second, sorted by TTY
-    let mut alacritty: Vec<_> = alacritty_ttys
-        .iter()
-        .filter(|tty| pid_by_tty.contains_key(*tty) && !seen.contains(*tty))
-        .cloned()
-        .collect();
-    alacritty.sort();
-
-    for tty in alacritty {
-        seen.insert(tty.clone());
-        result.push((tty, Terminal::Alacritty));
     }
 
     // Fallback: TTYs in pid_by_tty not claimed by any terminal (e.g. tmux/zellij PTYs)
@@ -103,6 +420,18 @@ pub fn merge_sessions(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::state::JetBrainsIde;
+
+    /// Enumerated TTYs in the default precedence order.
+    fn ttys(iterm2: &[String], alacritty: &[String]) -> TerminalTtys {
+        TerminalTtys {
+            apps: vec![
+                (TerminalApp::ITerm2, iterm2.to_vec()),
+                (TerminalApp::Alacritty, alacritty.to_vec()),
+            ],
+            tab_ids: HashMap::new(),
+        }
+    }
 
     #[test]
     fn test_parse_iterm2_output() {
@@ -118,12 +447,96 @@ mod tests {
         assert_eq!(ttys, vec!["/dev/ttys000", "/dev/ttys001"]);
     }
 
+    #[test]
+    fn test_terminal_for_tty() {
+        let iterm2 = vec!["/dev/ttys001".to_string(), "/dev/ttys002".to_string()];
+        let alacritty = vec!["/dev/ttys002".to_string(), "/dev/ttys003".to_string()];
+        let default_order = ttys(&iterm2, &alacritty);
+        assert_eq!(default_order.terminal_for("/dev/ttys002"), Terminal::ITerm2);
+        assert_eq!(
+            default_order.terminal_for("/dev/ttys003"),
+            Terminal::Alacritty
+        );
+        assert_eq!(
+            default_order.terminal_for("/dev/ttys009"),
+            Terminal::Unknown
+        );
+
+        let alacritty_first = TerminalTtys {
+            apps: default_order.apps.into_iter().rev().collect(),
+            ..Default::default()
+        };
+        assert_eq!(
+            alacritty_first.terminal_for("/dev/ttys002"),
+            Terminal::Alacritty
+        );
+    }
+
+    #[test]
+    fn test_parse_iterm2_session_ids() {
+        let output = "/dev/ttys000\t8E3A51B2-0C4D-4E57-9C4B-1F2E3D4C5B6A\n/dev/ttys001\t\n";
+        assert_eq!(
+            parse_iterm2_output(output),
+            vec!["/dev/ttys000", "/dev/ttys001"]
+        );
+        assert_eq!(
+            parse_iterm2_session_ids(output),
+            HashMap::from([(
+                "/dev/ttys000".to_string(),
+                "8E3A51B2-0C4D-4E57-9C4B-1F2E3D4C5B6A".to_string()
+            )])
+        );
+    }
+
     #[test]
     fn test_parse_iterm2_output_empty() {
         assert_eq!(parse_iterm2_output(""), Vec::<String>::new());
         assert_eq!(parse_iterm2_output("\n\n"), Vec::<String>::new());
     }
 
+    #[test]
+    fn test_cached_enumeration_freshness() {
+        let fingerprint = ProcessFingerprint {
+            apps: vec![
+                (TerminalApp::ITerm2, vec![500]),
+                (TerminalApp::Alacritty, vec![]),
+            ],
+            agents: vec![100, 200],
+        };
+        let start = Instant::now();
+        let cached = CachedEnumeration {
+            at: start,
+            fingerprint: fingerprint.clone(),
+            ttys: ttys(&["/dev/ttys000".into()], &[]),
+        };
+        assert!(cached.is_fresh(&fingerprint, start + Duration::from_secs(1)));
+        assert!(!cached.is_fresh(&fingerprint, start + ENUMERATION_TTL));
+
+        // A new agent (e.g. claude started in a new tab) forces re-enumeration
+        let new_agent = ProcessFingerprint {
+            agents: vec![100, 200, 300],
+            ..fingerprint.clone()
+        };
+        assert!(!cached.is_fresh(&new_agent, start));
+
+        // So does Alacritty launching
+        let alacritty_started = ProcessFingerprint {
+            apps: vec![
+                (TerminalApp::ITerm2, vec![500]),
+                (TerminalApp::Alacritty, vec![700]),
+            ],
+            ..fingerprint.clone()
+        };
+        assert!(!cached.is_fresh(&alacritty_started, start));
+
+        // And a config change to the enabled apps or their order
+        let reordered = ProcessFingerprint {
+            apps: fingerprint.apps.iter().rev().cloned().collect(),
+            ..fingerprint
+        };
+        assert!(!cached.is_fresh(&reordered, start));
+    }
+
     #[test]
     fn test_merge_iterm2_only() {
         let iterm = vec!["/dev/ttys000".into(), "/dev/ttys001".into()];
@@ -132,7 +545,7 @@ mod tests {
         pid_by_tty.insert("/dev/ttys000".into(), 100);
         pid_by_tty.insert("/dev/ttys001".into(), 200);
 
-        let result = merge_sessions(&iterm, &alacritty, &pid_by_tty);
+        let result = merge_sessions(&ttys(&iterm, &alacritty), &pid_by_tty);
         assert_eq!(
             result,
             vec![
@@ -150,7 +563,7 @@ mod tests {
         pid_by_tty.insert("/dev/ttys001".into(), 100);
         pid_by_tty.insert("/dev/ttys003".into(), 200);
 
-        let result = merge_sessions(&iterm, &alacritty, &pid_by_tty);
+        let result = merge_sessions(&ttys(&iterm, &alacritty), &pid_by_tty);
         // Alacritty sorted by TTY
         assert_eq!(
             result,
@@ -171,7 +584,7 @@ mod tests {
         pid_by_tty.insert("/dev/ttys002".into(), 300);
         pid_by_tty.insert("/dev/ttys003".into(), 400);
 
-        let result = merge_sessions(&iterm, &alacritty, &pid_by_tty);
+        let result = merge_sessions(&ttys(&iterm, &alacritty), &pid_by_tty);
         assert_eq!(
             result,
             vec![
@@ -192,7 +605,7 @@ mod tests {
         pid_by_tty.insert("/dev/ttys000".into(), 100);
         pid_by_tty.insert("/dev/ttys001".into(), 200);
 
-        let result = merge_sessions(&iterm, &alacritty, &pid_by_tty);
+        let result = merge_sessions(&ttys(&iterm, &alacritty), &pid_by_tty);
         assert_eq!(
             result,
             vec![
@@ -208,7 +621,7 @@ mod tests {
         let alacritty = vec!["/dev/ttys002".into()];
         let pid_by_tty = HashMap::new(); // no claude processes
 
-        let result = merge_sessions(&iterm, &alacritty, &pid_by_tty);
+        let result = merge_sessions(&ttys(&iterm, &alacritty), &pid_by_tty);
         assert!(result.is_empty());
     }
 
@@ -222,7 +635,7 @@ mod tests {
         pid_by_tty.insert("/dev/ttys003".into(), 300); // zellij PTY
         pid_by_tty.insert("/dev/ttys005".into(), 500); // tmux PTY
 
-        let result = merge_sessions(&iterm, &alacritty, &pid_by_tty);
+        let result = merge_sessions(&ttys(&iterm, &alacritty), &pid_by_tty);
         assert_eq!(
             result,
             vec![
@@ -242,7 +655,7 @@ mod tests {
         pid_by_tty.insert("/dev/ttys002".into(), 200);
         pid_by_tty.insert("/dev/ttys001".into(), 100);
 
-        let result = merge_sessions(&iterm, &alacritty, &pid_by_tty);
+        let result = merge_sessions(&ttys(&iterm, &alacritty), &pid_by_tty);
         assert_eq!(
             result,
             vec![
@@ -252,6 +665,45 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_merge_configured_order() {
+        let iterm = vec!["/dev/ttys001".into(), "/dev/ttys000".into()];
+        let alacritty = vec!["/dev/ttys001".into(), "/dev/ttys002".into()];
+        let pid_by_tty: HashMap<String, u32> = [("/dev/ttys000", 100), ("/dev/ttys001", 200)]
+            .into_iter()
+            .chain([("/dev/ttys002", 300)])
+            .map(|(tty, pid)| (tty.to_string(), pid))
+            .collect();
+
+        // Alacritty first: it claims the shared TTY and comes first
+        let alacritty_first = TerminalTtys {
+            apps: ttys(&iterm, &alacritty).apps.into_iter().rev().collect(),
+            ..Default::default()
+        };
+        assert_eq!(
+            merge_sessions(&alacritty_first, &pid_by_tty),
+            vec![
+                ("/dev/ttys001".into(), Terminal::Alacritty),
+                ("/dev/ttys002".into(), Terminal::Alacritty),
+                ("/dev/ttys000".into(), Terminal::ITerm2),
+            ]
+        );
+
+        // iTerm2 disabled: its sessions are unclaimed
+        let alacritty_only = TerminalTtys {
+            apps: vec![(TerminalApp::Alacritty, alacritty.clone())],
+            ..Default::default()
+        };
+        assert_eq!(
+            merge_sessions(&alacritty_only, &pid_by_tty),
+            vec![
+                ("/dev/ttys001".into(), Terminal::Alacritty),
+                ("/dev/ttys002".into(), Terminal::Alacritty),
+                ("/dev/ttys000".into(), Terminal::Unknown),
+            ]
+        );
+    }
+
     #[test]
     fn test_merge_partial_claude() {
         // Only some TTYs have Claude running
@@ -266,7 +718,7 @@ mod tests {
         pid_by_tty.insert("/dev/ttys002".into(), 300);
         // ttys001 has no Claude
 
-        let result = merge_sessions(&iterm, &alacritty, &pid_by_tty);
+        let result = merge_sessions(&ttys(&iterm, &alacritty), &pid_by_tty);
         assert_eq!(
             result,
             vec![
@@ -275,4 +727,83 @@ mod tests {
             ]
         );
     }
+
+    #[test]
+    fn test_tmux_client_ttys() {
+        let panes = parse_tmux_panes(
+            "/dev/ttys010 work\n/dev/ttys011 work\n/dev/ttys012 side project\n/dev/ttys013 detached\n",
+        );
+        assert_eq!(panes[2], ("/dev/ttys012".into(), "side project".into()));
+        let clients = parse_tmux_clients(
+            "1700000000 /dev/ttys001 work\n1700000500 /dev/ttys002 work\n1700000100 /dev/ttys003 side project\nbad line\n",
+        );
+        assert_eq!(clients.len(), 3);
+        let by_pane = tmux_client_ttys(&panes, &clients);
+        assert_eq!(
+            by_pane,
+            HashMap::from([
+                ("/dev/ttys010".to_string(), "/dev/ttys002".to_string()),
+                ("/dev/ttys011".to_string(), "/dev/ttys002".to_string()),
+                ("/dev/ttys012".to_string(), "/dev/ttys003".to_string()),
+            ])
+        );
+
+        let mut merged = vec![
+            ("/dev/ttys000".to_string(), Terminal::ITerm2),
+            ("/dev/ttys010".to_string(), Terminal::Unknown),
+            ("/dev/ttys012".to_string(), Terminal::Unknown),
+            ("/dev/ttys013".to_string(), Terminal::Unknown),
+        ];
+        resolve_tmux_terminals(
+            &mut merged,
+            &by_pane,
+            &ttys(
+                &["/dev/ttys000".into(), "/dev/ttys002".into()],
+                &["/dev/ttys003".into()],
+            ),
+        );
+        let terminals: Vec<Terminal> = merged.into_iter().map(|(_, t)| t).collect();
+        assert_eq!(
+            terminals,
+            vec![
+                Terminal::ITerm2,
+                Terminal::ITerm2,
+                Terminal::Alacritty,
+                Terminal::Unknown
+            ]
+        );
+    }
+
+    #[test]
+    fn test_find_ide_ancestor() {
+        // claude (300) <- zsh (200) <- rustrover (100) <- launchd (1)
+        let tree: HashMap<u32, (&str, u32)> = HashMap::from([
+            (300, ("claude", 200)),
+            (200, ("zsh", 100)),
+            (
+                100,
+                ("/Applications/RustRover.app/Contents/MacOS/rustrover", 1),
+            ),
+            (400, ("claude", 401)),
+            (401, ("zsh", 402)),
+            (402, ("tmux", 1)),
+            // claude (500) <- zsh (501) <- Cursor Helper (502) <- Cursor (503)
+            (500, ("claude", 501)),
+            (501, ("zsh", 502)),
+            (502, ("Cursor Helper (Plugin)", 503)),
+            (503, ("Cursor", 1)),
+        ]);
+        let name = |p: u32| tree.get(&p).map(|(n, _)| n.to_string());
+        let parent = |p: u32| tree.get(&p).map(|(_, pp)| *pp);
+        assert_eq!(
+            find_ide_ancestor(300, name, parent),
+            Some((Terminal::JetBrains(JetBrainsIde::RustRover), 100))
+        );
+        assert_eq!(
+            find_ide_ancestor(500, name, parent),
+            Some((Terminal::Cursor, 502))
+        );
+        assert_eq!(find_ide_ancestor(400, name, parent), None);
+        assert_eq!(find_ide_ancestor(999, name, parent), None);
+    }
 }
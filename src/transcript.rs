@@ -1,120 +1,863 @@
-use crate::state::{Provider, Status};
+use crate::state::{ProcessIdentity, Provider, Rule, SessionState, Status, ToolInFlight};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
 use std::fs;
 use std::io::{BufRead, BufReader, Read, Seek, SeekFrom};
 use std::path::{Path, PathBuf};
+use std::sync::Mutex;
 use std::time::SystemTime;
 
-/// Parse the tail of a transcript JSONL file.
-/// Returns (last_role, has_pending_tool, in_plan_mode).
+/// What a Claude transcript says about its session, gathered by scanning it
+/// newest-entry-first (see [`analyze_transcript`]).
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct TranscriptSummary {
+    /// Role of the newest user/assistant entry ("user" or "assistant")
+    pub last_role: Option<String>,
+    /// True if the newest assistant tool_use has no tool_result yet
+    pub pending: bool,
+    /// Name of the unanswered tool when `pending`
+    pub pending_tool: Option<String>,
+    /// EnterPlanMode completed and no successful ExitPlanMode since
+    pub in_plan_mode: bool,
+    /// The newest entry is the user interrupting the turn (Escape)
+    pub interrupted: bool,
+    /// Failed API calls (overloaded, rate limited) logged since the newest
+    /// successful message
+    pub api_errors: u32,
+    /// Model of the newest assistant message
+    pub model: Option<String>,
+    /// Token usage reported with the newest assistant message
+    pub tokens: Option<TokenUsage>,
+    pub session_id: Option<String>,
+    /// Seconds since the transcript was last written
+    pub age_secs: Option<f64>,
+}
+
+/// Token counts from an assistant message's `usage` block.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TokenUsage {
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub cache_creation_input_tokens: u64,
+    pub cache_read_input_tokens: u64,
+}
+
+impl TranscriptSummary {
+    /// The unanswered tool when `pending` ("" if it had no name).
+    fn waiting_tool(&self) -> Option<&str> {
+        self.pending
+            .then(|| self.pending_tool.as_deref().unwrap_or_default())
+    }
+
+    /// Session status under the given thresholds.
+    pub fn status(&self, thresholds: &Thresholds) -> Status {
+        self.decide(thresholds).0
+    }
+
+    /// Session status and the rule of [`classify`] that chose it.
+    fn decide(&self, thresholds: &Thresholds) -> (Status, Rule) {
+        self.decide_with_cpu(None, thresholds)
+    }
+
+    /// [`Self::decide`] with the agent process's CPU usage (percent of one core)
+    /// since the previous poll, when known.
+    fn decide_with_cpu(&self, cpu_percent: Option<f64>, thresholds: &Thresholds) -> (Status, Rule) {
+        match self.age_secs {
+            Some(age) => classify(
+                &RuleInputs {
+                    last_role: self.last_role.as_deref(),
+                    pending_tool: self.waiting_tool(),
+                    in_plan_mode: self.in_plan_mode,
+                    interrupted: self.interrupted,
+                    api_errors: self.api_errors,
+                    cpu_percent,
+                    age,
+                    tools: None,
+                },
+                thresholds,
+            ),
+            None => (Status::Active, Rule::NoTranscript),
+        }
+    }
+}
+
+/// Tool Claude Code runs subagents with.
+pub const SUBAGENT_TOOL: &str = "Task";
+
+/// A status together with the transcript state it was decided from
+/// (`poll --verbose`).
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct StatusDecision {
+    pub status: Status,
+    pub rule: Rule,
+    pub transcript: Option<String>,
+    /// Seconds since the transcript was last written
+    pub age_secs: Option<f64>,
+    pub last_role: Option<String>,
+    /// A tool call (Claude) or function call (Codex) has no result yet
+    pub pending: bool,
+    pub pending_tool: Option<String>,
+    pub in_plan_mode: bool,
+    pub interrupted: bool,
+    #[serde(skip_serializing_if = "crate::state::is_zero")]
+    pub api_errors: u32,
+    /// CPU used by the agent process since the previous poll (percent of one core)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cpu_percent: Option<f64>,
+    /// Prompts queued behind the current turn
+    #[serde(skip_serializing_if = "crate::state::is_zero")]
+    pub queued: u32,
+    /// Seconds since the agent process started, when known
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub process_age_secs: Option<f64>,
+    /// Subagents still running
+    #[serde(skip_serializing_if = "crate::state::is_zero")]
+    pub subagents: u32,
+    /// Tool calls in flight per the hooks, when they run for the session
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tools_in_flight: Option<Vec<ToolInFlight>>,
+}
+
+impl StatusDecision {
+    fn without_transcript(transcript: Option<&str>) -> Self {
+        StatusDecision {
+            status: Status::Active,
+            rule: Rule::NoTranscript,
+            transcript: transcript.map(str::to_string),
+            age_secs: None,
+            last_role: None,
+            pending: false,
+            pending_tool: None,
+            in_plan_mode: false,
+            interrupted: false,
+            api_errors: 0,
+            cpu_percent: None,
+            queued: 0,
+            process_age_secs: None,
+            subagents: 0,
+            tools_in_flight: None,
+        }
+    }
+
+    /// Decide the status of the Claude transcript at `transcript` from its summary
+    /// and, when known, the agent process's CPU usage.
+    pub fn from_summary(
+        summary: TranscriptSummary,
+        transcript: &str,
+        cpu_percent: Option<f64>,
+        thresholds: &Thresholds,
+    ) -> Self {
+        let (status, rule) = summary.decide_with_cpu(cpu_percent, thresholds);
+        StatusDecision {
+            status,
+            rule,
+            transcript: Some(transcript.to_string()),
+            age_secs: summary.age_secs,
+            last_role: summary.last_role,
+            pending: summary.pending,
+            pending_tool: summary.pending_tool,
+            in_plan_mode: summary.in_plan_mode,
+            interrupted: summary.interrupted,
+            api_errors: summary.api_errors,
+            cpu_percent,
+            queued: 0,
+            process_age_secs: None,
+            subagents: 0,
+            tools_in_flight: None,
+        }
+    }
+
+    /// Let the tool hooks say whether a tool call is pending, when they run for
+    /// the session: a call is in flight from its `PreToolUse` to its
+    /// `PostToolUse`, so neither the grace period nor the timeouts are needed,
+    /// and a call the transcript still shows unanswered is over once its
+    /// `PostToolUse` ran. Without tool hooks (`tools` is None) nothing changes.
+    pub fn with_tool_hooks(mut self, tools: Option<&[ToolInFlight]>, t: &Thresholds) -> Self {
+        let (Some(tools), Some(age)) = (tools, self.age_secs) else {
+            return self;
+        };
+        (self.status, self.rule) = classify(
+            &RuleInputs {
+                last_role: self.last_role.as_deref(),
+                pending_tool: self
+                    .pending
+                    .then(|| self.pending_tool.as_deref().unwrap_or_default()),
+                in_plan_mode: self.in_plan_mode,
+                interrupted: self.interrupted,
+                api_errors: self.api_errors,
+                cpu_percent: self.cpu_percent,
+                age,
+                tools: Some(tools),
+            },
+            t,
+        );
+        // An interrupted call never gets its PostToolUse
+        let newest = tools.last().filter(|_| !self.interrupted);
+        self.pending = newest.is_some();
+        self.pending_tool = newest.map(|call| call.tool.clone());
+        self.tools_in_flight = Some(tools.to_vec());
+        self
+    }
+
+    /// Account for prompts typed while the agent was busy: they run as soon as
+    /// the current turn ends, so an idle session is about to be active.
+    pub fn with_queued(mut self, queued: u32) -> Self {
+        self.queued = queued;
+        if self.status == Status::Idle && queued > 0 {
+            self.status = Status::Active;
+            self.rule = Rule::Queued;
+        }
+        self
+    }
+
+    /// Account for subagents: while they work the main transcript only shows
+    /// the unanswered `Task` call, which would otherwise read as waiting for
+    /// approval and then as abandoned. `running` is the hook's count; a pending
+    /// `Task` call counts as one when the hook didn't see it.
+    pub fn with_subagents(mut self, running: u32) -> Self {
+        let task_pending = self.pending && self.pending_tool.as_deref() == Some(SUBAGENT_TOOL);
+        self.subagents = match running {
+            0 if task_pending => 1,
+            n => n,
+        };
+        let quiet = match self.rule {
+            Rule::PendingTool | Rule::PendingTimeout => task_pending,
+            Rule::ProcessingTimeout | Rule::CpuQuiet => true,
+            _ => false,
+        };
+        if self.subagents > 0 && quiet {
+            self.status = Status::Active;
+            self.rule = Rule::Subagents;
+        }
+        self
+    }
+
+    /// Tell a session that is still starting (loading the model, starting MCP
+    /// servers) from one whose transcript never showed up: past
+    /// `no_transcript_secs` after the process started, its status is unknown.
+    pub fn with_process_age(mut self, process_age_secs: Option<f64>, t: &Thresholds) -> Self {
+        self.process_age_secs = process_age_secs;
+        if self.rule == Rule::NoTranscript
+            && process_age_secs.is_some_and(|age| age >= t.no_transcript_secs)
+        {
+            self.status = Status::Unknown;
+            self.rule = Rule::NoTranscriptTimeout;
+        }
+        self
+    }
+
+    /// One-line explanation of why the rule fired, for `replay`.
+    pub fn reason(&self, t: &Thresholds) -> String {
+        let age = self.age_secs.unwrap_or_default();
+        let tool = match self.pending_tool.as_deref() {
+            Some(name) if !name.is_empty() => name,
+            _ => "a tool call",
+        };
+        match self.rule {
+            Rule::NoTranscript => format!(
+                "no readable transcript yet; sessions count as active for {:.0}s after starting",
+                t.no_transcript_secs
+            ),
+            Rule::NoTranscriptTimeout => format!(
+                "no readable transcript {:.0}s after the process started; check the hooks and projects directory",
+                self.process_age_secs.unwrap_or_default()
+            ),
+            Rule::Interrupted => "the turn was interrupted with Escape".to_string(),
+            Rule::RateLimited => format!(
+                "{} failed API calls since the last message, written {:.0}s ago (idle after {:.0}s)",
+                self.api_errors, age, t.idle_after_secs
+            ),
+            Rule::PlanModePending => format!(
+                "{} has waited {:.0}s for approval in plan mode, which never times out",
+                tool, age
+            ),
+            Rule::PendingTool => format!(
+                "{} has waited {:.0}s for approval (grace {:.0}s, timeout {:.0}s)",
+                tool,
+                age,
+                t.pending_grace_secs,
+                t.pending_timeout(tool)
+            ),
+            Rule::PendingTimeout => format!(
+                "{} has waited {:.0}s, past its {:.0}s timeout; the session looks abandoned",
+                tool,
+                age,
+                t.pending_timeout(tool)
+            ),
+            Rule::CpuBusy => format!(
+                "the agent process used {:.0}% CPU since the last poll (busy at {:.0}%)",
+                self.cpu_percent.unwrap_or_default(),
+                t.busy_cpu_percent
+            ),
+            Rule::CpuQuiet => format!(
+                "the user spoke last, {:.0}s ago, but the agent process used {:.1}% CPU since the last poll",
+                age,
+                self.cpu_percent.unwrap_or_default()
+            ),
+            Rule::RecentWrite => format!(
+                "written {:.0}s ago, within active_secs ({:.0}s)",
+                age, t.active_secs
+            ),
+            Rule::Processing => format!(
+                "the user spoke last, {:.0}s ago; Claude is still replying (idle after {:.0}s)",
+                age, t.idle_after_secs
+            ),
+            Rule::ProcessingTimeout => format!(
+                "the user spoke last but nothing was written for {:.0}s (idle_after_secs {:.0}s)",
+                age, t.idle_after_secs
+            ),
+            Rule::PlanMode => {
+                "Claude finished its turn in plan mode and waits for the user".to_string()
+            }
+            Rule::TurnFinished => format!("the last turn finished {:.0}s ago", age),
+            Rule::Queued => format!(
+                "the turn ended with {} queued prompt(s), which run next",
+                self.queued
+            ),
+            Rule::HookPermission | Rule::HookTool => {
+                let call = self.tools_in_flight.as_deref().and_then(<[_]>::last);
+                let tool = call.map_or("a tool call", |call| call.tool.as_str());
+                let since = call.map_or(0.0, |call| crate::process::secs_since(call.since));
+                if self.rule == Rule::HookPermission {
+                    format!(
+                        "{} has waited {:.0}s for the user since its PreToolUse hook",
+                        tool, since
+                    )
+                } else {
+                    format!(
+                        "{} started {:.0}s ago (PreToolUse) and hasn't finished (no PostToolUse yet)",
+                        tool, since
+                    )
+                }
+            }
+            Rule::Subagents => format!(
+                "{} subagent(s) running; the main transcript was written {:.0}s ago",
+                self.subagents, age
+            ),
+            Rule::CodexEscalation => {
+                "a Codex command is waiting for escalation approval".to_string()
+            }
+            Rule::CodexCall => "a Codex function call has no output yet".to_string(),
+        }
+    }
+}
+
+/// Analyze a Claude transcript JSONL file with the default scan bound.
+/// Returns None if the file can't be read.
+pub fn analyze_transcript(path: impl AsRef<Path>) -> Option<TranscriptSummary> {
+    analyze_transcript_with(path, Thresholds::default().max_scan_bytes)
+}
+
+/// Analyze a transcript, scanning backwards at most `max_bytes` from its end.
 ///
-/// last_role: "user" | "assistant" | None
-/// has_pending_tool: true if last assistant message has unpaired tool_use
-/// in_plan_mode: true if EnterPlanMode completed but ExitPlanMode has not
-pub fn parse_transcript_tail(path: &str) -> (Option<String>, bool, bool) {
-    if path.is_empty() {
-        return (None, false, false);
+/// Plan mode is usually settled by a marker far back, so the state found is
+/// remembered per path with the file length it held for: the next scan only
+/// searches what was appended since (a file that shrank is searched afresh).
+pub fn analyze_transcript_with(
+    path: impl AsRef<Path>,
+    max_bytes: u64,
+) -> Option<TranscriptSummary> {
+    let path = path.as_ref();
+    let lines = ReverseLines::open(path, max_bytes)?;
+    let len = lines.pos;
+    let age_secs = fs::metadata(path)
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|mtime| SystemTime::now().duration_since(mtime).ok())
+        .map(|age| age.as_secs_f64());
+    let known = PLAN_MODE_CACHE
+        .lock()
+        .unwrap()
+        .get(path)
+        .filter(|(scanned_len, _)| *scanned_len <= len)
+        .map(|&(scanned_len, in_plan_mode)| (len - scanned_len, in_plan_mode));
+    let summary = scan_entries_reverse(lines, known);
+
+    let mut cache = PLAN_MODE_CACHE.lock().unwrap();
+    if cache.len() >= MAX_PLAN_MODE_CACHE && !cache.contains_key(path) {
+        cache.clear();
+    }
+    cache.insert(path.to_path_buf(), (len, summary.in_plan_mode));
+    Some(TranscriptSummary {
+        age_secs,
+        ..summary
+    })
+}
+
+/// Plan-mode state per transcript and the length it was found at, for
+/// [`analyze_transcript_with`]; cleared past [`MAX_PLAN_MODE_CACHE`] entries.
+static PLAN_MODE_CACHE: Mutex<BTreeMap<PathBuf, (u64, bool)>> = Mutex::new(BTreeMap::new());
+const MAX_PLAN_MODE_CACHE: usize = 256;
+
+/// Block size used when reading a file backwards.
+const REVERSE_BLOCK_BYTES: u64 = 65536;
+
+/// Lines of a file from last to first, reading fixed-size blocks backwards and
+/// stopping `max_bytes` before the end. A line cut by that bound is dropped.
+pub struct ReverseLines {
+    file: fs::File,
+    /// Start of the not-yet-read region
+    pos: u64,
+    /// Lowest offset that may be read
+    stop: u64,
+    /// Bytes before the first newline of the last block read (a partial line)
+    carry: Vec<u8>,
+    /// Complete lines from the last block read, in file order
+    lines: Vec<String>,
+}
+
+impl ReverseLines {
+    pub fn open(path: &Path, max_bytes: u64) -> Option<Self> {
+        let file = fs::File::open(path).ok()?;
+        let size = file.metadata().ok()?.len();
+        Some(ReverseLines {
+            file,
+            pos: size,
+            stop: size.saturating_sub(max_bytes),
+            carry: Vec::new(),
+            lines: Vec::new(),
+        })
     }
 
-    let content = match read_tail(path, 65536) {
-        Some(c) => c,
-        None => return (None, false, false),
-    };
+    fn read_block(&mut self) -> Option<()> {
+        let len = REVERSE_BLOCK_BYTES.min(self.pos - self.stop);
+        self.pos -= len;
+        self.file.seek(SeekFrom::Start(self.pos)).ok()?;
+        let mut block = vec![0u8; len as usize];
+        self.file.read_exact(&mut block).ok()?;
+        block.append(&mut self.carry);
+
+        let mut segments = block.split(|&b| b == b'\n');
+        self.carry = segments.next().unwrap_or_default().to_vec();
+        self.lines = segments
+            .filter(|l| !l.is_empty())
+            .map(decode_utf8)
+            .collect();
+        Some(())
+    }
+}
+
+impl Iterator for ReverseLines {
+    type Item = String;
 
-    parse_transcript_content(&content)
+    fn next(&mut self) -> Option<String> {
+        loop {
+            if let Some(line) = self.lines.pop() {
+                return Some(line);
+            }
+            if self.pos == self.stop {
+                // At the start of the file the carried bytes are the first line
+                let carry = std::mem::take(&mut self.carry);
+                return (self.stop == 0 && !carry.is_empty()).then(|| decode_utf8(&carry));
+            }
+            self.read_block()?;
+        }
+    }
 }
 
-/// Read the last `max_bytes` of a file as a string.
+/// Bytes read from the end of a transcript before parsing.
+const TAIL_BYTES: u64 = 65536;
+/// Largest window `read_tail` grows to when the tail holds no complete entry.
+const MAX_TAIL_BYTES: u64 = 4 * 1024 * 1024;
+
+/// Read whole lines from the last `max_bytes` of a file.
+///
+/// Unless the window covers the whole file, the first (partial) line is dropped so
+/// every returned line starts at an entry boundary and no multi-byte character is
+/// split. If that leaves no complete entry (e.g. one huge tool result), the
+/// window doubles up to [`MAX_TAIL_BYTES`].
 fn read_tail(path: &str, max_bytes: u64) -> Option<String> {
     let mut file = fs::File::open(path).ok()?;
     let size = file.metadata().ok()?.len();
-    let chunk = size.min(max_bytes);
-    if chunk == 0 {
-        return Some(String::new());
+    let mut window = max_bytes.max(1);
+    loop {
+        let chunk = size.min(window);
+        if chunk == 0 {
+            return Some(String::new());
+        }
+        file.seek(SeekFrom::Start(size - chunk)).ok()?;
+        let mut buf = vec![0u8; chunk as usize];
+        file.read_exact(&mut buf).ok()?;
+
+        let whole_file = chunk == size;
+        let lines = if whole_file {
+            &buf[..]
+        } else {
+            match buf.iter().position(|&b| b == b'\n') {
+                Some(i) => &buf[i + 1..],
+                None => &[],
+            }
+        };
+        if whole_file || has_complete_line(lines) || window >= MAX_TAIL_BYTES {
+            return Some(decode_utf8(lines));
+        }
+        window = (window * 2).min(MAX_TAIL_BYTES);
     }
-    file.seek(SeekFrom::Start(size - chunk)).ok()?;
-    let mut buf = vec![0u8; chunk as usize];
-    file.read_exact(&mut buf).ok()?;
-    Some(String::from_utf8_lossy(&buf).to_string())
+}
+
+/// True if `bytes` holds a newline-terminated, non-blank line.
+fn has_complete_line(bytes: &[u8]) -> bool {
+    let end = match bytes.iter().rposition(|&b| b == b'\n') {
+        Some(i) => i,
+        None => return false,
+    };
+    bytes[..end].iter().any(|b| !b.is_ascii_whitespace())
+}
+
+/// Decode as UTF-8, replacing invalid sequences only if strict decoding fails.
+fn decode_utf8(bytes: &[u8]) -> String {
+    match std::str::from_utf8(bytes) {
+        Ok(s) => s.to_string(),
+        Err(_) => String::from_utf8_lossy(bytes).into_owned(),
+    }
+}
+
+/// One line of a Claude transcript. Only the fields status detection needs are
+/// deserialized; everything else (tool inputs, results, usage) is skipped.
+#[derive(Debug, Deserialize)]
+struct ClaudeEntry {
+    #[serde(rename = "type", default)]
+    kind: Role,
+    #[serde(default)]
+    message: Option<ClaudeMessage>,
+    #[serde(rename = "sessionId", default)]
+    session_id: Option<String>,
+    /// `api_error` on the system entries logged while a failed API call is retried
+    #[serde(default)]
+    subtype: Option<String>,
+    /// Set on the assistant message reporting an API call that failed for good
+    #[serde(
+        rename = "isApiErrorMessage",
+        default,
+        deserialize_with = "null_as_false"
+    )]
+    is_api_error_message: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct ClaudeMessage {
+    #[serde(default)]
+    role: Role,
+    /// None when content is a plain string rather than a block array
+    #[serde(default, deserialize_with = "content_blocks")]
+    content: Option<Vec<ContentBlock>>,
+    #[serde(default)]
+    model: Option<String>,
+    #[serde(default)]
+    usage: Option<TokenUsage>,
+}
+
+/// Entry `type` and message `role` share these values.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Role {
+    User,
+    Assistant,
+    #[default]
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Debug, Deserialize)]
+struct ContentBlock {
+    #[serde(rename = "type", default)]
+    kind: BlockKind,
+    /// Tool name (tool_use only)
+    #[serde(default)]
+    name: Option<String>,
+    /// Call id (tool_use only)
+    #[serde(default)]
+    id: Option<String>,
+    /// Id of the call answered (tool_result only)
+    #[serde(default)]
+    tool_use_id: Option<String>,
+    /// Set on failed tool_result blocks
+    #[serde(default, deserialize_with = "null_as_false")]
+    is_error: bool,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum BlockKind {
+    ToolUse,
+    ToolResult,
+    #[default]
+    #[serde(other)]
+    Other,
+}
+
+impl ClaudeEntry {
+    /// Parse a line into its role and content blocks. Returns None for unparsable
+    /// lines.
+    #[cfg(test)]
+    fn parse(line: &str) -> Option<(Role, Option<Vec<ContentBlock>>)> {
+        let entry: ClaudeEntry = serde_json::from_str(line).ok()?;
+        match entry.role() {
+            Role::Other => Some((Role::Other, None)),
+            role => Some((role, entry.message.and_then(|m| m.content))),
+        }
+    }
+
+    /// A failed API call: a retry notice or the final error message.
+    fn is_api_error(&self) -> bool {
+        self.is_api_error_message || self.subtype.as_deref() == Some("api_error")
+    }
+
+    /// Other unless the entry type and message role agree.
+    fn role(&self) -> Role {
+        match &self.message {
+            Some(m) if m.role == self.kind => self.kind,
+            _ => Role::Other,
+        }
+    }
+}
+
+/// Deserialize a flag written as `null` as unset, instead of failing the entry.
+fn null_as_false<'de, D>(deserializer: D) -> Result<bool, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    Ok(Option::<bool>::deserialize(deserializer)?.unwrap_or(false))
+}
+
+/// Deserialize message content as a block array, or None for any other shape
+/// (plain-string prompts, null).
+fn content_blocks<'de, D>(deserializer: D) -> Result<Option<Vec<ContentBlock>>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    struct BlocksVisitor;
+
+    impl<'de> serde::de::Visitor<'de> for BlocksVisitor {
+        type Value = Option<Vec<ContentBlock>>;
+
+        fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            f.write_str("message content")
+        }
+
+        fn visit_seq<A: serde::de::SeqAccess<'de>>(
+            self,
+            mut seq: A,
+        ) -> Result<Self::Value, A::Error> {
+            let mut blocks = Vec::new();
+            while let Some(block) = seq.next_element()? {
+                blocks.push(block);
+            }
+            Ok(Some(blocks))
+        }
+
+        fn visit_map<A: serde::de::MapAccess<'de>>(
+            self,
+            mut map: A,
+        ) -> Result<Self::Value, A::Error> {
+            while map
+                .next_entry::<serde::de::IgnoredAny, serde::de::IgnoredAny>()?
+                .is_some()
+            {}
+            Ok(None)
+        }
+
+        fn visit_str<E>(self, _: &str) -> Result<Self::Value, E> {
+            Ok(None)
+        }
+
+        fn visit_bool<E>(self, _: bool) -> Result<Self::Value, E> {
+            Ok(None)
+        }
+
+        fn visit_i64<E>(self, _: i64) -> Result<Self::Value, E> {
+            Ok(None)
+        }
+
+        fn visit_u64<E>(self, _: u64) -> Result<Self::Value, E> {
+            Ok(None)
+        }
+
+        fn visit_f64<E>(self, _: f64) -> Result<Self::Value, E> {
+            Ok(None)
+        }
+
+        fn visit_unit<E>(self) -> Result<Self::Value, E> {
+            Ok(None)
+        }
+    }
+
+    deserializer.deserialize_any(BlocksVisitor)
+}
+
+/// Text Claude Code writes into the transcript when the user presses Escape:
+/// `[Request interrupted by user]`, or `... for tool use]` while a tool ran or
+/// waited for approval.
+const INTERRUPT_MARKER: &str = "[Request interrupted by user";
+
+/// Whether a user entry records an interrupt. Successful tool output that merely
+/// quotes the marker (e.g. reading a transcript) doesn't count.
+fn is_interrupt(line: &str, blocks: Option<&[ContentBlock]>) -> bool {
+    line.contains(INTERRUPT_MARKER)
+        && !blocks
+            .unwrap_or_default()
+            .iter()
+            .any(|b| b.kind == BlockKind::ToolResult && !b.is_error)
 }
 
 /// Parse transcript content (JSONL lines) and determine last_role + pending + plan mode state.
 ///
 /// Returns (last_role, has_pending_tool, in_plan_mode).
 /// in_plan_mode: true if EnterPlanMode completed but ExitPlanMode has not.
+#[cfg(test)]
 pub fn parse_transcript_content(content: &str) -> (Option<String>, bool, bool) {
-    let mut last_role: Option<String> = None;
-    let mut pending = false;
-    let mut in_plan_mode = false;
-    let mut last_assistant_tool_names: Vec<String> = Vec::new();
+    let summary = scan_entries_reverse(content.lines().rev(), None);
+    (summary.last_role, summary.pending, summary.in_plan_mode)
+}
 
-    for line in content.lines() {
-        let line = line.trim();
+/// Walk transcript lines newest-first until last_role, the pending pairing,
+/// plan-mode state and the newest assistant message are all known; whatever is
+/// unresolved at the end defaults to None/false.
+///
+/// A tool_result pairs with the nearest assistant entry before it, so going
+/// backwards we remember the earliest tool_result seen since the last assistant
+/// entry and judge EnterPlanMode/ExitPlanMode once that assistant entry appears.
+///
+/// `known_plan_mode` is `(bytes, state)`: the plan-mode state as of `bytes`
+/// before the end, so the search for it stops there.
+fn scan_entries_reverse<I, S>(lines: I, known_plan_mode: Option<(u64, bool)>) -> TranscriptSummary
+where
+    I: Iterator<Item = S>,
+    S: AsRef<str>,
+{
+    let mut summary = TranscriptSummary::default();
+    let mut last_role: Option<&str> = None;
+    let mut pending: Option<bool> = None;
+    let mut in_plan_mode: Option<bool> = None;
+    let mut seen_assistant = false;
+    // is_error of the tool_result that answers the next assistant entry back
+    let mut following_result: Option<bool> = None;
+    // Calls answered by tool_results newer than the newest assistant entry
+    let mut answered: Vec<String> = Vec::new();
+    // A successful user/assistant entry ends the run of trailing API errors
+    let mut api_errors_counted = false;
+    let mut scanned: u64 = 0;
+
+    for line in lines {
+        scanned += line.as_ref().len() as u64 + 1;
+        let line = line.as_ref().trim();
         if line.is_empty() {
             continue;
         }
-        let entry: serde_json::Value = match serde_json::from_str(line) {
-            Ok(v) => v,
+        let entry: ClaudeEntry = match serde_json::from_str(line) {
+            Ok(entry) => entry,
             Err(_) => continue,
         };
+        if summary.session_id.is_none() {
+            summary.session_id = entry.session_id.clone();
+        }
+        let role = entry.role();
+        if !api_errors_counted {
+            if entry.is_api_error() {
+                summary.api_errors += 1;
+            } else if role != Role::Other {
+                api_errors_counted = true;
+            }
+        }
+        let message = match entry.message {
+            Some(m) if role != Role::Other => m,
+            _ => continue,
+        };
 
-        let entry_type = entry.get("type").and_then(|v| v.as_str()).unwrap_or("");
-        let msg = entry.get("message").unwrap_or(&serde_json::Value::Null);
-        let role = msg.get("role").and_then(|v| v.as_str()).unwrap_or("");
-        let content_arr = msg.get("content").and_then(|v| v.as_array());
-
-        if entry_type == "assistant" && role == "assistant" {
-            last_role = Some("assistant".to_string());
-            last_assistant_tool_names.clear();
-            if let Some(items) = content_arr {
-                let types: Vec<&str> = items
-                    .iter()
-                    .filter_map(|c| c.get("type").and_then(|v| v.as_str()))
-                    .collect();
-                pending = types.contains(&"tool_use");
-
-                // Track tool names for plan mode detection
-                for item in items {
-                    if item.get("type").and_then(|v| v.as_str()) == Some("tool_use") {
-                        if let Some(name) = item.get("name").and_then(|v| v.as_str()) {
-                            last_assistant_tool_names.push(name.to_string());
-                        }
+        match role {
+            Role::Assistant => {
+                last_role.get_or_insert("assistant");
+                if !seen_assistant {
+                    seen_assistant = true;
+                    summary.model = message.model;
+                    summary.tokens = message.usage;
+                }
+                let result = following_result.take();
+                if let Some(blocks) = &message.content {
+                    let tool_uses: Vec<&ContentBlock> = blocks
+                        .iter()
+                        .filter(|b| b.kind == BlockKind::ToolUse)
+                        .collect();
+                    let tool_names: Vec<&str> = tool_uses
+                        .iter()
+                        .map(|b| b.name.as_deref().unwrap_or(""))
+                        .collect();
+                    if pending.is_none() {
+                        let waiting = tool_uses
+                            .iter()
+                            .rfind(|b| b.id.as_ref().is_none_or(|id| !answered.contains(id)));
+                        pending = Some(waiting.is_some());
+                        summary.pending_tool = waiting.map(|b| b.name.clone().unwrap_or_default());
+                    }
+
+                    if let (None, Some(is_error)) = (in_plan_mode, result) {
+                        in_plan_mode = plan_mode_after(&tool_names, is_error);
                     }
                 }
             }
-        } else if entry_type == "user" && role == "user" {
-            last_role = Some("user".to_string());
-            if let Some(items) = content_arr {
-                let types: Vec<&str> = items
-                    .iter()
-                    .filter_map(|c| c.get("type").and_then(|v| v.as_str()))
-                    .collect();
-                if types.contains(&"tool_result") {
-                    pending = false;
-
-                    // Check if completed tool is plan mode related
-                    for name in &last_assistant_tool_names {
-                        match name.as_str() {
-                            "EnterPlanMode" => {
-                                in_plan_mode = true;
-                            }
-                            "ExitPlanMode" => {
-                                let is_error = items.iter().any(|c| {
-                                    c.get("is_error").and_then(|v| v.as_bool()).unwrap_or(false)
-                                });
-                                if !is_error {
-                                    in_plan_mode = false;
-                                }
+            Role::User => {
+                if last_role.is_none() && is_interrupt(line, message.content.as_deref()) {
+                    // The interrupted tool (if any) is no longer waiting on anyone
+                    summary.interrupted = true;
+                    pending = Some(false);
+                }
+                last_role.get_or_insert("user");
+                if let Some(blocks) = &message.content {
+                    let results: Vec<&ContentBlock> = blocks
+                        .iter()
+                        .filter(|b| b.kind == BlockKind::ToolResult)
+                        .collect();
+                    for result in &results {
+                        match &result.tool_use_id {
+                            Some(id) => answered.push(id.clone()),
+                            // Can't tell which call it answers: take it for all of them
+                            None => {
+                                pending.get_or_insert(false);
                             }
-                            _ => {}
                         }
                     }
-                    last_assistant_tool_names.clear();
+                    if !results.is_empty() {
+                        following_result = Some(blocks.iter().any(|b| b.is_error));
+                    }
                 }
             }
+            Role::Other => {}
+        }
+
+        // Past the known state, unless a tool_result there awaits its call
+        if in_plan_mode.is_none() && following_result.is_none() {
+            if let Some((_, state)) = known_plan_mode.filter(|(b, _)| scanned >= *b) {
+                in_plan_mode = Some(state);
+            }
+        }
+        if last_role.is_some()
+            && pending.is_some()
+            && in_plan_mode.is_some()
+            && seen_assistant
+            && api_errors_counted
+        {
+            break;
         }
     }
 
-    (last_role, pending, in_plan_mode)
+    summary.last_role = last_role.map(str::to_string);
+    summary.pending = pending.unwrap_or(false);
+    summary.in_plan_mode = in_plan_mode
+        .or(known_plan_mode.map(|(_, state)| state))
+        .unwrap_or(false);
+    summary
+}
+
+/// Plan-mode state after the tools of one assistant entry completed, or None if
+/// none of them enter or (successfully) exit plan mode.
+fn plan_mode_after(tool_names: &[&str], is_error: bool) -> Option<bool> {
+    tool_names.iter().fold(None, |state, name| match *name {
+        "EnterPlanMode" => Some(true),
+        "ExitPlanMode" if !is_error => Some(false),
+        _ => state,
+    })
 }
 
 /// Get file mtime age in seconds (how long ago it was modified).
@@ -125,64 +868,240 @@ pub fn get_mtime_age(path: &str) -> Option<f64> {
     Some(age.as_secs_f64())
 }
 
+/// Server and tool of an MCP tool name: `mcp__github__create_pr` -> (`github`, `create_pr`).
+pub fn split_mcp_tool(name: &str) -> Option<(&str, &str)> {
+    let (server, tool) = name.strip_prefix("mcp__")?.split_once("__")?;
+    if server.is_empty() || tool.is_empty() {
+        return None;
+    }
+    Some((server, tool))
+}
+
+/// Thresholds used to classify sessions (configurable, see `config.rs`).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Thresholds {
+    /// Pending tool_use younger than this is assumed auto-approved
+    pub pending_grace_secs: f64,
+    /// Transcript written within this window counts as active
+    pub active_secs: f64,
+    /// Pending/processing sessions older than this degrade to idle
+    pub idle_after_secs: f64,
+    /// How far back from the end a transcript is scanned for pending and plan-mode state
+    pub max_scan_bytes: u64,
+    /// `idle_after_secs` for pending calls to slow tools, keyed by tool name
+    /// (`mcp__github__create_pr`) or MCP server (`mcp__github`)
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub pending_timeouts: BTreeMap<String, f64>,
+    /// Failed API calls in a row after which a session counts as rate limited
+    pub rate_limit_retries: u32,
+    /// Agent CPU usage (percent of one core) that keeps a session active
+    pub busy_cpu_percent: f64,
+    /// Agent CPU usage below which a processing session may be idle
+    pub quiet_cpu_percent: f64,
+    /// Seconds after the user's message before a quiet process counts as idle
+    pub quiet_processing_secs: f64,
+    /// Seconds a process may run without a transcript before its status is unknown
+    pub no_transcript_secs: f64,
+    /// Status rules switched on (`true`) or off (`false`); unlisted rules are on
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub rules: BTreeMap<Rule, bool>,
+}
+
+impl Thresholds {
+    /// How long a call to `tool` may stay pending before the session counts as
+    /// idle: its own override, else its MCP server's, else `idle_after_secs`.
+    pub fn pending_timeout(&self, tool: &str) -> f64 {
+        let server_key = split_mcp_tool(tool).map(|(server, _)| format!("mcp__{}", server));
+        self.pending_timeouts
+            .get(tool)
+            .or_else(|| server_key.and_then(|key| self.pending_timeouts.get(&key)))
+            .copied()
+            .unwrap_or(self.idle_after_secs)
+    }
+
+    /// Whether `rule` takes part in the status pipeline.
+    pub fn rule_enabled(&self, rule: Rule) -> bool {
+        self.rules.get(&rule).copied().unwrap_or(true)
+    }
+}
+
+impl Default for Thresholds {
+    fn default() -> Self {
+        Thresholds {
+            pending_grace_secs: 3.0,
+            active_secs: 10.0,
+            idle_after_secs: 120.0,
+            max_scan_bytes: 8 * 1024 * 1024,
+            pending_timeouts: BTreeMap::new(),
+            rate_limit_retries: 2,
+            busy_cpu_percent: 25.0,
+            quiet_cpu_percent: 0.5,
+            quiet_processing_secs: 45.0,
+            no_transcript_secs: 60.0,
+            rules: BTreeMap::new(),
+        }
+    }
+}
+
 /// Determine the status of a session based on its transcript file.
-pub fn determine_status(transcript: Option<&str>) -> Status {
-    let transcript = match transcript {
+pub fn determine_status(transcript: Option<&str>, thresholds: &Thresholds) -> Status {
+    explain_status(transcript, None, thresholds).status
+}
+
+/// [`determine_status`] with the inputs and rule behind it, taking the agent's
+/// CPU usage into account when known.
+pub fn explain_status(
+    transcript: Option<&str>,
+    cpu_percent: Option<f64>,
+    thresholds: &Thresholds,
+) -> StatusDecision {
+    let path = match transcript {
         Some(t) if !t.is_empty() => t,
-        _ => return Status::Active,
+        _ => return StatusDecision::without_transcript(None),
     };
 
-    let age = match get_mtime_age(transcript) {
-        Some(a) => a,
-        None => return Status::Active,
-    };
+    match analyze_transcript_with(path, thresholds.max_scan_bytes) {
+        Some(summary) => StatusDecision::from_summary(summary, path, cpu_percent, thresholds),
+        None => StatusDecision::without_transcript(transcript),
+    }
+}
 
-    let (last_role, pending, in_plan_mode) = parse_transcript_tail(transcript);
+/// What the status rules look at.
+struct RuleInputs<'a> {
+    last_role: Option<&'a str>,
+    pending_tool: Option<&'a str>,
+    in_plan_mode: bool,
+    interrupted: bool,
+    api_errors: u32,
+    cpu_percent: Option<f64>,
+    age: f64,
+    /// Tool calls in flight per the hooks, when they run for the session
+    tools: Option<&'a [ToolInFlight]>,
+}
 
-    // Pending: tool_use waiting for user action
-    // 3s grace period filters auto-approved tools (complete in <2s)
-    // 120s timeout degrades to idle (session likely abandoned)
-    // In plan mode, no timeout (user may review plan for a long time)
-    if pending && age >= 3.0 {
-        if in_plan_mode {
-            return Status::Pending;
-        }
-        return if age < 120.0 {
-            Status::Pending
-        } else {
-            Status::Idle
-        };
+impl RuleInputs<'_> {
+    /// The pending tool once the grace period (3s) has passed, which filters
+    /// out auto-approved tools (complete in <2s). With tool hooks the hook
+    /// rules decide instead.
+    fn waiting(&self, t: &Thresholds) -> Option<&str> {
+        self.pending_tool
+            .filter(|_| self.tools.is_none() && self.age >= t.pending_grace_secs)
     }
 
-    // Recent activity -> active
-    if age < 10.0 {
-        return Status::Active;
+    /// The newest tool call in flight per the hooks.
+    fn hook_call(&self) -> Option<&ToolInFlight> {
+        self.tools?.last()
     }
+}
 
+type RuleCheck = fn(&RuleInputs, &Thresholds) -> Option<Status>;
+
+/// Claude status rules in the order they are tried; the first enabled one that
+/// returns a status decides. Each checks its own conditions, so switching one off
+/// (`thresholds.rules`) lets the next matching rule decide instead.
+const PIPELINE: &[(Rule, RuleCheck)] = &[
+    // Escape stops the turn; nothing runs until the user types again
+    (Rule::Interrupted, |i, _| {
+        i.interrupted.then_some(Status::Idle)
+    }),
+    // With tool hooks a call is known to be in flight; it waits for the user
+    // once a permission prompt shows (or when it asks the user itself)
+    (Rule::HookPermission, |i, _| {
+        i.hook_call()
+            .filter(|call| call.awaits_user())
+            .map(|_| Status::Pending)
+    }),
+    (Rule::HookTool, |i, _| i.hook_call().map(|_| Status::Active)),
+    // Claude Code retries failed API calls for minutes; the user can't help
+    (Rule::RateLimited, |i, t| {
+        (i.api_errors >= t.rate_limit_retries && i.age < t.idle_after_secs)
+            .then_some(Status::RateLimited)
+    }),
+    // The agent itself is working (in-process tools like Read/Grep, compaction)
+    // even if nothing was written for a while
+    (Rule::CpuBusy, |i, t| {
+        i.cpu_percent
+            .is_some_and(|cpu| cpu >= t.busy_cpu_percent)
+            .then_some(Status::Active)
+    }),
+    // In plan mode, no timeout (user may review plan for a long time)
+    (Rule::PlanModePending, |i, t| {
+        (i.in_plan_mode && i.waiting(t).is_some()).then_some(Status::Pending)
+    }),
+    // Pending: tool_use waiting for user action
+    (Rule::PendingTool, |i, t| {
+        i.waiting(t)
+            .filter(|tool| i.age < t.pending_timeout(tool))
+            .map(|_| Status::Pending)
+    }),
+    // Timeout (120s, or the tool's override) degrades to idle (session likely abandoned)
+    (Rule::PendingTimeout, |i, t| {
+        i.waiting(t)
+            .filter(|tool| i.age >= t.pending_timeout(tool))
+            .map(|_| Status::Idle)
+    }),
+    // Recent activity -> active
+    (Rule::RecentWrite, |i, t| {
+        (i.age < t.active_secs).then_some(Status::Active)
+    }),
+    // No writes and no CPU since the user's message: nothing is processing it
+    (Rule::CpuQuiet, |i, t| {
+        (i.last_role == Some("user")
+            && i.age >= t.quiet_processing_secs
+            && i.cpu_percent.is_some_and(|cpu| cpu < t.quiet_cpu_percent))
+        .then_some(Status::Idle)
+    }),
     // User sent message, Claude processing (API call)
-    if last_role.as_deref() == Some("user") {
-        return if age < 120.0 {
-            Status::Active
-        } else {
-            Status::Idle
-        };
-    }
-
+    (Rule::Processing, |i, t| {
+        (i.last_role == Some("user") && i.age < t.idle_after_secs).then_some(Status::Active)
+    }),
+    (Rule::ProcessingTimeout, |i, t| {
+        (i.last_role == Some("user") && i.age >= t.idle_after_secs).then_some(Status::Idle)
+    }),
     // In plan mode, show pending instead of idle
     // (Claude is waiting for user input within a planning session)
-    if in_plan_mode {
-        return Status::Pending;
-    }
-
+    (Rule::PlanMode, |i, _| {
+        i.in_plan_mode.then_some(Status::Pending)
+    }),
     // Assistant finished -> idle
-    Status::Idle
+    (Rule::TurnFinished, |_, _| Some(Status::Idle)),
+];
+
+/// Rules of the Claude status pipeline, in order.
+pub fn pipeline_rules() -> impl Iterator<Item = Rule> {
+    PIPELINE.iter().map(|(rule, _)| *rule)
+}
+
+/// Status from parsed transcript state and its age in seconds, with the rule
+/// that decided it.
+fn classify(inputs: &RuleInputs, t: &Thresholds) -> (Status, Rule) {
+    PIPELINE
+        .iter()
+        .filter(|(rule, _)| t.rule_enabled(*rule))
+        .find_map(|(rule, check)| check(inputs, t).map(|status| (status, *rule)))
+        .unwrap_or((Status::Idle, Rule::TurnFinished))
 }
 
 /// Determine status for an agent provider using provider-specific transcript semantics.
-pub fn determine_status_for(provider: Provider, transcript: Option<&str>) -> Status {
+pub fn determine_status_for(
+    provider: Provider,
+    transcript: Option<&str>,
+    thresholds: &Thresholds,
+) -> Status {
+    explain_status_for(provider, transcript, None, thresholds).status
+}
+
+/// [`determine_status_for`] with the inputs and rule behind it.
+pub fn explain_status_for(
+    provider: Provider,
+    transcript: Option<&str>,
+    cpu_percent: Option<f64>,
+    thresholds: &Thresholds,
+) -> StatusDecision {
     match provider {
-        Provider::Claude => determine_status(transcript),
-        Provider::Codex => determine_codex_status(transcript),
+        Provider::Claude => explain_status(transcript, cpu_percent, thresholds),
+        Provider::Codex => explain_codex_status(transcript, thresholds),
     }
 }
 
@@ -193,7 +1112,7 @@ pub fn parse_codex_tail(path: &str) -> (bool, bool) {
         return (false, false);
     }
 
-    let content = match read_tail(path, 65536) {
+    let content = match read_tail(path, TAIL_BYTES) {
         Some(c) => c,
         None => return (false, false),
     };
@@ -215,6 +1134,11 @@ pub fn parse_codex_content(content: &str) -> (bool, bool) {
             Err(_) => continue,
         };
 
+        if codex_turn_aborted(&entry) {
+            // Escape abandons the turn's outstanding calls
+            pending_calls.clear();
+            continue;
+        }
         if entry.get("type").and_then(|v| v.as_str()) != Some("response_item") {
             continue;
         }
@@ -248,6 +1172,286 @@ pub fn parse_codex_content(content: &str) -> (bool, bool) {
     (has_pending_call, has_pending_escalation)
 }
 
+/// Name of the tool whose `tool_use` is still unpaired at the end of a Claude
+/// transcript: the pending call [`analyze_transcript`] reports.
+pub fn parse_pending_tool(content: &str) -> Option<String> {
+    let summary = scan_entries_reverse(content.lines().rev(), None);
+    summary.pending.then_some(summary.pending_tool).flatten()
+}
+
+/// Name of the pending Codex function call, preferring escalation requests.
+pub fn parse_codex_pending_tool(content: &str) -> Option<String> {
+    // (call_id, name, is_escalated) in call order
+    let mut pending: Vec<(String, String, bool)> = Vec::new();
+
+    for line in content.lines() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let entry: serde_json::Value = match serde_json::from_str(line) {
+            Ok(v) => v,
+            Err(_) => continue,
+        };
+        if codex_turn_aborted(&entry) {
+            pending.clear();
+            continue;
+        }
+        if entry.get("type").and_then(|v| v.as_str()) != Some("response_item") {
+            continue;
+        }
+        let payload = match entry.get("payload") {
+            Some(v) => v,
+            None => continue,
+        };
+        let call_id = payload
+            .get("call_id")
+            .and_then(|v| v.as_str())
+            .unwrap_or("")
+            .to_string();
+
+        match payload.get("type").and_then(|v| v.as_str()) {
+            Some("function_call") if !call_id.is_empty() => {
+                let name = payload
+                    .get("name")
+                    .and_then(|v| v.as_str())
+                    .unwrap_or("function_call")
+                    .to_string();
+                let is_escalated = payload
+                    .get("arguments")
+                    .and_then(|v| v.as_str())
+                    .map(codex_is_escalation_request)
+                    .unwrap_or(false);
+                pending.push((call_id, name, is_escalated));
+            }
+            Some("function_call_output") => pending.retain(|(id, _, _)| *id != call_id),
+            _ => {}
+        }
+    }
+
+    pending
+        .iter()
+        .find(|(_, _, escalated)| *escalated)
+        .or_else(|| pending.first())
+        .map(|(_, name, _)| name.clone())
+}
+
+/// Number of prompts the user typed while Claude was busy that are still queued.
+///
+/// Claude Code logs its input queue as `queue-operation` entries: `enqueue`
+/// adds a prompt, `dequeue`/`remove` take one off and `popAll` empties it.
+pub fn parse_queued_messages(content: &str) -> u32 {
+    #[derive(Deserialize)]
+    struct QueueEntry {
+        #[serde(rename = "type")]
+        kind: String,
+        #[serde(default)]
+        operation: String,
+    }
+
+    let mut queued: u32 = 0;
+    for line in content.lines() {
+        let line = line.trim();
+        if !line.contains("queue-operation") {
+            continue;
+        }
+        let entry: QueueEntry = match serde_json::from_str(line) {
+            Ok(e) => e,
+            Err(_) => continue,
+        };
+        if entry.kind != "queue-operation" {
+            continue;
+        }
+        match entry.operation.as_str() {
+            "enqueue" => queued += 1,
+            "dequeue" | "remove" => queued = queued.saturating_sub(1),
+            "popAll" => queued = 0,
+            _ => {}
+        }
+    }
+    queued
+}
+
+/// Read a Claude transcript tail and count the prompts still queued.
+pub fn queued_messages_for(path: &str) -> u32 {
+    read_tail(path, TAIL_BYTES)
+        .map(|content| parse_queued_messages(&content))
+        .unwrap_or(0)
+}
+
+/// Running totals for one transcript, advanced as the file grows so each poll
+/// only reads what was appended since the last one.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TranscriptStats {
+    /// Prompts the user sent (tool results, interrupts and meta entries don't count)
+    pub turns: u32,
+    /// Timestamp of the first entry (unix seconds)
+    pub started_at: Option<u64>,
+    /// Most recent `summary` entry Claude wrote for the conversation
+    pub summary: Option<String>,
+    /// First line of the first prompt, for sessions without a summary yet
+    pub first_prompt: Option<String>,
+    /// Bytes consumed so far, always at a line boundary
+    offset: u64,
+}
+
+/// A transcript line as far as [`TranscriptStats`] cares.
+#[derive(Debug, Deserialize)]
+struct StatsEntry {
+    #[serde(rename = "type", default)]
+    kind: Role,
+    #[serde(default)]
+    timestamp: Option<String>,
+    #[serde(rename = "isMeta", default)]
+    is_meta: bool,
+    #[serde(default)]
+    message: Option<StatsMessage>,
+    /// Set on `{"type":"summary"}` entries
+    #[serde(default)]
+    summary: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct StatsMessage {
+    /// A plain string or a block array; kept raw so prompt text can be read
+    #[serde(default)]
+    content: Value,
+}
+
+/// Longest title shown before it is cut off with an ellipsis.
+const MAX_TITLE_CHARS: usize = 80;
+
+/// First non-empty line of `text`, shortened to [`MAX_TITLE_CHARS`].
+fn title_line(text: &str) -> Option<String> {
+    let line = text.lines().map(str::trim).find(|l| !l.is_empty())?;
+    if line.chars().count() <= MAX_TITLE_CHARS {
+        return Some(line.to_string());
+    }
+    let cut: String = line.chars().take(MAX_TITLE_CHARS - 1).collect();
+    Some(format!("{}…", cut.trim_end()))
+}
+
+/// Text the user typed in a prompt's content, None for tool results and
+/// command wrappers (`<command-name>`, `<local-command-stdout>`).
+fn prompt_text(content: &Value) -> Option<&str> {
+    let text = match content {
+        Value::String(text) => text.as_str(),
+        Value::Array(blocks) => blocks
+            .iter()
+            .find(|b| b["type"] == "text")
+            .and_then(|b| b["text"].as_str())?,
+        _ => return None,
+    };
+    (!text.trim_start().starts_with('<')).then_some(text)
+}
+
+impl TranscriptStats {
+    /// Read whatever was appended to `path` since the last call. A file that
+    /// shrank was rewritten and is counted again from the start.
+    pub fn advance(&mut self, path: &Path) -> std::io::Result<()> {
+        let mut file = fs::File::open(path)?;
+        let len = file.metadata()?.len();
+        if len < self.offset {
+            *self = TranscriptStats::default();
+        }
+        if len == self.offset {
+            return Ok(());
+        }
+        file.seek(SeekFrom::Start(self.offset))?;
+        let mut bytes = Vec::new();
+        file.read_to_end(&mut bytes)?;
+        // A line still being written is picked up next time
+        let complete = match bytes.iter().rposition(|&b| b == b'\n') {
+            Some(i) => i + 1,
+            None => return Ok(()),
+        };
+        for line in decode_utf8(&bytes[..complete]).lines() {
+            self.ingest(line);
+        }
+        self.offset += complete as u64;
+        Ok(())
+    }
+
+    fn ingest(&mut self, line: &str) {
+        let line = line.trim();
+        // Only user and summary entries count once the start time is known
+        if line.is_empty()
+            || (self.started_at.is_some()
+                && !line.contains("\"user\"")
+                && !line.contains("\"summary\""))
+        {
+            return;
+        }
+        let entry: StatsEntry = match serde_json::from_str(line) {
+            Ok(entry) => entry,
+            Err(_) => return,
+        };
+        if self.started_at.is_none() {
+            self.started_at = entry
+                .timestamp
+                .as_deref()
+                .and_then(crate::logging::parse_timestamp);
+        }
+        if let Some(summary) = entry.summary.as_deref().and_then(title_line) {
+            self.summary = Some(summary);
+            return;
+        }
+        let content = match entry.message {
+            Some(message) if entry.kind == Role::User && !entry.is_meta => message.content,
+            _ => return,
+        };
+        let is_tool_result = content
+            .as_array()
+            .is_some_and(|blocks| blocks.iter().any(|b| b["type"] == "tool_result"));
+        if is_tool_result || line.contains(INTERRUPT_MARKER) {
+            return;
+        }
+        self.turns += 1;
+        if self.first_prompt.is_none() {
+            self.first_prompt = prompt_text(&content).and_then(title_line);
+        }
+    }
+
+    /// What the conversation is about: the latest summary, else the first prompt.
+    pub fn title(&self) -> Option<&str> {
+        self.summary.as_deref().or(self.first_prompt.as_deref())
+    }
+}
+
+/// Transcripts tracked by [`transcript_stats`]; cleared when it grows past
+/// [`MAX_STATS_CACHE`] entries so dead sessions don't pile up.
+static STATS_CACHE: Mutex<BTreeMap<String, TranscriptStats>> = Mutex::new(BTreeMap::new());
+const MAX_STATS_CACHE: usize = 256;
+
+/// Turn count, start time and title of a transcript, reading only what was appended
+/// since the previous call for the same path.
+pub fn transcript_stats(path: &str) -> TranscriptStats {
+    let mut cache = STATS_CACHE.lock().unwrap();
+    if cache.len() >= MAX_STATS_CACHE && !cache.contains_key(path) {
+        cache.clear();
+    }
+    let stats = cache.entry(path.to_string()).or_default();
+    if stats.advance(Path::new(path)).is_err() {
+        *stats = TranscriptStats::default();
+    }
+    stats.clone()
+}
+
+/// Read a transcript tail and return the tool that is waiting on the user, if any.
+pub fn pending_tool_for(provider: Provider, path: &str) -> Option<String> {
+    let content = read_tail(path, TAIL_BYTES)?;
+    match provider {
+        Provider::Claude => parse_pending_tool(&content),
+        Provider::Codex => parse_codex_pending_tool(&content),
+    }
+}
+
+/// A Codex `turn_aborted` event, written when the user interrupts a turn.
+fn codex_turn_aborted(entry: &serde_json::Value) -> bool {
+    entry.get("type").and_then(|v| v.as_str()) == Some("event_msg")
+        && entry.pointer("/payload/type").and_then(|v| v.as_str()) == Some("turn_aborted")
+}
+
 fn codex_is_escalation_request(arguments: &str) -> bool {
     let value: serde_json::Value = match serde_json::from_str(arguments) {
         Ok(v) => v,
@@ -257,28 +1461,39 @@ fn codex_is_escalation_request(arguments: &str) -> bool {
 }
 
 /// Determine status for a Codex session file.
-pub fn determine_codex_status(transcript: Option<&str>) -> Status {
-    let transcript = match transcript {
+pub fn determine_codex_status(transcript: Option<&str>, thresholds: &Thresholds) -> Status {
+    explain_codex_status(transcript, thresholds).status
+}
+
+/// [`determine_codex_status`] with the inputs and rule behind it.
+pub fn explain_codex_status(transcript: Option<&str>, thresholds: &Thresholds) -> StatusDecision {
+    let path = match transcript {
         Some(t) if !t.is_empty() => t,
-        _ => return Status::Active,
+        _ => return StatusDecision::without_transcript(None),
     };
 
-    let age = match get_mtime_age(transcript) {
+    let age = match get_mtime_age(path) {
         Some(a) => a,
-        None => return Status::Active,
+        None => return StatusDecision::without_transcript(transcript),
     };
 
-    let (has_pending_call, has_pending_escalation) = parse_codex_tail(transcript);
-    if has_pending_escalation {
-        return Status::Pending;
-    }
-    if has_pending_call {
-        return Status::Active;
-    }
-    if age < 10.0 {
-        return Status::Active;
+    let (has_pending_call, has_pending_escalation) = parse_codex_tail(path);
+    let (status, rule) = if has_pending_escalation {
+        (Status::Pending, Rule::CodexEscalation)
+    } else if has_pending_call {
+        (Status::Active, Rule::CodexCall)
+    } else if age < thresholds.active_secs {
+        (Status::Active, Rule::RecentWrite)
+    } else {
+        (Status::Idle, Rule::TurnFinished)
+    };
+    StatusDecision {
+        status,
+        rule,
+        age_secs: Some(age),
+        pending: has_pending_call,
+        ..StatusDecision::without_transcript(transcript)
     }
-    Status::Idle
 }
 
 /// Testable version of determine_status that takes age as parameter.
@@ -289,95 +1504,110 @@ pub fn determine_status_with_age(transcript_content: Option<&str>, age: Option<f
         None => return Status::Active,
     };
 
-    let (last_role, pending, in_plan_mode) = match transcript_content {
-        Some(content) if !content.is_empty() => parse_transcript_content(content),
-        _ => (None, false, false),
+    let summary = match transcript_content {
+        Some(content) if !content.is_empty() => scan_entries_reverse(content.lines().rev(), None),
+        _ => TranscriptSummary::default(),
     };
-
-    if pending && age >= 3.0 {
-        if in_plan_mode {
-            return Status::Pending;
-        }
-        return if age < 120.0 {
-            Status::Pending
-        } else {
-            Status::Idle
-        };
-    }
-
-    if age < 10.0 {
-        return Status::Active;
-    }
-
-    if last_role.as_deref() == Some("user") {
-        return if age < 120.0 {
-            Status::Active
-        } else {
-            Status::Idle
-        };
-    }
-
-    if in_plan_mode {
-        return Status::Pending;
+    TranscriptSummary {
+        age_secs: Some(age),
+        ..summary
     }
-
-    Status::Idle
+    .status(&Thresholds::default())
 }
 
 /// Resolve the correct transcript file for a given TTY's session.
 ///
-/// 1. Use this TTY's state file if its transcript still exists.
+/// 1. Use this TTY's state file if it was written for the live process and
+///    its transcript still exists.
 /// 2. Otherwise fall back to the most-recently-modified transcript
 ///    that is NOT claimed by another active session's state file.
+///
+/// `active` maps short TTY names to their live agent processes; a state file
+/// left behind by an earlier process on a reused TTY is ignored.
+///
+/// If `project_dir` doesn't exist, the directory of a transcript recorded by the
+/// hook for this project is searched instead.
 pub fn resolve_transcript(
     tty_short: &str,
     state_dir: &Path,
     project_dir: &Path,
-    active_ttys: &std::collections::HashSet<String>,
+    active: &HashMap<String, ProcessIdentity>,
 ) -> String {
+    resolve_transcript_with_source(tty_short, state_dir, project_dir, active)
+        .map(|(path, _)| path)
+        .unwrap_or_default()
+}
+
+/// How a session's transcript was found.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum TranscriptSource {
+    /// Guessed: the newest unclaimed transcript in the project
+    Newest,
+    /// Recorded by the hook for the live process
+    Hook,
+}
+
+/// State file name for a TTY: `session-<tty>.json`, with the `/` in Linux
+/// pseudo-terminals (`pts/3`) written as `-`.
+pub fn state_file_name(tty_short: &str) -> String {
+    format!("session-{}.json", tty_short.replace('/', "-"))
+}
+
+/// [`resolve_transcript`], also telling whether the hook confirmed the result.
+pub fn resolve_transcript_with_source(
+    tty_short: &str,
+    state_dir: &Path,
+    project_dir: &Path,
+    active: &HashMap<String, ProcessIdentity>,
+) -> Option<(String, TranscriptSource)> {
     // 1) Try this TTY's state file
-    let state_file = state_dir.join(format!("session-{}.json", tty_short));
-    if state_file.is_file() {
-        if let Ok(content) = fs::read_to_string(&state_file) {
-            if let Ok(state) = serde_json::from_str::<crate::state::SessionState>(&content) {
-                if !state.transcript_path.is_empty() && Path::new(&state.transcript_path).is_file()
-                {
-                    return state.transcript_path;
-                }
-            }
+    let state_file = state_dir.join(state_file_name(tty_short));
+    if let (Some(state), Some(live)) = (SessionState::read(&state_file), active.get(tty_short)) {
+        if state.is_for(live, state.written_at(&state_file))
+            && !state.transcript_path.is_empty()
+            && Path::new(&state.transcript_path).is_file()
+        {
+            return Some((state.transcript_path, TranscriptSource::Hook));
         }
     }
 
     // 2) Collect transcripts claimed by OTHER active sessions
     let mut claimed = std::collections::HashSet::new();
+    let others: HashMap<String, &ProcessIdentity> = active
+        .iter()
+        .filter(|(tty, _)| *tty != tty_short)
+        .map(|(tty, live)| (state_file_name(tty), live))
+        .collect();
     if let Ok(entries) = fs::read_dir(state_dir) {
         for entry in entries.flatten() {
             let name = entry.file_name().to_string_lossy().to_string();
-            if !name.starts_with("session-") || !name.ends_with(".json") {
-                continue;
-            }
-            let tty = &name["session-".len()..name.len() - ".json".len()];
-            if tty == tty_short || !active_ttys.contains(tty) {
+            let Some(live) = others.get(&name) else {
                 continue;
-            }
-            if let Ok(content) = fs::read_to_string(entry.path()) {
-                if let Ok(state) = serde_json::from_str::<crate::state::SessionState>(&content) {
-                    if !state.transcript_path.is_empty()
-                        && Path::new(&state.transcript_path).is_file()
-                    {
-                        claimed.insert(state.transcript_path);
-                    }
+            };
+            let path = entry.path();
+            if let Some(state) = SessionState::read(&path) {
+                if state.is_for(live, state.written_at(&path))
+                    && !state.transcript_path.is_empty()
+                    && Path::new(&state.transcript_path).is_file()
+                {
+                    claimed.insert(state.transcript_path);
                 }
             }
         }
     }
 
+    let project_dir = if project_dir.is_dir() {
+        project_dir.to_path_buf()
+    } else {
+        recorded_project_dir(state_dir).unwrap_or_else(|| project_dir.to_path_buf())
+    };
+
     // Pick the most recent unclaimed transcript
-    let mut transcripts: Vec<_> = fs::read_dir(project_dir)
+    let mut transcripts: Vec<_> = fs::read_dir(&project_dir)
         .into_iter()
         .flatten()
         .flatten()
-        .filter(|e| e.path().extension().map_or(false, |ext| ext == "jsonl"))
+        .filter(|e| e.path().extension().is_some_and(|ext| ext == "jsonl"))
         .filter_map(|e| {
             let path = e.path().to_string_lossy().to_string();
             let mtime = e.metadata().ok()?.modified().ok()?;
@@ -386,15 +1616,62 @@ pub fn resolve_transcript(
         .collect();
 
     // Sort by mtime descending (newest first)
-    transcripts.sort_by(|a, b| b.1.cmp(&a.1));
+    transcripts.sort_by_key(|t| std::cmp::Reverse(t.1));
 
-    for (path, _) in transcripts {
-        if !claimed.contains(&path) {
-            return path;
-        }
-    }
+    transcripts
+        .into_iter()
+        .map(|(path, _)| path)
+        .find(|path| !claimed.contains(path))
+        .map(|path| (path, TranscriptSource::Newest))
+}
 
-    String::new()
+/// Claude transcripts in the project directories under `projects_dir` written
+/// within the last `max_age_secs`, newest first.
+pub fn recent_transcripts(projects_dir: &Path, max_age_secs: f64) -> Vec<String> {
+    let now = SystemTime::now();
+    let mut recent: Vec<(String, SystemTime)> = fs::read_dir(projects_dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .flat_map(|project| fs::read_dir(project.path()).into_iter().flatten().flatten())
+        .filter(|e| e.path().extension().is_some_and(|ext| ext == "jsonl"))
+        .filter_map(|e| {
+            let mtime = e.metadata().ok()?.modified().ok()?;
+            let age = now.duration_since(mtime).unwrap_or_default();
+            (age.as_secs_f64() <= max_age_secs)
+                .then(|| (e.path().to_string_lossy().to_string(), mtime))
+        })
+        .collect();
+    recent.sort_by_key(|t| std::cmp::Reverse(t.1));
+    recent.into_iter().map(|(path, _)| path).collect()
+}
+
+/// Working directory of the latest entry in a Claude transcript.
+pub fn transcript_cwd(path: &str) -> Option<String> {
+    parse_transcript_cwd(&read_tail(path, TAIL_BYTES)?)
+}
+
+pub fn parse_transcript_cwd(content: &str) -> Option<String> {
+    content.lines().rev().find_map(|line| {
+        let entry: Value = serde_json::from_str(line).ok()?;
+        let cwd = entry.get("cwd")?.as_str()?;
+        (!cwd.is_empty()).then(|| cwd.to_string())
+    })
+}
+
+/// Directory of any existing transcript recorded in this project's state files.
+fn recorded_project_dir(state_dir: &Path) -> Option<PathBuf> {
+    fs::read_dir(state_dir)
+        .ok()?
+        .flatten()
+        .filter(|e| e.file_name().to_string_lossy().starts_with("session-"))
+        .filter_map(|e| SessionState::read(&e.path()))
+        .filter_map(|state| {
+            Path::new(&state.transcript_path)
+                .parent()
+                .map(Path::to_path_buf)
+        })
+        .find(|dir| dir.is_dir())
 }
 
 /// Find the most recently modified Codex session JSONL for a project CWD.
@@ -414,7 +1691,7 @@ fn find_latest_codex_session_for_cwd_in(cwd: &str, root: &Path) -> String {
         return String::new();
     }
 
-    candidates.sort_by(|a, b| b.1.cmp(&a.1));
+    candidates.sort_by_key(|c| std::cmp::Reverse(c.1));
     for (path, _) in candidates {
         if codex_session_matches_cwd(&path, cwd) {
             return path.to_string_lossy().to_string();
@@ -424,8 +1701,7 @@ fn find_latest_codex_session_for_cwd_in(cwd: &str, root: &Path) -> String {
 }
 
 fn codex_sessions_root() -> PathBuf {
-    let home = std::env::var("HOME").unwrap_or_default();
-    PathBuf::from(home).join(".codex").join("sessions")
+    crate::paths::home_dir().join(".codex").join("sessions")
 }
 
 fn collect_jsonl_files(dir: &Path, out: &mut Vec<(PathBuf, SystemTime)>) {
@@ -440,7 +1716,7 @@ fn collect_jsonl_files(dir: &Path, out: &mut Vec<(PathBuf, SystemTime)>) {
             collect_jsonl_files(&path, out);
             continue;
         }
-        if path.extension().map_or(false, |ext| ext == "jsonl") {
+        if path.extension().is_some_and(|ext| ext == "jsonl") {
             if let Ok(meta) = entry.metadata() {
                 if let Ok(mtime) = meta.modified() {
                     out.push((path, mtime));
@@ -481,24 +1757,144 @@ fn codex_session_matches_cwd(path: &Path, cwd: &str) -> bool {
     false
 }
 
-/// Compute project hash from CWD (replaces / and _ with -)
+/// Encode a CWD the way Claude Code names its `projects/` subdirectories: every
+/// character other than an ASCII letter or digit becomes `-`, once per UTF-16
+/// code unit (Claude applies a JavaScript regex to the path string).
 pub fn project_hash(cwd: &str) -> String {
-    cwd.replace(['/', '_'], "-")
+    let trimmed = cwd.trim_end_matches('/');
+    let path = if trimmed.is_empty() { cwd } else { trimmed };
+    let mut hash = String::with_capacity(path.len());
+    for c in path.chars() {
+        if c.is_ascii_alphanumeric() {
+            hash.push(c);
+        } else {
+            hash.extend(std::iter::repeat_n('-', c.len_utf16()));
+        }
+    }
+    hash
+}
+
+/// Root of the per-project session state directories:
+/// `sessions` in [`crate::paths::state_dir`].
+pub fn sessions_base_dir() -> PathBuf {
+    crate::paths::state_dir().join("sessions")
+}
+
+/// Return the session state directory for a given project CWD.
+/// State files are stored under `~/.local/state/claude-bar/sessions/<project-hash>/`.
+pub fn state_dir_for_cwd(cwd: &str) -> PathBuf {
+    project_dir_under(sessions_base_dir(), cwd)
+}
+
+/// The pre-migration state directory for `cwd`: `~/.claude/claude-bar/<project-hash>/`.
+/// Still read, and written by the hook when the regular one isn't writable.
+pub fn legacy_state_dir_for_cwd(cwd: &str) -> PathBuf {
+    project_dir_under(crate::paths::legacy_state_dir(), cwd)
 }
 
-/// Return the centralized state directory for a given project CWD.
-/// State files are stored under `~/.claude/claude-bar/<project-hash>/`.
-pub fn state_dir_for_cwd(cwd: &str) -> std::path::PathBuf {
-    let home = std::env::var("HOME").unwrap_or_default();
-    let base = std::path::PathBuf::from(&home)
-        .join(".claude")
-        .join("claude-bar");
+fn project_dir_under(base: PathBuf, cwd: &str) -> PathBuf {
     if cwd.is_empty() {
         return base;
     }
     base.join(project_hash(cwd))
 }
 
+/// The state directory holding the newest state file for `tty_short` in
+/// `cwd`'s project: the legacy one if it has the newer write, else the
+/// regular one.
+pub fn session_state_dir(cwd: &str, tty_short: &str) -> PathBuf {
+    newer_state_dir(
+        state_dir_for_cwd(cwd),
+        legacy_state_dir_for_cwd(cwd),
+        tty_short,
+    )
+}
+
+fn newer_state_dir(primary: PathBuf, fallback: PathBuf, tty_short: &str) -> PathBuf {
+    let file_name = state_file_name(tty_short);
+    let written_at = |dir: &Path| {
+        let path = dir.join(&file_name);
+        SessionState::read(&path).and_then(|s| s.written_at(&path))
+    };
+    match (written_at(&primary), written_at(&fallback)) {
+        (primary_at, Some(fallback_at)) if primary_at.is_none_or(|p| fallback_at > p) => fallback,
+        _ => primary,
+    }
+}
+
+/// Move session state files from the legacy `<legacy_base>/<hash>/`
+/// directories into `<base>/<hash>/`. Returns how many files moved.
+pub fn migrate_state_dirs(legacy_base: &Path, base: &Path) -> usize {
+    let Ok(projects) = fs::read_dir(legacy_base) else {
+        return 0;
+    };
+    let mut moved = 0;
+    for project in projects.flatten() {
+        let legacy_dir = project.path();
+        let Ok(files) = fs::read_dir(&legacy_dir) else {
+            continue;
+        };
+        let dir = base.join(project.file_name());
+        for file in files.flatten() {
+            let name = file.file_name();
+            let name_str = name.to_string_lossy();
+            if !(name_str.starts_with("session-") && name_str.ends_with(".json")) {
+                continue;
+            }
+            let from = file.path();
+            let to = dir.join(&name);
+            let newer_exists = {
+                let written =
+                    |path: &Path| SessionState::read(path).and_then(|s| s.written_at(path));
+                matches!((written(&from), written(&to)), (Some(old), Some(new)) if new >= old)
+            };
+            if newer_exists {
+                let _ = fs::remove_file(&from);
+                continue;
+            }
+            let copied = fs::create_dir_all(&dir)
+                .and_then(|_| fs::rename(&from, &to).or_else(|_| fs::copy(&from, &to).map(|_| ())));
+            if copied.is_ok() {
+                let _ = fs::remove_file(&from);
+                moved += 1;
+            }
+        }
+        // Only succeeds once the directory is empty
+        let _ = fs::remove_dir(&legacy_dir);
+    }
+    moved
+}
+
+/// The newest state file for `tty_short` in any project that was written for
+/// the live process `live`. Lets a poll learn the CWD without running lsof.
+pub fn find_state_for_tty(tty_short: &str, live: &ProcessIdentity) -> Option<SessionState> {
+    [sessions_base_dir(), crate::paths::legacy_state_dir()]
+        .iter()
+        .filter_map(|base| find_state_for_tty_in(base, tty_short, live))
+        .max_by_key(|state| state.updated_at)
+}
+
+fn find_state_for_tty_in(
+    base: &Path,
+    tty_short: &str,
+    live: &ProcessIdentity,
+) -> Option<SessionState> {
+    let file_name = state_file_name(tty_short);
+    fs::read_dir(base)
+        .ok()?
+        .flatten()
+        .filter_map(|e| {
+            let path = e.path().join(&file_name);
+            let state = SessionState::read(&path)?;
+            let written_at = state.written_at(&path);
+            state
+                .is_for(live, written_at)
+                .then_some((written_at, state))
+        })
+        .max_by_key(|(written_at, _)| *written_at)
+        .map(|(_, state)| state)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -515,15 +1911,570 @@ mod tests {
         path.to_string_lossy().to_string()
     }
 
-    fn set_mtime(path: &str, seconds_ago: f64) {
-        let t = SystemTime::now()
-            .duration_since(SystemTime::UNIX_EPOCH)
-            .unwrap()
-            .as_secs_f64()
-            - seconds_ago;
-        let ft =
-            filetime::FileTime::from_unix_time(t as i64, ((t.fract()) * 1_000_000_000.0) as u32);
-        filetime::set_file_mtime(path, ft).unwrap();
+    /// Live TTYs whose process identity is unknown (any state file matches).
+    fn active_ttys(ttys: &[&str]) -> HashMap<String, ProcessIdentity> {
+        ttys.iter()
+            .map(|tty| {
+                let live = ProcessIdentity {
+                    pid: 0,
+                    started_at: None,
+                };
+                (tty.to_string(), live)
+            })
+            .collect()
+    }
+
+    fn set_mtime(path: &str, seconds_ago: f64) {
+        let t = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap()
+            .as_secs_f64()
+            - seconds_ago;
+        let ft =
+            filetime::FileTime::from_unix_time(t as i64, ((t.fract()) * 1_000_000_000.0) as u32);
+        filetime::set_file_mtime(path, ft).unwrap();
+    }
+
+    // ─── read_tail tests ───
+
+    #[test]
+    fn test_read_tail_drops_partial_first_line() {
+        let tmp = TempDir::new().unwrap();
+        let path = tmp.path().join("t.jsonl");
+        // Multi-byte characters straddle every possible cut point
+        let first = format!(r#"{{"text":"{}"}}"#, "é".repeat(40));
+        fs::write(&path, format!("{}\n{{\"b\":1}}\n{{\"c\":2}}\n", first)).unwrap();
+        let path = path.to_string_lossy().to_string();
+
+        for max in 14..first.len() as u64 {
+            let tail = read_tail(&path, max).unwrap();
+            assert!(!tail.contains('\u{FFFD}'), "split char at {}", max);
+            assert!(tail.starts_with('{'), "partial line at {}: {:?}", max, tail);
+        }
+        // Covering the whole file keeps the first line
+        assert!(read_tail(&path, 10_000).unwrap().starts_with(&first));
+    }
+
+    #[test]
+    fn test_read_tail_grows_past_huge_entry() {
+        let tmp = TempDir::new().unwrap();
+        let path = tmp.path().join("t.jsonl");
+        let head = r#"{"type":"assistant","message":{"role":"assistant","content":[{"type":"tool_use","id":"t1","name":"Bash","input":{}}]}}"#;
+        let huge = format!(r#"{{"type":"progress","data":"{}"}}"#, "x".repeat(200_000));
+        let mut content = format!("{}\n", head).repeat(5000);
+        content.push_str(&format!("{}\n", huge));
+        fs::write(&path, &content).unwrap();
+
+        // The last 64KB is all one entry; the window grows until whole lines precede it
+        let tail = read_tail(&path.to_string_lossy(), TAIL_BYTES).unwrap();
+        assert!(tail.len() < content.len());
+        assert!(tail.starts_with(head));
+        assert!(tail.ends_with(&format!("{}\n", huge)));
+        assert_eq!(
+            parse_transcript_content(&tail),
+            (Some("assistant".into()), true, false)
+        );
+    }
+
+    #[test]
+    fn test_read_tail_invalid_utf8_is_lossy() {
+        let tmp = TempDir::new().unwrap();
+        let path = tmp.path().join("t.jsonl");
+        fs::write(&path, b"{\"a\":\"\xff\"}\n").unwrap();
+        let tail = read_tail(&path.to_string_lossy(), TAIL_BYTES).unwrap();
+        assert_eq!(tail, "{\"a\":\"\u{FFFD}\"}\n");
+    }
+
+    // ─── reverse scanning tests ───
+
+    fn write_lines(dir: &Path, lines: &[String]) -> PathBuf {
+        let path = dir.join("t.jsonl");
+        fs::write(&path, lines.join("\n") + "\n").unwrap();
+        path
+    }
+
+    #[test]
+    fn test_reverse_lines_across_blocks() {
+        let tmp = TempDir::new().unwrap();
+        // Lines of varying length so block boundaries fall mid-line
+        let lines: Vec<String> = (0..5000)
+            .map(|i| format!("{}:{}", i, "é".repeat(i % 37)))
+            .collect();
+        let path = write_lines(tmp.path(), &lines);
+
+        let back: Vec<String> = ReverseLines::open(&path, u64::MAX).unwrap().collect();
+        let expected: Vec<String> = lines.iter().rev().cloned().collect();
+        assert_eq!(back, expected);
+    }
+
+    #[test]
+    fn test_reverse_lines_bounded() {
+        let tmp = TempDir::new().unwrap();
+        let lines: Vec<String> = (0..10).map(|i| format!("line{}", i)).collect();
+        let path = write_lines(tmp.path(), &lines);
+
+        // 14 bytes: "line8\nline9\n" plus a cut-off piece of line7, which is dropped
+        let back: Vec<String> = ReverseLines::open(&path, 14).unwrap().collect();
+        assert_eq!(back, vec!["line9", "line8"]);
+        assert_eq!(ReverseLines::open(&path, 0).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn test_plan_mode_found_beyond_tail_window() {
+        let tmp = TempDir::new().unwrap();
+        let mut lines = vec![
+            r#"{"type":"assistant","message":{"role":"assistant","content":[{"type":"tool_use","id":"p1","name":"EnterPlanMode","input":{}}]}}"#.to_string(),
+            r#"{"type":"user","message":{"role":"user","content":[{"type":"tool_result","tool_use_id":"p1","content":"ok"}]}}"#.to_string(),
+        ];
+        // Hours of exploration inside plan mode push the marker far back
+        for i in 0..2000 {
+            lines.push(format!(
+                r#"{{"type":"assistant","message":{{"role":"assistant","content":[{{"type":"tool_use","id":"r{i}","name":"Read","input":{{}}}}]}}}}"#
+            ));
+            lines.push(format!(
+                r#"{{"type":"user","message":{{"role":"user","content":[{{"type":"tool_result","tool_use_id":"r{i}","content":"{}"}}]}}}}"#,
+                "x".repeat(100)
+            ));
+        }
+        lines.push(r#"{"type":"assistant","message":{"role":"assistant","content":[{"type":"text","text":"Plan ready"}]}}"#.to_string());
+        let path = write_lines(tmp.path(), &lines);
+        let path = path.to_string_lossy();
+
+        assert!(fs::metadata(path.as_ref()).unwrap().len() > TAIL_BYTES * 4);
+        let summary = analyze_transcript_with(path.as_ref(), u64::MAX).unwrap();
+        assert_eq!(summary.last_role.as_deref(), Some("assistant"));
+        assert!(!summary.pending);
+        assert!(summary.in_plan_mode);
+        // Outside the scan bound the marker is not seen...
+        PLAN_MODE_CACHE
+            .lock()
+            .unwrap()
+            .remove(Path::new(path.as_ref()));
+        let summary = analyze_transcript_with(path.as_ref(), TAIL_BYTES).unwrap();
+        assert!(!summary.in_plan_mode);
+    }
+
+    #[test]
+    fn test_plan_mode_remembered_across_appends() {
+        let tmp = TempDir::new().unwrap();
+        let read = |i: usize| {
+            [
+                format!(
+                    r#"{{"type":"assistant","message":{{"role":"assistant","content":[{{"type":"tool_use","id":"r{i}","name":"Read","input":{{}}}}]}}}}"#
+                ),
+                format!(
+                    r#"{{"type":"user","message":{{"role":"user","content":[{{"type":"tool_result","tool_use_id":"r{i}","content":"x"}}]}}}}"#
+                ),
+            ]
+        };
+        let mut lines = vec![
+            r#"{"type":"assistant","message":{"role":"assistant","content":[{"type":"tool_use","id":"p1","name":"EnterPlanMode","input":{}}]}}"#.to_string(),
+            r#"{"type":"user","message":{"role":"user","content":[{"type":"tool_result","tool_use_id":"p1","content":"ok"}]}}"#.to_string(),
+        ];
+        lines.extend((0..50).flat_map(read));
+        let path = write_lines(tmp.path(), &lines);
+        assert!(
+            analyze_transcript_with(&path, u64::MAX)
+                .unwrap()
+                .in_plan_mode
+        );
+
+        // Later polls only look at what was appended, which is less than the
+        // bound, so the marker must come from the first scan
+        lines.extend((50..60).flat_map(read));
+        let path = write_lines(tmp.path(), &lines);
+        let appended = 10 * (read(50)[0].len() + read(50)[1].len() + 2) as u64;
+        assert!(
+            analyze_transcript_with(&path, appended)
+                .unwrap()
+                .in_plan_mode
+        );
+
+        // A rewritten (shorter) file is searched afresh
+        let path = write_lines(tmp.path(), &lines[2..20]);
+        assert!(
+            !analyze_transcript_with(&path, u64::MAX)
+                .unwrap()
+                .in_plan_mode
+        );
+    }
+
+    #[test]
+    fn test_claude_entry_parse_shapes() {
+        let (kind, blocks) = ClaudeEntry::parse(
+            r#"{"type":"assistant","uuid":"u1","message":{"role":"assistant","model":"m","content":[{"type":"thinking","thinking":"..."},{"type":"tool_use","id":"t1","name":"Bash","input":{"command":"ls"}}],"usage":{"input_tokens":3}}}"#,
+        )
+        .unwrap();
+        assert_eq!(kind, Role::Assistant);
+        let blocks = blocks.unwrap();
+        assert_eq!(blocks[0].kind, BlockKind::Other);
+        assert_eq!(blocks[1].kind, BlockKind::ToolUse);
+        assert_eq!(blocks[1].name.as_deref(), Some("Bash"));
+
+        let (kind, blocks) = ClaudeEntry::parse(
+            r#"{"type":"user","message":{"role":"user","content":[{"type":"tool_result","tool_use_id":"t1","content":[{"type":"text","text":"no"}],"is_error":true}]}}"#,
+        )
+        .unwrap();
+        assert_eq!(kind, Role::User);
+        assert!(blocks.unwrap()[0].is_error);
+
+        // An explicit null flag reads as unset rather than dropping the entry
+        let (kind, blocks) = ClaudeEntry::parse(
+            r#"{"type":"user","message":{"role":"user","content":[{"type":"tool_result","tool_use_id":"t1","content":"ok","is_error":null}]},"isApiErrorMessage":null}"#,
+        )
+        .unwrap();
+        assert_eq!(kind, Role::User);
+        assert!(!blocks.unwrap()[0].is_error);
+
+        // Plain-string prompts have no blocks
+        let (kind, blocks) =
+            ClaudeEntry::parse(r#"{"type":"user","message":{"role":"user","content":"hi"}}"#)
+                .unwrap();
+        assert_eq!(kind, Role::User);
+        assert!(blocks.is_none());
+
+        // Unknown entry types and mismatched roles are ignored, not errors
+        for line in [
+            r#"{"type":"summary","summary":"x","leafUuid":"u"}"#,
+            r#"{"type":"user","message":{"role":"assistant","content":[]}}"#,
+            r#"{"type":"assistant"}"#,
+        ] {
+            assert_eq!(ClaudeEntry::parse(line).unwrap().0, Role::Other, "{}", line);
+        }
+        assert!(ClaudeEntry::parse("{truncated").is_none());
+    }
+
+    #[test]
+    fn test_analyze_transcript_summary() {
+        let tmp = TempDir::new().unwrap();
+        let path = make_transcript(
+            tmp.path(),
+            "s",
+            &[
+                serde_json::json!({"type":"user","sessionId":"abc","message":{"role":"user","content":"fix it"}}),
+                serde_json::json!({"type":"assistant","sessionId":"abc","message":{"role":"assistant","model":"claude-old","content":[{"type":"text","text":"ok"}],"usage":{"input_tokens":1,"output_tokens":1}}}),
+                serde_json::json!({"type":"assistant","sessionId":"abc","message":{"role":"assistant","model":"claude-new","content":[{"type":"text","text":"running"},{"type":"tool_use","id":"t1","name":"Read","input":{}},{"type":"tool_use","id":"t2","name":"Bash","input":{}}],"usage":{"input_tokens":120,"output_tokens":45,"cache_read_input_tokens":9000}}}),
+                serde_json::json!({"type":"summary","summary":"Fixing things"}),
+            ],
+        );
+        set_mtime(&path, 30.0);
+
+        let summary = analyze_transcript(&path).unwrap();
+        assert_eq!(summary.last_role.as_deref(), Some("assistant"));
+        assert!(summary.pending);
+        assert_eq!(summary.pending_tool.as_deref(), Some("Bash"));
+        assert!(!summary.in_plan_mode);
+        assert_eq!(summary.model.as_deref(), Some("claude-new"));
+        assert_eq!(
+            summary.tokens,
+            Some(TokenUsage {
+                input_tokens: 120,
+                output_tokens: 45,
+                cache_creation_input_tokens: 0,
+                cache_read_input_tokens: 9000,
+            })
+        );
+        assert_eq!(summary.session_id.as_deref(), Some("abc"));
+        assert!(summary.age_secs.unwrap() >= 29.0);
+        assert_eq!(summary.status(&Thresholds::default()), Status::Pending);
+
+        assert!(analyze_transcript(tmp.path().join("missing.jsonl")).is_none());
+    }
+
+    #[test]
+    fn test_explain_status_reports_inputs_and_rule() {
+        let tmp = TempDir::new().unwrap();
+        let path = make_transcript(
+            tmp.path(),
+            "s",
+            &[
+                serde_json::json!({"type":"user","message":{"role":"user","content":"fix it"}}),
+                serde_json::json!({"type":"assistant","message":{"role":"assistant","content":[{"type":"tool_use","id":"t1","name":"Bash","input":{}}]}}),
+            ],
+        );
+        set_mtime(&path, 300.0);
+
+        let decision = explain_status(Some(&path), None, &Thresholds::default());
+        assert_eq!(decision.status, Status::Idle);
+        assert_eq!(decision.rule, Rule::PendingTimeout);
+        assert_eq!(decision.transcript.as_deref(), Some(path.as_str()));
+        assert!(decision.age_secs.unwrap() >= 299.0);
+        assert_eq!(decision.last_role.as_deref(), Some("assistant"));
+        assert!(decision.pending);
+        assert_eq!(decision.pending_tool.as_deref(), Some("Bash"));
+        assert!(!decision.in_plan_mode);
+
+        let missing = tmp
+            .path()
+            .join("missing.jsonl")
+            .to_string_lossy()
+            .to_string();
+        let decision = explain_status(Some(&missing), None, &Thresholds::default());
+        assert_eq!(
+            (decision.status, decision.rule),
+            (Status::Active, Rule::NoTranscript)
+        );
+        assert_eq!(
+            serde_json::to_value(decision.rule).unwrap(),
+            serde_json::json!("no_transcript")
+        );
+    }
+
+    #[test]
+    fn test_missing_transcript_becomes_unknown_after_startup() {
+        let t = Thresholds::default();
+        // Still starting: MCP servers and the model load before the first write
+        let starting = explain_status(None, None, &t).with_process_age(Some(5.0), &t);
+        assert_eq!(
+            (starting.status, starting.rule),
+            (Status::Active, Rule::NoTranscript)
+        );
+        assert_eq!(starting.process_age_secs, Some(5.0));
+
+        let stuck = explain_status(None, None, &t).with_process_age(Some(300.0), &t);
+        assert_eq!(
+            (stuck.status, stuck.rule),
+            (Status::Unknown, Rule::NoTranscriptTimeout)
+        );
+        assert!(stuck.reason(&t).contains("300s after the process started"));
+
+        // Without a start time there is nothing to go by
+        let unknown_age = explain_status(None, None, &t).with_process_age(None, &t);
+        assert_eq!(unknown_age.status, Status::Active);
+
+        // A session with a transcript is never affected
+        let tmp = TempDir::new().unwrap();
+        let path = tmp.path().join("t.jsonl");
+        fs::write(
+            &path,
+            r#"{"type":"user","message":{"role":"user","content":"hi"}}"#,
+        )
+        .unwrap();
+        let decision = explain_status(Some(&path.to_string_lossy()), None, &t)
+            .with_process_age(Some(300.0), &t);
+        assert_eq!(decision.rule, Rule::RecentWrite);
+    }
+
+    #[test]
+    fn test_running_subagents_keep_session_active() {
+        let tmp = TempDir::new().unwrap();
+        let t = Thresholds::default();
+        let waiting_on = |tool: &str, age: f64| {
+            let path = make_transcript(
+                tmp.path(),
+                tool,
+                &[
+                    serde_json::json!({"type":"user","message":{"role":"user","content":"review it"}}),
+                    serde_json::json!({"type":"assistant","message":{"role":"assistant","content":[{"type":"tool_use","id":"t1","name":tool,"input":{}}]}}),
+                ],
+            );
+            set_mtime(&path, age);
+            explain_status(Some(&path), None, &t)
+        };
+
+        // A Task call without a result is a subagent at work, not a permission prompt
+        let task = waiting_on("Task", 30.0).with_subagents(0);
+        assert_eq!((task.status, task.rule), (Status::Active, Rule::Subagents));
+        assert_eq!(task.subagents, 1);
+        assert!(task.reason(&t).starts_with("1 subagent(s) running"));
+        // ... nor abandoned once it has run for a while
+        let long = waiting_on("Task", 600.0).with_subagents(3);
+        assert_eq!((long.status, long.subagents), (Status::Active, 3));
+
+        // Another tool still waits for the user, subagents or not
+        let bash = waiting_on("Bash", 30.0).with_subagents(2);
+        assert_eq!(
+            (bash.status, bash.rule),
+            (Status::Pending, Rule::PendingTool)
+        );
+        assert_eq!(bash.subagents, 2);
+
+        let plain = waiting_on("Bash", 30.0).with_subagents(0);
+        assert_eq!((plain.status, plain.subagents), (Status::Pending, 0));
+    }
+
+    #[test]
+    fn test_denied_hook_call_ends_with_next_prompt() {
+        let tmp = TempDir::new().unwrap();
+        let t = Thresholds::default();
+        let mut lines = vec![
+            serde_json::json!({"type":"user","message":{"role":"user","content":"build it"}}),
+            serde_json::json!({"type":"assistant","message":{"role":"assistant","content":[{"type":"tool_use","id":"t1","name":"Bash","input":{}}]}}),
+            serde_json::json!({"type":"user","message":{"role":"user","content":[{"type":"tool_result","tool_use_id":"t1","is_error":true,"content":"The user doesn't want to proceed with this tool use."}]}}),
+            serde_json::json!({"type":"user","message":{"role":"user","content":[{"type":"text","text":"[Request interrupted by user for tool use]"}]}}),
+        ];
+        let denied = vec![ToolInFlight {
+            tool: "Bash".into(),
+            id: Some("t1".into()),
+            since: 0,
+            awaiting_permission: true,
+        }];
+        let path = make_transcript(tmp.path(), "denied", &lines);
+        let decision = explain_status(Some(&path), None, &t).with_tool_hooks(Some(&denied), &t);
+        assert_eq!(decision.rule, Rule::Interrupted);
+
+        // The next prompt's UserPromptSubmit clears the call, so Claude working
+        // on it isn't mistaken for the old permission prompt
+        let prompt = serde_json::json!({
+            "session_id": "s", "transcript_path": path, "hook_event_name": "UserPromptSubmit",
+        });
+        let tools = crate::hook::parse_hook_input(&prompt.to_string())
+            .unwrap()
+            .tools_after(denied, 1);
+        lines.push(serde_json::json!({"type":"user","message":{"role":"user","content":"use cargo instead"}}));
+        lines.push(serde_json::json!({"type":"assistant","message":{"role":"assistant","content":[{"type":"text","text":"Switching to cargo."}]}}));
+        let path = make_transcript(tmp.path(), "denied", &lines);
+        set_mtime(&path, 1.0);
+        let decision = explain_status(Some(&path), None, &t).with_tool_hooks(Some(&tools), &t);
+        assert_eq!(decision.status, Status::Active);
+        assert!(!decision.pending);
+    }
+
+    #[test]
+    fn test_tool_hooks_replace_pending_heuristics() {
+        let tmp = TempDir::new().unwrap();
+        let t = Thresholds::default();
+        let path = make_transcript(
+            tmp.path(),
+            "s",
+            &[
+                serde_json::json!({"type":"user","message":{"role":"user","content":"build it"}}),
+                serde_json::json!({"type":"assistant","message":{"role":"assistant","content":[{"type":"tool_use","id":"t1","name":"Bash","input":{}}]}}),
+            ],
+        );
+        let call = |tool: &str, awaiting_permission: bool| ToolInFlight {
+            tool: tool.into(),
+            id: Some("t1".into()),
+            since: 0,
+            awaiting_permission,
+        };
+        let decide = |age: f64, tools: Option<&[ToolInFlight]>| {
+            set_mtime(&path, age);
+            explain_status(Some(&path), None, &t).with_tool_hooks(tools, &t)
+        };
+
+        // A long build is running, not waiting for approval, however long it takes
+        let running = decide(600.0, Some(&[call("Bash", false)]));
+        assert_eq!(
+            (running.status, running.rule),
+            (Status::Active, Rule::HookTool)
+        );
+        assert!(running.reason(&t).contains("no PostToolUse yet"));
+
+        // The permission prompt counts at once and never times out
+        let prompt = decide(1.0, Some(&[call("Bash", true)]));
+        assert_eq!(
+            (prompt.status, prompt.rule),
+            (Status::Pending, Rule::HookPermission)
+        );
+        assert_eq!(prompt.pending_tool.as_deref(), Some("Bash"));
+        let question = decide(900.0, Some(&[call("AskUserQuestion", false)]));
+        assert_eq!(question.status, Status::Pending);
+
+        // PostToolUse ran: the transcript just hasn't caught up
+        let done = decide(30.0, Some(&[]));
+        assert_eq!((done.status, done.rule), (Status::Idle, Rule::TurnFinished));
+        assert!(!done.pending);
+
+        // Without tool hooks the heuristics still apply
+        let heuristic = decide(30.0, None);
+        assert_eq!(
+            (heuristic.status, heuristic.rule),
+            (Status::Pending, Rule::PendingTool)
+        );
+        assert_eq!(heuristic.tools_in_flight, None);
+    }
+
+    #[test]
+    fn test_api_retries_mark_session_rate_limited() {
+        let prompt = r#"{"type":"user","message":{"role":"user","content":"go on"}}"#;
+        let retry = r#"{"type":"system","subtype":"api_error","level":"error","error":{"status":529},"retryAttempt":1,"maxRetries":10}"#;
+        let failed = r#"{"type":"assistant","isApiErrorMessage":true,"message":{"role":"assistant","model":"<synthetic>","content":[{"type":"text","text":"API Error: 529 Overloaded"}]}}"#;
+        let reply = r#"{"type":"assistant","message":{"role":"assistant","content":[{"type":"text","text":"Done"}]}}"#;
+        let t = Thresholds::default();
+        let summary = |lines: &[&str], age: f64| TranscriptSummary {
+            age_secs: Some(age),
+            ..scan_entries_reverse(lines.iter().rev(), None)
+        };
+
+        // One retry is noise; a second makes it a storm
+        let one = summary(&[prompt, retry], 30.0);
+        assert_eq!(one.api_errors, 1);
+        assert_eq!(one.decide(&t), (Status::Active, Rule::Processing));
+        let storm = summary(&[prompt, retry, retry, retry], 30.0);
+        assert_eq!(storm.api_errors, 3);
+        assert_eq!(storm.decide(&t), (Status::RateLimited, Rule::RateLimited));
+
+        // Retries given up on still count until the session goes idle
+        let gave_up = summary(&[prompt, retry, retry, failed], 60.0);
+        assert_eq!(gave_up.api_errors, 3);
+        assert_eq!(gave_up.status(&t), Status::RateLimited);
+        assert_eq!(
+            summary(&[prompt, retry, retry, failed], 600.0).status(&t),
+            Status::Idle
+        );
+
+        // A successful reply ends the storm
+        let recovered = summary(&[prompt, retry, retry, reply], 30.0);
+        assert_eq!(recovered.api_errors, 0);
+        assert_eq!(recovered.decide(&t), (Status::Idle, Rule::TurnFinished));
+    }
+
+    #[test]
+    fn test_cpu_usage_refines_status() {
+        let prompt = r#"{"type":"user","message":{"role":"user","content":"go on"}}"#;
+        let call = r#"{"type":"assistant","message":{"role":"assistant","content":[{"type":"tool_use","id":"g1","name":"Grep","input":{}}]}}"#;
+        let t = Thresholds::default();
+        let summary = |lines: &[&str], age: f64| TranscriptSummary {
+            age_secs: Some(age),
+            ..scan_entries_reverse(lines.iter().rev(), None)
+        };
+
+        // A long in-process tool call stays active while the process is busy
+        let crunching = summary(&[prompt, call], 900.0);
+        assert_eq!(crunching.decide(&t), (Status::Idle, Rule::PendingTimeout));
+        assert_eq!(
+            crunching.decide_with_cpu(Some(80.0), &t),
+            (Status::Active, Rule::CpuBusy)
+        );
+
+        // A prompt nothing is working on goes idle before idle_after
+        let waiting = summary(&[prompt], 60.0);
+        assert_eq!(waiting.decide(&t), (Status::Active, Rule::Processing));
+        assert_eq!(
+            waiting.decide_with_cpu(Some(0.0), &t),
+            (Status::Idle, Rule::CpuQuiet)
+        );
+        assert_eq!(
+            waiting.decide_with_cpu(Some(3.0), &t),
+            (Status::Active, Rule::Processing)
+        );
+        assert_eq!(
+            summary(&[prompt], 20.0).decide_with_cpu(Some(0.0), &t),
+            (Status::Active, Rule::Processing)
+        );
+
+        let decision = StatusDecision::from_summary(waiting, "t.jsonl", Some(0.0), &t);
+        assert_eq!(decision.cpu_percent, Some(0.0));
+        assert!(decision.reason(&t).contains("used 0.0% CPU"));
+    }
+
+    #[test]
+    fn test_reverse_scan_matches_forward_semantics() {
+        let enter = r#"{"type":"assistant","message":{"role":"assistant","content":[{"type":"tool_use","id":"p1","name":"EnterPlanMode","input":{}}]}}"#;
+        let result = r#"{"type":"user","message":{"role":"user","content":[{"type":"tool_result","tool_use_id":"p1","content":"ok"}]}}"#;
+        let prompt = r#"{"type":"user","message":{"role":"user","content":"go on"}}"#;
+        // A plain user prompt after the result keeps plan mode and makes user the last role
+        let content = [enter, result, prompt].join("\n");
+        assert_eq!(
+            parse_transcript_content(&content),
+            (Some("user".into()), false, true)
+        );
+        // Unanswered EnterPlanMode does not count yet
+        assert_eq!(
+            parse_transcript_content(enter),
+            (Some("assistant".into()), true, false)
+        );
     }
 
     // ─── parse_transcript_content tests ───
@@ -573,7 +2524,7 @@ mod tests {
 
     #[test]
     fn test_multiple_rounds() {
-        let lines = vec![
+        let lines = [
             r#"{"type":"assistant","message":{"role":"assistant","content":[{"type":"tool_use","id":"t1","name":"Read","input":{}}]}}"#,
             r#"{"type":"user","message":{"role":"user","content":[{"type":"tool_result","tool_use_id":"t1","content":"ok"}]}}"#,
             r#"{"type":"assistant","message":{"role":"assistant","content":[{"type":"tool_use","id":"t2","name":"Bash","input":{}}]}}"#,
@@ -587,7 +2538,7 @@ mod tests {
 
     #[test]
     fn test_all_paired_multiple_rounds() {
-        let lines = vec![
+        let lines = [
             r#"{"type":"assistant","message":{"role":"assistant","content":[{"type":"tool_use","id":"t1","name":"Read","input":{}}]}}"#,
             r#"{"type":"user","message":{"role":"user","content":[{"type":"tool_result","tool_use_id":"t1","content":"ok"}]}}"#,
             r#"{"type":"assistant","message":{"role":"assistant","content":[{"type":"text","text":"All done!"}]}}"#,
@@ -601,7 +2552,7 @@ mod tests {
 
     #[test]
     fn test_progress_lines_ignored() {
-        let lines = vec![
+        let lines = [
             r#"{"type":"user","message":{"role":"user","content":[{"type":"text","text":"Hi"}]}}"#,
             r#"{"type":"progress","content":{"type":"status","text":"thinking..."}}"#,
         ];
@@ -614,7 +2565,7 @@ mod tests {
 
     #[test]
     fn test_invalid_json_lines_skipped() {
-        let lines = vec![
+        let lines = [
             "NOT VALID JSON{{{",
             r#"{"type":"assistant","message":{"role":"assistant","content":[{"type":"text","text":"Done"}]}}"#,
         ];
@@ -648,7 +2599,7 @@ mod tests {
 
     #[test]
     fn test_enter_plan_mode_completed() {
-        let lines = vec![
+        let lines = [
             r#"{"type":"assistant","message":{"role":"assistant","content":[{"type":"tool_use","id":"t1","name":"EnterPlanMode","input":{}}]}}"#,
             r#"{"type":"user","message":{"role":"user","content":[{"type":"tool_result","tool_use_id":"t1","content":"Entered plan mode."}]}}"#,
         ];
@@ -662,7 +2613,7 @@ mod tests {
     #[test]
     fn test_plan_mode_with_research() {
         // In plan mode, Claude does research, then writes text
-        let lines = vec![
+        let lines = [
             r#"{"type":"assistant","message":{"role":"assistant","content":[{"type":"tool_use","id":"t1","name":"EnterPlanMode","input":{}}]}}"#,
             r#"{"type":"user","message":{"role":"user","content":[{"type":"tool_result","tool_use_id":"t1","content":"Entered plan mode."}]}}"#,
             r#"{"type":"assistant","message":{"role":"assistant","content":[{"type":"tool_use","id":"t2","name":"Read","input":{}}]}}"#,
@@ -679,7 +2630,7 @@ mod tests {
     #[test]
     fn test_plan_mode_exit_pending() {
         // ExitPlanMode called but not completed yet
-        let lines = vec![
+        let lines = [
             r#"{"type":"assistant","message":{"role":"assistant","content":[{"type":"tool_use","id":"t1","name":"EnterPlanMode","input":{}}]}}"#,
             r#"{"type":"user","message":{"role":"user","content":[{"type":"tool_result","tool_use_id":"t1","content":"Entered plan mode."}]}}"#,
             r#"{"type":"assistant","message":{"role":"assistant","content":[{"type":"tool_use","id":"t2","name":"ExitPlanMode","input":{}}]}}"#,
@@ -694,7 +2645,7 @@ mod tests {
     #[test]
     fn test_plan_mode_exit_completed() {
         // ExitPlanMode completed -> no longer in plan mode
-        let lines = vec![
+        let lines = [
             r#"{"type":"assistant","message":{"role":"assistant","content":[{"type":"tool_use","id":"t1","name":"EnterPlanMode","input":{}}]}}"#,
             r#"{"type":"user","message":{"role":"user","content":[{"type":"tool_result","tool_use_id":"t1","content":"Entered plan mode."}]}}"#,
             r#"{"type":"assistant","message":{"role":"assistant","content":[{"type":"tool_use","id":"t2","name":"ExitPlanMode","input":{}}]}}"#,
@@ -710,7 +2661,7 @@ mod tests {
     #[test]
     fn test_plan_mode_exit_rejected() {
         // ExitPlanMode rejected -> still in plan mode
-        let lines = vec![
+        let lines = [
             r#"{"type":"assistant","message":{"role":"assistant","content":[{"type":"tool_use","id":"t1","name":"EnterPlanMode","input":{}}]}}"#,
             r#"{"type":"user","message":{"role":"user","content":[{"type":"tool_result","tool_use_id":"t1","content":"Entered plan mode."}]}}"#,
             r#"{"type":"assistant","message":{"role":"assistant","content":[{"type":"tool_use","id":"t2","name":"ExitPlanMode","input":{}}]}}"#,
@@ -726,7 +2677,7 @@ mod tests {
     #[test]
     fn test_plan_mode_status_pending_not_idle() {
         // In plan mode, text-only assistant -> should be pending, not idle
-        let lines = vec![
+        let lines = [
             r#"{"type":"assistant","message":{"role":"assistant","content":[{"type":"tool_use","id":"t1","name":"EnterPlanMode","input":{}}]}}"#,
             r#"{"type":"user","message":{"role":"user","content":[{"type":"tool_result","tool_use_id":"t1","content":"Entered plan mode."}]}}"#,
             r#"{"type":"assistant","message":{"role":"assistant","content":[{"type":"text","text":"Here is my plan..."}]}}"#,
@@ -741,7 +2692,7 @@ mod tests {
     #[test]
     fn test_plan_mode_pending_no_timeout() {
         // In plan mode, pending tool_use should not timeout at 120s
-        let lines = vec![
+        let lines = [
             r#"{"type":"assistant","message":{"role":"assistant","content":[{"type":"tool_use","id":"t1","name":"EnterPlanMode","input":{}}]}}"#,
             r#"{"type":"user","message":{"role":"user","content":[{"type":"tool_result","tool_use_id":"t1","content":"Entered plan mode."}]}}"#,
             r#"{"type":"assistant","message":{"role":"assistant","content":[{"type":"tool_use","id":"t2","name":"ExitPlanMode","input":{}}]}}"#,
@@ -809,7 +2760,7 @@ mod tests {
 
     #[test]
     fn test_last_assistant_text_is_idle() {
-        let lines = vec![
+        let lines = [
             r#"{"type":"user","message":{"role":"user","content":[{"type":"text","text":"Hello"}]}}"#,
             r#"{"type":"assistant","message":{"role":"assistant","content":[{"type":"text","text":"Hi there!"}]}}"#,
         ];
@@ -912,7 +2863,7 @@ mod tests {
 
     #[test]
     fn test_tool_result_clears_pending() {
-        let lines = vec![
+        let lines = [
             r#"{"type":"assistant","message":{"role":"assistant","content":[{"type":"tool_use","id":"t1","name":"Bash","input":{}}]}}"#,
             r#"{"type":"user","message":{"role":"user","content":[{"type":"tool_result","tool_use_id":"t1","content":"ok"}]}}"#,
         ];
@@ -924,6 +2875,72 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_interrupt_mid_response_is_idle() {
+        let lines = [
+            r#"{"type":"user","message":{"role":"user","content":"refactor the parser"}}"#,
+            r#"{"type":"user","message":{"role":"user","content":[{"type":"text","text":"[Request interrupted by user]"}]}}"#,
+        ];
+        let content = lines.join("\n");
+        let summary = scan_entries_reverse(content.lines().rev(), None);
+        assert!(summary.interrupted);
+        // Would otherwise read as "user sent a message, Claude is working"
+        assert_eq!(
+            determine_status_with_age(Some(&content), Some(1.0)),
+            Status::Idle
+        );
+    }
+
+    #[test]
+    fn test_interrupted_tool_is_not_pending() {
+        let lines = [
+            r#"{"type":"assistant","message":{"role":"assistant","content":[{"type":"tool_use","id":"t1","name":"Bash","input":{}}]}}"#,
+            r#"{"type":"user","message":{"role":"user","content":[{"type":"tool_result","tool_use_id":"t1","is_error":true,"content":"[Request interrupted by user for tool use]"}]}}"#,
+        ];
+        let content = lines.join("\n");
+        assert_eq!(
+            determine_status_with_age(Some(&content), Some(30.0)),
+            Status::Idle
+        );
+        assert_eq!(parse_pending_tool(&content), None);
+
+        // Marker written without a tool_result for the running tool
+        let lines = [
+            lines[0],
+            r#"{"type":"user","message":{"role":"user","content":[{"type":"text","text":"[Request interrupted by user for tool use]"}]}}"#,
+        ];
+        let content = lines.join("\n");
+        assert_eq!(
+            determine_status_with_age(Some(&content), Some(30.0)),
+            Status::Idle
+        );
+        assert_eq!(parse_pending_tool(&content), None);
+    }
+
+    #[test]
+    fn test_tool_output_quoting_interrupt_marker_is_not_interrupt() {
+        let lines = [
+            r#"{"type":"assistant","message":{"role":"assistant","content":[{"type":"tool_use","id":"t1","name":"Read","input":{}}]}}"#,
+            r#"{"type":"user","message":{"role":"user","content":[{"type":"tool_result","tool_use_id":"t1","content":"... [Request interrupted by user] ..."}]}}"#,
+        ];
+        let content = lines.join("\n");
+        assert_eq!(
+            determine_status_with_age(Some(&content), Some(30.0)),
+            Status::Active
+        );
+
+        // An interrupt followed by a new prompt is an ordinary running turn
+        let lines = [
+            r#"{"type":"user","message":{"role":"user","content":[{"type":"text","text":"[Request interrupted by user]"}]}}"#,
+            r#"{"type":"user","message":{"role":"user","content":"try again"}}"#,
+        ];
+        let content = lines.join("\n");
+        assert_eq!(
+            determine_status_with_age(Some(&content), Some(30.0)),
+            Status::Active
+        );
+    }
+
     // ─── resolve_transcript tests ───
 
     #[test]
@@ -935,10 +2952,13 @@ mod tests {
         fs::create_dir_all(&project_dir).unwrap();
 
         let tp = make_transcript(&project_dir, "aaa", &[]);
-        let state = crate::state::SessionState {
+        let state = SessionState {
             session_id: "aaa".into(),
             transcript_path: tp.clone(),
             cwd: String::new(),
+            pid: None,
+            process_started: None,
+            ..Default::default()
         };
         fs::write(
             state_dir.join("session-ttys000.json"),
@@ -946,7 +2966,7 @@ mod tests {
         )
         .unwrap();
 
-        let active: std::collections::HashSet<String> = ["ttys000".into()].into();
+        let active = active_ttys(&["ttys000"]);
         let result = resolve_transcript("ttys000", &state_dir, &project_dir, &active);
         assert_eq!(result, tp);
     }
@@ -963,7 +2983,7 @@ mod tests {
         set_mtime(&project_dir.join("old.jsonl").to_string_lossy(), 10.0);
         let new_path = make_transcript(&project_dir, "new", &[]);
 
-        let active: std::collections::HashSet<String> = ["ttys000".into()].into();
+        let active = active_ttys(&["ttys000"]);
         let result = resolve_transcript("ttys000", &state_dir, &project_dir, &active);
         assert_eq!(result, new_path);
     }
@@ -976,10 +2996,13 @@ mod tests {
         fs::create_dir_all(&state_dir).unwrap();
         fs::create_dir_all(&project_dir).unwrap();
 
-        let state = crate::state::SessionState {
+        let state = SessionState {
             session_id: "gone".into(),
             transcript_path: "/nonexistent/gone.jsonl".into(),
             cwd: String::new(),
+            pid: None,
+            process_started: None,
+            ..Default::default()
         };
         fs::write(
             state_dir.join("session-ttys000.json"),
@@ -988,7 +3011,7 @@ mod tests {
         .unwrap();
 
         let tp = make_transcript(&project_dir, "real", &[]);
-        let active: std::collections::HashSet<String> = ["ttys000".into()].into();
+        let active = active_ttys(&["ttys000"]);
         let result = resolve_transcript("ttys000", &state_dir, &project_dir, &active);
         assert_eq!(result, tp);
     }
@@ -1001,7 +3024,7 @@ mod tests {
         fs::create_dir_all(&state_dir).unwrap();
         fs::create_dir_all(&project_dir).unwrap();
 
-        let active: std::collections::HashSet<String> = ["ttys000".into()].into();
+        let active = active_ttys(&["ttys000"]);
         let result = resolve_transcript("ttys000", &state_dir, &project_dir, &active);
         assert_eq!(result, "");
     }
@@ -1018,15 +3041,21 @@ mod tests {
         let tp_b = make_transcript(&project_dir, "bbb", &[]);
         set_mtime(&tp_b, 5.0);
 
-        let state_a = crate::state::SessionState {
+        let state_a = SessionState {
             session_id: "aaa".into(),
             transcript_path: tp_a.clone(),
             cwd: String::new(),
+            pid: None,
+            process_started: None,
+            ..Default::default()
         };
-        let state_b = crate::state::SessionState {
+        let state_b = SessionState {
             session_id: "bbb".into(),
             transcript_path: tp_b.clone(),
             cwd: String::new(),
+            pid: None,
+            process_started: None,
+            ..Default::default()
         };
         fs::write(
             state_dir.join("session-ttys000.json"),
@@ -1039,7 +3068,7 @@ mod tests {
         )
         .unwrap();
 
-        let active: std::collections::HashSet<String> = ["ttys000".into(), "ttys009".into()].into();
+        let active = active_ttys(&["ttys000", "ttys009"]);
         assert_eq!(
             resolve_transcript("ttys000", &state_dir, &project_dir, &active),
             tp_a
@@ -1063,15 +3092,21 @@ mod tests {
         set_mtime(&tp_a, 5.0);
 
         // A's state is stale
-        let state_a = crate::state::SessionState {
+        let state_a = SessionState {
             session_id: "gone".into(),
             transcript_path: "/nonexistent/gone.jsonl".into(),
             cwd: String::new(),
+            pid: None,
+            process_started: None,
+            ..Default::default()
         };
-        let state_b = crate::state::SessionState {
+        let state_b = SessionState {
             session_id: "bbb".into(),
             transcript_path: tp_b.clone(),
             cwd: String::new(),
+            pid: None,
+            process_started: None,
+            ..Default::default()
         };
         fs::write(
             state_dir.join("session-ttys000.json"),
@@ -1084,7 +3119,7 @@ mod tests {
         )
         .unwrap();
 
-        let active: std::collections::HashSet<String> = ["ttys000".into(), "ttys009".into()].into();
+        let active = active_ttys(&["ttys000", "ttys009"]);
         assert_eq!(
             resolve_transcript("ttys009", &state_dir, &project_dir, &active),
             tp_b
@@ -1108,10 +3143,13 @@ mod tests {
         make_transcript(&project_dir, "dead", &[]);
         set_mtime(&project_dir.join("dead.jsonl").to_string_lossy(), 5.0);
 
-        let state_dead = crate::state::SessionState {
+        let state_dead = SessionState {
             session_id: "dead".into(),
             transcript_path: project_dir.join("dead.jsonl").to_string_lossy().to_string(),
             cwd: String::new(),
+            pid: None,
+            process_started: None,
+            ..Default::default()
         };
         fs::write(
             state_dir.join("session-ttys005.json"),
@@ -1119,7 +3157,7 @@ mod tests {
         )
         .unwrap();
 
-        let active: std::collections::HashSet<String> = ["ttys000".into()].into();
+        let active = active_ttys(&["ttys000"]);
         assert_eq!(
             resolve_transcript("ttys000", &state_dir, &project_dir, &active),
             tp_live
@@ -1137,7 +3175,7 @@ mod tests {
         fs::write(state_dir.join("session-ttys000.json"), "NOT VALID JSON{{{").unwrap();
 
         let tp = make_transcript(&project_dir, "real", &[]);
-        let active: std::collections::HashSet<String> = ["ttys000".into()].into();
+        let active = active_ttys(&["ttys000"]);
         assert_eq!(
             resolve_transcript("ttys000", &state_dir, &project_dir, &active),
             tp
@@ -1151,7 +3189,7 @@ mod tests {
         fs::create_dir_all(&project_dir).unwrap();
         let tp = make_transcript(&project_dir, "only", &[]);
 
-        let active: std::collections::HashSet<String> = ["ttys000".into()].into();
+        let active = active_ttys(&["ttys000"]);
         let result = resolve_transcript(
             "ttys000",
             Path::new("/nonexistent/claude-bar"),
@@ -1173,6 +3211,14 @@ mod tests {
         assert_eq!(parse_codex_content(content), (true, true));
     }
 
+    #[test]
+    fn test_parse_codex_content_turn_aborted() {
+        let content = r#"{"type":"response_item","payload":{"type":"function_call","name":"exec_command","call_id":"call_1","arguments":"{\"cmd\":\"xcodebuild\",\"sandbox_permissions\":\"require_escalated\"}"}}
+{"type":"event_msg","payload":{"type":"turn_aborted","reason":"interrupted"}}"#;
+        assert_eq!(parse_codex_content(content), (false, false));
+        assert_eq!(parse_codex_pending_tool(content), None);
+    }
+
     #[test]
     fn test_parse_codex_content_paired_call() {
         let content = r#"{"type":"response_item","payload":{"type":"function_call","name":"exec_command","call_id":"call_1","arguments":"{\"cmd\":\"ls\"}"}}
@@ -1190,7 +3236,7 @@ mod tests {
         )
         .unwrap();
         assert_eq!(
-            determine_codex_status(Some(&path.to_string_lossy())),
+            determine_codex_status(Some(&path.to_string_lossy()), &Thresholds::default()),
             Status::Pending
         );
     }
@@ -1227,6 +3273,45 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_recent_transcripts_and_cwd() {
+        let tmp = TempDir::new().unwrap();
+        let project = tmp.path().join("-workspaces-api");
+        fs::create_dir_all(&project).unwrap();
+        let fresh = project.join("fresh.jsonl");
+        let stale = project.join("stale.jsonl");
+        fs::write(
+            &fresh,
+            concat!(
+                r#"{"type":"user","cwd":"/workspaces/api","message":{"role":"user","content":"hi"}}"#,
+                "\n",
+                r#"{"type":"assistant","cwd":"/workspaces/api/server","message":{"role":"assistant","content":[]}}"#,
+                "\n",
+                r#"{"type":"summary","summary":"x"}"#,
+                "\n",
+            ),
+        )
+        .unwrap();
+        fs::write(&stale, "").unwrap();
+        fs::write(project.join("notes.txt"), "").unwrap();
+        set_mtime(&fresh.to_string_lossy(), 5.0);
+        set_mtime(&stale.to_string_lossy(), 3600.0);
+
+        assert_eq!(
+            recent_transcripts(tmp.path(), 600.0),
+            vec![fresh.to_string_lossy().to_string()]
+        );
+        assert_eq!(
+            recent_transcripts(&tmp.path().join("missing"), 600.0),
+            Vec::<String>::new()
+        );
+        assert_eq!(
+            transcript_cwd(&fresh.to_string_lossy()).as_deref(),
+            Some("/workspaces/api/server")
+        );
+        assert_eq!(parse_transcript_cwd(r#"{"type":"summary"}"#), None);
+    }
+
     #[test]
     fn test_project_hash() {
         assert_eq!(
@@ -1236,10 +3321,169 @@ mod tests {
         assert_eq!(project_hash("/a/b/c"), "-a-b-c");
     }
 
+    #[test]
+    fn test_project_hash_matches_claude_encoding() {
+        assert_eq!(
+            project_hash("/Users/me/github.com/org/site.io"),
+            "-Users-me-github-com-org-site-io"
+        );
+        assert_eq!(
+            project_hash("/Users/me/My Projects/app (v2)"),
+            "-Users-me-My-Projects-app--v2-"
+        );
+        assert_eq!(project_hash("/Users/me/.config"), "-Users-me--config");
+        // Non-ASCII: one dash per UTF-16 code unit
+        assert_eq!(project_hash("/tmp/café"), "-tmp-caf-");
+        assert_eq!(project_hash("/tmp/项目"), "-tmp---");
+        assert_eq!(project_hash("/tmp/🚀"), "-tmp---");
+        // Trailing slashes don't change the project
+        assert_eq!(project_hash("/a/b/"), project_hash("/a/b"));
+        assert_eq!(project_hash("/"), "-");
+        assert_eq!(project_hash(""), "");
+    }
+
+    #[test]
+    fn test_resolve_ignores_state_from_previous_process_on_tty() {
+        let tmp = TempDir::new().unwrap();
+        let state_dir = tmp.path().join("claude-bar");
+        let project_dir = tmp.path().join("project");
+        fs::create_dir_all(&state_dir).unwrap();
+        fs::create_dir_all(&project_dir).unwrap();
+
+        let old = make_transcript(&project_dir, "old", &[]);
+        set_mtime(&old, 60.0);
+        let current = make_transcript(&project_dir, "current", &[]);
+        let state = SessionState {
+            session_id: "old".into(),
+            transcript_path: old.clone(),
+            cwd: String::new(),
+            pid: Some(100),
+            process_started: Some(1_000),
+            ..Default::default()
+        };
+        fs::write(
+            state_dir.join("session-ttys000.json"),
+            serde_json::to_string(&state).unwrap(),
+        )
+        .unwrap();
+
+        let live = |pid, started_at| -> HashMap<String, ProcessIdentity> {
+            [("ttys000".to_string(), ProcessIdentity { pid, started_at })].into()
+        };
+        assert_eq!(
+            resolve_transcript("ttys000", &state_dir, &project_dir, &live(100, Some(1_001))),
+            old
+        );
+        // A new claude on the recycled TTY must not inherit the old transcript
+        assert_eq!(
+            resolve_transcript("ttys000", &state_dir, &project_dir, &live(200, Some(5_000))),
+            current
+        );
+    }
+
+    #[test]
+    fn test_find_state_for_tty_across_projects() {
+        let tmp = TempDir::new().unwrap();
+        let write = |project: &str, pid, cwd: &str, updated_at| {
+            let dir = tmp.path().join(project);
+            fs::create_dir_all(&dir).unwrap();
+            SessionState {
+                session_id: project.into(),
+                transcript_path: "/t.jsonl".into(),
+                cwd: cwd.into(),
+                pid: Some(pid),
+                updated_at: Some(updated_at),
+                ..Default::default()
+            }
+            .write(&dir.join("session-ttys004.json"))
+            .unwrap();
+        };
+        write("-old", 100, "/old", 2_000);
+        write("-api", 300, "/api", 5_000);
+        write("-docs", 300, "/docs", 5_100);
+
+        let live = ProcessIdentity {
+            pid: 300,
+            started_at: Some(4_000),
+        };
+        let state = find_state_for_tty_in(tmp.path(), "ttys004", &live).unwrap();
+        assert_eq!(state.cwd, "/docs");
+        assert!(find_state_for_tty_in(tmp.path(), "ttys005", &live).is_none());
+
+        let other = ProcessIdentity {
+            pid: 999,
+            started_at: None,
+        };
+        assert!(find_state_for_tty_in(tmp.path(), "ttys004", &other).is_none());
+    }
+
+    #[test]
+    fn test_resolve_transcript_uses_recorded_project_dir() {
+        let tmp = TempDir::new().unwrap();
+        let state_dir = tmp.path().join("state");
+        let real_dir = tmp.path().join("projects").join("-weird-encoding");
+        fs::create_dir_all(&state_dir).unwrap();
+        fs::create_dir_all(&real_dir).unwrap();
+
+        let recorded = make_transcript(&real_dir, "old", &[]);
+        let newer = make_transcript(&real_dir, "new", &[]);
+        set_mtime(&recorded, 60.0);
+        fs::write(
+            state_dir.join("session-ttys009.json"),
+            serde_json::json!({"session_id": "s", "transcript_path": recorded}).to_string(),
+        )
+        .unwrap();
+
+        // ttys001 has no state file and the computed project dir doesn't exist
+        let missing = tmp.path().join("projects").join("-computed");
+        let result = resolve_transcript("ttys001", &state_dir, &missing, &HashMap::new());
+        assert_eq!(result, newer);
+    }
+
+    #[test]
+    fn test_newer_state_dir_prefers_latest_write() {
+        let tmp = TempDir::new().unwrap();
+        let primary = tmp.path().join("primary");
+        let fallback = tmp.path().join("fallback");
+        fs::create_dir_all(&primary).unwrap();
+        fs::create_dir_all(&fallback).unwrap();
+        let write = |dir: &Path, at: u64| {
+            SessionState {
+                session_id: "s".into(),
+                updated_at: Some(at),
+                ..Default::default()
+            }
+            .write(&dir.join("session-ttys001.json"))
+            .unwrap();
+        };
+
+        // Nothing written anywhere: the regular directory
+        assert_eq!(
+            newer_state_dir(primary.clone(), fallback.clone(), "ttys001"),
+            primary
+        );
+        write(&fallback, 100);
+        assert_eq!(
+            newer_state_dir(primary.clone(), fallback.clone(), "ttys001"),
+            fallback
+        );
+        write(&primary, 200);
+        assert_eq!(
+            newer_state_dir(primary.clone(), fallback.clone(), "ttys001"),
+            primary
+        );
+    }
+
+    #[test]
+    fn test_state_file_name() {
+        assert_eq!(state_file_name("ttys003"), "session-ttys003.json");
+        assert_eq!(state_file_name("pts/3"), "session-pts-3.json");
+    }
+
     #[test]
     fn test_state_dir_for_cwd() {
         let home = std::env::var("HOME").unwrap_or_default();
-        let base = format!("{}/.claude/claude-bar", home);
+        let base = crate::paths::state_dir().join("sessions");
 
         // Non-empty cwd -> base/project-hash
         assert_eq!(
@@ -1249,5 +3493,348 @@ mod tests {
 
         // Empty cwd -> base directory itself
         assert_eq!(state_dir_for_cwd(""), std::path::PathBuf::from(&base));
+        assert_eq!(
+            legacy_state_dir_for_cwd("/Users/test/my_project"),
+            std::path::PathBuf::from(&home).join(".claude/claude-bar/-Users-test-my-project")
+        );
+    }
+
+    #[test]
+    fn test_migrate_state_dirs() {
+        let tmp = TempDir::new().unwrap();
+        let legacy = tmp.path().join("legacy");
+        let base = tmp.path().join("sessions");
+        let write = |path: &Path, at: u64| {
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            SessionState {
+                session_id: format!("s{}", at),
+                updated_at: Some(at),
+                ..Default::default()
+            }
+            .write(path)
+            .unwrap();
+        };
+        write(&legacy.join("-a/session-ttys001.json"), 100);
+        write(&legacy.join("-b/session-ttys002.json"), 100);
+        write(&base.join("-b/session-ttys002.json"), 200);
+        fs::write(legacy.join("permissions.json"), "{}").unwrap();
+
+        assert_eq!(migrate_state_dirs(&legacy, &base), 1);
+        let moved = SessionState::read(&base.join("-a/session-ttys001.json")).unwrap();
+        assert_eq!(moved.session_id, "s100");
+        // The newer file already in place wins; the stale one is dropped
+        let kept = SessionState::read(&base.join("-b/session-ttys002.json")).unwrap();
+        assert_eq!(kept.session_id, "s200");
+        assert!(!legacy.join("-a").exists());
+        assert!(!legacy.join("-b").exists());
+        assert!(legacy.join("permissions.json").exists());
+        assert_eq!(migrate_state_dirs(&legacy, &base), 0);
+    }
+
+    // ─── pending tool name tests ───
+
+    #[test]
+    fn test_pending_tool_unpaired() {
+        let lines = [
+            r#"{"type":"assistant","message":{"role":"assistant","content":[{"type":"tool_use","id":"t1","name":"Read","input":{}}]}}"#,
+            r#"{"type":"user","message":{"role":"user","content":[{"type":"tool_result","tool_use_id":"t1","content":"ok"}]}}"#,
+            r#"{"type":"assistant","message":{"role":"assistant","content":[{"type":"text","text":"Running it"},{"type":"tool_use","id":"t2","name":"Bash","input":{}}]}}"#,
+        ];
+        assert_eq!(parse_pending_tool(&lines.join("\n")), Some("Bash".into()));
+    }
+
+    #[test]
+    fn test_pending_tool_paired_is_none() {
+        let lines = [
+            r#"{"type":"assistant","message":{"role":"assistant","content":[{"type":"tool_use","id":"t1","name":"Bash","input":{}}]}}"#,
+            r#"{"type":"user","message":{"role":"user","content":[{"type":"tool_result","tool_use_id":"t1","content":"ok"}]}}"#,
+        ];
+        assert_eq!(parse_pending_tool(&lines.join("\n")), None);
+        assert_eq!(parse_pending_tool(""), None);
+    }
+
+    #[test]
+    fn test_pending_tool_matches_results_by_id() {
+        let lines = [
+            r#"{"type":"assistant","message":{"role":"assistant","content":[{"type":"tool_use","id":"t1","name":"Read","input":{}},{"type":"tool_use","id":"t2","name":"Bash","input":{}}]}}"#,
+            r#"{"type":"user","message":{"role":"user","content":[{"type":"tool_result","tool_use_id":"t1","content":"ok"}]}}"#,
+        ];
+        let content = lines.join("\n");
+        assert_eq!(parse_pending_tool(&content), Some("Bash".into()));
+        assert!(parse_transcript_content(&content).1);
+
+        let answered = format!(
+            "{}\n{}",
+            content,
+            r#"{"type":"user","message":{"role":"user","content":[{"type":"tool_result","tool_use_id":"t2","content":"ok"}]}}"#
+        );
+        assert_eq!(parse_pending_tool(&answered), None);
+        assert!(!parse_transcript_content(&answered).1);
+    }
+
+    #[test]
+    fn test_codex_pending_tool_prefers_escalation() {
+        let lines = [
+            r#"{"type":"response_item","payload":{"type":"function_call","name":"read_file","call_id":"c1","arguments":"{}"}}"#,
+            r#"{"type":"response_item","payload":{"type":"function_call","name":"shell","call_id":"c2","arguments":"{\"sandbox_permissions\":\"require_escalated\"}"}}"#,
+        ];
+        assert_eq!(
+            parse_codex_pending_tool(&lines.join("\n")),
+            Some("shell".into())
+        );
+    }
+
+    #[test]
+    fn test_codex_pending_tool_cleared_by_output() {
+        let lines = [
+            r#"{"type":"response_item","payload":{"type":"function_call","name":"shell","call_id":"c1","arguments":"{}"}}"#,
+            r#"{"type":"response_item","payload":{"type":"function_call_output","call_id":"c1","output":"ok"}}"#,
+        ];
+        assert_eq!(parse_codex_pending_tool(&lines.join("\n")), None);
+    }
+
+    /// `classify` for transcript state without API errors.
+    fn classify(
+        last_role: Option<&str>,
+        pending_tool: Option<&str>,
+        in_plan_mode: bool,
+        interrupted: bool,
+        age: f64,
+        t: &Thresholds,
+    ) -> (Status, Rule) {
+        super::classify(
+            &RuleInputs {
+                last_role,
+                pending_tool,
+                in_plan_mode,
+                interrupted,
+                api_errors: 0,
+                cpu_percent: None,
+                age,
+                tools: None,
+            },
+            t,
+        )
+    }
+
+    #[test]
+    fn test_classify_skips_disabled_rules() {
+        let mut t = Thresholds::default();
+        // A finished turn in plan mode waits for the user...
+        assert_eq!(
+            classify(Some("assistant"), None, true, false, 600.0, &t),
+            (Status::Pending, Rule::PlanMode)
+        );
+        // ...unless plan_mode is off
+        t.rules.insert(Rule::PlanMode, false);
+        assert_eq!(
+            classify(Some("assistant"), None, true, false, 600.0, &t),
+            (Status::Idle, Rule::TurnFinished)
+        );
+
+        // Without the plan-mode exemption a pending tool times out as usual
+        t.rules.insert(Rule::PlanModePending, false);
+        assert_eq!(
+            classify(None, Some("Bash"), true, false, 30.0, &t),
+            (Status::Pending, Rule::PendingTool)
+        );
+        assert_eq!(
+            classify(None, Some("Bash"), true, false, 300.0, &t),
+            (Status::Idle, Rule::PendingTimeout)
+        );
+
+        // The timeout rule doesn't fire before the timeout
+        t.rules.insert(Rule::PendingTool, false);
+        assert_eq!(
+            classify(None, Some("Bash"), false, false, 30.0, &t),
+            (Status::Idle, Rule::TurnFinished)
+        );
+        assert!(pipeline_rules().any(|r| r == Rule::TurnFinished));
+        assert!(!pipeline_rules().any(|r| r == Rule::Queued));
+    }
+
+    #[test]
+    fn test_classify_with_custom_thresholds() {
+        let strict = Thresholds {
+            pending_grace_secs: 1.0,
+            active_secs: 5.0,
+            idle_after_secs: 30.0,
+            ..Thresholds::default()
+        };
+        // Pending after the shorter grace period
+        assert_eq!(
+            classify(None, Some(""), false, false, 2.0, &strict),
+            (Status::Pending, Rule::PendingTool)
+        );
+        assert_eq!(
+            classify(None, Some(""), false, false, 2.0, &Thresholds::default()),
+            (Status::Active, Rule::RecentWrite)
+        );
+        // Processing degrades to idle sooner
+        assert_eq!(
+            classify(Some("user"), None, false, false, 45.0, &strict),
+            (Status::Idle, Rule::ProcessingTimeout)
+        );
+        assert_eq!(
+            classify(
+                Some("user"),
+                None,
+                false,
+                false,
+                45.0,
+                &Thresholds::default()
+            ),
+            (Status::Active, Rule::Processing)
+        );
+    }
+
+    #[test]
+    fn test_parse_queued_messages() {
+        let op = |operation: &str| {
+            format!(
+                r#"{{"type":"queue-operation","operation":"{}","timestamp":"2025-01-01T00:00:00Z","content":"also fix the tests"}}"#,
+                operation
+            )
+        };
+        let user = r#"{"type":"user","message":{"role":"user","content":"queue-operation is just text here"}}"#;
+
+        assert_eq!(parse_queued_messages(""), 0);
+        let lines = [op("enqueue"), op("enqueue"), user.to_string()];
+        assert_eq!(parse_queued_messages(&lines.join("\n")), 2);
+
+        let lines = [op("enqueue"), op("enqueue"), op("dequeue")];
+        assert_eq!(parse_queued_messages(&lines.join("\n")), 1);
+
+        let lines = [op("enqueue"), op("remove"), op("dequeue"), op("enqueue")];
+        assert_eq!(parse_queued_messages(&lines.join("\n")), 1);
+
+        let lines = [op("enqueue"), op("enqueue"), op("popAll")];
+        assert_eq!(parse_queued_messages(&lines.join("\n")), 0);
+    }
+
+    #[test]
+    fn test_transcript_stats_advance_incrementally() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("t.jsonl");
+        let prompt = |text: &str, ts: &str| {
+            format!(
+                r#"{{"type":"user","timestamp":"{}","message":{{"role":"user","content":"{}"}}}}"#,
+                ts, text
+            )
+        };
+        let lines = [
+            r#"{"type":"summary","summary":"Earlier work"}"#.to_string(),
+            prompt("fix the build", "2023-11-14T22:13:20.000Z"),
+            r#"{"type":"assistant","timestamp":"2023-11-14T22:13:25Z","message":{"role":"assistant","content":[{"type":"tool_use","id":"t1","name":"Bash","input":{}}]}}"#.to_string(),
+            r#"{"type":"user","message":{"role":"user","content":[{"type":"tool_result","tool_use_id":"t1","content":"ok"}]}}"#.to_string(),
+            r#"{"type":"user","isMeta":true,"message":{"role":"user","content":"<command-name>/clear</command-name>"}}"#.to_string(),
+            r#"{"type":"user","message":{"role":"user","content":[{"type":"text","text":"[Request interrupted by user]"}]}}"#.to_string(),
+        ];
+        fs::write(&path, lines.join("\n") + "\n").unwrap();
+
+        let mut stats = TranscriptStats::default();
+        stats.advance(&path).unwrap();
+        assert_eq!(stats.turns, 1);
+        assert_eq!(stats.started_at, Some(1700000000));
+        assert_eq!(stats.title(), Some("Earlier work"));
+        assert_eq!(stats.first_prompt.as_deref(), Some("fix the build"));
+
+        // Appended lines are read from where the last call stopped; a partial line waits
+        let mut file = fs::OpenOptions::new().append(true).open(&path).unwrap();
+        use std::io::Write;
+        writeln!(file, "{}", prompt("now the tests", "2023-11-14T23:00:00Z")).unwrap();
+        file.write_all(br#"{"type":"user","message""#).unwrap();
+        stats.advance(&path).unwrap();
+        assert_eq!(stats.turns, 2);
+        writeln!(file, r#":{{"role":"user","content":"and docs"}}}}"#).unwrap();
+        stats.advance(&path).unwrap();
+        assert_eq!(stats.turns, 3);
+        assert_eq!(stats.started_at, Some(1700000000));
+
+        // Rewritten shorter: start over
+        fs::write(&path, prompt("fresh", "2024-01-01T00:00:00Z") + "\n").unwrap();
+        stats.advance(&path).unwrap();
+        assert_eq!(stats.turns, 1);
+        assert_eq!(stats.started_at, Some(1704067200));
+        assert_eq!(stats.title(), Some("fresh"));
+
+        // A later summary replaces the earlier one
+        let mut file = fs::OpenOptions::new().append(true).open(&path).unwrap();
+        writeln!(
+            file,
+            r#"{{"type":"summary","summary":"Fix flaky CI on macOS runners"}}"#
+        )
+        .unwrap();
+        stats.advance(&path).unwrap();
+        assert_eq!(stats.title(), Some("Fix flaky CI on macOS runners"));
+    }
+
+    #[test]
+    fn test_title_line_and_prompt_text() {
+        assert_eq!(
+            title_line("\n  Refactor the parser  \nwith details").as_deref(),
+            Some("Refactor the parser")
+        );
+        assert_eq!(title_line("  \n "), None);
+        let long = "x".repeat(100);
+        let cut = title_line(&long).unwrap();
+        assert_eq!(cut.chars().count(), MAX_TITLE_CHARS);
+        assert!(cut.ends_with('…'));
+
+        assert_eq!(
+            prompt_text(
+                &serde_json::json!([{"type": "image"}, {"type": "text", "text": "what is this"}])
+            ),
+            Some("what is this")
+        );
+        assert_eq!(
+            prompt_text(&serde_json::json!("<command-name>/model</command-name>")),
+            None
+        );
+    }
+
+    #[test]
+    fn test_split_mcp_tool() {
+        assert_eq!(
+            split_mcp_tool("mcp__github__create_pr"),
+            Some(("github", "create_pr"))
+        );
+        assert_eq!(
+            split_mcp_tool("mcp__claude_ai_Linear__list_issues"),
+            Some(("claude_ai_Linear", "list_issues"))
+        );
+        assert_eq!(split_mcp_tool("Bash"), None);
+        assert_eq!(split_mcp_tool("mcp__github"), None);
+        assert_eq!(split_mcp_tool("mcp____x"), None);
+    }
+
+    #[test]
+    fn test_pending_timeout_overrides() {
+        let t: Thresholds = serde_json::from_str(
+            r#"{"pending_timeouts": {"mcp__github": 900, "mcp__github__merge_pr": 60, "Bash": 300}}"#,
+        )
+        .unwrap();
+        assert_eq!(t.pending_timeout("mcp__github__create_pr"), 900.0);
+        assert_eq!(t.pending_timeout("mcp__github__merge_pr"), 60.0);
+        assert_eq!(t.pending_timeout("Bash"), 300.0);
+        assert_eq!(t.pending_timeout("mcp__slack__post"), 120.0);
+        assert_eq!(t.pending_timeout("Read"), 120.0);
+
+        // A long MCP call stays pending past the default timeout
+        assert_eq!(
+            classify(
+                None,
+                Some("mcp__github__create_pr"),
+                false,
+                false,
+                300.0,
+                &t
+            ),
+            (Status::Pending, Rule::PendingTool)
+        );
+        assert_eq!(
+            classify(None, Some("mcp__slack__post"), false, false, 300.0, &t),
+            (Status::Idle, Rule::PendingTimeout)
+        );
     }
 }
@@ -0,0 +1,695 @@
+//! Tray icon for Linux desktops (`claude-bar tray`), over the
+//! StatusNotifierItem DBus protocol that KDE Plasma, GNOME (with the
+//! AppIndicator extension), Xfce and most other panels implement.
+//!
+//! The menu is published with the `com.canonical.dbusmenu` protocol. Sessions
+//! come from the daemon when it is running, otherwise from a local poll.
+//! The DBus side needs the `tray` feature (`cargo build --features tray`);
+//! the menu model below is always built so it can be tested anywhere.
+
+use crate::icon;
+use crate::render::{self, Colors, MenuView};
+use crate::state::Status;
+
+/// One entry of the tray's dropdown.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TrayItem {
+    /// Disabled section header
+    Header(String),
+    /// A session; clicking focuses its terminal. `key` is [`crate::state::SessionInfo::key`].
+    Session {
+        key: String,
+        label: String,
+    },
+    /// Disabled informational row
+    Note(String),
+    Separator,
+    Quit,
+}
+
+/// The dropdown for a menu view: "Focus Next Pending" while a session waits,
+/// sections and sessions as the menu bar app shows them, the collapsed idle
+/// row, the footer (totals, daemon state and any warning), then Quit.
+pub fn build_tray_menu(view: &MenuView) -> Vec<TrayItem> {
+    let rows = render::build_rows(&view.sessions, "claude-bar", &Colors::default());
+    let mut items = Vec::new();
+    let next = view
+        .next_pending
+        .as_ref()
+        .and_then(|key| view.sessions.iter().find(|s| s.key() == *key));
+    if let Some(session) = next {
+        items.push(TrayItem::Session {
+            key: session.key(),
+            label: format!("Focus Next Pending — {}", session.project_name()),
+        });
+        items.push(TrayItem::Separator);
+    }
+    let mut rows = rows.iter().zip(&view.sessions);
+    for section in &view.sections {
+        if let Some(title) = &section.title {
+            items.push(TrayItem::Header(title.clone()));
+        }
+        for (row, session) in rows.by_ref().take(section.count) {
+            let label = format!("{} — {}", row.label(), row.status_detail());
+            // Nothing to focus for sessions known only by their transcript
+            items.push(if session.detached {
+                TrayItem::Note(label)
+            } else {
+                TrayItem::Session {
+                    key: session.key(),
+                    label,
+                }
+            });
+        }
+    }
+    if !view.collapsed_idle.is_empty() {
+        items.push(TrayItem::Note(match view.collapsed_idle.len() {
+            1 => "1 idle session".to_string(),
+            n => format!("{} idle sessions", n),
+        }));
+    }
+    let any_sessions = !items.is_empty();
+    if !any_sessions {
+        items.push(TrayItem::Note("No Claude sessions".into()));
+    }
+    let footer = &view.footer;
+    let rows: Vec<TrayItem> = any_sessions
+        .then(|| footer.totals.clone())
+        .into_iter()
+        .chain(footer.daemon.clone())
+        .chain(footer.warning.as_ref().map(|w| format!("⚠ {}", w)))
+        .map(TrayItem::Note)
+        .collect();
+    if !rows.is_empty() {
+        items.push(TrayItem::Separator);
+        items.extend(rows);
+    }
+    items.push(TrayItem::Separator);
+    items.push(TrayItem::Quit);
+    items
+}
+
+/// StatusNotifierItem `Status`: panels draw attention to `NeedsAttention` and
+/// may hide `Passive` items.
+pub fn item_status(view: &MenuView) -> &'static str {
+    let all = || view.sessions.iter().chain(&view.collapsed_idle);
+    if all().any(|s| s.status == Status::Pending) {
+        "NeedsAttention"
+    } else if all().next().is_some() {
+        "Active"
+    } else {
+        "Passive"
+    }
+}
+
+/// Convert RGBA pixels to the ARGB32 (network byte order) the protocol's
+/// `IconPixmap` expects.
+pub fn argb_pixmap(rgba: &[u8]) -> Vec<u8> {
+    rgba.chunks_exact(4)
+        .flat_map(|p| [p[3], p[0], p[1], p[2]])
+        .collect()
+}
+
+/// Icon for the view as (width, height, ARGB32 pixels). A single idle dot
+/// stands in when there are no sessions, as an empty pixmap would hide the item.
+pub fn tray_pixmap(
+    view: &MenuView,
+    style: icon::IconStyle,
+    colors: &Colors,
+    scale: u32,
+) -> (i32, i32, Vec<u8>) {
+    let statuses = if view.icon.is_empty() {
+        vec![Status::Idle]
+    } else {
+        view.icon.clone()
+    };
+    match icon::make_icon_rgba(&statuses, style, colors, scale) {
+        Some((width, height, rgba)) => (width as i32, height as i32, argb_pixmap(&rgba)),
+        None => (0, 0, Vec::new()),
+    }
+}
+
+#[cfg(not(feature = "tray"))]
+pub fn run_tray(_interval: f64, _style: icon::IconStyle) -> Result<(), Box<dyn std::error::Error>> {
+    Err("claude-bar was built without the `tray` feature; rebuild with `cargo build --release --features tray`".into())
+}
+
+#[cfg(feature = "tray")]
+pub use dbus::run_tray;
+
+#[cfg(feature = "tray")]
+mod dbus {
+    use super::*;
+    use crate::config::Config;
+    use crate::daemon;
+    use crate::filter::SessionFilter;
+    use crate::protocol::{self, Request};
+    use crate::state::SessionInfo;
+    use crate::{focus, logging, serve};
+    use serde::Serialize;
+    use std::collections::HashMap;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+    use std::time::Duration;
+    use zbus::blocking::connection;
+    use zbus::object_server::SignalEmitter;
+    use zbus::zvariant::{ObjectPath, OwnedValue, StructureBuilder, Type, Value};
+    use zbus::{fdo, interface};
+
+    const ITEM_PATH: &str = "/StatusNotifierItem";
+    const MENU_PATH: &str = "/MenuBar";
+    /// Pixels per icon unit; panels scale the pixmap down to fit
+    const ICON_SCALE: u32 = 2;
+
+    type Pixmap = (i32, i32, Vec<u8>);
+
+    /// What the DBus objects publish, replaced on every poll that changes it.
+    #[derive(Default)]
+    struct TrayState {
+        sessions: Vec<SessionInfo>,
+        items: Vec<TrayItem>,
+        status: &'static str,
+        tooltip: String,
+        pixmap: Pixmap,
+        revision: u32,
+    }
+
+    type Shared = Arc<Mutex<TrayState>>;
+
+    struct StatusNotifierItem {
+        state: Shared,
+    }
+
+    #[interface(name = "org.kde.StatusNotifierItem")]
+    impl StatusNotifierItem {
+        #[zbus(property)]
+        fn category(&self) -> &str {
+            "ApplicationStatus"
+        }
+
+        #[zbus(property)]
+        fn id(&self) -> &str {
+            "claude-bar"
+        }
+
+        #[zbus(property)]
+        fn title(&self) -> &str {
+            "Claude Bar"
+        }
+
+        #[zbus(property)]
+        fn status(&self) -> String {
+            self.state.lock().unwrap().status.to_string()
+        }
+
+        #[zbus(property)]
+        fn icon_name(&self) -> &str {
+            ""
+        }
+
+        #[zbus(property)]
+        fn icon_pixmap(&self) -> Vec<Pixmap> {
+            vec![self.state.lock().unwrap().pixmap.clone()]
+        }
+
+        #[zbus(property)]
+        fn tool_tip(&self) -> (String, Vec<Pixmap>, String, String) {
+            let tooltip = self.state.lock().unwrap().tooltip.clone();
+            (String::new(), Vec::new(), "Claude Bar".into(), tooltip)
+        }
+
+        #[zbus(property)]
+        fn item_is_menu(&self) -> bool {
+            true
+        }
+
+        #[zbus(property)]
+        fn menu(&self) -> ObjectPath<'_> {
+            ObjectPath::from_static_str_unchecked(MENU_PATH)
+        }
+
+        /// Left click on panels that don't open the menu themselves: focus
+        /// the session that needs input, if any.
+        fn activate(&self, _x: i32, _y: i32) {
+            let state = self.state.lock().unwrap();
+            if let Some(session) = state.sessions.iter().find(|s| s.status == Status::Pending) {
+                spawn_focus(session.key());
+            }
+        }
+
+        fn secondary_activate(&self, _x: i32, _y: i32) {}
+
+        fn context_menu(&self, _x: i32, _y: i32) {}
+
+        fn scroll(&self, _delta: i32, _orientation: &str) {}
+
+        #[zbus(signal)]
+        async fn new_icon(emitter: &SignalEmitter<'_>) -> zbus::Result<()>;
+
+        #[zbus(signal)]
+        async fn new_tool_tip(emitter: &SignalEmitter<'_>) -> zbus::Result<()>;
+
+        #[zbus(signal)]
+        async fn new_status(emitter: &SignalEmitter<'_>, status: &str) -> zbus::Result<()>;
+    }
+
+    /// A dbusmenu layout node: `(ia{sv}av)`, children nested as variants.
+    #[derive(Debug, Serialize, Type)]
+    struct Layout {
+        id: i32,
+        properties: HashMap<String, OwnedValue>,
+        children: Vec<OwnedValue>,
+    }
+
+    fn owned<'a>(value: impl Into<Value<'a>>) -> OwnedValue {
+        value
+            .into()
+            .try_to_owned()
+            .expect("menu values hold no fds")
+    }
+
+    /// dbusmenu properties of an item (ids are 1-based positions in `items`).
+    fn item_properties(item: &TrayItem) -> HashMap<String, OwnedValue> {
+        let mut props = HashMap::new();
+        let mut label = |text: &str| props.insert("label".to_string(), owned(text));
+        match item {
+            TrayItem::Header(text) | TrayItem::Note(text) => {
+                label(text);
+                props.insert("enabled".into(), owned(false));
+            }
+            TrayItem::Session { label: text, .. } => {
+                label(text);
+            }
+            TrayItem::Quit => {
+                label("Quit");
+            }
+            TrayItem::Separator => {
+                props.insert("type".into(), owned("separator"));
+            }
+        }
+        props
+    }
+
+    fn layout_value(id: i32, item: &TrayItem) -> OwnedValue {
+        let structure = StructureBuilder::new()
+            .add_field(id)
+            .add_field(item_properties(item))
+            .add_field(Vec::<OwnedValue>::new())
+            .build()
+            .expect("non-empty structure");
+        owned(structure)
+    }
+
+    fn root_layout(items: &[TrayItem], depth: i32) -> Layout {
+        let mut properties = HashMap::new();
+        properties.insert("children-display".to_string(), owned("submenu"));
+        let children = if depth == 0 {
+            Vec::new()
+        } else {
+            items
+                .iter()
+                .enumerate()
+                .map(|(i, item)| layout_value(i as i32 + 1, item))
+                .collect()
+        };
+        Layout {
+            id: 0,
+            properties,
+            children,
+        }
+    }
+
+    struct DBusMenu {
+        state: Shared,
+        quit: Arc<AtomicBool>,
+    }
+
+    impl DBusMenu {
+        fn item(&self, id: i32) -> Option<TrayItem> {
+            let index = usize::try_from(id).ok()?.checked_sub(1)?;
+            self.state.lock().unwrap().items.get(index).cloned()
+        }
+
+        fn clicked(&self, id: i32) {
+            match self.item(id) {
+                Some(TrayItem::Session { key, .. }) => spawn_focus(key),
+                Some(TrayItem::Quit) => self.quit.store(true, Ordering::SeqCst),
+                _ => {}
+            }
+        }
+    }
+
+    #[interface(name = "com.canonical.dbusmenu")]
+    impl DBusMenu {
+        #[zbus(out_args("revision", "layout"))]
+        fn get_layout(
+            &self,
+            parent_id: i32,
+            recursion_depth: i32,
+            _property_names: Vec<String>,
+        ) -> fdo::Result<(u32, Layout)> {
+            let state = self.state.lock().unwrap();
+            if parent_id == 0 {
+                return Ok((state.revision, root_layout(&state.items, recursion_depth)));
+            }
+            let item = usize::try_from(parent_id)
+                .ok()
+                .and_then(|id| state.items.get(id.checked_sub(1)?))
+                .ok_or_else(|| fdo::Error::InvalidArgs(format!("no menu item {}", parent_id)))?;
+            let layout = Layout {
+                id: parent_id,
+                properties: item_properties(item),
+                children: Vec::new(),
+            };
+            Ok((state.revision, layout))
+        }
+
+        fn get_group_properties(
+            &self,
+            ids: Vec<i32>,
+            _property_names: Vec<String>,
+        ) -> Vec<(i32, HashMap<String, OwnedValue>)> {
+            ids.into_iter()
+                .filter_map(|id| Some((id, item_properties(&self.item(id)?))))
+                .collect()
+        }
+
+        fn get_property(&self, id: i32, name: &str) -> fdo::Result<OwnedValue> {
+            self.item(id)
+                .and_then(|item| item_properties(&item).remove(name))
+                .ok_or_else(|| fdo::Error::InvalidArgs(format!("no {} on item {}", name, id)))
+        }
+
+        fn event(&self, id: i32, event_id: &str, _data: OwnedValue, _timestamp: u32) {
+            if event_id == "clicked" {
+                self.clicked(id);
+            }
+        }
+
+        #[zbus(out_args("id_errors"))]
+        fn event_group(&self, events: Vec<(i32, String, OwnedValue, u32)>) -> Vec<i32> {
+            for (id, event_id, _, _) in &events {
+                if event_id == "clicked" {
+                    self.clicked(*id);
+                }
+            }
+            Vec::new()
+        }
+
+        fn about_to_show(&self, _id: i32) -> bool {
+            false
+        }
+
+        #[zbus(out_args("updates_needed", "id_errors"))]
+        fn about_to_show_group(&self, _ids: Vec<i32>) -> (Vec<i32>, Vec<i32>) {
+            (Vec::new(), Vec::new())
+        }
+
+        #[zbus(property)]
+        fn version(&self) -> u32 {
+            3
+        }
+
+        #[zbus(property)]
+        fn text_direction(&self) -> &str {
+            "ltr"
+        }
+
+        #[zbus(property)]
+        fn status(&self) -> &str {
+            "normal"
+        }
+
+        #[zbus(property)]
+        fn icon_theme_path(&self) -> Vec<String> {
+            Vec::new()
+        }
+
+        #[zbus(signal)]
+        async fn layout_updated(
+            emitter: &SignalEmitter<'_>,
+            revision: u32,
+            parent: i32,
+        ) -> zbus::Result<()>;
+    }
+
+    /// Focus a session through the daemon (which knows remote sessions), or
+    /// directly when it isn't running. Runs off the DBus thread.
+    fn spawn_focus(key: String) {
+        thread::spawn(move || {
+            let request = Request::Focus { tty: key.clone() };
+            let result = match protocol::send_request(&protocol::socket_path(), &request) {
+                Ok(response) if response.ok => Ok(()),
+                Ok(response) => Err(response.error.unwrap_or_default()),
+                Err(_) => serve::poll_sessions()
+                    .iter()
+                    .find(|s| s.key() == key)
+                    .ok_or_else(|| format!("no live session on {}", key))
+                    .and_then(|s| focus::focus_session(s).map_err(|e| e.to_string())),
+            };
+            if let Err(e) = result {
+                logging::warn("tray", &format!("focus {}: {}", key, e));
+            }
+        });
+    }
+
+    /// Publish the tray item and keep it updated every `interval` seconds
+    /// until Quit is chosen.
+    pub fn run_tray(
+        interval: f64,
+        style: icon::IconStyle,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let state = Shared::default();
+        let quit = Arc::new(AtomicBool::new(false));
+        let bus_name = format!("org.kde.StatusNotifierItem-{}-1", std::process::id());
+        let conn = connection::Builder::session()?
+            .name(bus_name.as_str())?
+            .serve_at(
+                ITEM_PATH,
+                StatusNotifierItem {
+                    state: state.clone(),
+                },
+            )?
+            .serve_at(
+                MENU_PATH,
+                DBusMenu {
+                    state: state.clone(),
+                    quit: quit.clone(),
+                },
+            )?
+            .build()?;
+        conn.call_method(
+            Some("org.kde.StatusNotifierWatcher"),
+            "/StatusNotifierWatcher",
+            Some("org.kde.StatusNotifierWatcher"),
+            "RegisterStatusNotifierItem",
+            &bus_name,
+        )
+        .map_err(|e| format!("no StatusNotifierItem host on the session bus: {}", e))?;
+        logging::info("tray", &format!("registered {}", bus_name));
+
+        let item = conn
+            .object_server()
+            .interface::<_, StatusNotifierItem>(ITEM_PATH)?;
+        let menu = conn.object_server().interface::<_, DBusMenu>(MENU_PATH)?;
+        let tick = Duration::from_millis(250);
+        let ticks_per_poll = ((interval.max(0.5) * 1000.0) as u128 / tick.as_millis()).max(1);
+
+        while !quit.load(Ordering::SeqCst) {
+            let config = Config::load();
+            let (sessions, view) = daemon::current_view(&config, &SessionFilter::default());
+            let items = build_tray_menu(&view);
+            let status = item_status(&view);
+            let pixmap = tray_pixmap(&view, style, &config.colors, ICON_SCALE);
+
+            let (menu_changed, icon_changed, tooltip_changed, status_changed, revision) = {
+                let mut state = state.lock().unwrap();
+                let menu_changed = state.items != items;
+                let icon_changed = state.pixmap != pixmap;
+                let tooltip_changed = state.tooltip != view.tooltip;
+                let status_changed = state.status != status;
+                if menu_changed {
+                    state.revision += 1;
+                }
+                state.sessions = sessions;
+                state.items = items;
+                state.status = status;
+                state.tooltip = view.tooltip.clone();
+                state.pixmap = pixmap;
+                (
+                    menu_changed,
+                    icon_changed,
+                    tooltip_changed,
+                    status_changed,
+                    state.revision,
+                )
+            };
+            let emitted = zbus::block_on(async {
+                let item = item.signal_emitter();
+                if menu_changed {
+                    DBusMenu::layout_updated(menu.signal_emitter(), revision, 0).await?;
+                }
+                if icon_changed {
+                    StatusNotifierItem::new_icon(item).await?;
+                }
+                if tooltip_changed {
+                    StatusNotifierItem::new_tool_tip(item).await?;
+                }
+                if status_changed {
+                    StatusNotifierItem::new_status(item, status).await?;
+                }
+                zbus::Result::Ok(())
+            });
+            if let Err(e) = emitted {
+                logging::warn("tray", &format!("signal: {}", e));
+            }
+
+            for _ in 0..ticks_per_poll {
+                if quit.load(Ordering::SeqCst) {
+                    break;
+                }
+                thread::sleep(tick);
+            }
+        }
+        Ok(())
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn test_layout_signature_and_encoding() {
+            assert_eq!(Layout::SIGNATURE.to_string(), "(ia{sv}av)");
+            let items = vec![TrayItem::Header("Local".into()), TrayItem::Quit];
+            let layout = root_layout(&items, -1);
+            assert_eq!(layout.children.len(), 2);
+            let ctxt = zbus::zvariant::serialized::Context::new_dbus(zbus::zvariant::LE, 0);
+            assert!(zbus::zvariant::to_bytes(ctxt, &(1u32, layout)).is_ok());
+            assert!(root_layout(&items, 0).children.is_empty());
+        }
+
+        #[test]
+        fn test_item_properties() {
+            let header = item_properties(&TrayItem::Header("Local".into()));
+            assert_eq!(header["label"], owned("Local"));
+            assert_eq!(header["enabled"], owned(false));
+            let separator = item_properties(&TrayItem::Separator);
+            assert_eq!(separator["type"], owned("separator"));
+            assert!(!separator.contains_key("label"));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render::{build_menu_view, GroupBy, MenuOptions};
+    use crate::state::{SessionInfo, Terminal};
+
+    fn session(tty: &str, cwd: &str, status: Status) -> SessionInfo {
+        SessionInfo {
+            terminal: Terminal::Unknown,
+            ..SessionInfo::for_test(tty, cwd, status)
+        }
+    }
+
+    #[test]
+    fn test_build_tray_menu() {
+        let mut waiting = session("/dev/pts/2", "/home/me/api", Status::Pending);
+        waiting.pending_tool = Some("Bash".into());
+        let sessions = vec![
+            session("/dev/pts/1", "/home/me/web", Status::Active),
+            waiting,
+        ];
+        let options = MenuOptions {
+            group: GroupBy::None,
+            ..MenuOptions::default()
+        };
+        let view = build_menu_view(&sessions, &options);
+        let items = build_tray_menu(&view);
+        assert_eq!(
+            &items[..2],
+            [
+                TrayItem::Session {
+                    key: "/dev/pts/2".into(),
+                    label: "Focus Next Pending — api".into(),
+                },
+                TrayItem::Separator
+            ]
+        );
+        assert!(items.contains(&TrayItem::Session {
+            key: "/dev/pts/2".into(),
+            label: "api — Needs input · Bash".into(),
+        }));
+        assert!(items.contains(&TrayItem::Session {
+            key: "/dev/pts/1".into(),
+            label: "web — Running".into(),
+        }));
+        assert_eq!(
+            &items[items.len() - 4..],
+            [
+                TrayItem::Separator,
+                TrayItem::Note("2 sessions · 1 needs input".into()),
+                TrayItem::Separator,
+                TrayItem::Quit
+            ]
+        );
+        assert_eq!(item_status(&view), "NeedsAttention");
+    }
+
+    #[test]
+    fn test_build_tray_menu_empty_and_collapsed() {
+        let view = build_menu_view(&[], &MenuOptions::default());
+        assert_eq!(
+            build_tray_menu(&view),
+            vec![
+                TrayItem::Note("No Claude sessions".into()),
+                TrayItem::Separator,
+                TrayItem::Quit
+            ]
+        );
+        assert_eq!(item_status(&view), "Passive");
+
+        // A daemon warning shows even without sessions
+        let mut view = view;
+        view.footer.warning = Some("Daemon not responding (timed out)".into());
+        assert_eq!(
+            build_tray_menu(&view),
+            vec![
+                TrayItem::Note("No Claude sessions".into()),
+                TrayItem::Separator,
+                TrayItem::Note("⚠ Daemon not responding (timed out)".into()),
+                TrayItem::Separator,
+                TrayItem::Quit
+            ]
+        );
+
+        let options = MenuOptions {
+            collapse_idle: true,
+            ..MenuOptions::default()
+        };
+        let sessions = vec![
+            session("/dev/pts/1", "/home/me/web", Status::Idle),
+            session("/dev/pts/2", "/home/me/api", Status::Idle),
+        ];
+        let view = build_menu_view(&sessions, &options);
+        let items = build_tray_menu(&view);
+        assert!(items.contains(&TrayItem::Note("2 idle sessions".into())));
+        assert_eq!(item_status(&view), "Active");
+    }
+
+    #[test]
+    fn test_argb_pixmap_and_placeholder_icon() {
+        assert_eq!(
+            argb_pixmap(&[1, 2, 3, 4, 5, 6, 7, 8]),
+            vec![4, 1, 2, 3, 8, 5, 6, 7]
+        );
+        let view = build_menu_view(&[], &MenuOptions::default());
+        let (width, height, pixels) =
+            tray_pixmap(&view, icon::IconStyle::Grid, &Colors::default(), 1);
+        assert!(width > 0 && height > 0);
+        assert_eq!(pixels.len(), (width * height * 4) as usize);
+    }
+}
@@ -0,0 +1,361 @@
+//! `claude-bar update`: version check and self-update from GitHub releases.
+//!
+//! The latest release is looked up with `curl`; its binary for this target
+//! (`claude-bar-<arch>-apple-darwin`, or `-unknown-linux-gnu` on Linux) is
+//! downloaded next to the running executable, checked against the release's
+//! `<asset>.sha256`, and renamed over it, so a failed download
+//! never leaves a broken binary behind. Other platforms can only check. Every
+//! check is recorded in `update.json` in the state dir (see [`crate::paths`]),
+//! which the menu bar app reads for its "Update available" item.
+
+use crate::logging;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+pub const RELEASES_URL: &str =
+    "https://api.github.com/repos/qianxiaofeng/claude-menubar/releases/latest";
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Release {
+    pub tag_name: String,
+    #[serde(default)]
+    pub html_url: String,
+    #[serde(default)]
+    pub assets: Vec<Asset>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Asset {
+    pub name: String,
+    pub browser_download_url: String,
+}
+
+impl Release {
+    fn asset(&self, name: &str) -> Option<&Asset> {
+        self.assets.iter().find(|a| a.name == name)
+    }
+}
+
+/// Result of the last check, shared with the menu bar app.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UpdateStatus {
+    pub checked_at: u64,
+    pub current: String,
+    pub latest: String,
+    pub available: bool,
+    /// Release page
+    pub url: String,
+}
+
+pub fn default_path() -> PathBuf {
+    crate::paths::state_dir().join("update.json")
+}
+
+pub fn read_status(path: &Path) -> Option<UpdateStatus> {
+    fs::read_to_string(path)
+        .ok()
+        .and_then(|c| serde_json::from_str(&c).ok())
+}
+
+fn write_status(path: &Path, status: &UpdateStatus) {
+    if let Some(parent) = path.parent() {
+        let _ = fs::create_dir_all(parent);
+    }
+    if let Ok(json) = serde_json::to_string_pretty(status) {
+        let _ = fs::write(path, json);
+    }
+}
+
+/// `v1.2.3` / `1.2` as comparable numbers; pre-release suffixes are ignored.
+pub fn parse_version(s: &str) -> Option<(u64, u64, u64)> {
+    let s = s.trim().trim_start_matches('v');
+    let core = s.split(['-', '+']).next()?;
+    let mut parts = core.split('.').map(|p| p.parse::<u64>());
+    let major = parts.next()?.ok()?;
+    let minor = parts.next().unwrap_or(Ok(0)).ok()?;
+    let patch = parts.next().unwrap_or(Ok(0)).ok()?;
+    Some((major, minor, patch))
+}
+
+pub fn is_newer(latest: &str, current: &str) -> bool {
+    match (parse_version(latest), parse_version(current)) {
+        (Some(latest), Some(current)) => latest > current,
+        _ => false,
+    }
+}
+
+/// Release asset holding the binary for `arch` on `os` (as in
+/// [`std::env::consts`]); `None` where no binaries are published.
+pub fn asset_name_for(os: &str, arch: &str) -> Option<String> {
+    let vendor_os = match os {
+        "macos" => "apple-darwin",
+        "linux" => "unknown-linux-gnu",
+        _ => return None,
+    };
+    Some(format!("claude-bar-{}-{}", arch, vendor_os))
+}
+
+/// Release asset holding the binary for this machine.
+pub fn asset_name() -> Option<String> {
+    asset_name_for(std::env::consts::OS, std::env::consts::ARCH)
+}
+
+/// The hex digest for `asset` in a `.sha256` file: either a bare digest or
+/// `shasum` output (`<digest>  <name>` lines).
+pub fn parse_checksum(text: &str, asset: &str) -> Option<String> {
+    let is_digest = |s: &str| s.len() == 64 && s.chars().all(|c| c.is_ascii_hexdigit());
+    text.lines().find_map(|line| {
+        let mut fields = line.split_whitespace();
+        let digest = fields.next()?;
+        let named = match fields.next() {
+            Some(name) => name.trim_start_matches('*') == asset,
+            None => true,
+        };
+        (named && is_digest(digest)).then(|| digest.to_lowercase())
+    })
+}
+
+fn curl(url: &str, out: Option<&Path>) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let mut cmd = Command::new("curl");
+    cmd.args(["-fsSL", "--max-time", "120"])
+        .args(["-H", "Accept: application/vnd.github+json"])
+        .args([
+            "-H",
+            &format!("User-Agent: claude-bar/{}", env!("CARGO_PKG_VERSION")),
+        ]);
+    if let Some(out) = out {
+        cmd.arg("-o").arg(out);
+    }
+    let output = cmd.arg(url).output()?;
+    if !output.status.success() {
+        return Err(format!(
+            "curl {}: {}",
+            url,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )
+        .into());
+    }
+    Ok(output.stdout)
+}
+
+/// Lowercase hex SHA-256 of a file.
+fn sha256_of(path: &Path) -> Result<String, Box<dyn std::error::Error>> {
+    use std::io::Read;
+
+    let mut file = fs::File::open(path)?;
+    let mut context = ring::digest::Context::new(&ring::digest::SHA256);
+    let mut buf = vec![0u8; 64 * 1024];
+    loop {
+        let n = file.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        context.update(&buf[..n]);
+    }
+    Ok(context
+        .finish()
+        .as_ref()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect())
+}
+
+pub fn fetch_latest() -> Result<Release, Box<dyn std::error::Error>> {
+    let body = curl(RELEASES_URL, None)?;
+    Ok(serde_json::from_slice(&body)?)
+}
+
+/// Look up the latest release and record the result for the menu bar app.
+pub fn check(now: u64) -> Result<(Release, UpdateStatus), Box<dyn std::error::Error>> {
+    let current = env!("CARGO_PKG_VERSION");
+    let release = fetch_latest()?;
+    let status = UpdateStatus {
+        checked_at: now,
+        current: current.to_string(),
+        latest: release.tag_name.trim_start_matches('v').to_string(),
+        available: is_newer(&release.tag_name, current),
+        url: release.html_url.clone(),
+    };
+    write_status(&default_path(), &status);
+    Ok((release, status))
+}
+
+/// Download, verify and swap in the release binary for this machine.
+fn install(release: &Release, exe: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let name = asset_name().ok_or_else(|| {
+        format!(
+            "Self-update isn't supported on {}; install the release manually",
+            std::env::consts::OS
+        )
+    })?;
+    let binary = release
+        .asset(&name)
+        .ok_or_else(|| format!("Release {} has no {} asset", release.tag_name, name))?;
+    let checksum_name = format!("{}.sha256", name);
+    let checksum = release
+        .asset(&checksum_name)
+        .ok_or_else(|| format!("Release {} has no {}", release.tag_name, checksum_name))?;
+    let expected = parse_checksum(
+        &String::from_utf8_lossy(&curl(&checksum.browser_download_url, None)?),
+        &name,
+    )
+    .ok_or_else(|| format!("Couldn't read a digest from {}", checksum_name))?;
+
+    // Same directory as the executable, so the rename below is atomic
+    let staged = exe.with_extension("new");
+    let result = (|| {
+        curl(&binary.browser_download_url, Some(&staged))?;
+        let actual = sha256_of(&staged)?;
+        if actual != expected {
+            return Err(format!(
+                "Checksum mismatch for {}: expected {}, got {}",
+                name, expected, actual
+            )
+            .into());
+        }
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&staged, fs::Permissions::from_mode(0o755))?;
+        fs::rename(&staged, exe)?;
+        Ok(())
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&staged);
+    }
+    result
+}
+
+pub fn run_update(check_only: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let (release, status) = check(now)?;
+    if !status.available {
+        println!("claude-bar {} is up to date", status.current);
+        return Ok(());
+    }
+    if check_only {
+        println!(
+            "claude-bar {} is available (installed: {}): {}",
+            status.latest, status.current, status.url
+        );
+        return Ok(());
+    }
+
+    let exe = std::env::current_exe()?.canonicalize()?;
+    install(&release, &exe)?;
+    write_status(
+        &default_path(),
+        &UpdateStatus {
+            current: status.latest.clone(),
+            available: false,
+            ..status.clone()
+        },
+    );
+    logging::info(
+        "update",
+        &format!("updated {} -> {}", status.current, status.latest),
+    );
+    println!(
+        "Updated claude-bar {} -> {} ({}); restart the daemon to pick it up",
+        status.current,
+        status.latest,
+        exe.display()
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sha256_of() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("claude-bar.new");
+        fs::write(&path, "abc").unwrap();
+        assert_eq!(
+            sha256_of(&path).unwrap(),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert!(sha256_of(&dir.path().join("missing")).is_err());
+    }
+
+    #[test]
+    fn test_asset_name_follows_target() {
+        assert_eq!(
+            asset_name_for("macos", "aarch64").as_deref(),
+            Some("claude-bar-aarch64-apple-darwin")
+        );
+        assert_eq!(
+            asset_name_for("linux", "x86_64").as_deref(),
+            Some("claude-bar-x86_64-unknown-linux-gnu")
+        );
+        assert_eq!(asset_name_for("freebsd", "x86_64"), None);
+    }
+
+    #[test]
+    fn test_parse_version_and_is_newer() {
+        assert_eq!(parse_version("v1.2.3"), Some((1, 2, 3)));
+        assert_eq!(parse_version("0.4"), Some((0, 4, 0)));
+        assert_eq!(parse_version("2.0.0-rc.1"), Some((2, 0, 0)));
+        assert_eq!(parse_version("latest"), None);
+        assert!(is_newer("v0.2.0", "0.1.9"));
+        assert!(is_newer("0.10.0", "0.9.0"));
+        assert!(!is_newer("v0.1.0", "0.1.0"));
+        assert!(!is_newer("nightly", "0.1.0"));
+    }
+
+    #[test]
+    fn test_parse_checksum() {
+        let digest = "ab".repeat(32);
+        assert_eq!(
+            parse_checksum(&format!("{}\n", digest), "x"),
+            Some(digest.clone())
+        );
+        let listing = format!(
+            "{}  claude-bar-x86_64-apple-darwin\n{}  claude-bar-aarch64-apple-darwin\n",
+            "00".repeat(32),
+            digest.to_uppercase()
+        );
+        assert_eq!(
+            parse_checksum(&listing, "claude-bar-aarch64-apple-darwin"),
+            Some(digest)
+        );
+        assert_eq!(
+            parse_checksum(&listing, "claude-bar-arm-apple-darwin"),
+            None
+        );
+        assert_eq!(parse_checksum("not a digest", "x"), None);
+        assert_eq!(
+            parse_checksum(&format!("{}  other", "00".repeat(32)), "x"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_release_json_and_status_roundtrip() {
+        let release: Release = serde_json::from_str(
+            r#"{"tag_name":"v0.2.0","html_url":"https://example.com/r","assets":[{"name":"claude-bar-aarch64-apple-darwin","browser_download_url":"https://example.com/a","size":1}]}"#,
+        )
+        .unwrap();
+        assert!(release.asset("claude-bar-aarch64-apple-darwin").is_some());
+        assert!(release
+            .asset("claude-bar-aarch64-apple-darwin.sha256")
+            .is_none());
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("update.json");
+        let status = UpdateStatus {
+            checked_at: 10,
+            current: "0.1.0".into(),
+            latest: "0.2.0".into(),
+            available: true,
+            url: release.html_url,
+        };
+        write_status(&path, &status);
+        assert_eq!(read_status(&path), Some(status));
+    }
+}
@@ -0,0 +1,304 @@
+//! Minimal RFC 6455 WebSocket support (server side, text frames only).
+
+use base64::Engine;
+use std::io::{self, Read, Write};
+
+const HANDSHAKE_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+const OP_TEXT: u8 = 0x1;
+const OP_CLOSE: u8 = 0x8;
+const OP_PING: u8 = 0x9;
+const OP_PONG: u8 = 0xA;
+
+/// A frame received from a client.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Frame {
+    Text(String),
+    Ping(Vec<u8>),
+    Close,
+    /// Binary/continuation/pong frames we don't act on.
+    Other,
+}
+
+/// Longest upgrade request accepted.
+const MAX_REQUEST_BYTES: usize = 16 * 1024;
+
+/// Compute the `Sec-WebSocket-Accept` value for a client key.
+pub fn accept_key(client_key: &str) -> String {
+    let mut input = client_key.trim().as_bytes().to_vec();
+    input.extend_from_slice(HANDSHAKE_GUID.as_bytes());
+    let digest = ring::digest::digest(&ring::digest::SHA1_FOR_LEGACY_USE_ONLY, &input);
+    base64::engine::general_purpose::STANDARD.encode(digest.as_ref())
+}
+
+/// Whether a client sending `origin` may connect. Browsers send the page's
+/// origin and don't apply CORS to WebSockets, so any page could otherwise read
+/// every session; the Stream Deck plugin connects without one (or from a
+/// `file://` page).
+pub fn origin_allowed(origin: Option<&str>) -> bool {
+    origin.is_none_or(|o| o.trim().starts_with("file://"))
+}
+
+/// The `Sec-WebSocket-Key` of an upgrade request from an allowed origin.
+pub fn parse_request(request: &str) -> io::Result<String> {
+    let mut lines = request.lines();
+    if !lines.next().is_some_and(|l| l.starts_with("GET ")) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "not a GET request",
+        ));
+    }
+    let (mut key, mut origin) = (None, None);
+    for line in lines {
+        if let Some((name, value)) = line.split_once(':') {
+            let name = name.trim();
+            if name.eq_ignore_ascii_case("sec-websocket-key") {
+                key = Some(value.trim().to_string());
+            } else if name.eq_ignore_ascii_case("origin") {
+                origin = Some(value.trim().to_string());
+            }
+        }
+    }
+    if !origin_allowed(origin.as_deref()) {
+        return Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            format!("origin {} not allowed", origin.unwrap_or_default()),
+        ));
+    }
+    key.ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "missing Sec-WebSocket-Key"))
+}
+
+/// Read the HTTP request head. Bytes are read one at a time so that frames
+/// the client sends right behind it stay in the stream.
+fn read_request(stream: &mut impl Read) -> io::Result<String> {
+    let mut head = Vec::new();
+    let mut byte = [0u8; 1];
+    while !head.ends_with(b"\r\n\r\n") {
+        if head.len() >= MAX_REQUEST_BYTES {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "request too large",
+            ));
+        }
+        if stream.read(&mut byte)? == 0 {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "handshake"));
+        }
+        head.push(byte[0]);
+    }
+    Ok(String::from_utf8_lossy(&head).to_string())
+}
+
+/// Read the HTTP upgrade request and answer with the 101 handshake, or with
+/// 403 for a request from a browser page.
+pub fn handshake(stream: &mut (impl Read + Write)) -> io::Result<()> {
+    let key = match parse_request(&read_request(stream)?) {
+        Ok(key) => key,
+        Err(e) => {
+            let status = if e.kind() == io::ErrorKind::PermissionDenied {
+                "403 Forbidden"
+            } else {
+                "400 Bad Request"
+            };
+            let _ = write!(stream, "HTTP/1.1 {}\r\nContent-Length: 0\r\n\r\n", status);
+            return Err(e);
+        }
+    };
+    let response = format!(
+        "HTTP/1.1 101 Switching Protocols\r\n\
+         Upgrade: websocket\r\n\
+         Connection: Upgrade\r\n\
+         Sec-WebSocket-Accept: {}\r\n\r\n",
+        accept_key(&key)
+    );
+    stream.write_all(response.as_bytes())
+}
+
+/// Encode an unmasked server frame.
+fn encode_frame(opcode: u8, payload: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(payload.len() + 10);
+    out.push(0x80 | opcode);
+    let len = payload.len();
+    if len < 126 {
+        out.push(len as u8);
+    } else if len <= u16::MAX as usize {
+        out.push(126);
+        out.extend_from_slice(&(len as u16).to_be_bytes());
+    } else {
+        out.push(127);
+        out.extend_from_slice(&(len as u64).to_be_bytes());
+    }
+    out.extend_from_slice(payload);
+    out
+}
+
+pub fn write_text(stream: &mut impl Write, text: &str) -> io::Result<()> {
+    stream.write_all(&encode_frame(OP_TEXT, text.as_bytes()))
+}
+
+pub fn write_pong(stream: &mut impl Write, payload: &[u8]) -> io::Result<()> {
+    stream.write_all(&encode_frame(OP_PONG, payload))
+}
+
+pub fn write_close(stream: &mut impl Write) -> io::Result<()> {
+    stream.write_all(&encode_frame(OP_CLOSE, &[]))
+}
+
+/// Read one (possibly masked) client frame.
+pub fn read_frame(stream: &mut impl Read) -> io::Result<Frame> {
+    let mut header = [0u8; 2];
+    stream.read_exact(&mut header)?;
+    let opcode = header[0] & 0x0F;
+    let masked = header[1] & 0x80 != 0;
+    let len = match header[1] & 0x7F {
+        126 => {
+            let mut b = [0u8; 2];
+            stream.read_exact(&mut b)?;
+            u16::from_be_bytes(b) as u64
+        }
+        127 => {
+            let mut b = [0u8; 8];
+            stream.read_exact(&mut b)?;
+            u64::from_be_bytes(b)
+        }
+        n => n as u64,
+    };
+    // Control messages from a Stream Deck plugin are tiny; refuse anything huge.
+    if len > 1 << 20 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "frame too large",
+        ));
+    }
+
+    let mut mask = [0u8; 4];
+    if masked {
+        stream.read_exact(&mut mask)?;
+    }
+    let mut payload = vec![0u8; len as usize];
+    stream.read_exact(&mut payload)?;
+    if masked {
+        for (i, b) in payload.iter_mut().enumerate() {
+            *b ^= mask[i % 4];
+        }
+    }
+
+    Ok(match opcode {
+        OP_TEXT => Frame::Text(String::from_utf8_lossy(&payload).to_string()),
+        OP_PING => Frame::Ping(payload),
+        OP_CLOSE => Frame::Close,
+        _ => Frame::Other,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_accept_key_rfc_example() {
+        // Example from RFC 6455 section 1.3
+        assert_eq!(
+            accept_key("dGhlIHNhbXBsZSBub25jZQ=="),
+            "s3pPLMBiTxaQ9kYGzzhZRbK+xOo="
+        );
+    }
+
+    /// A client connection: what it sends, and what the server wrote back.
+    struct Conn {
+        input: io::Cursor<Vec<u8>>,
+        output: Vec<u8>,
+    }
+
+    impl Read for Conn {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.input.read(buf)
+        }
+    }
+
+    impl Write for Conn {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.output.write(buf)
+        }
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn conn(headers: &str, after: &[u8]) -> Conn {
+        let mut input = format!(
+            "GET / HTTP/1.1\r\nHost: 127.0.0.1:9001\r\nUpgrade: websocket\r\n\
+             Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n{}\r\n",
+            headers
+        )
+        .into_bytes();
+        input.extend_from_slice(after);
+        Conn {
+            input: io::Cursor::new(input),
+            output: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_handshake_keeps_following_frames() {
+        let mut client = conn("", &[0x88, 0x00]);
+        handshake(&mut client).unwrap();
+        let response = String::from_utf8(client.output.clone()).unwrap();
+        assert!(response.starts_with("HTTP/1.1 101 "));
+        assert!(response.contains("Sec-WebSocket-Accept: s3pPLMBiTxaQ9kYGzzhZRbK+xOo=\r\n"));
+        // A close frame sent along with the request is still there
+        assert_eq!(read_frame(&mut client).unwrap(), Frame::Close);
+    }
+
+    #[test]
+    fn test_handshake_rejects_browser_origins() {
+        let mut page = conn("Origin: https://example.com\r\n", &[]);
+        let err = handshake(&mut page).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+        assert!(String::from_utf8(page.output)
+            .unwrap()
+            .starts_with("HTTP/1.1 403 Forbidden\r\n"));
+        // Sandboxed iframes send `null`
+        assert!(handshake(&mut conn("Origin: null\r\n", &[])).is_err());
+        assert!(handshake(&mut conn("origin: file://\r\n", &[])).is_ok());
+
+        let mut huge = conn(&"X-Pad: y\r\n".repeat(2000), &[]);
+        assert_eq!(
+            handshake(&mut huge).unwrap_err().kind(),
+            io::ErrorKind::InvalidData
+        );
+    }
+
+    #[test]
+    fn test_encode_frame_lengths() {
+        let short = encode_frame(OP_TEXT, b"hi");
+        assert_eq!(short, vec![0x81, 2, b'h', b'i']);
+
+        let medium = encode_frame(OP_TEXT, &[0u8; 300]);
+        assert_eq!(&medium[..4], &[0x81, 126, 0x01, 0x2C]);
+        assert_eq!(medium.len(), 4 + 300);
+    }
+
+    #[test]
+    fn test_read_masked_text_frame() {
+        let payload = br#"{"cmd":"focus","index":2}"#;
+        let mask = [0x11, 0x22, 0x33, 0x44];
+        let mut frame = vec![0x81, 0x80 | payload.len() as u8];
+        frame.extend_from_slice(&mask);
+        frame.extend(payload.iter().enumerate().map(|(i, b)| b ^ mask[i % 4]));
+
+        let got = read_frame(&mut &frame[..]).unwrap();
+        assert_eq!(
+            got,
+            Frame::Text(String::from_utf8(payload.to_vec()).unwrap())
+        );
+    }
+
+    #[test]
+    fn test_read_control_frames() {
+        assert_eq!(read_frame(&mut &[0x88, 0x00][..]).unwrap(), Frame::Close);
+        assert_eq!(
+            read_frame(&mut &[0x89, 0x01, 0x7A][..]).unwrap(),
+            Frame::Ping(vec![0x7A])
+        );
+    }
+}
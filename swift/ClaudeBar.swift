@@ -10,20 +10,193 @@ struct SessionInfo: Decodable {
     let terminal: String
     let transcript: String?
     let status: String
+    let pending_reason: PendingReason?
+    let queued: Int?
+    let turns: Int?
+    let started_at: Double?
+    let title: String?
+    let remote: RemoteInfo?
+    let nested: [NestedAgent]?
+    let container: ContainerInfo?
+    /// Known only from its transcript: nothing to focus
+    let detached: Bool?
+    /// Status rule that decided `status` (e.g. "pending_tool")
+    let rule: String?
+    /// Agent CPU since the previous poll, percent of one core
+    let cpu_percent: Double?
+    /// `claude_profiles` name, e.g. "work" (cleared when `show_profiles` is off)
+    let profile: String?
+    /// Selected variables of the agent's environment (ANTHROPIC_MODEL, CLAUDE_BAR_*)
+    let env: [String: String]?
+    /// Subagents (Task tool calls) still running
+    let subagents: Int?
+    /// iTerm2 session unique ID of the tab, which `focus --terminal-id` goes to directly
+    let terminal_id: String?
+
+    /// Local TTY to focus: the SSH tab for remote sessions, none inside containers
+    var focusTTY: String? {
+        if container != nil || detached == true { return nil }
+        return remote == nil ? tty : remote?.local_tty
+    }
+}
+
+/// Why a pending session needs the user (mirrors Rust PendingReason)
+struct PendingReason: Decodable {
+    let kind: String
+    let tool: String?
+    let server: String?
+
+    var label: String {
+        switch kind {
+        case "question":      return "Question"
+        case "plan_approval": return "Plan approval"
+        default:
+            // MCP tools: "create_pr (github)" instead of "mcp__github__create_pr"
+            if let server = server, let tool = tool, let name = tool.components(separatedBy: "__").last {
+                return "\(name) (\(server))"
+            }
+            return tool ?? "Permission"
+        }
+    }
+
+    var sfSymbol: String {
+        switch kind {
+        case "question":      return "questionmark.circle.fill"
+        case "plan_approval": return "list.bullet.clipboard.fill"
+        default:              return "hand.raised.fill"
+        }
+    }
+}
+
+struct NestedAgent: Decodable {
+    let pid: UInt32
+    let provider: String
+}
+
+struct ContainerInfo: Decodable {
+    let name: String?
+    let local_folder: String?
+}
+
+struct RemoteInfo: Decodable {
+    let host: String
+    let local_tty: String?
+}
+
+/// Dropdown/icon options (mirrors Rust MenuOptions)
+struct MenuOptions: Decodable {
+    let hide_idle_in_icon: Bool
+    let collapse_idle: Bool
+    let pinned_only: Bool
+    let pinned_projects: [String]?
+    let sort: String
+    let group: String
+    let confirm_responses: Bool
+    let privacy_mode: Bool
+    let show_profiles: Bool?
+}
+
+/// Header over a run of consecutive sessions (mirrors Rust MenuSection)
+struct MenuSection: Decodable {
+    let title: String?
+    let count: Int
+}
+
+/// `poll --format menu` output (mirrors Rust MenuView)
+struct MenuView: Decodable {
+    let sessions: [SessionInfo]
+    let sections: [MenuSection]
+    let collapsed_idle: [SessionInfo]
+    let icon: [String]
+    let tooltip: String
+    /// Key of the session that has been pending longest
+    let next_pending: String?
+    let options: MenuOptions
+    let footer: MenuFooter
+
+    /// Listed sessions, then collapsed ones; menu item tags index into this
+    var allSessions: [SessionInfo] { sessions + collapsed_idle }
+
+    static let empty = MenuView(
+        sessions: [], sections: [], collapsed_idle: [], icon: [], tooltip: "", next_pending: nil,
+        options: MenuOptions(hide_idle_in_icon: false, collapse_idle: false, pinned_only: false,
+                             pinned_projects: nil, sort: "status", group: "host", confirm_responses: true,
+                             privacy_mode: false, show_profiles: true),
+        footer: MenuFooter(totals: "", daemon: nil, warning: nil)
+    )
+}
+
+/// Totals and daemon state for the bottom of the dropdown
+struct MenuFooter: Decodable {
+    let totals: String
+    let daemon: String?
+    let warning: String?
+}
+
+/// A missing macOS privacy permission, from ~/.local/state/claude-bar/permissions.json
+struct PermissionIssue: Decodable {
+    let permission: String
+    let app: String
+
+    var title: String {
+        permission == "accessibility"
+            ? "⚠︎ Grant Accessibility access…"
+            : "⚠︎ Grant Automation access to \(app)…"
+    }
+
+    var settingsURL: URL? {
+        let pane = permission == "accessibility" ? "Privacy_Accessibility" : "Privacy_Automation"
+        return URL(string: "x-apple.systempreferences:com.apple.preference.security?\(pane)")
+    }
+}
+
+/// App that was in front before the last focus, from ~/.local/state/claude-bar/previous_app.json
+struct PreviousApp: Decodable {
+    let name: String
+}
+
+/// Result of the last `claude-bar update --check`, from ~/.local/state/claude-bar/update.json
+struct UpdateStatus: Decodable {
+    let checked_at: Double
+    let latest: String
+    let available: Bool
+    let url: String
 }
 
 enum SessionStatus: String {
     case active
     case pending
     case idle
+    case rateLimited = "rate_limited"
+    case unknown
 
-    var sfSymbol: String { "cpu.fill" }
+    /// Rate-limited sessions are stuck on the API, not on the machine
+    var sfSymbol: String {
+        switch self {
+        case .rateLimited: return "icloud.slash.fill"
+        case .unknown: return "questionmark.circle.fill"
+        default: return "cpu.fill"
+        }
+    }
 
+    /// Resolved against the drawing appearance, so a light menu bar gets the
+    /// darker palette (mirrors `colors` / `light_colors` in the Rust config)
     var color: NSColor {
+        let (dark, light): (UInt32, UInt32)
         switch self {
-        case .active:  return NSColor(srgbRed: 0x32/255, green: 0xD7/255, blue: 0x4B/255, alpha: 1)
-        case .pending: return NSColor(srgbRed: 0xFF/255, green: 0x9F/255, blue: 0x0A/255, alpha: 1)
-        case .idle:    return NSColor(srgbRed: 0x8E/255, green: 0x8E/255, blue: 0x93/255, alpha: 1)
+        case .active:  (dark, light) = (0x32D74B, 0x248A3D)
+        case .pending: (dark, light) = (0xFF9F0A, 0xC93400)
+        case .idle:    (dark, light) = (0x8E8E93, 0x6C6C70)
+        case .rateLimited: (dark, light) = (0xBF5AF2, 0x8944AB)
+        case .unknown: (dark, light) = (0x64D2FF, 0x0071A4)
+        }
+        return NSColor(name: nil) { appearance in
+            let isDark = appearance.bestMatch(from: [.darkAqua, .aqua]) == .darkAqua
+            let rgb = isDark ? dark : light
+            return NSColor(srgbRed: CGFloat((rgb >> 16) & 0xFF) / 255,
+                           green: CGFloat((rgb >> 8) & 0xFF) / 255,
+                           blue: CGFloat(rgb & 0xFF) / 255,
+                           alpha: 1)
         }
     }
 
@@ -32,6 +205,8 @@ enum SessionStatus: String {
         case .active:  return "Running"
         case .pending: return "Needs input"
         case .idle:    return "Idle"
+        case .rateLimited: return "Rate limited"
+        case .unknown: return "No transcript"
         }
     }
 }
@@ -42,7 +217,7 @@ class AppDelegate: NSObject, NSApplicationDelegate {
     var statusItem: NSStatusItem!
     var timer: Timer!
     var animationTimer: Timer?
-    var currentSessions: [SessionInfo] = []
+    var currentView = MenuView.empty
     let binaryPath: String
 
     init(binaryPath: String) {
@@ -50,6 +225,16 @@ class AppDelegate: NSObject, NSApplicationDelegate {
         super.init()
     }
 
+    func applicationWillFinishLaunching(_ notification: Notification) {
+        // claude-bar:// URLs (registered via the app bundle's Info.plist)
+        NSAppleEventManager.shared().setEventHandler(
+            self,
+            andSelector: #selector(handleGetURL(_:withReplyEvent:)),
+            forEventClass: AEEventClass(kInternetEventClass),
+            andEventID: AEEventID(kAEGetURL)
+        )
+    }
+
     func applicationDidFinishLaunching(_ notification: Notification) {
         statusItem = NSStatusBar.system.statusItem(withLength: NSStatusItem.variableLength)
         statusItem.isVisible = false
@@ -60,13 +245,32 @@ class AppDelegate: NSObject, NSApplicationDelegate {
             self?.pollAndUpdate()
         }
         RunLoop.main.add(timer, forMode: .common)
+
+        checkForUpdates()
+        let updateTimer = Timer(timeInterval: 3600, repeats: true) { [weak self] _ in
+            self?.checkForUpdates()
+        }
+        RunLoop.main.add(updateTimer, forMode: .common)
+    }
+
+    /// Runs `claude-bar update --check` in the background at most once a day;
+    /// the dropdown footer reads the result from update.json
+    func checkForUpdates() {
+        if let status = readUpdateStatus(),
+           Date().timeIntervalSince1970 - status.checked_at < 86400 { return }
+        let proc = Process()
+        proc.executableURL = URL(fileURLWithPath: binaryPath)
+        proc.arguments = ["update", "--check"]
+        proc.standardOutput = FileHandle.nullDevice
+        proc.standardError = FileHandle.nullDevice
+        try? proc.run()
     }
 
     func pollAndUpdate() {
-        let sessions = pollSessions()
-        currentSessions = sessions
+        let view = pollView()
+        currentView = view
 
-        if sessions.isEmpty {
+        if view.allSessions.isEmpty {
             statusItem.isVisible = false
             statusItem.menu = nil
             stopAnimation()
@@ -74,19 +278,19 @@ class AppDelegate: NSObject, NSApplicationDelegate {
         }
 
         statusItem.isVisible = true
-        statusItem.menu = buildMenu(sessions: sessions)
+        statusItem.menu = buildMenu(view: view)
+        // Hover summary; VoiceOver reads the same text instead of "image"
+        statusItem.button?.toolTip = view.tooltip
+        statusItem.button?.setAccessibilityLabel("Claude Bar: \(view.tooltip)")
 
-        let needsAnimation = sessions.contains {
-            let s = SessionStatus(rawValue: $0.status) ?? .idle
-            return s == .pending || s == .idle
-        }
+        let needsAnimation = iconStatuses().contains { $0 == .pending || $0 == .idle }
         if needsAnimation { startAnimation() } else { stopAnimation() }
         updateIcon()
     }
 
     func updateIcon() {
         guard let button = statusItem.button else { return }
-        let icon = composeIcon(sessions: currentSessions)
+        let icon = composeIcon(statuses: iconStatuses())
         icon.isTemplate = false
         button.image = icon
     }
@@ -118,10 +322,15 @@ class AppDelegate: NSObject, NSApplicationDelegate {
 
     // MARK: - Poll
 
-    func pollSessions() -> [SessionInfo] {
+    /// Statuses drawn in the icon; hidden idle sessions leave it empty
+    func iconStatuses() -> [SessionStatus] {
+        currentView.icon.map { SessionStatus(rawValue: $0) ?? .idle }
+    }
+
+    func pollView() -> MenuView {
         let proc = Process()
         proc.executableURL = URL(fileURLWithPath: binaryPath)
-        proc.arguments = ["poll"]
+        proc.arguments = ["poll", "--format", "menu"]
 
         let pipe = Pipe()
         proc.standardOutput = pipe
@@ -131,30 +340,39 @@ class AppDelegate: NSObject, NSApplicationDelegate {
             try proc.run()
             proc.waitUntilExit()
         } catch {
-            return []
+            return .empty
         }
 
-        guard proc.terminationStatus == 0 else { return [] }
+        guard proc.terminationStatus == 0 else { return .empty }
 
         let data = pipe.fileHandleForReading.readDataToEndOfFile()
-        guard !data.isEmpty else { return [] }
+        guard !data.isEmpty else { return .empty }
 
         do {
-            return try JSONDecoder().decode([SessionInfo].self, from: data)
+            return try JSONDecoder().decode(MenuView.self, from: data)
         } catch {
-            return []
+            return .empty
         }
     }
 
     // MARK: - Icon Composition
 
-    func composeIcon(sessions: [SessionInfo]) -> NSImage {
+    func composeIcon(statuses: [SessionStatus]) -> NSImage {
         let symbolSize: CGFloat = 18
-        let count = sessions.count
+        let count = statuses.count
+
+        // Every session hidden (idle ones left out): a faint placeholder keeps the menu reachable
+        if count == 0 {
+            let sym = makeSymbol(for: .idle, pointSize: symbolSize)
+            return NSImage(size: sym.size, flipped: false) { rect in
+                sym.draw(in: rect, from: .zero, operation: .sourceOver, fraction: 0.3)
+                return true
+            }
+        }
 
         if count == 1 {
-            let status = SessionStatus(rawValue: sessions[0].status) ?? .idle
-            let sym = makeSymbol(for: sessions[0], pointSize: symbolSize)
+            let status = statuses[0]
+            let sym = makeSymbol(for: status, pointSize: symbolSize)
             let alpha = alphaForStatus(status)
             if alpha >= 1.0 { return sym }
             let size = sym.size
@@ -170,10 +388,9 @@ class AppDelegate: NSObject, NSApplicationDelegate {
         let height = smallSize + 2
 
         let composed = NSImage(size: NSSize(width: totalWidth, height: height), flipped: false) { rect in
-            for (i, session) in sessions.enumerated() {
-                let status = SessionStatus(rawValue: session.status) ?? .idle
+            for (i, status) in statuses.enumerated() {
                 let alpha = self.alphaForStatus(status)
-                let sym = self.makeSymbol(for: session, pointSize: smallSize)
+                let sym = self.makeSymbol(for: status, pointSize: smallSize)
                 let x = CGFloat(i) * step
                 sym.draw(in: NSRect(x: x, y: 0, width: smallSize, height: height),
                          from: .zero, operation: .sourceOver, fraction: alpha)
@@ -183,8 +400,7 @@ class AppDelegate: NSObject, NSApplicationDelegate {
         return composed
     }
 
-    func makeSymbol(for session: SessionInfo, pointSize: CGFloat) -> NSImage {
-        let status = SessionStatus(rawValue: session.status) ?? .idle
+    func makeSymbol(for status: SessionStatus, pointSize: CGFloat) -> NSImage {
         let config = NSImage.SymbolConfiguration(pointSize: pointSize, weight: .medium)
             .applying(NSImage.SymbolConfiguration(paletteColors: [status.color]))
 
@@ -197,26 +413,85 @@ class AppDelegate: NSObject, NSApplicationDelegate {
 
     // MARK: - Menu
 
-    func buildMenu(sessions: [SessionInfo]) -> NSMenu {
+    /// Compact age of a unix timestamp: "just now", "12m ago", "2h ago", "3d ago"
+    func relativeAge(since unix: Double) -> String {
+        let secs = max(0, Int(Date().timeIntervalSince1970 - unix))
+        switch secs {
+        case ..<60:    return "just now"
+        case ..<3600:  return "\(secs / 60)m ago"
+        case ..<86400: return "\(secs / 3600)h ago"
+        default:       return "\(secs / 86400)d ago"
+        }
+    }
+
+    func buildMenu(view: MenuView) -> NSMenu {
         let menu = NSMenu()
+        // Index where each titled section starts (per host or terminal, see `menu group`)
+        var headers: [Int: String] = [:]
+        var start = 0
+        for section in view.sections {
+            if let title = section.title { headers[start] = title }
+            start += section.count
+        }
+
+        if let key = view.next_pending,
+           let next = view.sessions.first(where: { ($0.remote.map { "\($0.host):" } ?? "") + $0.tty == key }) {
+            let project = URL(fileURLWithPath: next.cwd).lastPathComponent
+            let item = NSMenuItem(title: "Focus Next Pending: \(project)", action: #selector(focusNextPending(_:)), keyEquivalent: "n")
+            item.target = self
+            item.toolTip = "The session that has been waiting for input longest"
+            menu.addItem(item)
+            menu.addItem(.separator())
+        }
+
+        for (index, session) in view.sessions.enumerated() {
+            if let title = headers[index] {
+                if index > 0 { menu.addItem(.separator()) }
+                let header = NSMenuItem(title: title, action: nil, keyEquivalent: "")
+                header.isEnabled = false
+                menu.addItem(header)
+            }
 
-        for (index, session) in sessions.enumerated() {
             let status = SessionStatus(rawValue: session.status) ?? .idle
             let project = URL(fileURLWithPath: session.cwd).lastPathComponent
 
             // Project name row with status icon
-            let item = NSMenuItem(title: project, action: #selector(focusSession(_:)), keyEquivalent: "")
+            let focusable = session.focusTTY != nil || session.container != nil
+            var title = project
+            if let label = session.env?["CLAUDE_BAR_LABEL"] { title += " · \(label)" }
+            if let profile = session.profile { title += " [\(profile)]" }
+            let item = NSMenuItem(title: title, action: focusable ? #selector(focusSession(_:)) : nil, keyEquivalent: "")
             item.target = self
             item.tag = index
             item.image = makeSmallSymbol(for: session)
             menu.addItem(item)
 
             // Status sub-row (indented, disabled)
-            let statusItem = NSMenuItem(title: "  \(status.label)", action: nil, keyEquivalent: "")
+            var statusText = status.label
+            if status == .pending, let reason = session.pending_reason {
+                statusText += " · \(reason.label)"
+            }
+            if let subagents = session.subagents, subagents > 0 {
+                statusText += subagents == 1 ? " · 1 subagent running" : " · \(subagents) subagents running"
+            }
+            if let queued = session.queued, queued > 0 {
+                statusText += " · \(queued) queued"
+            }
+            if session.detached == true {
+                statusText += " · no terminal"
+            }
+            if let title = session.title {
+                statusText += " · \(title)"
+            }
+            item.toolTip = "\(statusText)\n\(session.cwd)" + (session.rule.map { "\nRule: \($0)" } ?? "")
+                + (session.cpu_percent.map { String(format: "\nCPU: %.0f%%", $0) } ?? "")
+                + (session.env?["ANTHROPIC_MODEL"].map { "\nModel: \($0)" } ?? "")
+            item.setAccessibilityLabel("\(project), \(statusText)")
+            let statusItem = NSMenuItem(title: "  \(statusText)", action: nil, keyEquivalent: "")
             statusItem.isEnabled = false
             if let font = NSFont.systemFont(ofSize: 11, weight: .regular) as NSFont? {
                 statusItem.attributedTitle = NSAttributedString(
-                    string: "  \(status.label)",
+                    string: "  \(statusText)",
                     attributes: [
                         .font: font,
                         .foregroundColor: NSColor.secondaryLabelColor
@@ -224,9 +499,132 @@ class AppDelegate: NSObject, NSApplicationDelegate {
                 )
             }
             menu.addItem(statusItem)
+
+            // Quick replies typed into the session's terminal: Approve/Deny answer a
+            // permission prompt (`claude-bar respond`), Continue is `send --action continue`
+            if status == .pending, let tty = session.focusTTY {
+                var actions: [(String, [String])] = []
+                if let kind = session.pending_reason?.kind, kind != "question" {
+                    actions.append(("Approve", ["respond", "--tty", tty, "approve"]))
+                    actions.append(("Deny", ["respond", "--tty", tty, "deny"]))
+                }
+                actions.append(("Continue", ["send", "--tty", tty, "--action", "continue"]))
+                for (title, args) in actions {
+                    let actionItem = NSMenuItem(title: title, action: #selector(sendQuickAction(_:)), keyEquivalent: "")
+                    actionItem.target = self
+                    actionItem.indentationLevel = 1
+                    actionItem.representedObject = args
+                    actionItem.setAccessibilityLabel("\(title) \(project)")
+                    menu.addItem(actionItem)
+                }
+            }
+
+            // History sub-row: "23 turns · started 2h ago"
+            var history: [String] = []
+            if let turns = session.turns, turns > 0 {
+                history.append(turns == 1 ? "1 turn" : "\(turns) turns")
+            }
+            if let startedAt = session.started_at {
+                history.append("started \(relativeAge(since: startedAt))")
+            }
+            if !history.isEmpty {
+                let historyItem = NSMenuItem(title: "", action: nil, keyEquivalent: "")
+                historyItem.isEnabled = false
+                historyItem.attributedTitle = NSAttributedString(
+                    string: "  \(history.joined(separator: " · "))",
+                    attributes: [
+                        .font: NSFont.systemFont(ofSize: 11, weight: .regular),
+                        .foregroundColor: NSColor.tertiaryLabelColor
+                    ]
+                )
+                menu.addItem(historyItem)
+            }
+
+            // Other agents running inside this session's terminal
+            for agent in session.nested ?? [] {
+                let nestedItem = NSMenuItem(title: "", action: nil, keyEquivalent: "")
+                nestedItem.isEnabled = false
+                nestedItem.attributedTitle = NSAttributedString(
+                    string: "  ↳ \(agent.provider) (pid \(agent.pid))",
+                    attributes: [
+                        .font: NSFont.systemFont(ofSize: 11, weight: .regular),
+                        .foregroundColor: NSColor.secondaryLabelColor
+                    ]
+                )
+                menu.addItem(nestedItem)
+            }
+        }
+
+        // Idle sessions folded into one row, each focusable from its submenu
+        if !view.collapsed_idle.isEmpty {
+            let count = view.collapsed_idle.count
+            let row = NSMenuItem(title: count == 1 ? "1 idle session" : "\(count) idle sessions",
+                                 action: nil, keyEquivalent: "")
+            row.image = makeSymbol(for: .idle, pointSize: 12)
+            let submenu = NSMenu()
+            for (offset, session) in view.collapsed_idle.enumerated() {
+                let project = URL(fileURLWithPath: session.cwd).lastPathComponent
+                let item = NSMenuItem(title: project, action: #selector(focusSession(_:)), keyEquivalent: "")
+                item.target = self
+                item.tag = view.sessions.count + offset
+                submenu.addItem(item)
+            }
+            row.submenu = submenu
+            menu.addItem(row)
+        }
+
+        menu.addItem(.separator())
+        menu.addItem(buildOptionsItem(view: view))
+
+        let issues = readPermissionIssues()
+        for (index, issue) in issues.enumerated() {
+            let item = NSMenuItem(title: issue.title, action: #selector(openPermissionSettings(_:)), keyEquivalent: "")
+            item.target = self
+            item.tag = index
+            menu.addItem(item)
+        }
+        if !issues.isEmpty {
+            menu.addItem(.separator())
+        }
+
+        if let update = readUpdateStatus(), update.available {
+            let item = NSMenuItem(title: "Update Available: v\(update.latest)…", action: #selector(openUpdatePage(_:)), keyEquivalent: "")
+            item.target = self
+            item.toolTip = "Run `claude-bar update` to install"
+            menu.addItem(item)
+        }
+
+        if let previous = readPreviousApp() {
+            let item = NSMenuItem(title: "Return to Previous App", action: #selector(focusBack(_:)), keyEquivalent: "b")
+            item.target = self
+            item.toolTip = "Bring \(previous.name) back to the front (`claude-bar focus --back`)"
+            menu.addItem(item)
         }
 
+        // Footer: totals, then the daemon's state (none when it isn't started)
         menu.addItem(.separator())
+        var footerRows = [view.footer.totals]
+        if let daemon = view.footer.daemon { footerRows.append(daemon) }
+        for title in footerRows {
+            let item = NSMenuItem(title: title, action: nil, keyEquivalent: "")
+            item.isEnabled = false
+            menu.addItem(item)
+        }
+        if let warning = view.footer.warning {
+            let item = NSMenuItem(title: "⚠ \(warning)", action: nil, keyEquivalent: "")
+            item.isEnabled = false
+            item.toolTip = "Check `claude-bar daemon status` and `claude-bar logs`"
+            menu.addItem(item)
+        }
+
+        // `daemon reload` needs a running `serve`; the app's own polls read the
+        // config file every time anyway
+        if view.footer.daemon != nil {
+            let reloadItem = NSMenuItem(title: "Reload Config", action: #selector(reloadConfig(_:)), keyEquivalent: "r")
+            reloadItem.target = self
+            reloadItem.toolTip = "Make the running daemon re-read the config file (`claude-bar daemon reload`)"
+            menu.addItem(reloadItem)
+        }
 
         let quitItem = NSMenuItem(title: "Quit Claude Bar", action: #selector(quitApp(_:)), keyEquivalent: "q")
         quitItem.target = self
@@ -235,29 +633,211 @@ class AppDelegate: NSObject, NSApplicationDelegate {
         return menu
     }
 
+    /// "View Options" submenu: the on/off toggles, then a pin checkbox per project
+    func buildOptionsItem(view: MenuView) -> NSMenuItem {
+        let submenu = NSMenu()
+        let toggles: [(String, String, Bool)] = [
+            ("Hide Idle Sessions from Icon", "hide-idle-in-icon", view.options.hide_idle_in_icon),
+            ("Collapse Idle Sessions", "collapse-idle", view.options.collapse_idle),
+            ("Only Show Pinned Projects", "pinned-only", view.options.pinned_only),
+            ("Confirm Approve/Deny", "confirm-responses", view.options.confirm_responses),
+            ("Privacy Mode (Hide Project Names)", "privacy-mode", view.options.privacy_mode),
+            ("Show Profile Badges", "show-profiles", view.options.show_profiles ?? true),
+        ]
+        for (title, option, on) in toggles {
+            let item = NSMenuItem(title: title, action: #selector(toggleMenuOption(_:)), keyEquivalent: "")
+            item.target = self
+            item.representedObject = [option, on ? "off" : "on"]
+            item.state = on ? .on : .off
+            submenu.addItem(item)
+        }
+
+        // Sort By / Group By: one checked choice each
+        let choices: [(String, String, String, [(String, String)])] = [
+            ("Sort By", "sort", view.options.sort,
+             [("Status", "status"), ("Project", "project"), ("Last Activity", "activity"), ("Terminal", "terminal")]),
+            ("Group By", "group", view.options.group,
+             [("None", "none"), ("Host", "host"), ("Terminal", "terminal")]),
+        ]
+        submenu.addItem(.separator())
+        for (title, command, current, values) in choices {
+            let choiceMenu = NSMenu()
+            for (label, value) in values {
+                let item = NSMenuItem(title: label, action: #selector(toggleMenuOption(_:)), keyEquivalent: "")
+                item.target = self
+                item.representedObject = [command, value]
+                item.state = value == current ? .on : .off
+                choiceMenu.addItem(item)
+            }
+            let item = NSMenuItem(title: title, action: nil, keyEquivalent: "")
+            item.submenu = choiceMenu
+            submenu.addItem(item)
+        }
+
+        let pinned = view.options.pinned_projects ?? []
+        let live = view.allSessions.map { URL(fileURLWithPath: $0.cwd).lastPathComponent }
+        var projects: [String] = []
+        for project in pinned + live where !projects.contains(project) {
+            projects.append(project)
+        }
+        if !projects.isEmpty {
+            submenu.addItem(.separator())
+            let header = NSMenuItem(title: "Pinned Projects", action: nil, keyEquivalent: "")
+            header.isEnabled = false
+            submenu.addItem(header)
+            for project in projects.sorted() {
+                let isPinned = pinned.contains(project)
+                let item = NSMenuItem(title: project, action: #selector(toggleMenuOption(_:)), keyEquivalent: "")
+                item.target = self
+                item.representedObject = [isPinned ? "unpin" : "pin", project]
+                item.state = isPinned ? .on : .off
+                submenu.addItem(item)
+            }
+        }
+
+        let item = NSMenuItem(title: "View Options", action: nil, keyEquivalent: "")
+        item.submenu = submenu
+        return item
+    }
+
+    func readPermissionIssues() -> [PermissionIssue] {
+        let path = stateDir.appendingPathComponent("permissions.json")
+        guard let data = try? Data(contentsOf: path) else { return [] }
+        return (try? JSONDecoder().decode([PermissionIssue].self, from: data)) ?? []
+    }
+
+    func readUpdateStatus() -> UpdateStatus? {
+        let path = stateDir.appendingPathComponent("update.json")
+        guard let data = try? Data(contentsOf: path) else { return nil }
+        return try? JSONDecoder().decode(UpdateStatus.self, from: data)
+    }
+
+    func readPreviousApp() -> PreviousApp? {
+        let path = stateDir.appendingPathComponent("previous_app.json")
+        guard let data = try? Data(contentsOf: path) else { return nil }
+        return try? JSONDecoder().decode(PreviousApp.self, from: data)
+    }
+
     func makeSmallSymbol(for session: SessionInfo) -> NSImage? {
         let status = SessionStatus(rawValue: session.status) ?? .idle
         let config = NSImage.SymbolConfiguration(pointSize: 12, weight: .medium)
             .applying(NSImage.SymbolConfiguration(paletteColors: [status.color]))
-        return NSImage(systemSymbolName: status.sfSymbol, accessibilityDescription: status.label)?
+        // Pending rows show what kind of input is needed
+        let reason = status == .pending ? session.pending_reason : nil
+        let symbol = reason?.sfSymbol ?? status.sfSymbol
+        return NSImage(systemSymbolName: symbol, accessibilityDescription: reason?.label ?? status.label)?
             .withSymbolConfiguration(config)
     }
 
     // MARK: - Actions
 
     @objc func focusSession(_ sender: NSMenuItem) {
-        let sessions = pollSessions()
+        let sessions = pollView().allSessions
         guard sender.tag < sessions.count else { return }
         let session = sessions[sender.tag]
 
         let proc = Process()
         proc.executableURL = URL(fileURLWithPath: binaryPath)
-        proc.arguments = ["focus", "--terminal", session.terminal, "--tty", session.tty, "--cwd", session.cwd]
+        if session.container != nil {
+            // Container sessions are focused through their devcontainer's VS Code window
+            proc.arguments = ["open-url", "claude-bar://focus?tty=\(session.tty)"]
+        } else if let tty = session.focusTTY {
+            var args = ["focus", "--terminal", session.terminal, "--tty", tty]
+            if let id = session.terminal_id { args += ["--terminal-id", id] }
+            proc.arguments = args + ["--cwd", session.cwd]
+        } else {
+            return
+        }
         proc.standardOutput = FileHandle.nullDevice
         proc.standardError = FileHandle.nullDevice
         try? proc.run()
     }
 
+    /// Runs `claude-bar respond ...` or `claude-bar send ...`; approving or denying
+    /// asks first unless `confirm_responses` is off
+    @objc func sendQuickAction(_ sender: NSMenuItem) {
+        guard let args = sender.representedObject as? [String], let command = args.first else { return }
+        if command == "respond" && pollView().options.confirm_responses {
+            let alert = NSAlert()
+            alert.messageText = "\(sender.title) the pending request?"
+            alert.informativeText = "Claude Bar will answer the prompt in the session's terminal."
+            alert.addButton(withTitle: sender.title)
+            alert.addButton(withTitle: "Cancel")
+            NSApp.activate(ignoringOtherApps: true)
+            guard alert.runModal() == .alertFirstButtonReturn else { return }
+        }
+        let proc = Process()
+        proc.executableURL = URL(fileURLWithPath: binaryPath)
+        proc.arguments = args
+        proc.standardOutput = FileHandle.nullDevice
+        proc.standardError = FileHandle.nullDevice
+        try? proc.run()
+    }
+
+    @objc func openPermissionSettings(_ sender: NSMenuItem) {
+        let issues = readPermissionIssues()
+        guard sender.tag < issues.count, let url = issues[sender.tag].settingsURL else { return }
+        NSWorkspace.shared.open(url)
+    }
+
+    @objc func handleGetURL(_ event: NSAppleEventDescriptor, withReplyEvent reply: NSAppleEventDescriptor) {
+        guard let url = event.paramDescriptor(forKeyword: keyDirectObject)?.stringValue else { return }
+
+        let proc = Process()
+        proc.executableURL = URL(fileURLWithPath: binaryPath)
+        proc.arguments = ["open-url", url]
+        proc.standardOutput = FileHandle.nullDevice
+        proc.standardError = FileHandle.nullDevice
+        try? proc.run()
+    }
+
+    @objc func openUpdatePage(_ sender: NSMenuItem) {
+        guard let url = readUpdateStatus().flatMap({ URL(string: $0.url) }) else { return }
+        NSWorkspace.shared.open(url)
+    }
+
+    @objc func focusNextPending(_ sender: NSMenuItem) {
+        let proc = Process()
+        proc.executableURL = URL(fileURLWithPath: binaryPath)
+        proc.arguments = ["focus", "--next-pending"]
+        proc.standardOutput = FileHandle.nullDevice
+        proc.standardError = FileHandle.nullDevice
+        try? proc.run()
+    }
+
+    @objc func focusBack(_ sender: NSMenuItem) {
+        let proc = Process()
+        proc.executableURL = URL(fileURLWithPath: binaryPath)
+        proc.arguments = ["focus", "--back"]
+        proc.standardOutput = FileHandle.nullDevice
+        proc.standardError = FileHandle.nullDevice
+        try? proc.run()
+    }
+
+    @objc func reloadConfig(_ sender: NSMenuItem) {
+        let proc = Process()
+        proc.executableURL = URL(fileURLWithPath: binaryPath)
+        proc.arguments = ["daemon", "reload"]
+        proc.standardOutput = FileHandle.nullDevice
+        proc.standardError = FileHandle.nullDevice
+        try? proc.run()
+    }
+
+    /// Runs `claude-bar menu set <option> on|off`, `menu pin|unpin <project>` or
+    /// `menu sort|group <value>`, then redraws
+    @objc func toggleMenuOption(_ sender: NSMenuItem) {
+        guard let args = sender.representedObject as? [String], args.count == 2 else { return }
+        let subcommands: Set<String> = ["pin", "unpin", "sort", "group"]
+        let proc = Process()
+        proc.executableURL = URL(fileURLWithPath: binaryPath)
+        proc.arguments = ["menu"] + (subcommands.contains(args[0]) ? args : ["set"] + args)
+        proc.standardOutput = FileHandle.nullDevice
+        proc.standardError = FileHandle.nullDevice
+        try? proc.run()
+        proc.waitUntilExit()
+        pollAndUpdate()
+    }
+
     @objc func quitApp(_ sender: NSMenuItem) {
         NSApplication.shared.terminate(nil)
     }
@@ -265,6 +845,15 @@ class AppDelegate: NSObject, NSApplicationDelegate {
 
 // MARK: - Main
 
+/// claude-bar's state directory: `$XDG_STATE_HOME/claude-bar`, else `~/.local/state/claude-bar`
+let stateDir: URL = {
+    if let xdg = ProcessInfo.processInfo.environment["XDG_STATE_HOME"], xdg.hasPrefix("/") {
+        return URL(fileURLWithPath: xdg).appendingPathComponent("claude-bar")
+    }
+    return FileManager.default.homeDirectoryForCurrentUser
+        .appendingPathComponent(".local/state/claude-bar")
+}()
+
 let binaryPath: String = {
     let execURL = URL(fileURLWithPath: CommandLine.arguments[0])
     let dir = execURL.deletingLastPathComponent()
@@ -14,20 +14,33 @@ launchctl bootout "gui/$(id -u)/$PLIST_LABEL" 2>/dev/null || true
 rm -f "$PLIST"
 echo "Stopped and removed daemon: $PLIST_LABEL"
 
-# 2. Remove hook config from settings.json
+# 2. Unregister the claude-bar:// URL handler bundle
+APP_BUNDLE="$SCRIPT_DIR/target/release/ClaudeBar.app"
+LSREGISTER="/System/Library/Frameworks/CoreServices.framework/Frameworks/LaunchServices.framework/Support/lsregister"
+if [[ -d "$APP_BUNDLE" ]]; then
+    "$LSREGISTER" -u "$APP_BUNDLE" 2>/dev/null || true
+    rm -rf "$APP_BUNDLE"
+    echo "Removed URL handler bundle"
+fi
+
+# 3. Remove hook config from settings.json
 SETTINGS="$HOME/.claude/settings.json"
 if [[ -f "$SETTINGS" ]]; then
     if [[ -x "$BINARY" ]]; then
-        "$BINARY" hooks-uninstall --settings "$SETTINGS"
+        "$BINARY" hooks uninstall --settings "$SETTINGS"
         echo "Removed hook config from settings"
     else
         echo "Warning: $BINARY not found, skipping hook cleanup in settings"
     fi
 fi
 
-# 3. Clean up state files
+# 4. Clean up state files
+if [[ -x "$BINARY" ]]; then
+    rm -f "$("$BINARY" daemon socket-path)"
+fi
 rm -f "$HOME/.claude/claude-bar.sock"
 rm -rf "$HOME/.claude/claude-bar"
+rm -rf "$HOME/.local/state/claude-bar"
 echo "Cleaned up state files"
 echo "Note: You may remove any leftover .claude-bar/ directories from project folders"
 